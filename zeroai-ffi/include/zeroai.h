@@ -0,0 +1,60 @@
+/*
+ * zeroai C API: chat with any configured provider without running the HTTP proxy.
+ *
+ * Link against libzeroai_ffi (cdylib or staticlib, built by `cargo build -p zeroai-ffi`).
+ *
+ * Requests, replies and stream events are UTF-8 JSON. A chat request looks like
+ *
+ *   {"model": "openai/gpt-4o", "messages": [{"role": "user", "content": "Hello!"}],
+ *    "system": "...", "tools": [...], "temperature": 0.2, "max_tokens": 512, "reasoning": "low"}
+ *
+ * where everything but "model" and "messages" is optional. Strings returned by the library
+ * are freed with zeroai_string_free. A failing call returns NULL (or -1) and leaves a message
+ * for zeroai_last_error on the calling thread. Calls block until the provider answers and
+ * must not be made from inside a tokio runtime.
+ */
+#ifndef ZEROAI_H
+#define ZEROAI_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ZeroAiClient ZeroAiClient;
+
+/* Receives each stream event as JSON ({"type": "text_delta", "text": "..."}, ...,
+ * {"type": "done", "message": {...}}). The string is only valid during the call.
+ * Return nonzero to stop the stream. */
+typedef int (*zeroai_event_callback)(const char *event_json, void *user_data);
+
+/* Open a client over the config at config_path (NULL: ~/.zeroai/config.json).
+ * Returns NULL on error. */
+ZeroAiClient *zeroai_client_new(const char *config_path);
+void zeroai_client_free(ZeroAiClient *client);
+
+/* JSON array of the full model IDs ("provider/model") the client can call. */
+char *zeroai_list_models(const ZeroAiClient *client);
+
+/* Run a completion; returns the assistant message as JSON, or NULL on error. */
+char *zeroai_chat(const ZeroAiClient *client, const char *request_json);
+
+/* Stream a completion through callback. Returns 0 when the stream ran to its end, 1 when
+ * the callback stopped it, and -1 on error. */
+int zeroai_stream(const ZeroAiClient *client, const char *request_json,
+                  zeroai_event_callback callback, void *user_data);
+
+/* Config file access; config_path NULL means the default config. */
+char *zeroai_config_enabled_models(const char *config_path);
+int zeroai_config_set_enabled_models(const char *config_path, const char *models_json);
+int zeroai_config_set_api_key(const char *config_path, const char *provider, const char *key);
+
+/* The error of the last failed call on this thread, or NULL. Valid until the next call. */
+const char *zeroai_last_error(void);
+
+void zeroai_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ZEROAI_H */
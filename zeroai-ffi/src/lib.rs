@@ -0,0 +1,314 @@
+//! C ABI for embedding zeroai without running the HTTP proxy; the API is in
+//! `include/zeroai.h`.
+//!
+//! Requests, replies and stream events are UTF-8 JSON strings in the shapes of
+//! [`zeroai::bindings`]. Strings handed to the caller are freed with `zeroai_string_free`.
+//! A failing call returns NULL (or -1) and leaves a message for `zeroai_last_error` on the
+//! calling thread. Calls block the calling thread until the provider answers.
+
+use futures::StreamExt;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::LazyLock;
+use tokio::runtime::Runtime;
+use zeroai::AiClient;
+use zeroai::auth::config::ConfigManager;
+use zeroai::auth::{ApiKeyCredential, Credential};
+use zeroai::bindings::{ChatRequest, event_json};
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| Runtime::new().expect("Failed to start the tokio runtime"));
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Receives each stream event as JSON; returning nonzero stops the stream.
+pub type EventCallback = extern "C" fn(event_json: *const c_char, user_data: *mut c_void) -> c_int;
+
+/// A client for the enabled models of a config file, authenticated with its credentials.
+pub struct ZeroAiClient {
+    client: AiClient,
+    config: ConfigManager,
+}
+
+/// Open a client over the config at `config_path` (NULL: the default `~/.zeroai/config.json`).
+/// Returns NULL on error.
+///
+/// # Safety
+/// `config_path` is NULL or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_client_new(config_path: *const c_char) -> *mut ZeroAiClient {
+    guard(std::ptr::null_mut(), || {
+        let config = unsafe { config_at(config_path) }?;
+        let client = AiClient::from_config(&config)?;
+        Ok(Box::into_raw(Box::new(ZeroAiClient { client, config })))
+    })
+}
+
+/// # Safety
+/// `client` is NULL or came from `zeroai_client_new` and is not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_client_free(client: *mut ZeroAiClient) {
+    if !client.is_null() {
+        drop(unsafe { Box::from_raw(client) });
+    }
+}
+
+/// JSON array of the full IDs (`provider/model`) the client can call.
+///
+/// # Safety
+/// `client` came from `zeroai_client_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_list_models(client: *const ZeroAiClient) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let client = unsafe { client_ref(client) }?;
+        let mut ids: Vec<&String> = client.client.models().keys().collect();
+        ids.sort();
+        into_c_string(serde_json::to_string(&ids)?)
+    })
+}
+
+/// Run a completion; returns the assistant message as JSON, or NULL on error.
+///
+/// # Safety
+/// `client` came from `zeroai_client_new`; `request_json` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_chat(client: *const ZeroAiClient, request_json: *const c_char) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let client = unsafe { client_ref(client) }?;
+        let request: ChatRequest = serde_json::from_str(unsafe { str_arg(request_json) }?)?;
+        let context = request.context()?;
+        let message = RUNTIME.block_on(async {
+            let options = request.options(&client.config).await?;
+            anyhow::Ok(client.client.chat(&request.model, &context, &options).await?)
+        })?;
+        into_c_string(serde_json::to_string(&message)?)
+    })
+}
+
+/// Stream a completion, calling `callback` with each event until the `done` event, an error,
+/// or a nonzero return from `callback`. Returns 0 when the stream ran to its end, 1 when the
+/// callback stopped it, and -1 on error.
+///
+/// # Safety
+/// `client` came from `zeroai_client_new`; `request_json` is a NUL-terminated string;
+/// `callback` is safe to call with `user_data` on this thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_stream(
+    client: *const ZeroAiClient,
+    request_json: *const c_char,
+    callback: EventCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(-1, || {
+        let client = unsafe { client_ref(client) }?;
+        let request: ChatRequest = serde_json::from_str(unsafe { str_arg(request_json) }?)?;
+        let context = request.context()?;
+        RUNTIME.block_on(async {
+            let options = request.options(&client.config).await?;
+            let mut stream = client.client.stream(&request.model, &context, &options)?;
+            while let Some(event) = stream.next().await {
+                let event = CString::new(event_json(&event?).to_string())?;
+                if callback(event.as_ptr(), user_data) != 0 {
+                    return Ok(1);
+                }
+            }
+            Ok(0)
+        })
+    })
+}
+
+/// JSON array of the enabled model IDs in the config at `config_path` (NULL: the default).
+///
+/// # Safety
+/// `config_path` is NULL or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_config_enabled_models(config_path: *const c_char) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let models = unsafe { config_at(config_path) }?.get_enabled_models()?;
+        into_c_string(serde_json::to_string(&models)?)
+    })
+}
+
+/// Replace the enabled models with `models_json`, a JSON array of full model IDs.
+/// Returns 0, or -1 on error.
+///
+/// # Safety
+/// `config_path` is NULL or a NUL-terminated string; `models_json` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_config_set_enabled_models(config_path: *const c_char, models_json: *const c_char) -> c_int {
+    guard(-1, || {
+        let models: Vec<String> = serde_json::from_str(unsafe { str_arg(models_json) }?)?;
+        unsafe { config_at(config_path) }?.set_enabled_models(models)?;
+        Ok(0)
+    })
+}
+
+/// Store `key` as the API key of `provider`. Returns 0, or -1 on error.
+///
+/// # Safety
+/// `config_path` is NULL or a NUL-terminated string; `provider` and `key` are NUL-terminated
+/// strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_config_set_api_key(
+    config_path: *const c_char,
+    provider: *const c_char,
+    key: *const c_char,
+) -> c_int {
+    guard(-1, || {
+        let key = unsafe { str_arg(key) }?.to_string();
+        let provider = unsafe { str_arg(provider) }?;
+        unsafe { config_at(config_path) }?.set_credential(provider, Credential::ApiKey(ApiKeyCredential { key }))?;
+        Ok(0)
+    })
+}
+
+/// The error of the last failed call on this thread, or NULL. Valid until the next call.
+#[unsafe(no_mangle)]
+pub extern "C" fn zeroai_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |e| e.as_ptr()))
+}
+
+/// Free a string returned by this library.
+///
+/// # Safety
+/// `s` is NULL or came from this library and is not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zeroai_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Run `f`, turning its error (or panic, which must not unwind into C) into `fail` and a
+/// message for `zeroai_last_error`.
+fn guard<T>(fail: T, f: impl FnOnce() -> anyhow::Result<T>) -> T {
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => {
+            LAST_ERROR.with(|e| e.borrow_mut().take());
+            return value;
+        }
+        Ok(Err(e)) => format!("{:#}", e),
+        Err(_) => "zeroai panicked".to_string(),
+    };
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+    fail
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> anyhow::Result<&'a str> {
+    anyhow::ensure!(!s.is_null(), "Unexpected NULL argument");
+    Ok(unsafe { CStr::from_ptr(s) }.to_str()?)
+}
+
+unsafe fn config_at(path: *const c_char) -> anyhow::Result<ConfigManager> {
+    if path.is_null() {
+        return Ok(ConfigManager::default_path());
+    }
+    Ok(ConfigManager::new(unsafe { str_arg(path) }?))
+}
+
+unsafe fn client_ref<'a>(client: *const ZeroAiClient) -> anyhow::Result<&'a ZeroAiClient> {
+    unsafe { client.as_ref() }.ok_or_else(|| anyhow::anyhow!("Unexpected NULL client"))
+}
+
+fn into_c_string(s: String) -> anyhow::Result<*mut c_char> {
+    Ok(CString::new(s)?.into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answers OpenAI chat calls with "Hello", streamed or not.
+    fn fake_upstream() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for socket in listener.incoming() {
+                let mut reader = BufReader::new(socket.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(n) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = n.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body: Value = serde_json::from_slice(&body).unwrap();
+                let chunk = |delta: Value, finish: Value| {
+                    json!({"id": "c", "object": "chat.completion.chunk", "model": "m", "choices": [{"index": 0, "delta": delta, "finish_reason": finish}]})
+                };
+                let (content_type, payload) = if body["stream"] == true {
+                    let events = [chunk(json!({"content": "Hel"}), Value::Null), chunk(json!({"content": "lo"}), Value::Null), chunk(json!({}), json!("stop"))];
+                    let events: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+                    ("text/event-stream", events + "data: [DONE]\n\n")
+                } else {
+                    let reply = json!({"id": "c", "object": "chat.completion", "model": "m", "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "stop"}]});
+                    ("application/json", reply.to_string())
+                };
+                let mut socket = reader.into_inner();
+                let head = format!("HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n", content_type, payload.len());
+                socket.write_all((head + &payload).as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}/v1", addr)
+    }
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn take(s: *mut c_char) -> String {
+        assert!(!s.is_null(), "{:?}", unsafe { CStr::from_ptr(zeroai_last_error()) });
+        let text = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        unsafe { zeroai_string_free(s) };
+        text
+    }
+
+    extern "C" fn collect(event: *const c_char, user_data: *mut c_void) -> c_int {
+        let events = unsafe { &mut *(user_data as *mut Vec<Value>) };
+        events.push(serde_json::from_str(unsafe { CStr::from_ptr(event) }.to_str().unwrap()).unwrap());
+        0
+    }
+
+    #[test]
+    fn chat_and_stream_through_the_c_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = c(dir.path().join("config.json").to_str().unwrap());
+        let config = ConfigManager::new(dir.path().join("config.json"));
+        let def = serde_json::from_value(json!({"id": "local", "base_url": fake_upstream()})).unwrap();
+        config.upsert_provider_def(def).unwrap();
+        unsafe {
+            assert_eq!(zeroai_config_set_api_key(path.as_ptr(), c("local").as_ptr(), c("sk-test").as_ptr()), 0);
+            assert_eq!(zeroai_config_set_enabled_models(path.as_ptr(), c(r#"["local/m"]"#).as_ptr()), 0);
+            assert_eq!(take(zeroai_config_enabled_models(path.as_ptr())), r#"["local/m"]"#);
+
+            let client = zeroai_client_new(path.as_ptr());
+            assert_eq!(take(zeroai_list_models(client)), r#"["local/m"]"#);
+
+            let request = c(r#"{"model": "local/m", "messages": [{"role": "user", "content": "hi"}]}"#);
+            let reply: Value = serde_json::from_str(&take(zeroai_chat(client, request.as_ptr()))).unwrap();
+            assert_eq!((&reply["content"][0]["text"], &reply["model"]), (&json!("Hello"), &json!("local/m")));
+
+            let mut events: Vec<Value> = Vec::new();
+            let status = zeroai_stream(client, request.as_ptr(), collect, &mut events as *mut _ as *mut c_void);
+            assert_eq!(status, 0);
+            let text: String = events.iter().filter_map(|e| e["text"].as_str()).collect();
+            assert_eq!((text.as_str(), &events.last().unwrap()["type"]), ("Hello", &json!("done")));
+
+            assert!(zeroai_chat(client, c("{").as_ptr()).is_null());
+            assert!(!zeroai_last_error().is_null());
+            zeroai_client_free(client);
+        }
+    }
+}
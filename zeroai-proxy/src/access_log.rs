@@ -0,0 +1,361 @@
+//! Request IDs and the access log.
+//!
+//! Every request gets an ID (the client's `x-request-id` when it sends a usable one), echoed
+//! in the response. Handlers fill in the model, account and token counts on the request's
+//! [`RequestLog`]; one `access` line is logged when the response body is finished, so streamed
+//! responses are logged with their full duration and usage. Requests for a model also send
+//! a `request.completed` or `request.failed` event to the event webhooks at that point, and
+//! are written to the request journal (`settings.journal_days`, see `usage lookup`) with the
+//! body hash and the provider's own request ID, for settling billing disputes.
+//!
+//! The client application is taken from `x-zeroai-app`, or else the product name at the start
+//! of the `user-agent`, so usage can be broken down per app. Handlers add the end user when
+//! the request body names one. Requests served by an A/B experiment variant get an
+//! `x-zeroai-variant: <experiment>/<variant>` response header.
+//!
+//! With `x-zeroai-debug: 1` on the request, the client also gets a summary of what the request
+//! took: the account that served it, retries, tokens, estimated cost and latency. It comes as
+//! an `x-zeroai-summary` JSON header, or for streams as a last `zeroai.summary` SSE event.
+
+use crate::server::AppState;
+use crate::webhooks::{REQUEST_COMPLETED, REQUEST_FAILED};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header::USER_AGENT},
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use zeroai::auth::usage::RequestSource;
+use zeroai::providers::api_error;
+use zeroai::store::JournalEntry;
+use zeroai::types::{ModelCost, Usage};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Names the client application for per-app usage.
+pub const APP_HEADER: &str = "x-zeroai-app";
+
+/// Names the experiment variant that served the request.
+pub const VARIANT_HEADER: &str = "x-zeroai-variant";
+
+/// `1` asks for a request summary in the response.
+pub const DEBUG_HEADER: &str = "x-zeroai-debug";
+
+/// The request summary of a non-streaming response.
+pub const SUMMARY_HEADER: &str = "x-zeroai-summary";
+
+/// SSE event type of the request summary that ends a stream.
+const SUMMARY_EVENT: &str = "zeroai.summary";
+
+/// Longest client-supplied request ID that is kept.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Longest app name that is kept; longer ones are truncated.
+const MAX_APP_LEN: usize = 64;
+
+/// Longest end-user ID that is kept; longer ones are truncated.
+const MAX_USER_LEN: usize = 128;
+
+/// Days requests stay in the journal when `settings.journal_days` is unset.
+const DEFAULT_JOURNAL_DAYS: u32 = 30;
+
+/// Per-request details for the access log, shared by the handler and the middleware.
+#[derive(Clone)]
+pub struct RequestLog {
+    id: Arc<str>,
+    app: Option<Arc<str>>,
+    user: Arc<OnceLock<String>>,
+    variant: Arc<OnceLock<String>>,
+    entry: Arc<Mutex<Entry>>,
+}
+
+#[derive(Default)]
+struct Entry {
+    model: Option<String>,
+    account: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cost_usd: f64,
+    /// Calls repeated with another account after a rate limit.
+    retries: u32,
+    request_hash: Option<String>,
+    upstream_request_id: Option<String>,
+}
+
+impl RequestLog {
+    fn new(id: String, app: Option<String>) -> Self {
+        Self {
+            id: id.into(),
+            app: app.map(Into::into),
+            user: Arc::default(),
+            variant: Arc::default(),
+            entry: Arc::default(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The client application, if the request named one or its user agent did.
+    pub fn app(&self) -> Option<&str> {
+        self.app.as_deref()
+    }
+
+    /// The end user named in the request body (OpenAI `user`), if any.
+    pub fn user(&self) -> Option<&str> {
+        self.user.get().map(String::as_str)
+    }
+
+    /// Set the end user; the first non-empty value wins.
+    pub fn set_user(&self, user: &str) {
+        let user = user.trim();
+        if !user.is_empty() {
+            let _ = self.user.set(user.chars().take(MAX_USER_LEN).collect());
+        }
+    }
+
+    /// The experiment variant serving the request, as `<experiment>/<variant>`.
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.get().map(String::as_str)
+    }
+
+    pub fn set_variant(&self, variant: String) {
+        let _ = self.variant.set(variant);
+    }
+
+    /// App, user and variant, for usage attribution.
+    pub fn source(&self) -> RequestSource<'_> {
+        RequestSource {
+            app: self.app(),
+            user: self.user(),
+            variant: self.variant(),
+        }
+    }
+
+    pub fn set_model(&self, model: &str) {
+        self.entry.lock().unwrap().model = Some(model.to_string());
+    }
+
+    /// Hash of the request body, for the journal.
+    pub fn set_request_hash(&self, hash: String) {
+        self.entry.lock().unwrap().request_hash = Some(hash);
+    }
+
+    /// Note the provider's ID for the upstream response, if its headers carry one.
+    pub fn record_upstream_headers(&self, headers: &HeaderMap) {
+        let id = api_error::upstream_request_id(headers).filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN);
+        if let Some(id) = id {
+            self.entry.lock().unwrap().upstream_request_id = Some(id);
+        }
+    }
+
+    /// Note the account that served the request, the tokens it used and their cost.
+    pub fn record(&self, provider: &str, account_id: &str, usage: Option<&Usage>, cost: &ModelCost) {
+        let mut entry = self.entry.lock().unwrap();
+        entry.account = Some(format!("{}/{}", provider, account_id));
+        if let Some(u) = usage {
+            entry.input_tokens += u.input_tokens;
+            entry.output_tokens += u.output_tokens;
+            entry.cost_usd += cost.usage_cost(u);
+        }
+    }
+
+    /// Note that the call is repeated with another account.
+    pub fn note_retry(&self) {
+        self.entry.lock().unwrap().retries += 1;
+    }
+
+    /// What the request took so far, for `x-zeroai-debug` clients.
+    fn summary(&self, start: Instant) -> Value {
+        let entry = self.entry.lock().unwrap();
+        json!({
+            "request_id": &*self.id,
+            "model": entry.model,
+            "account": entry.account,
+            "retries": entry.retries,
+            "input_tokens": entry.input_tokens,
+            "output_tokens": entry.output_tokens,
+            "cost_usd": entry.cost_usd,
+            "latency_ms": start.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+/// Assign the request ID, hand the handler a [`RequestLog`] and log the request once its
+/// response body is done (or dropped by a disconnecting client).
+pub async fn middleware(State(state): State<Arc<AppState>>, mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN)
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let log = RequestLog::new(id, client_app(req.headers()));
+    let debug = req.headers().get(DEBUG_HEADER).is_some_and(|v| v.as_bytes() == b"1");
+    req.extensions_mut().insert(log.clone());
+    let mut finished = Finished {
+        method: req.method().clone(),
+        route: req.uri().path().to_string(),
+        status: StatusCode::OK,
+        start: Instant::now(),
+        log,
+        state,
+    };
+
+    let mut resp = next.run(req).await;
+    if let Ok(v) = HeaderValue::from_str(finished.log.id()) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    if let Some(v) = finished.log.variant().and_then(|v| HeaderValue::from_str(v).ok()) {
+        resp.headers_mut().insert(VARIANT_HEADER, v);
+    }
+    finished.status = resp.status();
+    let is_stream = resp
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    if debug
+        && !is_stream
+        && let Ok(v) = HeaderValue::from_str(&finished.log.summary(finished.start).to_string())
+    {
+        resp.headers_mut().insert(SUMMARY_HEADER, v);
+    }
+    let (parts, body) = resp.into_parts();
+    let summary = (debug && is_stream).then(|| (finished.log.clone(), finished.start));
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &finished;
+        chunk
+    });
+    // Built once the stream is done, so it covers the whole response.
+    let summary = futures::stream::iter(summary).map(|(log, start)| {
+        Ok(format!("event: {}\ndata: {}\n\n", SUMMARY_EVENT, log.summary(start)).into())
+    });
+    Response::from_parts(parts, Body::from_stream(body.chain(summary)))
+}
+
+/// The `x-zeroai-app` header, else the first product token of the user agent
+/// (`claude-cli/1.0 (external)` is `claude-cli`).
+pub(crate) fn client_app(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    let app = header(APP_HEADER)
+        .filter(|v| !v.is_empty())
+        .or_else(|| header(USER_AGENT.as_str())?.split(['/', ' ']).next())?;
+    if app.is_empty() {
+        return None;
+    }
+    Some(app.chars().take(MAX_APP_LEN).collect())
+}
+
+/// Writes the access log line when dropped together with the response body.
+struct Finished {
+    method: Method,
+    route: String,
+    status: StatusCode,
+    start: Instant,
+    log: RequestLog,
+    state: Arc<AppState>,
+}
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        let entry = self.log.entry.lock().unwrap();
+        tracing::info!(
+            target: "access",
+            request_id = %self.log.id,
+            app = self.log.app().unwrap_or("-"),
+            user = self.log.user().unwrap_or("-"),
+            variant = self.log.variant().unwrap_or("-"),
+            method = %self.method,
+            route = %self.route,
+            status = self.status.as_u16(),
+            model = entry.model.as_deref().unwrap_or("-"),
+            account = entry.account.as_deref().unwrap_or("-"),
+            duration_ms = self.start.elapsed().as_millis() as u64,
+            input_tokens = entry.input_tokens,
+            output_tokens = entry.output_tokens,
+            "request"
+        );
+        let Some(model) = entry.model.clone() else {
+            return;
+        };
+        let data = json!({
+            "request_id": &*self.log.id,
+            "method": self.method.as_str(),
+            "route": self.route,
+            "status": self.status.as_u16(),
+            "model": model,
+            "account": entry.account,
+            "app": self.log.app(),
+            "user": self.log.user(),
+            "variant": self.log.variant(),
+            "duration_ms": self.start.elapsed().as_millis() as u64,
+            "input_tokens": entry.input_tokens,
+            "output_tokens": entry.output_tokens,
+        });
+        let journal = JournalEntry {
+            request_id: self.log.id.to_string(),
+            at_ms: chrono::Utc::now().timestamp_millis(),
+            route: self.route.clone(),
+            status: self.status.as_u16(),
+            model,
+            account: entry.account.clone(),
+            input_tokens: entry.input_tokens,
+            output_tokens: entry.output_tokens,
+            request_hash: entry.request_hash.clone(),
+            upstream_request_id: entry.upstream_request_id.clone(),
+        };
+        drop(entry);
+        let event = if self.status.is_success() { REQUEST_COMPLETED } else { REQUEST_FAILED };
+        self.state.webhooks.emit(event, data);
+
+        let days = self.state.config.get_settings().ok().and_then(|s| s.journal_days).unwrap_or(DEFAULT_JOURNAL_DAYS);
+        if days > 0 {
+            let store = self.state.store.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = store.record_journal_entry(&journal, i64::from(days) * 24 * 60 * 60 * 1000) {
+                    tracing::debug!("Failed to journal request {}: {}", journal.request_id, e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(k, v)| (k.parse().unwrap(), HeaderValue::from_static(v))).collect()
+    }
+
+    #[test]
+    fn app_header_wins_over_user_agent() {
+        let h = headers(&[(APP_HEADER, "my-agent"), ("user-agent", "curl/8.0")]);
+        assert_eq!(client_app(&h).as_deref(), Some("my-agent"));
+    }
+
+    #[test]
+    fn upstream_request_id_prefers_x_request_id() {
+        let log = RequestLog::new("r1".into(), None);
+        log.record_upstream_headers(&headers(&[("request-id", "req_1"), ("x-request-id", "req_2")]));
+        assert_eq!(log.entry.lock().unwrap().upstream_request_id.as_deref(), Some("req_2"));
+        log.record_upstream_headers(&HeaderMap::new());
+        assert_eq!(log.entry.lock().unwrap().upstream_request_id.as_deref(), Some("req_2"));
+    }
+
+    #[test]
+    fn app_is_inferred_from_user_agent() {
+        let h = headers(&[("user-agent", "claude-cli/1.0.3 (external, cli)")]);
+        assert_eq!(client_app(&h).as_deref(), Some("claude-cli"));
+        let h = headers(&[(APP_HEADER, ""), ("user-agent", "Aider")]);
+        assert_eq!(client_app(&h).as_deref(), Some("Aider"));
+        assert_eq!(client_app(&HeaderMap::new()), None);
+    }
+}
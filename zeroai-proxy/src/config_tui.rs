@@ -1,30 +1,34 @@
 use zeroai::{
-    ConfigManager,
+    CompressionConfig, ConfigManager, Provider, ProviderDef, ProviderError, split_model_id,
     auth::{
         self, AuthMethod, Credential, ApiKeyCredential, SetupTokenCredential,
-        ProviderAuthInfo, config::Account,
-    },
-    models::{fetch_models_for_provider, is_custom_provider},
-    oauth::{
-        google_antigravity::AntigravityOAuthProvider,
-        google_gemini_cli::GeminiCliOAuthProvider,
-        OAuthProvider, OAuthCallbacks, OAuthAuthInfo, OAuthPrompt,
+        ProviderAuthInfo, config::{Account, ContextGuard, NotificationConfig, ProviderMaintenance, ProxySettings, ShadowConfig},
+        discover::{DiscoveredServer, LOCAL_SERVERS, discover_local_servers, enable_local_server},
+        sniff::SniffedCredential,
+        usage::{AccountUsage, UsageStore},
     },
+    models::{fetch_models_cached, fetch_models_for_provider, is_custom_provider, supports_dynamic_models},
+    types::{InputModality, ModelDef, ModelOverride, RetryConfig},
+    oauth::{self, OAuthCallbacks, OAuthAuthInfo, OAuthPrompt},
+    probe::CapabilityProbe,
+    providers::{api_error, compatible::AuthStyle},
+    store::Store,
 };
 use async_trait::async_trait;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyModifiers},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Layout},
+    layout::{Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
+use std::collections::HashMap;
 use std::io::{self, stdout};
 use std::sync::{Arc, Mutex};
 
@@ -49,6 +53,78 @@ enum Screen {
     ModelSelect(ModelSelectState),
     AccountList(AccountListState),
     AccountLabelInput(AccountLabelInputState),
+    CustomProviderInput(FormState),
+    Settings(FormState),
+    SniffReview(SniffReviewState),
+    LocalServers(LocalServersState),
+}
+
+/// (label, hint) for each field of the "Add custom provider" form.
+const CUSTOM_PROVIDER_FIELDS: [(&str, &str); 5] = [
+    ("Name", "Provider ID, used as the model prefix (e.g. my-llm)"),
+    ("Base URL", "OpenAI-compatible API base (e.g. http://localhost:8000/v1)"),
+    ("Auth style", "bearer (default), x-api-key, or a custom header as \"Header-Name: prefix\""),
+    ("Models URL", "Optional; blank = {base URL}/models"),
+    ("Headers", "Optional default headers: \"Name: value; Other: value\""),
+];
+
+/// (label, hint) for each field of the Settings form.
+const SETTINGS_FIELDS: [(&str, &str); 24] = [
+    ("Host", "Default bind host for `serve` (blank = 127.0.0.1)"),
+    ("Port", "Default port for `serve` (blank = 8787)"),
+    ("Max retries", "Retries per upstream call, on top of account rotation (blank = none)"),
+    ("Retry backoff (ms)", "Base delay between retries, doubled each time (blank = 1000)"),
+    ("Log level", "Used when RUST_LOG is unset, e.g. debug or ai_proxy=trace (blank = ai_proxy=info)"),
+    ("Daily budget (USD)", "Spend cap over the last 24 hours, across all accounts (blank = none)"),
+    ("Model aliases", "\"alias=provider/model; other=provider/model\""),
+    ("Bare model IDs", "yes = also list and accept model names without the provider prefix (blank = no)"),
+    ("Bare ID priority", "Providers preferred when a bare name is ambiguous, e.g. \"openai, openrouter\""),
+    ("Compression model", "Cheap provider/model that summarizes long context before forwarding (blank = off)"),
+    ("Tool output limit", "Summarize tool results over this many tokens (blank = 4000)"),
+    ("History limit", "Fold older turns into a summary above this many tokens (blank = never)"),
+    ("Keep recent", "Latest messages never folded into the summary (blank = 10)"),
+    ("Transformers", "Rhai scripts per provider: \"provider=/path/script.rhai; *=/path/default.rhai\""),
+    ("Idempotency TTL (s)", "How long Idempotency-Key responses are kept for replay (blank = 86400)"),
+    ("Shadow model", "provider/model that also gets a copy of sampled chat requests, for comparison (blank = off)"),
+    ("Shadow percent", "Share of chat requests mirrored to the shadow model, 0-100 (blank = 10)"),
+    ("Shadow for", "Only mirror requests for these models, e.g. \"openai/gpt-4o, anthropic/claude-sonnet-4\" (blank = all)"),
+    ("Context limit (%)", "Largest share of a model's context window a chat prompt may fill (blank = no limit)"),
+    ("Over the limit", "reject = answer 400 with how much to trim, truncate = drop the oldest turns (blank = reject)"),
+    ("Desktop alerts", "yes = desktop notification on failed logins, exhausted rate limits and budget use (blank = no)"),
+    ("Alert webhooks", "URLs alerts are POSTed to (Slack and Discord webhooks work as is), comma-separated (blank = none)"),
+    ("Budget alerts (%)", "Shares of the daily budget that raise an alert, e.g. \"50, 90, 100\" (blank = 80, 100)"),
+    ("Journal days", "How long request metadata is kept for `usage lookup`, 0 = off (blank = 30)"),
+];
+
+/// A form of single-line text fields, described by (label, hint) pairs.
+struct FormState {
+    spec: &'static [(&'static str, &'static str)],
+    fields: Vec<String>,
+    focus: usize,
+    cursor_pos: usize,
+    error: Option<String>,
+}
+
+impl FormState {
+    fn new(spec: &'static [(&'static str, &'static str)]) -> Self {
+        Self::with_values(spec, vec![String::new(); spec.len()])
+    }
+
+    fn with_values(spec: &'static [(&'static str, &'static str)], fields: Vec<String>) -> Self {
+        let cursor_pos = fields[0].len();
+        Self {
+            spec,
+            fields,
+            focus: 0,
+            cursor_pos,
+            error: None,
+        }
+    }
+
+    fn focus_field(&mut self, idx: usize) {
+        self.focus = idx;
+        self.cursor_pos = self.fields[idx].len();
+    }
 }
 
 struct ModelsUrlInputState {
@@ -71,11 +147,61 @@ struct AuthInputState {
     cursor_pos: usize,
     initial_account_count: usize,
     oauth_error: Option<String>,
+    /// Input is a secret (API key, setup-token): shown masked unless `revealed`.
+    masked: bool,
+    revealed: bool,
+    /// Result of the connection test run before saving a key.
+    key_error: Option<String>,
+    /// Key whose test failed for a reason other than rejection; Enter again saves it anyway.
+    unverified_input: Option<String>,
+    /// Connection test of the entered key, run in the background before it is saved.
+    key_check: Option<KeyCheckTask>,
+    /// Background login task (OAuth only); aborted when the screen is left.
+    oauth_task: Option<OAuthTask>,
+    /// The login is waiting for the user to paste a code or redirect URL.
+    waiting_for_code: bool,
+}
+
+/// Handle to a spawned OAuth login that aborts the task when dropped, so leaving the
+/// screen (Esc, retry, quitting) never leaves a stale login running.
+struct OAuthTask(tokio::task::JoinHandle<()>);
+
+impl OAuthTask {
+    fn is_running(&self) -> bool {
+        !self.0.is_finished()
+    }
+}
+
+impl Drop for OAuthTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A running [`check_api_key`] of `input`; aborted when dropped, like [`OAuthTask`].
+struct KeyCheckTask {
+    input: String,
+    handle: tokio::task::JoinHandle<KeyCheck>,
+}
+
+impl Drop for KeyCheckTask {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+struct ModelItem {
+    full_id: String,
+    selected: bool,
+    /// Metadata shown next to the model (with config price/metadata overrides applied).
+    def: ModelDef,
+    /// The last `auth-check` capability probe of the model, if any.
+    probe: Option<CapabilityProbe>,
 }
 
 struct ModelSelectState {
     provider_id: String,
-    models: Vec<(String, bool)>, // (full_model_id, selected)
+    models: Vec<ModelItem>,
     list_state: ListState,
     /// Shown when fetch_models_for_provider failed (user can continue with empty list).
     error: Option<String>,
@@ -86,6 +212,34 @@ struct AccountListState {
     provider_label: String,
     accounts: Vec<Account>,
     list_state: ListState,
+    /// Usage recorded by the proxy, by account ID.
+    usage: HashMap<String, AccountUsage>,
+    /// Result of the last force-refresh.
+    status: Option<String>,
+}
+
+/// Credentials found in other tools' files, for the user to pick which to import.
+struct SniffReviewState {
+    /// Set when the review came up while setting up this provider; skipping it goes on to
+    /// the provider's normal auth flow.
+    provider_id: Option<String>,
+    items: Vec<SniffItem>,
+    list_state: ListState,
+}
+
+/// Local model servers found running, for the user to enable.
+struct LocalServersState {
+    servers: Vec<DiscoveredServer>,
+    /// Per entry of `servers`: all its models are enabled.
+    enabled: Vec<bool>,
+    list_state: ListState,
+}
+
+struct SniffItem {
+    found: SniffedCredential,
+    selected: bool,
+    /// Already one of the provider's accounts.
+    imported: bool,
 }
 
 struct AccountLabelInputState {
@@ -96,6 +250,38 @@ struct AccountLabelInputState {
     cursor_pos: usize,
 }
 
+/// Search and filter applied to the provider group list.
+#[derive(Default)]
+struct GroupFilter {
+    query: String,
+    /// Keys go to the search query instead of navigation.
+    searching: bool,
+    configured_only: bool,
+}
+
+impl GroupFilter {
+    fn is_active(&self) -> bool {
+        !self.query.is_empty() || self.configured_only
+    }
+
+    /// Indices into `groups` of the groups to show, in list order.
+    fn visible(&self, config: &ConfigManager, groups: &[(String, Vec<ProviderAuthInfo>)]) -> Vec<usize> {
+        groups
+            .iter()
+            .enumerate()
+            .filter(|(_, (label, providers))| {
+                let matches = self.query.is_empty()
+                    || fuzzy_match(&self.query, label)
+                    || providers.iter().any(|p| fuzzy_match(&self.query, &p.label) || fuzzy_match(&self.query, &p.provider_id));
+                matches
+                    && (!self.configured_only
+                        || providers.iter().any(|p| config.has_credential(&p.provider_id).unwrap_or(false)))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // OAuth Callbacks for TUI
 // ---------------------------------------------------------------------------
@@ -103,8 +289,21 @@ struct AccountLabelInputState {
 struct TuiOAuthCallbacks {
     auth_info: Arc<Mutex<Option<OAuthAuthInfo>>>,
     prompt_result: Arc<Mutex<Option<String>>>,
-    _waiting_for_prompt: Arc<Mutex<bool>>,
-    _progress: Arc<Mutex<String>>,
+    waiting_for_prompt: Arc<Mutex<bool>>,
+    progress: Arc<Mutex<String>>,
+    /// Set when the login task fails.
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl TuiOAuthCallbacks {
+    /// Clear state left over from a previous (cancelled or failed) login.
+    fn reset(&self) {
+        *self.auth_info.lock().unwrap() = None;
+        *self.prompt_result.lock().unwrap() = None;
+        *self.waiting_for_prompt.lock().unwrap() = false;
+        self.progress.lock().unwrap().clear();
+        *self.error.lock().unwrap() = None;
+    }
 }
 
 #[async_trait]
@@ -116,14 +315,14 @@ impl OAuthCallbacks for TuiOAuthCallbacks {
 
     async fn on_prompt(&self, _prompt: OAuthPrompt) -> anyhow::Result<String> {
         {
-            let mut waiting = self._waiting_for_prompt.lock().unwrap();
+            let mut waiting = self.waiting_for_prompt.lock().unwrap();
             *waiting = true;
         }
         loop {
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
             let mut res = self.prompt_result.lock().unwrap();
             if let Some(val) = res.take() {
-                let mut waiting = self._waiting_for_prompt.lock().unwrap();
+                let mut waiting = self.waiting_for_prompt.lock().unwrap();
                 *waiting = false;
                 return Ok(val);
             }
@@ -131,7 +330,7 @@ impl OAuthCallbacks for TuiOAuthCallbacks {
     }
 
     fn on_progress(&self, message: &str) {
-        let mut lock = self._progress.lock().unwrap();
+        let mut lock = self.progress.lock().unwrap();
         *lock = message.to_string();
     }
 }
@@ -143,21 +342,24 @@ impl OAuthCallbacks for TuiOAuthCallbacks {
 pub async fn run_config_tui() -> anyhow::Result<()> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    // Pasted keys arrive as one Event::Paste instead of keystrokes (a trailing newline would submit early).
+    stdout().execute(EnableBracketedPaste)?;
 
     let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend)?;
 
     let config = ConfigManager::default_path();
-    let groups = auth::provider_groups();
+    let mut groups = auth::provider_groups_with(&config.get_provider_defs().unwrap_or_default());
 
     let mut screen = Screen::ProviderGroups;
     let mut group_state = ListState::default();
     group_state.select(Some(0));
     let mut sub_state = ListState::default();
 
-    let result = run_tui_loop(&mut terminal, config, &groups, &mut screen, &mut group_state, &mut sub_state).await;
+    let result = run_tui_loop(&mut terminal, config, &mut groups, &mut screen, &mut group_state, &mut sub_state).await;
 
     disable_raw_mode()?;
+    stdout().execute(DisableBracketedPaste)?;
     stdout().execute(LeaveAlternateScreen)?;
 
     result
@@ -166,7 +368,7 @@ pub async fn run_config_tui() -> anyhow::Result<()> {
 async fn run_tui_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     config: ConfigManager,
-    groups: &[(String, Vec<ProviderAuthInfo>)],
+    groups: &mut Vec<(String, Vec<ProviderAuthInfo>)>,
     screen: &mut Screen,
     group_state: &mut ListState,
     sub_state: &mut ListState,
@@ -174,15 +376,17 @@ async fn run_tui_loop(
     let oauth_callbacks = Arc::new(TuiOAuthCallbacks {
         auth_info: Arc::new(Mutex::new(None)),
         prompt_result: Arc::new(Mutex::new(None)),
-        _waiting_for_prompt: Arc::new(Mutex::new(false)),
-        _progress: Arc::new(Mutex::new(String::new())),
+        waiting_for_prompt: Arc::new(Mutex::new(false)),
+        progress: Arc::new(Mutex::new(String::new())),
+        error: Arc::new(Mutex::new(None)),
     });
 
     // When there are no accounts at all, auto-enter add-account flow for the first provider (no keypress required).
     let mut auto_entered = false;
+    let mut filter = GroupFilter::default();
 
     loop {
-        terminal.draw(|f| draw(f, &config, groups, screen, group_state, sub_state))?;
+        terminal.draw(|f| draw(f, &config, groups, &filter, screen, group_state, sub_state))?;
 
         // Once per session: if we're on provider list and no provider has any account, auto-open add flow
         if !auto_entered {
@@ -205,43 +409,108 @@ async fn run_tui_loop(
         }
 
         if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
+            let ev = event::read()?;
+            if let Event::Paste(text) = &ev {
+                paste_into(screen, text);
+            }
+            if let Event::Key(key) = ev {
                 if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
                     return Ok(());
                 }
 
                 match screen {
                     Screen::ProviderGroups => {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                let i = group_state.selected().unwrap_or(0);
-                                let next = if i == 0 { groups.len().saturating_sub(1) } else { i - 1 };
-                                group_state.select(Some(next));
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                let i = group_state.selected().unwrap_or(0);
-                                let next = if i + 1 >= groups.len() { 0 } else { i + 1 };
-                                group_state.select(Some(next));
+                        let visible = filter.visible(&config, groups);
+                        let mut open = None;
+                        if filter.searching {
+                            match key.code {
+                                KeyCode::Esc => {
+                                    filter.searching = false;
+                                    filter.query.clear();
+                                    group_state.select(Some(0));
+                                }
+                                KeyCode::Enter => {
+                                    filter.searching = false;
+                                    open = group_state.selected().and_then(|i| visible.get(i).copied());
+                                }
+                                KeyCode::Backspace => {
+                                    filter.query.pop();
+                                    group_state.select(Some(0));
+                                }
+                                KeyCode::Up => select_prev(group_state, visible.len()),
+                                KeyCode::Down => select_next(group_state, visible.len()),
+                                KeyCode::Char(c) => {
+                                    filter.query.push(c);
+                                    group_state.select(Some(0));
+                                }
+                                _ => {}
                             }
-                            KeyCode::Enter => {
-                                if let Some(idx) = group_state.selected() {
-                                    if idx < groups.len() {
-                                        let (_, providers) = &groups[idx];
-                                        if providers.len() == 1 {
-                                            let prov = &providers[0];
-                                            let no_accounts = enter_account_list(config.clone(), prov, screen)?;
-                                            if no_accounts {
-                                                handle_provider_select(config.clone(), prov, screen, oauth_callbacks.clone(), false).await?;
-                                            }
-                                        } else {
-                                            sub_state.select(Some(0));
-                                            *screen = Screen::SubProviders(idx);
-                                        }
+                        } else {
+                            match key.code {
+                                KeyCode::Esc if filter.is_active() => {
+                                    filter = GroupFilter::default();
+                                    group_state.select(Some(0));
+                                }
+                                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                                KeyCode::Char('n') => {
+                                    *screen = Screen::CustomProviderInput(FormState::new(&CUSTOM_PROVIDER_FIELDS));
+                                }
+                                KeyCode::Char('i') => {
+                                    *screen = Screen::SniffReview(sniff_review(&config, None));
+                                }
+                                KeyCode::Char('d') => {
+                                    *screen = Screen::LocalServers(local_servers(&config).await);
+                                }
+                                KeyCode::Char('s') => {
+                                    let settings = config.get_settings()?;
+                                    *screen = Screen::Settings(FormState::with_values(&SETTINGS_FIELDS, settings_form_values(&settings)));
+                                }
+                                KeyCode::Char('/') => {
+                                    filter.searching = true;
+                                    group_state.select(Some(0));
+                                }
+                                KeyCode::Char('c') => {
+                                    filter.configured_only = !filter.configured_only;
+                                    group_state.select(Some(0));
+                                }
+                                KeyCode::Up | KeyCode::Char('k') => select_prev(group_state, visible.len()),
+                                KeyCode::Down | KeyCode::Char('j') => select_next(group_state, visible.len()),
+                                // 1-9 open the n-th listed group.
+                                KeyCode::Char(c @ '1'..='9') => {
+                                    let n = c as usize - '1' as usize;
+                                    if n < visible.len() {
+                                        group_state.select(Some(n));
+                                        open = Some(visible[n]);
                                     }
                                 }
+                                // Shift+letter jumps to the next group starting with that letter.
+                                KeyCode::Char(c) if c.is_ascii_uppercase() => {
+                                    let start = group_state.selected().unwrap_or(0);
+                                    let hit = (1..=visible.len())
+                                        .map(|off| (start + off) % visible.len())
+                                        .find(|&i| groups[visible[i]].0.to_ascii_uppercase().starts_with(c));
+                                    if let Some(i) = hit {
+                                        group_state.select(Some(i));
+                                    }
+                                }
+                                KeyCode::Enter => {
+                                    open = group_state.selected().and_then(|i| visible.get(i).copied());
+                                }
+                                _ => {}
+                            }
+                        }
+                        if let Some(idx) = open {
+                            let (_, providers) = &groups[idx];
+                            if providers.len() == 1 {
+                                let prov = &providers[0];
+                                let no_accounts = enter_account_list(config.clone(), prov, screen)?;
+                                if no_accounts {
+                                    handle_provider_select(config.clone(), prov, screen, oauth_callbacks.clone(), false).await?;
+                                }
+                            } else {
+                                sub_state.select(Some(0));
+                                *screen = Screen::SubProviders(idx);
                             }
-                            _ => {}
                         }
                     }
                     Screen::SubProviders(group_idx) => {
@@ -320,6 +589,36 @@ async fn run_tui_loop(
                                     }
                                 }
                             }
+                            KeyCode::Char('r') if state.list_state.selected().is_some_and(|i| i < state.accounts.len()) => {
+                                let idx = state.list_state.selected().unwrap_or(0);
+                                let account_id = state.accounts[idx].id.clone();
+                                state.status = Some(match config.refresh_account(&state.provider_id, &account_id).await {
+                                    Ok(()) => "✓ Token refreshed".to_string(),
+                                    Err(e) => format!("❌ {}", e),
+                                });
+                                state.accounts = config.list_accounts(&state.provider_id)?;
+                            }
+                            KeyCode::Char('x') if state.list_state.selected().is_some_and(|i| i < state.accounts.len()) => {
+                                let acc = &state.accounts[state.list_state.selected().unwrap_or(0)];
+                                config.set_account_enabled(&state.provider_id, &acc.id, !acc.enabled, None)?;
+                                state.status = Some(format!(
+                                    "✓ {} {}",
+                                    acc.display_label(),
+                                    if acc.enabled { "disabled" } else { "enabled" }
+                                ));
+                                state.accounts = config.list_accounts(&state.provider_id)?;
+                            }
+                            KeyCode::Char('m') => {
+                                let now = chrono::Utc::now().timestamp_millis();
+                                let down = config.providers_in_maintenance(now)?.contains_key(&state.provider_id);
+                                let maintenance = (!down).then(ProviderMaintenance::default);
+                                config.set_provider_maintenance(&state.provider_id, maintenance)?;
+                                state.status = Some(if down {
+                                    format!("✓ {} back in service", state.provider_label)
+                                } else {
+                                    format!("✓ {} in maintenance until turned back on", state.provider_label)
+                                });
+                            }
                             KeyCode::Char('e') => {
                                 if let Some(idx) = state.list_state.selected() {
                                     if idx < state.accounts.len() {
@@ -360,7 +659,7 @@ async fn run_tui_loop(
                                         let pid = state.provider_id.clone();
                                         let aid = state.accounts[idx].id.clone();
                                         config.use_account(&pid, &aid)?;
-                                        enter_model_selection(&config, &pid, screen).await?;
+                                        enter_model_selection(&config, &pid, screen, false).await?;
                                     }
                                 }
                             }
@@ -380,6 +679,8 @@ async fn run_tui_loop(
                                     provider_label: state.provider_label.clone(),
                                     accounts,
                                     list_state: ls,
+                                    usage: load_account_usage(&state.provider_id),
+                                    status: None,
                                 });
                             }
                             KeyCode::Char(c) => {
@@ -428,6 +729,8 @@ async fn run_tui_loop(
                                     provider_label: state.provider_label.clone(),
                                     accounts,
                                     list_state: ls,
+                                    usage: load_account_usage(&state.provider_id),
+                                    status: None,
                                 });
                             }
                             _ => {}
@@ -436,13 +739,26 @@ async fn run_tui_loop(
                     Screen::AuthInput(state) => {
                         match key.code {
                             KeyCode::Esc => {
+                                // Dropping the state aborts a running OAuth task.
                                 *screen = Screen::ProviderGroups;
                             }
+                            KeyCode::Char('r') if state.is_oauth && state.oauth_error.is_some() => {
+                                let (pid, is_add) = (state.provider_id.clone(), state.is_add);
+                                let prov = groups.iter().flat_map(|(_, ps)| ps).find(|p| p.provider_id == pid).cloned();
+                                if let Some(prov) = prov {
+                                    handle_provider_select(config.clone(), &prov, screen, oauth_callbacks.clone(), is_add).await?;
+                                }
+                            }
+                            KeyCode::Char('r') if state.masked && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                state.revealed = !state.revealed;
+                            }
                             KeyCode::Char(c) => {
+                                state.key_error = None;
                                 state.input.insert(state.cursor_pos, c);
                                 state.cursor_pos += 1;
                             }
                             KeyCode::Backspace => {
+                                state.key_error = None;
                                 if state.cursor_pos > 0 {
                                     state.cursor_pos -= 1;
                                     state.input.remove(state.cursor_pos);
@@ -477,41 +793,23 @@ async fn run_tui_loop(
                                         state.input.clear();
                                         state.cursor_pos = 0;
                                         state.hint = "Exchanging code for token...".into();
-                                    } else {
-                                        let provider_id = state.provider_id.clone();
+                                    } else if state.key_check.is_none() {
                                         let input = state.input.trim().to_string();
                                         let is_setup = state.hint.contains("setup-token");
 
-                                        let cred = if is_setup {
-                                            Credential::SetupToken(SetupTokenCredential {
-                                                token: input,
-                                            })
-                                        } else {
-                                            Credential::ApiKey(ApiKeyCredential {
-                                                key: input,
-                                            })
-                                        };
-
-                                        if state.is_add {
-                                            config.add_account(&provider_id, None, cred)?;
-                                        } else {
-                                            config.set_credential(&provider_id, cred)?;
-                                        }
-
-                                        if is_custom_provider(&provider_id) {
-                                            let base_url = provider_id.strip_prefix("custom:").unwrap_or("").trim().trim_end_matches('/');
-                                            let input_url = config.get_models_url(&provider_id).ok().flatten().unwrap_or_default();
-                                            let cursor_pos = input_url.len();
-                                            *screen = Screen::ModelsUrlInput(ModelsUrlInputState {
-                                                provider_id: provider_id.clone(),
-                                                base_url: base_url.to_string(),
-                                                input: input_url,
-                                                cursor_pos,
-                                                auth_error: None,
+                                        if !is_setup && state.unverified_input.as_deref() != Some(input.as_str()) {
+                                            // Saved once the check passes (see below the event handling).
+                                            let (config, provider_id, key) = (config.clone(), state.provider_id.clone(), input.clone());
+                                            state.key_error = None;
+                                            state.key_check = Some(KeyCheckTask {
+                                                input,
+                                                handle: tokio::spawn(async move { check_api_key(&config, &provider_id, &key).await }),
                                             });
-                                        } else {
-                                            enter_model_selection(&config, &provider_id, screen).await?;
+                                            continue;
                                         }
+
+                                        let (provider_id, is_add) = (state.provider_id.clone(), state.is_add);
+                                        save_key(&config, screen, &provider_id, input, is_setup, is_add).await?;
                                     }
                                 }
                             }
@@ -531,11 +829,11 @@ async fn run_tui_loop(
                                     Some(state.input.trim().to_string())
                                 };
                                 let api_key = config.resolve_api_key(&pid).await.ok().flatten();
-                                match fetch_models_for_provider(&pid, api_key.as_deref(), url_opt.as_deref()).await {
+                                match fetch_models_cached(&pid, api_key.as_deref(), url_opt.as_deref(), true).await {
                                     Ok(_) => {
                                         let _ = config.set_models_url(&pid, url_opt.as_deref());
                                         *screen = Screen::ProviderGroups;
-                                        enter_model_selection(&config, &pid, screen).await?;
+                                        enter_model_selection(&config, &pid, screen, false).await?;
                                     }
                                     Err(e) => {
                                         let msg = if e.is_auth_error() {
@@ -584,6 +882,124 @@ async fn run_tui_loop(
                             _ => {}
                         }
                     }
+                    Screen::CustomProviderInput(state) => {
+                        match key.code {
+                            KeyCode::Esc => {
+                                *screen = Screen::ProviderGroups;
+                            }
+                            KeyCode::Enter => {
+                                let builtin = auth::all_provider_auth_info();
+                                let is_builtin = |id: &str| builtin.iter().any(|p| p.provider_id == id);
+                                match build_provider_def(&state.fields, is_builtin) {
+                                    Ok(def) => {
+                                        let pid = def.id.clone();
+                                        config.upsert_provider_def(def)?;
+                                        *groups = auth::provider_groups_with(&config.get_provider_defs()?);
+                                        let prov = groups
+                                            .iter()
+                                            .flat_map(|(_, ps)| ps)
+                                            .find(|p| p.provider_id == pid)
+                                            .cloned();
+                                        *screen = Screen::ProviderGroups;
+                                        if let Some(prov) = prov {
+                                            handle_provider_select(config.clone(), &prov, screen, oauth_callbacks.clone(), false).await?;
+                                        }
+                                    }
+                                    Err(msg) => state.error = Some(format!("❌ {}", msg)),
+                                }
+                            }
+                            code => edit_form(state, code),
+                        }
+                    }
+                    Screen::Settings(state) => {
+                        match key.code {
+                            KeyCode::Esc => {
+                                *screen = Screen::ProviderGroups;
+                            }
+                            KeyCode::Enter => match parse_settings(&state.fields) {
+                                Ok(settings) => {
+                                    // Experiments, event webhooks, concurrency limits, warm connections,
+                                    // Ollama's keep_alive, the overload retry policy and the attempt budget
+                                    // aren't on the form; keep the ones in the config file.
+                                    let saved = config.get_settings().unwrap_or_default();
+                                    let retry = match (settings.retry, saved.retry) {
+                                        (Some(retry), Some(saved)) => Some(RetryConfig {
+                                            overload_retries: saved.overload_retries,
+                                            overload_backoff_ms: saved.overload_backoff_ms,
+                                            max_attempts: saved.max_attempts,
+                                            deadline_ms: saved.deadline_ms,
+                                            ..retry
+                                        }),
+                                        (retry, _) => retry,
+                                    };
+                                    config.set_settings(ProxySettings {
+                                        experiments: saved.experiments,
+                                        event_webhooks: saved.event_webhooks,
+                                        concurrency: saved.concurrency,
+                                        warm_connections: saved.warm_connections,
+                                        ollama_keep_alive: saved.ollama_keep_alive,
+                                        retry,
+                                        ..settings
+                                    })?;
+                                    *screen = Screen::ProviderGroups;
+                                }
+                                Err(msg) => state.error = Some(format!("❌ {}", msg)),
+                            },
+                            code => edit_form(state, code),
+                        }
+                    }
+                    Screen::SniffReview(state) => {
+                        // Enter imports the ticked credentials, Esc imports none; either way a
+                        // provider being set up moves on.
+                        let done = match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                select_prev(&mut state.list_state, state.items.len());
+                                None
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                select_next(&mut state.list_state, state.items.len());
+                                None
+                            }
+                            KeyCode::Char(' ') => {
+                                if let Some(item) = state.list_state.selected().and_then(|i| state.items.get_mut(i)) {
+                                    item.selected = !item.selected && !item.imported;
+                                }
+                                None
+                            }
+                            KeyCode::Enter => Some(import_sniffed(&config, &state.items)?),
+                            KeyCode::Esc | KeyCode::Char('q') => Some(0),
+                            _ => None,
+                        };
+                        if let Some(imported) = done {
+                            match state.provider_id.clone() {
+                                Some(pid) if imported > 0 => enter_model_selection(&config, &pid, screen, true).await?,
+                                Some(pid) => {
+                                    let prov_info = groups.iter().flat_map(|(_, ps)| ps).find(|p| p.provider_id == pid);
+                                    match prov_info {
+                                        Some(prov) => start_auth_flow(config.clone(), prov, screen, oauth_callbacks.clone(), false).await?,
+                                        None => *screen = Screen::ProviderGroups,
+                                    }
+                                }
+                                None => *screen = Screen::ProviderGroups,
+                            }
+                        }
+                    }
+                    Screen::LocalServers(state) => {
+                        match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => select_prev(&mut state.list_state, state.servers.len()),
+                            KeyCode::Down | KeyCode::Char('j') => select_next(&mut state.list_state, state.servers.len()),
+                            KeyCode::Enter => {
+                                if let Some(i) = state.list_state.selected().filter(|&i| i < state.servers.len()) {
+                                    enable_local_server(&config, &state.servers[i])?;
+                                    state.enabled[i] = true;
+                                    *groups = auth::provider_groups_with(&config.get_provider_defs()?);
+                                }
+                            }
+                            KeyCode::Char('r') => *state = local_servers(&config).await,
+                            KeyCode::Esc | KeyCode::Char('q') => *screen = Screen::ProviderGroups,
+                            _ => {}
+                        }
+                    }
                     Screen::ModelSelect(state) => {
                         match key.code {
                             KeyCode::Esc | KeyCode::Char('q') => {
@@ -603,16 +1019,21 @@ async fn run_tui_loop(
                             KeyCode::Char(' ') => {
                                 if let Some(idx) = state.list_state.selected() {
                                     if idx < state.models.len() {
-                                        state.models[idx].1 = !state.models[idx].1;
+                                        state.models[idx].selected = !state.models[idx].selected;
                                     }
                                 }
                             }
                             KeyCode::Char('a') => {
-                                let all_selected = state.models.iter().all(|(_, s)| *s);
+                                let all_selected = state.models.iter().all(|m| m.selected);
                                 for item in &mut state.models {
-                                    item.1 = !all_selected;
+                                    item.selected = !all_selected;
                                 }
                             }
+                            KeyCode::Char('r') => {
+                                save_models(&config, state)?;
+                                let pid = state.provider_id.clone();
+                                enter_model_selection(&config, &pid, screen, true).await?;
+                            }
                             KeyCode::Enter => {
                                 save_models(&config, state)?;
                                 *screen = Screen::ProviderGroups;
@@ -624,16 +1045,55 @@ async fn run_tui_loop(
             }
         }
 
+        if let Screen::AuthInput(state) = screen
+            && state.key_check.as_ref().is_some_and(|c| c.handle.is_finished())
+            && let Some(mut check) = state.key_check.take()
+        {
+            let result = (&mut check.handle).await;
+            // A key edited while it was being checked waits for the next Enter.
+            if check.input == state.input.trim() {
+                match result {
+                    Ok(KeyCheck::Failed { message, rejected: true }) => {
+                        state.key_error = Some(format!("❌ Key rejected: {}", message));
+                        state.unverified_input = None;
+                    }
+                    Ok(KeyCheck::Failed { message, rejected: false }) => {
+                        state.key_error = Some(format!(
+                            "⚠ Connection test failed: {} (press Enter again to save anyway)",
+                            message
+                        ));
+                        state.unverified_input = Some(check.input.clone());
+                    }
+                    Ok(KeyCheck::Passed | KeyCheck::Skipped) => {
+                        let (provider_id, is_add) = (state.provider_id.clone(), state.is_add);
+                        save_key(&config, screen, &provider_id, check.input.clone(), false, is_add).await?;
+                    }
+                    Err(e) => {
+                        state.key_error = Some(format!("⚠ Connection test failed: {} (press Enter again to save anyway)", e));
+                        state.unverified_input = Some(check.input.clone());
+                    }
+                }
+            }
+        }
+
         let mut next_provider_id = None;
         let mut oauth_error_msg = None;
         if let Screen::AuthInput(state) = screen {
             if state.is_oauth {
                 // Check for OAuth error from the background task
-                let progress = oauth_callbacks._progress.lock().unwrap();
-                if progress.starts_with("OAuth failed:") {
-                    oauth_error_msg = Some(progress.clone());
+                oauth_error_msg = oauth_callbacks.error.lock().unwrap().clone();
+                if oauth_error_msg.is_none() && !state.oauth_task.as_ref().is_some_and(OAuthTask::is_running) {
+                    // Finished without storing a credential or reporting an error (e.g. panicked).
+                    let saved = if state.is_add {
+                        config.list_accounts(&state.provider_id).map(|a| a.len() > state.initial_account_count).unwrap_or(false)
+                    } else {
+                        config.has_credential(&state.provider_id).unwrap_or(false)
+                    };
+                    if !saved {
+                        oauth_error_msg = Some("OAuth failed: login task stopped unexpectedly".into());
+                    }
                 }
-                drop(progress);
+                state.waiting_for_code = *oauth_callbacks.waiting_for_prompt.lock().unwrap();
 
                 if oauth_error_msg.is_none() {
                     // No error - check for successful completion
@@ -662,7 +1122,7 @@ async fn run_tui_loop(
                         }
                         drop(info);
 
-                        let progress = oauth_callbacks._progress.lock().unwrap();
+                        let progress = oauth_callbacks.progress.lock().unwrap();
                         if !progress.is_empty() {
                             state.hint = progress.clone();
                         }
@@ -706,7 +1166,7 @@ async fn run_tui_loop(
                     auth_error: None,
                 });
             } else {
-                enter_model_selection(&config, &pid, screen).await?;
+                enter_model_selection(&config, &pid, screen, true).await?;
             }
         }
     }
@@ -725,10 +1185,66 @@ fn enter_account_list(config: ConfigManager, prov: &ProviderAuthInfo, screen: &m
         provider_label: prov.label.clone(),
         accounts,
         list_state: ls,
+        usage: load_account_usage(&prov.provider_id),
+        status: None,
     });
     Ok(false)
 }
 
+/// Usage recorded by the proxy for a provider's accounts, keyed by account ID.
+fn load_account_usage(provider_id: &str) -> HashMap<String, AccountUsage> {
+    let prefix = format!("{}/", provider_id);
+    Store::open_default()
+        .and_then(|store| UsageStore::new(Arc::new(store)).load())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(key, usage)| key.strip_prefix(&prefix).map(|id| (id.to_string(), usage)))
+        .collect()
+}
+
+/// The import review of credentials found in other tools' files (for `provider_id` only, if
+/// given). New ones start ticked.
+fn sniff_review(config: &ConfigManager, provider_id: Option<&str>) -> SniffReviewState {
+    let items: Vec<SniffItem> = auth::sniff::sniff_credentials(None)
+        .into_iter()
+        .filter(|found| provider_id.is_none_or(|p| p == found.provider))
+        .map(|found| {
+            let imported = found.is_in(&config.list_accounts(&found.provider).unwrap_or_default());
+            SniffItem { found, selected: !imported, imported }
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    if !items.is_empty() {
+        list_state.select(Some(0));
+    }
+    SniffReviewState {
+        provider_id: provider_id.map(String::from),
+        items,
+        list_state,
+    }
+}
+
+/// Probe localhost for Ollama, vLLM, LM Studio and llama.cpp.
+async fn local_servers(config: &ConfigManager) -> LocalServersState {
+    let servers = discover_local_servers().await;
+    let enabled_models = config.get_enabled_models().unwrap_or_default();
+    let enabled = servers.iter().map(|s| s.is_enabled(&enabled_models)).collect();
+    let mut list_state = ListState::default();
+    if !servers.is_empty() {
+        list_state.select(Some(0));
+    }
+    LocalServersState { servers, enabled, list_state }
+}
+
+/// Add the selected credentials as accounts. Returns how many were added.
+fn import_sniffed(config: &ConfigManager, items: &[SniffItem]) -> anyhow::Result<usize> {
+    let picked: Vec<&SniffedCredential> = items.iter().filter(|i| i.selected && !i.imported).map(|i| &i.found).collect();
+    for found in &picked {
+        config.add_account(&found.provider, Some(found.label()), found.credential.clone())?;
+    }
+    Ok(picked.len())
+}
+
 async fn handle_provider_select(
     config: ConfigManager,
     prov: &ProviderAuthInfo,
@@ -738,9 +1254,6 @@ async fn handle_provider_select(
 ) -> anyhow::Result<()> {
     let provider_id = prov.provider_id.clone();
 
-    // Capture initial account count for detecting new accounts after OAuth
-    let initial_account_count = config.list_accounts(&provider_id).unwrap_or_default().len();
-
     if !is_add {
         if config.has_credential(&provider_id).unwrap_or(false) {
             if is_custom_provider(&provider_id) {
@@ -756,21 +1269,39 @@ async fn handle_provider_select(
                 });
                 return Ok(());
             }
-            return enter_model_selection(&config, &provider_id, screen).await;
-        }
-
-        if let Some(cred) = auth::sniff::sniff_external_credential(&provider_id) {
-            config.set_credential(&provider_id, cred)?;
-            return enter_model_selection(&config, &provider_id, screen).await;
+            return enter_model_selection(&config, &provider_id, screen, false).await;
         }
 
         if let Some(key) = auth::sniff::env_api_key(&provider_id) {
             let cred = Credential::ApiKey(ApiKeyCredential { key });
             config.set_credential(&provider_id, cred)?;
-            return enter_model_selection(&config, &provider_id, screen).await;
+            return enter_model_selection(&config, &provider_id, screen, false).await;
+        }
+
+        // Logins found in other tools' files are only imported once the user picks them.
+        let review = sniff_review(&config, Some(&provider_id));
+        if !review.items.is_empty() {
+            *screen = Screen::SniffReview(review);
+            return Ok(());
         }
     }
 
+    start_auth_flow(config, prov, screen, callbacks, is_add).await
+}
+
+/// Ask for a new credential for `prov`: an API key prompt or an OAuth login.
+async fn start_auth_flow(
+    config: ConfigManager,
+    prov: &ProviderAuthInfo,
+    screen: &mut Screen,
+    callbacks: Arc<TuiOAuthCallbacks>,
+    is_add: bool,
+) -> anyhow::Result<()> {
+    let provider_id = prov.provider_id.clone();
+
+    // Capture initial account count for detecting new accounts after OAuth
+    let initial_account_count = config.list_accounts(&provider_id).unwrap_or_default().len();
+
     let method = prov.auth_methods.first().cloned().unwrap_or(AuthMethod::ApiKey {
         env_var: None,
         hint: None,
@@ -789,6 +1320,13 @@ async fn handle_provider_select(
                 cursor_pos: 0,
                 initial_account_count,
                 oauth_error: None,
+                masked: true,
+                revealed: false,
+                key_error: None,
+                unverified_input: None,
+                key_check: None,
+                oauth_task: None,
+                waiting_for_code: false,
             });
         }
         AuthMethod::SetupToken { hint } => {
@@ -803,38 +1341,50 @@ async fn handle_provider_select(
                 cursor_pos: 0,
                 initial_account_count,
                 oauth_error: None,
+                masked: true,
+                revealed: false,
+                key_error: None,
+                unverified_input: None,
+                key_check: None,
+                oauth_task: None,
+                waiting_for_code: false,
             });
         }
         AuthMethod::OAuth { hint } => {
             let pid = provider_id.clone();
             let config_mgr = config.clone();
-            tokio::spawn(async move {
-                let oauth_provider: Box<dyn OAuthProvider + Send> = match pid.as_str() {
-                    "gemini-cli" => Box::new(GeminiCliOAuthProvider),
-                    "antigravity" => Box::new(AntigravityOAuthProvider),
-                    "openai-codex" => Box::new(zeroai::oauth::openai_codex::OpenAiCodexOAuthProvider),
-                    "github-copilot" => Box::new(zeroai::oauth::github_copilot::GitHubCopilotOAuthProvider),
-                    "qwen-portal" => Box::new(zeroai::oauth::qwen_portal::QwenPortalOAuthProvider),
-                    _ => return,
+            callbacks.reset();
+            let task = tokio::spawn(async move {
+                let Some(oauth_provider) = oauth::get_provider(&pid) else {
+                    *callbacks.error.lock().unwrap() = Some(format!("OAuth failed: no OAuth provider registered for {}", pid));
+                    return;
                 };
                 match oauth_provider.login(&*callbacks).await {
                     Ok(creds) => {
+                        // Best effort: without it the account keeps its generated label.
+                        callbacks.on_progress("Looking up the account...");
+                        let identity = oauth_provider.fetch_identity(&creds).await.unwrap_or_default();
                         let cred = Credential::OAuth(zeroai::auth::OAuthCredential {
                             refresh: creds.refresh,
                             access: creds.access,
                             expires: creds.expires,
                             extra: creds.extra,
                         });
-                        if is_add {
-                            let _ = config_mgr.add_account(&pid, None, cred);
+                        let saved = if is_add {
+                            config_mgr.add_account(&pid, None, cred)
                         } else {
-                            let _ = config_mgr.set_credential(&pid, cred);
+                            config_mgr.set_credential(&pid, cred).and_then(|()| {
+                                let accounts = config_mgr.list_accounts(&pid)?;
+                                accounts.first().map(|a| a.id.clone()).ok_or_else(|| anyhow::anyhow!("no account saved"))
+                            })
+                        };
+                        let saved = saved.and_then(|id| config_mgr.set_account_identity(&pid, &id, &identity));
+                        if let Err(e) = saved {
+                            *callbacks.error.lock().unwrap() = Some(format!("OAuth failed: could not save credential: {}", e));
                         }
                     }
                     Err(e) => {
-                        // Store error in the callbacks progress field for display
-                        let mut progress = callbacks._progress.lock().unwrap();
-                        *progress = format!("OAuth failed: {}", e);
+                        *callbacks.error.lock().unwrap() = Some(format!("OAuth failed: {}", e));
                     }
                 }
             });
@@ -849,17 +1399,510 @@ async fn handle_provider_select(
                 cursor_pos: 0,
                 initial_account_count,
                 oauth_error: None,
+                masked: false,
+                revealed: false,
+                key_error: None,
+                unverified_input: None,
+                key_check: None,
+                oauth_task: Some(OAuthTask(task)),
+                waiting_for_code: false,
             });
         }
     }
     Ok(())
 }
 
-async fn enter_model_selection(config: &ConfigManager, provider_id: &str, screen: &mut Screen) -> anyhow::Result<()> {
+/// Field navigation and text editing shared by the form screens.
+fn edit_form(state: &mut FormState, code: KeyCode) {
+    let field_count = state.fields.len();
+    match code {
+        KeyCode::Tab | KeyCode::Down => {
+            state.focus_field((state.focus + 1) % field_count);
+        }
+        KeyCode::BackTab | KeyCode::Up => {
+            state.focus_field((state.focus + field_count - 1) % field_count);
+        }
+        KeyCode::Char(c) => {
+            state.error = None;
+            state.fields[state.focus].insert(state.cursor_pos, c);
+            state.cursor_pos += 1;
+        }
+        KeyCode::Backspace => {
+            state.error = None;
+            if state.cursor_pos > 0 {
+                state.cursor_pos -= 1;
+                state.fields[state.focus].remove(state.cursor_pos);
+            }
+        }
+        KeyCode::Delete if state.cursor_pos < state.fields[state.focus].len() => {
+            state.fields[state.focus].remove(state.cursor_pos);
+        }
+        KeyCode::Left if state.cursor_pos > 0 => {
+            state.cursor_pos -= 1;
+        }
+        KeyCode::Right if state.cursor_pos < state.fields[state.focus].len() => {
+            state.cursor_pos += 1;
+        }
+        KeyCode::Home => {
+            state.cursor_pos = 0;
+        }
+        KeyCode::End => {
+            state.cursor_pos = state.fields[state.focus].len();
+        }
+        _ => {}
+    }
+}
+
+fn select_prev(state: &mut ListState, len: usize) {
+    let i = state.selected().unwrap_or(0);
+    state.select(Some(if i == 0 { len.saturating_sub(1) } else { i - 1 }));
+}
+
+fn select_next(state: &mut ListState, len: usize) {
+    let i = state.selected().unwrap_or(0);
+    state.select(Some(if i + 1 >= len { 0 } else { i + 1 }));
+}
+
+/// Case-insensitive subsequence match ("orr" matches "OpenRouter").
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|q| chars.any(|c| c == q))
+}
+
+/// Insert pasted text at the cursor of the current text input. Line breaks and other
+/// control characters are dropped (keys copied from web pages often end with a newline).
+fn paste_into(screen: &mut Screen, text: &str) {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    let (input, cursor_pos) = match screen {
+        Screen::AuthInput(s) => (&mut s.input, &mut s.cursor_pos),
+        Screen::ModelsUrlInput(s) => {
+            s.auth_error = None;
+            (&mut s.input, &mut s.cursor_pos)
+        }
+        Screen::AccountLabelInput(s) => (&mut s.input, &mut s.cursor_pos),
+        Screen::CustomProviderInput(s) | Screen::Settings(s) => {
+            s.error = None;
+            (&mut s.fields[s.focus], &mut s.cursor_pos)
+        }
+        _ => return,
+    };
+    input.insert_str(*cursor_pos, &text);
+    *cursor_pos += text.len();
+}
+
+/// Masked form of a secret: bullets, keeping the last 4 characters of keys long
+/// enough that this doesn't give most of it away.
+fn mask_secret(secret: &str) -> String {
+    let len = secret.chars().count();
+    let shown = if len > 8 { 4 } else { 0 };
+    secret
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i + shown < len { '•' } else { c })
+        .collect()
+}
+
+/// Outcome of probing a newly entered API key.
+enum KeyCheck {
+    Passed,
+    /// No cheap probe for this provider.
+    Skipped,
+    /// `rejected`: the provider answered 401/403, so the key itself is wrong.
+    Failed { message: String, rejected: bool },
+}
+
+impl KeyCheck {
+    fn from_result<T>(result: Result<T, ProviderError>) -> Self {
+        match result {
+            Ok(_) => KeyCheck::Passed,
+            Err(e) => KeyCheck::Failed {
+                rejected: matches!(e, ProviderError::Http { status: 401 | 403, .. }),
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Test `key` by listing the provider's models, before it is saved.
+async fn check_api_key(config: &ConfigManager, provider_id: &str, key: &str) -> KeyCheck {
+    if let Some(def) = config.get_provider_defs().unwrap_or_default().into_iter().find(|d| d.id == provider_id) {
+        return KeyCheck::from_result(def.to_provider().list_models(key).await);
+    }
+    if !supports_dynamic_models(provider_id) {
+        let Some(request) = static_key_probe(provider_id, key) else {
+            return KeyCheck::Skipped;
+        };
+        let result = match request.send().await {
+            Ok(resp) if resp.status().is_success() => Ok(()),
+            Ok(resp) => Err(api_error::from_response(resp).await),
+            Err(e) => Err(e.into()),
+        };
+        return KeyCheck::from_result(result);
+    }
+    let models_url = config.get_models_url(provider_id).ok().flatten();
+    match fetch_models_for_provider(provider_id, Some(key), models_url.as_deref()).await {
+        Ok(_) => KeyCheck::Passed,
+        Err(e) => KeyCheck::Failed {
+            rejected: matches!(e.status, Some(401 | 403)),
+            message: e.to_string(),
+        },
+    }
+}
+
+/// A cheap authenticated request for providers whose model list is static, where
+/// listing models doesn't touch the API.
+fn static_key_probe(provider_id: &str, key: &str) -> Option<reqwest::RequestBuilder> {
+    let client = reqwest::Client::new();
+    match provider_id {
+        "anthropic" => Some(
+            client
+                .get("https://api.anthropic.com/v1/models?limit=1")
+                .header("x-api-key", key)
+                .header("anthropic-version", "2023-06-01"),
+        ),
+        "google" => Some(
+            client
+                .get("https://generativelanguage.googleapis.com/v1beta/models?pageSize=1")
+                .header("x-goog-api-key", key),
+        ),
+        _ => None,
+    }
+}
+
+/// Store an entered API key or setup-token and go on to the provider's models.
+async fn save_key(
+    config: &ConfigManager,
+    screen: &mut Screen,
+    provider_id: &str,
+    input: String,
+    is_setup: bool,
+    is_add: bool,
+) -> anyhow::Result<()> {
+    let cred = if is_setup {
+        Credential::SetupToken(SetupTokenCredential { token: input })
+    } else {
+        Credential::ApiKey(ApiKeyCredential { key: input })
+    };
+
+    if is_add {
+        config.add_account(provider_id, None, cred)?;
+    } else {
+        config.set_credential(provider_id, cred)?;
+    }
+
+    if is_custom_provider(provider_id) {
+        let base_url = provider_id.strip_prefix("custom:").unwrap_or("").trim().trim_end_matches('/');
+        let input_url = config.get_models_url(provider_id).ok().flatten().unwrap_or_default();
+        let cursor_pos = input_url.len();
+        *screen = Screen::ModelsUrlInput(ModelsUrlInputState {
+            provider_id: provider_id.to_string(),
+            base_url: base_url.to_string(),
+            input: input_url,
+            cursor_pos,
+            auth_error: None,
+        });
+    } else {
+        enter_model_selection(config, provider_id, screen, false).await?;
+    }
+    Ok(())
+}
+
+/// Build a provider definition from the "Add custom provider" form fields.
+fn build_provider_def(fields: &[String], is_builtin: impl Fn(&str) -> bool) -> Result<ProviderDef, String> {
+    let id = fields[0].trim();
+    if id.is_empty() || id.contains('/') || id.contains(char::is_whitespace) {
+        return Err("Name must be non-empty and contain no spaces or '/'".into());
+    }
+    if is_custom_provider(id) || is_builtin(id) {
+        return Err(format!("'{}' is a built-in provider name", id));
+    }
+    let base_url = fields[1].trim().trim_end_matches('/');
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        return Err("Base URL must start with http:// or https://".into());
+    }
+    let models_url = Some(fields[3].trim().to_string()).filter(|u| !u.is_empty());
+    Ok(ProviderDef {
+        id: id.to_string(),
+        base_url: base_url.to_string(),
+        auth: parse_auth_style(&fields[2]),
+        api_key_env: None,
+        headers: parse_headers(&fields[4])?,
+        models_url,
+        models: Vec::new(),
+        embeddings: Default::default(),
+        completions: false,
+    })
+}
+
+/// `bearer` (or blank), `x-api-key`, or `Header-Name: prefix` for a custom header.
+fn parse_auth_style(input: &str) -> AuthStyle {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("bearer") {
+        return AuthStyle::Bearer;
+    }
+    if input.eq_ignore_ascii_case("x-api-key") {
+        return AuthStyle::XApiKey;
+    }
+    let (header, prefix) = input.split_once(':').unwrap_or((input, ""));
+    let prefix = prefix.trim();
+    let value_prefix = if prefix.is_empty() || prefix.contains('{') {
+        prefix.to_string()
+    } else {
+        format!("{} ", prefix)
+    };
+    AuthStyle::Custom {
+        header: header.trim().to_string(),
+        value_prefix,
+    }
+}
+
+/// `Name: value; Other: value`.
+fn parse_headers(input: &str) -> Result<HashMap<String, String>, String> {
+    input
+        .split(';')
+        .filter(|h| !h.trim().is_empty())
+        .map(|h| match h.split_once(':') {
+            Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.trim().to_string())),
+            _ => Err(format!("Invalid header '{}' (expected Name: value)", h.trim())),
+        })
+        .collect()
+}
+
+/// Settings form values, in `SETTINGS_FIELDS` order.
+fn settings_form_values(settings: &ProxySettings) -> Vec<String> {
+    let mut aliases: Vec<String> = settings.model_aliases.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    aliases.sort();
+    let mut transformers: Vec<String> = settings.transformers.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    transformers.sort();
+    vec![
+        settings.host.clone().unwrap_or_default(),
+        settings.port.map(|p| p.to_string()).unwrap_or_default(),
+        settings.retry.as_ref().map(|r| r.max_retries.to_string()).unwrap_or_default(),
+        settings.retry.as_ref().map(|r| r.base_backoff_ms.to_string()).unwrap_or_default(),
+        settings.log_level.clone().unwrap_or_default(),
+        settings.daily_budget_usd.map(|b| b.to_string()).unwrap_or_default(),
+        aliases.join("; "),
+        if settings.expose_bare_model_ids { "yes".into() } else { String::new() },
+        settings.bare_model_priority.join(", "),
+        settings.compression.as_ref().map(|c| c.model.clone()).unwrap_or_default(),
+        settings.compression.as_ref().map(|c| c.tool_output_tokens.to_string()).unwrap_or_default(),
+        settings.compression.as_ref().and_then(|c| c.history_tokens).map(|t| t.to_string()).unwrap_or_default(),
+        settings.compression.as_ref().map(|c| c.keep_recent.to_string()).unwrap_or_default(),
+        transformers.join("; "),
+        settings.idempotency_ttl_secs.map(|t| t.to_string()).unwrap_or_default(),
+        settings.shadow.as_ref().map(|s| s.model.clone()).unwrap_or_default(),
+        settings.shadow.as_ref().map(|s| s.percent.to_string()).unwrap_or_default(),
+        settings.shadow.as_ref().map(|s| s.models.join(", ")).unwrap_or_default(),
+        settings.context_guard.as_ref().map(|g| g.max_percent.to_string()).unwrap_or_default(),
+        match &settings.context_guard {
+            Some(g) if g.truncate => "truncate".into(),
+            _ => String::new(),
+        },
+        match &settings.notifications {
+            Some(n) if n.desktop => "yes".into(),
+            _ => String::new(),
+        },
+        settings.notifications.as_ref().map(|n| n.webhooks.join(", ")).unwrap_or_default(),
+        settings
+            .notifications
+            .as_ref()
+            .filter(|n| n.budget_alert_percent != [80.0, 100.0])
+            .map(|n| n.budget_alert_percent.iter().map(f64::to_string).collect::<Vec<_>>().join(", "))
+            .unwrap_or_default(),
+        settings.journal_days.map(|d| d.to_string()).unwrap_or_default(),
+    ]
+}
+
+fn parse_settings(fields: &[String]) -> Result<ProxySettings, String> {
+    fn optional<T: std::str::FromStr>(value: &str, name: &str) -> Result<Option<T>, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        value.parse().map(Some).map_err(|_| format!("Invalid {}: {}", name, value))
+    }
+    let non_empty = |v: &str| Some(v.trim().to_string()).filter(|v| !v.is_empty());
+
+    let max_retries: Option<u32> = optional(&fields[2], "max retries")?;
+    let backoff: Option<u64> = optional(&fields[3], "retry backoff")?;
+    let retry = match (max_retries, backoff) {
+        (None, None) => None,
+        (max_retries, backoff) => {
+            let default = RetryConfig::default();
+            Some(RetryConfig {
+                max_retries: max_retries.unwrap_or(default.max_retries),
+                base_backoff_ms: backoff.unwrap_or(default.base_backoff_ms),
+                ..default
+            })
+        }
+    };
+    let daily_budget_usd: Option<f64> = optional(&fields[5], "daily budget")?;
+    if daily_budget_usd.is_some_and(|b| !b.is_finite() || b < 0.0) {
+        return Err("Daily budget must be a positive amount".into());
+    }
+
+    let mut model_aliases = HashMap::new();
+    for pair in fields[6].split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((alias, target)) = pair.split_once('=') else {
+            return Err(format!("Invalid alias \"{}\" (expected alias=provider/model)", pair));
+        };
+        let (alias, target) = (alias.trim(), target.trim());
+        if alias.is_empty() || split_model_id(target).is_none() {
+            return Err(format!("Invalid alias \"{}\" (expected alias=provider/model)", pair));
+        }
+        model_aliases.insert(alias.to_string(), target.to_string());
+    }
+
+    let expose_bare_model_ids = match fields[7].trim().to_ascii_lowercase().as_str() {
+        "" | "no" | "n" | "false" | "off" => false,
+        "yes" | "y" | "true" | "on" => true,
+        other => return Err(format!("Invalid bare model IDs setting: {} (expected yes or no)", other)),
+    };
+    let bare_model_priority = fields[8]
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect();
+
+    let tool_output_tokens: Option<u64> = optional(&fields[10], "tool output limit")?;
+    let history_tokens: Option<u64> = optional(&fields[11], "history limit")?;
+    let keep_recent: Option<usize> = optional(&fields[12], "keep recent")?;
+    let compression = match non_empty(&fields[9]) {
+        None => None,
+        Some(model) if split_model_id(&model).is_none() => {
+            return Err(format!("Invalid compression model \"{}\" (expected provider/model)", model));
+        }
+        Some(model) => {
+            let defaults = CompressionConfig::new(model);
+            Some(CompressionConfig {
+                tool_output_tokens: tool_output_tokens.unwrap_or(defaults.tool_output_tokens),
+                history_tokens,
+                keep_recent: keep_recent.unwrap_or(defaults.keep_recent),
+                ..defaults
+            })
+        }
+    };
+
+    let mut transformers = HashMap::new();
+    for pair in fields[13].split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        match pair.split_once('=').map(|(p, path)| (p.trim(), path.trim())) {
+            Some((provider, path)) if !provider.is_empty() && !path.is_empty() => {
+                transformers.insert(provider.to_string(), path.to_string());
+            }
+            _ => return Err(format!("Invalid transformer \"{}\" (expected provider=path)", pair)),
+        }
+    }
+
+    let shadow_percent: Option<f64> = optional(&fields[16], "shadow percent")?;
+    if shadow_percent.is_some_and(|p| !(0.0..=100.0).contains(&p)) {
+        return Err("Shadow percent must be between 0 and 100".into());
+    }
+    let shadow_models: Vec<String> = fields[17]
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(String::from)
+        .collect();
+    if let Some(model) = shadow_models.iter().find(|m| split_model_id(m).is_none()) {
+        return Err(format!("Invalid model \"{}\" in shadow for (expected provider/model)", model));
+    }
+    let shadow = match non_empty(&fields[15]) {
+        None => None,
+        Some(model) if split_model_id(&model).is_none() => {
+            return Err(format!("Invalid shadow model \"{}\" (expected provider/model)", model));
+        }
+        Some(model) => Some(ShadowConfig {
+            model,
+            percent: shadow_percent.unwrap_or(10.0),
+            models: shadow_models,
+        }),
+    };
+
+    let truncate = match fields[19].trim().to_ascii_lowercase().as_str() {
+        "" | "reject" => false,
+        "truncate" => true,
+        other => return Err(format!("Invalid over-the-limit setting: {} (expected reject or truncate)", other)),
+    };
+    let context_guard = match optional::<f64>(&fields[18], "context limit")? {
+        None => None,
+        Some(p) if !(p > 0.0 && p <= 100.0) => return Err("Context limit must be between 0 and 100 percent".into()),
+        Some(max_percent) => Some(ContextGuard { max_percent, truncate }),
+    };
+
+    let desktop = match fields[20].trim().to_ascii_lowercase().as_str() {
+        "" | "no" | "n" | "false" | "off" => false,
+        "yes" | "y" | "true" | "on" => true,
+        other => return Err(format!("Invalid desktop alerts setting: {} (expected yes or no)", other)),
+    };
+    let webhooks: Vec<String> = fields[21]
+        .split(',')
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .map(String::from)
+        .collect();
+    if let Some(url) = webhooks.iter().find(|u| !u.starts_with("http://") && !u.starts_with("https://")) {
+        return Err(format!("Invalid alert webhook \"{}\" (expected an http(s) URL)", url));
+    }
+    let mut budget_alert_percent = Vec::new();
+    for p in fields[22].split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match p.trim_end_matches('%').parse::<f64>() {
+            Ok(p) if p > 0.0 && p.is_finite() => budget_alert_percent.push(p),
+            _ => return Err(format!("Invalid budget alert \"{}\" (expected a percentage)", p)),
+        }
+    }
+    let notifications = (desktop || !webhooks.is_empty()).then(|| NotificationConfig {
+        desktop,
+        webhooks,
+        budget_alert_percent: if budget_alert_percent.is_empty() { vec![80.0, 100.0] } else { budget_alert_percent },
+    });
+
+    Ok(ProxySettings {
+        host: non_empty(&fields[0]),
+        port: optional(&fields[1], "port")?,
+        retry,
+        log_level: non_empty(&fields[4]),
+        daily_budget_usd,
+        model_aliases,
+        expose_bare_model_ids,
+        bare_model_priority,
+        compression,
+        transformers,
+        idempotency_ttl_secs: optional(&fields[14], "idempotency TTL")?,
+        shadow,
+        experiments: Vec::new(),
+        context_guard,
+        notifications,
+        event_webhooks: Vec::new(),
+        journal_days: optional(&fields[23], "journal days")?,
+        concurrency: HashMap::new(),
+        warm_connections: None,
+        ollama_keep_alive: None,
+    })
+}
+
+/// Open the model list for a provider. The list comes from the model cache unless `refresh`.
+async fn enter_model_selection(
+    config: &ConfigManager,
+    provider_id: &str,
+    screen: &mut Screen,
+    refresh: bool,
+) -> anyhow::Result<()> {
     let api_key = config.resolve_api_key(provider_id).await.ok().flatten();
     let models_url = config.get_models_url(provider_id).ok().flatten();
-    let models = match fetch_models_for_provider(provider_id, api_key.as_deref(), models_url.as_deref()).await {
-        Ok(list) => list.into_iter().map(|m| m.id).collect::<Vec<_>>(),
+    let provider_def = config.get_provider_defs().unwrap_or_default().into_iter().find(|d| d.id == provider_id);
+    let fetched = match &provider_def {
+        Some(def) => def.fetch_models(api_key.as_deref()).await.map_err(|e| e.to_string()),
+        None => fetch_models_cached(provider_id, api_key.as_deref(), models_url.as_deref(), refresh)
+            .await
+            .map_err(|e| e.to_string()),
+    };
+    let models = match fetched {
+        Ok(list) => list,
         Err(e) => {
             let _enabled = config.get_enabled_models().unwrap_or_default();
             let ls = ListState::default();
@@ -867,18 +1910,29 @@ async fn enter_model_selection(config: &ConfigManager, provider_id: &str, screen
                 provider_id: provider_id.to_string(),
                 models: Vec::new(),
                 list_state: ls,
-                error: Some(e.to_string()),
+                error: Some(e),
             });
             return Ok(());
         }
     };
     let enabled = config.get_enabled_models().unwrap_or_default();
-    let model_items: Vec<(String, bool)> = models
+    let pricing = config.get_pricing_overrides().unwrap_or_default();
+    let overrides = config.get_model_overrides().unwrap_or_default();
+    let probes = Store::open_default().and_then(|store| store.capability_probes()).unwrap_or_default();
+    let model_items: Vec<ModelItem> = models
         .into_iter()
-        .map(|m| {
-            let full_id = format!("{}/{}", provider_id, m);
-            let selected = enabled.contains(&full_id);
-            (full_id, selected)
+        .map(|mut def| {
+            let full_id = format!("{}/{}", provider_id, def.id);
+            if let Some(cost) = pricing.get(&full_id) {
+                def.cost = cost.clone();
+            }
+            ModelOverride::apply_all(&overrides, &full_id, &mut def);
+            ModelItem {
+                selected: enabled.contains(&full_id),
+                probe: probes.iter().find(|p| p.model == full_id).cloned(),
+                full_id,
+                def,
+            }
         })
         .collect();
     let mut ls = ListState::default();
@@ -895,7 +1949,7 @@ async fn enter_model_selection(config: &ConfigManager, provider_id: &str, screen
 }
 
 fn save_models(config: &ConfigManager, state: &ModelSelectState) -> anyhow::Result<()> {
-    let selected: Vec<String> = state.models.iter().filter(|(_, s)| *s).map(|(id, _)| id.clone()).collect();
+    let selected: Vec<String> = state.models.iter().filter(|m| m.selected).map(|m| m.full_id.clone()).collect();
     let mut all_enabled = config.get_enabled_models().unwrap_or_default();
     all_enabled.retain(|m| !m.starts_with(&format!("{}/", state.provider_id)));
     all_enabled.extend(selected);
@@ -903,10 +1957,185 @@ fn save_models(config: &ConfigManager, state: &ModelSelectState) -> anyhow::Resu
     Ok(())
 }
 
+/// Context window, reasoning, input modalities and price, shown after a model ID.
+fn model_meta_spans(def: &ModelDef) -> Vec<Span<'static>> {
+    let gray = Style::default().fg(COLOR_GRAY);
+    let modalities: String = [
+        (InputModality::Text, '✎'),
+        (InputModality::Image, '▣'),
+        (InputModality::Audio, '♪'),
+    ]
+    .iter()
+    .map(|(m, icon)| if def.input.contains(m) { *icon } else { ' ' })
+    .collect();
+    let price = if def.cost.input == 0.0 && def.cost.output == 0.0 {
+        Span::styled("  price n/a", gray)
+    } else {
+        Span::styled(
+            format!("  ${:.2} in / ${:.2} out per 1M", def.cost.input, def.cost.output),
+            Style::default().fg(COLOR_YELLOW),
+        )
+    };
+    vec![
+        Span::styled(format!("  {: >5} ctx", format_token_count(def.context_window)), gray),
+        Span::styled(format!("  {}", modalities), Style::default().fg(COLOR_CYAN)),
+        if def.reasoning {
+            Span::styled("  reasoning", Style::default().fg(COLOR_GREEN))
+        } else {
+            Span::styled("           ", gray)
+        },
+        price,
+    ]
+}
+
+/// Probe results as `probed: stream ✓ tools ✗ vision –`, shown after the model metadata.
+fn probe_spans(probe: &CapabilityProbe) -> Vec<Span<'static>> {
+    let gray = Style::default().fg(COLOR_GRAY);
+    let mut spans = vec![Span::styled("  probed:", gray)];
+    for (name, flag) in [("stream", probe.streaming), ("tools", probe.tools), ("vision", probe.vision)] {
+        spans.push(match flag {
+            Some(true) => Span::styled(format!(" {} ✓", name), Style::default().fg(COLOR_GREEN)),
+            Some(false) => Span::styled(format!(" {} ✗", name), Style::default().fg(Color::Red)),
+            None => Span::styled(format!(" {} –", name), gray),
+        });
+    }
+    spans
+}
+
+/// Last use, 24h usage, premium quota, rate-limit cooldown and OAuth expiry, shown under an account.
+fn account_detail_spans(acc: &Account, usage: Option<&AccountUsage>, now_ms: i64) -> Vec<Span<'static>> {
+    let gray = Style::default().fg(COLOR_GRAY);
+    let mut parts = vec![Span::raw("     ")];
+    match usage.and_then(|u| u.last_used_ms) {
+        Some(t) => parts.push(Span::styled(format!("last used {} ago", format_duration_ms(now_ms - t)), gray)),
+        None => parts.push(Span::styled("never used", gray)),
+    }
+    if let Some(u) = usage {
+        let totals = u.rolling_totals(now_ms);
+        if totals.requests > 0 {
+            parts.push(Span::styled(
+                format!(
+                    " · 24h: {} req, {} in / {} out",
+                    totals.requests,
+                    format_token_count(totals.input_tokens),
+                    format_token_count(totals.output_tokens)
+                ),
+                gray,
+            ));
+        }
+    }
+    if let Some(quota) = usage.and_then(|u| u.quota.as_ref())
+        && let (Some(used), Some(entitlement)) = (quota.used(), quota.entitlement)
+    {
+        let style = if quota.is_exhausted_at(now_ms) { Style::default().fg(COLOR_YELLOW) } else { gray };
+        let renews = quota
+            .resets_at_ms
+            .filter(|t| *t > now_ms)
+            .map(|t| format!(", renews in {}", format_duration_ms(t - now_ms)))
+            .unwrap_or_default();
+        parts.push(Span::styled(format!(" · premium {}/{}{}", used, entitlement, renews), style));
+    }
+    if let Some(until) = acc.unhealthy_until_ms.filter(|u| *u > now_ms) {
+        parts.push(Span::styled(
+            format!(" · cooldown {}", format_duration_ms(until - now_ms)),
+            Style::default().fg(COLOR_YELLOW),
+        ));
+    }
+    if let Credential::OAuth(oauth) = &acc.credential {
+        parts.push(if oauth.expires > now_ms {
+            Span::styled(format!(" · token expires in {}", format_duration_ms(oauth.expires - now_ms)), gray)
+        } else {
+            Span::styled(" · token expired (refreshed on next use)", Style::default().fg(COLOR_YELLOW))
+        });
+    }
+    parts
+}
+
+/// Current frame of the progress spinner (advances every 100ms).
+fn spinner_frame() -> char {
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    let ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    FRAMES[(ms / 100) as usize % FRAMES.len()]
+}
+
+/// 45s, 4m12s, 2h05m, 3d04h.
+fn format_duration_ms(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h{:02}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d{:02}h", s / 86400, (s % 86400) / 3600),
+    }
+}
+
+/// 850 -> "850", 200000 -> "200K", 1048576 -> "1M".
+fn format_token_count(n: u64) -> String {
+    if n < 1000 {
+        n.to_string()
+    } else if n >= 1_000_000 {
+        let m = format!("{:.1}", n as f64 / 1_000_000.0);
+        format!("{}M", m.trim_end_matches(".0"))
+    } else {
+        format!("{}K", n / 1000)
+    }
+}
+
+fn draw_form(f: &mut Frame, area: Rect, title: &str, state: &FormState) {
+    let mut constraints = vec![Constraint::Length(3); state.fields.len()];
+    constraints.push(Constraint::Min(3));
+    let chunks = Layout::vertical(constraints).split(area);
+    for (i, ((label, hint), value)) in state.spec.iter().zip(&state.fields).enumerate() {
+        let focused = i == state.focus;
+        let title_style = if focused {
+            Style::default().fg(COLOR_YELLOW).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let line = if focused {
+            let (before, after) = value.split_at(state.cursor_pos);
+            Line::from(vec![
+                Span::raw(before),
+                Span::styled(" ", Style::default().bg(COLOR_CYAN)),
+                Span::raw(after),
+            ])
+        } else if value.is_empty() {
+            Line::from(Span::styled(*hint, Style::default().fg(COLOR_GRAY)))
+        } else {
+            Line::from(value.as_str())
+        };
+        let field_title = Span::styled(format!(" {} ", label), title_style);
+        f.render_widget(Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(field_title)), chunks[i]);
+    }
+    let mut info = vec![
+        Line::from(vec![
+            Span::raw(format!(" {} (", title)),
+            Span::styled("Tab/↑↓", Style::default().fg(COLOR_YELLOW)),
+            Span::raw(" field, "),
+            Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
+            Span::raw(" save, "),
+            Span::styled("Esc", Style::default().fg(COLOR_YELLOW)),
+            Span::raw(" cancel)"),
+        ]),
+        Line::from(Span::styled(
+            format!(" {}", state.spec[state.focus].1),
+            Style::default().fg(COLOR_GRAY),
+        )),
+    ];
+    if let Some(err) = &state.error {
+        info.push(Line::from(Span::styled(err.as_str(), Style::default().fg(Color::Red))));
+    }
+    f.render_widget(Paragraph::new(info).wrap(Wrap { trim: false }), chunks[state.fields.len()]);
+}
+
 fn draw(
     f: &mut Frame,
     config: &ConfigManager,
     groups: &[(String, Vec<ProviderAuthInfo>)],
+    filter: &GroupFilter,
     screen: &Screen,
     group_state: &mut ListState,
     sub_state: &mut ListState,
@@ -914,43 +2143,103 @@ fn draw(
     let area = f.area();
     match screen {
         Screen::ProviderGroups => {
-            let items: Vec<ListItem> = groups.iter().map(|(label, providers)| {
-                let has_any_cred = providers.iter().any(|p| config.has_credential(&p.provider_id).unwrap_or(false));
+            let visible = filter.visible(config, groups);
+            let down = config.providers_in_maintenance(chrono::Utc::now().timestamp_millis()).unwrap_or_default();
+            let items: Vec<ListItem> = visible.iter().enumerate().map(|(n, &idx)| {
+                let (label, providers) = &groups[idx];
+                let configured: Vec<_> =
+                    providers.iter().filter(|p| config.has_credential(&p.provider_id).unwrap_or(false)).collect();
+                let has_any_cred = !configured.is_empty();
+                // Greyed out while every configured provider of the group is in maintenance.
+                let in_maintenance = has_any_cred && configured.iter().all(|p| down.contains_key(&p.provider_id));
                 let marker = if has_any_cred { "●" } else { "○" };
-                let color = if has_any_cred { COLOR_GREEN } else { Color::White };
-                
-                ListItem::new(Line::from(vec![
+                let color = if in_maintenance { COLOR_GRAY } else if has_any_cred { COLOR_GREEN } else { Color::White };
+                let label_style = if in_maintenance {
+                    Style::default().fg(COLOR_GRAY)
+                } else {
+                    Style::default().add_modifier(Modifier::BOLD)
+                };
+
+                let jump = if n < 9 { format!("{}", n + 1) } else { " ".to_string() };
+
+                let mut spans = vec![
+                    Span::styled(format!(" {}", jump), Style::default().fg(COLOR_GRAY)),
                     Span::styled(format!(" {} ", marker), Style::default().fg(color)),
-                    Span::styled(format!("{: <15}", label), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(format!("{: <15}", label), label_style),
                     Span::raw(" - "),
                     Span::styled(providers[0].hint.as_str(), Style::default().fg(COLOR_GRAY)),
-                ]))
+                ];
+                if in_maintenance {
+                    spans.push(Span::styled("  [maintenance]", Style::default().fg(COLOR_YELLOW)));
+                }
+                ListItem::new(Line::from(spans))
             }).collect();
             
             let title = Line::from(vec![
                 Span::raw(" Providers ("),
                 Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" select, "),
+                Span::styled("/", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" search, "),
+                Span::styled("c", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" configured only, "),
+                Span::styled("n", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" new custom provider, "),
+                Span::styled("i", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" import logins, "),
+                Span::styled("d", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" local servers, "),
+                Span::styled("s", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" settings, "),
                 Span::styled("q", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" quit) "),
             ]);
-            
+
+            let mut filter_spans = Vec::new();
+            if filter.searching || !filter.query.is_empty() {
+                filter_spans.push(Span::raw(" Search: "));
+                filter_spans.push(Span::styled(filter.query.clone(), Style::default().fg(COLOR_CYAN)));
+                if filter.searching {
+                    filter_spans.push(Span::styled("_", Style::default().fg(COLOR_CYAN)));
+                }
+            }
+            if filter.configured_only {
+                filter_spans.push(Span::styled(" [configured only]", Style::default().fg(COLOR_GREEN)));
+            }
+            if filter.is_active() {
+                filter_spans.push(Span::styled(
+                    format!(" {}/{} (Esc clear) ", visible.len(), groups.len()),
+                    Style::default().fg(COLOR_GRAY),
+                ));
+            }
+            let mut block = Block::default().title(title).borders(Borders::ALL);
+            if !filter_spans.is_empty() {
+                block = block.title_bottom(Line::from(filter_spans));
+            }
+
             let list = List::new(items)
-                .block(Block::default().title(title).borders(Borders::ALL))
+                .block(block)
                 .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
             f.render_stateful_widget(list, area, group_state);
         }
         Screen::SubProviders(group_idx) => {
             let (group_label, providers) = &groups[*group_idx];
+            let down = config.providers_in_maintenance(chrono::Utc::now().timestamp_millis()).unwrap_or_default();
             let items: Vec<ListItem> = providers.iter().map(|p| {
                 let has_cred = config.has_credential(&p.provider_id).unwrap_or(false);
+                let maintenance = down.get(&p.provider_id);
                 let marker = if has_cred { "●" } else { "○" };
-                let color = if has_cred { COLOR_GREEN } else { Color::White };
-                
-                ListItem::new(Line::from(vec![
-                    Span::styled(format!(" {} ", marker), Style::default().fg(color)),
-                    Span::styled(format!("{: <25}", p.label), Style::default().add_modifier(Modifier::BOLD)),
-                ]))
+                let color = if maintenance.is_some() { COLOR_GRAY } else if has_cred { COLOR_GREEN } else { Color::White };
+
+                let mut spans = vec![Span::styled(format!(" {} ", marker), Style::default().fg(color))];
+                match maintenance {
+                    Some(m) => {
+                        spans.push(Span::styled(format!("{: <25}", p.label), Style::default().fg(COLOR_GRAY)));
+                        spans.push(Span::styled(format!("  [maintenance: {}]", m.describe()), Style::default().fg(COLOR_YELLOW)));
+                    }
+                    None => spans.push(Span::styled(format!("{: <25}", p.label), Style::default().add_modifier(Modifier::BOLD))),
+                }
+                ListItem::new(Line::from(spans))
             }).collect();
             
             let title = Line::from(vec![
@@ -967,20 +2256,52 @@ fn draw(
             f.render_stateful_widget(list, area, sub_state);
         }
         Screen::AccountList(state) => {
+            let now = chrono::Utc::now().timestamp_millis();
             let items: Vec<ListItem> = state.accounts.iter().enumerate().map(|(i, acc)| {
                 let marker = if i == 0 { "★" } else { " " };
-                let now = chrono::Utc::now().timestamp_millis();
                 let color = if acc.is_healthy_at(now) { COLOR_GREEN } else { Color::Red };
 
                 let id_prefix = acc.id.chars().take(8).collect::<String>();
-                ListItem::new(Line::from(vec![
+                let mut spans = vec![
                     Span::styled(format!(" {} ", marker), Style::default().fg(COLOR_YELLOW)),
                     Span::styled(acc.display_label(), Style::default().fg(color).add_modifier(Modifier::BOLD)),
-                    Span::raw(" - "),
-                    Span::styled(format!("ID: {}", id_prefix), Style::default().fg(COLOR_GRAY)),
-                ]))
+                ];
+                // The login's email when the label doesn't already show it, and its organization.
+                let identity: Vec<&str> = [acc.email.as_deref().filter(|e| acc.label.as_deref() != Some(*e)), acc.org.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                if !identity.is_empty() {
+                    spans.push(Span::styled(format!(" ({})", identity.join(", ")), Style::default().fg(COLOR_CYAN)));
+                }
+                spans.push(Span::raw(" - "));
+                spans.push(Span::styled(format!("ID: {}", id_prefix), Style::default().fg(COLOR_GRAY)));
+                if acc.needs_relogin() {
+                    spans.push(Span::styled(
+                        "  ⚠ token refresh failed, re-login required",
+                        Style::default().fg(Color::Red),
+                    ));
+                }
+                if !acc.enabled {
+                    let text = match &acc.disabled_reason {
+                        Some(reason) => format!("  ⊘ disabled: {}", reason),
+                        None => "  ⊘ disabled".to_string(),
+                    };
+                    spans.push(Span::styled(text, Style::default().fg(Color::Red)));
+                }
+                if let Some(schedule) = &acc.schedule {
+                    let (text, color) = if acc.enabled && !acc.is_available_at(now) {
+                        (format!("  ⏱ {} (off now)", schedule), COLOR_YELLOW)
+                    } else {
+                        (format!("  ⏱ {}", schedule), COLOR_GRAY)
+                    };
+                    spans.push(Span::styled(text, Style::default().fg(color)));
+                }
+                let details = Line::from(account_detail_spans(acc, state.usage.get(&acc.id), now));
+                ListItem::new(vec![Line::from(spans), details])
             }).collect();
 
+            let maintenance = config.providers_in_maintenance(now).unwrap_or_default().remove(&state.provider_id);
             let title = Line::from(vec![
                 Span::raw(format!(" {} Accounts (", state.provider_label)),
                 Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
@@ -991,16 +2312,36 @@ fn draw(
                 Span::raw(" label, "),
                 Span::styled("d", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" del, "),
+                Span::styled("r", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" refresh token, "),
+                Span::styled("x", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" on/off, "),
+                Span::styled("m", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" maintenance, "),
                 Span::styled("K/J", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" move) "),
             ]);
+            let mut block = Block::default().title(title).borders(Borders::ALL);
+            if let Some(m) = &maintenance {
+                block = block.title_bottom(Line::from(Span::styled(
+                    format!(" In maintenance: {}; routing skips this provider ", m.describe()),
+                    Style::default().fg(COLOR_YELLOW),
+                )));
+            }
 
             let list = List::new(items)
-                .block(Block::default().title(title).borders(Borders::ALL))
+                .block(block)
                 .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
             
             let mut ls = state.list_state.clone();
-            f.render_stateful_widget(list, area, &mut ls);
+            if let Some(status) = &state.status {
+                let chunks = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).split(area);
+                f.render_stateful_widget(list, chunks[0], &mut ls);
+                let color = if status.starts_with('✓') { COLOR_GREEN } else { Color::Red };
+                f.render_widget(Paragraph::new(status.as_str()).style(Style::default().fg(color)), chunks[1]);
+            } else {
+                f.render_stateful_widget(list, area, &mut ls);
+            }
         }
         Screen::AccountLabelInput(state) => {
             let chunks = Layout::vertical([Constraint::Length(3), Constraint::Min(2)]).split(area);
@@ -1023,7 +2364,7 @@ fn draw(
         }
         Screen::AuthInput(state) => {
             let has_info = !state.hint.is_empty() || state.oauth_url.is_some();
-            let has_error = state.oauth_error.is_some();
+            let has_error = state.oauth_error.is_some() || state.key_error.is_some() || state.key_check.is_some();
             let mut constraints = vec![
                 Constraint::Length(3),
                 Constraint::Length(3),
@@ -1038,15 +2379,31 @@ fn draw(
 
             f.render_widget(Paragraph::new(state.label.as_str()).block(Block::default().borders(Borders::ALL)), chunks[0]);
 
-            let input_title = Line::from(vec![
+            let mut title_spans = vec![
                 Span::raw(" Input ("),
                 Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" confirm, "),
-                Span::styled("Esc", Style::default().fg(COLOR_YELLOW)),
-                Span::raw(" cancel) "),
-            ]);
+            ];
+            if state.masked {
+                title_spans.push(Span::styled("Ctrl+R", Style::default().fg(COLOR_YELLOW)));
+                title_spans.push(Span::raw(if state.revealed { " hide, " } else { " reveal, " }));
+            }
+            if state.is_oauth && state.oauth_error.is_some() {
+                title_spans.push(Span::styled("r", Style::default().fg(COLOR_YELLOW)));
+                title_spans.push(Span::raw(" retry, "));
+            }
+            title_spans.push(Span::styled("Esc", Style::default().fg(COLOR_YELLOW)));
+            title_spans.push(Span::raw(" cancel) "));
+            let input_title = Line::from(title_spans);
             // Display input with cursor visualization
-            let (before, after) = state.input.split_at(state.cursor_pos);
+            let shown = if state.masked && !state.revealed {
+                mask_secret(&state.input)
+            } else {
+                state.input.clone()
+            };
+            let cursor_chars = state.input[..state.cursor_pos].chars().count();
+            let split = shown.char_indices().nth(cursor_chars).map(|(i, _)| i).unwrap_or(shown.len());
+            let (before, after) = shown.split_at(split);
             let cursor_span = Span::styled(" ", Style::default().bg(COLOR_CYAN));
             let line = Line::from(vec![
                 Span::raw(before),
@@ -1056,20 +2413,37 @@ fn draw(
             f.render_widget(Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(input_title)), chunks[1]);
 
             // Display OAuth error if present
-            if let Some(err) = &state.oauth_error {
+            if let Some(err) = state.oauth_error.as_ref().or(state.key_error.as_ref()) {
                 let error_idx = if has_error { 2 } else { 1 };
                 f.render_widget(
                     Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red)),
                     chunks[error_idx],
                 );
+            } else if state.key_check.is_some() {
+                f.render_widget(
+                    Paragraph::new(format!("{} Testing the key...", spinner_frame())).style(Style::default().fg(COLOR_CYAN)),
+                    chunks[2],
+                );
             }
 
             if has_info {
                 let info_start_idx = if has_error { 3 } else { 2 };
                 if info_start_idx < chunks.len() {
+                    // Spinner while the login works in the background (not while it waits for a pasted code).
+                    let busy = state.oauth_error.is_none()
+                        && !state.waiting_for_code
+                        && state.oauth_task.as_ref().is_some_and(OAuthTask::is_running);
+                    let hint_line = if busy {
+                        Line::from(vec![
+                            Span::styled(format!("{} ", spinner_frame()), Style::default().fg(COLOR_CYAN)),
+                            Span::raw(state.hint.as_str()),
+                        ])
+                    } else {
+                        Line::from(state.hint.as_str())
+                    };
                     let mut info_content = vec![
                         Line::from(Span::styled("Instructions: ", Style::default().fg(COLOR_YELLOW))),
-                        Line::from(state.hint.as_str()),
+                        hint_line,
                     ];
 
                     if let Some(url) = &state.oauth_url {
@@ -1126,14 +2500,119 @@ fn draw(
                 );
             }
         }
+        Screen::CustomProviderInput(state) => draw_form(f, area, "Add custom provider", state),
+        Screen::Settings(state) => draw_form(f, area, "Proxy settings", state),
+        Screen::SniffReview(state) => {
+            let now = chrono::Utc::now().timestamp_millis();
+            let gray = Style::default().fg(COLOR_GRAY);
+            let items: Vec<ListItem> = state.items.iter().map(|item| {
+                let found = &item.found;
+                let (marker, style) = if item.imported {
+                    ("[-]", gray)
+                } else if item.selected {
+                    ("[x]", Style::default().fg(COLOR_GREEN))
+                } else {
+                    ("[ ]", Style::default().fg(Color::White))
+                };
+                let mut spans = vec![
+                    Span::styled(format!(" {} ", marker), style),
+                    Span::styled(format!("{: <16}", found.provider), style.add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" {}", found.identity.as_deref().unwrap_or("unknown account")), Style::default().fg(COLOR_CYAN)),
+                ];
+                match found.expires_ms {
+                    Some(exp) if exp > now => {
+                        spans.push(Span::styled(format!(" · token expires in {}", format_duration_ms(exp - now)), gray));
+                    }
+                    Some(_) => spans.push(Span::styled(" · token expired (refreshed on use)", Style::default().fg(COLOR_YELLOW))),
+                    None => {}
+                }
+                if item.imported {
+                    spans.push(Span::styled(" · already imported", gray));
+                }
+                let source = Line::from(Span::styled(format!("       from {}: {}", found.source, found.path.display()), gray));
+                ListItem::new(vec![Line::from(spans), source])
+            }).collect();
+            let title = Line::from(vec![
+                Span::raw(" Found logins ("),
+                Span::styled("Space", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" toggle, "),
+                Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" import ticked, "),
+                Span::styled("Esc", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" skip) "),
+            ]);
+            if items.is_empty() {
+                f.render_widget(
+                    Paragraph::new(" No Claude Code, Gemini CLI or Codex CLI logins found.")
+                        .style(gray)
+                        .block(Block::default().title(title).borders(Borders::ALL)),
+                    area,
+                );
+            } else {
+                let list = List::new(items)
+                    .block(Block::default().title(title).borders(Borders::ALL))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                f.render_stateful_widget(list, area, &mut state.list_state.clone());
+            }
+        }
+        Screen::LocalServers(state) => {
+            let gray = Style::default().fg(COLOR_GRAY);
+            let items: Vec<ListItem> = state.servers.iter().zip(&state.enabled).map(|(found, &enabled)| {
+                let (marker, style) = if enabled {
+                    ("[x]", Style::default().fg(COLOR_GREEN))
+                } else {
+                    ("[ ]", Style::default().fg(Color::White))
+                };
+                let mut spans = vec![
+                    Span::styled(format!(" {} ", marker), style),
+                    Span::styled(format!("{: <10}", found.server.label), style.add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" {}", found.base_url), Style::default().fg(COLOR_CYAN)),
+                    Span::styled(format!(" · {} model(s)", found.models.len()), gray),
+                ];
+                if enabled {
+                    spans.push(Span::styled(" · enabled", gray));
+                }
+                let models = Line::from(Span::styled(format!("       {}", found.models.join(", ")), gray));
+                ListItem::new(vec![Line::from(spans), models])
+            }).collect();
+            let title = Line::from(vec![
+                Span::raw(" Local servers ("),
+                Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" enable models, "),
+                Span::styled("r", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" rescan, "),
+                Span::styled("Esc", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" back) "),
+            ]);
+            if items.is_empty() {
+                let probed: Vec<String> = LOCAL_SERVERS.iter().map(|s| format!("{} :{}", s.label, s.port)).collect();
+                f.render_widget(
+                    Paragraph::new(format!(" No local servers running (probed {}).", probed.join(", ")))
+                        .style(gray)
+                        .block(Block::default().title(title).borders(Borders::ALL)),
+                    area,
+                );
+            } else {
+                let list = List::new(items)
+                    .block(Block::default().title(title).borders(Borders::ALL))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                f.render_stateful_widget(list, area, &mut state.list_state.clone());
+            }
+        }
         Screen::ModelSelect(state) => {
-            let items: Vec<ListItem> = state.models.iter().map(|(id, selected)| {
-                let (marker, style) = if *selected {
+            let id_width = state.models.iter().map(|m| m.full_id.chars().count()).max().unwrap_or(0);
+            let items: Vec<ListItem> = state.models.iter().map(|m| {
+                let (marker, style) = if m.selected {
                     ("[x]", Style::default().fg(COLOR_GREEN))
                 } else {
                     ("[ ]", Style::default().fg(Color::White))
                 };
-                ListItem::new(Span::styled(format!(" {} {}", marker, id), style))
+                let mut spans = vec![Span::styled(format!(" {} {: <width$}", marker, m.full_id, width = id_width), style)];
+                spans.extend(model_meta_spans(&m.def));
+                if let Some(probe) = &m.probe {
+                    spans.extend(probe_spans(probe));
+                }
+                ListItem::new(Line::from(spans))
             }).collect();
             let title = Line::from(vec![
                 Span::raw(" Models ("),
@@ -1141,6 +2620,8 @@ fn draw(
                 Span::raw(" toggle, "),
                 Span::styled("a", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" all, "),
+                Span::styled("r", Style::default().fg(COLOR_YELLOW)),
+                Span::raw(" refresh, "),
                 Span::styled("Enter", Style::default().fg(COLOR_YELLOW)),
                 Span::raw(" confirm) "),
             ]);
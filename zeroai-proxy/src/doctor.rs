@@ -1,16 +1,27 @@
 use zeroai::{
-    AiClient, ConfigManager, StreamEvent, RequestOptions,
+    AiClient, ConfigManager, Credential, StreamEvent, RequestOptions,
+    auth::{
+        discover::{LOCAL_SERVERS, discover_local_servers, enable_local_server},
+        usage::UsageStore,
+        validate::{Severity, validate_config},
+        verify::{KeyCheck, KeyStatus, check_key},
+    },
     models::{fetch_models_for_provider, is_custom_provider},
+    probe::probe_model,
     split_model_id,
+    store::Store,
     types::{
         ChatContext, ContentBlock, Message, ModelDef, TextContent, ToolDef, ToolResultMessage,
         UserMessage,
     },
 };
 use futures::StreamExt;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use rand::seq::IndexedRandom;
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::Arc;
 
 /// Run the doctor check.
 pub async fn run_doctor(model_filter: Option<&str>) -> anyhow::Result<()> {
@@ -143,6 +154,7 @@ pub async fn run_doctor(model_filter: Option<&str>) -> anyhow::Result<()> {
         }
 
         println!("\n📋 Checking {}...", full_id);
+        print_relogin_warnings(&config, provider);
 
         let stream_result = check_model(
             &client,
@@ -207,9 +219,21 @@ async fn check_model(
         temperature: Some(0.0),
         max_tokens: Some(1024),
         reasoning: None,
+        thinking_budget: None,
         api_key: Some(api_key.to_string()),
         extra_headers: None,
         retry_config: None,
+        attempt_budget: None,
+        web_search: false,
+        code_execution: false,
+        capture: None,
+        response_headers: None,
+        anthropic_betas: Default::default(),
+        user: None,
+        metadata: None,
+        store: None,
+        extra_body: None,
+        provider_extra: serde_json::Value::Null,
     };
 
     let mut stream = client.stream(full_id, &context, &options)?;
@@ -327,8 +351,13 @@ async fn check_model(
 }
 
 /// Validate credentials for all configured providers by calling /v1/models (or static list).
-pub async fn run_auth_check() -> anyhow::Result<()> {
+///
+/// Unless `skip_probes` is set, one enabled model per provider is also probed for working
+/// streaming, tool calling and image input (see [`zeroai::probe`]). The results are saved to
+/// the store, where the proxy and the config TUI pick them up.
+pub async fn run_auth_check(skip_probes: bool) -> anyhow::Result<()> {
     let config = ConfigManager::default_path();
+    let enabled_models = config.get_enabled_models()?;
     let providers = config.list_providers_with_credentials()?;
     if providers.is_empty() {
         println!("No providers with credentials. Run `ai-proxy config` first.");
@@ -337,10 +366,14 @@ pub async fn run_auth_check() -> anyhow::Result<()> {
     println!("Checking credentials for {} provider(s)...\n", providers.len());
     for provider in &providers {
         let api_key = config.resolve_api_key(provider).await.ok().flatten();
+        print_relogin_warnings(&config, provider);
         let models_url = config.get_models_url(provider).ok().flatten();
         match fetch_models_for_provider(provider, api_key.as_deref(), models_url.as_deref()).await {
             Ok(list) => {
                 println!("  ✅ {} ({} model(s))", provider, list.len());
+                if !skip_probes && let Some(key) = api_key.as_deref() {
+                    probe_provider(provider, key, &list, &enabled_models).await;
+                }
             }
             Err(e) => {
                 if e.is_auth_error() {
@@ -357,3 +390,213 @@ pub async fn run_auth_check() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+/// List the local model servers running on their default ports (see
+/// [`zeroai::auth::discover`]) with their models, and offer to enable each one that isn't yet.
+pub async fn run_discover() -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    println!("Looking for local model servers...\n");
+    let found = discover_local_servers().await;
+    if found.is_empty() {
+        let ports: Vec<String> = LOCAL_SERVERS.iter().map(|s| format!("{} ({})", s.label, s.port)).collect();
+        println!("None running. Probed {}.", ports.join(", "));
+        return Ok(());
+    }
+    for server in &found {
+        let enabled = server.is_enabled(&config.get_enabled_models()?);
+        println!(
+            "  ✅ {} at {}: {} model(s){}",
+            server.server.label,
+            server.base_url,
+            server.models.len(),
+            if enabled { " (enabled)" } else { "" }
+        );
+        for model in &server.models {
+            println!("     {}/{}", server.server.provider, model);
+        }
+        if enabled || server.models.is_empty() {
+            continue;
+        }
+        print!("     Enable them? [Y/n]: ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        BufReader::new(tokio::io::stdin()).read_line(&mut line).await?;
+        if matches!(line.trim().to_ascii_lowercase().as_str(), "" | "y" | "yes") {
+            let added = enable_local_server(&config, server)?;
+            println!("     Enabled {} model(s).", added);
+        }
+    }
+    Ok(())
+}
+
+/// Print the problems `zeroai::auth::validate` finds in the config, each with its fix. Fails
+/// when any of them is an error, so scripts can run it before `serve`.
+pub fn run_config_validate() -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    let issues = validate_config(&config.load()?);
+    if issues.is_empty() {
+        println!("✅ {} looks good.", config.path().display());
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}\n", issue);
+    }
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    println!("{} error(s), {} warning(s) in {}", errors, issues.len() - errors, config.path().display());
+    anyhow::ensure!(errors == 0, "config has {} error(s)", errors);
+    Ok(())
+}
+
+/// Print the request journal entries for `request_id` (the proxy's or the provider's ID).
+pub fn run_usage_lookup(request_id: &str) -> anyhow::Result<()> {
+    let entries = Store::open_default()?.journal_entries(request_id)?;
+    if entries.is_empty() {
+        println!(
+            "No journal entry for {}. Requests are kept for settings.journal_days (default 30) days.",
+            request_id
+        );
+        return Ok(());
+    }
+    for entry in entries {
+        let at = chrono::DateTime::from_timestamp_millis(entry.at_ms)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        println!("Request {}", entry.request_id);
+        println!("  time:          {}", at);
+        println!("  route:         {} (HTTP {})", entry.route, entry.status);
+        println!("  model:         {}", entry.model);
+        println!("  account:       {}", entry.account.as_deref().unwrap_or("-"));
+        println!("  tokens:        {} in, {} out", entry.input_tokens, entry.output_tokens);
+        println!("  request hash:  {}", entry.request_hash.as_deref().unwrap_or("-"));
+        println!("  upstream ID:   {}", entry.upstream_request_id.as_deref().unwrap_or("-"));
+        println!();
+    }
+    Ok(())
+}
+
+/// Check every account of `provider` with a minimal request and report invalid or expired
+/// keys, the remaining credit where the provider tells, and the spend the proxy recorded in
+/// the last 24h. With `disable`, accounts whose key was rejected are turned off; accounts this
+/// turned off come back on once they pass.
+pub async fn run_auth_verify(provider: &str, disable: bool) -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    let accounts = config.list_accounts(provider)?;
+    if accounts.is_empty() {
+        println!("No saved accounts for {}.", provider);
+        return Ok(());
+    }
+    let enabled_models = config.get_enabled_models()?;
+    let models_url = config.get_models_url(provider).ok().flatten();
+    let usage = Store::open_default().and_then(|store| UsageStore::new(Arc::new(store)).load()).unwrap_or_default();
+    let now_ms = chrono::Utc::now().timestamp_millis();
+
+    println!("Verifying {} account(s) for {}...\n", accounts.len(), provider);
+    let (mut valid, mut invalid, mut unknown) = (0, 0, 0);
+    for mut acc in accounts {
+        let label = acc.display_label();
+        let mut check = None;
+        if matches!(acc.credential, Credential::OAuth(_)) && acc.credential.is_expired() {
+            match config.refresh_account(provider, &acc.id).await {
+                Ok(()) => {
+                    if let Some(refreshed) = config.list_accounts(provider)?.into_iter().find(|a| a.id == acc.id) {
+                        acc = refreshed;
+                    }
+                }
+                Err(e) => {
+                    check = Some(KeyCheck { status: KeyStatus::Invalid(format!("token expired: {}", e)), quota: None });
+                }
+            }
+        }
+        let check = match (check, acc.credential.api_key()) {
+            (Some(check), _) => check,
+            (None, Some(key)) => check_key(provider, &key, models_url.as_deref(), &enabled_models).await,
+            (None, None) => KeyCheck { status: KeyStatus::Invalid("no key".into()), quota: None },
+        };
+
+        let spend = usage
+            .get(&format!("{}/{}", provider, acc.id))
+            .map(|u| u.rolling_totals(now_ms).cost_usd)
+            .filter(|cost| *cost > 0.0)
+            .map(|cost| format!("${:.2} spent via proxy in 24h", cost));
+        let details: Vec<String> = check.quota.into_iter().chain(spend).collect();
+        let details = if details.is_empty() { String::new() } else { format!(" ({})", details.join(", ")) };
+        match check.status {
+            KeyStatus::Valid => {
+                valid += 1;
+                println!("  ✅ {}{}", label, details);
+                if !acc.enabled && acc.disabled_reason.is_some() {
+                    config.set_account_enabled(provider, &acc.id, true, None)?;
+                    println!("     re-enabled");
+                }
+            }
+            KeyStatus::Invalid(reason) => {
+                invalid += 1;
+                println!("  ❌ {}: {}{}", label, reason, details);
+                if disable && acc.enabled {
+                    config.set_account_enabled(provider, &acc.id, false, Some(reason))?;
+                    println!("     disabled");
+                } else if !acc.enabled {
+                    println!("     (disabled)");
+                }
+            }
+            KeyStatus::Unknown(reason) => {
+                unknown += 1;
+                println!("  ⚠️  {}: could not check: {}{}", label, reason, details);
+            }
+        }
+    }
+
+    println!("\n{} valid, {} invalid, {} not checked.", valid, invalid, unknown);
+    if invalid > 0 && !disable {
+        println!("Run with --disable to stop using the rejected accounts.");
+    }
+    Ok(())
+}
+
+/// Probe the first enabled model of `provider` that its models list still offers, and save
+/// the result.
+async fn probe_provider(provider: &str, api_key: &str, list: &[ModelDef], enabled_models: &[String]) {
+    let Some((full_id, def)) = enabled_models.iter().find_map(|full_id| {
+        let (p, model_id) = split_model_id(full_id)?;
+        let def = list.iter().find(|m| p == provider && m.id == model_id)?;
+        Some((full_id.clone(), def.clone()))
+    }) else {
+        println!("     no enabled model to probe");
+        return;
+    };
+    let client = AiClient::builder().with_models(vec![(full_id.clone(), def)]).build();
+    let probe = probe_model(&client, &full_id, api_key, chrono::Utc::now().timestamp_millis()).await;
+    let flag = |v: Option<bool>| match v {
+        Some(true) => "✅",
+        Some(false) => "❌",
+        None => "—",
+    };
+    println!(
+        "     {}: streaming {}  tools {}  vision {}",
+        full_id,
+        flag(probe.streaming),
+        flag(probe.tools),
+        flag(probe.vision)
+    );
+    if let Some(e) = &probe.error {
+        println!("     {}", e);
+    }
+    if let Err(e) = Store::open_default().and_then(|store| store.put_capability_probe(&probe)) {
+        println!("     ⚠️  Failed to save probe result: {}", e);
+    }
+}
+
+/// Warn about accounts whose OAuth refresh has been failing.
+fn print_relogin_warnings(config: &ConfigManager, provider: &str) {
+    for acc in config.list_accounts(provider).unwrap_or_default() {
+        if acc.needs_relogin() {
+            println!(
+                "  ⚠️  {} account '{}': token refresh failed ({}). Run `zeroai-proxy auth login {}`.",
+                provider,
+                acc.display_label(),
+                acc.last_refresh_error.as_deref().unwrap_or("unknown error"),
+                provider
+            );
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! `Idempotency-Key` support.
+//!
+//! A non-streaming POST carrying an `Idempotency-Key` header has its successful response kept
+//! in the [`ResponseCache`] (backed by the shared store, so it survives restarts) for
+//! `settings.idempotency_ttl_secs` (24 hours by default). A repeat with the same key and the
+//! same request gets the kept response back, marked `idempotent-replayed: true`, instead of
+//! a second upstream call that would be billed again.
+//! Reusing a key for a different request is rejected with 422; a repeat that arrives while the
+//! first is still running gets 409. Failed responses are not kept, so the client can retry.
+//!
+//! Requests are compared by [`request_hash`], so whitespace and key order in the body don't
+//! matter. Keys are scoped to the client (its credential header and app, see [`client_key`]),
+//! so two clients that happen to pick the same key never see each other's responses.
+
+use crate::server::AppState;
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zeroai::store::{Store, StoredResponse, blocking};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Set on responses that are replays of a kept response.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long responses are kept when `settings.idempotency_ttl_secs` is unset.
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest idempotency key accepted.
+const MAX_KEY_LEN: usize = 255;
+
+/// Largest request or response body that is buffered for hashing and keeping.
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Responses kept per idempotency key.
+pub struct ResponseCache {
+    store: Arc<Store>,
+    /// Request hash per key whose first request is still running.
+    in_flight: Mutex<HashMap<String, String>>,
+}
+
+enum Lookup {
+    /// First use of the key; the caller must `release` it when done, after a `store` on success.
+    New,
+    Replay(Response),
+    InFlight,
+    Mismatch,
+}
+
+impl ResponseCache {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self {
+            store,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Look up `key`, claiming it for this request when it is new (or its response expired).
+    /// The key is claimed before the store is read, so a request that finished (stored its
+    /// response and released the key) in between is replayed rather than run again.
+    async fn lookup(&self, key: &str, request_hash: &str, now_ms: i64) -> Lookup {
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if let Some(hash) = in_flight.get(key) {
+                return if hash == request_hash { Lookup::InFlight } else { Lookup::Mismatch };
+            }
+            in_flight.insert(key.to_string(), request_hash.to_string());
+        }
+        let (store, k) = (self.store.clone(), key.to_string());
+        let kept = match blocking(move || store.response(&k, now_ms)).await {
+            Ok(kept) => kept,
+            Err(e) => {
+                tracing::warn!("Failed to read kept response: {}", e);
+                None
+            }
+        };
+        let Some(kept) = kept else {
+            return Lookup::New;
+        };
+        self.release(key);
+        if kept.request_hash != request_hash {
+            Lookup::Mismatch
+        } else {
+            Lookup::Replay(replay(kept))
+        }
+    }
+
+    async fn store(&self, key: &str, response: StoredResponse) {
+        let (store, key) = (self.store.clone(), key.to_string());
+        if let Err(e) = blocking(move || store.put_response(&key, &response)).await {
+            tracing::warn!("Failed to keep response for replay: {}", e);
+        }
+    }
+
+    /// Forget that the request holding `key` is running.
+    fn release(&self, key: &str) {
+        self.in_flight.lock().unwrap().remove(key);
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut resp = (status, stored.body).into_response();
+    let headers = resp.headers_mut();
+    headers.clear();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    resp
+}
+
+/// Releases a claimed key once its request is done, or abandoned (e.g. when the client disconnects).
+struct Claim<'a> {
+    cache: &'a ResponseCache,
+    key: &'a str,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.cache.release(self.key);
+    }
+}
+
+/// `key` scoped to the client sending it: prefixed with a SHA-256 (hex) of its credential
+/// (`Authorization` or `x-api-key`) and app (see [`crate::access_log::client_app`]).
+pub fn client_key(headers: &HeaderMap, key: &str) -> String {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    for part in [header(AUTHORIZATION.as_str()), header("x-api-key")] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(crate::access_log::client_app(headers).unwrap_or_default().as_bytes());
+    format!("{:x}:{}", hasher.finalize(), key)
+}
+
+/// SHA-256 (hex) of the route and the JSON body written with object keys in sorted order.
+pub fn request_hash(route: &str, body: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(route.as_bytes());
+    hasher.update([0]);
+    hash_value(&mut hasher, body);
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_value(hasher: &mut Sha256, value: &Value) {
+    match value {
+        Value::Object(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            hasher.update(b"{");
+            for name in names {
+                hasher.update(Value::from(name.as_str()).to_string().as_bytes());
+                hasher.update(b":");
+                hash_value(hasher, &fields[name]);
+                hasher.update(b",");
+            }
+            hasher.update(b"}");
+        }
+        Value::Array(items) => {
+            hasher.update(b"[");
+            for item in items {
+                hash_value(hasher, item);
+                hasher.update(b",");
+            }
+            hasher.update(b"]");
+        }
+        scalar => hasher.update(scalar.to_string().as_bytes()),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": {"message": message, "type": "idempotency_error"}}))).into_response()
+}
+
+/// Replay or record responses of requests with an `Idempotency-Key` header.
+pub async fn middleware(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let key = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(String::from);
+    let Some(key) = key.filter(|_| req.method() == Method::POST) else {
+        return next.run(req).await;
+    };
+    if key.len() > MAX_KEY_LEN {
+        return error(StatusCode::BAD_REQUEST, "Idempotency-Key is too long");
+    }
+    let key = client_key(req.headers(), &key);
+
+    let (parts, body) = req.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+    };
+    let body: Option<Value> = serde_json::from_slice(&bytes).ok();
+    let req = Request::from_parts(parts, Body::from(bytes));
+    // Streams aren't kept; malformed bodies are left to the handler to reject.
+    let Some(body) = body.filter(|b| b.get("stream") != Some(&Value::Bool(true))) else {
+        return next.run(req).await;
+    };
+
+    let cache = &state.responses;
+    let hash = request_hash(req.uri().path(), &body);
+    match cache.lookup(&key, &hash, chrono::Utc::now().timestamp_millis()).await {
+        Lookup::New => {}
+        Lookup::Replay(resp) => return resp,
+        Lookup::InFlight => {
+            return error(StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress");
+        }
+        Lookup::Mismatch => {
+            return error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request",
+            );
+        }
+    }
+    let _claim = Claim { cache, key: &key };
+
+    let resp = next.run(req).await;
+    if !resp.status().is_success() {
+        return resp;
+    }
+    let (parts, body) = resp.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return error(StatusCode::BAD_GATEWAY, "Response body could not be read");
+    };
+    let ttl = state
+        .config
+        .get_settings()
+        .ok()
+        .and_then(|s| s.idempotency_ttl_secs)
+        .map_or(DEFAULT_TTL, Duration::from_secs);
+    let headers = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    cache
+        .store(
+            &key,
+            StoredResponse {
+                request_hash: hash,
+                status: parts.status.as_u16(),
+                headers,
+                body: body.to_vec(),
+                expires_at_ms: chrono::Utc::now().timestamp_millis() + ttl.as_millis() as i64,
+            },
+        )
+        .await;
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_ignores_key_order_but_not_values() {
+        let a = json!({"model": "m", "messages": [{"role": "user", "content": "hi"}]});
+        let b: Value = serde_json::from_str(r#"{ "messages": [{"content": "hi", "role": "user"}], "model": "m" }"#).unwrap();
+        assert_eq!(request_hash("/v1/chat/completions", &a), request_hash("/v1/chat/completions", &b));
+        assert_ne!(request_hash("/v1/chat/completions", &a), request_hash("/v1/messages", &a));
+        let c = json!({"model": "m", "messages": [{"role": "user", "content": "hello"}]});
+        assert_ne!(request_hash("/v1/chat/completions", &a), request_hash("/v1/chat/completions", &c));
+    }
+
+    #[test]
+    fn keys_are_scoped_to_the_client() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut h = HeaderMap::new();
+            for (name, value) in pairs {
+                h.insert(*name, HeaderValue::from_static(value));
+            }
+            h
+        };
+        let alice = headers(&[("authorization", "Bearer alice"), ("x-zeroai-app", "agent")]);
+        assert_eq!(client_key(&alice, "k"), client_key(&alice.clone(), "k"));
+        assert_ne!(client_key(&alice, "k"), client_key(&alice, "k2"));
+        for other in [
+            headers(&[("authorization", "Bearer bob"), ("x-zeroai-app", "agent")]),
+            headers(&[("authorization", "Bearer alice"), ("x-zeroai-app", "other")]),
+            headers(&[("x-api-key", "alice"), ("x-zeroai-app", "agent")]),
+        ] {
+            assert_ne!(client_key(&alice, "k"), client_key(&other, "k"));
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_are_claimed_replayed_and_expire() {
+        let cache = ResponseCache::new(Arc::new(Store::open_in_memory().unwrap()));
+        let t0 = 1_700_000_000_000;
+        assert!(matches!(cache.lookup("k", "h1", t0).await, Lookup::New));
+        assert!(matches!(cache.lookup("k", "h1", t0).await, Lookup::InFlight));
+        assert!(matches!(cache.lookup("k", "h2", t0).await, Lookup::Mismatch));
+
+        cache
+            .store(
+                "k",
+                StoredResponse {
+                    request_hash: "h1".into(),
+                    status: 200,
+                    headers: vec![("content-type".into(), "application/json".into())],
+                    body: b"{}".to_vec(),
+                    expires_at_ms: t0 + 60_000,
+                },
+            )
+            .await;
+        cache.release("k");
+        let Lookup::Replay(resp) = cache.lookup("k", "h1", t0).await else { panic!("expected a replay") };
+        assert_eq!(resp.headers()[REPLAYED_HEADER], "true");
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        assert!(matches!(cache.lookup("k", "h2", t0).await, Lookup::Mismatch));
+        assert!(matches!(cache.lookup("k", "h2", t0 + 61_000).await, Lookup::New));
+
+        cache.release("k");
+        assert!(matches!(cache.lookup("k", "h1", t0 + 61_000).await, Lookup::New));
+    }
+}
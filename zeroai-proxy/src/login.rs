@@ -0,0 +1,400 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use std::io::Write;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use zeroai::{
+    auth::{self, ApiKeyCredential, AuthMethod, OAuthCredential, config::Account, sniff::{self, SniffSource}},
+    models::is_custom_provider, oauth, ConfigManager, Credential, OAuthAuthInfo, OAuthCallbacks, OAuthPrompt,
+};
+
+// ---------------------------------------------------------------------------
+// OAuth Callbacks for plain terminals
+// ---------------------------------------------------------------------------
+
+/// Line-oriented callbacks: print the URL and read the code from stdin.
+/// Works over SSH and in headless environments where the TUI is unavailable.
+struct TerminalOAuthCallbacks;
+
+#[async_trait]
+impl OAuthCallbacks for TerminalOAuthCallbacks {
+    fn on_auth(&self, info: OAuthAuthInfo) {
+        println!();
+        println!("Open this URL in a browser:");
+        println!();
+        println!("  {}", info.url);
+        println!();
+        if let Some(instructions) = info.instructions {
+            println!("{}", instructions);
+        }
+    }
+
+    async fn on_prompt(&self, prompt: OAuthPrompt) -> anyhow::Result<String> {
+        match &prompt.placeholder {
+            Some(p) => print!("{} ({}) ", prompt.message, p),
+            None => print!("{} ", prompt.message),
+        }
+        std::io::stdout().flush()?;
+
+        let mut line = String::new();
+        let n = BufReader::new(tokio::io::stdin()).read_line(&mut line).await?;
+        if n == 0 {
+            anyhow::bail!("stdin closed before a value was entered");
+        }
+        let value = line.trim().to_string();
+        if value.is_empty() {
+            anyhow::bail!("No value entered");
+        }
+        Ok(value)
+    }
+
+    fn on_progress(&self, message: &str) {
+        println!("{}", message);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// `auth login`
+// ---------------------------------------------------------------------------
+
+/// Run the OAuth login flow for `provider_id` without the TUI and persist
+/// the resulting credential. With `add`, the login is appended as a new
+/// account instead of replacing the active one.
+pub async fn run_login(provider_id: &str, add: bool, label: Option<String>) -> anyhow::Result<()> {
+    let Some(oauth_provider) = oauth::get_provider(provider_id) else {
+        let mut available: Vec<String> = oauth::registry().into_keys().collect();
+        available.sort();
+        anyhow::bail!(
+            "Provider '{}' does not support OAuth login. Available: {}",
+            provider_id,
+            available.join(", ")
+        );
+    };
+
+    println!("Logging in to {}...", oauth_provider.name());
+
+    let creds = oauth_provider.login(&TerminalOAuthCallbacks).await?;
+    let identity = match oauth_provider.fetch_identity(&creds).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            println!("Could not look up the account's email: {}", e);
+            Default::default()
+        }
+    };
+    let cred = Credential::OAuth(OAuthCredential {
+        refresh: creds.refresh,
+        access: creds.access,
+        expires: creds.expires,
+        extra: creds.extra,
+    });
+
+    let config = ConfigManager::default_path();
+    let has_existing = config.has_credential(provider_id).unwrap_or(false);
+    if add || label.is_some() || !has_existing {
+        let id = config.add_account(provider_id, label, cred)?;
+        config.set_account_identity(provider_id, &id, &identity)?;
+        let accounts = config.list_accounts(provider_id)?;
+        let saved = accounts.iter().find(|a| a.id == id);
+        println!(
+            "Saved account {} for {}.",
+            saved.map(|a| a.display_label()).unwrap_or(id),
+            provider_id
+        );
+    } else {
+        config.set_credential(provider_id, cred)?;
+        if let Some(first) = config.list_accounts(provider_id)?.first() {
+            config.set_account_identity(provider_id, &first.id, &identity)?;
+        }
+        println!("Updated credential for {}.", provider_id);
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// `auth import`
+// ---------------------------------------------------------------------------
+
+/// List the credentials found in other tools' files (from `source`, or all known tools),
+/// ask which to import, and add those as accounts. `yes` imports every new one without
+/// asking.
+pub async fn run_import(source: Option<SniffSource>, yes: bool) -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    let found = sniff::sniff_credentials(source);
+    if found.is_empty() {
+        match source {
+            Some(s) => println!("No {} credentials found.", s),
+            None => println!("No Claude Code, Gemini CLI or Codex CLI credentials found."),
+        }
+        return Ok(());
+    }
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut new = Vec::new();
+    println!("Found {} credential(s):", found.len());
+    for (i, f) in found.iter().enumerate() {
+        let imported = f.is_in(&config.list_accounts(&f.provider).unwrap_or_default());
+        let expiry = match f.expires_ms {
+            Some(exp) if exp > now_ms => format!(", token expires {}", format_time(exp)),
+            Some(_) => ", token expired".to_string(),
+            None => String::new(),
+        };
+        println!(
+            "  {}. {} - {}{}{}",
+            i + 1,
+            f.provider,
+            f.identity.as_deref().unwrap_or("unknown account"),
+            expiry,
+            if imported { " (already imported)" } else { "" }
+        );
+        println!("     from {}", f.path.display());
+        if !imported {
+            new.push(i);
+        }
+    }
+    if new.is_empty() {
+        println!("Nothing new to import.");
+        return Ok(());
+    }
+
+    let picked = if yes {
+        new
+    } else {
+        print!("Import which? (e.g. 1,3 / all / none) [all new]: ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        BufReader::new(tokio::io::stdin()).read_line(&mut line).await?;
+        parse_selection(&line, &new, found.len()).map_err(anyhow::Error::msg)?
+    };
+    for &i in &picked {
+        let f = &found[i];
+        config.add_account(&f.provider, Some(f.label()), f.credential.clone())?;
+        println!("Imported {} as account '{}'.", f.provider, f.label());
+    }
+    if picked.is_empty() {
+        println!("Nothing imported.");
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// `auth enable` / `auth disable` / `auth schedule`
+// ---------------------------------------------------------------------------
+
+/// Put the account of `provider_id` named by `account` (label, email or ID prefix) in or out
+/// of rotation.
+pub fn run_set_enabled(provider_id: &str, account: &str, enabled: bool) -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    let accounts = config.list_accounts(provider_id)?;
+    let acc = find_account(&accounts, account)?;
+    config.set_account_enabled(provider_id, &acc.id, enabled, None)?;
+    let live = accounts.iter().filter(|a| if a.id == acc.id { enabled } else { a.enabled }).count();
+    println!(
+        "{} {} account {} ({} of {} in rotation).",
+        if enabled { "Enabled" } else { "Disabled" },
+        provider_id,
+        acc.display_label(),
+        live,
+        accounts.len()
+    );
+    Ok(())
+}
+
+/// Set, clear (`clear`) or show (neither) the availability schedule of an account.
+pub fn run_schedule(provider_id: &str, account: &str, schedule: Option<&str>, clear: bool) -> anyhow::Result<()> {
+    let config = ConfigManager::default_path();
+    let accounts = config.list_accounts(provider_id)?;
+    let acc = find_account(&accounts, account)?;
+    if clear || schedule.is_some() {
+        config.set_account_schedule(provider_id, &acc.id, schedule.filter(|_| !clear))?;
+    }
+    let acc = config
+        .list_accounts(provider_id)?
+        .into_iter()
+        .find(|a| a.id == acc.id)
+        .ok_or_else(|| anyhow::anyhow!("account not found: {}", acc.id))?;
+    match &acc.schedule {
+        Some(schedule) => {
+            let now = if acc.is_available_at(chrono::Utc::now().timestamp_millis()) { "available" } else { "unavailable" };
+            println!("{} account {}: {} ({} now).", provider_id, acc.display_label(), schedule, now);
+        }
+        None => println!("{} account {}: no schedule, always available.", provider_id, acc.display_label()),
+    }
+    Ok(())
+}
+
+/// The one account whose label or email is `name`, or whose ID starts with it.
+fn find_account<'a>(accounts: &'a [Account], name: &str) -> anyhow::Result<&'a Account> {
+    let exact: Vec<&Account> = accounts
+        .iter()
+        .filter(|a| a.label.as_deref() == Some(name) || a.email.as_deref() == Some(name) || a.id == name)
+        .collect();
+    let matches = if exact.is_empty() {
+        accounts.iter().filter(|a| a.id.starts_with(name)).collect()
+    } else {
+        exact
+    };
+    match matches.as_slice() {
+        [acc] => Ok(acc),
+        [] => {
+            let names: Vec<String> = accounts.iter().map(|a| a.display_label()).collect();
+            anyhow::bail!("No account '{}'. Accounts: {}", name, names.join(", "))
+        }
+        _ => anyhow::bail!("'{}' matches {} accounts; use the account ID", name, matches.len()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// `auth import-keys`
+// ---------------------------------------------------------------------------
+
+/// Add an account for each API key listed in `file` ("-" reads stdin): one key per line,
+/// or `label,key` CSV rows. Keys the provider already has are skipped; clashing labels
+/// get a numeric suffix.
+pub fn run_import_keys(provider_id: &str, file: &Path) -> anyhow::Result<()> {
+    let takes_keys = is_custom_provider(provider_id)
+        || auth::all_provider_auth_info().iter().any(|p| {
+            p.provider_id == provider_id && p.auth_methods.iter().any(|m| matches!(m, AuthMethod::ApiKey { .. }))
+        });
+    if !takes_keys {
+        anyhow::bail!("Provider '{}' does not take API keys", provider_id);
+    }
+
+    let text = if file == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?
+    };
+    let entries = parse_key_list(&text).map_err(anyhow::Error::msg)?;
+    if entries.is_empty() {
+        println!("No keys found in {}.", file.display());
+        return Ok(());
+    }
+
+    let config = ConfigManager::default_path();
+    let mut existing: Vec<String> = config
+        .list_accounts(provider_id)?
+        .iter()
+        .filter_map(|a| a.credential.api_key())
+        .collect();
+    let (mut added, mut skipped) = (0, 0);
+    for (label, key) in entries {
+        if existing.contains(&key) {
+            skipped += 1;
+            continue;
+        }
+        let cred = Credential::ApiKey(ApiKeyCredential { key: key.clone() });
+        let id = config.add_account(provider_id, label, cred)?;
+        let saved = config.list_accounts(provider_id)?.into_iter().find(|a| a.id == id);
+        println!("Added account {}.", saved.map(|a| a.display_label()).unwrap_or(id));
+        existing.push(key);
+        added += 1;
+    }
+    println!("Imported {} key(s) for {}, skipped {} already configured.", added, provider_id, skipped);
+    Ok(())
+}
+
+/// `(label, key)` pairs from a key list: one key per line or `label,key` rows. Blank lines,
+/// `#` comments and a `label,key` header row are ignored.
+fn parse_key_list(text: &str) -> Result<Vec<(Option<String>, String)>, String> {
+    let unquote = |s: &str| s.trim().trim_matches('"').trim().to_string();
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (label, key) = match line.split_once(',') {
+            Some((label, key)) => (unquote(label), unquote(key)),
+            None => (String::new(), unquote(line)),
+        };
+        if label.eq_ignore_ascii_case("label") && key.eq_ignore_ascii_case("key") {
+            continue;
+        }
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("Line {}: expected a key or label,key", i + 1));
+        }
+        entries.push(((!label.is_empty()).then_some(label), key));
+    }
+    Ok(entries)
+}
+
+/// Indexes picked by an answer to the import prompt: blank or "all" for every `new` one,
+/// "none", or 1-based numbers separated by commas or spaces.
+fn parse_selection(input: &str, new: &[usize], count: usize) -> Result<Vec<usize>, String> {
+    match input.trim() {
+        "" | "all" => return Ok(new.to_vec()),
+        "none" => return Ok(Vec::new()),
+        _ => {}
+    }
+    let mut picked = Vec::new();
+    for part in input.split([',', ' ']).filter(|p| !p.trim().is_empty()) {
+        let n: usize = part.trim().parse().map_err(|_| format!("Not a number: {}", part.trim()))?;
+        if n == 0 || n > count {
+            return Err(format!("No credential numbered {}", n));
+        }
+        if new.contains(&(n - 1)) && !picked.contains(&(n - 1)) {
+            picked.push(n - 1);
+        }
+    }
+    Ok(picked)
+}
+
+fn format_time(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_accounts_by_label_email_or_id_prefix() {
+        let account = |id: &str, label: &str, email: Option<&str>| Account {
+            id: id.into(),
+            label: Some(label.into()),
+            credential: Credential::ApiKey(ApiKeyCredential { key: id.into() }),
+            unhealthy_until_ms: None,
+            last_rate_limited_ms: None,
+            refresh_failures: 0,
+            last_refresh_error: None,
+            email: email.map(String::from),
+            org: None,
+            enabled: true,
+            disabled_reason: None,
+            schedule: None,
+        };
+        let accounts = [
+            account("a1b2", "work", Some("alice@example.com")),
+            account("a1c3", "weekend", None),
+        ];
+        assert_eq!(find_account(&accounts, "weekend").unwrap().id, "a1c3");
+        assert_eq!(find_account(&accounts, "alice@example.com").unwrap().id, "a1b2");
+        assert_eq!(find_account(&accounts, "a1b").unwrap().id, "a1b2");
+        assert!(find_account(&accounts, "a1").is_err());
+        assert!(find_account(&accounts, "nope").is_err());
+    }
+
+    #[test]
+    fn parses_key_lists() {
+        let text = "# rotation pool\nlabel,key\nsk-one\n\n\"prod\", \"sk-two\"\n";
+        assert_eq!(
+            parse_key_list(text),
+            Ok(vec![(None, "sk-one".to_string()), (Some("prod".to_string()), "sk-two".to_string())])
+        );
+        assert!(parse_key_list("sk-one sk-two").is_err());
+    }
+
+    #[test]
+    fn parses_import_selection() {
+        let new = [0, 2];
+        assert_eq!(parse_selection("\n", &new, 3), Ok(vec![0, 2]));
+        assert_eq!(parse_selection("none", &new, 3), Ok(vec![]));
+        // Already imported ones are skipped.
+        assert_eq!(parse_selection("3, 2 3", &new, 3), Ok(vec![2]));
+        assert!(parse_selection("4", &new, 3).is_err());
+        assert!(parse_selection("x", &new, 3).is_err());
+    }
+}
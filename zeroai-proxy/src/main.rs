@@ -1,12 +1,34 @@
+mod access_log;
+#[cfg(feature = "tui")]
 mod config_tui;
 mod doctor;
+mod idempotency;
+mod login;
+mod notify;
+mod openapi;
 mod server;
+mod shadow;
+mod sse;
+#[cfg(test)]
+mod test_support;
+mod transform;
+mod webhooks;
 
 use clap::{Parser, Subcommand};
+use zeroai::ConfigManager;
+use zeroai::models::catalog;
+use std::path::PathBuf;
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 8787;
 
 #[derive(Parser)]
 #[command(name = "ai-proxy", version, about = "AI model proxy server")]
 struct Cli {
+    /// Config root holding config.json, the store and caches [env: ZEROAI_CONFIG_DIR, default: ~/.zeroai]
+    #[arg(long, global = true)]
+    config_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -15,52 +37,256 @@ struct Cli {
 enum Commands {
     /// Start the HTTP proxy server
     Serve {
-        /// Port to listen on
-        #[arg(short, long, default_value = "8787")]
-        port: u16,
+        /// Port to listen on [default: settings.port, then 8787]
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Host to bind to [default: settings.host, then 127.0.0.1]
+        #[arg(long)]
+        host: Option<String>,
 
-        /// Host to bind to
-        #[arg(long, default_value = "127.0.0.1")]
-        host: String,
+        /// Write each upstream request (auth redacted) and raw response to this directory
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        debug_capture: Option<PathBuf>,
+
+        /// Answer upstream calls from a --debug-capture directory instead of the network
+        #[arg(long, value_name = "DIR")]
+        replay: Option<PathBuf>,
     },
 
     /// Configure providers and models (TUI)
-    Config,
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+    },
 
-    /// Validate credentials for all configured providers (e.g. /v1/models)
-    AuthCheck,
+    /// Manage provider credentials without the TUI
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+
+    /// Manage model metadata
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommands,
+    },
+
+    /// Validate credentials for all configured providers (e.g. /v1/models) and probe model capabilities
+    AuthCheck {
+        /// Only check credentials; skip the streaming/tools/vision probe requests
+        #[arg(long)]
+        skip_probes: bool,
+    },
+
+    /// Look into recorded usage
+    Usage {
+        #[command(subcommand)]
+        command: UsageCommands,
+    },
 
     /// Check provider health
     Doctor {
         /// Specific model to check (format: <provider>/<model>)
         #[arg(short, long)]
         model: Option<String>,
+
+        /// Look for Ollama, vLLM, LM Studio and llama.cpp on localhost and offer to enable them
+        #[arg(long, conflicts_with = "model")]
+        discover: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Log in to an OAuth provider from the terminal (for SSH/headless use)
+    Login {
+        /// Provider ID (e.g. gemini-cli, antigravity, openai-codex, github-copilot, qwen-portal)
+        provider: String,
+
+        /// Add as a new account instead of replacing the active credential
+        #[arg(long)]
+        add: bool,
+
+        /// Label for the new account (implies --add)
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// Import logins found in other tools' credential files, after review
+    Import {
+        /// Only look at this tool's files: claude, gemini or codex [default: all]
+        #[arg(long)]
+        from: Option<zeroai::auth::sniff::SniffSource>,
+
+        /// Import every new credential found without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Put an account back in rotation
+    Enable {
+        /// Provider ID (e.g. openai, anthropic)
+        provider: String,
+
+        /// Account label, email or ID prefix
+        account: String,
+    },
+
+    /// Take an account out of rotation without deleting its credential
+    Disable {
+        /// Provider ID (e.g. openai, anthropic)
+        provider: String,
+
+        /// Account label, email or ID prefix
+        account: String,
+    },
+
+    /// Limit when an account is used, e.g. "Mon-Fri 18:00-09:00, Sat-Sun UTC+1"
+    Schedule {
+        /// Provider ID (e.g. openai, anthropic)
+        provider: String,
+
+        /// Account label, email or ID prefix
+        account: String,
+
+        /// Days and hour ranges, with an optional time zone (local, Europe/Berlin or UTC+H);
+        /// omit to show the current schedule
+        schedule: Option<String>,
+
+        /// Remove the schedule (always available)
+        #[arg(long, conflicts_with = "schedule")]
+        clear: bool,
+    },
+
+    /// Check each account's key with a minimal request and show remaining credit where known
+    Verify {
+        /// Provider ID (e.g. openai, openrouter, anthropic)
+        provider: String,
+
+        /// Disable accounts whose key is rejected (re-enabled once they pass again)
+        #[arg(long)]
+        disable: bool,
+    },
+
+    /// Add an account for each API key in a file (one key per line, or label,key CSV)
+    ImportKeys {
+        /// Provider ID (e.g. openai, anthropic, groq)
+        provider: String,
+
+        /// Key list file ("-" for stdin)
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check the config for models without credentials, unknown providers, stale models URLs
+    /// and overlapping aliases (also run at serve startup)
+    Validate,
+}
+
+#[derive(Subcommand)]
+enum UsageCommands {
+    /// Show what the request journal kept for a request: model, account, tokens, body hash and
+    /// the provider's request ID
+    Lookup {
+        /// The proxy's request ID (x-request-id) or the provider's
+        request_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsCommands {
+    /// Download the model metadata catalog (context windows, costs, modalities)
+    UpdateCatalog {
+        /// Catalog URL (models.dev format)
+        #[arg(long, default_value = catalog::DEFAULT_CATALOG_URL)]
+        url: String,
     },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    if let Some(dir) = &cli.config_dir {
+        zeroai::auth::config::set_config_dir(dir);
+    }
+
+    let settings = ConfigManager::default_path().get_settings().unwrap_or_default();
+    let default_filter = settings.log_level.clone().unwrap_or_else(|| "ai_proxy=info,access=info".into());
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "ai_proxy=info".into()),
+                .unwrap_or_else(|_| default_filter.into()),
         )
         .init();
 
-    let cli = Cli::parse();
-
     match cli.command {
-        Commands::Serve { port, host } => {
-            server::run_server(&host, port).await?;
+        Commands::Serve { port, host, debug_capture, replay } => {
+            let host = host.or(settings.host).unwrap_or_else(|| DEFAULT_HOST.to_string());
+            let port = port.or(settings.port).unwrap_or(DEFAULT_PORT);
+            let capture_mode = debug_capture
+                .map(server::CaptureMode::Record)
+                .or(replay.map(server::CaptureMode::Replay));
+            server::run_server(&host, port, capture_mode).await?;
         }
-        Commands::Config => {
+        Commands::Config { command: None } => {
+            #[cfg(feature = "tui")]
             config_tui::run_config_tui().await?;
+            #[cfg(not(feature = "tui"))]
+            anyhow::bail!("This build has no config TUI (built without the `tui` feature); use `auth login` and edit config.json");
+        }
+        Commands::Config { command: Some(ConfigCommands::Validate) } => {
+            doctor::run_config_validate()?;
+        }
+        Commands::Auth { command } => match command {
+            AuthCommands::Login { provider, add, label } => {
+                login::run_login(&provider, add, label).await?;
+            }
+            AuthCommands::Import { from, yes } => {
+                login::run_import(from, yes).await?;
+            }
+            AuthCommands::Enable { provider, account } => {
+                login::run_set_enabled(&provider, &account, true)?;
+            }
+            AuthCommands::Disable { provider, account } => {
+                login::run_set_enabled(&provider, &account, false)?;
+            }
+            AuthCommands::Schedule { provider, account, schedule, clear } => {
+                login::run_schedule(&provider, &account, schedule.as_deref(), clear)?;
+            }
+            AuthCommands::Verify { provider, disable } => {
+                doctor::run_auth_verify(&provider, disable).await?;
+            }
+            AuthCommands::ImportKeys { provider, file } => {
+                login::run_import_keys(&provider, &file)?;
+            }
+        },
+        Commands::Models { command } => match command {
+            ModelsCommands::UpdateCatalog { url } => {
+                let path = catalog::default_cache_path();
+                let updated = catalog::update_catalog(&url, &path).await?;
+                println!(
+                    "Saved {} models from {} providers to {}",
+                    updated.model_count(),
+                    updated.providers.len(),
+                    path.display()
+                );
+            }
+        },
+        Commands::AuthCheck { skip_probes } => {
+            doctor::run_auth_check(skip_probes).await?;
         }
-        Commands::AuthCheck => {
-            doctor::run_auth_check().await?;
+        Commands::Usage { command: UsageCommands::Lookup { request_id } } => {
+            doctor::run_usage_lookup(&request_id)?;
         }
-        Commands::Doctor { model } => {
-            doctor::run_doctor(model.as_deref()).await?;
+        Commands::Doctor { model, discover } => {
+            if discover {
+                doctor::run_discover().await?;
+            } else {
+                doctor::run_doctor(model.as_deref()).await?;
+            }
         }
     }
 
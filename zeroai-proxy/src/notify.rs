@@ -0,0 +1,247 @@
+//! Alerts that need a person: an OAuth login that stopped refreshing, a provider with every
+//! account rate limited, spend crossing a share of the daily budget.
+//!
+//! With `settings.notifications` set, each alert goes to a desktop notification and/or is
+//! POSTed to webhooks: Slack and Discord incoming webhooks get a chat message, other URLs a
+//! JSON object with the event and its details. The same alert isn't repeated within
+//! [`REPEAT_AFTER_MS`]; a budget alert comes again only after the spend has dropped back
+//! under its share.
+
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio_util::task::TaskTracker;
+use zeroai::ConfigManager;
+use zeroai::auth::config::NotificationConfig;
+
+/// Quiet time before the same alert (same event, provider and account) goes out again.
+const REPEAT_AFTER_MS: i64 = 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// An account's OAuth refresh failed after all retries; it needs a new login.
+    LoginExpired { provider: String, account: String, error: Option<String> },
+    /// Every account of a provider is resting after rate limits.
+    AllRateLimited { provider: String, until_ms: Option<i64> },
+    /// The rolling 24h spend reached `percent` of the daily budget.
+    Budget { percent: f64, spent_usd: f64, budget_usd: f64 },
+}
+
+impl Alert {
+    fn event(&self) -> &'static str {
+        match self {
+            Alert::LoginExpired { .. } => "login_expired",
+            Alert::AllRateLimited { .. } => "all_accounts_rate_limited",
+            Alert::Budget { .. } => "budget",
+        }
+    }
+
+    /// Identifies repeats of the same alert.
+    fn key(&self) -> String {
+        match self {
+            Alert::LoginExpired { provider, account, .. } => format!("login_expired/{}/{}", provider, account),
+            Alert::AllRateLimited { provider, .. } => format!("all_accounts_rate_limited/{}", provider),
+            Alert::Budget { percent, .. } => format!("budget/{}", percent),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Alert::LoginExpired { provider, .. } => format!("zeroai: {} login expired", provider),
+            Alert::AllRateLimited { provider, .. } => format!("zeroai: {} rate limited", provider),
+            Alert::Budget { percent, .. } => format!("zeroai: {}% of the daily budget used", percent),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Alert::LoginExpired { provider, account, error } => format!(
+                "The {} account '{}' could not refresh its login{}. Run `zeroai-proxy auth login {}`.",
+                provider,
+                account,
+                error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default(),
+                provider
+            ),
+            Alert::AllRateLimited { provider, until_ms } => format!(
+                "Every {} account is rate limited{}; its models fail until one recovers.",
+                provider,
+                until_ms
+                    .and_then(chrono::DateTime::from_timestamp_millis)
+                    .map(|t| format!(" until {}", t.format("%H:%M UTC")))
+                    .unwrap_or_default()
+            ),
+            Alert::Budget { percent, spent_usd, budget_usd } => format!(
+                "${:.2} spent in the last 24 hours, {}% of the ${:.2} daily budget{}.",
+                spent_usd,
+                percent,
+                budget_usd,
+                if *percent >= 100.0 { "; requests are rejected until spend drops" } else { "" }
+            ),
+        }
+    }
+
+    /// Webhook body for `url`: a chat message for Slack and Discord, else the event as JSON.
+    fn payload(&self, url: &str, at_ms: i64) -> Value {
+        let text = self.message();
+        if url.contains("hooks.slack.com") {
+            return json!({"text": format!("*{}*\n{}", self.title(), text)});
+        }
+        if url.contains("discord.com/api/webhooks") || url.contains("discordapp.com/api/webhooks") {
+            return json!({"content": format!("**{}**\n{}", self.title(), text)});
+        }
+        let mut body = json!({"event": self.event(), "title": self.title(), "message": text, "at_ms": at_ms});
+        let details = match self {
+            Alert::LoginExpired { provider, account, error } => {
+                json!({"provider": provider, "account": account, "error": error})
+            }
+            Alert::AllRateLimited { provider, until_ms } => json!({"provider": provider, "until_ms": until_ms}),
+            Alert::Budget { percent, spent_usd, budget_usd } => {
+                json!({"percent": percent, "spent_usd": spent_usd, "budget_usd": budget_usd})
+            }
+        };
+        if let (Some(body), Value::Object(details)) = (body.as_object_mut(), details) {
+            body.extend(details);
+        }
+        body
+    }
+}
+
+pub struct Notifier {
+    config: ConfigManager,
+    http: reqwest::Client,
+    /// Alert key -> when it last went out.
+    sent: Mutex<HashMap<String, i64>>,
+    /// Budget alert shares the spend is currently over.
+    budget_crossed: Mutex<Vec<f64>>,
+    /// Where webhook deliveries run.
+    tasks: TaskTracker,
+}
+
+impl Notifier {
+    pub fn new(config: ConfigManager, tasks: TaskTracker) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            sent: Mutex::new(HashMap::new()),
+            budget_crossed: Mutex::new(Vec::new()),
+            tasks,
+        }
+    }
+
+    /// Send `alert` if notifications are configured and it didn't go out recently.
+    pub fn send(&self, alert: Alert) {
+        let Some(config) = self.config.get_settings().ok().and_then(|s| s.notifications) else {
+            return;
+        };
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        {
+            let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+            if sent.get(&alert.key()).is_some_and(|&at| now_ms - at < REPEAT_AFTER_MS) {
+                return;
+            }
+            sent.insert(alert.key(), now_ms);
+        }
+        self.deliver(&config, &alert, now_ms);
+    }
+
+    /// Alert when the rolling 24h spend crosses one of the configured shares of the budget
+    /// (the highest one, if it jumped several).
+    pub fn check_budget(&self, spent_usd: f64, budget_usd: f64) {
+        let Some(config) = self.config.get_settings().ok().and_then(|s| s.notifications) else {
+            return;
+        };
+        let used_percent = if budget_usd > 0.0 { spent_usd / budget_usd * 100.0 } else { 100.0 };
+        let crossed = {
+            let mut crossed = self.budget_crossed.lock().unwrap_or_else(|e| e.into_inner());
+            newly_crossed(&config.budget_alert_percent, &mut crossed, used_percent)
+        };
+        if let Some(percent) = crossed {
+            let alert = Alert::Budget { percent, spent_usd, budget_usd };
+            self.deliver(&config, &alert, chrono::Utc::now().timestamp_millis());
+        }
+    }
+
+    fn deliver(&self, config: &NotificationConfig, alert: &Alert, at_ms: i64) {
+        tracing::warn!("{}: {}", alert.title(), alert.message());
+        if config.desktop {
+            show_desktop(&alert.title(), &alert.message());
+        }
+        for url in &config.webhooks {
+            let request = self.http.post(url).json(&alert.payload(url, at_ms));
+            let url = url.clone();
+            self.tasks.spawn(async move {
+                match request.send().await {
+                    Ok(resp) if !resp.status().is_success() => {
+                        tracing::warn!("Alert webhook {} answered {}", url, resp.status());
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Alert webhook {} failed: {}", url, e),
+                }
+            });
+        }
+    }
+}
+
+/// The highest threshold in `thresholds` that `used_percent` reached and `crossed` didn't
+/// hold yet. `crossed` keeps the thresholds reached so far; those the spend fell back under
+/// are dropped, so they fire again on the next crossing.
+fn newly_crossed(thresholds: &[f64], crossed: &mut Vec<f64>, used_percent: f64) -> Option<f64> {
+    crossed.retain(|&t| used_percent >= t);
+    let new: Vec<f64> = thresholds
+        .iter()
+        .copied()
+        .filter(|&t| used_percent >= t && !crossed.contains(&t))
+        .collect();
+    crossed.extend(&new);
+    new.into_iter().reduce(f64::max)
+}
+
+/// Show a desktop notification with `notify-send` (Linux) or `osascript` (macOS).
+fn show_desktop(title: &str, message: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut c = std::process::Command::new("osascript");
+        c.arg("-e")
+            .arg(format!("display notification \"{}\" with title \"{}\"", quote(message), quote(title)));
+        c
+    } else {
+        let mut c = std::process::Command::new("notify-send");
+        c.arg(title).arg(message);
+        c
+    };
+    if let Err(e) = command.spawn() {
+        tracing::debug!("Desktop notification failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_alerts_fire_once_per_crossing() {
+        let thresholds = [50.0, 80.0, 100.0];
+        let mut crossed = Vec::new();
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 40.0), None);
+        // Jumping past two shares at once alerts for the higher one.
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 85.0), Some(80.0));
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 90.0), None);
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 100.0), Some(100.0));
+        // Once old spend rolls out of the window, the shares are armed again.
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 60.0), None);
+        assert_eq!(newly_crossed(&thresholds, &mut crossed, 82.0), Some(80.0));
+    }
+
+    #[test]
+    fn webhook_payloads_follow_the_service() {
+        let alert = Alert::AllRateLimited { provider: "openai".into(), until_ms: None };
+        let slack = alert.payload("https://hooks.slack.com/services/T/B/x", 0);
+        assert_eq!(slack["text"], "*zeroai: openai rate limited*\nEvery openai account is rate limited; its models fail until one recovers.");
+        let discord = alert.payload("https://discord.com/api/webhooks/1/x", 0);
+        assert!(discord["content"].as_str().unwrap().starts_with("**zeroai: openai rate limited**"));
+        let generic = alert.payload("https://example.com/hook", 7);
+        assert_eq!(generic["event"], "all_accounts_rate_limited");
+        assert_eq!(generic["provider"], "openai");
+        assert_eq!(generic["at_ms"], 7);
+    }
+}
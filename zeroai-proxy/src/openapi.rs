@@ -0,0 +1,177 @@
+//! The OpenAPI 3.1 document served at `GET /openapi.json`. Routes and bodies come from the
+//! `#[utoipa::path]` and `ToSchema` annotations in `server`; the zeroai headers, which the
+//! middleware reads and sets for every route, are added here.
+
+use crate::access_log::{APP_HEADER, DEBUG_HEADER, REQUEST_ID_HEADER, SUMMARY_HEADER, VARIANT_HEADER};
+use crate::idempotency::{IDEMPOTENCY_KEY_HEADER, REPLAYED_HEADER};
+use crate::server::{self, EXTRA_HEADER, WARNING_HEADER};
+use axum::Json;
+use utoipa::openapi::path::{Operation, ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{Object, Type};
+use utoipa::openapi::{Header, RefOr, Required};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zeroai proxy",
+        description = "OpenAI, Anthropic and Cohere/Jina compatible endpoints over the configured providers.",
+    ),
+    paths(
+        server::list_models,
+        server::retrieve_model,
+        server::usage_report,
+        server::list_providers,
+        server::disable_provider,
+        server::enable_provider,
+        server::chat_completions,
+        server::anthropic_messages,
+        server::anthropic_count_tokens,
+        server::completions,
+        server::engine_completions,
+        server::embeddings,
+        server::rerank,
+    ),
+    modifiers(&ZeroaiHeaders),
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    let mut doc = ApiDoc::openapi();
+    // The crate declares no license; utoipa would fill in an empty one.
+    doc.info.license = None;
+    Json(doc)
+}
+
+/// Routes that complete a chat, and so may be served by an experiment variant, have
+/// messages dropped, and report a request summary.
+const CHAT_PATHS: &[&str] = &["/v1/chat/completions", "/v1/messages"];
+
+/// Served like the path they are paired with; the unprefixed routes are those of Copilot
+/// clients given a proxy URL.
+const ALIASES: &[(&str, &str)] = &[
+    ("/v2/rerank", "/v1/rerank"),
+    ("/chat/completions", "/v1/chat/completions"),
+    ("/models", "/v1/models"),
+];
+
+struct ZeroaiHeaders;
+
+impl Modify for ZeroaiHeaders {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            let chat = CHAT_PATHS.contains(&path.as_str());
+            if let Some(op) = item.get.as_mut() {
+                add_headers(op, path, chat, false);
+            }
+            if let Some(op) = item.post.as_mut() {
+                add_headers(op, path, chat, true);
+            }
+        }
+        for (alias, path) in ALIASES {
+            if let Some(item) = openapi.paths.paths.get(*path).cloned() {
+                openapi.paths.paths.insert(alias.to_string(), item);
+            }
+        }
+    }
+}
+
+fn add_headers(op: &mut Operation, path: &str, chat: bool, post: bool) {
+    let mut params = vec![
+        (REQUEST_ID_HEADER, "ID for the request in logs and upstream; generated when absent."),
+        (APP_HEADER, "Client application for per-app usage; defaults to the `user-agent` product."),
+    ];
+    if post {
+        params.push((IDEMPOTENCY_KEY_HEADER, "Replay the kept response of an earlier non-streaming request with this key."));
+    }
+    if chat {
+        params.push((DEBUG_HEADER, "`1` asks for a request summary: the `x-zeroai-summary` header, or a final `zeroai.summary` event."));
+    }
+    if path == "/v1/chat/completions" {
+        params.push((EXTRA_HEADER, "JSON object of extra fields for the upstream request body."));
+    }
+    let parameters = op.parameters.get_or_insert_with(Vec::new);
+    for (name, description) in params {
+        let param = ParameterBuilder::new()
+            .name(name)
+            .parameter_in(ParameterIn::Header)
+            .required(Required::False)
+            .description(Some(description))
+            .schema(Some(Object::with_type(Type::String)))
+            .build();
+        parameters.push(param);
+    }
+
+    let mut headers = vec![(REQUEST_ID_HEADER, "The request's ID.")];
+    if post {
+        headers.push((REPLAYED_HEADER, "`true` on a replay of a kept response."));
+    }
+    if chat {
+        headers.extend([
+            (VARIANT_HEADER, "`experiment/variant` that served the request."),
+            (SUMMARY_HEADER, "JSON request summary, when asked for with `x-zeroai-debug`."),
+            (WARNING_HEADER, "Request features dropped for the target model, and context trimming."),
+        ]);
+    }
+    for response in op.responses.responses.values_mut() {
+        let RefOr::T(response) = response else { continue };
+        for (name, description) in &headers {
+            let header = Header::builder()
+                .schema(Object::with_type(Type::String))
+                .description(Some(*description))
+                .build();
+            response.headers.insert(name.to_string(), header);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{FakeReply, FakeUpstream, TestProxy};
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn document_covers_every_route_and_extension() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let doc: Value = reqwest::get(format!("{}/openapi.json", proxy.base_url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3.1"));
+        let routes = [
+            ("/v1/models", "get"),
+            ("/v1/models/{model}", "get"),
+            ("/v1/usage", "get"),
+            ("/v1/providers", "get"),
+            ("/v1/providers/disable", "post"),
+            ("/v1/providers/enable", "post"),
+            ("/v1/chat/completions", "post"),
+            ("/v1/messages", "post"),
+            ("/v1/messages/count_tokens", "post"),
+            ("/v1/completions", "post"),
+            ("/v1/engines/{engine}/completions", "post"),
+            ("/v1/embeddings", "post"),
+            ("/v1/rerank", "post"),
+            ("/v2/rerank", "post"),
+            ("/chat/completions", "post"),
+            ("/models", "get"),
+        ];
+        for (path, method) in routes {
+            assert!(doc["paths"][path][method].is_object(), "{} {} is missing", method, path);
+        }
+        assert_eq!(doc["paths"].as_object().unwrap().len(), routes.len());
+
+        let chat = &doc["paths"]["/v1/chat/completions"]["post"];
+        let params: Vec<&str> = chat["parameters"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        for name in ["x-request-id", "x-zeroai-app", "idempotency-key", "x-zeroai-debug", "x-zeroai-extra"] {
+            assert!(params.contains(&name), "{} is missing", name);
+        }
+        assert!(chat["responses"]["200"]["headers"]["x-zeroai-warning"].is_object());
+        let schema = &doc["components"]["schemas"]["ChatCompletionRequest"];
+        assert!(schema["properties"]["transforms"].is_object());
+    }
+}
@@ -1,135 +1,731 @@
 use zeroai::{
-    AiClient, ConfigManager, StreamEvent, RequestOptions,
+    AiClient, ConfigManager, ContextCompressor,
+    auth::{config::{Account, ProviderMaintenance, ProxySettings, WarmConnections}, usage::{AccountUsage, RequestSource, UsageStore}, validate::{Severity, validate_config}}, ProviderRegistry, StreamEvent, RequestOptions,
     split_model_id,
-    providers::retry as retry_helpers,
+    routing::{self, CHEAPEST_MODEL_ID, FASTEST_MODEL_ID, ModelRequirements},
+    store::{AccountEvent, Store, blocking},
+    providers::{ResponseHeaders, capture::Capture, github_copilot, retry::{self as retry_helpers, AttemptBudget}},
     types::{
-        AssistantMessage, ChatContext, ContentBlock, Message, StopReason, TextContent,
+        AnthropicBetas, Api, AssistantMessage, ChatContext, Citation, CodeExecutionResult, CompletionRequest, ContentBlock, EmbeddingRequest, ExecutableCode, ImageContent, Message, ModelCost, ModelDef, RerankRequest,
+        StopReason, TextContent,
         ThinkingContent, ToolCall, ToolDef, ToolResultMessage, UserMessage,
     },
 };
 use axum::{
     Json, Router,
-    extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Response, Sse, sse::Event},
+    extract::{Extension, Path, State},
+    middleware,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
 };
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio_util::task::TaskTracker;
+use utoipa::ToSchema;
+use crate::access_log::{self, RequestLog};
+use crate::idempotency::{self, ResponseCache};
+use crate::notify::{Alert, Notifier};
+use crate::openapi;
+use crate::shadow;
+use crate::sse;
+use crate::transform::{Transformer, Transformers};
+use crate::webhooks::{self, Webhooks};
 
 // ---------------------------------------------------------------------------
 // App state
 // ---------------------------------------------------------------------------
 
+/// How long a rate-limited account is skipped when the provider doesn't say when to retry.
+const RATE_LIMIT_COOLDOWN_MS: u64 = 60_000;
+
 pub struct AppState {
     pub client: RwLock<AiClient>,
     pub config: ConfigManager,
+    /// Database behind `usage`, `responses` and account health history.
+    pub store: Arc<Store>,
+    /// Per-account usage shown in the config TUI.
+    pub usage: UsageStore,
+    /// Responses kept for `Idempotency-Key` replays.
+    pub responses: ResponseCache,
+    /// Summary cache for `settings.compression`.
+    compressor: ContextCompressor,
+    /// Compiled `settings.transformers` scripts.
+    transformers: Transformers,
+    /// `auto/fastest` requests routed so far; every [`routing::EXPLORE_EVERY`]th explores.
+    fastest_routed: AtomicU64,
+    /// `serve --debug-capture` / `--replay` mode.
+    capture_mode: Option<CaptureMode>,
+    /// Alerts per `settings.notifications`.
+    pub notifier: Arc<Notifier>,
+    /// Events for `settings.event_webhooks`.
+    pub webhooks: Arc<Webhooks>,
+    /// Work that outlives its request (webhook and alert deliveries, shadow calls), which
+    /// shutdown waits for.
+    pub background: TaskTracker,
+}
+
+/// What `serve` does with upstream traffic besides forwarding it.
+pub enum CaptureMode {
+    /// Write each upstream request and raw response to this directory.
+    Record(PathBuf),
+    /// Answer upstream calls from recordings in this directory instead of the network.
+    Replay(PathBuf),
 }
 
 impl AppState {
-    pub async fn new() -> anyhow::Result<Self> {
-        let config = ConfigManager::default_path();
-        let client = build_client(&config);
+    pub async fn new(capture_mode: Option<CaptureMode>) -> anyhow::Result<Self> {
+        Ok(Self::with_stores(
+            ConfigManager::default_path(),
+            Arc::new(Store::open_default()?),
+            capture_mode,
+        ))
+    }
 
-        Ok(Self {
+    /// State over the given config file and store.
+    pub fn with_stores(config: ConfigManager, store: Arc<Store>, capture_mode: Option<CaptureMode>) -> Self {
+        let client = build_client(&config, &store);
+        let background = TaskTracker::new();
+        let notifier = Arc::new(Notifier::new(config.clone(), background.clone()));
+        let webhooks = Arc::new(Webhooks::new(config.clone(), background.clone()));
+        Self {
             client: RwLock::new(client),
             config,
-        })
+            usage: UsageStore::new(store.clone()),
+            responses: ResponseCache::new(store.clone()),
+            store,
+            compressor: ContextCompressor::new(),
+            transformers: Transformers::new(),
+            fastest_routed: AtomicU64::new(0),
+            capture_mode,
+            notifier,
+            webhooks,
+            background,
+        }
+    }
+
+    /// The capture for one client request: a fresh recording named after its request ID,
+    /// or the shared replay.
+    fn capture(&self, request_id: &str) -> Option<Capture> {
+        match self.capture_mode.as_ref()? {
+            CaptureMode::Record(dir) => {
+                let request_id: String = request_id
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                    .collect();
+                let id = format!("{}-{}", chrono::Utc::now().format("%Y%m%dT%H%M%S"), request_id);
+                tracing::info!("Capturing upstream traffic to {}/{}-*", dir.display(), id);
+                Some(Capture::new(dir, id))
+            }
+            CaptureMode::Replay(dir) => Some(Capture::replay(dir)),
+        }
     }
 
     /// Rebuild the AiClient with fresh model data from config.
     pub async fn refresh_models(&self) {
-        let new_client = build_client(&self.config);
+        let new_client = build_client(&self.config, &self.store);
         *self.client.write().await = new_client;
     }
 
+    /// Record a completed request against the account that served it, the client app and
+    /// the end user.
+    pub(crate) async fn record_usage(
+        &self,
+        provider: &str,
+        account_id: &str,
+        source: RequestSource<'_>,
+        usage: Option<&zeroai::types::Usage>,
+        cost: &ModelCost,
+    ) {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let cost_usd = usage.map(|u| cost.usage_cost(u)).unwrap_or(0.0);
+        let (store, usage) = (self.usage.clone(), usage.cloned());
+        let (p, a) = (provider.to_string(), account_id.to_string());
+        let [app, user, variant] = [source.app, source.user, source.variant].map(|s| s.map(String::from));
+        let recorded = blocking(move || {
+            let source = RequestSource { app: app.as_deref(), user: user.as_deref(), variant: variant.as_deref() };
+            store.record(&p, &a, source, usage.as_ref(), cost_usd, now_ms)
+        });
+        if let Err(e) = recorded.await {
+            tracing::debug!("Failed to record usage for {}/{}: {}", provider, account_id, e);
+        }
+    }
+
+    /// Add a response's timing to the rolling latency of `model`, for `auto/fastest`.
+    pub(crate) async fn record_latency(&self, model: &str, usage: Option<&zeroai::types::Usage>) {
+        let Some(usage) = usage.cloned() else {
+            return;
+        };
+        let (store, model) = (self.usage.clone(), model.to_string());
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        if let Err(e) = blocking(move || store.record_latency(&model, &usage, now_ms)).await {
+            tracing::debug!("Failed to record latency: {}", e);
+        }
+    }
+
+    /// The model a routing pseudo-model (`auto/cheapest`, `auto/fastest`) stands for in a
+    /// request with these requirements.
+    async fn route_model(&self, id: &str, requirements: &ModelRequirements) -> Result<String, String> {
+        let client = self.client.read().await;
+        let down = self.providers_down();
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        // A provider is healthy while any of its accounts isn't resting; keyless providers always are.
+        let mut healthy: HashMap<&str, bool> = HashMap::new();
+        for full_id in client.models().keys() {
+            if let Some((provider, _)) = split_model_id(full_id) {
+                healthy.entry(provider).or_insert_with(|| {
+                    !down.contains_key(provider)
+                        && self
+                            .config
+                            .list_accounts(provider)
+                            .map_or(true, |accounts| accounts.is_empty() || accounts.iter().any(|a| a.is_healthy_at(now_ms)))
+                });
+            }
+        }
+        let available = |model: &str| split_model_id(model).is_some_and(|(provider, _)| healthy.get(provider) == Some(&true));
+        if id != FASTEST_MODEL_ID {
+            return routing::cheapest_capable(client.models(), requirements, available)
+                .map(String::from)
+                .ok_or_else(|| format!("No healthy model meets the requirements of this request for {}", CHEAPEST_MODEL_ID));
+        }
+        let usage = self.usage.clone();
+        let latency = blocking(move || usage.latency_stats(now_ms)).await.unwrap_or_else(|e| {
+            tracing::debug!("Failed to read latency: {}", e);
+            HashMap::new()
+        });
+        let explore = (self.fastest_routed.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(routing::EXPLORE_EVERY);
+        routing::fastest_capable(client.models(), requirements, &latency, explore, available)
+            .map(String::from)
+            .ok_or_else(|| format!("No healthy model meets the requirements of this request for {}", FASTEST_MODEL_ID))
+    }
+
+    /// If `err` is a rate limit, rest the account until the provider says the limit resets
+    /// (e.g. the end of a subscription window), or for [`RATE_LIMIT_COOLDOWN_MS`] when it
+    /// doesn't say. Returns whether it was a rate limit.
+    pub(crate) fn rate_limit(&self, provider: &str, account_id: &str, err: &zeroai::ProviderError) -> bool {
+        if !retry_helpers::is_rate_limited(err) {
+            return false;
+        }
+        let backoff_ms = retry_helpers::parse_retry_after_ms(err).unwrap_or(RATE_LIMIT_COOLDOWN_MS);
+        if let Err(e) = self.config.rate_limit_account(provider, account_id, backoff_ms) {
+            tracing::debug!("Failed to mark {}/{} rate limited: {}", provider, account_id, e);
+        }
+        self.record_account_event(provider, account_id, "rate_limited", backoff_ms);
+        self.webhooks.emit(
+            webhooks::ACCOUNT_RATELIMITED,
+            json!({
+                "provider": provider,
+                "account_id": account_id,
+                "until_ms": chrono::Utc::now().timestamp_millis().saturating_add(backoff_ms as i64),
+            }),
+        );
+        self.alert_if_all_limited(provider);
+        true
+    }
+
+    /// Alert when no account of `provider` is left to rotate to.
+    fn alert_if_all_limited(&self, provider: &str) {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let Ok(accounts) = self.config.list_accounts(provider) else {
+            return;
+        };
+        if accounts.is_empty() || accounts.iter().any(|a| a.is_healthy_at(now_ms)) {
+            return;
+        }
+        let until_ms = accounts
+            .iter()
+            .filter(|a| a.is_available_at(now_ms))
+            .filter_map(|a| a.unhealthy_until_ms)
+            .min();
+        self.notifier.send(Alert::AllRateLimited { provider: provider.to_string(), until_ms });
+    }
+
+    /// Add to the account's health history that it was rested for `rest_ms`.
+    fn record_account_event(&self, provider: &str, account_id: &str, kind: &str, rest_ms: u64) {
+        let at_ms = chrono::Utc::now().timestamp_millis();
+        let event = AccountEvent {
+            provider: provider.to_string(),
+            account_id: account_id.to_string(),
+            at_ms,
+            kind: kind.to_string(),
+            until_ms: Some(at_ms.saturating_add(rest_ms as i64)),
+        };
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = store.record_account_event(&event) {
+                tracing::debug!("Failed to record {} for {}/{}: {}", event.kind, event.provider, event.account_id, e);
+            }
+        });
+    }
+
+    /// Note the upstream request ID on `log`, store the premium-request quota a Copilot
+    /// response reported and, once it is used up, rest the account until the quota renews so
+    /// rotation prefers accounts with quota left.
+    fn track_response(&self, provider: &str, account_id: &str, headers: Option<&ResponseHeaders>, log: &RequestLog) {
+        let Some(headers) = headers.and_then(ResponseHeaders::take) else {
+            return;
+        };
+        log.record_upstream_headers(&headers);
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let Some(quota) = github_copilot::quota_from_headers(&headers, now_ms) else {
+            return;
+        };
+        if quota.is_exhausted_at(now_ms)
+            && let Some(resets_at_ms) = quota.resets_at_ms
+        {
+            tracing::info!("{}/{} has no premium requests left until it renews", provider, account_id);
+            let rest_ms = (resets_at_ms - now_ms) as u64;
+            if let Err(e) = self.config.rate_limit_account(provider, account_id, rest_ms) {
+                tracing::debug!("Failed to rest {}/{}: {}", provider, account_id, e);
+            }
+            self.record_account_event(provider, account_id, "quota_exhausted", rest_ms);
+        }
+        let (usage, provider, account_id) = (self.usage.clone(), provider.to_string(), account_id.to_string());
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = usage.set_quota(&provider, &account_id, quota) {
+                tracing::debug!("Failed to record quota for {}/{}: {}", provider, account_id, e);
+            }
+        });
+    }
+
+    /// Providers taken out of service, which routing skips.
+    fn providers_down(&self) -> HashMap<String, ProviderMaintenance> {
+        self.config
+            .providers_in_maintenance(chrono::Utc::now().timestamp_millis())
+            .unwrap_or_default()
+    }
+
+    /// Error message if `provider` is in maintenance.
+    fn maintenance(&self, provider: &str) -> Option<String> {
+        let down = self.providers_down();
+        let m = down.get(provider)?;
+        Some(format!("Provider {} is in maintenance ({})", provider, m.describe()))
+    }
+
+    /// Error message if the rolling 24h spend has reached the configured budget, in which
+    /// case `log`'s request is reported to the event webhooks as rejected.
+    pub(crate) async fn budget_exceeded(&self, settings: &ProxySettings, log: &RequestLog) -> Option<String> {
+        let cap = settings.daily_budget_usd?;
+        let usage = self.usage.clone();
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let spent = blocking(move || usage.rolling_cost(now_ms)).await.unwrap_or(0.0);
+        self.notifier.check_budget(spent, cap);
+        if spent < cap {
+            return None;
+        }
+        self.webhooks.emit(
+            webhooks::BUDGET_EXCEEDED,
+            json!({
+                "request_id": log.id(),
+                "app": log.app(),
+                "user": log.user(),
+                "budget_usd": cap,
+                "spent_usd": spent,
+            }),
+        );
+        Some(format!("Daily budget of ${:.2} reached (${:.2} spent in the last 24 hours)", cap, spent))
+    }
+
+    /// Apply `settings.compression` to a request context, charging the summaries to an
+    /// account of the summarizing model's provider (and to the requesting app and user). On failure the
+    /// context goes out as is.
+    async fn compress_context(
+        &self,
+        settings: &ProxySettings,
+        client: &AiClient,
+        source: RequestSource<'_>,
+        context: ChatContext,
+    ) -> ChatContext {
+        let Some(config) = &settings.compression else {
+            return context;
+        };
+        let Some((provider, _)) = split_model_id(&config.model) else {
+            tracing::warn!("Invalid compression model ID: {}", config.model);
+            return context;
+        };
+        let Some(sel) = self.resolve_account(provider).await else {
+            tracing::warn!("No credentials for compression provider: {}", provider);
+            return context;
+        };
+        let options = RequestOptions {
+            api_key: Some(sel.api_key.clone()),
+            retry_config: settings.retry.clone(),
+            ..Default::default()
+        };
+        match self.compressor.compress(client, config, &context, &options).await {
+            Ok(compressed) => {
+                if compressed.usage.total_tokens > 0 {
+                    let cost = client.get_model(&config.model).map(|m| m.cost.clone()).unwrap_or_default();
+                    self.record_usage(provider, &sel.account_id, source, Some(&compressed.usage), &cost).await;
+                }
+                compressed.context
+            }
+            Err(e) => {
+                tracing::warn!("Context compression failed, forwarding uncompressed: {}", e);
+                context
+            }
+        }
+    }
+
     /// Resolve an account+api_key for a provider.
     pub async fn resolve_account(&self, provider: &str) -> Option<zeroai::auth::config::AccountSelection> {
         self.config.resolve_account(provider).await.ok().flatten()
     }
 }
 
-/// Build an AiClient populated with the enabled models from config.
-fn build_client(config: &ConfigManager) -> AiClient {
-    let enabled = config.get_enabled_models().unwrap_or_default();
-    let all_static = zeroai::models::static_models::all_static_models();
+/// Build an AiClient populated with the enabled models from config, with the capabilities
+/// that `auth-check` probes found broken turned off.
+fn build_client(config: &ConfigManager, store: &Store) -> AiClient {
+    let probes = store.capability_probes().unwrap_or_default();
+    let provider_defs = config.get_provider_defs().unwrap_or_default();
 
-    let mut models = Vec::new();
-    for full_id in &enabled {
-        if let Some((provider, model_id)) = split_model_id(full_id) {
-            if let Some(def) = all_static
-                .iter()
-                .find(|m| m.provider == provider && m.id == model_id)
-            {
-                models.push((full_id.clone(), def.clone()));
-            } else if let Some(def) = zeroai::models::default_model_def_for_provider(provider, model_id) {
-                models.push((full_id.clone(), def));
-            }
+    let mut registry = ProviderRegistry::new();
+    for def in &provider_defs {
+        registry.register_def(def);
+    }
+
+    let mut models = config.enabled_model_defs().unwrap_or_default();
+    for (full_id, def) in &mut models {
+        if let Some(probe) = probes.iter().find(|p| &p.model == full_id) {
+            probe.apply_to(def);
         }
     }
 
-    AiClient::builder().with_models(models).build()
+    AiClient::builder()
+        .with_registry(registry)
+        .with_models(models)
+        .with_concurrency_limits(config.get_settings().unwrap_or_default().concurrency)
+        .build()
 }
 
 // ---------------------------------------------------------------------------
 // Server
 // ---------------------------------------------------------------------------
 
-pub async fn run_server(host: &str, port: u16) -> anyhow::Result<()> {
-    let state = Arc::new(AppState::new().await?);
+pub async fn run_server(host: &str, port: u16, capture_mode: Option<CaptureMode>) -> anyhow::Result<()> {
+    match &capture_mode {
+        Some(CaptureMode::Record(dir)) => {
+            std::fs::create_dir_all(dir)?;
+            tracing::warn!("Debug capture is on: upstream requests and responses are written to {}", dir.display());
+        }
+        Some(CaptureMode::Replay(dir)) => {
+            anyhow::ensure!(dir.is_dir(), "Replay directory {} does not exist", dir.display());
+            tracing::warn!("Replay mode: upstream calls are answered from recordings in {}", dir.display());
+        }
+        None => {}
+    }
+    let replaying = matches!(capture_mode, Some(CaptureMode::Replay(_)));
+    let state = Arc::new(AppState::new(capture_mode).await?);
+    log_config_issues(&state.config);
+    let notifier = state.notifier.clone();
+    zeroai::auth::config::on_refresh_failure(move |provider, account| {
+        notifier.send(Alert::LoginExpired {
+            provider: provider.to_string(),
+            account: account.display_label(),
+            error: account.last_refresh_error.clone(),
+        });
+    });
 
     // Start background auto-refresh service (check every 15 minutes, with 20 minute buffer)
     let refresh_config = state.config.clone();
     refresh_config.start_auto_refresh_service(15 * 60, 20 * 60);
 
-    let app = Router::new()
-        .route("/v1/models", get(list_models))
-        .route("/v1/chat/completions", post(chat_completions))
-        .route("/v1/messages", post(anthropic_messages))
-        .with_state(state);
+    if let Some(warm) = state.config.get_settings().unwrap_or_default().warm_connections
+        && !replaying
+    {
+        start_connection_warmer(state.clone(), warm);
+    }
+
+    let background = state.background.clone();
+    let app = router(state);
 
     let addr = format!("{}:{}", host, port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     tracing::info!("AI proxy listening on {}", addr);
 
-    axum::serve(listener, app).await?;
+    // On ctrl-c / SIGTERM stop accepting connections and let in-flight requests finish (their
+    // usage is recorded when they complete), but don't wait on a stuck stream forever.
+    let draining = Arc::new(tokio::sync::Notify::new());
+    let drain_started = draining.clone();
+    let drain_deadline = Arc::new(std::sync::OnceLock::new());
+    let deadline = drain_deadline.clone();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down: waiting up to {}s for in-flight requests", SHUTDOWN_DRAIN_TIMEOUT.as_secs());
+        let _ = deadline.set(tokio::time::Instant::now() + SHUTDOWN_DRAIN_TIMEOUT);
+        drain_started.notify_one();
+    });
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            draining.notified().await;
+            tokio::time::sleep(SHUTDOWN_DRAIN_TIMEOUT).await;
+        } => tracing::warn!("In-flight requests still running after {}s; exiting anyway", SHUTDOWN_DRAIN_TIMEOUT.as_secs()),
+    }
+
+    // Flush webhooks, alerts and shadow calls started by the last requests, within what is
+    // left of the drain timeout.
+    background.close();
+    let deadline = drain_deadline.get().copied().unwrap_or_else(|| tokio::time::Instant::now() + SHUTDOWN_DRAIN_TIMEOUT);
+    if tokio::time::timeout_at(deadline, background.wait()).await.is_err() {
+        tracing::warn!("{} background tasks still running at shutdown; exiting anyway", background.len());
+    }
 
     Ok(())
 }
 
+/// Open connections to `warm.providers` now and ping them every `interval_secs` (see
+/// `settings.warm_connections`).
+fn start_connection_warmer(state: Arc<AppState>, warm: WarmConnections) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(warm.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            for (provider, e) in state.client.read().await.warm_connections(&warm.providers).await {
+                tracing::debug!("Could not warm the connection to {}: {}", provider, e);
+            }
+        }
+    })
+}
+
+/// Log what `config validate` would report, so a broken setup shows up before the first request.
+fn log_config_issues(config: &ConfigManager) {
+    let Ok(cfg) = config.load() else {
+        return;
+    };
+    for issue in validate_config(&cfg) {
+        match issue.severity {
+            Severity::Error => tracing::error!("Config: {} (fix: {})", issue.message, issue.fix),
+            Severity::Warning => tracing::warn!("Config: {} (fix: {})", issue.message, issue.fix),
+        }
+    }
+}
+
+/// How long shutdown waits for in-flight requests and streams to finish.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Resolves on ctrl-c, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/v1/models", get(list_models))
+        .route("/v1/models/{*model}", get(retrieve_model))
+        .route("/v1/usage", get(usage_report))
+        .route("/v1/providers", get(list_providers))
+        .route("/v1/providers/disable", post(disable_provider))
+        .route("/v1/providers/enable", post(enable_provider))
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/messages", post(anthropic_messages))
+        .route("/v1/messages/count_tokens", post(anthropic_count_tokens))
+        .route("/v1/completions", post(completions))
+        .route("/v1/engines/{engine}/completions", post(engine_completions))
+        .route("/chat/completions", post(copilot_chat_completions))
+        .route("/models", get(list_models))
+        .route("/v1/embeddings", post(embeddings))
+        .route("/v1/rerank", post(rerank))
+        .route("/v2/rerank", post(rerank))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .layer(middleware::from_fn_with_state(state.clone(), idempotency::middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), access_log::middleware))
+        .with_state(state)
+}
+
 // ---------------------------------------------------------------------------
-// GET /v1/models - OpenAI compatible
+// GET /v1/models, GET /v1/models/{model} - OpenAI compatible
 // ---------------------------------------------------------------------------
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ModelsResponse {
     object: String,
     data: Vec<ModelObject>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ModelObject {
     id: String,
     object: String,
     created: i64,
     owned_by: String,
+    /// Extension: USD per million tokens; omitted when the price is unknown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pricing: Option<ModelPricing>,
+    /// Extension: display name.
+    name: String,
+    /// Extension: context window in tokens.
+    context_window: u64,
+    /// Extension: maximum output tokens.
+    max_tokens: u64,
+    /// Extension: what the model accepts and supports.
+    capabilities: ModelObjectCapabilities,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ModelObjectCapabilities {
+    tools: bool,
+    vision: bool,
+    audio: bool,
+    json_mode: bool,
+    reasoning: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ModelPricing {
+    input: f64,
+    output: f64,
+    cache_read: f64,
+    cache_write: f64,
+}
+
+impl ModelPricing {
+    fn from_cost(cost: &ModelCost) -> Option<Self> {
+        if cost.input == 0.0 && cost.output == 0.0 {
+            return None;
+        }
+        Some(Self {
+            input: cost.input,
+            output: cost.output,
+            cache_read: cost.cache_read,
+            cache_write: cost.cache_write,
+        })
+    }
+}
+
+fn model_object(id: &str, def: &ModelDef) -> ModelObject {
+    ModelObject {
+        id: id.to_string(),
+        object: "model".into(),
+        created: 0,
+        owned_by: def.provider.clone(),
+        pricing: ModelPricing::from_cost(&def.cost),
+        name: def.name.clone(),
+        context_window: def.context_window,
+        max_tokens: def.max_tokens,
+        capabilities: ModelObjectCapabilities {
+            tools: def.supports_tools(),
+            vision: def.supports_vision(),
+            audio: def.supports_audio(),
+            json_mode: def.supports_json_mode(),
+            reasoning: def.reasoning,
+        },
+    }
+}
+
+/// Full model ID for a requested name: an alias, an exact `provider/model` ID, then a bare
+/// model name (when `settings.expose_bare_model_ids` is on). Bare names skip the providers
+/// in `down`.
+fn resolve_model_id(
+    settings: &ProxySettings,
+    client: &AiClient,
+    down: &HashMap<String, ProviderMaintenance>,
+    model: &str,
+) -> String {
+    let model = settings.resolve_model(model);
+    if client.get_model(model).is_some() {
+        return model.to_string();
+    }
+    let up = client
+        .models()
+        .keys()
+        .filter(|id| split_model_id(id).is_none_or(|(provider, _)| !down.contains_key(provider)));
+    settings
+        .bare_model_ids(up.map(String::as_str))
+        .remove(model)
+        .unwrap_or_else(|| model.to_string())
 }
 
+/// If `model` is the model of one of `settings.experiments`, replace it with the model of
+/// the variant assigned to the requesting user (or else app) and note the variant on `log`.
+fn apply_experiment(settings: &ProxySettings, model: &mut String, log: &RequestLog) {
+    let Some(experiment) = settings.experiments.iter().find(|e| e.model == *model) else {
+        return;
+    };
+    let Some(variant) = experiment.assign(log.user().or(log.app())) else {
+        return;
+    };
+    log.set_variant(format!("{}/{}", experiment.name, variant.name));
+    *model = variant.model.clone();
+}
+
+/// A routing pseudo-model: supports whatever some enabled model supports, up to the largest
+/// context window.
+fn routed_model_object(client: &AiClient, id: &str) -> ModelObject {
+    let models = client.models().values();
+    ModelObject {
+        id: id.into(),
+        object: "model".into(),
+        created: 0,
+        owned_by: "zeroai".into(),
+        pricing: None,
+        name: if id == FASTEST_MODEL_ID { "Fastest capable model" } else { "Cheapest capable model" }.into(),
+        context_window: models.clone().map(|m| m.context_window).max().unwrap_or(0),
+        max_tokens: models.clone().map(|m| m.max_tokens).max().unwrap_or(0),
+        capabilities: ModelObjectCapabilities {
+            tools: models.clone().any(ModelDef::supports_tools),
+            vision: models.clone().any(ModelDef::supports_vision),
+            audio: models.clone().any(ModelDef::supports_audio),
+            json_mode: models.clone().any(ModelDef::supports_json_mode),
+            reasoning: models.clone().any(|m| m.reasoning),
+        },
+    }
+}
+
+#[utoipa::path(get, path = "/v1/models", tag = "models", responses(
+    (status = 200, description = "Enabled models, bare model IDs and the routing pseudo-models", body = ModelsResponse),
+))]
 async fn list_models(State(state): State<Arc<AppState>>) -> Json<ModelsResponse> {
+    let settings = state.config.get_settings().unwrap_or_default();
     let client = state.client.read().await;
-    let data: Vec<ModelObject> = client
+    let mut data: Vec<ModelObject> = client
         .models()
         .iter()
-        .map(|(full_id, def)| ModelObject {
-            id: full_id.clone(),
-            object: "model".into(),
-            created: 0,
-            owned_by: def.provider.clone(),
-        })
+        .map(|(full_id, def)| model_object(full_id, def))
         .collect();
+    // Bare names that equal a full ID (e.g. OpenRouter's `vendor/model`) are already listed.
+    let bare = settings.bare_model_ids(client.models().keys().map(String::as_str));
+    data.extend(
+        bare.iter()
+            .filter(|(name, _)| client.get_model(name).is_none())
+            .filter_map(|(name, full_id)| client.get_model(full_id).map(|def| model_object(name, def))),
+    );
+    if !client.models().is_empty() {
+        data.push(routed_model_object(&client, CHEAPEST_MODEL_ID));
+        data.push(routed_model_object(&client, FASTEST_MODEL_ID));
+    }
 
     Json(ModelsResponse {
         object: "list".into(),
@@ -137,11 +733,288 @@ async fn list_models(State(state): State<Arc<AppState>>) -> Json<ModelsResponse>
     })
 }
 
+/// One model by ID (`provider/model`, an alias or a bare name); the ID may contain slashes.
+#[utoipa::path(get, path = "/v1/models/{model}", tag = "models",
+    params(("model" = String, Path, description = "`provider/model`, an alias or a bare name")),
+    responses(
+        (status = 200, body = ModelObject),
+        (status = 404, description = "No such model", body = serde_json::Value),
+    ),
+)]
+async fn retrieve_model(State(state): State<Arc<AppState>>, Path(model): Path<String>) -> Response {
+    let settings = state.config.get_settings().unwrap_or_default();
+    let client = state.client.read().await;
+    let full_id = resolve_model_id(&settings, &client, &state.providers_down(), &model);
+    if routing::is_routed(&full_id) && !client.models().is_empty() {
+        return Json(routed_model_object(&client, &full_id)).into_response();
+    }
+    match client.get_model(&full_id) {
+        Some(def) => Json(model_object(&model, def)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": {
+                "message": format!("The model '{}' does not exist", model),
+                "type": "invalid_request_error",
+                "code": "model_not_found"
+            }})),
+        )
+            .into_response(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GET /v1/usage - rolling 24h usage per account, client app, end user and experiment variant, and account events
+// ---------------------------------------------------------------------------
+
+#[utoipa::path(get, path = "/v1/usage", tag = "admin", responses(
+    (status = 200, description = "Usage of the last 24 hours per account, app, end user, experiment variant and model", body = serde_json::Value),
+))]
+async fn usage_report(State(state): State<Arc<AppState>>) -> Response {
+    let usage = state.usage.clone();
+    let load = blocking(move || Ok((usage.load()?, usage.load_apps()?, usage.load_users()?, usage.load_variants()?)));
+    let (accounts, apps, users, variants) = match load.await {
+        Ok(usage) => usage,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": {"message": format!("Failed to read usage: {}", e), "type": "server_error"}})),
+            )
+                .into_response();
+        }
+    };
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut account_rows = usage_rows("account", accounts, now_ms);
+    label_accounts(&state.config, &mut account_rows);
+    let events = state
+        .store
+        .account_events(now_ms - zeroai::auth::usage::ROLLING_WINDOW_MS)
+        .unwrap_or_default();
+    let events: Vec<_> = events
+        .into_iter()
+        .map(|e| {
+            json!({
+                "provider": e.provider,
+                "account_id": e.account_id,
+                "at_ms": e.at_ms,
+                "kind": e.kind,
+                "until_ms": e.until_ms,
+            })
+        })
+        .collect();
+    Json(json!({
+        "object": "usage",
+        "window_hours": zeroai::auth::usage::ROLLING_WINDOW_MS / 3_600_000,
+        "accounts": account_rows,
+        "apps": usage_rows("app", apps, now_ms),
+        "users": usage_rows("user", users, now_ms),
+        "variants": usage_rows("variant", variants, now_ms),
+        "account_events": events,
+        "models": latency_rows(&state.usage, now_ms),
+        "shadow": shadow_rows(&state.store, now_ms - zeroai::auth::usage::ROLLING_WINDOW_MS),
+    }))
+    .into_response()
+}
+
+/// Add each account's label and login identity (email, org) to its `/v1/usage` row.
+fn label_accounts(config: &ConfigManager, rows: &mut [serde_json::Value]) {
+    let mut accounts: HashMap<String, Account> = HashMap::new();
+    for provider in config.list_providers_with_credentials().unwrap_or_default() {
+        for acc in config.list_accounts(&provider).unwrap_or_default() {
+            accounts.insert(format!("{}/{}", provider, acc.id), acc);
+        }
+    }
+    for row in rows {
+        let Some(acc) = row["account"].as_str().and_then(|key| accounts.get(key)) else {
+            continue;
+        };
+        row["label"] = json!(acc.display_label());
+        row["email"] = json!(acc.email);
+        row["org"] = json!(acc.org);
+    }
+}
+
+/// Rolling latency per model, fastest first.
+fn latency_rows(usage: &UsageStore, now_ms: i64) -> Vec<serde_json::Value> {
+    let mut rows: Vec<_> = usage.latency_stats(now_ms).unwrap_or_default().into_iter().collect();
+    rows.sort_by(|a, b| a.1.p50_ms.cmp(&b.1.p50_ms).then_with(|| a.0.cmp(&b.0)));
+    rows.into_iter()
+        .map(|(model, l)| json!({"model": model, "p50_ms": l.p50_ms, "p95_ms": l.p95_ms, "samples": l.samples}))
+        .collect()
+}
+
+/// Shadow traffic comparisons per model pair since `since_ms`.
+fn shadow_rows(store: &Store, since_ms: i64) -> Vec<serde_json::Value> {
+    store
+        .shadow_summary(since_ms)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| {
+            json!({
+                "model": s.model,
+                "shadow_model": s.shadow_model,
+                "requests": s.requests,
+                "errors": s.errors,
+                "avg_duration_ms": s.avg_duration_ms,
+                "avg_shadow_duration_ms": s.avg_shadow_duration_ms,
+                "cost_usd": s.cost_usd,
+                "shadow_cost_usd": s.shadow_cost_usd,
+                "avg_similarity": s.avg_similarity,
+                "tool_call_agreement": s.tool_call_agreement,
+            })
+        })
+        .collect()
+}
+
+/// Rolling totals per entry, biggest spender first.
+fn usage_rows(key: &str, usage: HashMap<String, AccountUsage>, now_ms: i64) -> Vec<serde_json::Value> {
+    let mut rows: Vec<_> = usage
+        .into_iter()
+        .map(|(name, u)| (name, u.rolling_totals(now_ms), u))
+        .filter(|(_, totals, _)| totals.requests > 0)
+        .collect();
+    rows.sort_by(|a, b| b.1.cost_usd.total_cmp(&a.1.cost_usd).then_with(|| a.0.cmp(&b.0)));
+    rows.into_iter()
+        .map(|(name, t, u)| {
+            let mut row = json!({
+                key: name,
+                "requests": t.requests,
+                "input_tokens": t.input_tokens,
+                "output_tokens": t.output_tokens,
+                "cost_usd": t.cost_usd,
+                "avg_ttft_ms": t.avg_ttft_ms(),
+                "output_tokens_per_second": t.output_tokens_per_sec(),
+                "last_used_ms": u.last_used_ms,
+            });
+            if let Some(q) = u.quota {
+                row["premium_requests"] = json!({
+                    "used": q.used(),
+                    "entitlement": q.entitlement,
+                    "remaining_percent": q.remaining_percent,
+                    "resets_at_ms": q.resets_at_ms,
+                });
+            }
+            row
+        })
+        .collect()
+}
+
+/// An OpenAI `usage` object, with zeroai's timing extension.
+fn openai_usage(u: &zeroai::types::Usage) -> serde_json::Value {
+    with_timing(
+        json!({
+            "prompt_tokens": u.input_tokens,
+            "completion_tokens": u.output_tokens,
+            "total_tokens": u.total_tokens,
+        }),
+        u,
+    )
+}
+
+/// Add `zeroai_timing` (time to first token, duration, output tokens per second) to a usage
+/// object when the response was timed.
+fn with_timing(mut usage: serde_json::Value, u: &zeroai::types::Usage) -> serde_json::Value {
+    if u.duration_ms.is_some() {
+        usage["zeroai_timing"] = json!({
+            "ttft_ms": u.ttft_ms,
+            "duration_ms": u.duration_ms,
+            "output_tokens_per_second": u.output_tokens_per_sec(),
+        });
+    }
+    usage
+}
+
+// ---------------------------------------------------------------------------
+// GET /v1/providers, POST /v1/providers/disable, /v1/providers/enable - provider maintenance
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, ToSchema)]
+struct DisableProviderRequest {
+    provider: String,
+    #[serde(default)]
+    reason: Option<String>,
+    /// Minutes until the provider comes back by itself; until `enable` when omitted.
+    #[serde(default)]
+    minutes: Option<u64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct EnableProviderRequest {
+    provider: String,
+}
+
+/// Configured providers, with their account count and maintenance window if any.
+#[utoipa::path(get, path = "/v1/providers", tag = "admin", responses(
+    (status = 200, description = "`{\"object\": \"list\", \"data\": [{\"id\", \"accounts\", \"maintenance\"}]}`", body = serde_json::Value),
+))]
+async fn list_providers(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let down = state.providers_down();
+    let mut ids = state.config.list_providers_with_credentials().unwrap_or_default();
+    ids.extend(down.keys().cloned());
+    ids.sort();
+    ids.dedup();
+    let data: Vec<_> = ids
+        .into_iter()
+        .map(|id| {
+            let accounts = state.config.list_accounts(&id).map(|a| a.len()).unwrap_or(0);
+            json!({"id": id, "accounts": accounts, "maintenance": down.get(&id)})
+        })
+        .collect();
+    Json(json!({"object": "list", "data": data}))
+}
+
+/// Put a provider into maintenance: requests skip it until `minutes` pass or it is enabled.
+#[utoipa::path(post, path = "/v1/providers/disable", tag = "admin", request_body = DisableProviderRequest, responses(
+    (status = 200, description = "The provider and its maintenance window", body = serde_json::Value),
+    (status = 400, description = "No provider given", body = serde_json::Value),
+))]
+async fn disable_provider(State(state): State<Arc<AppState>>, Json(req): Json<DisableProviderRequest>) -> Response {
+    let maintenance = ProviderMaintenance {
+        reason: req.reason.filter(|r| !r.trim().is_empty()),
+        until_ms: req
+            .minutes
+            .map(|m| chrono::Utc::now().timestamp_millis().saturating_add(m.saturating_mul(60_000) as i64)),
+    };
+    set_maintenance(&state, &req.provider, Some(maintenance))
+}
+
+/// End a provider's maintenance.
+#[utoipa::path(post, path = "/v1/providers/enable", tag = "admin", request_body = EnableProviderRequest, responses(
+    (status = 200, description = "The provider, with `maintenance: null`", body = serde_json::Value),
+    (status = 400, description = "No provider given", body = serde_json::Value),
+))]
+async fn enable_provider(State(state): State<Arc<AppState>>, Json(req): Json<EnableProviderRequest>) -> Response {
+    set_maintenance(&state, &req.provider, None)
+}
+
+fn set_maintenance(state: &AppState, provider: &str, maintenance: Option<ProviderMaintenance>) -> Response {
+    if provider.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "provider is required", "type": "invalid_request_error"}})),
+        )
+            .into_response();
+    }
+    match state.config.set_provider_maintenance(provider, maintenance.clone()) {
+        Ok(()) => {
+            match &maintenance {
+                Some(m) => tracing::info!("Provider {} disabled ({})", provider, m.describe()),
+                None => tracing::info!("Provider {} enabled", provider),
+            }
+            Json(json!({"id": provider, "maintenance": maintenance})).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": {"message": format!("Failed to save config: {}", e), "type": "server_error"}})),
+        )
+            .into_response(),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // POST /v1/chat/completions - OpenAI compatible
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ChatCompletionRequest {
     model: String,
     messages: Vec<OpenAIMessage>,
@@ -153,9 +1026,90 @@ struct ChatCompletionRequest {
     max_tokens: Option<u64>,
     #[serde(default)]
     tools: Option<Vec<OpenAITool>>,
+    #[serde(default)]
+    response_format: Option<serde_json::Value>,
+    #[serde(default)]
+    web_search_options: Option<serde_json::Value>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    metadata: Option<HashMap<String, String>>,
+    #[serde(default)]
+    store: Option<bool>,
+    /// OpenRouter provider routing preferences (order, allow_fallbacks, quantizations, ...).
+    #[serde(default)]
+    provider: Option<serde_json::Value>,
+    /// OpenRouter prompt transforms (e.g. `["middle-out"]`).
+    #[serde(default)]
+    transforms: Option<serde_json::Value>,
+    /// Any other top-level fields (`top_p`, `stop`, vLLM `top_k`, ...), passed on upstream.
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// Top-level request fields the proxy sets itself rather than passing on: `stream_options`
+/// (usage reporting depends on it) and `n` (only the first choice is read back).
+const UNFORWARDED_FIELDS: &[&str] = &["stream_options", "n"];
+
+/// Unknown top-level fields of a request (`extra`), for `RequestOptions::extra_body`.
+fn extra_body(extra: &HashMap<String, serde_json::Value>) -> Option<HashMap<String, serde_json::Value>> {
+    let extra: HashMap<_, _> = extra
+        .iter()
+        .filter(|(name, _)| !UNFORWARDED_FIELDS.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    (!extra.is_empty()).then_some(extra)
 }
 
-#[derive(Deserialize)]
+/// Request header carrying extra upstream body fields as a JSON object.
+pub const EXTRA_HEADER: &str = "x-zeroai-extra";
+
+/// Vendor fields for the upstream body (`RequestOptions::provider_extra`): the
+/// `x-zeroai-extra` header's object.
+fn provider_extra(headers: &HeaderMap) -> Result<serde_json::Value, String> {
+    match headers.get(EXTRA_HEADER) {
+        Some(v) => match v.to_str().ok().and_then(|v| serde_json::from_str(v).ok()) {
+            Some(serde_json::Value::Object(fields)) => Ok(fields.into()),
+            _ => Err(format!("{} must be a JSON object", EXTRA_HEADER)),
+        },
+        None => Ok(serde_json::Value::Null),
+    }
+}
+
+/// Add the request's `provider` routing preferences and `transforms` for OpenRouter models.
+/// Other upstreams don't know them, and OpenAI rejects unknown parameters.
+fn apply_openrouter_fields(req: &ChatCompletionRequest, model: &ModelDef, options: &mut RequestOptions) {
+    if model.provider != "openrouter" {
+        return;
+    }
+    for (name, value) in [("provider", &req.provider), ("transforms", &req.transforms)] {
+        if let Some(value) = value {
+            if !options.provider_extra.is_object() {
+                options.provider_extra = json!({});
+            }
+            options.provider_extra[name] = value.clone();
+        }
+    }
+}
+
+/// Add the configured `ollama_keep_alive` to requests for Ollama models, unless the client
+/// already set `keep_alive`.
+fn apply_ollama_keep_alive(settings: &ProxySettings, model: &ModelDef, options: &mut RequestOptions) {
+    let Some(keep_alive) = &settings.ollama_keep_alive else {
+        return;
+    };
+    let client_set = options.extra_body.as_ref().is_some_and(|extra| extra.contains_key("keep_alive"))
+        || options.provider_extra.get("keep_alive").is_some();
+    if model.provider != "ollama" || client_set {
+        return;
+    }
+    if !options.provider_extra.is_object() {
+        options.provider_extra = json!({});
+    }
+    options.provider_extra["keep_alive"] = keep_alive.clone();
+}
+
+#[derive(Deserialize, ToSchema)]
 struct OpenAIMessage {
     role: String,
     #[serde(default)]
@@ -168,24 +1122,24 @@ struct OpenAIMessage {
     name: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct OpenAIToolCall {
     id: String,
     function: OpenAIFunction,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct OpenAIFunction {
     name: String,
     arguments: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct OpenAITool {
     function: OpenAIToolFunction,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct OpenAIToolFunction {
     name: String,
     description: Option<String>,
@@ -198,32 +1152,26 @@ fn convert_openai_messages(msgs: &[OpenAIMessage]) -> (Option<String>, Vec<Messa
 
     for msg in msgs {
         match msg.role.as_str() {
+            // Copilot chat sends several system messages; they are joined in order.
             "system" => {
-                if let Some(content) = &msg.content {
-                    system = content.as_str().map(String::from);
+                if let Some(text) = msg.content.as_ref().and_then(openai_text) {
+                    system = Some(match system {
+                        Some(prev) => format!("{}\n\n{}", prev, text),
+                        None => text,
+                    });
                 }
             }
             "user" => {
-                let text = msg
-                    .content
-                    .as_ref()
-                    .and_then(|c| c.as_str())
-                    .unwrap_or("")
-                    .to_string();
                 messages.push(Message::User(UserMessage {
-                    content: vec![ContentBlock::Text(TextContent { text })],
+                    content: convert_openai_user_content(msg.content.as_ref()),
                 }));
             }
             "assistant" => {
                 let mut content = Vec::new();
-                if let Some(c) = &msg.content {
-                    if let Some(text) = c.as_str() {
-                        if !text.is_empty() {
-                            content.push(ContentBlock::Text(TextContent {
-                                text: text.to_string(),
-                            }));
-                        }
-                    }
+                if let Some(text) = msg.content.as_ref().and_then(openai_text)
+                    && !text.is_empty()
+                {
+                    content.push(ContentBlock::Text(TextContent { text }));
                 }
                 if let Some(tcs) = &msg.tool_calls {
                     for tc in tcs {
@@ -242,15 +1190,12 @@ fn convert_openai_messages(msgs: &[OpenAIMessage]) -> (Option<String>, Vec<Messa
                     provider: String::new(),
                     usage: None,
                     stop_reason: StopReason::Stop,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
                 }));
             }
             "tool" => {
-                let text = msg
-                    .content
-                    .as_ref()
-                    .and_then(|c| c.as_str())
-                    .unwrap_or("")
-                    .to_string();
+                let text = msg.content.as_ref().and_then(openai_text).unwrap_or_default();
                 messages.push(Message::ToolResult(ToolResultMessage {
                     tool_call_id: msg.tool_call_id.clone().unwrap_or_default(),
                     tool_name: msg.name.clone().unwrap_or_default(),
@@ -265,21 +1210,289 @@ fn convert_openai_messages(msgs: &[OpenAIMessage]) -> (Option<String>, Vec<Messa
     (system, messages)
 }
 
-fn convert_openai_tools(tools: &[OpenAITool]) -> Vec<ToolDef> {
-    tools
+/// Text of non-user content: a plain string, or the `text` parts of an array joined.
+fn openai_text(content: &serde_json::Value) -> Option<String> {
+    match content {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter(|p| p["type"] == "text")
+                .filter_map(|p| p["text"].as_str())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// User content: a plain string, or an array of `text` / `image_url` parts.
+/// Only inline (data URL) images can be forwarded.
+fn convert_openai_user_content(content: Option<&serde_json::Value>) -> Vec<ContentBlock> {
+    let Some(parts) = content.and_then(|c| c.as_array()) else {
+        let text = content.and_then(|c| c.as_str()).unwrap_or("").to_string();
+        return vec![ContentBlock::Text(TextContent { text })];
+    };
+    parts
         .iter()
-        .map(|t| ToolDef {
-            name: t.function.name.clone(),
-            description: t.function.description.clone().unwrap_or_default(),
-            parameters: t.function.parameters.clone().unwrap_or(json!({})),
+        .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+            Some("text") => part
+                .get("text")
+                .and_then(|t| t.as_str())
+                .map(|text| ContentBlock::Text(TextContent { text: text.to_string() })),
+            Some("image_url") => part
+                .pointer("/image_url/url")
+                .and_then(|u| u.as_str())
+                .and_then(parse_data_url)
+                .map(ContentBlock::Image),
+            _ => None,
         })
         .collect()
 }
 
-async fn chat_completions(
+/// Parse `data:<mime>;base64,<data>`.
+fn parse_data_url(url: &str) -> Option<ImageContent> {
+    let (meta, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = meta.strip_suffix(";base64")?;
+    Some(ImageContent {
+        data: data.to_string(),
+        mime_type: mime_type.to_string(),
+    })
+}
+
+/// Whether the request asks for JSON output (`response_format` `json_object` or `json_schema`).
+fn openai_json_mode(req: &ChatCompletionRequest) -> bool {
+    req.response_format
+        .as_ref()
+        .and_then(|f| f.get("type"))
+        .and_then(|t| t.as_str())
+        .is_some_and(|t| t == "json_object" || t == "json_schema")
+}
+
+fn openai_request_has_audio(msgs: &[OpenAIMessage]) -> bool {
+    msgs.iter().any(|m| {
+        m.content
+            .as_ref()
+            .and_then(|c| c.as_array())
+            .is_some_and(|parts| {
+                parts
+                    .iter()
+                    .any(|p| p.get("type").and_then(|t| t.as_str()) == Some("input_audio"))
+            })
+    })
+}
+
+fn convert_openai_tools(tools: &[OpenAITool]) -> Vec<ToolDef> {
+    tools
+        .iter()
+        .map(|t| ToolDef {
+            name: t.function.name.clone(),
+            description: t.function.description.clone().unwrap_or_default(),
+            parameters: t.function.parameters.clone().unwrap_or(json!({})),
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Capability checks
+// ---------------------------------------------------------------------------
+
+/// Response header listing request features that were dropped for the target model.
+pub const WARNING_HEADER: &str = "x-zeroai-warning";
+
+/// Features a request uses that aren't visible in the `ChatContext`.
+#[derive(Default)]
+struct RequestFeatures {
+    audio: bool,
+    json_mode: bool,
+}
+
+/// Check a request against the model's capabilities. Unsupported features that would make
+/// the request fail are rejected (`Err`); ones that can be dropped are removed and reported
+/// as warnings.
+fn adapt_to_model(
+    full_id: &str,
+    def: &ModelDef,
+    context: &mut ChatContext,
+    features: &RequestFeatures,
+) -> Result<Vec<String>, String> {
+    if !context.tools.is_empty() && !def.supports_tools() {
+        return Err(format!("Model {} does not support tool calling", full_id));
+    }
+    if features.audio && !def.supports_audio() {
+        return Err(format!("Model {} does not support audio input", full_id));
+    }
+
+    let mut warnings = Vec::new();
+    if !def.supports_vision() {
+        let mut removed = 0;
+        for msg in &mut context.messages {
+            let blocks = match msg {
+                Message::User(u) => &mut u.content,
+                Message::ToolResult(t) => &mut t.content,
+                Message::Assistant(_) => continue,
+            };
+            let before = blocks.len();
+            blocks.retain(|b| !matches!(b, ContentBlock::Image(_)));
+            removed += before - blocks.len();
+            if blocks.is_empty() && before > 0 {
+                blocks.push(ContentBlock::Text(TextContent {
+                    text: "[image omitted]".into(),
+                }));
+            }
+        }
+        if removed > 0 {
+            warnings.push(format!("{} image(s) removed: {} does not accept image input", removed, full_id));
+        }
+    }
+    if features.json_mode && !def.supports_json_mode() {
+        warnings.push(format!("response_format ignored: {} does not support JSON mode", full_id));
+    }
+    Ok(warnings)
+}
+
+/// A prompt over `settings.context_guard`'s share of the model's context window.
+struct ContextLimitExceeded {
+    model: String,
+    estimated_tokens: u64,
+    limit_tokens: u64,
+    context_window: u64,
+    max_percent: f64,
+}
+
+impl ContextLimitExceeded {
+    fn message(&self) -> String {
+        format!(
+            "Prompt is about {} tokens, over the {} tokens ({}% of the {}-token context window) allowed for {}; trim at least {} tokens",
+            self.estimated_tokens,
+            self.limit_tokens,
+            self.max_percent,
+            self.context_window,
+            self.model,
+            self.estimated_tokens - self.limit_tokens
+        )
+    }
+
+    /// Machine-readable details, added to the error object.
+    fn details(&self) -> serde_json::Value {
+        json!({
+            "estimated_tokens": self.estimated_tokens,
+            "limit_tokens": self.limit_tokens,
+            "context_window": self.context_window,
+            "max_percent": self.max_percent,
+            "trim_tokens": self.estimated_tokens - self.limit_tokens,
+        })
+    }
+}
+
+/// Hold `context` to `settings.context_guard` for `def`, dropping its oldest turns when the
+/// guard allows it. Returns a warning if turns were dropped.
+fn guard_context(
+    settings: &ProxySettings,
+    full_id: &str,
+    def: &ModelDef,
+    context: &mut ChatContext,
+) -> Result<Option<String>, ContextLimitExceeded> {
+    let Some(guard) = &settings.context_guard else {
+        return Ok(None);
+    };
+    let Some(limit_tokens) = guard.limit_tokens(def.context_window) else {
+        return Ok(None);
+    };
+    let dropped = if guard.truncate { zeroai::tokens::truncate_to_fit(context, limit_tokens) } else { 0 };
+    let estimated_tokens = zeroai::tokens::estimate_input_tokens(context);
+    if estimated_tokens > limit_tokens {
+        return Err(ContextLimitExceeded {
+            model: full_id.to_string(),
+            estimated_tokens,
+            limit_tokens,
+            context_window: def.context_window,
+            max_percent: guard.max_percent,
+        });
+    }
+    Ok((dropped > 0).then(|| {
+        format!(
+            "{} oldest message(s) dropped to keep the prompt within {}% of the context window of {}",
+            dropped, guard.max_percent, full_id
+        )
+    }))
+}
+
+fn with_warnings(mut resp: Response, warnings: &[String]) -> Response {
+    if !warnings.is_empty()
+        && let Ok(v) = axum::http::HeaderValue::from_str(&warnings.join("; "))
+    {
+        resp.headers_mut().insert(WARNING_HEADER, v);
+    }
+    resp
+}
+
+#[utoipa::path(post, path = "/v1/chat/completions", tag = "chat", request_body = ChatCompletionRequest, responses(
+    (status = 200, description = "A chat completion, or with `stream: true` its chunks as server-sent events",
+        content((serde_json::Value = "application/json"), (String = "text/event-stream"))),
+    (status = 400, description = "Invalid request, unknown model or unsupported feature", body = serde_json::Value),
+    (status = 429, description = "The 24h spending budget is used up, or every account is rate limited", body = serde_json::Value),
+))]
+async fn chat_completions(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<ChatCompletionRequest>,
+    Extension(log): Extension<RequestLog>,
+    headers: HeaderMap,
+    Json(mut body): Json<serde_json::Value>,
 ) -> Response {
+    log.set_request_hash(idempotency::request_hash("/v1/chat/completions", &body));
+    let mut req = match ChatCompletionRequest::deserialize(&body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {"message": format!("Invalid request: {}", e), "type": "invalid_request_error"}})),
+            )
+                .into_response();
+        }
+    };
+    let provider_extra = match provider_extra(&headers) {
+        Ok(extra) => extra,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {"message": message, "type": "invalid_request_error"}})),
+            )
+                .into_response();
+        }
+    };
+    let settings = state.config.get_settings().unwrap_or_default();
+    if let Some(user) = &req.user {
+        log.set_user(user);
+    }
+    apply_experiment(&settings, &mut req.model, &log);
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    if routing::is_routed(&req.model) {
+        let (system_prompt, messages) = convert_openai_messages(&req.messages);
+        let tools = req.tools.as_ref().map(|t| convert_openai_tools(t)).unwrap_or_default();
+        let context = ChatContext { system_prompt, messages, tools };
+        let requirements = ModelRequirements {
+            audio: openai_request_has_audio(&req.messages),
+            json_mode: openai_json_mode(&req),
+            ..ModelRequirements::for_context(&context, req.max_tokens)
+        };
+        match state.route_model(&req.model, &requirements).await {
+            Ok(model) => req.model = model,
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": {"message": message, "type": "invalid_request_error"}})),
+                )
+                    .into_response();
+            }
+        }
+    }
+    if let Some(message) = state.budget_exceeded(&settings, &log).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"error": {"message": message, "type": "budget_exceeded"}})),
+        )
+            .into_response();
+    }
+
     let provider_name = match split_model_id(&req.model) {
         Some((p, _)) => p.to_string(),
         None => {
@@ -290,219 +1503,175 @@ async fn chat_completions(
                 .into_response();
         }
     };
+    log.set_model(&req.model);
+    if let Some(message) = state.maintenance(&provider_name) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": {"message": message, "type": "provider_unavailable"}})),
+        )
+            .into_response();
+    }
+
+    let transformer = state.transformers.for_provider(&settings, &provider_name, &req.model);
+    let mut upstream_headers = None;
+    if let Some(t) = &transformer {
+        match transform_request::<ChatCompletionRequest>(t, &mut body) {
+            Ok((r, headers)) => {
+                req = ChatCompletionRequest { model: req.model, ..r };
+                upstream_headers = headers;
+            }
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": {"message": message, "type": "invalid_request_error"}})),
+                )
+                    .into_response();
+            }
+        }
+    }
 
     let client_arc = {
         let client = state.client.read().await;
         Arc::new((*client).clone())
     };
 
-    if client_arc.get_model(&req.model).is_none() {
+    let Some(model_def) = client_arc.get_model(&req.model).cloned() else {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({"error": {"message": format!("Model not found: {}", req.model)}})),
         )
             .into_response();
-    }
+    };
 
     let (system_prompt, messages) = convert_openai_messages(&req.messages);
     let tools = req.tools.as_ref().map(|t| convert_openai_tools(t)).unwrap_or_default();
 
-    let context = ChatContext {
+    let mut context = ChatContext {
         system_prompt,
         messages,
         tools,
     };
 
-    let base_options = RequestOptions {
+    let features = RequestFeatures {
+        audio: openai_request_has_audio(&req.messages),
+        json_mode: openai_json_mode(&req),
+    };
+    let mut warnings = match adapt_to_model(&req.model, &model_def, &mut context, &features) {
+        Ok(w) => w,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {"message": message, "type": "invalid_request_error"}})),
+            )
+                .into_response();
+        }
+    };
+
+    let mut context = state.compress_context(&settings, &client_arc, log.source(), context).await;
+    match guard_context(&settings, &req.model, &model_def, &mut context) {
+        Ok(warning) => warnings.extend(warning),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {
+                    "message": e.message(),
+                    "type": "invalid_request_error",
+                    "code": "context_limit_exceeded",
+                    "context_limit": e.details(),
+                }})),
+            )
+                .into_response();
+        }
+    }
+
+    let mut base_options = RequestOptions {
         temperature: req.temperature,
         max_tokens: req.max_tokens,
         reasoning: None,
+        thinking_budget: None,
         api_key: None,
-        extra_headers: None,
-        retry_config: None,
+        extra_headers: with_request_id_header(&model_def, log.id(), upstream_headers),
+        retry_config: settings.retry.clone(),
+        attempt_budget: settings.retry.as_ref().and_then(AttemptBudget::from_config),
+        web_search: req.web_search_options.is_some(),
+        code_execution: false,
+        capture: state.capture(log.id()),
+        response_headers: Some(ResponseHeaders::new()),
+        anthropic_betas: Default::default(),
+        user: req.user.clone(),
+        metadata: req.metadata.clone(),
+        store: req.store,
+        extra_body: extra_body(&req.extra),
+        provider_extra,
     };
+    apply_ollama_keep_alive(&settings, &model_def, &mut base_options);
+    apply_openrouter_fields(&req, &model_def, &mut base_options);
 
     let is_stream = req.stream.unwrap_or(false);
 
     if is_stream {
-        // Streaming rotation strategy:
-        // - pick first healthy account
-        // - if the stream fails with 429 BEFORE any content/tool events are emitted, rotate+retry with next account
-        // - once anything is emitted, we cannot safely restart; return the error
-        let provider_name2 = provider_name.clone();
-        let state2 = state.clone();
-        let model = req.model.clone();
-        let ctx = context.clone();
-        let opts0 = base_options.clone();
-        let client_arc2 = client_arc.clone();
-
-        let event_stream = async_stream::stream! {
-            let mut attempt: usize = 0;
-            let max_attempts: usize = state2.config.list_accounts(&provider_name2).map(|v| v.len().max(1)).unwrap_or(1);
-
-            loop {
-                let mut emitted_any = false;
-                let sel = match state2.resolve_account(&provider_name2).await {
-                    Some(s) => s,
-                    None => {
-                        yield Err(zeroai::ProviderError::AuthRequired(format!("No credentials for provider: {}", provider_name2)));
-                        return;
-                    }
-                };
-
-                let mut opts = opts0.clone();
-                opts.api_key = Some(sel.api_key.clone());
-
-                let mut inner = match client_arc2.stream(&model, &ctx, &opts) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        yield Err(e);
-                        return;
-                    }
-                };
-
-                while let Some(item) = inner.next().await {
-                    match item {
-                        Ok(evt) => {
-                            match &evt {
-                                StreamEvent::TextDelta(_) | StreamEvent::ThinkingDelta(_) | StreamEvent::ToolCallStart {..} | StreamEvent::ToolCallDelta {..} | StreamEvent::ToolCallEnd {..} | StreamEvent::Done {..} => {
-                                    emitted_any = true;
-                                }
-                                _ => {}
-                            }
-                            yield Ok(evt);
-                        }
-                        Err(e) => {
-                            if !emitted_any && retry_helpers::is_rate_limited(&e) && attempt + 1 < max_attempts {
-                                let backoff_ms = retry_helpers::parse_retry_after_ms(&e).unwrap_or(60_000);
-                                let _ = state2.config.rate_limit_account(&provider_name2, &sel.account_id, backoff_ms);
-                                attempt += 1;
-                                // retry outer loop
-                                break;
-                            }
-                            yield Err(e);
-                            return;
-                        }
-                    }
-                }
-
-                if attempt + 1 >= max_attempts {
-                    return;
-                }
-
-                // if inner ended without error, we're done
-                if emitted_any {
-                    return;
-                }
-            }
-        };
-
-        let event_stream: futures::stream::BoxStream<'static, Result<StreamEvent, zeroai::ProviderError>> = Box::pin(event_stream);
+        let event_stream = account_stream(
+            state.clone(),
+            client_arc.clone(),
+            log.clone(),
+            req.model.clone(),
+            model_def.cost.clone(),
+            StreamRequest::Chat(context.clone()),
+            base_options.clone(),
+        );
 
         // Map to OpenAI SSE
-        let event_stream = event_stream;
-
-
-        let model_name = req.model.clone();
-        let sse = event_stream.filter_map(move |event| {
-            let model_name = model_name.clone();
-            async move {
-                match event {
-                    Ok(StreamEvent::TextDelta(delta)) => {
-                        let chunk = json!({
-                            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                            "object": "chat.completion.chunk",
-                            "created": chrono::Utc::now().timestamp(),
-                            "model": model_name,
-                            "choices": [{
-                                "index": 0,
-                                "delta": {"content": delta},
-                                "finish_reason": null
-                            }]
-                        });
-                        Some(Ok::<_, std::convert::Infallible>(
-                            Event::default().data(chunk.to_string()),
-                        ))
-                    }
-                    Ok(StreamEvent::ToolCallStart { index, id, name }) => {
-                        let chunk = json!({
-                            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                            "object": "chat.completion.chunk",
-                            "created": chrono::Utc::now().timestamp(),
-                            "model": model_name,
-                            "choices": [{
-                                "index": 0,
-                                "delta": {
-                                    "tool_calls": [{
-                                        "index": index,
-                                        "id": id,
-                                        "type": "function",
-                                        "function": {"name": name, "arguments": ""}
-                                    }]
-                                },
-                                "finish_reason": null
-                            }]
-                        });
-                        Some(Ok(Event::default().data(chunk.to_string())))
-                    }
-                    Ok(StreamEvent::ToolCallDelta { index, delta }) => {
-                        let chunk = json!({
-                            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                            "object": "chat.completion.chunk",
-                            "created": chrono::Utc::now().timestamp(),
-                            "model": model_name,
-                            "choices": [{
-                                "index": 0,
-                                "delta": {
-                                    "tool_calls": [{
-                                        "index": index,
-                                        "function": {"arguments": delta}
-                                    }]
-                                },
-                                "finish_reason": null
-                            }]
-                        });
-                        Some(Ok(Event::default().data(chunk.to_string())))
+        let mut writer = sse::ChunkWriter::new(&req.model, transformer);
+        let events = async_stream::stream! {
+            futures::pin_mut!(event_stream);
+            while let Some(event) = event_stream.next().await {
+                yield match event {
+                    Ok(StreamEvent::TextDelta(delta)) => writer.content(&delta),
+                    Ok(StreamEvent::RefusalDelta(refusal)) => writer.delta(&json!({"refusal": refusal})),
+                    Ok(StreamEvent::CitationDelta(citation)) => {
+                        writer.delta(&json!({"annotations": [openai_annotation(&citation)]}))
                     }
+                    Ok(StreamEvent::ExecutableCode(code)) => writer.content(&executable_code_markdown(&code)),
+                    Ok(StreamEvent::CodeExecutionResult(result)) => writer.content(&code_execution_result_markdown(&result)),
+                    Ok(StreamEvent::ToolCallStart { index, id, name }) => writer.delta(&json!({
+                        "tool_calls": [{
+                            "index": index,
+                            "id": id,
+                            "type": "function",
+                            "function": {"name": name, "arguments": ""}
+                        }]
+                    })),
+                    Ok(StreamEvent::ToolCallDelta { index, delta }) => writer.delta(&json!({
+                        "tool_calls": [{
+                            "index": index,
+                            "function": {"arguments": delta}
+                        }]
+                    })),
                     Ok(StreamEvent::Done { message }) => {
-                        let reason = match message.stop_reason {
-                            StopReason::Stop => "stop",
-                            StopReason::Length => "length",
-                            StopReason::ToolUse => "tool_calls",
-                            _ => "stop",
-                        };
-                        let chunk = json!({
-                            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
-                            "object": "chat.completion.chunk",
-                            "created": chrono::Utc::now().timestamp(),
-                            "model": model_name,
-                            "choices": [{
-                                "index": 0,
-                                "delta": {},
-                                "finish_reason": reason
-                            }],
-                            "usage": message.usage.as_ref().map(|u| json!({
-                                "prompt_tokens": u.input_tokens,
-                                "completion_tokens": u.output_tokens,
-                                "total_tokens": u.total_tokens,
-                            }))
-                        });
-                        Some(Ok(Event::default().data(chunk.to_string())))
-                    }
-                    Ok(StreamEvent::Error { message }) => {
-                        let chunk = json!({
-                            "error": {"message": message.content.iter().filter_map(|b| {
-                                if let ContentBlock::Text(t) = b { Some(t.text.as_str()) } else { None }
-                            }).collect::<Vec<_>>().join("")}
+                        let mut choice = json!({
+                            "index": 0,
+                            "delta": {},
+                            "finish_reason": openai_finish_reason(&message.stop_reason)
                         });
-                        Some(Ok(Event::default().data(chunk.to_string())))
+                        if !message.safety_ratings.is_empty() {
+                            choice["safety_ratings"] = json!(message.safety_ratings);
+                        }
+                        let mut chunk = writer.chunk(choice);
+                        chunk["usage"] = json!(message.usage.as_ref().map(openai_usage));
+                        writer.event(chunk)
                     }
-                    _ => None,
-                }
+                    Ok(StreamEvent::Error { message }) => writer.event(json!({
+                        "error": {"message": message.content.iter().filter_map(|b| {
+                            if let ContentBlock::Text(t) = b { Some(t.text.as_str()) } else { None }
+                        }).collect::<Vec<_>>().join("")}
+                    })),
+                    Err(e) => writer.event(json!({"error": upstream_error(&e)})),
+                    _ => continue,
+                };
             }
-        });
+        };
 
-        Sse::new(sse).into_response()
+        with_warnings(sse::response(events), &warnings)
     } else {
         // Non-streaming: rotate accounts on 429.
         let max_attempts: usize = state
@@ -527,15 +1696,27 @@ async fn chat_completions(
             let mut options = base_options.clone();
             options.api_key = Some(sel.api_key.clone());
 
-            match client_arc.chat(&req.model, &context, &options).await {
+            let result = client_arc.chat(&req.model, &context, &options).await;
+            state.track_response(&provider_name, &sel.account_id, options.response_headers.as_ref(), &log);
+            match result {
                 Ok(msg) => {
+                    state.record_usage(&provider_name, &sel.account_id, log.source(), msg.usage.as_ref(), &model_def.cost).await;
+                    state.record_latency(&req.model, msg.usage.as_ref()).await;
+                    shadow::mirror(&state, &client_arc, shadow::Primary { model: &req.model, context: &context, options: &options, message: &msg, cost: &model_def.cost }, &log);
+                    log.record(&provider_name, &sel.account_id, msg.usage.as_ref(), &model_def.cost);
                     // Format OpenAI-compatible response below
                     let mut content_text = String::new();
+                    let mut refusal = String::new();
                     let mut tool_calls_json = Vec::new();
 
                     for block in &msg.content {
                         match block {
                             ContentBlock::Text(t) => content_text.push_str(&t.text),
+                            ContentBlock::Refusal(r) => refusal.push_str(&r.text),
+                            ContentBlock::ExecutableCode(code) => content_text.push_str(&executable_code_markdown(code)),
+                            ContentBlock::CodeExecutionResult(result) => {
+                                content_text.push_str(&code_execution_result_markdown(result))
+                            }
                             ContentBlock::ToolCall(tc) => {
                                 tool_calls_json.push(json!({
                                     "id": tc.id,
@@ -550,14 +1731,9 @@ async fn chat_completions(
                         }
                     }
 
-                    let finish_reason = match msg.stop_reason {
-                        StopReason::Stop => "stop",
-                        StopReason::Length => "length",
-                        StopReason::ToolUse => "tool_calls",
-                        _ => "stop",
-                    };
+                    let finish_reason = openai_finish_reason(&msg.stop_reason);
 
-                    let response = json!({
+                    let mut response = json!({
                         "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
                         "object": "chat.completion",
                         "created": chrono::Utc::now().timestamp(),
@@ -567,26 +1743,27 @@ async fn chat_completions(
                             "message": {
                                 "role": "assistant",
                                 "content": if content_text.is_empty() { serde_json::Value::Null } else { json!(content_text) },
-                                "tool_calls": if tool_calls_json.is_empty() { serde_json::Value::Null } else { json!(tool_calls_json) }
+                                "refusal": if refusal.is_empty() { serde_json::Value::Null } else { json!(refusal) },
+                                "tool_calls": if tool_calls_json.is_empty() { serde_json::Value::Null } else { json!(tool_calls_json) },
+                                "annotations": msg.citations.iter().map(openai_annotation).collect::<Vec<_>>()
                             },
                             "finish_reason": finish_reason
                         }],
-                        "usage": msg.usage.as_ref().map(|u| json!({
-                            "prompt_tokens": u.input_tokens,
-                            "completion_tokens": u.output_tokens,
-                            "total_tokens": u.total_tokens,
-                        }))
+                        "usage": msg.usage.as_ref().map(openai_usage)
                     });
+                    if !msg.safety_ratings.is_empty() {
+                        response["choices"][0]["safety_ratings"] = json!(msg.safety_ratings);
+                    }
 
-                    return Json(response).into_response();
+                    if let Some(t) = &transformer {
+                        t.response(&mut response, false);
+                    }
+                    return with_warnings(Json(response).into_response(), &warnings);
                 }
                 Err(e) => {
-                    if retry_helpers::is_rate_limited(&e) && attempt + 1 < max_attempts {
-                        let backoff_ms = retry_helpers::parse_retry_after_ms(&e).unwrap_or(60_000);
-                        let _ = state
-                            .config
-                            .rate_limit_account(&provider_name, &sel.account_id, backoff_ms);
+                    if state.rate_limit(&provider_name, &sel.account_id, &e) && attempt + 1 < max_attempts && !budget_spent(&base_options) {
                         last_err = Some(e);
+                        log.note_retry();
                         continue;
                     }
                     last_err = Some(e);
@@ -595,44 +1772,267 @@ async fn chat_completions(
             }
         }
 
-        let msg = last_err
-            .map(|e| e.to_string())
-            .unwrap_or_else(|| "No response received".into());
+        let error = last_err
+            .map(|e| upstream_error(&e))
+            .unwrap_or_else(|| json!({"message": "No response received"}));
         (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": {"message": msg}})),
+            Json(json!({"error": error})),
         )
             .into_response()
     }
 }
 
+/// What an [`account_stream`] asks the upstream for.
+enum StreamRequest {
+    Chat(ChatContext),
+    Completion(CompletionRequest),
+}
+
+/// `model`'s events for `request`, from the provider's next account. A stream that hits a rate
+/// limit before emitting anything is retried with the following account; once something is
+/// emitted it can't be restarted, so a later error ends it. Usage, latency and the shadow copy
+/// are recorded when it completes.
+fn account_stream(
+    state: Arc<AppState>,
+    client: Arc<AiClient>,
+    log: RequestLog,
+    model: String,
+    cost: ModelCost,
+    request: StreamRequest,
+    options: RequestOptions,
+) -> impl Stream<Item = Result<StreamEvent, zeroai::ProviderError>> + Send + 'static {
+    let provider = split_model_id(&model).map(|(p, _)| p.to_string()).unwrap_or_default();
+    // A completion's chat form is what a shadow model gets.
+    let (ctx, completion) = match request {
+        StreamRequest::Chat(ctx) => (ctx, None),
+        StreamRequest::Completion(request) => (request.to_context(), Some(request)),
+    };
+    async_stream::stream! {
+        let mut attempt: usize = 0;
+        let max_attempts: usize = state.config.list_accounts(&provider).map(|v| v.len().max(1)).unwrap_or(1);
+
+        loop {
+            let mut emitted_any = false;
+            let mut rotate = false;
+            let sel = match state.resolve_account(&provider).await {
+                Some(s) => s,
+                None => {
+                    yield Err(zeroai::ProviderError::AuthRequired(format!("No credentials for provider: {}", provider)));
+                    return;
+                }
+            };
+
+            let mut opts = options.clone();
+            opts.api_key = Some(sel.api_key.clone());
+
+            let inner = match &completion {
+                Some(request) => client.stream_completion(&model, request, &opts),
+                None => client.stream(&model, &ctx, &opts),
+            };
+            let mut inner = match inner {
+                Ok(s) => s,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+
+            while let Some(item) = inner.next().await {
+                match item {
+                    Ok(evt) => {
+                        match &evt {
+                            StreamEvent::Done { message } => {
+                                emitted_any = true;
+                                state.record_usage(&provider, &sel.account_id, log.source(), message.usage.as_ref(), &cost).await;
+                                state.record_latency(&model, message.usage.as_ref()).await;
+                                shadow::mirror(&state, &client, shadow::Primary { model: &model, context: &ctx, options: &opts, message, cost: &cost }, &log);
+                                state.track_response(&provider, &sel.account_id, opts.response_headers.as_ref(), &log);
+                                log.record(&provider, &sel.account_id, message.usage.as_ref(), &cost);
+                            }
+                            StreamEvent::TextDelta(_) | StreamEvent::RefusalDelta(_) | StreamEvent::ThinkingDelta(_) | StreamEvent::CitationDelta(_) | StreamEvent::ExecutableCode(_) | StreamEvent::CodeExecutionResult(_) | StreamEvent::ToolCallStart {..} | StreamEvent::ToolCallDelta {..} | StreamEvent::ToolCallEnd {..} => {
+                                emitted_any = true;
+                            }
+                            _ => {}
+                        }
+                        yield Ok(evt);
+                    }
+                    Err(e) => {
+                        state.track_response(&provider, &sel.account_id, opts.response_headers.as_ref(), &log);
+                        if !emitted_any && state.rate_limit(&provider, &sel.account_id, &e) && attempt + 1 < max_attempts && !budget_spent(&opts) {
+                            attempt += 1;
+                            rotate = true;
+                            log.note_retry();
+                            break;
+                        }
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+
+            // Retry with the next account only after a rate limit; otherwise the stream is done.
+            if !rotate {
+                return;
+            }
+        }
+    }
+}
+
+/// The `error` object for a failed upstream call. Provider error replies keep their normalized
+/// `code`, `type`, `param` and `upstream_request_id`.
+fn upstream_error(e: &zeroai::ProviderError) -> serde_json::Value {
+    let mut error = match e {
+        zeroai::ProviderError::Http { error, .. } => json!(error),
+        _ => json!({}),
+    };
+    error["message"] = json!(e.to_string());
+    error
+}
+
+/// OpenAI `finish_reason` for a stop reason. Filters and refusals are all `content_filter`;
+/// OpenAI has no paused turns, so those end as `stop`.
+fn openai_finish_reason(reason: &StopReason) -> &'static str {
+    match reason {
+        StopReason::Length => "length",
+        StopReason::ToolUse => "tool_calls",
+        StopReason::ContentFilter | StopReason::Safety | StopReason::Recitation | StopReason::Refusal => "content_filter",
+        StopReason::Stop | StopReason::Error | StopReason::Aborted | StopReason::PauseTurn => "stop",
+    }
+}
+
+/// An Anthropic `usage` object, with zeroai's timing extension.
+fn anthropic_usage(u: &zeroai::types::Usage) -> serde_json::Value {
+    with_timing(
+        json!({
+            "input_tokens": u.input_tokens,
+            "output_tokens": u.output_tokens,
+            "cache_read_input_tokens": u.cache_read_tokens,
+            "cache_creation_input_tokens": u.cache_write_tokens,
+        }),
+        u,
+    )
+}
+
+/// Anthropic `stop_reason` for a stop reason. Content filters of other providers are
+/// `refusal`, the closest Anthropic has.
+fn anthropic_stop_reason(reason: &StopReason) -> &'static str {
+    match reason {
+        StopReason::Length => "max_tokens",
+        StopReason::ToolUse => "tool_use",
+        StopReason::ContentFilter | StopReason::Safety | StopReason::Recitation | StopReason::Refusal => "refusal",
+        StopReason::PauseTurn => "pause_turn",
+        StopReason::Stop | StopReason::Error | StopReason::Aborted => "end_turn",
+    }
+}
+
+/// OpenAI `url_citation` annotation for a citation.
+fn openai_annotation(citation: &Citation) -> serde_json::Value {
+    json!({
+        "type": "url_citation",
+        "url_citation": {
+            "url": citation.url,
+            "title": citation.title,
+            "start_index": citation.start_index,
+            "end_index": citation.end_index,
+        }
+    })
+}
+
+/// Provider-executed code as a fenced block; neither client API has a native equivalent.
+fn executable_code_markdown(code: &ExecutableCode) -> String {
+    format!("\n```{}\n{}\n```\n", code.language.to_lowercase(), code.code.trim_end())
+}
+
+/// Output of provider-executed code as a fenced block, noting failed runs.
+fn code_execution_result_markdown(result: &CodeExecutionResult) -> String {
+    let label = if result.outcome == "OUTCOME_OK" { "output" } else { "error" };
+    format!("\n```{}\n{}\n```\n", label, result.output.trim_end())
+}
+
+/// Upstream headers for a chat call: the transformer's, plus the request ID for APIs that
+/// accept a client request ID (OpenAI's `X-Client-Request-Id`).
+fn with_request_id_header(
+    model: &ModelDef,
+    request_id: &str,
+    headers: Option<HashMap<String, String>>,
+) -> Option<HashMap<String, String>> {
+    if !matches!(model.api, Api::OpenaiCompletions | Api::OpenaiResponses) {
+        return headers;
+    }
+    let mut headers = headers.unwrap_or_default();
+    headers
+        .entry("X-Client-Request-Id".into())
+        .or_insert_with(|| request_id.to_string());
+    Some(headers)
+}
+
+/// Run the provider's `on_request` script on a raw request body and parse it again.
+/// Returns the request and the headers the script added for the upstream call.
+fn transform_request<T: DeserializeOwned>(
+    transformer: &Transformer,
+    body: &mut serde_json::Value,
+) -> Result<(T, Option<HashMap<String, String>>), String> {
+    let headers = transformer.request(body);
+    let req = T::deserialize(&*body).map_err(|e| format!("Transformed request is invalid: {}", e))?;
+    Ok((req, Some(headers).filter(|h| !h.is_empty())))
+}
+
 // ---------------------------------------------------------------------------
 // POST /v1/messages - Anthropic compatible
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
-#[allow(dead_code)]
+#[derive(Deserialize, ToSchema)]
 struct AnthropicRequest {
     model: String,
     messages: Vec<AnthropicMessage>,
     max_tokens: u64,
+    /// A string or an array of text blocks.
     #[serde(default)]
-    system: Option<String>,
+    system: Option<serde_json::Value>,
     #[serde(default)]
     stream: Option<bool>,
     #[serde(default)]
     temperature: Option<f64>,
     #[serde(default)]
     tools: Option<Vec<AnthropicToolReq>>,
+    #[serde(default)]
+    thinking: Option<AnthropicThinking>,
+}
+
+/// `thinking` of a Messages request: `{"type": "enabled", "budget_tokens": N}` or disabled.
+#[derive(Deserialize, ToSchema)]
+struct AnthropicThinking {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    budget_tokens: Option<u64>,
+}
+
+impl AnthropicThinking {
+    fn budget(&self) -> Option<u64> {
+        (self.kind == "enabled").then_some(self.budget_tokens).flatten()
+    }
+}
+
+/// Body of `POST /v1/messages/count_tokens`.
+#[derive(Deserialize, ToSchema)]
+struct AnthropicCountTokensRequest {
+    model: String,
+    messages: Vec<AnthropicMessage>,
+    #[serde(default)]
+    system: Option<serde_json::Value>,
+    #[serde(default)]
+    tools: Option<Vec<AnthropicToolReq>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AnthropicMessage {
     role: String,
     content: serde_json::Value,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AnthropicToolReq {
     name: String,
     description: Option<String>,
@@ -647,10 +2047,33 @@ fn convert_anthropic_messages(
     for msg in msgs {
         match msg.role.as_str() {
             "user" => {
-                let text = msg.content.as_str().unwrap_or("").to_string();
-                messages.push(Message::User(UserMessage {
-                    content: vec![ContentBlock::Text(TextContent { text })],
-                }));
+                let content = match msg.content.as_array() {
+                    Some(blocks) => {
+                        // Tool results become their own messages, ahead of the rest of the turn.
+                        for block in blocks.iter().filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("tool_result")) {
+                            let tool_call_id = block.get("tool_use_id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                            let content = match block.get("content") {
+                                Some(serde_json::Value::String(text)) => vec![ContentBlock::Text(TextContent { text: text.clone() })],
+                                Some(serde_json::Value::Array(items)) => items.iter().filter_map(anthropic_content_block).collect(),
+                                _ => Vec::new(),
+                            };
+                            messages.push(Message::ToolResult(ToolResultMessage {
+                                tool_name: anthropic_tool_name(&messages, &tool_call_id),
+                                tool_call_id,
+                                content,
+                                is_error: block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false),
+                            }));
+                        }
+                        blocks.iter().filter_map(anthropic_content_block).collect()
+                    }
+                    None => {
+                        let text = msg.content.as_str().unwrap_or("").to_string();
+                        vec![ContentBlock::Text(TextContent { text })]
+                    }
+                };
+                if !content.is_empty() {
+                    messages.push(Message::User(UserMessage { content }));
+                }
             }
             "assistant" => {
                 let mut content = Vec::new();
@@ -673,7 +2096,7 @@ fn convert_anthropic_messages(
                                     if let Some(text) = block.get("thinking").and_then(|v| v.as_str()) {
                                         content.push(ContentBlock::Thinking(ThinkingContent {
                                             thinking: text.to_string(),
-                                            signature: None,
+                                            signature: block.get("signature").and_then(|v| v.as_str()).map(String::from),
                                         }));
                                     }
                                 }
@@ -698,6 +2121,8 @@ fn convert_anthropic_messages(
                     provider: String::new(),
                     usage: None,
                     stop_reason: StopReason::Stop,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
                 }));
             }
             _ => {}
@@ -707,34 +2132,61 @@ fn convert_anthropic_messages(
     messages
 }
 
-async fn anthropic_messages(
-    State(state): State<Arc<AppState>>,
-    Json(req): Json<AnthropicRequest>,
-) -> Response {
-    let provider_name = match split_model_id(&req.model) {
-        Some((p, _)) => p.to_string(),
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": "Invalid model ID format"}})),
-            )
-                .into_response();
+/// A text or base64 image block from Anthropic user or `tool_result` content.
+fn anthropic_content_block(block: &serde_json::Value) -> Option<ContentBlock> {
+    match block.get("type").and_then(|v| v.as_str()) {
+        Some("text") => block
+            .get("text")
+            .and_then(|v| v.as_str())
+            .map(|text| ContentBlock::Text(TextContent { text: text.to_string() })),
+        Some("image") if block.pointer("/source/type").and_then(|v| v.as_str()) == Some("base64") => {
+            Some(ContentBlock::Image(ImageContent {
+                data: block.pointer("/source/data").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                mime_type: block.pointer("/source/media_type").and_then(|v| v.as_str()).unwrap_or("image/png").to_string(),
+            }))
         }
-    };
+        _ => None,
+    }
+}
 
-    let client = state.client.read().await;
-    if client.get_model(&req.model).is_none() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(json!({"type": "error", "error": {"type": "not_found_error", "message": format!("Model not found: {}", req.model)}})),
-        )
-            .into_response();
+/// Name of the tool called as `tool_call_id` in an earlier assistant message.
+fn anthropic_tool_name(messages: &[Message], tool_call_id: &str) -> String {
+    messages
+        .iter()
+        .rev()
+        .filter_map(|m| match m {
+            Message::Assistant(a) => Some(&a.content),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|b| match b {
+            ContentBlock::ToolCall(tc) if tc.id == tool_call_id => Some(tc.name.clone()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// System prompt text from an Anthropic `system` field (a string or text blocks).
+fn anthropic_system_text(system: &serde_json::Value) -> Option<String> {
+    match system {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(blocks) => {
+            let texts: Vec<&str> = blocks
+                .iter()
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect();
+            (!texts.is_empty()).then(|| texts.join("\n\n"))
+        }
+        _ => None,
     }
+}
 
-    let messages = convert_anthropic_messages(&req.messages);
-    let tools = req
-        .tools
-        .as_ref()
+fn anthropic_context(
+    messages: &[AnthropicMessage],
+    system: Option<&serde_json::Value>,
+    tools: Option<&[AnthropicToolReq]>,
+) -> ChatContext {
+    let tools = tools
         .map(|t| {
             t.iter()
                 .map(|tool| ToolDef {
@@ -746,20 +2198,211 @@ async fn anthropic_messages(
         })
         .unwrap_or_default();
 
-    let context = ChatContext {
-        system_prompt: req.system.clone(),
-        messages,
+    ChatContext {
+        system_prompt: system.and_then(anthropic_system_text),
+        messages: convert_anthropic_messages(messages),
         tools,
+    }
+}
+
+#[utoipa::path(post, path = "/v1/messages", tag = "anthropic", request_body = AnthropicRequest, responses(
+    (status = 200, description = "A message, or with `stream: true` its events as server-sent events",
+        content((serde_json::Value = "application/json"), (String = "text/event-stream"))),
+    (status = 400, description = "Invalid request, unknown model or unsupported feature", body = serde_json::Value),
+    (status = 429, description = "The 24h spending budget is used up, or every account is rate limited", body = serde_json::Value),
+))]
+async fn anthropic_messages(
+    State(state): State<Arc<AppState>>,
+    Extension(log): Extension<RequestLog>,
+    headers: HeaderMap,
+    Json(mut body): Json<serde_json::Value>,
+) -> Response {
+    log.set_request_hash(idempotency::request_hash("/v1/messages", &body));
+    let mut req = match AnthropicRequest::deserialize(&body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": format!("Invalid request: {}", e)}})),
+            )
+                .into_response();
+        }
+    };
+    let settings = state.config.get_settings().unwrap_or_default();
+    apply_experiment(&settings, &mut req.model, &log);
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    if routing::is_routed(&req.model) {
+        let context = anthropic_context(&req.messages, req.system.as_ref(), req.tools.as_deref());
+        let requirements = ModelRequirements::for_context(&context, Some(req.max_tokens));
+        match state.route_model(&req.model, &requirements).await {
+            Ok(model) => req.model = model,
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": message}})),
+                )
+                    .into_response();
+            }
+        }
+    }
+    if let Some(message) = state.budget_exceeded(&settings, &log).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"type": "error", "error": {"type": "rate_limit_error", "message": message}})),
+        )
+            .into_response();
+    }
+
+    let provider_name = match split_model_id(&req.model) {
+        Some((p, _)) => p.to_string(),
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": "Invalid model ID format"}})),
+            )
+                .into_response();
+        }
+    };
+    log.set_model(&req.model);
+    if let Some(message) = state.maintenance(&provider_name) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"type": "error", "error": {"type": "overloaded_error", "message": message}})),
+        )
+            .into_response();
+    }
+
+    let transformer = state.transformers.for_provider(&settings, &provider_name, &req.model);
+    let mut upstream_headers = None;
+    if let Some(t) = &transformer {
+        match transform_request::<AnthropicRequest>(t, &mut body) {
+            Ok((r, headers)) => {
+                req = AnthropicRequest { model: req.model, ..r };
+                upstream_headers = headers;
+            }
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": message}})),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    let client = state.client.read().await;
+    let Some(model_def) = client.get_model(&req.model).cloned() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"type": "error", "error": {"type": "not_found_error", "message": format!("Model not found: {}", req.model)}})),
+        )
+            .into_response();
+    };
+
+    let mut context = anthropic_context(&req.messages, req.system.as_ref(), req.tools.as_deref());
+
+    let mut warnings = match adapt_to_model(&req.model, &model_def, &mut context, &RequestFeatures::default()) {
+        Ok(w) => w,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": message}})),
+            )
+                .into_response();
+        }
     };
 
-    let base_options = RequestOptions {
+    let mut context = state.compress_context(&settings, &client, log.source(), context).await;
+    match guard_context(&settings, &req.model, &model_def, &mut context) {
+        Ok(warning) => warnings.extend(warning),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"type": "error", "error": {
+                    "type": "invalid_request_error",
+                    "message": e.message(),
+                    "context_limit": e.details(),
+                }})),
+            )
+                .into_response();
+        }
+    }
+
+    let mut base_options = RequestOptions {
         temperature: req.temperature,
         max_tokens: Some(req.max_tokens),
         reasoning: None,
+        thinking_budget: req.thinking.as_ref().and_then(AnthropicThinking::budget),
         api_key: None,
-        extra_headers: None,
-        retry_config: None,
+        extra_headers: with_request_id_header(&model_def, log.id(), upstream_headers),
+        retry_config: settings.retry.clone(),
+        attempt_budget: settings.retry.as_ref().and_then(AttemptBudget::from_config),
+        web_search: false,
+        code_execution: false,
+        capture: state.capture(log.id()),
+        response_headers: Some(ResponseHeaders::new()),
+        anthropic_betas: headers
+            .get(AnthropicBetas::HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(AnthropicBetas::from_header)
+            .unwrap_or_default(),
+        user: None,
+        metadata: None,
+        store: None,
+        extra_body: None,
+        provider_extra: serde_json::Value::Null,
     };
+    apply_ollama_keep_alive(&settings, &model_def, &mut base_options);
+
+    if req.stream.unwrap_or(false) {
+        let event_stream = account_stream(
+            state.clone(),
+            Arc::new((*client).clone()),
+            log.clone(),
+            req.model.clone(),
+            model_def.cost.clone(),
+            StreamRequest::Chat(context),
+            base_options,
+        );
+        let mut writer = sse::MessageWriter::new(&req.model, transformer);
+        let events = async_stream::stream! {
+            yield writer.start();
+            futures::pin_mut!(event_stream);
+            while let Some(event) = event_stream.next().await {
+                yield match event {
+                    Ok(StreamEvent::TextDelta(text) | StreamEvent::RefusalDelta(text)) => writer.text(&text),
+                    Ok(StreamEvent::ThinkingDelta(thinking)) => writer.thinking(&thinking),
+                    Ok(StreamEvent::ThoughtSignature(signature)) => writer.signature(&signature),
+                    Ok(StreamEvent::ExecutableCode(code)) => writer.text(&executable_code_markdown(&code)),
+                    Ok(StreamEvent::CodeExecutionResult(result)) => writer.text(&code_execution_result_markdown(&result)),
+                    Ok(StreamEvent::ToolCallStart { id, name, .. }) => writer.tool_use(&id, &name),
+                    Ok(StreamEvent::ToolCallDelta { delta, .. }) => writer.input_json(&delta),
+                    Ok(StreamEvent::ToolCallEnd { tool_call, .. }) => writer.tool_use_end(&tool_call.arguments),
+                    Ok(StreamEvent::Done { message }) => {
+                        let usage = message.usage.as_ref().map(anthropic_usage).unwrap_or_else(|| json!({"output_tokens": 0}));
+                        writer.finish(anthropic_stop_reason(&message.stop_reason), usage)
+                    }
+                    Ok(StreamEvent::Error { message }) => {
+                        let text: String = message.content.iter().filter_map(|b| {
+                            if let ContentBlock::Text(t) = b { Some(t.text.as_str()) } else { None }
+                        }).collect();
+                        yield writer.error(json!({"type": "api_error", "message": text}));
+                        return;
+                    }
+                    Err(e) => {
+                        let mut error = upstream_error(&e);
+                        if error["type"].is_null() {
+                            error["type"] = json!("api_error");
+                        }
+                        yield writer.error(error);
+                        return;
+                    }
+                    _ => continue,
+                };
+            }
+        };
+        return with_warnings(sse::response(events), &warnings);
+    }
 
     let max_attempts: usize = state
         .config
@@ -785,18 +2428,21 @@ async fn anthropic_messages(
         let mut options = base_options.clone();
         options.api_key = Some(sel.api_key.clone());
 
-        match client.chat(&req.model, &context, &options).await {
+        let result = client.chat(&req.model, &context, &options).await;
+        state.track_response(&provider_name, &sel.account_id, options.response_headers.as_ref(), &log);
+        match result {
             Ok(m) => {
+                state.record_usage(&provider_name, &sel.account_id, log.source(), m.usage.as_ref(), &model_def.cost).await;
+                state.record_latency(&req.model, m.usage.as_ref()).await;
+                shadow::mirror(&state, &client, shadow::Primary { model: &req.model, context: &context, options: &options, message: &m, cost: &model_def.cost }, &log);
+                log.record(&provider_name, &sel.account_id, m.usage.as_ref(), &model_def.cost);
                 msg_opt = Some(m);
                 break;
             }
             Err(e) => {
-                if retry_helpers::is_rate_limited(&e) && attempt + 1 < max_attempts {
-                    let backoff_ms = retry_helpers::parse_retry_after_ms(&e).unwrap_or(60_000);
-                    let _ = state
-                        .config
-                        .rate_limit_account(&provider_name, &sel.account_id, backoff_ms);
+                if state.rate_limit(&provider_name, &sel.account_id, &e) && attempt + 1 < max_attempts && !budget_spent(&base_options) {
                     last_err = Some(e);
+                    log.note_retry();
                     continue;
                 }
                 last_err = Some(e);
@@ -808,12 +2454,15 @@ async fn anthropic_messages(
     let msg = match msg_opt {
         Some(m) => m,
         None => {
-            let message = last_err
-                .map(|e| e.to_string())
-                .unwrap_or_else(|| "No response".into());
+            let mut error = last_err
+                .map(|e| upstream_error(&e))
+                .unwrap_or_else(|| json!({"message": "No response"}));
+            if error["type"].is_null() {
+                error["type"] = json!("api_error");
+            }
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"type": "error", "error": {"type": "api_error", "message": message}})),
+                Json(json!({"type": "error", "error": error})),
             )
                 .into_response();
         }
@@ -825,8 +2474,22 @@ async fn anthropic_messages(
             ContentBlock::Text(t) => {
                 content_blocks.push(json!({"type": "text", "text": t.text}));
             }
+            // Anthropic has no refusal block; its own refusals arrive as text.
+            ContentBlock::Refusal(r) => {
+                content_blocks.push(json!({"type": "text", "text": r.text}));
+            }
             ContentBlock::Thinking(th) => {
-                content_blocks.push(json!({"type": "thinking", "thinking": th.thinking}));
+                let mut block = json!({"type": "thinking", "thinking": th.thinking});
+                if let Some(sig) = &th.signature {
+                    block["signature"] = json!(sig);
+                }
+                content_blocks.push(block);
+            }
+            ContentBlock::ExecutableCode(code) => {
+                content_blocks.push(json!({"type": "text", "text": executable_code_markdown(code)}));
+            }
+            ContentBlock::CodeExecutionResult(result) => {
+                content_blocks.push(json!({"type": "text", "text": code_execution_result_markdown(result)}));
             }
             ContentBlock::ToolCall(tc) => {
                 content_blocks.push(json!({
@@ -840,27 +2503,1683 @@ async fn anthropic_messages(
         }
     }
 
-    let stop_reason = match msg.stop_reason {
-        StopReason::Stop => "end_turn",
-        StopReason::Length => "max_tokens",
-        StopReason::ToolUse => "tool_use",
-        _ => "end_turn",
-    };
+    let stop_reason = anthropic_stop_reason(&msg.stop_reason);
 
-    let response = json!({
+    let mut response = json!({
         "id": format!("msg_{}", uuid::Uuid::new_v4()),
         "type": "message",
         "role": "assistant",
         "content": content_blocks,
         "model": req.model,
         "stop_reason": stop_reason,
-        "usage": msg.usage.as_ref().map(|u| json!({
-            "input_tokens": u.input_tokens,
-            "output_tokens": u.output_tokens,
-            "cache_read_input_tokens": u.cache_read_tokens,
-            "cache_creation_input_tokens": u.cache_write_tokens,
-        }))
+        "usage": msg.usage.as_ref().map(anthropic_usage),
     });
+    if !msg.safety_ratings.is_empty() {
+        response["safety_ratings"] = json!(msg.safety_ratings);
+    }
+    if let Some(t) = &transformer {
+        t.response(&mut response, false);
+    }
+
+    with_warnings(Json(response).into_response(), &warnings)
+}
+
+// ---------------------------------------------------------------------------
+// POST /v1/messages/count_tokens - Anthropic compatible
+// ---------------------------------------------------------------------------
+
+/// Counted by the provider when it supports it (Anthropic), estimated locally otherwise.
+#[utoipa::path(post, path = "/v1/messages/count_tokens", tag = "anthropic", request_body = AnthropicCountTokensRequest, responses(
+    (status = 200, description = "`{\"input_tokens\": N}`", body = serde_json::Value),
+    (status = 400, description = "Invalid model ID", body = serde_json::Value),
+))]
+async fn anthropic_count_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(log): Extension<RequestLog>,
+    Json(mut req): Json<AnthropicCountTokensRequest>,
+) -> Response {
+    let settings = state.config.get_settings().unwrap_or_default();
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    let Some((provider_name, _)) = split_model_id(&req.model) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": "Invalid model ID format"}})),
+        )
+            .into_response();
+    };
+    let provider_name = provider_name.to_string();
+    log.set_model(&req.model);
+
+    let client = state.client.read().await.clone();
+    if client.get_model(&req.model).is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"type": "error", "error": {"type": "not_found_error", "message": format!("Model not found: {}", req.model)}})),
+        )
+            .into_response();
+    }
+
+    let context = anthropic_context(&req.messages, req.system.as_ref(), req.tools.as_deref());
+    let options = RequestOptions {
+        api_key: state.resolve_account(&provider_name).await.map(|sel| sel.api_key),
+        ..RequestOptions::default()
+    };
+    let input_tokens = match client.count_tokens(&req.model, &context, &options).await {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::debug!("Token count for {} failed, estimating locally: {}", req.model, e);
+            zeroai::tokens::estimate_input_tokens(&context)
+        }
+    };
+
+    Json(json!({"input_tokens": input_tokens})).into_response()
+}
+
+// ---------------------------------------------------------------------------
+// POST /v1/completions - OpenAI legacy text completions
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, ToSchema)]
+struct TextCompletionRequest {
+    model: String,
+    /// A string, or an array holding one string.
+    prompt: serde_json::Value,
+    /// Text after the completion, for fill-in-the-middle.
+    #[serde(default)]
+    suffix: Option<String>,
+    #[serde(default)]
+    stream: Option<bool>,
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default)]
+    max_tokens: Option<u64>,
+    #[serde(default)]
+    user: Option<String>,
+    /// Any other top-level fields (`top_p`, `stop`, ...), passed on upstream.
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// The prompt of a request; batches of prompts are not supported.
+fn completion_prompt(prompt: &serde_json::Value) -> Option<String> {
+    match prompt {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(items) if items.len() == 1 => items[0].as_str().map(String::from),
+        _ => None,
+    }
+}
+
+/// Sent to the provider's own `/completions` endpoint where it has one (vLLM, Fireworks, and
+/// declarative providers with `"completions": true`); other models answer it as a chat.
+#[utoipa::path(post, path = "/v1/completions", tag = "completions", request_body = TextCompletionRequest, responses(
+    (status = 200, description = "A text completion, or with `stream: true` its chunks as server-sent events",
+        content((serde_json::Value = "application/json"), (String = "text/event-stream"))),
+    (status = 400, description = "Invalid request or unknown model", body = serde_json::Value),
+    (status = 429, description = "The 24h spending budget is used up, or every account is rate limited", body = serde_json::Value),
+))]
+async fn completions(
+    State(state): State<Arc<AppState>>,
+    Extension(log): Extension<RequestLog>,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    log.set_request_hash(idempotency::request_hash("/v1/completions", &body));
+    let mut req = match TextCompletionRequest::deserialize(&body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {"message": format!("Invalid request: {}", e), "type": "invalid_request_error"}})),
+            )
+                .into_response();
+        }
+    };
+    let Some(prompt) = completion_prompt(&req.prompt) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "prompt must be a string or an array of one string", "type": "invalid_request_error"}})),
+        )
+            .into_response();
+    };
+    let settings = state.config.get_settings().unwrap_or_default();
+    if let Some(user) = &req.user {
+        log.set_user(user);
+    }
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    if let Some(message) = state.budget_exceeded(&settings, &log).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"error": {"message": message, "type": "budget_exceeded"}})),
+        )
+            .into_response();
+    }
+
+    let Some((provider_name, _)) = split_model_id(&req.model) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "Invalid model ID format"}})),
+        )
+            .into_response();
+    };
+    let provider_name = provider_name.to_string();
+    log.set_model(&req.model);
+    if let Some(message) = state.maintenance(&provider_name) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": {"message": message, "type": "provider_unavailable"}})),
+        )
+            .into_response();
+    }
 
-    Json(response).into_response()
+    let client = Arc::new(state.client.read().await.clone());
+    let Some(model_def) = client.get_model(&req.model).cloned() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": {"message": format!("Model not found: {}", req.model), "type": "invalid_request_error"}})),
+        )
+            .into_response();
+    };
+
+    let mut options = RequestOptions {
+        temperature: req.temperature,
+        max_tokens: req.max_tokens,
+        extra_headers: with_request_id_header(&model_def, log.id(), None),
+        retry_config: settings.retry.clone(),
+        attempt_budget: settings.retry.as_ref().and_then(AttemptBudget::from_config),
+        capture: state.capture(log.id()),
+        response_headers: Some(ResponseHeaders::new()),
+        user: req.user.clone(),
+        extra_body: extra_body(&req.extra),
+        ..RequestOptions::default()
+    };
+    apply_ollama_keep_alive(&settings, &model_def, &mut options);
+    let request = CompletionRequest {
+        suffix: req.suffix.take(),
+        ..CompletionRequest::new(prompt)
+    };
+    let event_stream = account_stream(
+        state.clone(),
+        client,
+        log.clone(),
+        req.model.clone(),
+        model_def.cost.clone(),
+        StreamRequest::Completion(request),
+        options,
+    );
+
+    if req.stream.unwrap_or(false) {
+        let mut writer = sse::CompletionWriter::new(&req.model);
+        let events = async_stream::stream! {
+            futures::pin_mut!(event_stream);
+            while let Some(event) = event_stream.next().await {
+                yield match event {
+                    Ok(StreamEvent::TextDelta(text)) => writer.text(&text),
+                    Ok(StreamEvent::Done { message }) => {
+                        let usage = json!(message.usage.as_ref().map(openai_usage));
+                        writer.finish(openai_finish_reason(&message.stop_reason), usage)
+                    }
+                    Ok(StreamEvent::Error { message }) => writer.error(json!({"message": message_text(&message)})),
+                    Err(e) => writer.error(upstream_error(&e)),
+                    _ => continue,
+                };
+            }
+        };
+        return sse::response(events);
+    }
+
+    futures::pin_mut!(event_stream);
+    let mut error = json!({"message": "No response received"});
+    while let Some(event) = event_stream.next().await {
+        match event {
+            Ok(StreamEvent::Done { message }) => {
+                return Json(json!({
+                    "id": format!("cmpl-{}", uuid::Uuid::new_v4()),
+                    "object": "text_completion",
+                    "created": chrono::Utc::now().timestamp(),
+                    "model": req.model,
+                    "choices": [{
+                        "index": 0,
+                        "text": message_text(&message),
+                        "logprobs": null,
+                        "finish_reason": openai_finish_reason(&message.stop_reason),
+                    }],
+                    "usage": message.usage.as_ref().map(openai_usage),
+                }))
+                .into_response();
+            }
+            Ok(StreamEvent::Error { message }) => error = json!({"message": message_text(&message)}),
+            Err(e) => error = upstream_error(&e),
+            _ => {}
+        }
+    }
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": error}))).into_response()
+}
+
+/// Fields Copilot editor plugins add to their requests for GitHub's own backend: the editor
+/// context (`extra`: `language`, `next_indent`...), the repository (`nwo`) and `intent`.
+const COPILOT_FIELDS: &[&str] = &["extra", "nwo", "intent"];
+
+fn strip_copilot_fields(body: &mut serde_json::Value) {
+    if let Some(fields) = body.as_object_mut() {
+        fields.retain(|name, _| !COPILOT_FIELDS.contains(&name.as_str()));
+    }
+}
+
+/// The completions route of editors' Copilot-style inline completion clients. The engine is
+/// the model ID, or an alias for one (map `copilot-codex` in `model_aliases`).
+#[utoipa::path(post, path = "/v1/engines/{engine}/completions", tag = "completions", request_body = TextCompletionRequest,
+    params(("engine" = String, Path, description = "Model ID or alias; overrides any `model` in the body")),
+    responses(
+        (status = 200, description = "A text completion, or with `stream: true` its chunks as server-sent events",
+            content((serde_json::Value = "application/json"), (String = "text/event-stream"))),
+        (status = 400, description = "Invalid request or unknown model", body = serde_json::Value),
+    ),
+)]
+async fn engine_completions(
+    state: State<Arc<AppState>>,
+    log: Extension<RequestLog>,
+    Path(engine): Path<String>,
+    Json(mut body): Json<serde_json::Value>,
+) -> Response {
+    strip_copilot_fields(&mut body);
+    if let Some(fields) = body.as_object_mut() {
+        fields.insert("model".into(), json!(engine));
+    }
+    completions(state, log, Json(body)).await
+}
+
+/// `/chat/completions` without the `/v1`, where Copilot chat clients with a proxy URL send
+/// their OpenAI-format requests.
+async fn copilot_chat_completions(
+    state: State<Arc<AppState>>,
+    log: Extension<RequestLog>,
+    headers: HeaderMap,
+    Json(mut body): Json<serde_json::Value>,
+) -> Response {
+    strip_copilot_fields(&mut body);
+    chat_completions(state, log, headers, Json(body)).await
+}
+
+/// The text blocks of `message`, joined.
+fn message_text(message: &AssistantMessage) -> String {
+    message
+        .content
+        .iter()
+        .filter_map(|b| if let ContentBlock::Text(t) = b { Some(t.text.as_str()) } else { None })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// POST /v1/embeddings - OpenAI compatible
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, ToSchema)]
+struct OpenAiEmbeddingRequest {
+    model: String,
+    /// A string or an array of strings.
+    input: serde_json::Value,
+    #[serde(default)]
+    dimensions: Option<u32>,
+    /// `float` (default) or `base64`.
+    #[serde(default)]
+    encoding_format: Option<String>,
+}
+
+/// `input` as a list of texts; `None` for token-array inputs, which are not supported.
+fn embedding_inputs(input: &serde_json::Value) -> Option<Vec<String>> {
+    match input {
+        serde_json::Value::String(s) => Some(vec![s.clone()]),
+        serde_json::Value::Array(items) => items.iter().map(|v| v.as_str().map(String::from)).collect(),
+        _ => None,
+    }
+}
+
+/// Little-endian f32 bytes, base64 encoded, as returned by OpenAI for `encoding_format: base64`.
+fn encode_embedding_base64(vector: &[f32]) -> String {
+    use base64::Engine;
+    let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Embedding models need not be enabled: any model of a provider with credentials is accepted.
+#[utoipa::path(post, path = "/v1/embeddings", tag = "embeddings", request_body = OpenAiEmbeddingRequest, responses(
+    (status = 200, description = "An OpenAI embedding list", body = serde_json::Value),
+    (status = 400, description = "Invalid input or model", body = serde_json::Value),
+    (status = 429, description = "The 24h spending budget is used up, or every account is rate limited", body = serde_json::Value),
+))]
+async fn embeddings(
+    State(state): State<Arc<AppState>>,
+    Extension(log): Extension<RequestLog>,
+    Json(mut req): Json<OpenAiEmbeddingRequest>,
+) -> Response {
+    let settings = state.config.get_settings().unwrap_or_default();
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    if let Some(message) = state.budget_exceeded(&settings, &log).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"error": {"message": message, "type": "budget_exceeded"}})),
+        )
+            .into_response();
+    }
+
+    let Some((provider_name, _)) = split_model_id(&req.model) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "Invalid model ID format"}})),
+        )
+            .into_response();
+    };
+    let provider_name = provider_name.to_string();
+    log.set_model(&req.model);
+    if let Some(message) = state.maintenance(&provider_name) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": {"message": message, "type": "provider_unavailable"}})),
+        )
+            .into_response();
+    }
+    let Some(input) = embedding_inputs(&req.input).filter(|i| !i.is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "input must be a string or a non-empty array of strings", "type": "invalid_request_error"}})),
+        )
+            .into_response();
+    };
+    let base64 = match req.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": {"message": format!("Unsupported encoding_format: {}", other), "type": "invalid_request_error"}})),
+            )
+                .into_response();
+        }
+    };
+
+    let client = state.client.read().await.clone();
+    let cost = client
+        .get_model(&req.model)
+        .map(|d| d.cost.clone())
+        .or_else(|| state.config.get_pricing_overrides().ok()?.remove(&req.model))
+        .unwrap_or_default();
+    let request = EmbeddingRequest {
+        dimensions: req.dimensions,
+        ..EmbeddingRequest::new(input)
+    };
+
+    let options = RequestOptions {
+        retry_config: settings.retry.clone(),
+        attempt_budget: settings.retry.as_ref().and_then(AttemptBudget::from_config),
+        ..RequestOptions::default()
+    };
+    let result = with_account_rotation(&state, &log, &provider_name, &options, |opts| {
+        let client = &client;
+        let request = &request;
+        let model = &req.model;
+        async move { client.embed(model, request, &opts).await }
+    })
+    .await;
+    let (resp, account_id) = match result {
+        Ok(v) => v,
+        Err(response) => return response,
+    };
+
+    state.record_usage(&provider_name, &account_id, log.source(), resp.usage.as_ref(), &cost).await;
+    log.record(&provider_name, &account_id, resp.usage.as_ref(), &cost);
+    let data: Vec<_> = resp
+        .embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, vector)| {
+            let embedding = if base64 { json!(encode_embedding_base64(vector)) } else { json!(vector) };
+            json!({"object": "embedding", "index": index, "embedding": embedding})
+        })
+        .collect();
+    let usage = resp.usage.unwrap_or_default();
+    Json(json!({
+        "object": "list",
+        "data": data,
+        "model": req.model,
+        "usage": {"prompt_tokens": usage.input_tokens, "total_tokens": usage.total_tokens},
+    }))
+    .into_response()
+}
+
+/// Whether the request has used up `settings.retry.max_attempts` / `deadline_ms`, so no further
+/// account may be tried.
+fn budget_spent(options: &RequestOptions) -> bool {
+    options.attempt_budget.as_ref().is_some_and(AttemptBudget::is_spent)
+}
+
+/// Run a non-streaming request with the provider's accounts, rotating to the next account
+/// on 429. Returns the result and the account used, or an error response.
+async fn with_account_rotation<T, F, Fut>(
+    state: &AppState,
+    log: &RequestLog,
+    provider_name: &str,
+    base_options: &RequestOptions,
+    call: F,
+) -> Result<(T, String), Response>
+where
+    F: Fn(RequestOptions) -> Fut,
+    Fut: std::future::Future<Output = Result<T, zeroai::ProviderError>>,
+{
+    let max_attempts: usize = state
+        .config
+        .list_accounts(provider_name)
+        .map(|v| v.len().max(1))
+        .unwrap_or(1);
+
+    let mut last_err: Option<zeroai::ProviderError> = None;
+    for attempt in 0..max_attempts {
+        let Some(sel) = state.resolve_account(provider_name).await else {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": {"message": format!("No credentials for provider: {}", provider_name)}})),
+            )
+                .into_response());
+        };
+        let mut options = base_options.clone();
+        options.api_key = Some(sel.api_key.clone());
+
+        match call(options).await {
+            Ok(value) => return Ok((value, sel.account_id)),
+            Err(e) => {
+                if state.rate_limit(provider_name, &sel.account_id, &e) && attempt + 1 < max_attempts && !budget_spent(base_options) {
+                    last_err = Some(e);
+                    log.note_retry();
+                    continue;
+                }
+                last_err = Some(e);
+                break;
+            }
+        }
+    }
+
+    let error = last_err
+        .map(|e| upstream_error(&e))
+        .unwrap_or_else(|| json!({"message": "No response received"}));
+    Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": error})),
+    )
+        .into_response())
+}
+
+// ---------------------------------------------------------------------------
+// POST /v1/rerank - Cohere / Jina compatible
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, ToSchema)]
+struct RerankApiRequest {
+    model: String,
+    query: String,
+    /// Strings, or objects with a `text` field.
+    documents: Vec<serde_json::Value>,
+    #[serde(default)]
+    top_n: Option<usize>,
+    #[serde(default)]
+    return_documents: bool,
+}
+
+fn rerank_document_text(doc: &serde_json::Value) -> Option<String> {
+    match doc {
+        serde_json::Value::String(s) => Some(s.clone()),
+        _ => doc.get("text").and_then(|t| t.as_str()).map(String::from),
+    }
+}
+
+/// Like embeddings, rerank models need not be enabled.
+#[utoipa::path(post, path = "/v1/rerank", tag = "rerank", request_body = RerankApiRequest, responses(
+    (status = 200, description = "Documents by relevance: `{\"results\": [{\"index\", \"relevance_score\", \"document\"?}]}`", body = serde_json::Value),
+    (status = 400, description = "Invalid model", body = serde_json::Value),
+    (status = 429, description = "The 24h spending budget is used up, or every account is rate limited", body = serde_json::Value),
+))]
+async fn rerank(
+    State(state): State<Arc<AppState>>,
+    Extension(log): Extension<RequestLog>,
+    Json(mut req): Json<RerankApiRequest>,
+) -> Response {
+    let settings = state.config.get_settings().unwrap_or_default();
+    req.model = resolve_model_id(&settings, &*state.client.read().await, &state.providers_down(), &req.model);
+    if let Some(message) = state.budget_exceeded(&settings, &log).await {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"error": {"message": message, "type": "budget_exceeded"}})),
+        )
+            .into_response();
+    }
+
+    let Some((provider_name, _)) = split_model_id(&req.model) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "Invalid model ID format"}})),
+        )
+            .into_response();
+    };
+    let provider_name = provider_name.to_string();
+    log.set_model(&req.model);
+    if let Some(message) = state.maintenance(&provider_name) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": {"message": message, "type": "provider_unavailable"}})),
+        )
+            .into_response();
+    }
+    let Some(documents) = req.documents.iter().map(rerank_document_text).collect::<Option<Vec<_>>>() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": {"message": "documents must be strings or objects with a text field", "type": "invalid_request_error"}})),
+        )
+            .into_response();
+    };
+
+    let client = state.client.read().await.clone();
+    let cost = client
+        .get_model(&req.model)
+        .map(|d| d.cost.clone())
+        .or_else(|| state.config.get_pricing_overrides().ok()?.remove(&req.model))
+        .unwrap_or_default();
+    let request = RerankRequest {
+        top_n: req.top_n,
+        ..RerankRequest::new(req.query.clone(), documents)
+    };
+
+    let options = RequestOptions {
+        retry_config: settings.retry.clone(),
+        attempt_budget: settings.retry.as_ref().and_then(AttemptBudget::from_config),
+        ..RequestOptions::default()
+    };
+    let result = with_account_rotation(&state, &log, &provider_name, &options, |opts| {
+        let client = &client;
+        let request = &request;
+        let model = &req.model;
+        async move { client.rerank(model, request, &opts).await }
+    })
+    .await;
+    let (resp, account_id) = match result {
+        Ok(v) => v,
+        Err(response) => return response,
+    };
+
+    state.record_usage(&provider_name, &account_id, log.source(), resp.usage.as_ref(), &cost).await;
+    log.record(&provider_name, &account_id, resp.usage.as_ref(), &cost);
+    let results: Vec<_> = resp
+        .results
+        .iter()
+        .map(|r| {
+            let mut item = json!({"index": r.index, "relevance_score": r.relevance_score});
+            if req.return_documents
+                && let Some(text) = request.documents.get(r.index)
+            {
+                item["document"] = json!({"text": text});
+            }
+            item
+        })
+        .collect();
+    Json(json!({
+        "id": uuid::Uuid::new_v4().to_string(),
+        "model": req.model,
+        "results": results,
+        "usage": {"total_tokens": resp.usage.map(|u| u.total_tokens).unwrap_or(0)},
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{FakeReply, FakeUpstream, MODEL, PROVIDER, TestProxy, UPSTREAM_REQUEST_ID};
+    use serde_json::{Value, json};
+    use zeroai::ProviderDef;
+
+    fn chat(stream: bool) -> Value {
+        json!({"model": MODEL, "stream": stream, "messages": [{"role": "user", "content": "hi"}]})
+    }
+
+    /// `data:` payloads of an SSE body.
+    fn sse_data(body: &str) -> Vec<String> {
+        body.lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .map(String::from)
+            .collect()
+    }
+
+    fn streamed_text(data: &[String]) -> String {
+        data.iter()
+            .filter_map(|d| serde_json::from_str::<Value>(d).ok())
+            .filter_map(|c| c["choices"][0]["delta"]["content"].as_str().map(String::from))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn request_id_is_echoed_and_sent_upstream() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy
+            .request("/v1/chat/completions")
+            .header("x-request-id", "req-123")
+            .json(&chat(false))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-request-id"], "req-123");
+        assert_eq!(upstream.requests()[0].headers["x-client-request-id"], "req-123");
+
+        let resp = proxy.post("/v1/chat/completions", chat(true)).await;
+        let id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert!(!id.is_empty());
+        resp.text().await.unwrap();
+        assert_eq!(upstream.requests()[1].headers["x-client-request-id"], id.as_str());
+    }
+
+    #[tokio::test]
+    async fn usage_is_reported_per_app() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        for app in ["agent-a", "agent-a", "agent-b"] {
+            let resp = proxy
+                .request("/v1/chat/completions")
+                .header("x-zeroai-app", app)
+                .json(&chat(false))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(report["accounts"][0]["requests"], 3);
+        let apps = report["apps"].as_array().unwrap();
+        let requests = |name: &str| apps.iter().find(|a| a["app"] == name).map(|a| a["requests"].clone());
+        assert_eq!(requests("agent-a"), Some(json!(2)));
+        assert_eq!(requests("agent-b"), Some(json!(1)));
+        assert_eq!(report["apps"][0]["input_tokens"], 20);
+    }
+
+    #[tokio::test]
+    async fn usage_shows_account_identity() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let config = &proxy.state.config;
+        let account = config.list_accounts(PROVIDER).unwrap().remove(0);
+        let identity = zeroai::oauth::AccountIdentity {
+            email: Some("alice@example.com".into()),
+            org: Some("Acme".into()),
+        };
+        config.set_account_identity(PROVIDER, &account.id, &identity).unwrap();
+        assert_eq!(proxy.post("/v1/chat/completions", chat(false)).await.status(), 200);
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url)).await.unwrap().json().await.unwrap();
+        let row = &report["accounts"][0];
+        assert_eq!(row["account"], format!("{}/{}", PROVIDER, account.id));
+        assert_eq!(row["label"], "alice@example.com");
+        assert_eq!(row["org"], "Acme");
+    }
+
+    #[tokio::test]
+    async fn vendor_fields_reach_the_upstream_body() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let mut body = chat(false);
+        body["provider"] = json!({"order": ["DeepInfra"], "allow_fallbacks": false});
+        body["repetition_penalty"] = json!(1.1);
+        body["n"] = json!(2);
+        let resp = proxy
+            .request("/v1/chat/completions")
+            .header("x-zeroai-extra", r#"{"transforms": ["middle-out"], "top_k": 20, "model": "other", "stream": true}"#)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let sent = &upstream.requests()[0].body;
+        assert_eq!(sent["transforms"][0], "middle-out");
+        assert_eq!(sent["top_k"], 20);
+        assert_eq!(sent["repetition_penalty"], 1.1);
+        assert!(sent.get("n").is_none());
+        // OpenRouter's routing preferences only go to OpenRouter.
+        assert!(sent.get("provider").is_none());
+        // Extras never replace what the proxy built.
+        assert_ne!(sent["model"], "other");
+        assert_eq!(sent["stream"], false);
+
+        let resp = proxy
+            .request("/v1/chat/completions")
+            .header("x-zeroai-extra", "not json")
+            .json(&chat(false))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn usage_is_reported_per_user() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let mut body = chat(false);
+        body["user"] = json!("user-42");
+        body["store"] = json!(true);
+        body["metadata"] = json!({"team": "search"});
+        assert_eq!(proxy.post("/v1/chat/completions", body).await.status(), 200);
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(report["users"][0]["user"], "user-42");
+        assert_eq!(report["users"][0]["requests"], 1);
+    }
+
+    #[tokio::test]
+    async fn idempotent_requests_are_replayed() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let send = |body: Value| {
+            proxy
+                .request("/v1/chat/completions")
+                .header("idempotency-key", "order-7")
+                .json(&body)
+                .send()
+        };
+
+        let first = send(chat(false)).await.unwrap();
+        assert_eq!(first.status(), 200);
+        assert!(first.headers().get("idempotent-replayed").is_none());
+        let first: Value = first.json().await.unwrap();
+
+        let again = send(chat(false)).await.unwrap();
+        assert_eq!(again.status(), 200);
+        assert_eq!(again.headers()["idempotent-replayed"], "true");
+        assert_eq!(again.json::<Value>().await.unwrap(), first);
+        assert_eq!(upstream.requests().len(), 1);
+
+        let mut other = chat(false);
+        other["temperature"] = json!(0.5);
+        assert_eq!(send(other).await.unwrap().status(), 422);
+        assert_eq!(upstream.requests().len(), 1);
+
+        // Another client reusing the key gets its own response.
+        let elsewhere = proxy
+            .request("/v1/chat/completions")
+            .header("idempotency-key", "order-7")
+            .header("authorization", "Bearer another-client")
+            .json(&chat(false))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(elsewhere.status(), 200);
+        assert!(elsewhere.headers().get("idempotent-replayed").is_none());
+        assert_eq!(upstream.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn auto_cheapest_routes_to_the_cheapest_capable_model() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [
+                {"id": "test", "cost": {"input": 3.0, "output": 15.0}, "input": ["text", "image"]},
+                {"id": "cheap", "cost": {"input": 0.1, "output": 0.4}},
+            ],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.config.set_enabled_models(vec![MODEL.to_string(), "fake/cheap".to_string()]).unwrap();
+        proxy.state.refresh_models().await;
+
+        let models: Value = reqwest::get(format!("{}/v1/models", proxy.base_url)).await.unwrap().json().await.unwrap();
+        assert!(models["data"].as_array().unwrap().iter().any(|m| m["id"] == "auto/cheapest"));
+
+        let mut body = chat(false);
+        body["model"] = json!("auto/cheapest");
+        let resp = proxy.post("/v1/chat/completions", body.clone()).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.json::<Value>().await.unwrap()["model"], "fake/cheap");
+        assert_eq!(upstream.requests()[0].body["model"], "cheap");
+
+        body["messages"][0]["content"] = json!([
+            {"type": "text", "text": "what is this?"},
+            {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}},
+        ]);
+        assert_eq!(proxy.post("/v1/chat/completions", body).await.status(), 200);
+        assert_eq!(upstream.requests()[1].body["model"], "test");
+
+        // Every account of the provider is resting after a rate limit.
+        let account = proxy.state.config.list_accounts(PROVIDER).unwrap().remove(0);
+        proxy.state.config.rate_limit_account(PROVIDER, &account.id, 60_000).unwrap();
+        let mut body = chat(false);
+        body["model"] = json!("auto/cheapest");
+        assert_eq!(proxy.post("/v1/chat/completions", body).await.status(), 400);
+        assert_eq!(upstream.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn providers_in_maintenance_are_skipped() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy
+            .post("/v1/providers/disable", json!({"provider": PROVIDER, "reason": "outage", "minutes": 30}))
+            .await;
+        assert_eq!(resp.status(), 200);
+        let providers: Value = reqwest::get(format!("{}/v1/providers", proxy.base_url)).await.unwrap().json().await.unwrap();
+        let fake = &providers["data"][0];
+        assert_eq!((fake["id"].as_str(), fake["accounts"].as_u64()), (Some(PROVIDER), Some(1)));
+        assert_eq!(fake["maintenance"]["reason"], "outage");
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 503);
+        let body: Value = resp.json().await.unwrap();
+        assert!(body["error"]["message"].as_str().unwrap().contains("outage"));
+        let mut routed = chat(false);
+        routed["model"] = json!("auto/fastest");
+        assert_eq!(proxy.post("/v1/chat/completions", routed).await.status(), 400);
+        assert!(upstream.requests().is_empty());
+
+        assert_eq!(proxy.post("/v1/providers/enable", json!({"provider": PROVIDER})).await.status(), 200);
+        assert_eq!(proxy.post("/v1/chat/completions", chat(false)).await.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn failed_capability_probes_turn_model_capabilities_off() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test", "input": ["text", "image"]}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.refresh_models().await;
+        let vision = |client: &zeroai::AiClient| client.get_model(MODEL).unwrap().supports_vision();
+        assert!(vision(&*proxy.state.client.read().await));
+
+        let probe = zeroai::probe::CapabilityProbe {
+            model: MODEL.to_string(),
+            probed_at_ms: 1,
+            streaming: Some(true),
+            tools: None,
+            vision: Some(false),
+            error: Some("vision: 400".into()),
+        };
+        proxy.state.store.put_capability_probe(&probe).unwrap();
+        proxy.state.refresh_models().await;
+        assert!(!vision(&*proxy.state.client.read().await));
+    }
+
+    #[tokio::test]
+    async fn auto_fastest_routes_to_the_fastest_measured_model() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test"}, {"id": "quick"}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.config.set_enabled_models(vec![MODEL.to_string(), "fake/quick".to_string()]).unwrap();
+        proxy.state.refresh_models().await;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        for (model, ttft_ms) in [(MODEL, 900), ("fake/quick", 200)] {
+            let usage = zeroai::types::Usage { ttft_ms: Some(ttft_ms), ..Default::default() };
+            proxy.state.usage.record_latency(model, &usage, now_ms).unwrap();
+        }
+
+        let mut body = chat(false);
+        body["model"] = json!("auto/fastest");
+        let resp = proxy.post("/v1/chat/completions", body).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.json::<Value>().await.unwrap()["model"], "fake/quick");
+        assert_eq!(upstream.requests()[0].body["model"], "quick");
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url)).await.unwrap().json().await.unwrap();
+        let models = report["models"].as_array().unwrap();
+        assert_eq!(models[0]["model"], "fake/quick");
+        // The non-streamed reply has no time to first token, so it adds no sample.
+        assert_eq!(models[0]["samples"], 1);
+        assert_eq!(models[1]["p50_ms"], 900);
+    }
+
+    #[tokio::test]
+    async fn shadow_model_gets_a_copy_of_requests() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test", "cost": {"input": 1.0, "output": 2.0}}, {"id": "candidate"}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.config.set_enabled_models(vec![MODEL.to_string(), "fake/candidate".to_string()]).unwrap();
+        proxy.state.refresh_models().await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.shadow = Some(zeroai::auth::config::ShadowConfig {
+            model: "fake/candidate".into(),
+            percent: 100.0,
+            models: Vec::new(),
+        });
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.json::<Value>().await.unwrap()["choices"][0]["message"]["content"], "ok");
+
+        let mut shadow = Value::Null;
+        for _ in 0..100 {
+            let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url)).await.unwrap().json().await.unwrap();
+            shadow = report["shadow"].clone();
+            if shadow.as_array().is_some_and(|s| !s.is_empty()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert_eq!(shadow[0]["model"], MODEL);
+        assert_eq!(shadow[0]["shadow_model"], "fake/candidate");
+        assert_eq!(shadow[0]["requests"], 1);
+        assert_eq!(shadow[0]["errors"], 0);
+        assert_eq!(shadow[0]["avg_similarity"], 1.0);
+        let requests = upstream.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].body["model"], "candidate");
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url)).await.unwrap().json().await.unwrap();
+        let apps = report["apps"].as_array().unwrap();
+        assert!(apps.iter().any(|a| a["app"] == crate::shadow::SHADOW_APP && a["requests"] == 1), "{:?}", apps);
+
+
+        // A request that uses up the daily budget isn't mirrored.
+        let spent = proxy.state.usage.rolling_cost(chrono::Utc::now().timestamp_millis()).unwrap();
+        assert!(spent > 0.0);
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.daily_budget_usd = Some(spent * 1.5);
+        proxy.state.config.set_settings(settings).unwrap();
+        assert_eq!(proxy.post("/v1/chat/completions", chat(false)).await.status(), 200);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(upstream.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn experiment_variants_are_tagged_and_counted() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test"}, {"id": "candidate"}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.config.set_enabled_models(vec![MODEL.to_string(), "fake/candidate".to_string()]).unwrap();
+        proxy.state.refresh_models().await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.experiments = vec![serde_json::from_value(json!({
+            "name": "chat",
+            "model": "chat",
+            "variants": [
+                {"name": "control", "model": MODEL},
+                {"name": "candidate", "model": "fake/candidate"},
+            ],
+        }))
+        .unwrap()];
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let mut body = chat(false);
+        body["model"] = json!("chat");
+        body["user"] = json!("user-7");
+        let mut tags = Vec::new();
+        for _ in 0..2 {
+            let resp = proxy.post("/v1/chat/completions", body.clone()).await;
+            assert_eq!(resp.status(), 200);
+            let tag = resp.headers()["x-zeroai-variant"].to_str().unwrap().to_string();
+            let model = resp.json::<Value>().await.unwrap()["model"].clone();
+            assert_eq!(model, if tag == "chat/control" { MODEL } else { "fake/candidate" });
+            tags.push(tag);
+        }
+        assert_eq!(tags[0], tags[1]);
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert!(resp.headers().get("x-zeroai-variant").is_none());
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url)).await.unwrap().json().await.unwrap();
+        let variants = report["variants"].as_array().unwrap();
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0]["variant"], tags[0].as_str());
+        assert_eq!(variants[0]["requests"], 2);
+    }
+
+    #[tokio::test]
+    async fn context_guard_rejects_or_truncates_long_prompts() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test", "context_window": 1000}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.refresh_models().await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.context_guard = Some(zeroai::auth::config::ContextGuard { max_percent: 50.0, truncate: false });
+        proxy.state.config.set_settings(settings.clone()).unwrap();
+
+        let long = "word ".repeat(400);
+        let body = json!({"model": MODEL, "messages": [
+            {"role": "user", "content": long},
+            {"role": "assistant", "content": "noted"},
+            {"role": "user", "content": "hi"},
+        ]});
+        let resp = proxy.post("/v1/chat/completions", body.clone()).await;
+        assert_eq!(resp.status(), 400);
+        let error = resp.json::<Value>().await.unwrap()["error"].clone();
+        assert_eq!(error["code"], "context_limit_exceeded");
+        assert_eq!(error["context_limit"]["limit_tokens"], 500);
+        assert!(error["context_limit"]["trim_tokens"].as_u64().unwrap() > 0);
+        assert!(upstream.requests().is_empty());
+
+        settings.context_guard = Some(zeroai::auth::config::ContextGuard { max_percent: 50.0, truncate: true });
+        proxy.state.config.set_settings(settings).unwrap();
+        let resp = proxy.post("/v1/chat/completions", body).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers()["x-zeroai-warning"].to_str().unwrap().contains("2 oldest message(s) dropped"));
+        let sent = upstream.requests()[0].body["messages"].clone();
+        assert_eq!(sent.as_array().unwrap().len(), 1);
+        assert_eq!(sent[0]["content"], "hi");
+    }
+
+    #[tokio::test]
+    async fn chat_completion_returns_upstream_reply() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("Hello there".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 200);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], "Hello there");
+        assert_eq!(body["usage"]["total_tokens"], 15);
+        assert!(body["usage"]["zeroai_timing"]["duration_ms"].is_u64());
+
+        let requests = upstream.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].api_key, "k1");
+        assert_eq!(requests[0].body["model"], "test");
+    }
+
+    #[test]
+    fn finish_reasons_survive_both_formats() {
+        use super::{StopReason, anthropic_stop_reason, openai_finish_reason};
+        let cases = [
+            (StopReason::from_openai("content_filter"), "content_filter", "refusal"),
+            (StopReason::from_gemini("SAFETY"), "content_filter", "refusal"),
+            (StopReason::from_gemini("RECITATION"), "content_filter", "refusal"),
+            (StopReason::from_anthropic("refusal"), "content_filter", "refusal"),
+            (StopReason::from_anthropic("pause_turn"), "stop", "pause_turn"),
+            (StopReason::from_anthropic("max_tokens"), "length", "max_tokens"),
+            (StopReason::from_gemini("STOP"), "stop", "end_turn"),
+        ];
+        for (reason, openai, anthropic) in cases {
+            assert_eq!((openai_finish_reason(&reason), anthropic_stop_reason(&reason)), (openai, anthropic), "{:?}", reason);
+        }
+    }
+
+    #[test]
+    fn openrouter_fields_only_reach_openrouter() {
+        use super::{ChatCompletionRequest, ModelDef, RequestOptions, apply_openrouter_fields};
+        let model = |provider: &str| -> ModelDef {
+            serde_json::from_value(json!({
+                "id": "gpt-4o", "name": "gpt-4o", "api": "openai-completions", "provider": provider,
+                "base_url": "https://example.com/v1", "reasoning": false, "input": ["text"], "cost": {},
+                "context_window": 128000, "max_tokens": 16384,
+            }))
+            .unwrap()
+        };
+        let req: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "openrouter/openai/gpt-4o", "messages": [],
+            "provider": {"order": ["Azure"]}, "transforms": ["middle-out"],
+        }))
+        .unwrap();
+
+        let mut options = RequestOptions::default();
+        apply_openrouter_fields(&req, &model("openrouter"), &mut options);
+        assert_eq!(options.provider_extra, json!({"provider": {"order": ["Azure"]}, "transforms": ["middle-out"]}));
+
+        let mut options = RequestOptions::default();
+        apply_openrouter_fields(&req, &model("openai"), &mut options);
+        assert!(options.provider_extra.is_null());
+    }
+
+    #[test]
+    fn ollama_keep_alive_is_added_unless_the_client_set_one() {
+        use super::{ModelDef, ProxySettings, RequestOptions, apply_ollama_keep_alive};
+        let model = |provider: &str| -> ModelDef {
+            serde_json::from_value(json!({
+                "id": "qwen3:32b", "name": "qwen3:32b", "api": "openai-completions", "provider": provider,
+                "base_url": "http://127.0.0.1:11434/v1", "reasoning": false, "input": ["text"], "cost": {},
+                "context_window": 32768, "max_tokens": 8192,
+            }))
+            .unwrap()
+        };
+        let settings = ProxySettings { ollama_keep_alive: Some(json!("30m")), ..Default::default() };
+
+        let mut options = RequestOptions::default();
+        apply_ollama_keep_alive(&settings, &model("ollama"), &mut options);
+        assert_eq!(options.provider_extra, json!({"keep_alive": "30m"}));
+
+        let mut options = RequestOptions::default();
+        apply_ollama_keep_alive(&settings, &model("openai"), &mut options);
+        assert!(options.provider_extra.is_null());
+
+        let mut options = RequestOptions {
+            extra_body: Some([("keep_alive".to_string(), json!(-1))].into()),
+            ..Default::default()
+        };
+        apply_ollama_keep_alive(&settings, &model("ollama"), &mut options);
+        assert!(options.provider_extra.is_null());
+    }
+
+    #[tokio::test]
+    async fn refusals_are_not_empty_answers() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Refusal("I can't help with that".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let body: Value = proxy.post("/v1/chat/completions", chat(false)).await.json().await.unwrap();
+        let message = &body["choices"][0]["message"];
+        assert_eq!(message["refusal"], "I can't help with that");
+        assert!(message["content"].is_null());
+
+        let resp = proxy.post("/v1/chat/completions", chat(true)).await;
+        let data = sse_data(&resp.text().await.unwrap());
+        let refusal: String = data
+            .iter()
+            .filter_map(|d| serde_json::from_str::<Value>(d).ok())
+            .filter_map(|v| v["choices"][0]["delta"]["refusal"].as_str().map(String::from))
+            .collect();
+        assert_eq!(refusal, "I can't help with that");
+        assert_eq!(streamed_text(&data), "");
+
+        let body: Value = proxy
+            .post("/v1/messages", json!({"model": MODEL, "max_tokens": 64, "messages": [{"role": "user", "content": "hi"}]}))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["content"][0], json!({"type": "text", "text": "I can't help with that"}));
+    }
+
+    #[tokio::test]
+    async fn streamed_chat_completion_ends_with_finish_reason() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("one two three".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy.post("/v1/chat/completions", chat(true)).await;
+        assert_eq!(resp.status(), 200);
+        let data = sse_data(&resp.text().await.unwrap());
+        assert_eq!(streamed_text(&data), "one two three");
+        let last: Value = serde_json::from_str(data.last().unwrap()).unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "stop");
+        assert_eq!(last["usage"]["total_tokens"], 15);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_account_is_rotated() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" => FakeReply::Status(429),
+            _ => FakeReply::Text("from k2".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 200);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], "from k2");
+        let keys: Vec<_> = upstream.requests().into_iter().map(|r| r.api_key).collect();
+        assert_eq!(keys, ["k1", "k2"]);
+
+        // The limited account sits out the next request.
+        proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(upstream.requests().last().unwrap().api_key, "k2");
+
+        let report: Value = reqwest::get(format!("{}/v1/usage", proxy.base_url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let events = report["account_events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["kind"], "rate_limited");
+    }
+
+    #[tokio::test]
+    async fn overloads_retry_on_the_same_account() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" => FakeReply::Status(529),
+            _ => FakeReply::Text("from k2".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.retry = Some(zeroai::RetryConfig { max_retries: 0, overload_retries: 2, overload_backoff_ms: 1, ..Default::default() });
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 500);
+        let keys: Vec<_> = upstream.requests().into_iter().map(|r| r.api_key).collect();
+        assert_eq!(keys, ["k1", "k1", "k1"]);
+        let account = proxy.state.config.list_accounts(PROVIDER).unwrap().remove(0);
+        assert!(account.unhealthy_until_ms.is_none());
+    }
+
+    #[tokio::test]
+    async fn attempt_budget_caps_account_rotation() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Status(429)).await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2", "k3"]).await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.retry = Some(zeroai::RetryConfig { max_retries: 3, base_backoff_ms: 1, max_attempts: Some(2), ..Default::default() });
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 500);
+        assert_eq!(upstream.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn alert_goes_out_when_every_account_is_rate_limited() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" | "k2" => FakeReply::Status(429),
+            _ => FakeReply::Text("ok".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.notifications = Some(serde_json::from_value(json!({
+            "webhooks": [format!("{}/chat/completions", upstream.base_url)]
+        })).unwrap());
+        proxy.state.config.set_settings(settings).unwrap();
+
+        // The alert waits until the last account is limited too, and goes out once.
+        proxy.post("/v1/chat/completions", chat(false)).await;
+        proxy.post("/v1/chat/completions", chat(false)).await;
+        let mut alerts = Vec::new();
+        for _ in 0..50 {
+            alerts = upstream.requests().into_iter().filter(|r| r.body.get("event").is_some()).collect();
+            if !alerts.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].body["event"], "all_accounts_rate_limited");
+        assert_eq!(alerts[0].body["provider"], PROVIDER);
+        assert!(alerts[0].body["until_ms"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn subscribed_events_go_to_webhooks() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" => FakeReply::Status(429),
+            _ => FakeReply::Text("ok".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.event_webhooks = serde_json::from_value(json!([{
+            "url": format!("{}/chat/completions", upstream.base_url),
+            "events": ["request.completed", "account.ratelimited"],
+            "headers": {"Authorization": "Bearer hook-token"}
+        }]))
+        .unwrap();
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        resp.text().await.unwrap();
+        // Not subscribed: a failed request sends nothing.
+        proxy.post("/v1/chat/completions", json!({"model": "fake/missing", "messages": []})).await;
+
+        let events = || upstream.requests().into_iter().filter(|r| r.body.get("type").is_some()).count();
+        for _ in 0..50 {
+            if events() >= 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        // Give an unwanted request.failed time to show up.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let events: Vec<_> = upstream.requests().into_iter().filter(|r| r.body.get("type").is_some()).collect();
+        let types: Vec<_> = events.iter().map(|e| e.body["type"].as_str().unwrap().to_string()).collect();
+        assert_eq!(types, ["account.ratelimited", "request.completed"]);
+        assert_eq!(events[0].api_key, "hook-token");
+        assert_eq!(events[0].body["data"]["provider"], PROVIDER);
+        let completed = &events[1].body["data"];
+        assert_eq!(completed["model"], MODEL);
+        assert_eq!(completed["status"], 200);
+        assert_eq!(completed["output_tokens"], 5);
+        assert!(completed["account"].as_str().unwrap().starts_with("fake/"));
+    }
+
+    #[tokio::test]
+    async fn requests_are_journaled_with_the_upstream_id() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy.request("/v1/chat/completions").header("x-request-id", "r-1").json(&chat(false)).send().await.unwrap();
+        resp.text().await.unwrap();
+        let resp = proxy.request("/v1/chat/completions").header("x-request-id", "r-2").json(&chat(true)).send().await.unwrap();
+        resp.text().await.unwrap();
+
+        // The entry is written once the response body is dropped, just after the client has it.
+        let store = &proxy.state.store;
+        let journal = |id: &'static str| async move {
+            for _ in 0..50 {
+                let entries = store.journal_entries(id).unwrap();
+                if !entries.is_empty() {
+                    return entries;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            Vec::new()
+        };
+        let entries = journal("r-1").await;
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!((entry.model.as_str(), entry.status), (MODEL, 200));
+        assert_eq!((entry.input_tokens, entry.output_tokens), (10, 5));
+        assert_eq!(entry.upstream_request_id.as_deref(), Some(UPSTREAM_REQUEST_ID));
+        assert_eq!(entry.request_hash, Some(crate::idempotency::request_hash("/v1/chat/completions", &chat(false))));
+        let streamed = journal("r-2").await;
+        assert_eq!(streamed[0].upstream_request_id.as_deref(), Some(UPSTREAM_REQUEST_ID));
+        assert_eq!(streamed[0].output_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn debug_requests_get_a_summary() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" => FakeReply::Status(429),
+            _ => FakeReply::Text("ok".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+
+        let resp = proxy.request("/v1/chat/completions").header("x-zeroai-debug", "1").json(&chat(false)).send().await.unwrap();
+        let summary: Value = serde_json::from_str(resp.headers()["x-zeroai-summary"].to_str().unwrap()).unwrap();
+        assert_eq!(summary["model"], MODEL);
+        assert_eq!(summary["retries"], 1);
+        assert_eq!((summary["input_tokens"].as_u64(), summary["output_tokens"].as_u64()), (Some(10), Some(5)));
+        assert!(summary["account"].as_str().unwrap().starts_with("fake/"));
+        assert!(summary["latency_ms"].is_u64());
+
+        let resp = proxy.request("/v1/chat/completions").header("x-zeroai-debug", "1").json(&chat(true)).send().await.unwrap();
+        assert!(resp.headers().get("x-zeroai-summary").is_none());
+        let body = resp.text().await.unwrap();
+        let (_, event) = body.rsplit_once("event: zeroai.summary\ndata: ").unwrap();
+        let summary: Value = serde_json::from_str(event.trim()).unwrap();
+        assert_eq!(summary["output_tokens"], 5);
+        assert!(body.trim_end().ends_with('}'));
+
+        // Without the header, responses are unchanged.
+        let resp = proxy.post("/v1/chat/completions", chat(true)).await;
+        assert!(!resp.text().await.unwrap().contains("zeroai.summary"));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_stream_is_rotated_before_first_token() {
+        let upstream = FakeUpstream::start(|r| match r.api_key.as_str() {
+            "k1" => FakeReply::Status(429),
+            _ => FakeReply::Text("streamed by k2".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+
+        let resp = proxy.post("/v1/chat/completions", chat(true)).await;
+        let data = sse_data(&resp.text().await.unwrap());
+        assert_eq!(streamed_text(&data), "streamed by k2");
+        assert_eq!(upstream.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upstream_error_is_returned_when_every_account_fails() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Status(429)).await;
+        let proxy = TestProxy::start(&upstream, &["k1", "k2"]).await;
+
+        let resp = proxy.post("/v1/chat/completions", chat(false)).await;
+        assert_eq!(resp.status(), 500);
+        let body: Value = resp.json().await.unwrap();
+        assert!(body["error"]["message"].as_str().unwrap().contains("429"));
+        assert_eq!(upstream.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upstream_errors_are_normalized() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Status(400)).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let body: Value = proxy.post("/v1/chat/completions", chat(false)).await.json().await.unwrap();
+        let error = &body["error"];
+        assert_eq!(error["message"], "HTTP error 400: fake 400 Bad Request");
+        assert_eq!((error["type"].as_str(), error["code"].as_str()), (Some("fake_error"), Some("400")));
+        assert_eq!(error["upstream_request_id"], UPSTREAM_REQUEST_ID);
+
+        let body: Value = proxy
+            .post("/v1/messages", json!({"model": MODEL, "max_tokens": 64, "messages": [{"role": "user", "content": "hi"}]}))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "fake_error");
+        assert_eq!(body["error"]["upstream_request_id"], UPSTREAM_REQUEST_ID);
+    }
+
+    #[tokio::test]
+    async fn usage_is_recorded_against_the_serving_account() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("ok".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        proxy.post("/v1/chat/completions", chat(false)).await;
+        let account = proxy.state.config.list_accounts(PROVIDER).unwrap().remove(0);
+        let usage = proxy.state.usage.get(PROVIDER, &account.id).unwrap().unwrap();
+        assert_eq!(usage.rolling_totals(chrono::Utc::now().timestamp_millis()).requests, 1);
+    }
+
+    #[tokio::test]
+    async fn anthropic_messages_are_served_by_openai_upstream() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("Bonjour".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+
+        let resp = proxy
+            .post(
+                "/v1/messages",
+                json!({"model": MODEL, "max_tokens": 100, "messages": [{"role": "user", "content": "hi"}]}),
+            )
+            .await;
+        assert_eq!(resp.status(), 200);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["type"], "message");
+        assert_eq!(body["content"][0]["text"], "Bonjour");
+        assert_eq!(body["stop_reason"], "end_turn");
+    }
+
+    #[tokio::test]
+    async fn anthropic_messages_stream_in_anthropic_framing() {
+        let upstream = FakeUpstream::start(|req| match req.body["messages"][0]["content"].as_str() {
+            Some("fail") => FakeReply::Status(400),
+            _ => FakeReply::Text("one two three".into()),
+        })
+        .await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let request = |content: &str| json!({"model": MODEL, "max_tokens": 64, "stream": true, "messages": [{"role": "user", "content": content}]});
+        let parse = |body: String| -> Vec<(String, Value)> {
+            body.split_terminator("\n\n")
+                .map(|event| {
+                    let (name, data) = event.split_once("\ndata: ").unwrap();
+                    (name.strip_prefix("event: ").unwrap().to_string(), serde_json::from_str(data).unwrap())
+                })
+                .collect()
+        };
+
+        let resp = proxy.post("/v1/messages", request("hi")).await;
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let events = parse(resp.text().await.unwrap());
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names[..3], ["message_start", "ping", "content_block_start"]);
+        assert_eq!(names[names.len() - 3..], ["content_block_stop", "message_delta", "message_stop"]);
+        assert!(events.iter().all(|(name, data)| data["type"] == name.as_str()));
+        let text: String = events.iter().filter_map(|(_, data)| data["delta"]["text"].as_str()).collect();
+        assert_eq!(text, "one two three");
+        let delta = &events[events.len() - 2].1;
+        assert_eq!(delta["delta"]["stop_reason"], "end_turn");
+        assert_eq!((delta["usage"]["input_tokens"].as_u64(), delta["usage"]["output_tokens"].as_u64()), (Some(10), Some(5)));
+
+        let resp = proxy.post("/v1/messages", request("fail")).await;
+        let body = resp.text().await.unwrap();
+        let (name, error) = parse(body).pop().unwrap();
+        assert_eq!(name, "error");
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["error"]["type"], "fake_error");
+    }
+
+    #[tokio::test]
+    async fn completions_are_answered_as_chats_without_a_native_endpoint() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("x + y".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let body = json!({"model": MODEL, "prompt": ["fn add(x: i32, y: i32) -> i32 { "], "suffix": " }", "max_tokens": 16});
+        let resp = proxy.post("/v1/completions", body).await;
+        assert_eq!(resp.status(), 200);
+        let resp: Value = resp.json().await.unwrap();
+        assert_eq!(resp["object"], "text_completion");
+        assert_eq!(resp["choices"][0]["text"], "x + y");
+        assert_eq!(resp["choices"][0]["finish_reason"], "stop");
+        assert_eq!(resp["usage"]["completion_tokens"], 5);
+
+        let sent = &upstream.requests()[0].body;
+        assert_eq!(sent["max_tokens"], 16);
+        let user = sent["messages"].as_array().unwrap().last().unwrap()["content"].to_string();
+        assert!(user.contains("<prefix>fn add(x: i32, y: i32) -> i32 { </prefix><suffix> }</suffix>"), "{}", user);
+
+        let batch = json!({"model": MODEL, "prompt": ["a", "b"]});
+        assert_eq!(proxy.post("/v1/completions", batch).await.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn completions_pass_through_to_a_native_endpoint() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("one two three".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "completions": true,
+            "models": [{"id": "test"}],
+        }))
+        .unwrap();
+        proxy.state.config.upsert_provider_def(def).unwrap();
+        proxy.state.refresh_models().await;
+
+        let body = json!({"model": MODEL, "prompt": "def f(", "suffix": "\n", "stream": true, "top_p": 0.9});
+        let resp = proxy.post("/v1/completions", body).await;
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let chunks: Vec<Value> = sse_data(&resp.text().await.unwrap())
+            .iter()
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect();
+        assert!(chunks.iter().all(|c| c["object"] == "text_completion"));
+        let text: String = chunks.iter().filter_map(|c| c["choices"][0]["text"].as_str()).collect();
+        assert_eq!(text, "one two three");
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+        assert_eq!(last["usage"]["prompt_tokens"], 10);
+
+        let sent = &upstream.requests()[0].body;
+        assert_eq!((&sent["prompt"], &sent["suffix"], &sent["top_p"]), (&json!("def f("), &json!("\n"), &json!(0.9)));
+        assert!(sent.get("messages").is_none());
+    }
+
+    #[tokio::test]
+    async fn copilot_engine_route_completes_with_the_aliased_model() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("return a".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let mut settings = proxy.state.config.get_settings().unwrap();
+        settings.model_aliases.insert("copilot-codex".into(), MODEL.into());
+        proxy.state.config.set_settings(settings).unwrap();
+
+        let body = json!({
+            "prompt": "def first(a):\n    ",
+            "suffix": "\n",
+            "max_tokens": 64,
+            "n": 1,
+            "stream": true,
+            "extra": {"language": "python", "next_indent": 0},
+        });
+        let resp = proxy.post("/v1/engines/copilot-codex/completions", body).await;
+        assert_eq!(resp.status(), 200);
+        let text: String = sse_data(&resp.text().await.unwrap())
+            .iter()
+            .filter_map(|d| serde_json::from_str::<Value>(d).unwrap()["choices"][0]["text"].as_str().map(String::from))
+            .collect();
+        assert_eq!(text, "return a");
+
+        let sent = &upstream.requests()[0].body;
+        assert_eq!(sent["model"], "test");
+        assert!(sent.get("extra").is_none() && sent.get("n").is_none());
+    }
+
+    #[tokio::test]
+    async fn copilot_chat_requests_are_served_without_the_v1_prefix() {
+        let upstream = FakeUpstream::start(|_| FakeReply::Text("Use a slice".into())).await;
+        let proxy = TestProxy::start(&upstream, &["k1"]).await;
+        let body = json!({
+            "model": MODEL,
+            "intent": true,
+            "n": 1,
+            "stream": false,
+            "messages": [
+                {"role": "system", "content": "You are a programming assistant."},
+                {"role": "system", "content": [{"type": "text", "text": "The user works in Rust."}]},
+                {"role": "user", "content": [{"type": "text", "text": "How do I pass a Vec?"}]},
+            ],
+        });
+        let resp = proxy.post("/chat/completions", body).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.json::<Value>().await.unwrap()["choices"][0]["message"]["content"], "Use a slice");
+
+        let sent = &upstream.requests()[0].body;
+        assert!(sent.get("intent").is_none());
+        assert_eq!(sent["messages"][0]["content"], "You are a programming assistant.\n\nThe user works in Rust.");
+
+        let models: Value = reqwest::get(format!("{}/models", proxy.base_url)).await.unwrap().json().await.unwrap();
+        assert!(models["data"].as_array().unwrap().iter().any(|m| m["id"] == MODEL));
+    }
+
+    #[test]
+    fn thinking_signatures_are_kept_from_client_turns() {
+        let msgs: Vec<super::AnthropicMessage> = serde_json::from_value(json!([{
+            "role": "assistant",
+            "content": [
+                {"type": "thinking", "thinking": "look it up", "signature": "sig=="},
+                {"type": "tool_use", "id": "toolu_1", "name": "search", "input": {}}
+            ]
+        }]))
+        .unwrap();
+        let messages = super::convert_anthropic_messages(&msgs);
+        let zeroai::Message::Assistant(a) = &messages[0] else { panic!("expected assistant turn") };
+        let zeroai::ContentBlock::Thinking(th) = &a.content[0] else { panic!("expected thinking") };
+        assert_eq!(th.signature.as_deref(), Some("sig=="));
+    }
 }
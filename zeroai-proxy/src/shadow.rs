@@ -0,0 +1,195 @@
+//! Shadow traffic: trying a second model on real requests without clients noticing.
+//!
+//! With `settings.shadow` set, a sampled `percent` of successful chat requests (chat
+//! completions and `/v1/messages`, streamed or not) answered by the listed models are sent
+//! again, in the background, to `shadow.model`. Clients only ever get the primary answer. The
+//! shadow answer is compared with it and discarded: both latencies, token counts and costs,
+//! how close the two texts are and whether both called the same tools are logged (target
+//! `shadow`) and kept in the store, and `GET /v1/usage` sums them up per model pair. Shadow
+//! calls are billed, so their usage is recorded like any other, under the client app
+//! [`SHADOW_APP`], and none are made once the daily budget is reached.
+
+use crate::access_log::RequestLog;
+use crate::server::AppState;
+use std::collections::HashSet;
+use std::sync::Arc;
+use zeroai::auth::usage::RequestSource;
+use zeroai::store::{ShadowResult, blocking};
+use zeroai::types::{AssistantMessage, ChatContext, ContentBlock, ModelCost, RequestOptions};
+use zeroai::{AiClient, split_model_id};
+
+/// Client app that shadow calls are counted against, apart from the clients they copy.
+pub const SHADOW_APP: &str = "zeroai-shadow";
+
+/// An answered request that may get a shadow copy.
+pub struct Primary<'a> {
+    pub model: &'a str,
+    pub context: &'a ChatContext,
+    pub options: &'a RequestOptions,
+    pub message: &'a AssistantMessage,
+    pub cost: &'a ModelCost,
+}
+
+/// Send a copy of `primary`'s request to the shadow model in the background if
+/// `settings.shadow` samples it.
+pub fn mirror(state: &Arc<AppState>, client: &AiClient, primary: Primary<'_>, log: &RequestLog) {
+    let Ok(settings) = state.config.get_settings() else {
+        return;
+    };
+    let Some(config) = settings.shadow.clone() else {
+        return;
+    };
+    if !config.mirrors(primary.model) || rand::random::<f64>() * 100.0 >= config.percent {
+        return;
+    }
+    let usage = primary.message.usage.clone().unwrap_or_default();
+    let mut result = ShadowResult {
+        at_ms: chrono::Utc::now().timestamp_millis(),
+        model: primary.model.to_string(),
+        shadow_model: config.model,
+        error: None,
+        duration_ms: usage.duration_ms,
+        shadow_duration_ms: None,
+        output_tokens: usage.output_tokens,
+        shadow_output_tokens: 0,
+        cost_usd: primary.cost.usage_cost(&usage),
+        shadow_cost_usd: 0.0,
+        similarity: None,
+        same_tool_calls: None,
+    };
+    // Sampling settings carry over; keys, headers and vendor fields belong to the primary's provider.
+    let options = RequestOptions {
+        temperature: primary.options.temperature,
+        max_tokens: primary.options.max_tokens,
+        reasoning: primary.options.reasoning.clone(),
+        thinking_budget: primary.options.thinking_budget,
+        web_search: primary.options.web_search,
+        code_execution: primary.options.code_execution,
+        ..Default::default()
+    };
+    let (state, client, log) = (state.clone(), client.clone(), log.clone());
+    let (context, answer) = (primary.context.clone(), primary.message.clone());
+    state.background.clone().spawn(async move {
+        if state.budget_exceeded(&settings, &log).await.is_some() {
+            return;
+        }
+        match call(&state, &client, &result.shadow_model, &context, options).await {
+            Ok((shadow, cost_usd)) => {
+                let usage = shadow.usage.clone().unwrap_or_default();
+                result.shadow_duration_ms = usage.duration_ms;
+                result.shadow_output_tokens = usage.output_tokens;
+                result.shadow_cost_usd = cost_usd;
+                result.similarity = Some(similarity(&text(&answer), &text(&shadow)));
+                result.same_tool_calls = Some(tool_names(&answer) == tool_names(&shadow));
+            }
+            Err(e) => result.error = Some(e),
+        }
+        tracing::info!(
+            target: "shadow",
+            request_id = log.id(),
+            model = %result.model,
+            shadow_model = %result.shadow_model,
+            error = result.error.as_deref().unwrap_or("-"),
+            duration_ms = result.duration_ms,
+            shadow_duration_ms = result.shadow_duration_ms,
+            output_tokens = result.output_tokens,
+            shadow_output_tokens = result.shadow_output_tokens,
+            cost_usd = result.cost_usd,
+            shadow_cost_usd = result.shadow_cost_usd,
+            similarity = result.similarity,
+            same_tool_calls = result.same_tool_calls,
+            "shadow"
+        );
+        let store = state.store.clone();
+        if let Err(e) = blocking(move || store.record_shadow_result(&result)).await {
+            tracing::debug!("Failed to record shadow result: {}", e);
+        }
+    });
+}
+
+/// Ask the shadow model, charging an account of its provider. Returns the answer and its cost.
+async fn call(
+    state: &AppState,
+    client: &AiClient,
+    model: &str,
+    context: &ChatContext,
+    mut options: RequestOptions,
+) -> Result<(AssistantMessage, f64), String> {
+    let (provider, _) = split_model_id(model).ok_or_else(|| format!("Invalid shadow model ID: {}", model))?;
+    let cost = client
+        .get_model(model)
+        .map(|m| m.cost.clone())
+        .ok_or_else(|| format!("Shadow model {} is not enabled", model))?;
+    let sel = state
+        .resolve_account(provider)
+        .await
+        .ok_or_else(|| format!("No credentials for provider: {}", provider))?;
+    options.api_key = Some(sel.api_key.clone());
+    match client.chat(model, context, &options).await {
+        Ok(message) => {
+            let source = RequestSource { app: Some(SHADOW_APP), ..Default::default() };
+            state.record_usage(provider, &sel.account_id, source, message.usage.as_ref(), &cost).await;
+            state.record_latency(model, message.usage.as_ref()).await;
+            let cost_usd = message.usage.as_ref().map_or(0.0, |u| cost.usage_cost(u));
+            Ok((message, cost_usd))
+        }
+        Err(e) => {
+            state.rate_limit(provider, &sel.account_id, &e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// The answer's text blocks, joined.
+fn text(message: &AssistantMessage) -> String {
+    message
+        .content
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text(t) => Some(t.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tool_names(message: &AssistantMessage) -> Vec<&str> {
+    message
+        .content
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::ToolCall(c) => Some(c.name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Share of distinct words (case-insensitive) the two texts have in common, from 0 to 1.
+/// Two empty texts (e.g. two tool-call-only answers) count as the same.
+fn similarity(a: &str, b: &str) -> f64 {
+    let words = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_compares_words() {
+        assert_eq!(similarity("The cat sat.", "the CAT sat"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("yes", ""), 0.0);
+        assert_eq!(similarity("a b c d", "a b x y"), 2.0 / 6.0);
+    }
+}
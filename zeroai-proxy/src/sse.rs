@@ -0,0 +1,484 @@
+//! Server-sent events for streamed OpenAI chat and text completions and Anthropic messages.
+//!
+//! Every `chat.completion.chunk` of a stream shares its envelope (`id`, `created`, `model`), so
+//! [`ChunkWriter`] serializes it once and writes each delta between that prefix and a fixed
+//! suffix, straight into a reused buffer. The per-token path builds no `serde_json::Value` and
+//! no intermediate `String`s. [`MessageWriter`] does the same for the `content_block_delta`
+//! events of a message. A provider with an `on_response` script still gets each chunk or event
+//! as a `Value` to rewrite.
+
+use crate::transform::Transformer;
+use axum::body::Body;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::Stream;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+
+/// Everything after a delta in a chunk that doesn't finish the choice.
+const DELTA_SUFFIX: &[u8] = b",\"finish_reason\":null}]}\n\n";
+
+/// Frames the chunks of one streamed completion as SSE `data:` events.
+pub struct ChunkWriter {
+    id: String,
+    created: i64,
+    model: String,
+    transformer: Option<Transformer>,
+    /// `data: {"id":...,"choices":[{"index":0,"delta":`
+    prefix: Vec<u8>,
+    buf: BytesMut,
+}
+
+impl ChunkWriter {
+    pub fn new(model: &str, transformer: Option<Transformer>) -> Self {
+        let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+        let created = chrono::Utc::now().timestamp();
+        let mut prefix = b"data: ".to_vec();
+        let envelope = json!({"id": id, "object": "chat.completion.chunk", "created": created, "model": model});
+        let envelope = envelope.to_string();
+        prefix.extend_from_slice(&envelope.as_bytes()[..envelope.len() - 1]);
+        prefix.extend_from_slice(b",\"choices\":[{\"index\":0,\"delta\":");
+        Self {
+            id,
+            created,
+            model: model.to_string(),
+            transformer,
+            prefix,
+            buf: BytesMut::with_capacity(4096),
+        }
+    }
+
+    /// A chunk whose delta is `{"content": text}`, the one sent for every token.
+    pub fn content(&mut self, text: &str) -> Bytes {
+        if self.transformer.is_some() {
+            return self.delta(&json!({"content": text}));
+        }
+        self.buf.extend_from_slice(&self.prefix);
+        self.buf.extend_from_slice(b"{\"content\":");
+        write_json(&mut self.buf, text);
+        self.buf.put_u8(b'}');
+        self.buf.extend_from_slice(DELTA_SUFFIX);
+        self.buf.split().freeze()
+    }
+
+    /// A chunk with any other delta (a refusal, citations, a tool call fragment).
+    pub fn delta(&mut self, delta: &impl Serialize) -> Bytes {
+        if self.transformer.is_some() {
+            let chunk = self.chunk(json!({"index": 0, "delta": delta, "finish_reason": null}));
+            return self.event(chunk);
+        }
+        self.buf.extend_from_slice(&self.prefix);
+        write_json(&mut self.buf, delta);
+        self.buf.extend_from_slice(DELTA_SUFFIX);
+        self.buf.split().freeze()
+    }
+
+    /// A whole chunk with `choice` as its only choice, for chunks that need more than a delta
+    /// (the closing one). Send it with [`event`](Self::event).
+    pub fn chunk(&self, choice: Value) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [choice],
+        })
+    }
+
+    /// `data: <json>` for `value`, after the provider's `on_response` script.
+    pub fn event(&mut self, mut value: Value) -> Bytes {
+        if let Some(t) = &self.transformer {
+            t.response(&mut value, true);
+        }
+        self.buf.extend_from_slice(b"data: ");
+        write_json(&mut self.buf, &value);
+        self.buf.extend_from_slice(b"\n\n");
+        self.buf.split().freeze()
+    }
+}
+
+/// Everything after the text of a `text_completion` chunk that doesn't finish the choice.
+const TEXT_SUFFIX: &[u8] = b",\"logprobs\":null,\"finish_reason\":null}]}\n\n";
+
+/// Frames the chunks of one streamed legacy text completion (`"object": "text_completion"`)
+/// like [`ChunkWriter`], with the text in place of a delta.
+pub struct CompletionWriter {
+    id: String,
+    created: i64,
+    model: String,
+    /// `data: {"id":...,"choices":[{"index":0,"text":`
+    prefix: Vec<u8>,
+    buf: BytesMut,
+}
+
+impl CompletionWriter {
+    pub fn new(model: &str) -> Self {
+        let id = format!("cmpl-{}", uuid::Uuid::new_v4());
+        let created = chrono::Utc::now().timestamp();
+        let mut prefix = b"data: ".to_vec();
+        let envelope = json!({"id": id, "object": "text_completion", "created": created, "model": model});
+        let envelope = envelope.to_string();
+        prefix.extend_from_slice(&envelope.as_bytes()[..envelope.len() - 1]);
+        prefix.extend_from_slice(b",\"choices\":[{\"index\":0,\"text\":");
+        Self {
+            id,
+            created,
+            model: model.to_string(),
+            prefix,
+            buf: BytesMut::with_capacity(4096),
+        }
+    }
+
+    /// A chunk with the next piece of text.
+    pub fn text(&mut self, text: &str) -> Bytes {
+        self.buf.extend_from_slice(&self.prefix);
+        write_json(&mut self.buf, text);
+        self.buf.extend_from_slice(TEXT_SUFFIX);
+        self.buf.split().freeze()
+    }
+
+    /// The closing chunk: no text, the finish reason and the usage.
+    pub fn finish(&mut self, finish_reason: &str, usage: Value) -> Bytes {
+        self.event(&json!({
+            "id": self.id,
+            "object": "text_completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "text": "", "logprobs": null, "finish_reason": finish_reason}],
+            "usage": usage,
+        }))
+    }
+
+    /// `data: {"error": error}`, which ends the stream.
+    pub fn error(&mut self, error: Value) -> Bytes {
+        self.event(&json!({"error": error}))
+    }
+
+    fn event(&mut self, value: &Value) -> Bytes {
+        self.buf.extend_from_slice(b"data: ");
+        write_json(&mut self.buf, value);
+        self.buf.extend_from_slice(b"\n\n");
+        self.buf.split().freeze()
+    }
+}
+
+/// Kinds of content block a streamed message opens.
+#[derive(Clone, Copy, PartialEq)]
+enum BlockKind {
+    Text,
+    Thinking,
+    ToolUse,
+}
+
+/// Frames one streamed Anthropic message as SSE events, each an `event: <type>` line naming
+/// the `type` of its `data:` object: `message_start` and a `ping`, then every content block
+/// between `content_block_start` and `content_block_stop`, then `message_delta` and
+/// `message_stop`. Blocks are opened and closed as the kind of delta changes.
+pub struct MessageWriter {
+    id: String,
+    model: String,
+    transformer: Option<Transformer>,
+    /// The open content block and its index.
+    block: Option<(BlockKind, usize)>,
+    /// Index of the next content block.
+    next_index: usize,
+    /// Whether the open `tool_use` block has had an `input_json_delta`.
+    input_sent: bool,
+    buf: BytesMut,
+}
+
+impl MessageWriter {
+    pub fn new(model: &str, transformer: Option<Transformer>) -> Self {
+        Self {
+            id: format!("msg_{}", uuid::Uuid::new_v4()),
+            model: model.to_string(),
+            transformer,
+            block: None,
+            next_index: 0,
+            input_sent: false,
+            buf: BytesMut::with_capacity(4096),
+        }
+    }
+
+    /// `message_start` and the first `ping`. Usage is only known at the end, in `message_delta`.
+    pub fn start(&mut self) -> Bytes {
+        let message = json!({
+            "id": self.id,
+            "type": "message",
+            "role": "assistant",
+            "content": [],
+            "model": self.model,
+            "stop_reason": null,
+            "stop_sequence": null,
+            "usage": {"input_tokens": 0, "output_tokens": 0},
+        });
+        self.write(json!({"type": "message_start", "message": message}));
+        self.write(json!({"type": "ping"}));
+        self.buf.split().freeze()
+    }
+
+    pub fn text(&mut self, text: &str) -> Bytes {
+        self.open(BlockKind::Text, json!({"type": "text", "text": ""}));
+        self.delta("text_delta", "text", text);
+        self.buf.split().freeze()
+    }
+
+    pub fn thinking(&mut self, thinking: &str) -> Bytes {
+        self.open(BlockKind::Thinking, json!({"type": "thinking", "thinking": ""}));
+        self.delta("thinking_delta", "thinking", thinking);
+        self.buf.split().freeze()
+    }
+
+    /// A `signature_delta` for the open thinking block; nothing outside one.
+    pub fn signature(&mut self, signature: &str) -> Bytes {
+        if matches!(self.block, Some((BlockKind::Thinking, _))) {
+            self.delta("signature_delta", "signature", signature);
+        }
+        self.buf.split().freeze()
+    }
+
+    /// Open a `tool_use` block; its input follows as [`input_json`](Self::input_json).
+    pub fn tool_use(&mut self, id: &str, name: &str) -> Bytes {
+        self.close();
+        self.open(BlockKind::ToolUse, json!({"type": "tool_use", "id": id, "name": name, "input": {}}));
+        self.buf.split().freeze()
+    }
+
+    /// A fragment of the open tool call's input, passed on as it arrives.
+    pub fn input_json(&mut self, partial_json: &str) -> Bytes {
+        self.input_delta(partial_json);
+        self.buf.split().freeze()
+    }
+
+    /// The end of the open tool call: its whole input, for providers that send no fragments.
+    pub fn tool_use_end(&mut self, input: &Value) -> Bytes {
+        if !self.input_sent {
+            self.input_delta(&input.to_string());
+        }
+        self.buf.split().freeze()
+    }
+
+    /// Close the open block, then `message_delta` with the stop reason and usage, and
+    /// `message_stop`.
+    pub fn finish(&mut self, stop_reason: &str, usage: Value) -> Bytes {
+        self.close();
+        self.write(json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": stop_reason, "stop_sequence": null},
+            "usage": usage,
+        }));
+        self.write(json!({"type": "message_stop"}));
+        self.buf.split().freeze()
+    }
+
+    /// An `error` event, which ends the stream; `error` is `{"type": ..., "message": ...}`.
+    pub fn error(&mut self, error: Value) -> Bytes {
+        self.write(json!({"type": "error", "error": error}));
+        self.buf.split().freeze()
+    }
+
+    /// Unless a block of `kind` is open, close the open block and start `block`.
+    fn open(&mut self, kind: BlockKind, block: Value) {
+        if self.block.is_some_and(|(open, _)| open == kind) {
+            return;
+        }
+        self.close();
+        let index = self.next_index;
+        self.next_index += 1;
+        self.block = Some((kind, index));
+        self.input_sent = false;
+        self.write(json!({"type": "content_block_start", "index": index, "content_block": block}));
+    }
+
+    fn input_delta(&mut self, partial_json: &str) {
+        if matches!(self.block, Some((BlockKind::ToolUse, _))) {
+            self.input_sent = true;
+            self.delta("input_json_delta", "partial_json", partial_json);
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some((_, index)) = self.block.take() {
+            self.write(json!({"type": "content_block_stop", "index": index}));
+        }
+    }
+
+    /// `content_block_delta` of the open block with `{"type": kind, field: value}`.
+    fn delta(&mut self, kind: &str, field: &str, value: &str) {
+        let Some((_, index)) = self.block else { return };
+        if self.transformer.is_some() {
+            self.write(json!({"type": "content_block_delta", "index": index, "delta": {"type": kind, field: value}}));
+            return;
+        }
+        self.buf.extend_from_slice(b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":");
+        self.buf.extend_from_slice(index.to_string().as_bytes());
+        self.buf.extend_from_slice(b",\"delta\":{\"type\":");
+        write_json(&mut self.buf, kind);
+        self.buf.put_u8(b',');
+        write_json(&mut self.buf, field);
+        self.buf.put_u8(b':');
+        write_json(&mut self.buf, value);
+        self.buf.extend_from_slice(b"}}\n\n");
+    }
+
+    /// `event: <type>` and `data: <json>` for `value`, after the provider's `on_response` script.
+    fn write(&mut self, mut value: Value) {
+        if let Some(t) = &self.transformer {
+            t.response(&mut value, true);
+        }
+        self.buf.extend_from_slice(b"event: ");
+        self.buf.extend_from_slice(value["type"].as_str().unwrap_or("message").as_bytes());
+        self.buf.extend_from_slice(b"\ndata: ");
+        write_json(&mut self.buf, &value);
+        self.buf.extend_from_slice(b"\n\n");
+    }
+}
+
+fn write_json(buf: &mut BytesMut, value: &(impl Serialize + ?Sized)) {
+    // Writing into memory only fails for values that can't be JSON (e.g. non-string map keys),
+    // which chunks never contain.
+    let _ = serde_json::to_writer(buf.writer(), value);
+}
+
+/// A `text/event-stream` response streaming `events` as they come.
+pub fn response(events: impl Stream<Item = Bytes> + Send + 'static) -> Response {
+    use futures::StreamExt;
+    let body = Body::from_stream(events.map(Ok::<_, Infallible>));
+    ([(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn parse(event: &Bytes) -> Value {
+        let text = std::str::from_utf8(event).unwrap();
+        let json = text.strip_prefix("data: ").unwrap().strip_suffix("\n\n").unwrap();
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn spliced_chunks_are_whole_json() {
+        let mut writer = ChunkWriter::new("openai/gpt-4o \"mini\"", None);
+        let text = parse(&writer.content("line\n\"quoted\" \u{1F600}"));
+        assert_eq!(text["object"], "chat.completion.chunk");
+        assert_eq!(text["model"], "openai/gpt-4o \"mini\"");
+        assert_eq!(text["choices"][0]["delta"]["content"], "line\n\"quoted\" \u{1F600}");
+        assert!(text["choices"][0]["finish_reason"].is_null() && text.get("usage").is_none());
+
+        let tool = parse(&writer.delta(&json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"a\""}}]})));
+        assert_eq!(tool["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"], "{\"a\"");
+        // One ID for the whole completion.
+        assert_eq!(tool["id"], text["id"]);
+
+        let done = writer.chunk(json!({"index": 0, "delta": {}, "finish_reason": "stop"}));
+        let done = parse(&writer.event(done));
+        assert_eq!((&done["id"], &done["choices"][0]["finish_reason"]), (&text["id"], &json!("stop")));
+    }
+
+    #[test]
+    fn completion_chunks_are_text_completions() {
+        let mut writer = CompletionWriter::new("vllm/qwen");
+        let text = parse(&writer.text("fn main() {\n\"x\""));
+        assert_eq!(text["object"], "text_completion");
+        assert_eq!(text["choices"][0]["text"], "fn main() {\n\"x\"");
+        assert!(text["choices"][0]["finish_reason"].is_null());
+
+        let done = parse(&writer.finish("length", json!({"completion_tokens": 5})));
+        assert_eq!((&done["id"], &done["choices"][0]["finish_reason"]), (&text["id"], &json!("length")));
+        assert_eq!(done["usage"]["completion_tokens"], 5);
+    }
+
+    /// `(event, data)` of each event in `bytes`.
+    fn parse_events(bytes: &[Bytes]) -> Vec<(String, Value)> {
+        let text: String = bytes.iter().map(|b| std::str::from_utf8(b).unwrap()).collect();
+        text.split_terminator("\n\n")
+            .map(|event| {
+                let (name, data) = event.split_once('\n').unwrap();
+                let name = name.strip_prefix("event: ").unwrap().to_string();
+                (name, serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn message_events_follow_anthropic_framing() {
+        let mut writer = MessageWriter::new("anthropic/claude \"x\"", None);
+        let events = parse_events(&[
+            writer.start(),
+            writer.thinking("hmm"),
+            writer.signature("sig"),
+            writer.text("Let me "),
+            writer.text("look \"it\" up.\n"),
+            writer.tool_use("toolu_1", "search"),
+            writer.tool_use_end(&json!({"q": "rust"})),
+            writer.tool_use("toolu_2", "fetch"),
+            writer.input_json("{\"url\":"),
+            writer.tool_use_end(&json!({"url": "x"})),
+            writer.finish("tool_use", json!({"input_tokens": 3, "output_tokens": 9})),
+        ]);
+        for (name, data) in &events {
+            assert_eq!(data["type"], name.as_str());
+        }
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "message_start", "ping",
+                "content_block_start", "content_block_delta", "content_block_delta", "content_block_stop",
+                "content_block_start", "content_block_delta", "content_block_delta", "content_block_stop",
+                "content_block_start", "content_block_delta", "content_block_stop",
+                "content_block_start", "content_block_delta", "content_block_stop",
+                "message_delta", "message_stop",
+            ]
+        );
+        assert_eq!(events[0].1["message"]["model"], "anthropic/claude \"x\"");
+        assert_eq!(events[4].1["delta"], json!({"type": "signature_delta", "signature": "sig"}));
+        assert_eq!(events[8].1["delta"], json!({"type": "text_delta", "text": "look \"it\" up.\n"}));
+        assert_eq!(events[11].1["delta"], json!({"type": "input_json_delta", "partial_json": "{\"q\":\"rust\"}"}));
+        // Fragments are passed on as they come, not replaced by the whole input.
+        assert_eq!(events[14].1["delta"]["partial_json"], "{\"url\":");
+        assert_eq!((events[13].1["index"].as_u64(), events[15].1["index"].as_u64()), (Some(3), Some(3)));
+        assert_eq!(events[16].1["delta"]["stop_reason"], "tool_use");
+
+        let error = parse_events(&[writer.error(json!({"type": "overloaded_error", "message": "Overloaded"}))]);
+        assert_eq!(error[0].0, "error");
+        assert_eq!(error[0].1, json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}));
+    }
+
+    /// `cargo test -p zeroai-proxy sse_throughput -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn sse_throughput() {
+        const TOKENS: u32 = 200_000;
+        let model = "openai/gpt-4o-mini";
+
+        let started = Instant::now();
+        let mut bytes = 0;
+        for _ in 0..TOKENS {
+            let chunk = json!({
+                "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
+                "object": "chat.completion.chunk",
+                "created": chrono::Utc::now().timestamp(),
+                "model": model,
+                "choices": [{"index": 0, "delta": {"content": " token"}, "finish_reason": null}]
+            });
+            bytes += format!("data: {}\n\n", chunk).len();
+        }
+        let per_value = started.elapsed() / TOKENS;
+
+        let started = Instant::now();
+        let mut writer = ChunkWriter::new(model, None);
+        let mut spliced = 0;
+        for _ in 0..TOKENS {
+            spliced += writer.content(" token").len();
+        }
+        let per_splice = started.elapsed() / TOKENS;
+
+        println!("Value per chunk: {:?}/token ({} bytes); spliced: {:?}/token ({} bytes)", per_value, bytes, per_splice, spliced);
+        assert!(per_splice < per_value);
+    }
+}
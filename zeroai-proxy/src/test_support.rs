@@ -0,0 +1,208 @@
+//! In-process fakes for the proxy's tests: a programmable OpenAI-compatible upstream and a
+//! proxy instance wired to it through a temporary config.
+
+use crate::server::{AppState, router};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use zeroai::auth::{ApiKeyCredential, Credential};
+use zeroai::store::Store;
+use zeroai::{ConfigManager, ProviderDef};
+
+/// Provider ID of the fake upstream in the test config.
+pub const PROVIDER: &str = "fake";
+/// The model enabled in the test config.
+pub const MODEL: &str = "fake/test";
+/// The `x-request-id` the fake upstream answers with.
+pub const UPSTREAM_REQUEST_ID: &str = "req_fake";
+
+/// What the fake upstream answers to one chat (or text) completion request.
+pub enum FakeReply {
+    /// Reply with this text; streamed word by word when the request asks for a stream.
+    Text(String),
+    /// Refuse with this text in the `refusal` field instead of `content`.
+    Refusal(String),
+    /// Fail with this HTTP status and an OpenAI-style error body.
+    Status(u16),
+}
+
+/// A chat or text completion request the fake upstream received.
+#[derive(Debug, Clone)]
+pub struct UpstreamRequest {
+    /// Bearer token the proxy sent.
+    pub api_key: String,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+type Handler = dyn Fn(&UpstreamRequest) -> FakeReply + Send + Sync;
+
+struct Upstream {
+    handler: Box<Handler>,
+    requests: Mutex<Vec<UpstreamRequest>>,
+}
+
+/// An OpenAI-compatible server on a random local port answering `POST /v1/chat/completions`
+/// and the legacy `POST /v1/completions`.
+pub struct FakeUpstream {
+    pub base_url: String,
+    upstream: Arc<Upstream>,
+}
+
+impl FakeUpstream {
+    pub async fn start(handler: impl Fn(&UpstreamRequest) -> FakeReply + Send + Sync + 'static) -> Self {
+        let upstream = Arc::new(Upstream {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        });
+        let app = Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/completions", post(completions))
+            .with_state(upstream.clone());
+        Self {
+            base_url: format!("{}/v1", serve(app).await),
+            upstream,
+        }
+    }
+
+    /// Requests received so far, in order.
+    pub fn requests(&self) -> Vec<UpstreamRequest> {
+        self.upstream.requests.lock().unwrap().clone()
+    }
+}
+
+/// Record the request and ask the test's handler for the reply.
+fn receive(upstream: &Upstream, headers: HeaderMap, body: Value) -> (FakeReply, bool, Value) {
+    let request = UpstreamRequest {
+        api_key: headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or_default()
+            .to_string(),
+        headers,
+        body,
+    };
+    let reply = (upstream.handler)(&request);
+    let stream = request.body["stream"].as_bool().unwrap_or(false);
+    let model = request.body["model"].clone();
+    upstream.requests.lock().unwrap().push(request);
+    (reply, stream, model)
+}
+
+fn error_response(status: u16) -> Response {
+    let error = json!({"message": format!("fake {}", StatusCode::from_u16(status).unwrap()), "type": "fake_error", "code": status});
+    let status = StatusCode::from_u16(status).unwrap();
+    (status, [("x-request-id", UPSTREAM_REQUEST_ID)], Json(json!({"error": error}))).into_response()
+}
+
+fn usage() -> Value {
+    json!({"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15})
+}
+
+async fn chat_completions(State(upstream): State<Arc<Upstream>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    let (reply, stream, model) = receive(&upstream, headers, body);
+    let (field, text) = match reply {
+        FakeReply::Status(status) => return error_response(status),
+        FakeReply::Text(text) => ("content", text),
+        FakeReply::Refusal(text) => ("refusal", text),
+    };
+    let usage = usage();
+    if !stream {
+        return (
+            [("x-request-id", UPSTREAM_REQUEST_ID)],
+            Json(json!({
+            "id": "chatcmpl-fake",
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{"index": 0, "message": {"role": "assistant", field: text}, "finish_reason": "stop"}],
+            "usage": usage,
+            })),
+        )
+            .into_response();
+    }
+
+    let mut sse = String::new();
+    let mut push = |chunk: Value| sse.push_str(&format!("data: {}\n\n", chunk));
+    for word in text.split_inclusive(' ') {
+        push(json!({"choices": [{"index": 0, "delta": {field: word}, "finish_reason": null}]}));
+    }
+    push(json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}], "usage": usage}));
+    sse.push_str("data: [DONE]\n\n");
+    ([("content-type", "text/event-stream"), ("x-request-id", UPSTREAM_REQUEST_ID)], sse).into_response()
+}
+
+/// Streams its text completions word by word; a refusal is sent as plain text.
+async fn completions(State(upstream): State<Arc<Upstream>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    let (reply, _, model) = receive(&upstream, headers, body);
+    let text = match reply {
+        FakeReply::Status(status) => return error_response(status),
+        FakeReply::Text(text) | FakeReply::Refusal(text) => text,
+    };
+    let mut sse = String::new();
+    let mut push = |chunk: Value| sse.push_str(&format!("data: {}\n\n", chunk));
+    for word in text.split_inclusive(' ') {
+        push(json!({"object": "text_completion", "model": model, "choices": [{"index": 0, "text": word, "finish_reason": null}]}));
+    }
+    push(json!({"object": "text_completion", "model": model, "choices": [{"index": 0, "text": "", "finish_reason": "length"}], "usage": usage()}));
+    sse.push_str("data: [DONE]\n\n");
+    ([("content-type", "text/event-stream"), ("x-request-id", UPSTREAM_REQUEST_ID)], sse).into_response()
+}
+
+/// A proxy serving [`MODEL`] from a [`FakeUpstream`], with one account per API key.
+pub struct TestProxy {
+    pub base_url: String,
+    pub state: Arc<AppState>,
+    _dir: TempDir,
+}
+
+impl TestProxy {
+    pub async fn start(upstream: &FakeUpstream, api_keys: &[&str]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ConfigManager::new(dir.path().join("config.json"));
+        let def: ProviderDef = serde_json::from_value(json!({
+            "id": PROVIDER,
+            "base_url": upstream.base_url,
+            "models": [{"id": "test"}],
+        }))
+        .unwrap();
+        config.upsert_provider_def(def).unwrap();
+        config.set_enabled_models(vec![MODEL.to_string()]).unwrap();
+        for key in api_keys {
+            let credential = Credential::ApiKey(ApiKeyCredential { key: key.to_string() });
+            config.add_account(PROVIDER, None, credential).unwrap();
+        }
+        let store = Arc::new(Store::open(dir.path().join("zeroai.db")).unwrap());
+        let state = Arc::new(AppState::with_stores(config, store, None));
+        Self {
+            base_url: serve(router(state.clone())).await,
+            state,
+            _dir: dir,
+        }
+    }
+
+    /// POST a JSON body to the proxy and return the response.
+    pub async fn post(&self, path: &str, body: Value) -> reqwest::Response {
+        self.request(path).json(&body).send().await.unwrap()
+    }
+
+    /// A POST to the proxy, for requests that need their own headers.
+    pub fn request(&self, path: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new().post(format!("{}{}", self.base_url, path))
+    }
+}
+
+/// Serve `app` on a random local port; returns its base URL.
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
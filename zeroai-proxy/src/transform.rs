@@ -0,0 +1,313 @@
+//! Per-provider request/response transformer scripts (`settings.transformers`).
+//!
+//! A transformer is a Rhai script defining `on_request` and/or `on_response`. Both run with
+//! `this` bound to a map the script edits in place:
+//!
+//! - `on_request`: `#{provider, model, body, headers}`. `body` is the client request
+//!   (OpenAI or Anthropic format); entries added to `headers` are sent upstream.
+//! - `on_response`: `#{provider, model, body, stream}`. `body` is the response, or one
+//!   chunk of it when `stream` is true.
+//!
+//! `on_request` sees the client request rather than the provider-bound body: zeroai's
+//! providers build that body after the proxy hands the request off, in a different wire
+//! format per API, and this way one script works for every provider. Top-level fields the
+//! proxy doesn't know in an OpenAI-format request are added to the provider body, so a
+//! script can still send provider-specific fields.
+//!
+//! Scripts are recompiled when the file changes. A failing script is logged and the payload
+//! passes through unchanged. Without the `transformers` feature, configured scripts are
+//! ignored with a warning.
+
+#[cfg(feature = "transformers")]
+use rhai::{AST, CallFnOptions, Dynamic, Engine, Scope};
+#[cfg(feature = "transformers")]
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+#[cfg(feature = "transformers")]
+use std::path::PathBuf;
+#[cfg(feature = "transformers")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "transformers")]
+use std::time::SystemTime;
+use zeroai::auth::config::ProxySettings;
+
+/// Key in `settings.transformers` for the script used by providers without their own.
+#[cfg(feature = "transformers")]
+const ANY_PROVIDER: &str = "*";
+
+/// Keeps runaway scripts from stalling a request.
+#[cfg(feature = "transformers")]
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Compiled transformer scripts, keyed by path.
+#[cfg(feature = "transformers")]
+pub struct Transformers {
+    engine: Arc<Engine>,
+    scripts: Mutex<HashMap<PathBuf, (SystemTime, Arc<AST>)>>,
+}
+
+#[cfg(feature = "transformers")]
+impl Transformers {
+    pub fn new() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|s| tracing::info!("transformer: {}", s));
+        engine.on_debug(|s, _, pos| tracing::debug!("transformer {}: {}", pos, s));
+        Self {
+            engine: Arc::new(engine),
+            scripts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The transformer for `provider` (its own script, else the `*` one), if any.
+    pub fn for_provider(&self, settings: &ProxySettings, provider: &str, model: &str) -> Option<Transformer> {
+        let path = settings
+            .transformers
+            .get(provider)
+            .or_else(|| settings.transformers.get(ANY_PROVIDER))?;
+        let ast = self.load(PathBuf::from(path))?;
+        Some(Transformer {
+            engine: self.engine.clone(),
+            ast,
+            provider: provider.to_string(),
+            model: model.to_string(),
+        })
+    }
+
+    fn load(&self, path: PathBuf) -> Option<Arc<AST>> {
+        let modified = match std::fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::warn!("Transformer script {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let mut scripts = self.scripts.lock().unwrap();
+        if let Some((compiled_at, ast)) = scripts.get(&path)
+            && *compiled_at == modified
+        {
+            return Some(ast.clone());
+        }
+        match self.engine.compile_file(path.clone()) {
+            Ok(ast) => {
+                let ast = Arc::new(ast);
+                scripts.insert(path, (modified, ast.clone()));
+                Some(ast)
+            }
+            Err(e) => {
+                tracing::warn!("Transformer script {} failed to compile: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// A compiled script bound to one request.
+#[cfg(feature = "transformers")]
+#[derive(Clone)]
+pub struct Transformer {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    provider: String,
+    model: String,
+}
+
+#[cfg(feature = "transformers")]
+impl Transformer {
+    /// Run `on_request` on a client request body. Returns the headers it added.
+    pub fn request(&self, body: &mut Value) -> HashMap<String, String> {
+        let this = json!({
+            "provider": self.provider,
+            "model": self.model,
+            "body": body,
+            "headers": {},
+        });
+        let Some(mut this) = self.call("on_request", this) else {
+            return HashMap::new();
+        };
+        *body = this["body"].take();
+        match this["headers"].take() {
+            Value::Object(headers) => headers
+                .into_iter()
+                .map(|(k, v)| {
+                    let v = v.as_str().map(String::from).unwrap_or_else(|| v.to_string());
+                    (k, v)
+                })
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Run `on_response` on a response body or, with `stream`, one streamed chunk.
+    pub fn response(&self, body: &mut Value, stream: bool) {
+        let this = json!({
+            "provider": self.provider,
+            "model": self.model,
+            "body": body,
+            "stream": stream,
+        });
+        if let Some(mut this) = self.call("on_response", this) {
+            *body = this["body"].take();
+        }
+    }
+
+    /// Call `func` with `this` bound to the given map; `None` if the script doesn't define
+    /// it or fails.
+    fn call(&self, func: &str, this: Value) -> Option<Value> {
+        if !self.ast.iter_functions().any(|f| f.name == func && f.params.is_empty()) {
+            return None;
+        }
+        let result = rhai::serde::to_dynamic(&this).and_then(|mut this| {
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+            // The return value is ignored; scripts edit `this` in place.
+            let _: Dynamic = self
+                .engine
+                .call_fn_with_options(options, &mut Scope::new(), &self.ast, func, ())?;
+            rhai::serde::from_dynamic::<Value>(&this)
+        });
+        match result {
+            Ok(this) => Some(this),
+            Err(e) => {
+                tracing::warn!("Transformer {} for {} failed: {}", func, self.provider, e);
+                None
+            }
+        }
+    }
+}
+
+/// Stands in for the script engine in builds without the `transformers` feature.
+#[cfg(not(feature = "transformers"))]
+pub struct Transformers {
+    warned: std::sync::Once,
+}
+
+#[cfg(not(feature = "transformers"))]
+impl Transformers {
+    pub fn new() -> Self {
+        Self { warned: std::sync::Once::new() }
+    }
+
+    /// Always `None`; warns once when scripts are configured.
+    pub fn for_provider(&self, settings: &ProxySettings, _provider: &str, _model: &str) -> Option<Transformer> {
+        if !settings.transformers.is_empty() {
+            self.warned.call_once(|| {
+                tracing::warn!("settings.transformers is ignored: built without the `transformers` feature");
+            });
+        }
+        None
+    }
+}
+
+/// Never constructed without the `transformers` feature.
+#[cfg(not(feature = "transformers"))]
+#[derive(Clone)]
+pub enum Transformer {}
+
+#[cfg(not(feature = "transformers"))]
+impl Transformer {
+    pub fn request(&self, _body: &mut Value) -> HashMap<String, String> {
+        match *self {}
+    }
+
+    pub fn response(&self, _body: &mut Value, _stream: bool) {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "transformers"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn settings(path: &std::path::Path) -> ProxySettings {
+        ProxySettings {
+            transformers: [(ANY_PROVIDER.to_string(), path.display().to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scripts_edit_requests_and_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.rhai");
+        std::fs::write(
+            &path,
+            r#"
+            fn on_request() {
+                this.body.temperature = 0.2;
+                this.headers["X-Title"] = this.provider;
+            }
+            fn on_response() {
+                if !this.stream { this.body.model = this.model; }
+            }
+            "#,
+        )
+        .unwrap();
+        let transformers = Transformers::new();
+        let t = transformers.for_provider(&settings(&path), "openrouter", "openrouter/gpt-4o").unwrap();
+
+        let mut body = json!({"model": "openrouter/gpt-4o", "temperature": 1.0});
+        let headers = t.request(&mut body);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(headers, HashMap::from([("X-Title".to_string(), "openrouter".to_string())]));
+
+        let mut response = json!({"model": "gpt-4o-2024-08-06"});
+        t.response(&mut response, false);
+        assert_eq!(response["model"], "openrouter/gpt-4o");
+        let mut chunk = json!({"model": "gpt-4o-2024-08-06"});
+        t.response(&mut chunk, true);
+        assert_eq!(chunk["model"], "gpt-4o-2024-08-06");
+    }
+
+    #[test]
+    fn changed_scripts_are_recompiled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.rhai");
+        let write = |temperature: f64, age: u64| {
+            std::fs::write(&path, format!("fn on_request() {{ this.body.temperature = {:?}; }}", temperature)).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+        };
+        let transformers = Transformers::new();
+        let temperature = || {
+            let mut body = json!({});
+            transformers.for_provider(&settings(&path), "openai", "openai/gpt-4o").unwrap().request(&mut body);
+            body["temperature"].clone()
+        };
+
+        write(0.1, 60);
+        assert_eq!(temperature(), 0.1);
+        write(0.9, 0);
+        assert_eq!(temperature(), 0.9);
+    }
+
+    #[test]
+    fn failing_scripts_pass_the_payload_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.rhai");
+        std::fs::write(
+            &path,
+            r#"
+            fn on_request() {
+                this.body.temperature = 0.2;
+                throw "boom";
+            }
+            fn on_response() { loop {} }
+            "#,
+        )
+        .unwrap();
+        let t = Transformers::new().for_provider(&settings(&path), "openai", "openai/gpt-4o").unwrap();
+
+        let mut body = json!({"temperature": 1.0});
+        assert!(t.request(&mut body).is_empty());
+        assert_eq!(body, json!({"temperature": 1.0}));
+        let mut response = json!({"id": "r1"});
+        t.response(&mut response, false);
+        assert_eq!(response, json!({"id": "r1"}));
+
+        // A script that doesn't compile leaves the provider without a transformer.
+        std::fs::write(&path, "fn on_request( {").unwrap();
+        assert!(Transformers::new().for_provider(&settings(&path), "openai", "openai/gpt-4o").is_none());
+    }
+}
@@ -0,0 +1,71 @@
+//! Event webhooks: proxy activity pushed to other systems as it happens, so they don't have
+//! to poll `/v1/usage`.
+//!
+//! Each endpoint in `settings.event_webhooks` gets a POST per event it subscribes to:
+//!
+//! ```json
+//! {"id": "…", "type": "request.completed", "created_ms": 1767225600000, "data": {…}}
+//! ```
+//!
+//! Deliveries run in the background and are not retried; a failing endpoint is logged.
+//! Shutdown waits for deliveries still running (see `run_server`).
+
+use serde_json::{Value, json};
+use tokio_util::task::TaskTracker;
+use zeroai::ConfigManager;
+
+/// A model request answered with a success status; `data` has the request ID, route, model,
+/// account, app, user, status, duration and tokens.
+pub const REQUEST_COMPLETED: &str = "request.completed";
+/// A model request answered with an error status; same `data` as [`REQUEST_COMPLETED`].
+pub const REQUEST_FAILED: &str = "request.failed";
+/// A request rejected because the daily budget is used up.
+pub const BUDGET_EXCEEDED: &str = "budget.exceeded";
+/// An account rested after a rate limit.
+pub const ACCOUNT_RATELIMITED: &str = "account.ratelimited";
+
+pub struct Webhooks {
+    config: ConfigManager,
+    http: reqwest::Client,
+    /// Where deliveries run.
+    tasks: TaskTracker,
+}
+
+impl Webhooks {
+    pub fn new(config: ConfigManager, tasks: TaskTracker) -> Self {
+        Self { config, http: reqwest::Client::new(), tasks }
+    }
+
+    /// POST `event` to every endpoint subscribed to it.
+    pub fn emit(&self, event: &str, data: Value) {
+        let Ok(settings) = self.config.get_settings() else {
+            return;
+        };
+        let mut hooks = settings.event_webhooks.into_iter().filter(|h| h.wants(event)).peekable();
+        if hooks.peek().is_none() {
+            return;
+        }
+        let body = json!({
+            "id": uuid::Uuid::new_v4().to_string(),
+            "type": event,
+            "created_ms": chrono::Utc::now().timestamp_millis(),
+            "data": data,
+        });
+        for hook in hooks {
+            let mut request = self.http.post(&hook.url).json(&body);
+            for (name, value) in &hook.headers {
+                request = request.header(name, value);
+            }
+            let event = event.to_string();
+            self.tasks.spawn(async move {
+                match request.send().await {
+                    Ok(resp) if !resp.status().is_success() => {
+                        tracing::warn!("Webhook {} for {} answered {}", hook.url, event, resp.status());
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Webhook {} for {} failed: {}", hook.url, event, e),
+                }
+            });
+        }
+    }
+}
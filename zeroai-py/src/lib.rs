@@ -0,0 +1,225 @@
+//! Python bindings: the `zeroai` module, with `AiClient` and `ConfigManager` over the same
+//! config file, credential store and provider implementations as the proxy.
+//!
+//! Messages, tools and replies cross the boundary as dicts in the JSON shapes of the Rust types
+//! (`{"role": "user", "content": [{"type": "text", "text": "hi"}]}`). A message's `content` may
+//! also be a plain string. Calls release the GIL while they wait on the provider.
+
+use futures::StreamExt;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use tokio::runtime::Runtime;
+use zeroai::auth::config::ConfigManager;
+use zeroai::auth::{ApiKeyCredential, Credential};
+use zeroai::bindings::{ChatRequest, event_json};
+use zeroai::providers::EventStream;
+use zeroai::AiClient;
+
+pyo3::create_exception!(zeroai, ProviderError, PyRuntimeError, "A provider call failed.");
+
+/// Runs every call; calls from several Python threads proceed in parallel.
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| Runtime::new().expect("Failed to start the tokio runtime"));
+
+/// The config file and credential store (`~/.zeroai/config.json` unless given a path).
+#[pyclass(name = "ConfigManager", frozen)]
+#[derive(Clone)]
+struct PyConfigManager {
+    inner: ConfigManager,
+}
+
+#[pymethods]
+impl PyConfigManager {
+    #[new]
+    #[pyo3(signature = (path=None))]
+    fn new(path: Option<PathBuf>) -> Self {
+        let inner = path.map(ConfigManager::new).unwrap_or_else(ConfigManager::default_path);
+        Self { inner }
+    }
+
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.inner.path().to_path_buf()
+    }
+
+    fn enabled_models(&self) -> PyResult<Vec<String>> {
+        self.inner.get_enabled_models().map_err(runtime_error)
+    }
+
+    fn set_enabled_models(&self, models: Vec<String>) -> PyResult<()> {
+        self.inner.set_enabled_models(models).map_err(runtime_error)
+    }
+
+    /// Providers that have a credential.
+    fn providers(&self) -> PyResult<Vec<String>> {
+        self.inner.list_providers_with_credentials().map_err(runtime_error)
+    }
+
+    fn set_api_key(&self, provider: &str, key: String) -> PyResult<()> {
+        self.inner
+            .set_credential(provider, Credential::ApiKey(ApiKeyCredential { key }))
+            .map_err(runtime_error)
+    }
+
+    fn remove_credential(&self, provider: &str) -> PyResult<()> {
+        self.inner.remove_credential(provider).map_err(runtime_error)
+    }
+
+    /// The API key (or fresh OAuth access token) the next request to `provider` would use.
+    fn resolve_api_key(&self, py: Python<'_>, provider: &str) -> PyResult<Option<String>> {
+        py.allow_threads(|| RUNTIME.block_on(self.inner.resolve_api_key(provider))).map_err(runtime_error)
+    }
+}
+
+/// A client for the enabled models of a config, authenticated with its credentials.
+#[pyclass(name = "AiClient", frozen)]
+struct PyAiClient {
+    client: AiClient,
+    config: ConfigManager,
+}
+
+#[pymethods]
+impl PyAiClient {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<PyConfigManager>) -> PyResult<Self> {
+        let config = config.map_or_else(ConfigManager::default_path, |c| c.inner);
+        let client = AiClient::from_config(&config).map_err(runtime_error)?;
+        Ok(Self { client, config })
+    }
+
+    /// Full IDs (`provider/model`) of the models this client can call.
+    fn models(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.client.models().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Run a completion and return the assistant message as a dict.
+    #[pyo3(signature = (model, messages, *, system=None, tools=None, temperature=None, max_tokens=None, reasoning=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn chat(
+        &self,
+        py: Python<'_>,
+        model: &str,
+        messages: &Bound<'_, PyAny>,
+        system: Option<String>,
+        tools: Option<&Bound<'_, PyAny>>,
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
+        reasoning: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let request = request(model, messages, system, tools, temperature, max_tokens, reasoning)?;
+        let context = request.context().map_err(value_error)?;
+        let message = py.allow_threads(|| {
+            RUNTIME.block_on(async {
+                let options = request.options(&self.config).await.map_err(runtime_error)?;
+                self.client.chat(model, &context, &options).await.map_err(provider_error)
+            })
+        })?;
+        to_py(py, &message)
+    }
+
+    /// Stream a completion: an iterator of event dicts, ending with `{"type": "done", ...}`.
+    #[pyo3(signature = (model, messages, *, system=None, tools=None, temperature=None, max_tokens=None, reasoning=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn stream(
+        &self,
+        py: Python<'_>,
+        model: &str,
+        messages: &Bound<'_, PyAny>,
+        system: Option<String>,
+        tools: Option<&Bound<'_, PyAny>>,
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
+        reasoning: Option<&str>,
+    ) -> PyResult<PyEventStream> {
+        let request = request(model, messages, system, tools, temperature, max_tokens, reasoning)?;
+        let context = request.context().map_err(value_error)?;
+        let options = py.allow_threads(|| RUNTIME.block_on(request.options(&self.config))).map_err(runtime_error)?;
+        let stream = self.client.stream(model, &context, &options).map_err(provider_error)?;
+        Ok(PyEventStream { stream: Mutex::new(stream) })
+    }
+}
+
+/// Events of a streamed completion.
+#[pyclass(name = "EventStream")]
+struct PyEventStream {
+    stream: Mutex<EventStream>,
+}
+
+#[pymethods]
+impl PyEventStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let next = py.allow_threads(|| {
+            let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+            RUNTIME.block_on(stream.next())
+        });
+        match next {
+            None => Ok(None),
+            Some(Ok(event)) => to_py(py, &event_json(&event)).map(Some),
+            Some(Err(e)) => Err(provider_error(e)),
+        }
+    }
+}
+
+/// The keyword arguments of `chat` and `stream` as a [`ChatRequest`].
+fn request(
+    model: &str,
+    messages: &Bound<'_, PyAny>,
+    system: Option<String>,
+    tools: Option<&Bound<'_, PyAny>>,
+    temperature: Option<f64>,
+    max_tokens: Option<u64>,
+    reasoning: Option<&str>,
+) -> PyResult<ChatRequest> {
+    let request = json!({
+        "model": model,
+        "messages": to_value(messages)?,
+        "system": system,
+        "tools": tools.map(to_value).transpose()?,
+        "temperature": temperature,
+        "max_tokens": max_tokens,
+        "reasoning": reasoning,
+    });
+    serde_json::from_value(request).map_err(value_error)
+}
+
+fn to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let text: String = obj.py().import("json")?.call_method1("dumps", (obj,))?.extract()?;
+    serde_json::from_str(&text).map_err(value_error)
+}
+
+fn to_py(py: Python<'_>, value: &impl Serialize) -> PyResult<PyObject> {
+    let text = serde_json::to_string(value).map_err(value_error)?;
+    Ok(py.import("json")?.call_method1("loads", (text,))?.unbind())
+}
+
+fn provider_error(e: zeroai::ProviderError) -> PyErr {
+    ProviderError::new_err(e.to_string())
+}
+
+fn runtime_error(e: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", e))
+}
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+#[pymodule]
+#[pyo3(name = "zeroai")]
+fn zeroai_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyConfigManager>()?;
+    m.add_class::<PyAiClient>()?;
+    m.add_class::<PyEventStream>()?;
+    m.add("ProviderError", m.py().get_type::<ProviderError>())?;
+    Ok(())
+}
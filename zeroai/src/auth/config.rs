@@ -1,10 +1,20 @@
 use super::Credential;
+use super::schedule::Schedule;
+use crate::compress::CompressionConfig;
+use crate::concurrency::ConcurrencyLimit;
+use crate::oauth::AccountIdentity;
+use crate::providers::ProviderError;
+use crate::providers::registry::ProviderDef;
+use crate::routing::Experiment;
+use crate::types::{ModelCost, ModelDef, ModelOverride, RetryConfig};
+#[cfg(not(target_arch = "wasm32"))]
 use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
 
 /// A single named credential slot for a provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,19 +31,124 @@ pub struct Account {
     /// Bookkeeping only.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_rate_limited_ms: Option<i64>,
+
+    /// Consecutive OAuth refresh failures. Reset on the next successful refresh.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub refresh_failures: u32,
+
+    /// Last OAuth refresh error, kept so the TUI/doctor can tell the user to re-login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_refresh_error: Option<String>,
+
+    /// Who the OAuth login belongs to, looked up from the provider after login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+
+    /// The login's organization or workspace, when the provider reports one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+
+    /// Whether the account is in rotation. Disabled accounts are never selected, but keep
+    /// their credential (e.g. a key saved for nights and weekends).
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+
+    /// Why `auth verify --disable` turned the account off; `None` when it was turned off by
+    /// hand. Only such accounts come back on by themselves (a new credential or a passing
+    /// `auth verify`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_reason: Option<String>,
+
+    /// When the account may be used (see [`super::schedule`]), e.g. "Mon-Fri 18:00-09:00,
+    /// Sat-Sun". Outside it the account is skipped like a disabled one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(b: &bool) -> bool {
+    *b
 }
 
 impl Account {
     pub fn is_healthy_at(&self, now_ms: i64) -> bool {
-        self.unhealthy_until_ms.unwrap_or(0) <= now_ms
+        self.is_available_at(now_ms) && self.unhealthy_until_ms.unwrap_or(0) <= now_ms
+    }
+
+    /// Enabled and inside its schedule, if it has one. A schedule that doesn't parse (e.g.
+    /// after a hand edit) keeps the account out of use until it is fixed.
+    pub fn is_available_at(&self, now_ms: i64) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Some(source) = self.schedule.as_deref() else {
+            return true;
+        };
+        match source.parse::<Schedule>() {
+            Ok(schedule) => schedule.is_open_at(now_ms),
+            Err(e) => {
+                tracing::warn!("Account {} has an invalid schedule '{}' ({}); not using it", self.id, source, e);
+                false
+            }
+        }
+    }
+
+    /// True if the last OAuth refresh attempt failed and the user likely needs to log in again.
+    pub fn needs_relogin(&self) -> bool {
+        self.refresh_failures > 0
     }
 
     pub fn display_label(&self) -> String {
         let id_prefix = self.id.chars().take(4).collect::<String>();
-        self.label.clone().unwrap_or_else(|| format!("account-{}", id_prefix))
+        self.label
+            .clone()
+            .or_else(|| self.email.clone())
+            .unwrap_or_else(|| format!("account-{}", id_prefix))
     }
 }
 
+/// Label prefix of generated account labels ("openai" in "openai-2").
+fn label_prefix(provider_id: &str) -> &str {
+    provider_id
+        .strip_prefix("custom:")
+        .unwrap_or(provider_id)
+        .split('/')
+        .next()
+        .unwrap_or(provider_id)
+}
+
+/// Whether `label` is one zeroai made up ("default", "openai-2") rather than one the user chose.
+fn is_generated_label(provider_id: &str, label: &str) -> bool {
+    label == "default"
+        || label
+            .strip_prefix(label_prefix(provider_id))
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// A label for a new account that no existing account uses: `wanted` itself, or with "-2",
+/// "-3"... appended; without `wanted`, the next free "<prefix>-N" after the account count
+/// (e.g. "openai-3", "gemini-cli-2").
+fn unique_label(accounts: &[Account], provider_id: &str, wanted: Option<String>) -> String {
+    let taken = |l: &str| accounts.iter().any(|a| a.label.as_deref() == Some(l));
+    let (base, first) = match wanted {
+        Some(w) if !taken(&w) => return w,
+        Some(w) => (w, 2),
+        None => (label_prefix(provider_id).to_string(), accounts.len() + 1),
+    };
+    (first..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|l| !taken(l))
+        .expect("unbounded range")
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderAccounts {
     #[serde(default)]
@@ -46,6 +161,217 @@ pub struct AccountSelection {
     pub api_key: String,
 }
 
+/// Proxy options (edited in the config TUI's Settings screen).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProxySettings {
+    /// Default bind host for `serve`; `--host` overrides. Default 127.0.0.1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+
+    /// Default port for `serve`; `--port` overrides. Default 8787.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// Retry policy for upstream calls (on top of rotating accounts on 429).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+
+    /// Log filter used when `RUST_LOG` is unset (e.g. `debug`, `ai_proxy=trace`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+
+    /// Spend cap in USD over the rolling 24 hours, across all accounts. Requests are
+    /// rejected once it is reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_budget_usd: Option<f64>,
+
+    /// Model aliases: name clients may send -> `<provider>/<model>`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub model_aliases: HashMap<String, String>,
+
+    /// Also list and accept bare model names (`gpt-4o` for `openai/gpt-4o`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expose_bare_model_ids: bool,
+
+    /// Providers in order of preference when several offer the same bare model name;
+    /// unlisted providers follow in alphabetical order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bare_model_priority: Vec<String>,
+
+    /// Summarize long tool outputs and old turns with a cheap model before forwarding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionConfig>,
+    /// Rhai scripts that rewrite requests and responses: provider ID (or `*` for all
+    /// others) -> script path.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub transformers: HashMap<String, String>,
+    /// How long responses to requests with an `Idempotency-Key` header are kept for replay
+    /// (default 24 hours).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_ttl_secs: Option<u64>,
+    /// Mirror a share of chat requests to a second model to compare it with the one in use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<ShadowConfig>,
+    /// A/B experiments; a request for an experiment's model is served by one of its variants.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub experiments: Vec<Experiment>,
+    /// Reject (or trim) chat requests whose prompt takes too much of the model's context window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_guard: Option<ContextGuard>,
+    /// Alerts on failed logins, providers with every account rate limited, and budget use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationConfig>,
+    /// Endpoints that get request and account events as they happen.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_webhooks: Vec<EventWebhook>,
+    /// Days the request journal (for `usage lookup`) keeps each request (default 30, 0 = off).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal_days: Option<u32>,
+    /// Caps on concurrent upstream requests: provider ID (or `*` for all others) -> limits.
+    /// Takes effect on restart.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub concurrency: HashMap<String, ConcurrencyLimit>,
+    /// Providers whose connections are opened at startup and kept alive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_connections: Option<WarmConnections>,
+    /// `keep_alive` sent with requests to Ollama models (e.g. `"30m"`, or `-1` for
+    /// forever) so they stay loaded between requests; a client's own value wins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ollama_keep_alive: Option<serde_json::Value>,
+}
+
+/// An endpoint each proxy event (`request.completed`, `account.ratelimited`...) is POSTed to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventWebhook {
+    pub url: String,
+    /// Event types sent; empty: all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    /// Extra headers, e.g. a token the receiver checks.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+impl EventWebhook {
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event)
+    }
+}
+
+/// Where the proxy sends alerts that need a person: an OAuth login that stopped refreshing,
+/// a provider whose accounts are all rate limited, spend crossing a share of the daily budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Show a desktop notification (`notify-send` on Linux, `osascript` on macOS).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub desktop: bool,
+    /// URLs each alert is POSTed to as JSON. Slack and Discord incoming webhook URLs get a
+    /// message in their own format.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<String>,
+    /// Shares of `daily_budget_usd`, in percent, that raise an alert when the rolling 24h
+    /// spend crosses them.
+    #[serde(default = "default_budget_alerts")]
+    pub budget_alert_percent: Vec<f64>,
+}
+
+fn default_budget_alerts() -> Vec<f64> {
+    vec![80.0, 100.0]
+}
+
+/// Connections the proxy opens to providers' API hosts at startup and pings to keep open, so
+/// the first request after a quiet spell doesn't wait on TCP and TLS handshakes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WarmConnections {
+    /// Provider IDs; every API host of their enabled models is kept warm.
+    pub providers: Vec<String>,
+    /// Seconds between keep-alive pings (default 30, under the HTTP client's 90s idle timeout).
+    #[serde(default = "default_warm_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_warm_interval() -> u64 {
+    30
+}
+
+/// How much of the target model's context window a chat prompt may fill, by local estimate.
+/// Providers handle overlong prompts differently (an error, or silently cutting the start),
+/// so the proxy checks before forwarding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextGuard {
+    /// Largest share of the context window, in percent, the estimated prompt may take.
+    pub max_percent: f64,
+    /// Drop the oldest turns of longer prompts instead of rejecting them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncate: bool,
+}
+
+impl ContextGuard {
+    /// Prompt token limit for a model with this context window; `None` when the window is
+    /// unknown.
+    pub fn limit_tokens(&self, context_window: u64) -> Option<u64> {
+        (context_window > 0).then(|| (context_window as f64 * self.max_percent / 100.0) as u64)
+    }
+}
+
+/// Shadow traffic: copies of sampled chat requests go to `model` in the background; its
+/// answers are compared with the real ones and discarded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShadowConfig {
+    /// Model the copies go to, as `<provider>/<model>`.
+    pub model: String,
+    /// Share of requests mirrored, in percent (0 to 100).
+    pub percent: f64,
+    /// Only mirror requests answered by these models (`<provider>/<model>`); empty: all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+}
+
+impl ShadowConfig {
+    /// Whether requests answered by `model` are mirrored (before sampling).
+    pub fn mirrors(&self, model: &str) -> bool {
+        model != self.model && (self.models.is_empty() || self.models.iter().any(|m| m == model))
+    }
+}
+
+impl ProxySettings {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The `<provider>/<model>` ID a requested model name refers to.
+    pub fn resolve_model<'a>(&'a self, model: &'a str) -> &'a str {
+        self.model_aliases.get(model).map(String::as_str).unwrap_or(model)
+    }
+
+    /// Bare model name -> `<provider>/<model>` for the given full IDs (empty unless
+    /// `expose_bare_model_ids` is set). Names offered by several providers go to the
+    /// provider ranked first by `bare_model_priority`.
+    pub fn bare_model_ids<'a>(&self, full_ids: impl IntoIterator<Item = &'a str>) -> HashMap<String, String> {
+        let mut out: HashMap<String, String> = HashMap::new();
+        if !self.expose_bare_model_ids {
+            return out;
+        }
+        let rank = |provider: &str| {
+            let pos = self.bare_model_priority.iter().position(|p| p == provider);
+            (pos.unwrap_or(usize::MAX), provider.to_string())
+        };
+        for full_id in full_ids {
+            let Some((provider, bare)) = crate::split_model_id(full_id) else {
+                continue;
+            };
+            let better = match out.get(bare).and_then(|cur| crate::split_model_id(cur)) {
+                Some((current, _)) => rank(provider) < rank(current),
+                None => true,
+            };
+            if better {
+                out.insert(bare.to_string(), full_id.to_string());
+            }
+        }
+        out
+    }
+}
+
 /// The main configuration file structure.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -67,6 +393,138 @@ pub struct AppConfig {
     /// Custom OpenAI-compatible provider models URL (provider_id -> URL). Blank = use {base_url}/v1/models.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub provider_models_url: HashMap<String, String>,
+
+    /// Declarative OpenAI-compatible providers (see `ProviderDef`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<ProviderDef>,
+
+    /// Price overrides: `<provider>/<model>` -> cost in USD per million tokens.
+    /// Omitted fields default to 0.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, ModelCost>,
+
+    /// Model metadata overrides: `<provider>/<model>` -> context window, max tokens,
+    /// reasoning flag, headers and base URL.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub model_overrides: HashMap<String, ModelOverride>,
+
+    /// Proxy options: listen address, retries, logging, budget and model aliases.
+    #[serde(default, skip_serializing_if = "ProxySettings::is_default")]
+    pub settings: ProxySettings,
+
+    /// Providers taken out of service (maintenance, outage): provider_id -> why and until when.
+    /// Their accounts and enabled models are kept, but routing skips them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub provider_maintenance: HashMap<String, ProviderMaintenance>,
+}
+
+/// A provider taken out of service by hand.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderMaintenance {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the provider comes back by itself (unix ms); `None` until turned back on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<i64>,
+}
+
+impl ProviderMaintenance {
+    pub fn is_active_at(&self, now_ms: i64) -> bool {
+        self.until_ms.is_none_or(|until| now_ms < until)
+    }
+
+    /// "outage, until 2026-03-02 18:00 UTC", for error messages and listings.
+    pub fn describe(&self) -> String {
+        let until = self
+            .until_ms
+            .and_then(chrono::DateTime::from_timestamp_millis)
+            .map(|t| format!("until {}", t.format("%Y-%m-%d %H:%M UTC")));
+        let parts: Vec<String> = self.reason.clone().into_iter().chain(until).collect();
+        if parts.is_empty() { "no reason given".into() } else { parts.join(", ") }
+    }
+}
+
+/// Attempts per OAuth refresh before the account is marked as failing.
+const REFRESH_MAX_ATTEMPTS: u32 = 3;
+
+/// Base delay between refresh attempts; doubled per attempt, plus jitter.
+const REFRESH_BASE_BACKOFF_MS: u64 = 500;
+
+/// How long an account is skipped for selection after its refresh fails.
+const REFRESH_FAILURE_COOLDOWN_MS: i64 = 5 * 60 * 1000;
+
+/// Refresh OAuth credentials, retrying transient failures with exponential backoff and jitter.
+async fn refresh_with_retry(
+    provider: &dyn crate::oauth::OAuthProvider,
+    creds: &crate::oauth::OAuthCredentials,
+) -> anyhow::Result<crate::oauth::OAuthCredentials> {
+    let mut backoff_ms = REFRESH_BASE_BACKOFF_MS;
+    let mut attempt = 1;
+    loop {
+        match provider.refresh_token(creds).await {
+            Ok(new_creds) => return Ok(new_creds),
+            Err(e) if attempt >= REFRESH_MAX_ATTEMPTS => return Err(e),
+            Err(e) => {
+                tracing::debug!(
+                    "OAuth refresh attempt {}/{} for {} failed: {}",
+                    attempt,
+                    REFRESH_MAX_ATTEMPTS,
+                    provider.id(),
+                    e
+                );
+                let jitter = rand::random_range(0..=backoff_ms / 2);
+                crate::rt::sleep(std::time::Duration::from_millis(backoff_ms + jitter)).await;
+                backoff_ms = backoff_ms.saturating_mul(2);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Per-account refresh locks, keyed by config path, provider and account id.
+static REFRESH_LOCKS: LazyLock<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn refresh_lock(path: &Path, provider_id: &str, account_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+    let key = format!("{}\n{}\n{}", path.display(), provider_id, account_id);
+    let mut locks = REFRESH_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(key).or_default().clone()
+}
+
+/// Environment variable that moves the config root away from `~/.zeroai`.
+pub const CONFIG_DIR_ENV: &str = "ZEROAI_CONFIG_DIR";
+
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Make `dir` the config root of this process, ahead of [`CONFIG_DIR_ENV`]. Only the first
+/// call counts, and it must come before anything opens the default config or store.
+pub fn set_config_dir(dir: impl Into<PathBuf>) {
+    let _ = CONFIG_DIR.set(dir.into());
+}
+
+/// The config root: the directory holding `config.json`, the store and the model caches.
+/// Set by [`set_config_dir`], else [`CONFIG_DIR_ENV`], else `~/.zeroai`. Separate roots keep
+/// separate credentials, settings and usage, so e.g. staging and production proxies can share
+/// a machine.
+pub fn config_dir() -> PathBuf {
+    pick_config_dir(CONFIG_DIR.get().cloned(), std::env::var_os(CONFIG_DIR_ENV))
+}
+
+fn pick_config_dir(set: Option<PathBuf>, env: Option<std::ffi::OsString>) -> PathBuf {
+    set.or_else(|| env.filter(|v| !v.is_empty()).map(PathBuf::from)).unwrap_or_else(|| {
+        dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".zeroai")
+    })
+}
+
+type RefreshFailureHook = Box<dyn Fn(&str, &Account) + Send + Sync>;
+
+static REFRESH_FAILURE_HOOK: OnceLock<RefreshFailureHook> = OnceLock::new();
+
+/// Call `hook` with the provider ID and the account when an account's OAuth refresh fails
+/// after all retries, once per account until a refresh or a new login succeeds. Only the
+/// first call counts.
+pub fn on_refresh_failure(hook: impl Fn(&str, &Account) + Send + Sync + 'static) {
+    let _ = REFRESH_FAILURE_HOOK.set(Box::new(hook));
 }
 
 /// Manages reading/writing the config file with safe atomic writes + file lock.
@@ -81,10 +539,14 @@ impl ConfigManager {
         Self { path: path.into() }
     }
 
-    /// Create a config manager with the default path (~/.zeroai/config.json).
+    /// Create a config manager with the default path (`config.json` in [`config_dir`]).
     pub fn default_path() -> Self {
-        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        Self::new(home.join(".zeroai").join("config.json"))
+        Self::in_dir(config_dir())
+    }
+
+    /// Create a config manager for the config root `dir` (`<dir>/config.json`).
+    pub fn in_dir(dir: impl AsRef<Path>) -> Self {
+        Self::new(dir.as_ref().join("config.json"))
     }
 
     /// Get the config file path.
@@ -92,6 +554,12 @@ impl ConfigManager {
         &self.path
     }
 
+    /// The config root this manager's file lives in.
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     fn lock_path(&self) -> PathBuf {
         // A sibling lock file (avoids locking the config file itself during atomic replace).
         self.path.with_extension("json.lock")
@@ -107,15 +575,19 @@ impl ConfigManager {
             }
         }
 
-        let lock_path = self.lock_path();
-        let lock_file = fs::OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .open(&lock_path)?;
-
-        lock_file.lock_exclusive()?;
+        // No advisory locks on wasm32, which has no filesystem to share.
+        #[cfg(not(target_arch = "wasm32"))]
+        let lock_file = {
+            let lock_file = fs::OpenOptions::new()
+                .create(true)
+                .read(true)
+                .write(true)
+                .open(self.lock_path())?;
+            lock_file.lock_exclusive()?;
+            lock_file
+        };
         let out = f();
+        #[cfg(not(target_arch = "wasm32"))]
         let _ = lock_file.unlock();
         out
     }
@@ -138,6 +610,13 @@ impl ConfigManager {
                     credential: cred,
                     unhealthy_until_ms: None,
                     last_rate_limited_ms: None,
+                    refresh_failures: 0,
+                    last_refresh_error: None,
+                    email: None,
+                    org: None,
+                    enabled: true,
+                    disabled_reason: None,
+                    schedule: None,
                 });
             }
         }
@@ -235,22 +714,11 @@ impl ConfigManager {
             let id = uuid::Uuid::new_v4().to_string();
             {
                 let accs = Self::ensure_accounts(&mut cfg, provider_id);
-                // Generate provider-specific label based on existing accounts for this provider
-                let next_index = accs.accounts.len() + 1;
                 let label = label.and_then(|s| {
                     let t = s.trim().to_string();
                     if t.is_empty() { None } else { Some(t) }
                 });
-                // Auto-generate label using provider prefix for clarity (e.g., "openai-1", "gemini-cli-2")
-                let label = label.or_else(|| {
-                    let provider_prefix = provider_id
-                        .strip_prefix("custom:")
-                        .unwrap_or(provider_id)
-                        .split('/')
-                        .next()
-                        .unwrap_or(provider_id);
-                    Some(format!("{}-{}", provider_prefix, next_index))
-                });
+                let label = Some(unique_label(&accs.accounts, provider_id, label));
 
                 accs.accounts.push(Account {
                     id: id.clone(),
@@ -258,6 +726,13 @@ impl ConfigManager {
                     credential,
                     unhealthy_until_ms: None,
                     last_rate_limited_ms: None,
+                    refresh_failures: 0,
+                    last_refresh_error: None,
+                    email: None,
+                    org: None,
+                    enabled: true,
+                    disabled_reason: None,
+                    schedule: None,
                 });
             }
 
@@ -381,6 +856,77 @@ impl ConfigManager {
         })
     }
 
+    /// Save who an account's login belongs to. A generated label ("openai-2") is replaced by
+    /// the email; one the user chose is kept.
+    pub fn set_account_identity(
+        &self,
+        provider_id: &str,
+        account_id: &str,
+        identity: &AccountIdentity,
+    ) -> anyhow::Result<()> {
+        self.with_exclusive_lock(|| {
+            let mut cfg = self.load_unlocked()?;
+            {
+                let accs = Self::ensure_accounts(&mut cfg, provider_id);
+                let Some(acc) = accs.accounts.iter_mut().find(|a| a.id == account_id) else {
+                    anyhow::bail!("account not found: {}", account_id);
+                };
+                acc.email = identity.email.clone();
+                acc.org = identity.org.clone();
+                if acc.email.is_some() && acc.label.as_deref().is_none_or(|l| is_generated_label(provider_id, l)) {
+                    acc.label = acc.email.clone();
+                }
+            }
+            self.save_unlocked(&cfg)
+        })
+    }
+
+    /// Put an account in or out of rotation. `reason` records why a failing account was turned
+    /// off (see [`Account::disabled_reason`]).
+    pub fn set_account_enabled(
+        &self,
+        provider_id: &str,
+        account_id: &str,
+        enabled: bool,
+        reason: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.with_exclusive_lock(|| {
+            let mut cfg = self.load_unlocked()?;
+            {
+                let accs = Self::ensure_accounts(&mut cfg, provider_id);
+                let Some(acc) = accs.accounts.iter_mut().find(|a| a.id == account_id) else {
+                    anyhow::bail!("account not found: {}", account_id);
+                };
+                acc.enabled = enabled;
+                acc.disabled_reason = if enabled { None } else { reason };
+            }
+            self.save_unlocked(&cfg)
+        })
+    }
+
+    /// Set (`Some`) or clear (`None`) the account's availability schedule.
+    pub fn set_account_schedule(
+        &self,
+        provider_id: &str,
+        account_id: &str,
+        schedule: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let schedule = schedule
+            .map(|s| s.parse::<Schedule>().map_err(|e| anyhow::anyhow!("Invalid schedule '{}': {}", s, e)))
+            .transpose()?;
+        self.with_exclusive_lock(|| {
+            let mut cfg = self.load_unlocked()?;
+            {
+                let accs = Self::ensure_accounts(&mut cfg, provider_id);
+                let Some(acc) = accs.accounts.iter_mut().find(|a| a.id == account_id) else {
+                    anyhow::bail!("account not found: {}", account_id);
+                };
+                acc.schedule = schedule.as_ref().map(Schedule::to_string);
+            }
+            self.save_unlocked(&cfg)
+        })
+    }
+
     /// Mark the account as temporarily unhealthy and move it to the end.
     pub fn rate_limit_account(
         &self,
@@ -410,7 +956,10 @@ impl ConfigManager {
     }
 
     /// Resolve API key for provider, preferring the first *healthy* account.
-    /// If all accounts are unhealthy, falls back to the first account.
+    /// If all accounts are unhealthy, falls back to the one that recovers first.
+    /// An account whose expired OAuth token can't be refreshed is skipped for the next one;
+    /// if none is left, this fails with [`ProviderError::AuthRequired`] rather than handing
+    /// out a token the provider will reject.
     pub async fn resolve_account(&self, provider_id: &str) -> anyhow::Result<Option<AccountSelection>> {
         // We keep this async because legacy code refreshes OAuth tokens.
         // For multi-account, we select an account first, then refresh that account if needed.
@@ -426,83 +975,168 @@ impl ConfigManager {
             .map(|p| p.accounts.clone())
             .unwrap_or_default();
         if accs.is_empty() {
-            if let Some(key) = super::sniff::env_api_key(provider_id) {
+            if let Some(key) = super::sniff::env_api_key(provider_id).or_else(|| {
+                cfg.providers
+                    .iter()
+                    .find(|d| d.id == provider_id)
+                    .and_then(ProviderDef::env_api_key)
+            }) {
                 return Ok(Some(AccountSelection { account_id: "env".into(), api_key: key }));
             }
-            if let Some(cred) = super::sniff::sniff_external_credential(provider_id) {
-                // Persist as a new account.
-                let _id = self.add_account(provider_id, Some("sniffed".into()), cred.clone())?;
-                if let Some(k) = cred.api_key() {
-                    return Ok(Some(AccountSelection { account_id: _id, api_key: k }));
-                }
+            // Used as found but never saved: importing is the user's call (`auth import` or
+            // the config TUI).
+            if let Some(k) = super::sniff::sniff_external_credential(provider_id).and_then(|c| c.api_key()) {
+                return Ok(Some(AccountSelection { account_id: "sniffed".into(), api_key: k }));
             }
             return Ok(None);
         }
 
         let now = Self::now_ms();
-        let pick = accs
-            .iter()
-            .enumerate()
-            .find(|(_, a)| a.is_healthy_at(now))
-            .map(|(i, _)| i)
-            .unwrap_or(0);
-
-        let mut chosen = accs[pick].clone();
-
-        // Refresh OAuth if needed. (We re-use the old single-credential refresh logic.)
-        if chosen.credential.is_expired() {
-            if let Credential::OAuth(ref mut oauth) = chosen.credential {
-                let oauth_provider: Box<dyn crate::oauth::OAuthProvider> = match provider_id {
-                    "gemini-cli" => Box::new(crate::oauth::google_gemini_cli::GeminiCliOAuthProvider),
-                    "antigravity" => Box::new(crate::oauth::google_antigravity::AntigravityOAuthProvider),
-                    "openai-codex" => Box::new(crate::oauth::openai_codex::OpenAiCodexOAuthProvider),
-                    "github-copilot" => Box::new(crate::oauth::github_copilot::GitHubCopilotOAuthProvider),
-                    "qwen-portal" => Box::new(crate::oauth::qwen_portal::QwenPortalOAuthProvider),
-                    _ => {
-                        // Unknown provider, can't refresh
-                        if let Some(k) = chosen.credential.api_key() {
-                            return Ok(Some(AccountSelection { account_id: chosen.id, api_key: k }));
-                        }
-                        return Ok(None);
-                    }
-                };
+        // Accounts whose token refresh failed during this call.
+        let mut refresh_failed: Vec<String> = Vec::new();
+        loop {
+            let candidates = || accs.iter().filter(|a| !refresh_failed.contains(&a.id));
+            let Some(pick) = candidates().find(|a| a.is_healthy_at(now)).or_else(|| {
+                candidates()
+                    .filter(|a| a.is_available_at(now))
+                    .min_by_key(|a| a.unhealthy_until_ms)
+            }) else {
+                if refresh_failed.is_empty() {
+                    // Every account is disabled or outside its schedule.
+                    return Ok(None);
+                }
+                return Err(ProviderError::AuthRequired(format!(
+                    "OAuth token refresh failed for every {} account; re-login with `auth login {}`",
+                    provider_id, provider_id
+                ))
+                .into());
+            };
+
+            let mut chosen = pick.clone();
+
+            // Only one refresh per account runs at a time: refresh tokens may rotate, so
+            // concurrent refreshes with the same token can invalidate each other.
+            let mut needs_refresh = chosen.credential.is_expired();
+            let mut failed = false;
+            let _refresh_guard = if needs_refresh && matches!(chosen.credential, Credential::OAuth(_)) {
+                let guard = refresh_lock(&self.path, provider_id, &chosen.id).lock_owned().await;
+                // Another task may have refreshed (or failed to) while we waited; reuse its result.
+                if let Some(latest) = self
+                    .list_accounts(provider_id)?
+                    .into_iter()
+                    .find(|a| a.id == chosen.id)
+                    && (!latest.credential.is_expired() || latest.refresh_failures > chosen.refresh_failures)
+                {
+                    failed = latest.credential.is_expired();
+                    chosen = latest;
+                    needs_refresh = false;
+                }
+                Some(guard)
+            } else {
+                None
+            };
 
-                let old_creds = crate::oauth::OAuthCredentials {
-                    refresh: oauth.refresh.clone(),
-                    access: oauth.access.clone(),
-                    expires: oauth.expires,
-                    extra: oauth.extra.clone(),
-                };
+            // Refresh OAuth if needed. Providers without an OAuth implementation keep the stored token.
+            if needs_refresh && self.refresh_oauth_account(provider_id, &mut chosen, now).await? {
+                failed = chosen.credential.is_expired();
+            }
+            if failed {
+                refresh_failed.push(chosen.id);
+                continue;
+            }
+
+            return Ok(chosen
+                .credential
+                .api_key()
+                .map(|k| AccountSelection {
+                    account_id: chosen.id,
+                    api_key: k,
+                }));
+        }
+    }
 
-                if let Ok(new_creds) = oauth_provider.refresh_token(&old_creds).await {
-                    oauth.access = new_creds.access;
-                    oauth.refresh = new_creds.refresh;
-                    oauth.expires = new_creds.expires;
-                    oauth.extra = new_creds.extra;
-
-                    // Persist refreshed token to the same account.
-                    self.with_exclusive_lock(|| {
-                        let mut cfg = self.load_unlocked()?;
-                        {
-                            let accs = Self::ensure_accounts(&mut cfg, provider_id);
-                            if let Some(pos) = accs.accounts.iter().position(|a| a.id == chosen.id) {
-                                accs.accounts[pos].credential = chosen.credential.clone();
-                            }
-                        }
-                        Self::mirror_first_to_legacy(&mut cfg, provider_id);
-                        self.save_unlocked(&cfg)
-                    })?;
+    /// Refresh an OAuth account's token and persist the result (or the failure bookkeeping).
+    /// Returns `Ok(false)` if the account has no OAuth credential or the provider can't refresh.
+    /// Callers hold the account's refresh lock.
+    async fn refresh_oauth_account(&self, provider_id: &str, chosen: &mut Account, now: i64) -> anyhow::Result<bool> {
+        let Credential::OAuth(ref mut oauth) = chosen.credential else {
+            return Ok(false);
+        };
+        let Some(oauth_provider) = crate::oauth::get_provider(provider_id) else {
+            return Ok(false);
+        };
+
+        let old_creds = crate::oauth::OAuthCredentials {
+            refresh: oauth.refresh.clone(),
+            access: oauth.access.clone(),
+            expires: oauth.expires,
+            extra: oauth.extra.clone(),
+        };
+
+        match refresh_with_retry(&*oauth_provider, &old_creds).await {
+            Ok(new_creds) => {
+                oauth.access = new_creds.access;
+                oauth.refresh = new_creds.refresh;
+                oauth.expires = new_creds.expires;
+                oauth.extra = new_creds.extra;
+                chosen.refresh_failures = 0;
+                chosen.last_refresh_error = None;
+            }
+            Err(e) => {
+                chosen.refresh_failures = chosen.refresh_failures.saturating_add(1);
+                chosen.last_refresh_error =
+                    Some(crate::providers::sanitize::sanitize_api_error(&e.to_string()));
+                chosen.unhealthy_until_ms = Some(now.saturating_add(REFRESH_FAILURE_COOLDOWN_MS));
+                tracing::warn!(
+                    "OAuth refresh failed for {} account '{}' ({} consecutive failure(s)): {}. \
+                     Re-login with `auth login {}`.",
+                    provider_id,
+                    chosen.display_label(),
+                    chosen.refresh_failures,
+                    e,
+                    provider_id
+                );
+                if chosen.refresh_failures == 1
+                    && let Some(hook) = REFRESH_FAILURE_HOOK.get()
+                {
+                    hook(provider_id, chosen);
                 }
             }
         }
 
-        Ok(chosen
-            .credential
-            .api_key()
-            .map(|k| AccountSelection {
-                account_id: chosen.id,
-                api_key: k,
-            }))
+        // Persist the refreshed token (or failure bookkeeping) to the same account.
+        self.with_exclusive_lock(|| {
+            let mut cfg = self.load_unlocked()?;
+            {
+                let accs = Self::ensure_accounts(&mut cfg, provider_id);
+                if let Some(acc) = accs.accounts.iter_mut().find(|a| a.id == chosen.id) {
+                    acc.credential = chosen.credential.clone();
+                    acc.refresh_failures = chosen.refresh_failures;
+                    acc.last_refresh_error = chosen.last_refresh_error.clone();
+                    acc.unhealthy_until_ms = chosen.unhealthy_until_ms;
+                }
+            }
+            Self::mirror_first_to_legacy(&mut cfg, provider_id);
+            self.save_unlocked(&cfg)
+        })?;
+        Ok(true)
+    }
+
+    /// Refresh one account's OAuth token now, regardless of its expiry (TUI "force refresh").
+    pub async fn refresh_account(&self, provider_id: &str, account_id: &str) -> anyhow::Result<()> {
+        let _guard = refresh_lock(&self.path, provider_id, account_id).lock_owned().await;
+        let mut account = self
+            .list_accounts(provider_id)?
+            .into_iter()
+            .find(|a| a.id == account_id)
+            .ok_or_else(|| anyhow::anyhow!("Account not found: {}", account_id))?;
+        if !self.refresh_oauth_account(provider_id, &mut account, Self::now_ms()).await? {
+            anyhow::bail!("{} account '{}' has no refreshable OAuth token", provider_id, account.display_label());
+        }
+        if let Some(err) = account.last_refresh_error.filter(|_| account.refresh_failures > 0) {
+            anyhow::bail!("Refresh failed: {}", err);
+        }
+        Ok(())
     }
 
     /// Backward-compatible: resolve API key only.
@@ -565,6 +1199,16 @@ impl ConfigManager {
                 let accs = Self::ensure_accounts(&mut cfg, provider_id);
                 if let Some(first) = accs.accounts.first_mut() {
                     first.credential = credential.clone();
+                    // A fresh credential supersedes any earlier refresh failure.
+                    if first.needs_relogin() {
+                        first.unhealthy_until_ms = None;
+                    }
+                    first.refresh_failures = 0;
+                    first.last_refresh_error = None;
+                    // The old credential was what got it disabled; a pause by hand stays.
+                    if first.disabled_reason.take().is_some() {
+                        first.enabled = true;
+                    }
                 } else {
                     accs.accounts.push(Account {
                         id: "default".into(),
@@ -572,6 +1216,13 @@ impl ConfigManager {
                         credential: credential.clone(),
                         unhealthy_until_ms: None,
                         last_rate_limited_ms: None,
+                        refresh_failures: 0,
+                        last_refresh_error: None,
+                        email: None,
+                        org: None,
+                        enabled: true,
+                        disabled_reason: None,
+                        schedule: None,
                     });
                 }
             }
@@ -640,6 +1291,98 @@ impl ConfigManager {
         Ok(cfg.enabled_models)
     }
 
+    /// Definitions of the enabled models, with configured prices and overrides applied.
+    /// Models that no built-in list or provider definition knows are left out.
+    pub fn enabled_model_defs(&self) -> anyhow::Result<Vec<(String, ModelDef)>> {
+        let cfg = self.load()?;
+        let mut all_static = crate::models::static_models::all_static_models();
+        all_static.extend(cfg.providers.iter().flat_map(|d| d.model_defs()));
+
+        let mut models = Vec::new();
+        for full_id in &cfg.enabled_models {
+            let Some((provider, model_id)) = crate::mapper::split_model_id(full_id) else {
+                continue;
+            };
+            let def = all_static
+                .iter()
+                .find(|m| m.provider == provider && m.id == model_id)
+                .cloned()
+                .or_else(|| cfg.providers.iter().find(|d| d.id == provider).map(|d| d.model_def(model_id)))
+                .or_else(|| crate::models::default_model_def_for_provider(provider, model_id));
+            if let Some(mut def) = def {
+                if let Some(cost) = cfg.pricing.get(full_id) {
+                    def.cost = cost.clone();
+                }
+                ModelOverride::apply_all(&cfg.model_overrides, full_id, &mut def);
+                models.push((full_id.clone(), def));
+            }
+        }
+        Ok(models)
+    }
+
+    /// Declarative provider definitions from config.
+    pub fn get_provider_defs(&self) -> anyhow::Result<Vec<ProviderDef>> {
+        Ok(self.load()?.providers)
+    }
+
+    /// Add a provider definition, replacing any existing one with the same ID.
+    pub fn upsert_provider_def(&self, def: ProviderDef) -> anyhow::Result<()> {
+        let mut cfg = self.load()?;
+        match cfg.providers.iter_mut().find(|d| d.id == def.id) {
+            Some(existing) => *existing = def,
+            None => cfg.providers.push(def),
+        }
+        self.save(&cfg)
+    }
+
+    /// Get per-model price overrides (`<provider>/<model>` -> cost).
+    pub fn get_pricing_overrides(&self) -> anyhow::Result<HashMap<String, ModelCost>> {
+        Ok(self.load()?.pricing)
+    }
+
+    /// Get per-model metadata overrides (`<provider>/<model>` -> override).
+    pub fn get_model_overrides(&self) -> anyhow::Result<HashMap<String, ModelOverride>> {
+        Ok(self.load()?.model_overrides)
+    }
+
+    /// Get proxy settings.
+    pub fn get_settings(&self) -> anyhow::Result<ProxySettings> {
+        Ok(self.load()?.settings)
+    }
+
+    /// Replace proxy settings.
+    pub fn set_settings(&self, settings: ProxySettings) -> anyhow::Result<()> {
+        let mut cfg = self.load()?;
+        cfg.settings = settings;
+        self.save(&cfg)
+    }
+
+    /// Providers in maintenance at `now_ms`, with why and until when.
+    pub fn providers_in_maintenance(&self, now_ms: i64) -> anyhow::Result<HashMap<String, ProviderMaintenance>> {
+        let mut down = self.load()?.provider_maintenance;
+        down.retain(|_, m| m.is_active_at(now_ms));
+        Ok(down)
+    }
+
+    /// Take a provider out of service (`Some`) or put it back (`None`).
+    pub fn set_provider_maintenance(
+        &self,
+        provider_id: &str,
+        maintenance: Option<ProviderMaintenance>,
+    ) -> anyhow::Result<()> {
+        self.with_exclusive_lock(|| {
+            let mut cfg = self.load_unlocked()?;
+            match maintenance {
+                Some(m) => cfg.provider_maintenance.insert(provider_id.to_string(), m),
+                None => cfg.provider_maintenance.remove(provider_id),
+            };
+            // Windows that have ended are dropped on the next change.
+            let now = Self::now_ms();
+            cfg.provider_maintenance.retain(|_, m| m.is_active_at(now));
+            self.save_unlocked(&cfg)
+        })
+    }
+
     /// Get custom models URL for a provider (for OpenAI-compatible custom providers).
     pub fn get_models_url(&self, provider_id: &str) -> anyhow::Result<Option<String>> {
         let cfg = self.load()?;
@@ -701,6 +1444,7 @@ impl ConfigManager {
 
     /// Start a background task that periodically refreshes all OAuth credentials.
     /// buffer_secs should ideally be >= interval_secs to avoid missing tokens.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn start_auto_refresh_service(
         self,
         interval_secs: u64,
@@ -755,6 +1499,101 @@ mod tests {
         assert_eq!(accs[0].id, "default");
     }
 
+    #[test]
+    fn upsert_provider_def_replaces_by_id() {
+        let (_dir, mgr) = tmp_cfg();
+        let def = |url: &str| -> ProviderDef {
+            serde_json::from_value(serde_json::json!({ "id": "my-llm", "base_url": url })).unwrap()
+        };
+        mgr.upsert_provider_def(def("http://a/v1")).unwrap();
+        mgr.upsert_provider_def(def("http://b/v1")).unwrap();
+        let defs = mgr.get_provider_defs().unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].base_url, "http://b/v1");
+    }
+
+    #[test]
+    fn settings_round_trip_and_aliases() {
+        let (_dir, mgr) = tmp_cfg();
+        assert_eq!(mgr.get_settings().unwrap(), ProxySettings::default());
+        mgr.save(&AppConfig::default()).unwrap();
+        assert!(!std::fs::read_to_string(&mgr.path).unwrap().contains("settings"));
+
+        let mut settings = ProxySettings {
+            port: Some(9000),
+            daily_budget_usd: Some(5.0),
+            ..ProxySettings::default()
+        };
+        settings.model_aliases.insert("fast".into(), "openai/gpt-4o-mini".into());
+        mgr.set_settings(settings.clone()).unwrap();
+
+        let loaded = mgr.get_settings().unwrap();
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.resolve_model("fast"), "openai/gpt-4o-mini");
+        assert_eq!(loaded.resolve_model("openai/gpt-4o"), "openai/gpt-4o");
+    }
+
+    #[test]
+    fn bare_model_ids_follow_priority() {
+        let ids = ["openrouter/gpt-4o", "openai/gpt-4o", "azure/gpt-4o", "deepseek/deepseek-chat"];
+        let mut settings = ProxySettings::default();
+        assert!(settings.bare_model_ids(ids).is_empty());
+
+        settings.expose_bare_model_ids = true;
+        let bare = settings.bare_model_ids(ids);
+        assert_eq!(bare["gpt-4o"], "azure/gpt-4o");
+        assert_eq!(bare["deepseek-chat"], "deepseek/deepseek-chat");
+
+        settings.bare_model_priority = vec!["openai".into(), "openrouter".into()];
+        assert_eq!(settings.bare_model_ids(ids)["gpt-4o"], "openai/gpt-4o");
+    }
+
+    #[test]
+    fn model_overrides_parse_and_apply() {
+        let (_dir, mgr) = tmp_cfg();
+        std::fs::write(
+            &mgr.path,
+            r#"{"model_overrides": {"ollama/qwen3:32b": {
+                "context_window": 32768, "reasoning": true,
+                "headers": {"X-Tenant": "lab"}, "base_url": "http://gpu-box:11434/v1/"
+            }}}"#,
+        )
+        .unwrap();
+        let overrides = mgr.get_model_overrides().unwrap();
+        let mut def = crate::models::default_model_def_for_provider("ollama", "qwen3:32b").unwrap();
+        overrides["ollama/qwen3:32b"].apply_to(&mut def);
+        assert_eq!(def.context_window, 32768);
+        assert_eq!(def.max_tokens, 16384);
+        assert!(def.reasoning);
+        assert_eq!(def.base_url, "http://gpu-box:11434/v1");
+        assert_eq!(def.headers.unwrap()["X-Tenant"], "lab");
+    }
+
+    #[test]
+    fn persona_overrides_cover_a_provider_or_a_model() {
+        let (_dir, mgr) = tmp_cfg();
+        std::fs::write(
+            &mgr.path,
+            r#"{"model_overrides": {
+                "antigravity/*": {"persona": "off"},
+                "antigravity/gemini-3-pro-high": {"persona": {"append": "Answer in French."}}
+            }}"#,
+        )
+        .unwrap();
+        let overrides = mgr.get_model_overrides().unwrap();
+        let persona = |model: &str| {
+            let mut def = crate::models::default_model_def_for_provider("ollama", model).unwrap();
+            ModelOverride::apply_all(&overrides, &format!("antigravity/{}", model), &mut def);
+            def.persona_prompt(Some("You are Antigravity."))
+        };
+        assert_eq!(persona("gemini-3-flash"), None);
+        assert_eq!(persona("gemini-3-pro-high").as_deref(), Some("You are Antigravity.\n\nAnswer in French."));
+
+        let mut def = crate::models::default_model_def_for_provider("ollama", "gemini-2.5-pro").unwrap();
+        def.persona = Some(crate::types::Persona::Replace("Be terse.".into()));
+        assert_eq!(def.persona_prompt(None).as_deref(), Some("Be terse."));
+    }
+
     #[test]
     fn rate_limit_moves_account_to_end_and_sets_unhealthy() {
         let (_dir, mgr) = tmp_cfg();
@@ -772,4 +1611,236 @@ mod tests {
         assert_eq!(list2[1].id, id1);
         assert!(list2[1].unhealthy_until_ms.is_some());
     }
+
+    #[tokio::test]
+    async fn all_limited_falls_back_to_the_account_that_resets_first() {
+        let (_dir, mgr) = tmp_cfg();
+        let id1 = mgr.add_account("anthropic", Some("a1".into()), api_key("k1")).unwrap();
+        let id2 = mgr.add_account("anthropic", Some("a2".into()), api_key("k2")).unwrap();
+
+        // a1's plan window resets in hours, a2's limit lifts in a minute.
+        mgr.rate_limit_account("anthropic", &id1, 5 * 60 * 60 * 1000).unwrap();
+        mgr.rate_limit_account("anthropic", &id2, 60_000).unwrap();
+        let sel = mgr.resolve_account("anthropic").await.unwrap().unwrap();
+        assert_eq!(sel.account_id, id2);
+        assert_eq!(mgr.list_accounts("anthropic").unwrap()[0].id, id1);
+    }
+
+    struct FlakyRefresh {
+        failures_left: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::oauth::OAuthProvider for FlakyRefresh {
+        fn id(&self) -> &str { "flaky" }
+        fn name(&self) -> &str { "Flaky" }
+        async fn login(&self, _: &dyn crate::oauth::OAuthCallbacks) -> anyhow::Result<crate::oauth::OAuthCredentials> {
+            anyhow::bail!("login is not supported by the test provider")
+        }
+        async fn refresh_token(&self, c: &crate::oauth::OAuthCredentials) -> anyhow::Result<crate::oauth::OAuthCredentials> {
+            use std::sync::atomic::Ordering;
+            if self.failures_left.load(Ordering::SeqCst) > 0 {
+                self.failures_left.fetch_sub(1, Ordering::SeqCst);
+                anyhow::bail!("transient");
+            }
+            let mut out = c.clone();
+            out.access = "new-access".into();
+            Ok(out)
+        }
+        fn get_api_key(&self, c: &crate::oauth::OAuthCredentials) -> String { c.access.clone() }
+    }
+
+    fn old_oauth() -> crate::oauth::OAuthCredentials {
+        crate::oauth::OAuthCredentials {
+            refresh: "r".into(),
+            access: "old".into(),
+            expires: 0,
+            extra: HashMap::new(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_retries_transient_failures() {
+        let p = FlakyRefresh { failures_left: 1.into() };
+        let out = refresh_with_retry(&p, &old_oauth()).await.unwrap();
+        assert_eq!(out.access, "new-access");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_refresh_rotates_to_the_next_account() {
+        crate::oauth::register_provider(Arc::new(FlakyRefresh { failures_left: u32::MAX.into() }));
+        let (_dir, mgr) = tmp_cfg();
+        let expired = mgr.add_account("flaky", Some("a1".into()), Credential::OAuth(super::super::OAuthCredential {
+            refresh: "r".into(),
+            access: "old".into(),
+            expires: 0,
+            extra: HashMap::new(),
+        }))
+        .unwrap();
+        let working = mgr.add_account("flaky", Some("a2".into()), api_key("k2")).unwrap();
+
+        // a1 comes first, but its token can't be refreshed: a2 is used instead.
+        let sel = mgr.resolve_account("flaky").await.unwrap().unwrap();
+        assert_eq!((sel.account_id, sel.api_key), (working.clone(), "k2".to_string()));
+        let acc = mgr.list_accounts("flaky").unwrap().into_iter().find(|a| a.id == expired).unwrap();
+        assert_eq!(acc.refresh_failures, 1);
+        assert!(acc.unhealthy_until_ms.is_some());
+
+        // With no other account, the expired token is never handed out.
+        mgr.remove_account("flaky", &working).unwrap();
+        let err = mgr.resolve_account("flaky").await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(ProviderError::AuthRequired(_))), "{}", err);
+    }
+
+    #[test]
+    fn refresh_failure_fields_default_and_relogin_cleared_by_set_credential() {
+        let (_dir, mgr) = tmp_cfg();
+        mgr.add_account("qwen-portal", None, api_key("k1")).unwrap();
+        let mut cfg = mgr.load().unwrap();
+        {
+            let acc = &mut cfg.provider_accounts.get_mut("qwen-portal").unwrap().accounts[0];
+            assert!(!acc.needs_relogin());
+            acc.refresh_failures = 2;
+            acc.last_refresh_error = Some("invalid_grant".into());
+            acc.unhealthy_until_ms = Some(i64::MAX);
+        }
+        mgr.save(&cfg).unwrap();
+        assert!(mgr.list_accounts("qwen-portal").unwrap()[0].needs_relogin());
+
+        mgr.set_credential("qwen-portal", api_key("k2")).unwrap();
+        let acc = &mgr.list_accounts("qwen-portal").unwrap()[0];
+        assert!(!acc.needs_relogin());
+        assert!(acc.last_refresh_error.is_none());
+        assert!(acc.unhealthy_until_ms.is_none());
+    }
+
+    #[test]
+    fn refresh_lock_is_shared_per_account() {
+        let path = Path::new("/tmp/zeroai-test/config.json");
+        let a = refresh_lock(path, "qwen-portal", "acc-1");
+        let b = refresh_lock(path, "qwen-portal", "acc-1");
+        let c = refresh_lock(path, "qwen-portal", "acc-2");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn identity_replaces_generated_labels_only() {
+        let (_dir, cfg) = tmp_cfg();
+        let generated = cfg.add_account("openai-codex", None, api_key("a")).unwrap();
+        let chosen = cfg.add_account("openai-codex", Some("work".into()), api_key("b")).unwrap();
+        let identity = AccountIdentity { email: Some("alice@example.com".into()), org: Some("Acme".into()) };
+        cfg.set_account_identity("openai-codex", &generated, &identity).unwrap();
+        cfg.set_account_identity("openai-codex", &chosen, &identity).unwrap();
+
+        let accs = cfg.list_accounts("openai-codex").unwrap();
+        assert_eq!(accs[0].display_label(), "alice@example.com");
+        assert_eq!(accs[1].display_label(), "work");
+        assert_eq!(accs[1].org.as_deref(), Some("Acme"));
+        assert!(is_generated_label("custom:my-llm", "my-llm-12"));
+        assert!(!is_generated_label("openai", "openai-prod"));
+    }
+
+    #[test]
+    fn new_account_labels_never_collide() {
+        let (_dir, cfg) = tmp_cfg();
+        let first = cfg.add_account("openai", None, api_key("a")).unwrap();
+        cfg.add_account("openai", None, api_key("b")).unwrap();
+        cfg.remove_account("openai", &first).unwrap();
+        // One account left ("openai-2"); the next generated label skips it.
+        cfg.add_account("openai", None, api_key("c")).unwrap();
+        cfg.add_account("openai", Some("work".into()), api_key("d")).unwrap();
+        cfg.add_account("openai", Some("work".into()), api_key("e")).unwrap();
+
+        let labels: Vec<String> =
+            cfg.list_accounts("openai").unwrap().iter().map(|a| a.display_label()).collect();
+        assert_eq!(labels, ["openai-2", "openai-3", "work", "work-2"]);
+    }
+
+    #[tokio::test]
+    async fn disabled_accounts_are_never_selected() {
+        let (_dir, cfg) = tmp_cfg();
+        let bad = cfg.add_account("openai", None, api_key("bad")).unwrap();
+        cfg.add_account("openai", None, api_key("good")).unwrap();
+        cfg.set_account_enabled("openai", &bad, false, Some("401 Unauthorized".into())).unwrap();
+        assert_eq!(cfg.resolve_api_key("openai").await.unwrap().as_deref(), Some("good"));
+
+        for acc in cfg.list_accounts("openai").unwrap() {
+            cfg.set_account_enabled("openai", &acc.id, false, None).unwrap();
+        }
+        assert_eq!(cfg.resolve_api_key("openai").await.unwrap(), None);
+
+        cfg.set_account_enabled("openai", &bad, true, None).unwrap();
+        assert_eq!(cfg.resolve_api_key("openai").await.unwrap().as_deref(), Some("bad"));
+        // A new credential re-enables an account `auth verify` turned off, not one paused by hand.
+        let first = cfg.list_accounts("openai").unwrap()[0].id.clone();
+        cfg.set_account_enabled("openai", &first, false, Some("401".into())).unwrap();
+        cfg.set_credential("openai", api_key("new")).unwrap();
+        assert!(cfg.list_accounts("openai").unwrap()[0].enabled);
+        cfg.set_account_enabled("openai", &first, false, None).unwrap();
+        cfg.set_credential("openai", api_key("newer")).unwrap();
+        assert!(!cfg.list_accounts("openai").unwrap()[0].enabled);
+    }
+
+    #[tokio::test]
+    async fn accounts_are_skipped_outside_their_schedule() {
+        let (_dir, cfg) = tmp_cfg();
+        let personal = cfg.add_account("anthropic", Some("personal".into()), api_key("personal")).unwrap();
+        cfg.add_account("anthropic", Some("work".into()), api_key("work")).unwrap();
+        assert!(cfg.set_account_schedule("anthropic", &personal, Some("Mon-Fry")).is_err());
+
+        // Only open tomorrow.
+        let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).format("%a UTC").to_string();
+        cfg.set_account_schedule("anthropic", &personal, Some(&tomorrow)).unwrap();
+        assert_eq!(cfg.resolve_api_key("anthropic").await.unwrap().as_deref(), Some("work"));
+
+        cfg.set_account_schedule("anthropic", &personal, Some("daily UTC")).unwrap();
+        assert_eq!(cfg.resolve_api_key("anthropic").await.unwrap().as_deref(), Some("personal"));
+        cfg.set_account_schedule("anthropic", &personal, None).unwrap();
+        let mut account = cfg.list_accounts("anthropic").unwrap().remove(0);
+        assert_eq!(account.schedule, None);
+
+        // A hand-edited schedule that doesn't parse keeps the account out of use.
+        account.schedule = Some("Mon-Fry".into());
+        assert!(!account.is_available_at(ConfigManager::now_ms()));
+    }
+
+    #[test]
+    fn provider_maintenance_expires() {
+        let (_dir, cfg) = tmp_cfg();
+        let now = ConfigManager::now_ms();
+        let outage = ProviderMaintenance { reason: Some("outage".into()), until_ms: Some(now + 60_000) };
+        cfg.set_provider_maintenance("openai", Some(outage.clone())).unwrap();
+        cfg.set_provider_maintenance("groq", Some(ProviderMaintenance::default())).unwrap();
+
+        let down = cfg.providers_in_maintenance(now).unwrap();
+        assert_eq!(down.get("openai"), Some(&outage));
+        assert!(down.contains_key("groq"));
+        assert!(!cfg.providers_in_maintenance(now + 60_000).unwrap().contains_key("openai"));
+
+        cfg.set_provider_maintenance("groq", None).unwrap();
+        assert!(!cfg.providers_in_maintenance(now).unwrap().contains_key("groq"));
+    }
+
+    #[test]
+    fn config_dir_prefers_the_set_dir_then_the_env_var() {
+        let set = Some(PathBuf::from("/srv/staging"));
+        let env = Some("/srv/prod".into());
+        assert_eq!(pick_config_dir(set, env.clone()), PathBuf::from("/srv/staging"));
+        assert_eq!(pick_config_dir(None, env), PathBuf::from("/srv/prod"));
+        assert!(pick_config_dir(None, Some("".into())).ends_with(".zeroai"));
+    }
+
+    #[test]
+    fn config_roots_are_independent() {
+        let (staging, prod) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (a, b) = (ConfigManager::in_dir(staging.path()), ConfigManager::in_dir(prod.path()));
+        assert_eq!(a.dir(), staging.path());
+        a.add_account("openai", None, api_key("staging")).unwrap();
+        b.set_settings(ProxySettings { port: Some(9000), ..Default::default() }).unwrap();
+        assert_eq!(a.list_accounts("openai").unwrap().len(), 1);
+        assert!(b.list_accounts("openai").unwrap().is_empty());
+        assert_eq!(a.get_settings().unwrap().port, None);
+        assert_eq!(b.get_settings().unwrap().port, Some(9000));
+    }
 }
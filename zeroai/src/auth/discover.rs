@@ -0,0 +1,148 @@
+//! Local model servers (Ollama, vLLM, LM Studio, llama.cpp) found by probing their default
+//! ports on localhost, and enabling one's models in one step.
+
+use super::config::ConfigManager;
+use super::{ApiKeyCredential, Credential};
+use crate::mapper::join_model_id;
+use crate::providers::registry::ProviderDef;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// A kind of local server and the port it listens on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalServer {
+    /// Provider ID its models are enabled under.
+    pub provider: &'static str,
+    pub label: &'static str,
+    pub port: u16,
+}
+
+/// The servers probed. Ollama and vLLM are built-in providers; LM Studio and llama.cpp get a
+/// provider definition when enabled.
+pub const LOCAL_SERVERS: &[LocalServer] = &[
+    LocalServer { provider: "ollama", label: "Ollama", port: 11434 },
+    LocalServer { provider: "vllm", label: "vLLM", port: 8000 },
+    LocalServer { provider: "lmstudio", label: "LM Studio", port: 1234 },
+    LocalServer { provider: "llamacpp", label: "llama.cpp", port: 8080 },
+];
+
+/// Nothing on localhost should take longer than this to list its models.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A server that answered, with the models it serves.
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer {
+    pub server: LocalServer,
+    /// OpenAI-compatible API base, e.g. `http://127.0.0.1:11434/v1`.
+    pub base_url: String,
+    pub models: Vec<String>,
+}
+
+impl DiscoveredServer {
+    /// `<provider>/<model>` IDs of its models.
+    pub fn model_ids(&self) -> Vec<String> {
+        self.models.iter().map(|m| join_model_id(self.server.provider, m)).collect()
+    }
+
+    /// Every one of its models is already in `enabled`.
+    pub fn is_enabled(&self, enabled: &[String]) -> bool {
+        self.model_ids().iter().all(|id| enabled.contains(id))
+    }
+}
+
+/// Probe every server in `LOCAL_SERVERS` on 127.0.0.1; the ones running come back in that order.
+pub async fn discover_local_servers() -> Vec<DiscoveredServer> {
+    let probes = LOCAL_SERVERS
+        .iter()
+        .map(|server| probe(*server, format!("http://127.0.0.1:{}/v1", server.port)));
+    futures::future::join_all(probes).await.into_iter().flatten().collect()
+}
+
+/// `GET {base_url}/models`; `None` unless a model list comes back. All four servers have the
+/// OpenAI-compatible endpoint (Ollama's lists the same models as its `/api/tags`).
+pub async fn probe(server: LocalServer, base_url: String) -> Option<DiscoveredServer> {
+    #[derive(Deserialize)]
+    struct ModelList {
+        data: Vec<Entry>,
+    }
+    #[derive(Deserialize)]
+    struct Entry {
+        id: String,
+    }
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}/models", base_url))
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+    let list: ModelList = resp.error_for_status().ok()?.json().await.ok()?;
+    Some(DiscoveredServer {
+        server,
+        base_url,
+        models: list.data.into_iter().map(|e| e.id).collect(),
+    })
+}
+
+/// Enable a discovered server's models. LM Studio and llama.cpp get a provider definition
+/// pointing at the server, and a provider without credentials gets a placeholder API key
+/// (local servers don't check it). Returns how many models were newly enabled.
+pub fn enable_local_server(config: &ConfigManager, found: &DiscoveredServer) -> anyhow::Result<usize> {
+    let provider = found.server.provider;
+    let builtin = super::provider_base_url(provider).is_some();
+    if !builtin && !config.get_provider_defs()?.iter().any(|d| d.id == provider) {
+        config.upsert_provider_def(ProviderDef {
+            id: provider.to_string(),
+            base_url: found.base_url.clone(),
+            auth: Default::default(),
+            api_key_env: None,
+            headers: Default::default(),
+            models_url: None,
+            models: Vec::new(),
+            embeddings: Default::default(),
+            completions: false,
+        })?;
+    }
+    if !config.has_credential(provider)? {
+        config.set_credential(provider, Credential::ApiKey(ApiKeyCredential { key: "local".into() }))?;
+    }
+
+    let enabled = config.get_enabled_models()?;
+    let new: Vec<String> = found.model_ids().into_iter().filter(|id| !enabled.contains(id)).collect();
+    config.add_enabled_models(&new)?;
+    Ok(new.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn running_servers_are_listed_and_enabled() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"object":"list","data":[{"id":"qwen2.5-coder-7b","object":"model"}]}"#;
+            let reply = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(reply.as_bytes()).await.unwrap();
+        });
+        let lmstudio = LOCAL_SERVERS[2];
+        let base_url = format!("http://{}/v1", addr);
+
+        let found = probe(lmstudio, base_url.clone()).await.unwrap();
+        assert_eq!(found.model_ids(), ["lmstudio/qwen2.5-coder-7b"]);
+        assert!(probe(lmstudio, "http://127.0.0.1:1/v1".into()).await.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = ConfigManager::new(dir.path().join("config.json"));
+        assert_eq!(enable_local_server(&config, &found).unwrap(), 1);
+        assert_eq!(enable_local_server(&config, &found).unwrap(), 0);
+        assert!(found.is_enabled(&config.get_enabled_models().unwrap()));
+        assert_eq!(config.get_provider_defs().unwrap()[0].base_url, base_url);
+        assert!(config.has_credential("lmstudio").unwrap());
+    }
+}
@@ -1,6 +1,12 @@
 pub mod config;
+pub mod discover;
+pub mod schedule;
 pub mod sniff;
+pub mod usage;
+pub mod validate;
+pub mod verify;
 
+use crate::providers::registry::ProviderDef;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -135,6 +141,15 @@ pub fn all_provider_auth_info() -> Vec<ProviderAuthInfo> {
                 hint: Some("run `claude setup-token` elsewhere, then paste the token here".into()),
             }],
         },
+        ProviderAuthInfo {
+            provider_id: "anthropic-oauth".into(),
+            label: "Anthropic (Claude Pro/Max OAuth)".into(),
+            group: "Anthropic".into(),
+            hint: "Sign in with your Claude subscription".into(),
+            auth_methods: vec![AuthMethod::OAuth {
+                hint: Some("Sign in with Claude, then paste the code from the callback page".into()),
+            }],
+        },
         // vLLM Group
         ProviderAuthInfo {
             provider_id: "vllm".into(),
@@ -298,7 +313,7 @@ pub fn all_provider_auth_info() -> Vec<ProviderAuthInfo> {
             provider_id: "huggingface".into(),
             label: "Hugging Face API key (HF token)".into(),
             group: "Hugging Face".into(),
-            hint: "Inference API (HF token)".into(),
+            hint: "Inference Providers router (HF token)".into(),
             auth_methods: vec![AuthMethod::ApiKey {
                 env_var: Some("HUGGINGFACE_API_KEY".into()),
                 hint: Some("Inference Providers — OpenAI-compatible chat".into()),
@@ -323,6 +338,28 @@ pub fn all_provider_auth_info() -> Vec<ProviderAuthInfo> {
             hint: "Account ID + Gateway ID + API key".into(),
             auth_methods: vec![AuthMethod::ApiKey {
                 env_var: None,
+                hint: Some(r#"JSON: {"account_id": "...", "gateway_id": "...", "api_key": "<upstream key>"}, plus optional gateway_token, cache_ttl, skip_cache, collect_log, metadata"#.into()),
+            }],
+        },
+        // Cohere Group
+        ProviderAuthInfo {
+            provider_id: "cohere".into(),
+            label: "Cohere API key".into(),
+            group: "Cohere".into(),
+            hint: "Command models, embeddings, rerank".into(),
+            auth_methods: vec![AuthMethod::ApiKey {
+                env_var: Some("COHERE_API_KEY".into()),
+                hint: None,
+            }],
+        },
+        // Jina AI Group
+        ProviderAuthInfo {
+            provider_id: "jina".into(),
+            label: "Jina AI API key".into(),
+            group: "Jina AI".into(),
+            hint: "Embeddings and rerank".into(),
+            auth_methods: vec![AuthMethod::ApiKey {
+                env_var: Some("JINA_API_KEY".into()),
                 hint: None,
             }],
         },
@@ -337,11 +374,44 @@ pub fn all_provider_auth_info() -> Vec<ProviderAuthInfo> {
                 hint: None,
             }],
         },
+        ProviderAuthInfo {
+            provider_id: "mistral".into(),
+            label: "Mistral API key".into(),
+            group: "Mistral".into(),
+            hint: "Mistral Large, Codestral (code completion)".into(),
+            auth_methods: vec![AuthMethod::ApiKey {
+                env_var: Some("MISTRAL_API_KEY".into()),
+                hint: None,
+            }],
+        },
     ]
 }
 
+/// Group label for providers declared in config (see [`ProviderDef`]).
+pub const CUSTOM_PROVIDER_GROUP: &str = "Custom";
+
+/// Auth info for a provider declared in config.
+pub fn custom_provider_auth_info(def: &ProviderDef) -> ProviderAuthInfo {
+    ProviderAuthInfo {
+        provider_id: def.id.clone(),
+        label: def.id.clone(),
+        group: CUSTOM_PROVIDER_GROUP.into(),
+        hint: def.base_url.clone(),
+        auth_methods: vec![AuthMethod::ApiKey {
+            env_var: def.api_key_env.clone(),
+            hint: Some(format!("API key for {}", def.base_url)),
+        }],
+    }
+}
+
 pub fn provider_groups() -> Vec<(String, Vec<ProviderAuthInfo>)> {
-    let all = all_provider_auth_info();
+    provider_groups_with(&[])
+}
+
+/// Provider groups with the config-declared providers in a trailing "Custom" group.
+pub fn provider_groups_with(defs: &[ProviderDef]) -> Vec<(String, Vec<ProviderAuthInfo>)> {
+    let mut all = all_provider_auth_info();
+    all.extend(defs.iter().map(custom_provider_auth_info));
     let mut groups: Vec<(String, Vec<ProviderAuthInfo>)> = Vec::new();
     for info in all {
         if let Some(g) = groups.iter_mut().find(|(label, _)| label == &info.group) {
@@ -367,6 +437,9 @@ pub fn provider_base_url(provider_id: &str) -> Option<&'static str> {
         // See OpenClaw implementation: https://chatgpt.com/backend-api/codex/responses
         "openai-codex" => Some("https://chatgpt.com/backend-api"),
         "deepseek" => Some("https://api.deepseek.com/v1"),
+        "mistral" => Some("https://api.mistral.ai/v1"),
+        "cohere" => Some("https://api.cohere.com/compatibility/v1"),
+        "jina" => Some("https://api.jina.ai/v1"),
         "xai" => Some("https://api.x.ai/v1"),
         "groq" => Some("https://api.groq.com/openai/v1"),
         "together" => Some("https://api.together.xyz/v1"),
@@ -376,7 +449,7 @@ pub fn provider_base_url(provider_id: &str) -> Option<&'static str> {
         "openrouter" => Some("https://openrouter.ai/api/v1"),
         "minimax" => Some("https://api.minimax.chat/v1"),
         "moonshot" => Some("https://api.moonshot.ai/v1"),
-        "huggingface" => Some("https://api-inference.huggingface.co/v1"),
+        "huggingface" => Some("https://router.huggingface.co/v1"),
         "venice" => Some("https://api.venice.ai/api/v1"),
         "ollama" => Some("http://127.0.0.1:11434/v1"),
         "vllm" => Some("http://127.0.0.1:8000/v1"),
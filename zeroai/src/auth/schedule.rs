@@ -0,0 +1,252 @@
+//! Account availability windows, e.g. a personal subscription that should only be used
+//! outside work hours.
+//!
+//! A schedule is a comma-separated list of rules, each with optional days and optional hour
+//! ranges, plus an optional time zone (`local` by default, an IANA name such as
+//! `Europe/Berlin`, which follows daylight saving time, or a fixed `UTC+8` / `UTC-05:30`):
+//!
+//! ```text
+//! Mon-Fri 18:00-09:00, Sat-Sun
+//! Mon-Fri 0-9 12-13 19-24 UTC+2
+//! Mon-Fri 9-17 America/New_York
+//! ```
+//!
+//! A range ending at or before its start runs past midnight, so `Fri 18:00-09:00` ends on
+//! Saturday morning.
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, Timelike};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+
+const DAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+const DAY_MINUTES: u32 = 24 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    rules: Vec<Rule>,
+    zone: Zone,
+    source: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    /// The machine's local time zone.
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    /// Indexed by days from Monday.
+    days: [bool; 7],
+    /// Minutes since midnight, `[start, end)`; an empty list is the whole day.
+    ranges: Vec<(u32, u32)>,
+}
+
+impl Schedule {
+    /// Whether the schedule allows use at `now_ms`.
+    pub fn is_open_at(&self, now_ms: i64) -> bool {
+        let Some(utc) = DateTime::from_timestamp_millis(now_ms) else {
+            return true;
+        };
+        let (day, minute) = match self.zone {
+            Zone::Local => day_and_minute(utc.with_timezone(&Local)),
+            Zone::Fixed(offset) => day_and_minute(utc.with_timezone(&offset)),
+            Zone::Named(tz) => day_and_minute(utc.with_timezone(&tz)),
+        };
+        self.rules.iter().any(|rule| rule.contains(day, minute))
+    }
+}
+
+fn day_and_minute<Tz: chrono::TimeZone>(t: DateTime<Tz>) -> (usize, u32) {
+    (t.weekday().num_days_from_monday() as usize, t.hour() * 60 + t.minute())
+}
+
+impl Rule {
+    fn contains(&self, day: usize, minute: u32) -> bool {
+        if self.ranges.is_empty() {
+            return self.days[day];
+        }
+        let yesterday = (day + 6) % 7;
+        self.ranges.iter().any(|&(start, end)| {
+            if start < end {
+                self.days[day] && (start..end).contains(&minute)
+            } else {
+                (self.days[day] && minute >= start) || (self.days[yesterday] && minute < end)
+            }
+        })
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        let mut zone = Zone::Local;
+        for part in s.split(',') {
+            let mut days = None;
+            let mut ranges = Vec::new();
+            for token in part.split_whitespace() {
+                if let Some(z) = parse_zone(token)? {
+                    zone = z;
+                } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+                    ranges.push(parse_range(token)?);
+                } else if days.is_none() {
+                    days = Some(parse_days(token)?);
+                } else {
+                    return Err(format!("unexpected '{}' after the days", token));
+                }
+            }
+            if days.is_none() && ranges.is_empty() {
+                continue;
+            }
+            rules.push(Rule { days: days.unwrap_or([true; 7]), ranges });
+        }
+        if rules.is_empty() {
+            return Err("no days or hours given".into());
+        }
+        Ok(Schedule { rules, zone, source: s.trim().to_string() })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// `local`, an IANA name (`Europe/Berlin`), or `UTC+8` / `GMT-05:30` / `UTC`; `None` for
+/// anything else.
+fn parse_zone(token: &str) -> Result<Option<Zone>, String> {
+    let lower = token.to_ascii_lowercase();
+    if lower == "local" {
+        return Ok(Some(Zone::Local));
+    }
+    if token.contains('/') {
+        return token
+            .parse::<Tz>()
+            .map(|tz| Some(Zone::Named(tz)))
+            .map_err(|_| format!("unknown time zone '{}'", token));
+    }
+    let Some(rest) = lower.strip_prefix("utc").or_else(|| lower.strip_prefix("gmt")) else {
+        return Ok(None);
+    };
+    if rest.is_empty() {
+        return Ok(Some(Zone::Fixed(FixedOffset::east_opt(0).unwrap())));
+    }
+    let bad = || format!("bad time zone '{}' (use local, an IANA name or UTC+H[:MM])", token);
+    let (sign, rest) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return Err(bad()),
+    };
+    let (h, m) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (h, m): (i32, i32) = (h.parse().map_err(|_| bad())?, m.parse().map_err(|_| bad())?);
+    if h > 14 || m > 59 {
+        return Err(bad());
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60)).map(|o| Some(Zone::Fixed(o))).ok_or_else(bad)
+}
+
+/// `Mon`, `Mon-Fri`, `Sat-Sun`, `Fri-Mon` (wrapping), `daily`.
+fn parse_days(token: &str) -> Result<[bool; 7], String> {
+    let lower = token.to_ascii_lowercase();
+    if lower == "daily" {
+        return Ok([true; 7]);
+    }
+    let day = |name: &str| {
+        DAYS.iter()
+            .position(|d| name.len() >= 3 && d.starts_with(name))
+            .ok_or_else(|| format!("unknown day '{}'", name))
+    };
+    let (from, to) = match lower.split_once('-') {
+        Some((a, b)) => (day(a)?, day(b)?),
+        None => (day(&lower)?, day(&lower)?),
+    };
+    let mut days = [false; 7];
+    let mut d = from;
+    loop {
+        days[d] = true;
+        if d == to {
+            break;
+        }
+        d = (d + 1) % 7;
+    }
+    Ok(days)
+}
+
+/// `18:00-09:00` or `18-9`, in minutes since midnight; `24` / `24:00` is the end of the day.
+fn parse_range(token: &str) -> Result<(u32, u32), String> {
+    let (a, b) = token
+        .split_once('-')
+        .ok_or_else(|| format!("bad hours '{}' (use HH:MM-HH:MM)", token))?;
+    let time = |t: &str| {
+        let (h, m) = t.split_once(':').unwrap_or((t, "0"));
+        match (h.parse::<u32>(), m.parse::<u32>()) {
+            (Ok(h), Ok(m)) if m < 60 && h * 60 + m <= DAY_MINUTES => Ok(h * 60 + m),
+            _ => Err(format!("bad time '{}'", t)),
+        }
+    };
+    Ok((time(a)? % DAY_MINUTES, time(b)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix ms of a UTC time in the week of Monday 2026-03-02.
+    fn at(day: u32, hour: u32, minute: u32) -> i64 {
+        chrono::NaiveDate::from_ymd_opt(2026, 3, 2 + day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn outside_work_hours() {
+        let s: Schedule = "Mon-Fri 18:00-09:00, Sat-Sun UTC".parse().unwrap();
+        assert!(!s.is_open_at(at(0, 12, 0)));
+        assert!(s.is_open_at(at(0, 18, 0)));
+        assert!(s.is_open_at(at(1, 8, 59)));
+        assert!(!s.is_open_at(at(1, 9, 0)));
+        // The weekend is open all day.
+        assert!(s.is_open_at(at(5, 3, 0)));
+        assert!(s.is_open_at(at(6, 15, 0)));
+        // A whole-day rule ends at midnight.
+        assert!(!s.is_open_at(at(0, 0, 30)));
+    }
+
+    #[test]
+    fn hour_ranges_with_offset() {
+        let s: Schedule = "12-13 19-24 UTC+2".parse().unwrap();
+        assert!(s.is_open_at(at(2, 10, 30)));
+        assert!(!s.is_open_at(at(2, 12, 30)));
+        assert!(s.is_open_at(at(2, 21, 59)));
+        assert!(!s.is_open_at(at(2, 22, 0)));
+    }
+
+    #[test]
+    fn named_zones_follow_daylight_saving() {
+        let s: Schedule = "9-17 Europe/Berlin".parse().unwrap();
+        // Winter time (UTC+1).
+        assert!(!s.is_open_at(at(0, 7, 30)));
+        assert!(s.is_open_at(at(0, 8, 0)));
+        assert!(s.is_open_at(at(0, 15, 30)));
+        // Summer time (UTC+2) starts on 2026-03-29.
+        assert!(s.is_open_at(at(28, 7, 30)));
+        assert!(!s.is_open_at(at(28, 15, 30)));
+    }
+
+    #[test]
+    fn rejects_bad_schedules() {
+        for bad in ["", "Mon-Funday", "Mon 25:00-26:00", "9-17 UTC+x", "Mon Tue", "9-17 Europe/Nowhere"] {
+            assert!(bad.parse::<Schedule>().is_err(), "{}", bad);
+        }
+        assert_eq!(parse_days("Fri-Mon").unwrap(), [true, false, false, false, true, true, true]);
+    }
+}
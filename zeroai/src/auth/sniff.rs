@@ -1,6 +1,7 @@
 use super::{ApiKeyCredential, Credential, OAuthCredential};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // ---------------------------------------------------------------------------
 // Environment variable sniffing
@@ -28,6 +29,7 @@ const ENV_VAR_MAP: &[(&str, &str)] = &[
     ("venice", "VENICE_API_KEY"),
     ("perplexity", "PERPLEXITY_API_KEY"),
     ("cohere", "COHERE_API_KEY"),
+    ("jina", "JINA_API_KEY"),
     ("moonshot", "MOONSHOT_API_KEY"),
     ("kimi", "MOONSHOT_API_KEY"),
     ("glm", "GLM_API_KEY"),
@@ -72,6 +74,7 @@ fn provider_env_candidates(name: &str) -> &'static [&'static str] {
         "fireworks" | "fireworks-ai" => &["FIREWORKS_API_KEY"],
         "perplexity" => &["PERPLEXITY_API_KEY"],
         "cohere" => &["COHERE_API_KEY"],
+        "jina" => &["JINA_API_KEY"],
         "moonshot" | "kimi" => &["MOONSHOT_API_KEY"],
         "glm" | "zhipu" | "zhipuai" => &["GLM_API_KEY", "ZHIPUAI_API_KEY"],
         "minimax" => &["MINIMAX_API_KEY"],
@@ -203,6 +206,76 @@ mod tests {
         }
         assert_eq!(got.as_deref(), Some("generic-api-key"));
     }
+
+    fn write(home: &std::path::Path, rel: &str, content: serde_json::Value) {
+        let path = home.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content.to_string()).unwrap();
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        format!("h.{}.s", URL_SAFE_NO_PAD.encode(claims.to_string()))
+    }
+
+    #[test]
+    fn sniffs_claude_and_codex_logins_with_identity_and_expiry() {
+        let home = tempfile::tempdir().unwrap();
+        write(home.path(), ".claude/.credentials.json", serde_json::json!({"claudeAiOauth": {
+            "accessToken": "at", "refreshToken": "rt", "expiresAt": 1_700_000_000_000i64, "subscriptionType": "max",
+        }}));
+        write(home.path(), ".codex/auth.json", serde_json::json!({
+            "OPENAI_API_KEY": "sk-codex-1234",
+            "tokens": {
+                "id_token": jwt(serde_json::json!({"email": "me@example.com"})),
+                "access_token": jwt(serde_json::json!({"exp": 1_800_000_000})),
+                "refresh_token": "codex-rt",
+            },
+        }));
+
+        let found = sniff_credentials_in(home.path(), None);
+        let summary: Vec<_> = found
+            .iter()
+            .map(|s| (s.source, s.provider.as_str(), s.identity.as_deref(), s.expires_ms))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (SniffSource::Claude, "anthropic-oauth", Some("max plan"), Some(1_700_000_000_000)),
+                (SniffSource::Codex, "openai-codex", Some("me@example.com"), Some(1_800_000_000_000)),
+                (SniffSource::Codex, "openai", Some("key …1234"), None),
+            ]
+        );
+        assert_eq!(found[0].label(), "claude (max plan)");
+
+        let codex_only = sniff_credentials_in(home.path(), Some(SniffSource::Codex));
+        assert_eq!(codex_only.len(), 2);
+        assert!(sniff_credentials_in(home.path(), Some(SniffSource::Gemini)).is_empty());
+    }
+
+    #[test]
+    fn recognizes_already_imported_credentials() {
+        let home = tempfile::tempdir().unwrap();
+        write(home.path(), ".openai/auth.json", serde_json::json!({"api_key": "sk-abc"}));
+        let found = sniff_credentials_in(home.path(), None).remove(0);
+        let account = |key: &str| super::super::config::Account {
+            id: "a".into(),
+            label: None,
+            credential: Credential::ApiKey(ApiKeyCredential { key: key.into() }),
+            unhealthy_until_ms: None,
+            last_rate_limited_ms: None,
+            refresh_failures: 0,
+            last_refresh_error: None,
+            email: None,
+            org: None,
+            enabled: true,
+            disabled_reason: None,
+            schedule: None,
+        };
+        assert!(found.is_in(&[account("sk-abc")]));
+        assert!(!found.is_in(&[account("sk-other")]));
+        assert_eq!("codex".parse::<SniffSource>(), Ok(SniffSource::Codex));
+        assert!("vim".parse::<SniffSource>().is_err());
+    }
 }
 
 /// Try to get an API key from environment variables for the given provider.
@@ -235,178 +308,328 @@ pub fn sniff_all_env_vars() -> HashMap<String, String> {
 // External credential file sniffing
 // ---------------------------------------------------------------------------
 
-/// Known external credential file paths.
-fn external_credential_paths() -> Vec<ExternalCredFile> {
-    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+/// The tool whose login files a sniffed credential comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SniffSource {
+    /// Claude Code (`~/.claude`) and `~/.anthropic`.
+    Claude,
+    /// Gemini CLI (`~/.gemini`) and gcloud application default credentials.
+    Gemini,
+    /// Codex CLI (`~/.codex`) and `~/.openai`.
+    Codex,
+}
+
+impl SniffSource {
+    pub const ALL: [SniffSource; 3] = [SniffSource::Claude, SniffSource::Gemini, SniffSource::Codex];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SniffSource::Claude => "claude",
+            SniffSource::Gemini => "gemini",
+            SniffSource::Codex => "codex",
+        }
+    }
+}
+
+impl std::fmt::Display for SniffSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for SniffSource {
+    type Err = String;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SniffSource::ALL
+            .into_iter()
+            .find(|src| src.name() == s)
+            .ok_or_else(|| format!("unknown source '{}' (expected claude, gemini or codex)", s))
+    }
+}
+
+/// A credential found in another tool's files. Nothing is saved until the user picks it for
+/// import.
+#[derive(Debug, Clone)]
+pub struct SniffedCredential {
+    pub source: SniffSource,
+    /// The zeroai provider it works with.
+    pub provider: String,
+    /// The file it was read from.
+    pub path: PathBuf,
+    /// Who it belongs to, as far as the file tells: an email, account or project, a plan, or
+    /// the tail of a key.
+    pub identity: Option<String>,
+    /// When the stored access token expires (Unix ms). Refreshable OAuth credentials keep
+    /// working past it.
+    pub expires_ms: Option<i64>,
+    pub credential: Credential,
+}
+
+impl SniffedCredential {
+    /// Account label to import it under.
+    pub fn label(&self) -> String {
+        match &self.identity {
+            Some(identity) => format!("{} ({})", self.source, identity),
+            None => format!("{} import", self.source),
+        }
+    }
+
+    /// Whether one of `accounts` already holds this credential (same key or refresh token).
+    pub fn is_in(&self, accounts: &[super::config::Account]) -> bool {
+        accounts.iter().any(|a| match (&a.credential, &self.credential) {
+            (Credential::ApiKey(a), Credential::ApiKey(b)) => a.key == b.key,
+            (Credential::OAuth(a), Credential::OAuth(b)) => a.refresh == b.refresh,
+            (Credential::SetupToken(a), Credential::SetupToken(b)) => a.token == b.token,
+            _ => false,
+        })
+    }
+}
+
+/// Known external credential files under `home`.
+fn external_credential_files(home: &Path) -> Vec<ExternalCredFile> {
+    let file = |source, provider: &str, path: PathBuf, kind| ExternalCredFile {
+        source,
+        provider: provider.into(),
+        path,
+        kind,
+    };
     vec![
-        ExternalCredFile {
-            provider: "gemini-cli".into(),
-            path: home.join(".gemini").join("oauth_creds.json"),
-            kind: CredFileKind::GeminiOAuth,
-        },
-        ExternalCredFile {
-            provider: "gemini-cli".into(),
-            path: home
-                .join(".config")
-                .join("gcloud")
-                .join("application_default_credentials.json"),
-            kind: CredFileKind::GCloudADC,
-        },
-        ExternalCredFile {
-            provider: "anthropic".into(),
-            path: home.join(".anthropic").join("config.json"),
-            kind: CredFileKind::AnthropicConfig,
-        },
-        ExternalCredFile {
-            provider: "openai".into(),
-            path: home.join(".openai").join("auth.json"),
-            kind: CredFileKind::OpenAiAuth,
-        },
+        file(
+            SniffSource::Claude,
+            "anthropic-oauth",
+            home.join(".claude").join(".credentials.json"),
+            CredFileKind::ClaudeCode,
+        ),
+        file(SniffSource::Claude, "anthropic", home.join(".anthropic").join("config.json"), CredFileKind::AnthropicConfig),
+        file(SniffSource::Gemini, "gemini-cli", home.join(".gemini").join("oauth_creds.json"), CredFileKind::GeminiOAuth),
+        file(
+            SniffSource::Gemini,
+            "gemini-cli",
+            home.join(".config").join("gcloud").join("application_default_credentials.json"),
+            CredFileKind::GCloudADC,
+        ),
+        file(SniffSource::Codex, "openai-codex", home.join(".codex").join("auth.json"), CredFileKind::CodexTokens),
+        file(SniffSource::Codex, "openai", home.join(".codex").join("auth.json"), CredFileKind::CodexApiKey),
+        file(SniffSource::Codex, "openai", home.join(".openai").join("auth.json"), CredFileKind::OpenAiAuth),
     ]
 }
 
 struct ExternalCredFile {
+    source: SniffSource,
     provider: String,
     path: PathBuf,
     kind: CredFileKind,
 }
 
 enum CredFileKind {
+    ClaudeCode,
+    AnthropicConfig,
     GeminiOAuth,
     GCloudADC,
-    AnthropicConfig,
+    CodexTokens,
+    CodexApiKey,
     OpenAiAuth,
 }
 
+/// What a file parser found.
+struct Found {
+    credential: Credential,
+    identity: Option<String>,
+    expires_ms: Option<i64>,
+}
+
 /// Returns all known external credential file scan paths: (provider_id, path).
 pub fn all_external_credential_paths() -> Vec<(String, PathBuf)> {
-    external_credential_paths()
+    external_credential_files(&home_dir())
         .into_iter()
         .map(|f| (f.provider, f.path))
         .collect()
 }
 
-/// Try to sniff an external credential file for the given provider.
-pub fn sniff_external_credential(provider_id: &str) -> Option<Credential> {
-    for file in external_credential_paths() {
-        if file.provider != provider_id {
-            continue;
-        }
-        if !file.path.exists() {
-            continue;
-        }
+/// Credentials found in other tools' files, from `source` or from all of them, in scan order.
+pub fn sniff_credentials(source: Option<SniffSource>) -> Vec<SniffedCredential> {
+    sniff_credentials_in(&home_dir(), source)
+}
 
-        let content = std::fs::read_to_string(&file.path).ok()?;
+fn sniff_credentials_in(home: &Path, source: Option<SniffSource>) -> Vec<SniffedCredential> {
+    external_credential_files(home)
+        .into_iter()
+        .filter(|f| source.is_none_or(|s| s == f.source))
+        .filter_map(|f| {
+            let content = std::fs::read_to_string(&f.path).ok()?;
+            let found = match f.kind {
+                CredFileKind::ClaudeCode => parse_claude_code_creds(&content),
+                CredFileKind::AnthropicConfig => parse_anthropic_config(&content),
+                CredFileKind::GeminiOAuth => parse_gemini_oauth_creds(&content),
+                CredFileKind::GCloudADC => parse_gcloud_adc(&content),
+                CredFileKind::CodexTokens => parse_codex_tokens(&content),
+                CredFileKind::CodexApiKey => parse_codex_api_key(&content),
+                CredFileKind::OpenAiAuth => parse_openai_auth(&content),
+            }?;
+            Some(SniffedCredential {
+                source: f.source,
+                provider: f.provider,
+                path: f.path,
+                identity: found.identity,
+                expires_ms: found.expires_ms,
+                credential: found.credential,
+            })
+        })
+        .collect()
+}
 
-        match file.kind {
-            CredFileKind::GeminiOAuth => {
-                return parse_gemini_oauth_creds(&content);
-            }
-            CredFileKind::GCloudADC => {
-                return parse_gcloud_adc(&content);
-            }
-            CredFileKind::AnthropicConfig => {
-                return parse_anthropic_config(&content);
-            }
-            CredFileKind::OpenAiAuth => {
-                return parse_openai_auth(&content);
-            }
-        }
-    }
-    None
+/// Try to sniff an external credential file for the given provider.
+pub fn sniff_external_credential(provider_id: &str) -> Option<Credential> {
+    sniff_credentials(None)
+        .into_iter()
+        .find(|s| s.provider == provider_id)
+        .map(|s| s.credential)
 }
 
 /// Sniff all external credential files and return found credentials.
 pub fn sniff_all_external_credentials() -> HashMap<String, Credential> {
     let mut found = HashMap::new();
-    for file in external_credential_paths() {
-        if !file.path.exists() {
-            continue;
-        }
-        let content = match std::fs::read_to_string(&file.path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-
-        let cred = match file.kind {
-            CredFileKind::GeminiOAuth => parse_gemini_oauth_creds(&content),
-            CredFileKind::GCloudADC => parse_gcloud_adc(&content),
-            CredFileKind::AnthropicConfig => parse_anthropic_config(&content),
-            CredFileKind::OpenAiAuth => parse_openai_auth(&content),
-        };
-
-        if let Some(c) = cred {
-            found.insert(file.provider.clone(), c);
-        }
+    for s in sniff_credentials(None) {
+        found.entry(s.provider).or_insert(s.credential);
     }
     found
 }
 
+fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
 // ---------------------------------------------------------------------------
 // File parsers
 // ---------------------------------------------------------------------------
 
+/// Claims of a JWT (unverified), e.g. for the email in an OpenID `id_token`.
+fn jwt_claims(token: &str) -> Option<serde_json::Value> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn jwt_email(token: &str) -> Option<String> {
+    jwt_claims(token)?.get("email")?.as_str().map(String::from)
+}
+
+/// The last four characters of a key, enough to tell keys apart.
+fn key_identity(key: &str) -> String {
+    let tail: String = key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("key …{}", tail)
+}
+
+fn api_key_found(key: String) -> Option<Found> {
+    if key.is_empty() {
+        return None;
+    }
+    Some(Found {
+        identity: Some(key_identity(&key)),
+        credential: Credential::ApiKey(ApiKeyCredential { key }),
+        expires_ms: None,
+    })
+}
+
+/// Parse ~/.claude/.credentials.json (Claude Code's subscription login)
+fn parse_claude_code_creds(content: &str) -> Option<Found> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClaudeAiOAuth {
+        access_token: Option<String>,
+        refresh_token: Option<String>,
+        expires_at: Option<i64>,
+        subscription_type: Option<String>,
+    }
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClaudeCodeCreds {
+        claude_ai_oauth: Option<ClaudeAiOAuth>,
+    }
+
+    let creds = serde_json::from_str::<ClaudeCodeCreds>(content).ok()?.claude_ai_oauth?;
+    Some(Found {
+        credential: Credential::OAuth(OAuthCredential {
+            refresh: creds.refresh_token?,
+            access: creds.access_token.unwrap_or_default(),
+            expires: creds.expires_at.unwrap_or(0),
+            extra: HashMap::new(),
+        }),
+        identity: creds.subscription_type.map(|t| format!("{} plan", t)),
+        expires_ms: creds.expires_at,
+    })
+}
+
 /// Parse ~/.gemini/oauth_creds.json
-fn parse_gemini_oauth_creds(content: &str) -> Option<Credential> {
+fn parse_gemini_oauth_creds(content: &str) -> Option<Found> {
     #[derive(serde::Deserialize)]
-    #[allow(dead_code)]
     struct GeminiOAuth {
         refresh_token: Option<String>,
         access_token: Option<String>,
+        /// RFC 3339 (older files)
         #[serde(default)]
         expiry: Option<String>,
-        client_id: Option<String>,
-        client_secret: Option<String>,
+        /// Unix ms (current Gemini CLI)
+        #[serde(default)]
+        expiry_date: Option<i64>,
+        #[serde(default)]
+        id_token: Option<String>,
     }
 
     let creds: GeminiOAuth = serde_json::from_str(content).ok()?;
     let refresh = creds.refresh_token?;
     let access = creds.access_token.unwrap_or_default();
-
-    // Parse expiry
-    let expires = creds
-        .expiry
-        .and_then(|e| {
-            chrono::DateTime::parse_from_rfc3339(&e)
-                .ok()
-                .map(|dt| dt.timestamp_millis())
-        })
-        .unwrap_or(0);
-
-    Some(Credential::OAuth(OAuthCredential {
-        refresh,
-        access,
-        expires,
-        extra: HashMap::new(),
-    }))
+    let expires_ms = creds.expiry_date.or_else(|| {
+        creds
+            .expiry
+            .and_then(|e| chrono::DateTime::parse_from_rfc3339(&e).ok().map(|dt| dt.timestamp_millis()))
+    });
+
+    Some(Found {
+        credential: Credential::OAuth(OAuthCredential {
+            refresh,
+            access,
+            expires: expires_ms.unwrap_or(0),
+            extra: HashMap::new(),
+        }),
+        identity: creds.id_token.as_deref().and_then(jwt_email),
+        expires_ms,
+    })
 }
 
 /// Parse ~/.config/gcloud/application_default_credentials.json
-fn parse_gcloud_adc(content: &str) -> Option<Credential> {
+fn parse_gcloud_adc(content: &str) -> Option<Found> {
     #[derive(serde::Deserialize)]
-    #[allow(dead_code)]
-    struct ADC {
-        client_id: Option<String>,
-        client_secret: Option<String>,
+    struct Adc {
         refresh_token: Option<String>,
-        #[serde(rename = "type")]
-        cred_type: Option<String>,
+        #[serde(default)]
+        account: Option<String>,
+        #[serde(default)]
+        quota_project_id: Option<String>,
     }
 
-    let creds: ADC = serde_json::from_str(content).ok()?;
+    let creds: Adc = serde_json::from_str(content).ok()?;
     let refresh = creds.refresh_token?;
-
-    Some(Credential::OAuth(OAuthCredential {
-        refresh,
-        access: String::new(),
-        expires: 0,
-        extra: HashMap::new(),
-    }))
+    let identity = creds
+        .account
+        .filter(|a| !a.is_empty())
+        .or_else(|| creds.quota_project_id.map(|p| format!("project {}", p)));
+
+    Some(Found {
+        credential: Credential::OAuth(OAuthCredential {
+            refresh,
+            access: String::new(),
+            expires: 0,
+            extra: HashMap::new(),
+        }),
+        identity,
+        expires_ms: None,
+    })
 }
 
 /// Parse ~/.anthropic/config.json
-fn parse_anthropic_config(content: &str) -> Option<Credential> {
+fn parse_anthropic_config(content: &str) -> Option<Found> {
     #[derive(serde::Deserialize)]
     struct AnthropicConfig {
         api_key: Option<String>,
@@ -414,31 +637,62 @@ fn parse_anthropic_config(content: &str) -> Option<Credential> {
     }
 
     let config: AnthropicConfig = serde_json::from_str(content).ok()?;
+    config
+        .api_key
+        .filter(|k| !k.is_empty())
+        .or(config.oauth_token)
+        .and_then(api_key_found)
+}
 
-    if let Some(key) = config.api_key {
-        if !key.is_empty() {
-            return Some(Credential::ApiKey(ApiKeyCredential { key }));
-        }
+/// The ChatGPT login in ~/.codex/auth.json
+fn parse_codex_tokens(content: &str) -> Option<Found> {
+    #[derive(serde::Deserialize)]
+    struct CodexTokens {
+        id_token: Option<String>,
+        access_token: Option<String>,
+        refresh_token: Option<String>,
+        account_id: Option<String>,
     }
-    if let Some(token) = config.oauth_token {
-        if !token.is_empty() {
-            return Some(Credential::ApiKey(ApiKeyCredential { key: token }));
-        }
+    #[derive(serde::Deserialize)]
+    struct CodexAuth {
+        tokens: Option<CodexTokens>,
     }
-    None
+
+    let tokens = serde_json::from_str::<CodexAuth>(content).ok()?.tokens?;
+    let access = tokens.access_token.unwrap_or_default();
+    let expires_ms = jwt_claims(&access)
+        .and_then(|c| c.get("exp")?.as_i64())
+        .map(|exp| exp * 1000);
+    Some(Found {
+        credential: Credential::OAuth(OAuthCredential {
+            refresh: tokens.refresh_token?,
+            access,
+            expires: expires_ms.unwrap_or(0),
+            extra: HashMap::new(),
+        }),
+        identity: tokens.id_token.as_deref().and_then(jwt_email).or(tokens.account_id),
+        expires_ms,
+    })
+}
+
+/// The API key in ~/.codex/auth.json
+fn parse_codex_api_key(content: &str) -> Option<Found> {
+    #[derive(serde::Deserialize)]
+    struct CodexAuth {
+        #[serde(rename = "OPENAI_API_KEY")]
+        openai_api_key: Option<String>,
+    }
+
+    api_key_found(serde_json::from_str::<CodexAuth>(content).ok()?.openai_api_key?)
 }
 
 /// Parse ~/.openai/auth.json
-fn parse_openai_auth(content: &str) -> Option<Credential> {
+fn parse_openai_auth(content: &str) -> Option<Found> {
     #[derive(serde::Deserialize)]
     struct OpenAiAuth {
         api_key: Option<String>,
     }
 
     let auth: OpenAiAuth = serde_json::from_str(content).ok()?;
-    let key = auth.api_key?;
-    if key.is_empty() {
-        return None;
-    }
-    Some(Credential::ApiKey(ApiKeyCredential { key }))
+    api_key_found(auth.api_key?)
 }
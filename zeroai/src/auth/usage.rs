@@ -0,0 +1,652 @@
+//! Per-account usage bookkeeping, kept in the shared [`Store`].
+//!
+//! The proxy records every completed request against the account that served it; the config
+//! TUI reads it to show last-used time and rolling token usage, and the proxy sums the
+//! spend to enforce the daily budget. Requests that name their client application are also
+//! counted per app, and requests naming an end user per user; requests in an A/B experiment
+//! also per experiment variant. Usage is kept in hourly buckets
+//! and pruned to the rolling window on every write. Accounts with a provider-reported monthly
+//! allowance (GitHub Copilot premium requests) also keep the latest quota snapshot.
+//!
+//! Older versions kept all of this in `~/.zeroai/usage.json`; [`import_legacy_file`] moves
+//! such a file into the store.
+
+#[cfg(feature = "store")]
+use crate::store::Store;
+#[cfg(feature = "store")]
+use crate::types::Usage;
+#[cfg(feature = "store")]
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "store")]
+use std::collections::HashMap;
+#[cfg(feature = "store")]
+use std::fs;
+#[cfg(feature = "store")]
+use std::path::Path;
+#[cfg(feature = "store")]
+use std::sync::Arc;
+
+/// Usage older than this is dropped.
+pub const ROLLING_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+
+/// Latency percentiles cover this much recent history; older samples are dropped.
+pub const LATENCY_WINDOW_MS: i64 = HOUR_MS;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageBucket {
+    /// Start of the hour (unix ms).
+    pub hour_ms: i64,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Spend in USD, from the model's price at the time of the request.
+    #[serde(default)]
+    pub cost_usd: f64,
+    /// Streamed requests with a measured time to first token, and the sum of those times.
+    #[serde(default)]
+    pub ttft_requests: u64,
+    #[serde(default)]
+    pub ttft_ms_total: u64,
+    /// Time spent generating and the output tokens generated in it, over requests with timing
+    /// (see `Usage::output_tokens_per_sec`).
+    #[serde(default)]
+    pub generation_ms: u64,
+    #[serde(default)]
+    pub generated_tokens: u64,
+}
+
+impl UsageBucket {
+    /// Mean time to first token of streamed requests.
+    pub fn avg_ttft_ms(&self) -> Option<f64> {
+        (self.ttft_requests > 0).then(|| self.ttft_ms_total as f64 / self.ttft_requests as f64)
+    }
+
+    /// Output tokens per second of generation, over requests with timing.
+    pub fn output_tokens_per_sec(&self) -> Option<f64> {
+        (self.generation_ms > 0).then(|| self.generated_tokens as f64 * 1000.0 / self.generation_ms as f64)
+    }
+}
+
+/// A monthly request allowance as last reported by the provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestQuota {
+    /// Requests included per period; `None` when unlimited.
+    pub entitlement: Option<u64>,
+    pub remaining_percent: f64,
+    /// Whether requests beyond the allowance are billed rather than refused.
+    pub overage_permitted: bool,
+    /// When the allowance renews (unix ms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at_ms: Option<i64>,
+    /// When this snapshot was taken (unix ms).
+    pub updated_ms: i64,
+}
+
+impl RequestQuota {
+    /// Requests used so far this period, as of the snapshot.
+    pub fn used(&self) -> Option<u64> {
+        let entitlement = self.entitlement?;
+        let used = entitlement as f64 * (1.0 - self.remaining_percent.clamp(0.0, 100.0) / 100.0);
+        Some(used.round() as u64)
+    }
+
+    /// True if further requests would be refused until `resets_at_ms`. A snapshot from a
+    /// period that has since renewed is never exhausted.
+    pub fn is_exhausted_at(&self, now_ms: i64) -> bool {
+        self.entitlement.is_some()
+            && self.remaining_percent <= 0.0
+            && !self.overage_permitted
+            && self.resets_at_ms.is_none_or(|t| t > now_ms)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountUsage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_ms: Option<i64>,
+    #[serde(default)]
+    pub hourly: Vec<UsageBucket>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<RequestQuota>,
+}
+
+impl AccountUsage {
+    /// Totals over the rolling window ending at `now_ms`.
+    pub fn rolling_totals(&self, now_ms: i64) -> UsageBucket {
+        let since = now_ms - ROLLING_WINDOW_MS;
+        self.hourly
+            .iter()
+            .filter(|b| b.hour_ms + HOUR_MS > since)
+            .fold(UsageBucket::default(), |mut acc, b| {
+                acc.requests += b.requests;
+                acc.input_tokens += b.input_tokens;
+                acc.output_tokens += b.output_tokens;
+                acc.cost_usd += b.cost_usd;
+                acc.ttft_requests += b.ttft_requests;
+                acc.ttft_ms_total += b.ttft_ms_total;
+                acc.generation_ms += b.generation_ms;
+                acc.generated_tokens += b.generated_tokens;
+                acc
+            })
+    }
+}
+
+/// Rolling time to first token of one model's streamed responses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+#[cfg(feature = "store")]
+impl LatencyStats {
+    fn from_samples(mut samples: Vec<u64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        // Nearest-rank percentile.
+        let at = |p: f64| samples[((p * samples.len() as f64).ceil() as usize).clamp(1, samples.len()) - 1];
+        Some(Self {
+            samples: samples.len(),
+            p50_ms: at(0.5),
+            p95_ms: at(0.95),
+        })
+    }
+}
+
+/// Who sent a request, for usage per client app and per end user, and the experiment variant
+/// (`<experiment>/<variant>`) that served it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestSource<'a> {
+    pub app: Option<&'a str>,
+    pub user: Option<&'a str>,
+    pub variant: Option<&'a str>,
+}
+
+/// What a usage row is counted against.
+#[cfg(feature = "store")]
+#[derive(Debug, Clone, Copy)]
+enum Scope {
+    /// `<provider>/<account_id>`
+    Account,
+    App,
+    User,
+    /// `<experiment>/<variant>`
+    Variant,
+}
+
+#[cfg(feature = "store")]
+impl Scope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::Account => "account",
+            Scope::App => "app",
+            Scope::User => "user",
+            Scope::Variant => "variant",
+        }
+    }
+}
+
+#[cfg(feature = "store")]
+fn key(provider_id: &str, account_id: &str) -> String {
+    format!("{}/{}", provider_id, account_id)
+}
+
+#[cfg(feature = "store")]
+#[derive(Clone)]
+pub struct UsageStore {
+    store: Arc<Store>,
+}
+
+#[cfg(feature = "store")]
+impl UsageStore {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// All accounts' usage, keyed by `<provider>/<account_id>`.
+    pub fn load(&self) -> anyhow::Result<HashMap<String, AccountUsage>> {
+        self.load_scope(Scope::Account)
+    }
+
+    /// Usage per client application, keyed by app name.
+    pub fn load_apps(&self) -> anyhow::Result<HashMap<String, AccountUsage>> {
+        self.load_scope(Scope::App)
+    }
+
+    /// Usage per end user named in requests (OpenAI `user`), keyed by user.
+    pub fn load_users(&self) -> anyhow::Result<HashMap<String, AccountUsage>> {
+        self.load_scope(Scope::User)
+    }
+
+    /// Usage per A/B experiment variant, keyed by `<experiment>/<variant>`.
+    pub fn load_variants(&self) -> anyhow::Result<HashMap<String, AccountUsage>> {
+        self.load_scope(Scope::Variant)
+    }
+
+    fn load_scope(&self, scope: Scope) -> anyhow::Result<HashMap<String, AccountUsage>> {
+        let (subjects, hours) = self.store.with_conn(|conn| {
+            let subjects = conn
+                .prepare("SELECT name, last_used_ms, quota FROM usage_subjects WHERE scope = ?1")?
+                .query_map(params![scope.as_str()], |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, Option<i64>>(1)?, r.get::<_, Option<String>>(2)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let hours = conn
+                .prepare(
+                    "SELECT name, hour_ms, requests, input_tokens, output_tokens, cost_usd,
+                         ttft_requests, ttft_ms_total, generation_ms, generated_tokens
+                     FROM usage_hours WHERE scope = ?1 ORDER BY hour_ms",
+                )?
+                .query_map(params![scope.as_str()], |r| {
+                    Ok((
+                        r.get::<_, String>(0)?,
+                        UsageBucket {
+                            hour_ms: r.get(1)?,
+                            requests: r.get(2)?,
+                            input_tokens: r.get(3)?,
+                            output_tokens: r.get(4)?,
+                            cost_usd: r.get(5)?,
+                            ttft_requests: r.get(6)?,
+                            ttft_ms_total: r.get(7)?,
+                            generation_ms: r.get(8)?,
+                            generated_tokens: r.get(9)?,
+                        },
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok((subjects, hours))
+        })?;
+        let mut all = HashMap::new();
+        for (name, last_used_ms, quota) in subjects {
+            let quota = quota.map(|q| serde_json::from_str(&q)).transpose()?;
+            all.insert(name, AccountUsage { last_used_ms, hourly: Vec::new(), quota });
+        }
+        for (name, bucket) in hours {
+            all.entry(name).or_insert_with(AccountUsage::default).hourly.push(bucket);
+        }
+        Ok(all)
+    }
+
+    pub fn get(&self, provider_id: &str, account_id: &str) -> anyhow::Result<Option<AccountUsage>> {
+        Ok(self.load()?.remove(&key(provider_id, account_id)))
+    }
+
+    /// Spend in USD over the rolling window ending at `now_ms`, across all accounts.
+    pub fn rolling_cost(&self, now_ms: i64) -> anyhow::Result<f64> {
+        self.store.with_conn(|conn| {
+            conn.query_row(
+                "SELECT COALESCE(SUM(cost_usd), 0) FROM usage_hours WHERE scope = ?1 AND hour_ms + ?2 > ?3",
+                params![Scope::Account.as_str(), HOUR_MS, now_ms - ROLLING_WINDOW_MS],
+                |r| r.get(0),
+            )
+        })
+    }
+
+    /// Record one completed request (with its token usage, if the provider reported it,
+    /// and what it cost) against the account and, if known, the app and user that sent it and
+    /// the experiment variant that served it.
+    pub fn record(
+        &self,
+        provider_id: &str,
+        account_id: &str,
+        source: RequestSource<'_>,
+        usage: Option<&Usage>,
+        cost_usd: f64,
+        now_ms: i64,
+    ) -> anyhow::Result<()> {
+        let account = key(provider_id, account_id);
+        let subjects = [
+            (Scope::Account, Some(account.as_str())),
+            (Scope::App, source.app),
+            (Scope::User, source.user),
+            (Scope::Variant, source.variant),
+        ];
+        let (input_tokens, output_tokens) = usage.map_or((0, 0), |u| (u.input_tokens, u.output_tokens));
+        let ttft_ms = usage.and_then(|u| u.ttft_ms);
+        let generation = usage
+            .and_then(|u| Some((u.duration_ms?.saturating_sub(u.ttft_ms.unwrap_or(0)), u.output_tokens)))
+            .filter(|&(ms, tokens)| ms > 0 && tokens > 0)
+            .unwrap_or((0, 0));
+        let hour_ms = now_ms - now_ms.rem_euclid(HOUR_MS);
+        self.store.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for (scope, name) in subjects {
+                let Some(name) = name else { continue };
+                tx.execute(
+                    "INSERT INTO usage_subjects (scope, name, last_used_ms) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (scope, name) DO UPDATE SET last_used_ms = excluded.last_used_ms",
+                    params![scope.as_str(), name, now_ms],
+                )?;
+                tx.execute(
+                    "INSERT INTO usage_hours (scope, name, hour_ms, requests, input_tokens, output_tokens, cost_usd,
+                                              ttft_requests, ttft_ms_total, generation_ms, generated_tokens)
+                     VALUES (?1, ?2, ?3, 1, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT (scope, name, hour_ms) DO UPDATE SET
+                         requests = requests + 1,
+                         input_tokens = input_tokens + excluded.input_tokens,
+                         output_tokens = output_tokens + excluded.output_tokens,
+                         cost_usd = cost_usd + excluded.cost_usd,
+                         ttft_requests = ttft_requests + excluded.ttft_requests,
+                         ttft_ms_total = ttft_ms_total + excluded.ttft_ms_total,
+                         generation_ms = generation_ms + excluded.generation_ms,
+                         generated_tokens = generated_tokens + excluded.generated_tokens",
+                    params![
+                        scope.as_str(),
+                        name,
+                        hour_ms,
+                        input_tokens,
+                        output_tokens,
+                        cost_usd,
+                        ttft_ms.is_some() as u64,
+                        ttft_ms.unwrap_or(0),
+                        generation.0,
+                        generation.1,
+                    ],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM usage_hours WHERE hour_ms + ?1 <= ?2",
+                params![HOUR_MS, now_ms - ROLLING_WINDOW_MS],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Record how long a streamed response from `model` (a full `provider/model` ID) took to
+    /// start. Responses without a time to first token are skipped: the whole-response time of
+    /// a non-streamed one isn't comparable.
+    pub fn record_latency(&self, model: &str, usage: &Usage, now_ms: i64) -> anyhow::Result<()> {
+        let Some(latency_ms) = usage.ttft_ms else {
+            return Ok(());
+        };
+        self.store.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO latency_samples (model, at_ms, latency_ms) VALUES (?1, ?2, ?3)",
+                params![model, now_ms, latency_ms],
+            )?;
+            tx.execute("DELETE FROM latency_samples WHERE at_ms <= ?1", params![now_ms - LATENCY_WINDOW_MS])?;
+            tx.commit()
+        })
+    }
+
+    /// p50/p95 latency per model over the [`LATENCY_WINDOW_MS`] ending at `now_ms`.
+    pub fn latency_stats(&self, now_ms: i64) -> anyhow::Result<HashMap<String, LatencyStats>> {
+        let rows = self.store.with_conn(|conn| {
+            conn.prepare("SELECT model, latency_ms FROM latency_samples WHERE at_ms > ?1")?
+                .query_map(params![now_ms - LATENCY_WINDOW_MS], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u64>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+        let mut samples: HashMap<String, Vec<u64>> = HashMap::new();
+        for (model, latency_ms) in rows {
+            samples.entry(model).or_default().push(latency_ms);
+        }
+        Ok(samples
+            .into_iter()
+            .filter_map(|(model, s)| Some((model, LatencyStats::from_samples(s)?)))
+            .collect())
+    }
+
+    /// Replace an account's quota snapshot.
+    pub fn set_quota(&self, provider_id: &str, account_id: &str, quota: RequestQuota) -> anyhow::Result<()> {
+        let quota = serde_json::to_string(&quota)?;
+        self.store.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO usage_subjects (scope, name, quota) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (scope, name) DO UPDATE SET quota = excluded.quota",
+                params![Scope::Account.as_str(), key(provider_id, account_id), quota],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+/// Layout of the `usage.json` written by older versions: account usage at the top level (keys
+/// always contain a `/`), per-app usage under `apps` and per-user usage under `users`.
+#[cfg(feature = "store")]
+#[derive(Debug, Default, Deserialize)]
+struct LegacyUsageFile {
+    #[serde(default)]
+    apps: HashMap<String, AccountUsage>,
+    #[serde(default)]
+    users: HashMap<String, AccountUsage>,
+    #[serde(flatten)]
+    accounts: HashMap<String, AccountUsage>,
+}
+
+/// Copy a `usage.json` from an older version into `store`, then rename it to
+/// `usage.json.imported` so it is only imported once. Returns whether there was a file.
+#[cfg(feature = "store")]
+pub fn import_legacy_file(store: &Store, path: &Path) -> anyhow::Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let file: LegacyUsageFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut subjects = Vec::new();
+    for (scope, usage) in [(Scope::Account, file.accounts), (Scope::App, file.apps), (Scope::User, file.users)] {
+        for (name, u) in usage {
+            let quota = u.quota.as_ref().map(serde_json::to_string).transpose()?;
+            subjects.push((scope, name, u, quota));
+        }
+    }
+    store.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for (scope, name, u, quota) in &subjects {
+            // Rows already in the store are newer than the file.
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO usage_subjects (scope, name, last_used_ms, quota) VALUES (?1, ?2, ?3, ?4)",
+                params![scope.as_str(), name, u.last_used_ms, quota],
+            )?;
+            if inserted == 0 {
+                continue;
+            }
+            for b in &u.hourly {
+                tx.execute(
+                    "INSERT OR IGNORE INTO usage_hours
+                     (scope, name, hour_ms, requests, input_tokens, output_tokens, cost_usd)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![scope.as_str(), name, b.hour_ms, b.requests, b.input_tokens, b.output_tokens, b.cost_usd],
+                )?;
+            }
+        }
+        tx.commit()
+    })?;
+    let mut imported = path.as_os_str().to_owned();
+    imported.push(".imported");
+    fs::rename(path, imported)?;
+    Ok(true)
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+
+    fn store() -> UsageStore {
+        UsageStore::new(Arc::new(Store::open_in_memory().unwrap()))
+    }
+
+    fn usage(input: u64, output: u64) -> Usage {
+        Usage {
+            input_tokens: input,
+            output_tokens: output,
+            ..Usage::default()
+        }
+    }
+
+    #[test]
+    fn records_into_hourly_buckets_and_totals() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        store.record("openai", "a1", RequestSource::default(), Some(&usage(10, 5)), 0.0, t0).unwrap();
+        store.record("openai", "a1", RequestSource::default(), Some(&usage(1, 1)), 0.0, t0 + 60_000).unwrap();
+        store.record("openai", "a1", RequestSource::default(), None, 0.0, t0 + 2 * HOUR_MS).unwrap();
+
+        let acc = store.get("openai", "a1").unwrap().unwrap();
+        assert_eq!(acc.last_used_ms, Some(t0 + 2 * HOUR_MS));
+        assert_eq!(acc.hourly.len(), 2);
+        let totals = acc.rolling_totals(t0 + 2 * HOUR_MS);
+        assert_eq!((totals.requests, totals.input_tokens, totals.output_tokens), (3, 11, 6));
+        assert!(store.get("openai", "a2").unwrap().is_none());
+    }
+
+    #[test]
+    fn records_per_app_alongside_accounts() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let aider = RequestSource { app: Some("aider"), ..Default::default() };
+        store.record("openai", "a1", aider, Some(&usage(10, 5)), 0.5, t0).unwrap();
+        store.record("openai", "a2", aider, Some(&usage(1, 1)), 0.25, t0).unwrap();
+        store.record("openai", "a1", RequestSource::default(), None, 0.0, t0).unwrap();
+
+        let apps = store.load_apps().unwrap();
+        assert_eq!(apps.len(), 1);
+        let totals = apps["aider"].rolling_totals(t0);
+        assert_eq!((totals.requests, totals.input_tokens, totals.output_tokens), (2, 11, 6));
+        assert!((totals.cost_usd - 0.75).abs() < 1e-9);
+
+        let accounts = store.load().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts["openai/a1"].rolling_totals(t0).requests, 2);
+        assert!((store.rolling_cost(t0).unwrap() - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn records_per_user() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let source = RequestSource { app: Some("chat-ui"), user: Some("user-42"), variant: Some("chat/candidate") };
+        store.record("openai", "a1", source, Some(&usage(3, 2)), 0.1, t0).unwrap();
+
+        let users = store.load_users().unwrap();
+        assert_eq!(users["user-42"].rolling_totals(t0).requests, 1);
+        assert_eq!(store.load_variants().unwrap()["chat/candidate"].rolling_totals(t0).requests, 1);
+        assert_eq!(store.load_apps().unwrap()["chat-ui"].rolling_totals(t0).requests, 1);
+        assert_eq!(store.load().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn timing_is_aggregated() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let timed = |ttft_ms, duration_ms, output_tokens| Usage {
+            output_tokens,
+            ttft_ms,
+            duration_ms: Some(duration_ms),
+            ..Usage::default()
+        };
+        store.record("openai", "a1", RequestSource::default(), Some(&timed(Some(200), 1200, 50)), 0.0, t0).unwrap();
+        store.record("openai", "a1", RequestSource::default(), Some(&timed(Some(400), 1400, 150)), 0.0, t0).unwrap();
+        store.record("openai", "a1", RequestSource::default(), Some(&timed(None, 1000, 100)), 0.0, t0).unwrap();
+        store.record("openai", "a1", RequestSource::default(), None, 0.0, t0).unwrap();
+
+        let totals = store.get("openai", "a1").unwrap().unwrap().rolling_totals(t0);
+        assert_eq!(totals.avg_ttft_ms(), Some(300.0));
+        assert_eq!(totals.output_tokens_per_sec(), Some(100.0));
+        assert_eq!(timed(Some(200), 1200, 50).output_tokens_per_sec(), Some(50.0));
+    }
+
+    #[test]
+    fn latency_percentiles_cover_the_window() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let timed = |ttft_ms, duration_ms| Usage {
+            ttft_ms,
+            duration_ms: Some(duration_ms),
+            ..Usage::default()
+        };
+        store.record_latency("openai/gpt", &timed(Some(5000), 9000), t0 - LATENCY_WINDOW_MS).unwrap();
+        for ms in 1..=20 {
+            store.record_latency("openai/gpt", &timed(Some(ms * 100), 9000), t0).unwrap();
+        }
+        store.record_latency("groq/llama", &timed(Some(300), 400), t0).unwrap();
+        // Non-streamed responses have no time to first token to compare.
+        store.record_latency("groq/llama", &timed(None, 100), t0).unwrap();
+        store.record_latency("groq/llama", &Usage::default(), t0).unwrap();
+
+        let stats = store.latency_stats(t0).unwrap();
+        assert_eq!(stats["openai/gpt"], LatencyStats { samples: 20, p50_ms: 1000, p95_ms: 1900 });
+        assert_eq!(stats["groq/llama"], LatencyStats { samples: 1, p50_ms: 300, p95_ms: 300 });
+    }
+
+    #[test]
+    fn quota_snapshot_is_kept_with_the_account() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let quota = RequestQuota {
+            entitlement: Some(300),
+            remaining_percent: 0.0,
+            overage_permitted: false,
+            resets_at_ms: Some(t0 + HOUR_MS),
+            updated_ms: t0,
+        };
+        store.record("github-copilot", "a1", RequestSource::default(), None, 0.0, t0).unwrap();
+        store.set_quota("github-copilot", "a1", quota.clone()).unwrap();
+        store.record("github-copilot", "a1", RequestSource::default(), None, 0.0, t0).unwrap();
+
+        let acc = store.get("github-copilot", "a1").unwrap().unwrap();
+        assert_eq!(acc.quota.as_ref(), Some(&quota));
+        assert_eq!(acc.rolling_totals(t0).requests, 2);
+        assert_eq!(quota.used(), Some(300));
+        assert!(quota.is_exhausted_at(t0));
+        assert!(!quota.is_exhausted_at(t0 + HOUR_MS));
+    }
+
+    #[test]
+    fn imports_legacy_files_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        fs::write(
+            &path,
+            r#"{"openai/a1":{"last_used_ms":1,"hourly":[{"hour_ms":0,"requests":2,"input_tokens":3,"output_tokens":4}]},
+                "apps":{"aider":{"hourly":[]}}}"#,
+        )
+        .unwrap();
+        let db = Arc::new(Store::open_in_memory().unwrap());
+        assert!(import_legacy_file(&db, &path).unwrap());
+        assert!(!path.exists());
+        assert!(!import_legacy_file(&db, &path).unwrap());
+
+        let store = UsageStore::new(db);
+        let acc = &store.load().unwrap()["openai/a1"];
+        assert_eq!(acc.last_used_ms, Some(1));
+        assert_eq!(acc.rolling_totals(0).requests, 2);
+        assert!(store.load_apps().unwrap().contains_key("aider"));
+        assert!(store.load_users().unwrap().is_empty());
+    }
+
+    #[test]
+    fn old_buckets_leave_the_window() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        store.record("openai", "a1", RequestSource::default(), Some(&usage(100, 100)), 0.0, t0).unwrap();
+        let later = t0 + ROLLING_WINDOW_MS + HOUR_MS;
+        assert_eq!(store.get("openai", "a1").unwrap().unwrap().rolling_totals(later).requests, 0);
+
+        store.record("openai", "a1", RequestSource::default(), None, 0.0, later).unwrap();
+        assert_eq!(store.get("openai", "a1").unwrap().unwrap().hourly.len(), 1);
+    }
+
+    #[test]
+    fn rolling_cost_sums_all_accounts() {
+        let store = store();
+        let t0 = 1_700_000_000_000;
+        let price = crate::types::ModelCost {
+            input: 3.0,
+            output: 15.0,
+            ..Default::default()
+        };
+        let u = usage(1_000_000, 100_000);
+        assert!((price.usage_cost(&u) - 4.5).abs() < 1e-9);
+
+        store.record("anthropic", "a1", RequestSource::default(), Some(&u), price.usage_cost(&u), t0).unwrap();
+        store.record("openai", "b1", RequestSource::default(), None, 0.25, t0 + HOUR_MS).unwrap();
+        assert!((store.rolling_cost(t0 + HOUR_MS).unwrap() - 4.75).abs() < 1e-9);
+        assert!((store.rolling_cost(t0 + ROLLING_WINDOW_MS + HOUR_MS).unwrap() - 0.25).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,315 @@
+//! Config lint for `config validate` and `serve` startup: settings that parse fine but won't
+//! work as intended, each with the command or edit that fixes it.
+
+use super::config::AppConfig;
+use super::{AuthMethod, all_provider_auth_info};
+use crate::models::is_custom_provider;
+use crate::providers::registry::ProviderRegistry;
+use crate::routing::{CHEAPEST_MODEL_ID, FASTEST_MODEL_ID};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Requests will fail or settings are ignored.
+    Error,
+    /// Works, but probably not as meant.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub message: String,
+    /// What to run or change.
+    pub fix: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}\n  fix: {}", level, self.message, self.fix)
+    }
+}
+
+/// Check `cfg` against the environment (API key variables, credentials of other tools).
+pub fn validate_config(cfg: &AppConfig) -> Vec<ConfigIssue> {
+    lint(cfg, |provider| {
+        super::sniff::env_api_key(provider).is_some()
+            || cfg.providers.iter().any(|d| d.id == provider && d.env_api_key().is_some())
+            || super::sniff::sniff_external_credential(provider).is_some()
+    })
+}
+
+/// Issues in `cfg`, errors first; `has_outside_key` tells whether a provider without accounts
+/// still gets a key from the environment.
+fn lint(cfg: &AppConfig, has_outside_key: impl Fn(&str) -> bool) -> Vec<ConfigIssue> {
+    let infos: HashMap<String, Vec<AuthMethod>> =
+        all_provider_auth_info().into_iter().map(|i| (i.provider_id, i.auth_methods)).collect();
+    let mut provider_ids: BTreeSet<String> = ProviderRegistry::builtin().ids().map(String::from).collect();
+    provider_ids.extend(infos.keys().cloned());
+    provider_ids.extend(cfg.providers.iter().map(|d| d.id.clone()));
+    let known = |p: &str| is_custom_provider(p) || provider_ids.contains(p);
+    let mut issues = Vec::new();
+
+    // Unknown provider IDs, wherever one can be named.
+    let mut referenced: BTreeSet<(&str, &str)> = BTreeSet::new();
+    for id in &cfg.enabled_models {
+        referenced.extend(crate::split_model_id(id).map(|(p, _)| (p, "enabled_models")));
+    }
+    for (key, place) in cfg
+        .pricing
+        .keys()
+        .map(|k| (k, "pricing"))
+        .chain(cfg.model_overrides.keys().map(|k| (k, "model_overrides")))
+        .chain(cfg.settings.model_aliases.values().map(|k| (k, "model_aliases")))
+    {
+        referenced.extend(crate::split_model_id(key).filter(|(p, _)| *p != "auto").map(|(p, _)| (p, place)));
+    }
+    referenced.extend(cfg.provider_accounts.keys().map(|p| (p.as_str(), "provider_accounts")));
+    referenced.extend(cfg.provider_models_url.keys().map(|p| (p.as_str(), "provider_models_url")));
+    referenced.extend(cfg.provider_maintenance.keys().map(|p| (p.as_str(), "provider_maintenance")));
+    referenced.extend(cfg.settings.bare_model_priority.iter().map(|p| (p.as_str(), "bare_model_priority")));
+    referenced.extend(cfg.settings.transformers.keys().filter(|p| *p != "*").map(|p| (p.as_str(), "transformers")));
+    for (provider, place) in referenced {
+        if known(provider) {
+            continue;
+        }
+        let fix = match closest(provider, provider_ids.iter().map(String::as_str)) {
+            Some(near) => format!("did you mean '{}'? Fix the name in {}", near, place),
+            None => format!(
+                "declare it under \"providers\" (or `zeroai-proxy config`, `n`), or remove it from {}",
+                place
+            ),
+        };
+        issues.push(ConfigIssue {
+            severity: Severity::Error,
+            message: format!("unknown provider '{}' in {}", provider, place),
+            fix,
+        });
+    }
+
+    // Enabled models whose provider has no usable key.
+    let mut enabled_by_provider: HashMap<&str, usize> = HashMap::new();
+    for id in &cfg.enabled_models {
+        if let Some((provider, _)) = crate::split_model_id(id) {
+            *enabled_by_provider.entry(provider).or_default() += 1;
+        }
+    }
+    let mut enabled_by_provider: Vec<(&str, usize)> = enabled_by_provider.into_iter().collect();
+    enabled_by_provider.sort();
+    for (provider, count) in enabled_by_provider {
+        if !known(provider) {
+            continue;
+        }
+        let accounts = cfg.provider_accounts.get(provider).map(|p| p.accounts.as_slice()).unwrap_or_default();
+        let models = if count == 1 { "1 enabled model".to_string() } else { format!("{} enabled models", count) };
+        if accounts.is_empty() {
+            if has_outside_key(provider) {
+                continue;
+            }
+            let methods = infos.get(provider).map(Vec::as_slice).unwrap_or_default();
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("{} has {} but no credentials", provider, models),
+                fix: format!("{}, or disable its models in `zeroai-proxy config`", login_hint(provider, methods)),
+            });
+        } else if accounts.iter().all(|a| !a.enabled) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("{} has {} but all its accounts are disabled", provider, models),
+                fix: format!("zeroai-proxy auth enable {} {}", provider, accounts[0].display_label()),
+            });
+        }
+    }
+
+    // Models URLs left behind by removed providers, or not URLs at all.
+    let mut urls: Vec<(&String, &String)> = cfg.provider_models_url.iter().collect();
+    urls.sort();
+    for (provider, url) in urls.into_iter().filter(|(_, u)| !u.trim().is_empty()) {
+        let in_use = cfg.provider_accounts.get(provider).is_some_and(|p| !p.accounts.is_empty())
+            || cfg.enabled_models.iter().any(|m| crate::split_model_id(m).is_some_and(|(p, _)| p == provider));
+        if !is_http_url(url) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("models URL of {} is not an http(s) URL: '{}'", provider, url),
+                fix: format!("set a full URL in provider_models_url.{}, or clear it to use {{base_url}}/v1/models", provider),
+            });
+        } else if !in_use && known(provider) {
+            issues.push(ConfigIssue {
+                severity: Severity::Warning,
+                message: format!("models URL of {} is stale: the provider has no accounts or enabled models", provider),
+                fix: format!("remove provider_models_url.{}", provider),
+            });
+        }
+    }
+    for def in &cfg.providers {
+        if let Some(url) = def.models_url.as_deref().filter(|u| !u.trim().is_empty() && !is_http_url(u)) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("models URL of {} is not an http(s) URL: '{}'", def.id, url),
+                fix: format!("set a full URL as models_url of {} in \"providers\", or remove it to use {{base_url}}/models", def.id),
+            });
+        }
+    }
+
+    // Aliases hiding other names, or pointing where nothing is served.
+    let settings = &cfg.settings;
+    let bare = settings.bare_model_ids(cfg.enabled_models.iter().map(String::as_str));
+    let mut aliases: Vec<(&String, &String)> = settings.model_aliases.iter().collect();
+    aliases.sort();
+    for (alias, target) in aliases {
+        let hidden = if cfg.enabled_models.contains(alias) {
+            Some(format!("the enabled model {}", alias))
+        } else if alias == CHEAPEST_MODEL_ID || alias == FASTEST_MODEL_ID {
+            Some(format!("the {} routing model", alias))
+        } else {
+            bare.get(alias.as_str()).map(|full| format!("the bare name of {}", full))
+        };
+        if let Some(hidden) = hidden {
+            issues.push(ConfigIssue {
+                severity: Severity::Warning,
+                message: format!("alias '{}' hides {}", alias, hidden),
+                fix: format!("rename the alias '{}' in model_aliases", alias),
+            });
+        }
+        if let Some(next) = settings.model_aliases.get(target) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("alias '{}' points to another alias ('{}'); aliases aren't followed twice", alias, target),
+                fix: format!("point '{}' at {} directly", alias, next),
+            });
+        } else if target != CHEAPEST_MODEL_ID && target != FASTEST_MODEL_ID && !cfg.enabled_models.contains(target) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                message: format!("alias '{}' points to {}, which is not an enabled model", alias, target),
+                fix: format!("enable {} in `zeroai-proxy config`, or point the alias at an enabled model", target),
+            });
+        }
+        if let Some(experiment) = settings.experiments.iter().find(|e| e.model == *alias) {
+            issues.push(ConfigIssue {
+                severity: Severity::Warning,
+                message: format!("alias '{}' is also the model of experiment '{}', which takes its requests", alias, experiment.name),
+                fix: format!("remove the alias '{}' or change the experiment's model", alias),
+            });
+        }
+    }
+
+    issues.sort_by_key(|i| i.severity);
+    issues
+}
+
+fn is_http_url(url: &str) -> bool {
+    let url = url.trim();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// How to add a key or log in for `provider`.
+fn login_hint(provider: &str, methods: &[AuthMethod]) -> String {
+    let env = methods.iter().find_map(|m| match m {
+        AuthMethod::ApiKey { env_var, .. } => env_var.clone(),
+        _ => None,
+    });
+    if methods.iter().any(|m| matches!(m, AuthMethod::ApiKey { .. })) || methods.is_empty() {
+        let cmd = format!("add a key with `zeroai-proxy config` or `echo $KEY | zeroai-proxy auth import-keys {} -`", provider);
+        match env {
+            Some(var) => format!("{}, or set {}", cmd, var),
+            None => cmd,
+        }
+    } else {
+        format!("run `zeroai-proxy auth login {}`", provider)
+    }
+}
+
+/// The known ID within two edits of `name`, if any.
+fn closest<'a>(name: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    known
+        .map(|k| (edit_distance(name, k), k))
+        .filter(|&(d, _)| d <= 2)
+        .min()
+        .map(|(_, k)| k)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev + usize::from(ca != cb);
+            prev = row[j + 1];
+            row[j + 1] = substitute.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> AppConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn messages(issues: &[ConfigIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.message.as_str()).collect()
+    }
+
+    #[test]
+    fn flags_missing_credentials_and_unknown_providers() {
+        let cfg = config(serde_json::json!({
+            "enabled_models": ["openai/gpt-4o", "anthropic/claude-sonnet-4-5", "opnai/gpt-4o"],
+            "provider_accounts": {"anthropic": {"accounts": [{
+                "id": "a", "credential": {"type": "api_key", "key": "sk"}, "enabled": false
+            }]}},
+            "provider_models_url": {"groq": "https://api.groq.com/openai/v1/models", "openai": "api.openai.com"}
+        }));
+        let issues = lint(&cfg, |_| false);
+        assert_eq!(
+            messages(&issues),
+            [
+                "unknown provider 'opnai' in enabled_models",
+                "anthropic has 1 enabled model but all its accounts are disabled",
+                "openai has 1 enabled model but no credentials",
+                "models URL of openai is not an http(s) URL: 'api.openai.com'",
+                "models URL of groq is stale: the provider has no accounts or enabled models",
+            ]
+        );
+        assert!(issues[0].fix.contains("did you mean 'openai'"));
+        assert!(issues[2].fix.contains("OPENAI_API_KEY"));
+        // A key from the environment is enough.
+        assert!(!messages(&lint(&cfg, |p| p == "openai")).iter().any(|m| m.contains("no credentials")));
+    }
+
+    #[test]
+    fn flags_overlapping_aliases() {
+        let cfg = config(serde_json::json!({
+            "enabled_models": ["openai/gpt-4o", "groq/llama-3.3-70b-versatile"],
+            "settings": {
+                "expose_bare_model_ids": true,
+                "model_aliases": {
+                    "gpt-4o": "groq/llama-3.3-70b-versatile",
+                    "fast": "auto/fastest",
+                    "default": "fast",
+                    "openai/gpt-4o": "openai/gpt-4o-mini"
+                }
+            }
+        }));
+        let issues = lint(&cfg, |_| true);
+        assert_eq!(
+            messages(&issues),
+            [
+                "alias 'default' points to another alias ('fast'); aliases aren't followed twice",
+                "alias 'openai/gpt-4o' points to openai/gpt-4o-mini, which is not an enabled model",
+                "alias 'gpt-4o' hides the bare name of openai/gpt-4o",
+                "alias 'openai/gpt-4o' hides the enabled model openai/gpt-4o",
+            ]
+        );
+        assert_eq!(issues[0].fix, "point 'default' at auto/fastest directly");
+    }
+}
@@ -0,0 +1,151 @@
+//! Per-account key checks for `auth verify`: one minimal request with the key (a models list
+//! where the provider has one, otherwise a one-line completion), plus the remaining credit or
+//! the organization when the provider's API tells.
+
+use crate::client::AiClient;
+use crate::models::{fetch_models_for_provider, is_custom_provider, static_models_for_provider, supports_dynamic_models};
+use crate::providers::ProviderError;
+use crate::types::{ChatContext, ContentBlock, Message, ModelDef, RequestOptions, TextContent, UserMessage};
+
+const OPENROUTER_KEY_URL: &str = "https://openrouter.ai/api/v1/key";
+const OPENAI_ME_URL: &str = "https://api.openai.com/v1/me";
+
+/// Output limit of the completion used to test a key.
+const CHECK_MAX_TOKENS: u64 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyStatus {
+    Valid,
+    /// The provider rejected the key (unauthorized, forbidden, out of credit, expired login).
+    Invalid(String),
+    /// The check couldn't tell: network error, rate limit, no model to test with.
+    Unknown(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCheck {
+    pub status: KeyStatus,
+    /// Remaining credit or the key's organization, e.g. "$4.20 of $10.00 left".
+    pub quota: Option<String>,
+}
+
+/// Check `api_key` against `provider`. `enabled_models` (full `<provider>/<model>` IDs) picks
+/// the model for providers without a models list.
+pub async fn check_key(
+    provider: &str,
+    api_key: &str,
+    models_url: Option<&str>,
+    enabled_models: &[String],
+) -> KeyCheck {
+    let status = if is_custom_provider(provider) || supports_dynamic_models(provider) {
+        match fetch_models_for_provider(provider, Some(api_key), models_url).await {
+            Ok(_) => KeyStatus::Valid,
+            Err(e) if e.is_auth_error() || e.status == Some(402) => KeyStatus::Invalid(e.to_string()),
+            Err(e) => KeyStatus::Unknown(e.to_string()),
+        }
+    } else {
+        match test_model(provider, enabled_models) {
+            Some((full_id, def)) => check_with_completion(&full_id, def, api_key).await,
+            None => KeyStatus::Unknown("no model to test the key with".into()),
+        }
+    };
+    let quota = match status {
+        KeyStatus::Valid => fetch_quota(provider, api_key).await,
+        _ => None,
+    };
+    KeyCheck { status, quota }
+}
+
+/// The provider's first enabled model, else its first known one.
+fn test_model(provider: &str, enabled_models: &[String]) -> Option<(String, ModelDef)> {
+    let models = static_models_for_provider(provider);
+    let enabled = enabled_models.iter().find_map(|full_id| {
+        let (p, model_id) = crate::split_model_id(full_id)?;
+        models.iter().find(|m| p == provider && m.id == model_id).cloned()
+    });
+    let def = enabled.or_else(|| models.into_iter().next())?;
+    Some((format!("{}/{}", provider, def.id), def))
+}
+
+async fn check_with_completion(full_id: &str, def: ModelDef, api_key: &str) -> KeyStatus {
+    let client = AiClient::builder().with_models(vec![(full_id.to_string(), def)]).build();
+    let context = ChatContext {
+        system_prompt: None,
+        messages: vec![Message::User(UserMessage {
+            content: vec![ContentBlock::Text(TextContent { text: "Reply with the word OK.".into() })],
+        })],
+        tools: Vec::new(),
+    };
+    let options = RequestOptions {
+        max_tokens: Some(CHECK_MAX_TOKENS),
+        api_key: Some(api_key.to_string()),
+        ..Default::default()
+    };
+    match client.chat(full_id, &context, &options).await {
+        Ok(_) => KeyStatus::Valid,
+        Err(e) => classify_error(&e),
+    }
+}
+
+/// Whether a failed request says the key is bad, or nothing about it.
+fn classify_error(e: &ProviderError) -> KeyStatus {
+    match e {
+        ProviderError::Http { status: status @ 401..=403, error } => {
+            KeyStatus::Invalid(format!("{} {}", status, error))
+        }
+        ProviderError::AuthRequired(message) => KeyStatus::Invalid(message.clone()),
+        e => KeyStatus::Unknown(e.to_string()),
+    }
+}
+
+/// Remaining credit (OpenRouter) or organization (OpenAI) of a working key.
+async fn fetch_quota(provider: &str, api_key: &str) -> Option<String> {
+    match provider {
+        "openrouter" => crate::oauth::get_json(OPENROUTER_KEY_URL, api_key, &[])
+            .await
+            .ok()
+            .and_then(|key| openrouter_quota(&key)),
+        "openai" => crate::oauth::get_json(OPENAI_ME_URL, api_key, &[])
+            .await
+            .ok()
+            .and_then(|me| crate::oauth::openai_identity(&me).org)
+            .map(|org| format!("org {}", org)),
+        _ => None,
+    }
+}
+
+/// "$4.20 of $10.00 left" from OpenRouter's `/key`, or the spend so far for keys without a limit.
+fn openrouter_quota(key: &serde_json::Value) -> Option<String> {
+    let data = key.get("data")?;
+    let usage = data.get("usage").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    match data.get("limit").and_then(|v| v.as_f64()) {
+        Some(limit) => {
+            let remaining = data.get("limit_remaining").and_then(|v| v.as_f64()).unwrap_or(limit - usage);
+            Some(format!("${:.2} of ${:.2} left", remaining.max(0.0), limit))
+        }
+        None => Some(format!("${:.2} spent, no limit", usage)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::api_error::ApiError;
+
+    #[test]
+    fn reads_openrouter_credit() {
+        let limited = serde_json::json!({"data": {"usage": 5.8, "limit": 10, "limit_remaining": 4.2}});
+        assert_eq!(openrouter_quota(&limited).as_deref(), Some("$4.20 of $10.00 left"));
+        let unlimited = serde_json::json!({"data": {"usage": 1.5, "limit": null}});
+        assert_eq!(openrouter_quota(&unlimited).as_deref(), Some("$1.50 spent, no limit"));
+    }
+
+    #[test]
+    fn only_auth_and_credit_errors_mark_keys_invalid() {
+        let http = |status| ProviderError::Http { status, error: Box::new(ApiError::parse("denied")) };
+        assert!(matches!(classify_error(&http(401)), KeyStatus::Invalid(_)));
+        assert!(matches!(classify_error(&http(402)), KeyStatus::Invalid(_)));
+        assert!(matches!(classify_error(&http(500)), KeyStatus::Unknown(_)));
+        assert!(matches!(classify_error(&ProviderError::RateLimited { retry_after_ms: None }), KeyStatus::Unknown(_)));
+    }
+}
@@ -0,0 +1,126 @@
+//! Chat requests and stream events as JSON, shared by the language bindings (`zeroai-py`,
+//! `zeroai-ffi`) so they all accept and emit the same shapes.
+
+use crate::auth::config::ConfigManager;
+use crate::mapper::split_model_id;
+use crate::types::*;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// One chat call. `messages` and `tools` take the JSON shapes of [`Message`] and [`ToolDef`];
+/// a message's `content` may also be a plain string.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Value>,
+    pub system: Option<String>,
+    pub tools: Option<Vec<ToolDef>>,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub reasoning: Option<ThinkingLevel>,
+}
+
+impl ChatRequest {
+    pub fn context(&self) -> anyhow::Result<ChatContext> {
+        let messages = self
+            .messages
+            .iter()
+            .map(|m| serde_json::from_value(expand_message(m.clone())))
+            .collect::<Result<_, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid message: {}", e))?;
+        Ok(ChatContext {
+            system_prompt: self.system.clone(),
+            messages,
+            tools: self.tools.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Options for the call, with the key `config` holds for the model's provider.
+    pub async fn options(&self, config: &ConfigManager) -> anyhow::Result<RequestOptions> {
+        let (provider, _) = split_model_id(&self.model)
+            .ok_or_else(|| anyhow::anyhow!("Model ID must be provider/model: {}", self.model))?;
+        Ok(RequestOptions {
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            reasoning: self.reasoning.clone(),
+            api_key: config.resolve_api_key(provider).await?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Fill in what a hand-written message leaves out: string content becomes one text block,
+/// and an assistant turn needn't carry the metadata of a reply.
+fn expand_message(mut message: Value) -> Value {
+    let Some(fields) = message.as_object_mut() else {
+        return message;
+    };
+    if let Some(Value::String(text)) = fields.get("content") {
+        let block = json!([{"type": "text", "text": text}]);
+        fields.insert("content".into(), block);
+    }
+    match fields.get("role").and_then(Value::as_str) {
+        Some("assistant") => {
+            for (key, default) in [("model", json!("")), ("provider", json!("")), ("usage", Value::Null), ("stop_reason", json!("stop"))] {
+                fields.entry(key).or_insert(default);
+            }
+        }
+        Some("tool_result") => {
+            fields.entry("is_error").or_insert(json!(false));
+        }
+        _ => {}
+    }
+    message
+}
+
+/// `{"type": "text_delta", "text": ...}` and so on, ending with `{"type": "done", "message": ...}`.
+pub fn event_json(event: &StreamEvent) -> Value {
+    match event {
+        StreamEvent::Start => json!({"type": "start"}),
+        StreamEvent::TextDelta(text) => json!({"type": "text_delta", "text": text}),
+        StreamEvent::ThinkingDelta(text) => json!({"type": "thinking_delta", "thinking": text}),
+        StreamEvent::ToolCallStart { index, id, name } => {
+            json!({"type": "tool_call_start", "index": index, "id": id, "name": name})
+        }
+        StreamEvent::ToolCallDelta { index, delta } => json!({"type": "tool_call_delta", "index": index, "delta": delta}),
+        StreamEvent::ToolCallEnd { index, tool_call } => {
+            json!({"type": "tool_call_end", "index": index, "tool_call": tool_call})
+        }
+        StreamEvent::ThoughtSignature(signature) => json!({"type": "thought_signature", "signature": signature}),
+        StreamEvent::CitationDelta(citation) => json!({"type": "citation", "citation": citation}),
+        StreamEvent::RefusalDelta(text) => json!({"type": "refusal_delta", "text": text}),
+        StreamEvent::ExecutableCode(code) => json!({"type": "executable_code", "code": code}),
+        StreamEvent::CodeExecutionResult(result) => json!({"type": "code_execution_result", "result": result}),
+        StreamEvent::Done { message } => json!({"type": "done", "message": message}),
+        StreamEvent::Error { message } => json!({"type": "error", "message": message}),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_written_messages_are_expanded() {
+        let request: ChatRequest = serde_json::from_value(json!({
+            "model": "openai/gpt-4o",
+            "system": "Be brief.",
+            "messages": [
+                {"role": "user", "content": "What time is it?"},
+                {"role": "assistant", "content": [{"type": "tool_call", "id": "call_1", "name": "time", "arguments": {}}]},
+                {"role": "tool_result", "tool_call_id": "call_1", "tool_name": "time", "content": "12:00"},
+            ],
+            "reasoning": "low",
+        }))
+        .unwrap();
+        let context = request.context().unwrap();
+        assert_eq!(context.messages.len(), 3);
+        let Message::ToolResult(result) = &context.messages[2] else { panic!() };
+        assert!(!result.is_error);
+
+        let delta = event_json(&StreamEvent::TextDelta("hi".into()));
+        assert_eq!(delta, json!({"type": "text_delta", "text": "hi"}));
+        let bad = json!({"model": "m", "messages": [], "reasoning": "extreme"});
+        assert!(serde_json::from_value::<ChatRequest>(bad).is_err());
+    }
+}
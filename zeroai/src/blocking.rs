@@ -0,0 +1,184 @@
+//! A blocking facade over [`AiClient`] for CLI tools and applications without an async runtime.
+//!
+//! The HTTP stack (reqwest/hyper) runs on tokio, so [`AiClientBlocking`] owns a small
+//! current-thread runtime and drives each call to completion on the calling thread. Nothing
+//! runs between calls; there are no background threads.
+//!
+//! ```
+//! use zeroai::{AiClient, AiClientBlocking, MockProvider, MockReply};
+//! use std::sync::Arc;
+//!
+//! let client = AiClient::builder()
+//!     .with_provider("mock", Arc::new(MockProvider::new().with_reply(MockReply::text("Hello!"))))
+//!     .with_model("mock/test".into(), MockProvider::model("test"))
+//!     .build();
+//! let client = AiClientBlocking::new(client).unwrap();
+//! # let context = zeroai::ChatContext { system_prompt: None, messages: vec![], tools: vec![] };
+//! let reply = client.chat("mock/test", &context, &Default::default()).unwrap();
+//! ```
+
+use crate::client::AiClient;
+use crate::providers::{EventStream, ProviderError};
+use crate::types::*;
+use futures::stream::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// [`AiClient`] with blocking methods. Cheap to clone; clones share the runtime.
+///
+/// Must not be used from inside an async runtime: calls there fail rather than block a worker.
+#[derive(Clone)]
+pub struct AiClientBlocking {
+    client: AiClient,
+    runtime: Arc<Runtime>,
+}
+
+impl AiClientBlocking {
+    pub fn new(client: AiClient) -> Result<Self, ProviderError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ProviderError::Other(format!("Failed to start runtime: {}", e)))?;
+        Ok(Self { client, runtime: Arc::new(runtime) })
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &AiClient {
+        &self.client
+    }
+
+    pub fn models(&self) -> &HashMap<String, ModelDef> {
+        self.client.models()
+    }
+
+    pub fn get_model(&self, full_model_id: &str) -> Option<&ModelDef> {
+        self.client.get_model(full_model_id)
+    }
+
+    pub fn chat(
+        &self,
+        full_model_id: &str,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        self.block_on(self.client.chat(full_model_id, context, options))?
+    }
+
+    /// Stream a completion; each `next()` blocks until the next event arrives.
+    pub fn stream(
+        &self,
+        full_model_id: &str,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<BlockingStream, ProviderError> {
+        check_no_runtime()?;
+        let stream = self.client.stream(full_model_id, context, options)?;
+        Ok(BlockingStream { stream, runtime: Arc::clone(&self.runtime) })
+    }
+
+    pub fn count_tokens(
+        &self,
+        full_model_id: &str,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<u64, ProviderError> {
+        self.block_on(self.client.count_tokens(full_model_id, context, options))?
+    }
+
+    pub fn embed(
+        &self,
+        full_model_id: &str,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        self.block_on(self.client.embed(full_model_id, request, options))?
+    }
+
+    pub fn rerank(
+        &self,
+        full_model_id: &str,
+        request: &RerankRequest,
+        options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        self.block_on(self.client.rerank(full_model_id, request, options))?
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output, ProviderError> {
+        check_no_runtime()?;
+        Ok(self.runtime.block_on(future))
+    }
+}
+
+/// Events of a streamed completion, pulled one at a time.
+pub struct BlockingStream {
+    stream: EventStream,
+    runtime: Arc<Runtime>,
+}
+
+impl Iterator for BlockingStream {
+    type Item = Result<StreamEvent, ProviderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = check_no_runtime() {
+            return Some(Err(e));
+        }
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+fn check_no_runtime() -> Result<(), ProviderError> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(_) => Err(ProviderError::Other(
+            "AiClientBlocking called from inside an async runtime; use AiClient there".into(),
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock::{MockProvider, MockReply};
+
+    fn context() -> ChatContext {
+        ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage {
+                content: vec![ContentBlock::Text(TextContent { text: "hi".into() })],
+            })],
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn calls_complete_without_a_caller_runtime() {
+        let mock = MockProvider::new()
+            .with_reply(MockReply::error(429))
+            .with_reply(MockReply::text("Hello there"));
+        let client = AiClient::builder()
+            .with_provider("mock", Arc::new(mock))
+            .with_model("mock/test".into(), MockProvider::model("test"))
+            .build();
+        let client = AiClientBlocking::new(client).unwrap();
+        let options = RequestOptions { retry_config: Some(RetryConfig { base_backoff_ms: 1, ..Default::default() }), ..Default::default() };
+
+        let reply = client.chat("mock/test", &context(), &options).unwrap();
+        assert_eq!(reply.model, "mock/test");
+
+        let text: String = client
+            .stream("mock/test", &context(), &options)
+            .unwrap()
+            .filter_map(|event| match event.unwrap() {
+                StreamEvent::TextDelta(t) => Some(t),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Hello there");
+
+        // Inside a runtime the call fails instead of panicking or stalling a worker.
+        let inside = tokio::runtime::Runtime::new().unwrap().block_on(async { client.chat("mock/test", &context(), &options) });
+        assert!(matches!(inside, Err(ProviderError::Other(_))));
+    }
+}
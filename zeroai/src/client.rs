@@ -1,16 +1,17 @@
+use crate::auth::config::ConfigManager;
 use crate::auth::sniff;
+use crate::concurrency::{ConcurrencyLimit, ConcurrencyPools};
 use crate::mapper::{join_model_id, split_model_id};
 use crate::providers::compatible::{AuthStyle, OpenAiCompatibleProvider};
-use crate::providers::retry::{self, compute_backoff, is_non_retryable};
-use crate::providers::{Provider, ProviderError};
-use crate::providers::google_gemini_cli::GoogleGeminiCliProvider;
-use crate::providers::anthropic::AnthropicProvider;
-use crate::providers::openai::OpenAiProvider;
-use crate::providers::google::GoogleProvider;
+use crate::providers::retry;
+use crate::providers::{EventStream, Provider, ProviderError};
+use crate::providers::registry::{ProviderDef, ProviderRegistry};
+use crate::tokens;
 use crate::types::*;
-use futures::stream::{BoxStream, StreamExt};
+use futures::stream::StreamExt;
 use std::sync::Arc;
 use std::collections::HashMap;
+use crate::rt::{self, Instant};
 use std::time::Duration;
 
 /// High-level AI client that coordinates multiple providers and model mapping.
@@ -18,6 +19,7 @@ use std::time::Duration;
 pub struct AiClient {
     providers: HashMap<String, Arc<dyn Provider>>,
     models: HashMap<String, ModelDef>,
+    pools: Arc<ConcurrencyPools>,
 }
 
 impl AiClient {
@@ -25,6 +27,20 @@ impl AiClient {
         AiClientBuilder::new()
     }
 
+    /// A client for the enabled models of `config`, with its provider definitions and
+    /// concurrency limits. Credentials are not resolved here; pass them per call.
+    pub fn from_config(config: &ConfigManager) -> anyhow::Result<Self> {
+        let mut registry = ProviderRegistry::new();
+        for def in &config.get_provider_defs()? {
+            registry.register_def(def);
+        }
+        Ok(AiClientBuilder::new()
+            .with_registry(registry)
+            .with_models(config.enabled_model_defs()?)
+            .with_concurrency_limits(config.get_settings()?.concurrency)
+            .build())
+    }
+
     /// Return a reference to the internal models map.
     pub fn models(&self) -> &HashMap<String, ModelDef> {
         &self.models
@@ -40,14 +56,17 @@ impl AiClient {
         full_model_id: &str,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> Result<BoxStream<'static, Result<StreamEvent, ProviderError>>, ProviderError> {
+    ) -> Result<EventStream, ProviderError> {
         let (provider_name, model_def) = self.resolve(full_model_id)?;
+        let builtin = extract_builtin_tools(context, options);
+        let (context, options) = builtin.as_ref().map_or((context, options), |(c, o)| (c, o));
+        let options = &limit_output_tokens(&model_def, options);
 
         let provider = self.providers.get(provider_name).ok_or_else(|| {
             ProviderError::Other(format!("Unknown provider: {}", provider_name))
         })?;
 
-        let stream: BoxStream<'static, Result<StreamEvent, ProviderError>> = match &options.retry_config {
+        let stream: EventStream = match &options.retry_config {
             Some(config) => {
                 let provider = Arc::clone(provider);
                 let model_def = model_def.clone();
@@ -56,27 +75,94 @@ impl AiClient {
                 let config = config.clone();
                 retry::retry_stream(provider, model_def, context, options, config)
             }
-            None => provider.stream(&model_def, context, options),
+            None => {
+                retry::AttemptBudget::acquire(options.attempt_budget.as_ref())?;
+                provider.stream(&model_def, context, options)
+            }
         };
 
-        let p_name = provider_name.to_string();
-        let mapped = stream.map(move |event| match event {
-            Ok(StreamEvent::Done { mut message }) => {
-                let short_id = message.model.clone();
-                message.model = join_model_id(&p_name, &short_id);
-                message.provider = p_name.clone();
-                Ok(StreamEvent::Done { message })
+        Ok(self.track_stream(provider_name, stream, options))
+    }
+
+    /// Stream a text completion: natively where the provider has a `/completions` endpoint,
+    /// otherwise as a chat over `request.to_context()`.
+    pub fn stream_completion(
+        &self,
+        full_model_id: &str,
+        request: &CompletionRequest,
+        options: &RequestOptions,
+    ) -> Result<EventStream, ProviderError> {
+        let (provider_name, model_def) = self.resolve(full_model_id)?;
+        let options = &limit_output_tokens(&model_def, options);
+        let provider = self.providers.get(provider_name).ok_or_else(|| {
+            ProviderError::Other(format!("Unknown provider: {}", provider_name))
+        })?;
+
+        match provider.stream_completion(&model_def, request, options) {
+            Some(stream) => {
+                retry::AttemptBudget::acquire(options.attempt_budget.as_ref())?;
+                Ok(self.track_stream(provider_name, stream, options))
             }
-            Ok(StreamEvent::Error { mut message }) => {
-                let short_id = message.model.clone();
-                message.model = join_model_id(&p_name, &short_id);
-                message.provider = p_name.clone();
-                Ok(StreamEvent::Error { message })
+            None => self.stream(full_model_id, &request.to_context(), options),
+        }
+    }
+
+    /// Hold a concurrency permit for the stream's life, qualify the model ID of its final
+    /// message and record the timing. The stream fails once the attempt budget's deadline
+    /// passes, whether it is still waiting for a permit or for upstream events.
+    fn track_stream(&self, provider_name: &str, stream: EventStream, options: &RequestOptions) -> EventStream {
+        let p_name = provider_name.to_string();
+        let (pools, api_key) = (Arc::clone(&self.pools), options.api_key.clone());
+        let budget = options.attempt_budget.clone();
+        let mapped = async_stream::stream! {
+            // Held until the stream ends, across its retries.
+            let permit = retry::AttemptBudget::within(budget.as_ref(), async {
+                Ok(pools.acquire(&p_name, api_key.as_deref()).await)
+            });
+            let _permit = match permit.await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut stream = stream;
+            let started = Instant::now();
+            let mut ttft_ms = None;
+            loop {
+                let next = retry::AttemptBudget::within(budget.as_ref(), async { Ok(stream.next().await) });
+                let event = match next.await {
+                    Ok(Some(event)) => event,
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                };
+                yield match event {
+                    Ok(StreamEvent::Done { mut message }) => {
+                        let short_id = message.model.clone();
+                        message.model = join_model_id(&p_name, &short_id);
+                        message.provider = p_name.clone();
+                        record_timing(&mut message, started, ttft_ms);
+                        Ok(StreamEvent::Done { message })
+                    }
+                    Ok(StreamEvent::Error { mut message }) => {
+                        let short_id = message.model.clone();
+                        message.model = join_model_id(&p_name, &short_id);
+                        message.provider = p_name.clone();
+                        Ok(StreamEvent::Error { message })
+                    }
+                    Ok(event @ (StreamEvent::TextDelta(_) | StreamEvent::ThinkingDelta(_) | StreamEvent::ToolCallStart { .. })) => {
+                        ttft_ms.get_or_insert_with(|| started.elapsed().as_millis() as u64);
+                        Ok(event)
+                    }
+                    other => other,
+                };
             }
-            other => other,
-        });
+        };
 
-        Ok(Box::pin(mapped))
+        Box::pin(mapped)
     }
 
     pub async fn chat(
@@ -86,40 +172,147 @@ impl AiClient {
         options: &RequestOptions,
     ) -> Result<AssistantMessage, ProviderError> {
         let (provider_name, model_def) = self.resolve(full_model_id)?;
+        let builtin = extract_builtin_tools(context, options);
+        let (context, options) = builtin.as_ref().map_or((context, options), |(c, o)| (c, o));
+        let options = &limit_output_tokens(&model_def, options);
 
         let provider = self.providers.get(provider_name).ok_or_else(|| {
             ProviderError::Other(format!("Unknown provider: {}", provider_name))
         })?;
 
-        let config = options.retry_config.as_ref();
-        let max_retries = config.map(|c| c.max_retries).unwrap_or(0);
-        let mut backoff_ms = config.map(|c| c.base_backoff_ms).unwrap_or(1000);
+        let budget = options.attempt_budget.as_ref();
+        let mut retry = options.retry_config.as_ref().map(|c| retry::RetryState::new(c, budget.cloned()));
 
-        let mut last_err = None;
-        for attempt in 0..=max_retries {
-            match provider.chat(&model_def, context, options).await {
+        let started = Instant::now();
+        loop {
+            retry::AttemptBudget::acquire(budget)?;
+            let result = retry::AttemptBudget::within(budget, async {
+                let _permit = self.pools.acquire(provider_name, options.api_key.as_deref()).await;
+                provider.chat(&model_def, context, options).await
+            })
+            .await;
+            match result {
                 Ok(mut message) => {
                     let p_name = provider_name.to_string();
                     let short_id = message.model.clone();
                     message.model = join_model_id(&p_name, &short_id);
                     message.provider = p_name;
+                    record_timing(&mut message, started, None);
                     return Ok(message);
                 }
-                Err(e) => {
-                    last_err = Some(e);
-                    let err = last_err.as_ref().unwrap();
-                    if is_non_retryable(err) || attempt >= max_retries {
-                        break;
-                    }
-                    let wait = config
-                        .map(|c| compute_backoff(c, backoff_ms, err))
-                        .unwrap_or(backoff_ms);
-                    tokio::time::sleep(Duration::from_millis(wait)).await;
-                    backoff_ms = (backoff_ms.saturating_mul(2)).min(10_000);
-                }
+                Err(e) => match retry.as_mut().and_then(|r| r.next_delay(&e)) {
+                    Some(wait) => rt::sleep(Duration::from_millis(wait)).await,
+                    None => return Err(e),
+                },
             }
         }
-        Err(last_err.unwrap_or_else(|| ProviderError::Other("no attempt".into())))
+    }
+
+    /// Input tokens of a request: the provider's count when it has a counting endpoint,
+    /// otherwise a local estimate (see `tokens::estimate_input_tokens`).
+    pub async fn count_tokens(
+        &self,
+        full_model_id: &str,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<u64, ProviderError> {
+        let (provider_name, model_def) = self.resolve(full_model_id)?;
+
+        let provider = self.providers.get(provider_name).ok_or_else(|| {
+            ProviderError::Other(format!("Unknown provider: {}", provider_name))
+        })?;
+
+        let _permit = self.pools.acquire(provider_name, options.api_key.as_deref()).await;
+        let counted = provider.count_tokens(&model_def, context, options).await?;
+        Ok(counted.unwrap_or_else(|| tokens::estimate_input_tokens(context)))
+    }
+
+    /// Embed `request.input` with an embedding model. The response's `model` is the full model ID.
+    pub async fn embed(
+        &self,
+        full_model_id: &str,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let (provider, model_def) = self.resolve_auxiliary(full_model_id)?;
+        let budget = options.attempt_budget.as_ref();
+        retry::AttemptBudget::acquire(budget)?;
+        let mut resp = retry::AttemptBudget::within(budget, async {
+            let _permit = self.pools.acquire(&model_def.provider, options.api_key.as_deref()).await;
+            provider.embed(&model_def, request, options).await
+        })
+        .await?;
+        resp.model = full_model_id.to_string();
+        resp.provider = model_def.provider;
+        Ok(resp)
+    }
+
+    /// Rank `request.documents` by relevance to `request.query`, most relevant first.
+    pub async fn rerank(
+        &self,
+        full_model_id: &str,
+        request: &RerankRequest,
+        options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        let (provider, model_def) = self.resolve_auxiliary(full_model_id)?;
+        let budget = options.attempt_budget.as_ref();
+        retry::AttemptBudget::acquire(budget)?;
+        let mut resp = retry::AttemptBudget::within(budget, async {
+            let _permit = self.pools.acquire(&model_def.provider, options.api_key.as_deref()).await;
+            provider.rerank(&model_def, request, options).await
+        })
+        .await?;
+        resp.model = full_model_id.to_string();
+        resp.provider = model_def.provider;
+        Ok(resp)
+    }
+
+    /// Open pooled connections to the API hosts of `providers`' models, so their next request
+    /// skips the TCP and TLS handshakes; repeat now and then to keep the connections alive.
+    /// Returns the hosts that couldn't be reached, by provider.
+    pub async fn warm_connections(&self, providers: &[String]) -> Vec<(String, ProviderError)> {
+        let mut targets: Vec<(&str, &str)> = self
+            .models
+            .iter()
+            .filter_map(|(full_id, def)| Some((split_model_id(full_id)?.0, def.base_url.as_str())))
+            .filter(|(name, base_url)| !base_url.is_empty() && providers.iter().any(|p| p == name))
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        let warms = targets.into_iter().filter_map(|(name, base_url)| {
+            let provider = self.providers.get(name)?;
+            Some(async move { provider.warm(base_url).await.err().map(|e| (name.to_string(), e)) })
+        });
+        futures::future::join_all(warms).await.into_iter().flatten().collect()
+    }
+
+    /// Resolve an embedding or rerank model. These are usually not in the chat model catalog,
+    /// so unregistered IDs fall back to the provider's default settings.
+    fn resolve_auxiliary(&self, full_model_id: &str) -> Result<(&Arc<dyn Provider>, ModelDef), ProviderError> {
+        let (provider_name, short_id) = split_model_id(full_model_id).ok_or_else(|| {
+            ProviderError::Other(format!("Invalid model ID format: {}", full_model_id))
+        })?;
+        let provider = self.providers.get(provider_name).ok_or_else(|| {
+            ProviderError::Other(format!("Unknown provider: {}", provider_name))
+        })?;
+        let model_def = match self.models.get(full_model_id) {
+            Some(def) => def.clone(),
+            None => crate::models::default_model_def_for_provider(provider_name, short_id)
+                .unwrap_or_else(|| ModelDef {
+                    // Declarative and custom providers carry their own base URL.
+                    id: short_id.to_string(),
+                    name: short_id.to_string(),
+                    api: Api::OpenaiCompletions,
+                    provider: provider_name.to_string(),
+                    base_url: String::new(),
+                    reasoning: false,
+                    input: vec![InputModality::Text, InputModality::Image],
+                    context_window: 0,
+                    max_tokens: 0,
+                    ..Default::default()
+                }),
+        };
+        Ok((provider, model_def))
     }
 
     /// Resolve a full model ID to (provider_name, ModelDef).
@@ -136,6 +329,42 @@ impl AiClient {
     }
 }
 
+/// Turn `web_search` / `code_execution` pseudo-tools into the matching `RequestOptions`
+/// flags, so providers only check the flags. `None` when the context has no such tool.
+fn extract_builtin_tools(context: &ChatContext, options: &RequestOptions) -> Option<(ChatContext, RequestOptions)> {
+    let web_search = context.tools.iter().any(ToolDef::is_web_search);
+    let code_execution = context.tools.iter().any(ToolDef::is_code_execution);
+    if !web_search && !code_execution {
+        return None;
+    }
+    let mut context = context.clone();
+    context.tools.retain(|t| !t.is_web_search() && !t.is_code_execution());
+    let options = RequestOptions {
+        web_search: options.web_search || web_search,
+        code_execution: options.code_execution || code_execution,
+        ..options.clone()
+    };
+    Some((context, options))
+}
+
+/// Fill in how long the response took (and, for streams, until its first token) on its usage.
+/// Responses without usage are left alone.
+fn record_timing(message: &mut AssistantMessage, started: Instant, ttft_ms: Option<u64>) {
+    if let Some(usage) = message.usage.as_mut() {
+        usage.ttft_ms = ttft_ms;
+        usage.duration_ms = Some(started.elapsed().as_millis() as u64);
+    }
+}
+
+/// `options` with `max_tokens` resolved against the model's limits (see
+/// `ModelDef::output_token_limit`).
+fn limit_output_tokens(model: &ModelDef, options: &RequestOptions) -> RequestOptions {
+    RequestOptions {
+        max_tokens: model.output_token_limit(options.max_tokens),
+        ..options.clone()
+    }
+}
+
 /// Custom provider registration for build().
 struct CustomProviderReg {
     name: String,
@@ -147,6 +376,8 @@ struct CustomProviderReg {
 pub struct AiClientBuilder {
     models: HashMap<String, ModelDef>,
     custom_providers: Vec<CustomProviderReg>,
+    registry: ProviderRegistry,
+    concurrency: HashMap<String, ConcurrencyLimit>,
 }
 
 impl AiClientBuilder {
@@ -154,7 +385,30 @@ impl AiClientBuilder {
         Self {
             models: HashMap::new(),
             custom_providers: Vec::new(),
+            registry: ProviderRegistry::new(),
+            concurrency: HashMap::new(),
+        }
+    }
+
+    /// Register a provider implementation under `id`, overriding any built-in provider.
+    pub fn with_provider(mut self, id: &str, provider: Arc<dyn Provider>) -> Self {
+        self.registry.register(id, provider);
+        self
+    }
+
+    /// Merge a registry of providers on top of the built-in ones.
+    pub fn with_registry(mut self, registry: ProviderRegistry) -> Self {
+        self.registry.extend(registry);
+        self
+    }
+
+    /// Register a declarative OpenAI-compatible provider together with its static models.
+    pub fn with_provider_def(mut self, def: &ProviderDef) -> Self {
+        self.registry.register_def(def);
+        for m in def.model_defs() {
+            self.models.insert(join_model_id(&def.id, &m.id), m);
         }
+        self
     }
 
     /// Register a single model under its full ID (`provider/model`).
@@ -170,6 +424,13 @@ impl AiClientBuilder {
         self
     }
 
+    /// Cap concurrent upstream requests: provider ID (or `*` for all others) -> limits.
+    /// Calls over a cap wait for a slot.
+    pub fn with_concurrency_limits(mut self, limits: HashMap<String, ConcurrencyLimit>) -> Self {
+        self.concurrency = limits;
+        self
+    }
+
     /// Add an OpenAI-compatible custom provider with a fixed list of models.
     pub fn with_custom_provider(
         mut self,
@@ -212,41 +473,9 @@ impl AiClientBuilder {
     }
 
     pub fn build(self) -> AiClient {
-        let mut providers: HashMap<String, Arc<dyn Provider>> = HashMap::new();
-
-        let openai = Arc::new(OpenAiProvider::new());
-        providers.insert("openai".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("deepseek".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("xai".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("groq".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("together".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("siliconflow".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("zhipuai".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("fireworks".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("nebius".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("openrouter".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("minimax".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("moonshot".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("qwen".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("qwen-portal".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("qianfan".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("ollama".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("vllm".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("huggingface".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("github-copilot".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("amazon-bedrock".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("openai-codex".into(), openai.clone() as Arc<dyn Provider>);
-        providers.insert("xiaomi".into(), openai.clone() as Arc<dyn Provider>);
-
-        let anthropic = Arc::new(AnthropicProvider::new());
-        providers.insert("anthropic".into(), anthropic.clone() as Arc<dyn Provider>);
-        providers.insert("anthropic-setup-token".into(), anthropic.clone() as Arc<dyn Provider>);
-        providers.insert("synthetic".into(), anthropic.clone() as Arc<dyn Provider>);
-        providers.insert("cloudflare-ai-gateway".into(), anthropic.clone() as Arc<dyn Provider>);
-
-        providers.insert("google".into(), Arc::new(GoogleProvider::new()) as Arc<dyn Provider>);
-        providers.insert("gemini-cli".into(), Arc::new(GoogleGeminiCliProvider::new_gemini_cli()) as Arc<dyn Provider>);
-        providers.insert("antigravity".into(), Arc::new(GoogleGeminiCliProvider::new_antigravity()) as Arc<dyn Provider>);
+        let mut registry = ProviderRegistry::builtin();
+        registry.extend(self.registry);
+        let mut providers = registry.into_map();
 
         // Register custom providers (with_custom_provider / with_custom_provider_with_models_url)
         for reg in &self.custom_providers {
@@ -284,6 +513,85 @@ impl AiClientBuilder {
         AiClient {
             providers,
             models: self.models,
+            pools: Arc::new(ConcurrencyPools::new(self.concurrency)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool(name: &str, parameters: serde_json::Value) -> ToolDef {
+        ToolDef {
+            name: name.into(),
+            description: String::new(),
+            parameters,
         }
     }
+
+    #[test]
+    fn builtin_pseudo_tools_become_options() {
+        let mut context = ChatContext {
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: vec![tool("read", json!({"type": "object", "properties": {"path": {"type": "string"}}}))],
+        };
+        assert!(extract_builtin_tools(&context, &RequestOptions::default()).is_none());
+
+        context.tools.push(tool(WEB_SEARCH_TOOL, json!({})));
+        context.tools.push(tool(CODE_EXECUTION_TOOL, json!({"type": "object", "properties": {}})));
+        let (context, options) = extract_builtin_tools(&context, &RequestOptions::default()).unwrap();
+        assert!(options.web_search && options.code_execution);
+        assert_eq!(context.tools.len(), 1);
+        assert_eq!(context.tools[0].name, "read");
+
+        // A client-side web_search function with parameters is an ordinary tool.
+        let custom = tool(WEB_SEARCH_TOOL, json!({"type": "object", "properties": {"q": {"type": "string"}}}));
+        assert!(!custom.is_web_search());
+    }
+
+    #[test]
+    fn output_tokens_are_capped_and_defaulted() {
+        let mut gemini = crate::models::static_models::static_google_models().remove(0);
+        assert_eq!(gemini.max_tokens, 8192);
+        let limited = |m: &ModelDef, n| limit_output_tokens(m, &RequestOptions { max_tokens: n, ..Default::default() }).max_tokens;
+        assert_eq!(limited(&gemini, Some(100_000)), Some(8192));
+        assert_eq!(limited(&gemini, Some(1024)), Some(1024));
+        assert_eq!(limited(&gemini, None), None);
+        gemini.default_max_tokens = Some(2048);
+        assert_eq!(limited(&gemini, None), Some(2048));
+
+        // The Messages API requires max_tokens.
+        #[cfg(feature = "providers-anthropic")]
+        {
+            let claude = crate::providers::anthropic::static_anthropic_models().remove(0);
+            assert_eq!(limited(&claude, None), Some(claude.max_tokens));
+        }
+    }
+
+    #[tokio::test]
+    async fn warming_opens_connections_to_listed_providers() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+        let model = crate::models::default_model_def_for_provider("openai", "m").unwrap();
+        let client = AiClient::builder()
+            .with_custom_provider("local", &format!("http://{}/v1", addr), None, vec![model.clone()])
+            .with_custom_provider("down", "http://127.0.0.1:1/v1", None, vec![model.clone()])
+            .with_custom_provider("unlisted", "http://127.0.0.1:1/v1", None, vec![model])
+            .build();
+
+        let failed = client.warm_connections(&["local".into(), "down".into()]).await;
+        assert_eq!(failed.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), ["down"]);
+        assert!(server.await.unwrap().starts_with("HEAD /v1 "));
+    }
 }
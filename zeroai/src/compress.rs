@@ -0,0 +1,428 @@
+//! Context compression: summarize oversized tool outputs and old conversation turns with a
+//! cheap model before a request goes to an expensive one.
+//!
+//! Agents resend the whole conversation on every turn, so summaries are cached by content:
+//! each tool output is summarized once, not once per request.
+
+use crate::client::AiClient;
+use crate::providers::ProviderError;
+use crate::tokens::{estimate_input_tokens, estimate_text_tokens};
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+/// Output limit for a single summary.
+const SUMMARY_MAX_TOKENS: u64 = 2048;
+/// Cached summaries kept before the cache is cleared.
+const CACHE_CAPACITY: usize = 1024;
+
+const SUMMARIZER_PROMPT: &str = "You compress context for another AI model that cannot see the original. \
+Keep every fact it may need: errors, file paths, identifiers, numbers, decisions and open tasks. \
+Drop repetition and boilerplate. Reply with the summary only.";
+
+fn default_tool_output_tokens() -> u64 {
+    4000
+}
+
+fn default_keep_recent() -> usize {
+    10
+}
+
+/// When to compress and which model writes the summaries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Summarizing model as `<provider>/<model>`; a small, cheap one.
+    pub model: String,
+    /// Tool results above this many (estimated) tokens are replaced by a summary.
+    #[serde(default = "default_tool_output_tokens")]
+    pub tool_output_tokens: u64,
+    /// When a request is still above this many (estimated) tokens, turns before the last
+    /// `keep_recent` messages are folded into a summary in the system prompt. Unset: never.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_tokens: Option<u64>,
+    /// Most recent messages that are never folded into the history summary.
+    #[serde(default = "default_keep_recent")]
+    pub keep_recent: usize,
+}
+
+impl CompressionConfig {
+    /// Summarize with `model` using the default thresholds (no history folding).
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            tool_output_tokens: default_tool_output_tokens(),
+            history_tokens: None,
+            keep_recent: default_keep_recent(),
+        }
+    }
+}
+
+/// A compressed request context.
+#[derive(Debug, Clone)]
+pub struct Compressed {
+    pub context: ChatContext,
+    /// Tokens spent by the summarizing model (zero when nothing new was summarized).
+    pub usage: Usage,
+}
+
+/// Applies a [`CompressionConfig`] to request contexts, caching the summaries it writes.
+#[derive(Default)]
+pub struct ContextCompressor {
+    cache: Mutex<HashMap<u64, String>>,
+}
+
+impl ContextCompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compress `context` using `client` for the summaries. `options` carries the credentials
+    /// for the summarizing model; sampling options are replaced.
+    pub async fn compress(
+        &self,
+        client: &AiClient,
+        config: &CompressionConfig,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<Compressed, ProviderError> {
+        let mut context = context.clone();
+        let mut usage = Usage::default();
+
+        // Oversized tool outputs, summarized concurrently.
+        let long_outputs: Vec<(usize, String, u64)> = context
+            .messages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| match m {
+                Message::ToolResult(tr) => {
+                    let text = text_of(&tr.content);
+                    let tokens = estimate_text_tokens(&text);
+                    (tokens > config.tool_output_tokens).then_some((i, text, tokens))
+                }
+                _ => None,
+            })
+            .collect();
+        let summaries = futures::future::try_join_all(long_outputs.iter().map(|(i, text, _)| {
+            let tool = match &context.messages[*i] {
+                Message::ToolResult(tr) => tr.tool_name.as_str(),
+                _ => "",
+            };
+            self.summarize(client, config, options, format!("Summarize this output of the `{}` tool:\n\n{}", tool, text))
+        }))
+        .await?;
+        for ((i, _, tokens), (summary, u)) in long_outputs.iter().zip(summaries) {
+            if let Message::ToolResult(tr) = &mut context.messages[*i] {
+                tr.content.retain(|b| !matches!(b, ContentBlock::Text(_)));
+                tr.content.insert(
+                    0,
+                    ContentBlock::Text(TextContent {
+                        text: format!("[Summary of {} tokens of output]\n{}", tokens, summary),
+                    }),
+                );
+            }
+            add_usage(&mut usage, u.as_ref());
+        }
+
+        // Old turns, folded into the system prompt.
+        if let Some(limit) = config.history_tokens
+            && estimate_input_tokens(&context) > limit
+            && let Some(split) = history_split(&context.messages, config.keep_recent)
+        {
+            let prompt = format!(
+                "Summarize this conversation so far:\n\n{}",
+                transcript(&context.messages[..split])
+            );
+            let (summary, u) = self.summarize(client, config, options, prompt).await?;
+            add_usage(&mut usage, u.as_ref());
+            context.messages.drain(..split);
+            let note = format!("Summary of the earlier conversation:\n{}", summary);
+            context.system_prompt = Some(match context.system_prompt.take() {
+                Some(system) => format!("{}\n\n{}", system, note),
+                None => note,
+            });
+        }
+
+        Ok(Compressed { context, usage })
+    }
+
+    /// Summary of `prompt` from the cache or the summarizing model (with its usage).
+    async fn summarize(
+        &self,
+        client: &AiClient,
+        config: &CompressionConfig,
+        options: &RequestOptions,
+        prompt: String,
+    ) -> Result<(String, Option<Usage>), ProviderError> {
+        let mut hasher = DefaultHasher::new();
+        (&config.model, &prompt).hash(&mut hasher);
+        let key = hasher.finish();
+        if let Some(summary) = self.cache.lock().unwrap().get(&key) {
+            return Ok((summary.clone(), None));
+        }
+
+        let context = ChatContext {
+            system_prompt: Some(SUMMARIZER_PROMPT.into()),
+            messages: vec![Message::User(UserMessage {
+                content: vec![ContentBlock::Text(TextContent { text: prompt })],
+            })],
+            tools: Vec::new(),
+        };
+        let options = RequestOptions {
+            temperature: None,
+            max_tokens: Some(SUMMARY_MAX_TOKENS),
+            reasoning: None,
+            web_search: false,
+            code_execution: false,
+            ..options.clone()
+        };
+        let message = client.chat(&config.model, &context, &options).await?;
+        let summary = text_of(&message.content);
+        if summary.trim().is_empty() {
+            return Err(ProviderError::Other(format!("{} returned an empty summary", config.model)));
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, summary.clone());
+        Ok((summary, message.usage))
+    }
+}
+
+fn text_of(blocks: &[ContentBlock]) -> String {
+    blocks
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text(t) => Some(t.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn add_usage(total: &mut Usage, usage: Option<&Usage>) {
+    if let Some(u) = usage {
+        total.input_tokens += u.input_tokens;
+        total.output_tokens += u.output_tokens;
+        total.cache_read_tokens += u.cache_read_tokens;
+        total.cache_write_tokens += u.cache_write_tokens;
+        total.total_tokens += u.total_tokens;
+    }
+}
+
+/// Where to cut the history so at least `keep_recent` messages remain and the kept part
+/// starts with a user message (never with a tool result cut off from its call). `None` if
+/// there is nothing to fold.
+fn history_split(messages: &[Message], keep_recent: usize) -> Option<usize> {
+    let last = messages.len().saturating_sub(keep_recent).min(messages.len().saturating_sub(1));
+    (1..=last).rev().find(|&i| matches!(messages[i], Message::User(_)))
+}
+
+/// Plain-text rendering of messages for the summarizing model.
+fn transcript(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for m in messages {
+        match m {
+            Message::User(u) => out.push_str(&format!("User: {}\n\n", text_of(&u.content))),
+            Message::Assistant(a) => {
+                let text = text_of(&a.content);
+                if !text.is_empty() {
+                    out.push_str(&format!("Assistant: {}\n\n", text));
+                }
+                for b in &a.content {
+                    if let ContentBlock::ToolCall(tc) = b {
+                        out.push_str(&format!("Assistant called `{}` with {}\n\n", tc.name, tc.arguments));
+                    }
+                }
+            }
+            Message::ToolResult(tr) => {
+                let status = if tr.is_error { "error" } else { "result" };
+                out.push_str(&format!("`{}` {}: {}\n\n", tr.tool_name, status, text_of(&tr.content)));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::Provider;
+    use async_trait::async_trait;
+    use futures::stream::BoxStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Replies "summary N" (or, when `failing`, a 503) and counts calls.
+    #[derive(Default)]
+    struct Summarizer {
+        calls: AtomicUsize,
+        failing: bool,
+    }
+
+    impl Summarizer {
+        fn reply(&self, model: &ModelDef) -> Result<AssistantMessage, ProviderError> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.failing {
+                return Err(ProviderError::Http { status: 503, error: Box::new(crate::providers::api_error::ApiError::parse("unavailable")) });
+            }
+            Ok(AssistantMessage {
+                content: vec![ContentBlock::Text(TextContent { text: format!("summary {}", n) })],
+                model: model.id.clone(),
+                provider: model.provider.clone(),
+                usage: Some(Usage { input_tokens: 100, output_tokens: 10, total_tokens: 110, ..Usage::default() }),
+                stop_reason: StopReason::Stop,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Provider for Summarizer {
+        fn stream(
+            &self,
+            model: &ModelDef,
+            _context: &ChatContext,
+            _options: &RequestOptions,
+        ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+            let event = self.reply(model).map(|message| StreamEvent::Done { message });
+            Box::pin(futures::stream::once(async move { event }))
+        }
+
+        async fn chat(
+            &self,
+            model: &ModelDef,
+            _context: &ChatContext,
+            _options: &RequestOptions,
+        ) -> Result<AssistantMessage, ProviderError> {
+            self.reply(model)
+        }
+
+        async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn client(summarizer: Arc<Summarizer>) -> AiClient {
+        let model = ModelDef {
+            id: "cheap".into(),
+            name: "cheap".into(),
+            api: Api::OpenaiCompletions,
+            provider: "stub".into(),
+            base_url: String::new(),
+            reasoning: false,
+            input: vec![InputModality::Text],
+            context_window: 128_000,
+            max_tokens: 4096,
+            ..Default::default()
+        };
+        AiClient::builder()
+            .with_provider("stub", summarizer)
+            .with_model("stub/cheap".into(), model)
+            .build()
+    }
+
+    fn user(text: &str) -> Message {
+        Message::User(UserMessage {
+            content: vec![ContentBlock::Text(TextContent { text: text.into() })],
+        })
+    }
+
+    fn tool_result(text: &str) -> Message {
+        Message::ToolResult(ToolResultMessage {
+            tool_call_id: "call_1".into(),
+            tool_name: "bash".into(),
+            content: vec![ContentBlock::Text(TextContent { text: text.into() })],
+            is_error: false,
+        })
+    }
+
+    fn config() -> CompressionConfig {
+        CompressionConfig {
+            model: "stub/cheap".into(),
+            tool_output_tokens: 100,
+            history_tokens: None,
+            keep_recent: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn long_tool_outputs_are_summarized_once() {
+        let summarizer = Arc::new(Summarizer::default());
+        let client = client(summarizer.clone());
+        let compressor = ContextCompressor::new();
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![user("build it"), tool_result(&"error: boom\n".repeat(200)), tool_result("ok")],
+            tools: Vec::new(),
+        };
+
+        let out = compressor.compress(&client, &config(), &context, &RequestOptions::default()).await.unwrap();
+        assert!(matches!(&out.context.messages[1], Message::ToolResult(tr)
+            if text_of(&tr.content).starts_with("[Summary of") && text_of(&tr.content).ends_with("summary 1")));
+        assert!(matches!(&out.context.messages[2], Message::ToolResult(tr) if text_of(&tr.content) == "ok"));
+        assert_eq!(out.usage.total_tokens, 110);
+
+        // The next turn resends the same output: served from the cache.
+        let again = compressor.compress(&client, &config(), &context, &RequestOptions::default()).await.unwrap();
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(again.usage.total_tokens, 0);
+    }
+
+    #[tokio::test]
+    async fn old_turns_fold_into_system_prompt() {
+        let client = client(Arc::new(Summarizer::default()));
+        let context = ChatContext {
+            system_prompt: Some("Be brief.".into()),
+            messages: vec![user("one"), user("two"), tool_result("three"), user("four"), user("five")],
+            tools: Vec::new(),
+        };
+        let config = CompressionConfig {
+            history_tokens: Some(1),
+            ..config()
+        };
+
+        let out = ContextCompressor::new()
+            .compress(&client, &config, &context, &RequestOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(out.context.messages.len(), 2);
+        assert_eq!(
+            out.context.system_prompt.as_deref(),
+            Some("Be brief.\n\nSummary of the earlier conversation:\nsummary 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_summaries_fail_the_compression_and_are_not_cached() {
+        let compressor = ContextCompressor::new();
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![user("build it"), tool_result(&"error: boom\n".repeat(200))],
+            tools: Vec::new(),
+        };
+
+        let down = client(Arc::new(Summarizer { failing: true, ..Summarizer::default() }));
+        let err = compressor.compress(&down, &config(), &context, &RequestOptions::default()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::Http { status: 503, .. }));
+
+        let summarizer = Arc::new(Summarizer::default());
+        let out = compressor.compress(&client(summarizer.clone()), &config(), &context, &RequestOptions::default()).await.unwrap();
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(out.usage.total_tokens, 110);
+    }
+
+    #[test]
+    fn history_split_keeps_tool_results_with_their_turn() {
+        let messages = vec![user("a"), tool_result("b"), tool_result("c"), user("d")];
+        // Cutting at either tool result would orphan it, and index 0 leaves nothing to fold.
+        assert_eq!(history_split(&messages, 2), None);
+        assert_eq!(history_split(&messages, 1), Some(3));
+        assert_eq!(history_split(&messages, 10), None);
+        assert_eq!(history_split(&[], 0), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Concurrency pools: caps on in-flight upstream requests per provider and per account.
+//!
+//! Local servers (vLLM, Ollama) slow to a crawl or fail when agents fan out dozens of parallel
+//! calls. [`AiClient`](crate::AiClient) takes a slot from its provider's pool before each
+//! upstream call and holds it until the response (or stream) is done; calls over the cap wait.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Limits for one provider. Unset fields don't limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConcurrencyLimit {
+    /// In-flight requests across all of the provider's accounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests: Option<usize>,
+    /// In-flight requests per account (API key).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests_per_account: Option<usize>,
+}
+
+/// Semaphores for the limits in force: provider ID (or `*` for all others) -> limit.
+#[derive(Debug, Default)]
+pub struct ConcurrencyPools {
+    limits: HashMap<String, ConcurrencyLimit>,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Slots held for one upstream request; released on drop.
+#[derive(Debug, Default)]
+pub struct Permit {
+    _account: Option<OwnedSemaphorePermit>,
+    _provider: Option<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyPools {
+    pub fn new(limits: HashMap<String, ConcurrencyLimit>) -> Self {
+        Self { limits, semaphores: Mutex::default() }
+    }
+
+    /// Wait for a slot for a request to `provider` with `api_key` (`None`: the provider's
+    /// default credential, counted as one account).
+    pub async fn acquire(&self, provider: &str, api_key: Option<&str>) -> Permit {
+        let Some(limit) = self.limits.get(provider).or_else(|| self.limits.get("*")) else {
+            return Permit::default();
+        };
+        let account = limit
+            .max_requests_per_account
+            .map(|n| self.semaphore(format!("{}\n{}", provider, api_key.unwrap_or_default()), n));
+        let provider = limit.max_requests.map(|n| self.semaphore(provider.to_string(), n));
+        // Account first, so a request queued behind its own busy account doesn't hold one of
+        // the provider's slots that another account could use.
+        let account = match account {
+            Some(s) => s.acquire_owned().await.ok(),
+            None => None,
+        };
+        let provider = match provider {
+            Some(s) => s.acquire_owned().await.ok(),
+            None => None,
+        };
+        Permit { _account: account, _provider: provider }
+    }
+
+    fn semaphore(&self, key: String, size: usize) -> Arc<Semaphore> {
+        let mut semaphores = self.semaphores.lock().unwrap_or_else(|e| e.into_inner());
+        semaphores.entry(key).or_insert_with(|| Arc::new(Semaphore::new(size.max(1)))).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn waits(pools: &ConcurrencyPools, provider: &str, api_key: &str) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), pools.acquire(provider, Some(api_key))).await.is_err()
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_limit_wait_for_a_slot() {
+        let limits = HashMap::from([
+            ("ollama".to_string(), ConcurrencyLimit { max_requests: Some(2), max_requests_per_account: Some(1) }),
+            ("*".to_string(), ConcurrencyLimit { max_requests: Some(1), ..Default::default() }),
+        ]);
+        let pools = ConcurrencyPools::new(limits);
+
+        let first = pools.acquire("ollama", Some("k1")).await;
+        assert!(waits(&pools, "ollama", "k1").await);
+        let second = pools.acquire("ollama", Some("k2")).await;
+        assert!(waits(&pools, "ollama", "k3").await);
+        drop(first);
+        assert!(!waits(&pools, "ollama", "k1").await);
+        drop(second);
+
+        // `*` gives every other provider a pool of its own.
+        let _openai = pools.acquire("openai", None).await;
+        assert!(waits(&pools, "openai", "k1").await);
+        assert!(!waits(&pools, "groq", "k1").await);
+    }
+}
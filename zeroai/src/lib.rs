@@ -1,9 +1,20 @@
 pub mod auth;
+pub mod bindings;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod blocking;
 pub mod client;
+pub mod compress;
+pub mod concurrency;
 pub mod mapper;
 pub mod models;
 pub mod oauth;
+pub mod probe;
 pub mod providers;
+pub mod routing;
+mod rt;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod tokens;
 pub mod types;
 
 // Re-exports for convenience
@@ -12,9 +23,16 @@ pub use auth::{
     all_provider_auth_info, provider_base_url, provider_groups, AuthMethod, Credential,
     ProviderAuthInfo,
 };
+#[cfg(not(target_arch = "wasm32"))]
+pub use blocking::AiClientBlocking;
 pub use client::{AiClient, AiClientBuilder};
+pub use compress::{CompressionConfig, ContextCompressor};
+pub use concurrency::ConcurrencyLimit;
 pub use mapper::{join_model_id, split_model_id};
 pub use models::static_models;
 pub use oauth::{OAuthAuthInfo, OAuthCallbacks, OAuthCredentials, OAuthPrompt, OAuthProvider};
+pub use providers::mock::{MockProvider, MockReply};
+pub use providers::registry::{ProviderDef, ProviderRegistry};
+pub use providers::api_error::ApiError;
 pub use providers::{Provider, ProviderError};
 pub use types::*;
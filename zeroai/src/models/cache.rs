@@ -0,0 +1,129 @@
+//! On-disk cache of dynamically fetched model lists.
+//!
+//! One JSON file per provider under `~/.zeroai/cache/models/`, holding the raw model IDs,
+//! the URL they were fetched from and the response `ETag`. Only IDs are stored: metadata is
+//! re-merged from the static models on every load, so catalog updates apply to cached lists.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a cached list is used without revalidating it.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedModelList {
+    /// Models endpoint the list came from; a different URL invalidates the entry.
+    pub url: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// Unix timestamp (seconds) of the last successful fetch or revalidation.
+    pub fetched_at: i64,
+    pub ids: Vec<String>,
+}
+
+impl CachedModelList {
+    pub fn new(url: &str, etag: Option<String>, ids: Vec<String>) -> Self {
+        Self {
+            url: url.to_string(),
+            etag,
+            fetched_at: chrono::Utc::now().timestamp(),
+            ids,
+        }
+    }
+
+    /// Mark the entry as revalidated now (e.g. after a 304 Not Modified).
+    pub fn touch(&mut self) {
+        self.fetched_at = chrono::Utc::now().timestamp();
+    }
+}
+
+pub struct ModelListCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ModelListCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// Default cache directory (`cache/models` in the config root, ~/.zeroai/cache/models).
+    pub fn default_dir() -> PathBuf {
+        crate::auth::config::config_dir().join("cache").join("models")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, provider: &str) -> PathBuf {
+        let name: String = provider
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Cached list for `provider`, if present and readable.
+    pub fn load(&self, provider: &str) -> Option<CachedModelList> {
+        let data = std::fs::read_to_string(self.path(provider)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn store(&self, provider: &str, entry: &CachedModelList) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(provider), serde_json::to_string_pretty(entry)?)?;
+        Ok(())
+    }
+
+    /// True if `entry` is younger than the TTL.
+    pub fn is_fresh(&self, entry: &CachedModelList) -> bool {
+        let age = chrono::Utc::now().timestamp() - entry.fetched_at;
+        age >= 0 && (age as u64) < self.ttl.as_secs()
+    }
+}
+
+impl Default for ModelListCache {
+    fn default() -> Self {
+        Self::new(Self::default_dir(), DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ModelListCache::new(dir.path().to_path_buf(), DEFAULT_TTL);
+        assert!(cache.load("openai").is_none());
+
+        let entry = CachedModelList::new("https://api.openai.com/v1/models", Some("\"abc\"".into()), vec!["gpt-4o".into()]);
+        cache.store("openai", &entry).unwrap();
+        let loaded = cache.load("openai").unwrap();
+        assert_eq!(loaded.ids, vec!["gpt-4o"]);
+        assert_eq!(loaded.etag.as_deref(), Some("\"abc\""));
+    }
+
+    #[test]
+    fn custom_provider_ids_map_to_flat_file_names() {
+        let cache = ModelListCache::new(PathBuf::from("/tmp/c"), DEFAULT_TTL);
+        assert_eq!(
+            cache.path("custom:https://example.com/v1"),
+            PathBuf::from("/tmp/c/custom_https___example.com_v1.json")
+        );
+    }
+
+    #[test]
+    fn freshness_follows_ttl() {
+        let cache = ModelListCache::new(PathBuf::from("/tmp/c"), Duration::from_secs(60));
+        let mut entry = CachedModelList::new("u", None, vec![]);
+        assert!(cache.is_fresh(&entry));
+        entry.fetched_at -= 120;
+        assert!(!cache.is_fresh(&entry));
+        entry.touch();
+        assert!(cache.is_fresh(&entry));
+    }
+}
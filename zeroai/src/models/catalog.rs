@@ -0,0 +1,375 @@
+//! External model metadata catalog (models.dev format).
+//!
+//! The catalog is downloaded with `zeroai-proxy models update-catalog` and cached at
+//! `~/.zeroai/models-catalog.json`. When present, it is merged over the built-in static
+//! models: known models get fresh context windows, output limits, costs and modalities, and
+//! models the crate doesn't know yet are added for providers with a static catalog (except
+//! subscriptions, see [`SUBSCRIPTION_PROVIDERS`]).
+
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+
+pub const DEFAULT_CATALOG_URL: &str = "https://models.dev/api.json";
+
+/// Catalog provider IDs that differ from zeroai's provider IDs.
+const PROVIDER_ALIASES: &[(&str, &str)] = &[
+    ("openai-codex", "openai"),
+    ("anthropic-setup-token", "anthropic"),
+    ("anthropic-oauth", "anthropic"),
+    ("gemini-cli", "google"),
+    ("antigravity", "google"),
+    ("together", "togetherai"),
+    ("fireworks", "fireworks-ai"),
+    ("moonshot", "moonshotai"),
+    ("qwen", "alibaba"),
+];
+
+/// Subscription logins that serve only some of their catalog entry's models: their known
+/// models get the catalog's metadata, but models they don't list aren't added.
+const SUBSCRIPTION_PROVIDERS: &[&str] = &[
+    "openai-codex",
+    "anthropic-setup-token",
+    "anthropic-oauth",
+    "gemini-cli",
+    "antigravity",
+    "github-copilot",
+    "qwen-portal",
+];
+
+// ---------------------------------------------------------------------------
+// Catalog types
+// ---------------------------------------------------------------------------
+
+/// Provider ID → provider entry, as served by models.dev.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Catalog {
+    pub providers: HashMap<String, CatalogProvider>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogProvider {
+    #[serde(default)]
+    pub models: HashMap<String, CatalogModel>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogModel {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub reasoning: Option<bool>,
+    #[serde(default)]
+    pub tool_call: Option<bool>,
+    #[serde(default)]
+    pub modalities: Option<CatalogModalities>,
+    #[serde(default)]
+    pub cost: Option<CatalogCost>,
+    #[serde(default)]
+    pub limit: Option<CatalogLimit>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogModalities {
+    #[serde(default)]
+    pub input: Vec<String>,
+}
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogCost {
+    #[serde(default)]
+    pub input: f64,
+    #[serde(default)]
+    pub output: f64,
+    #[serde(default)]
+    pub cache_read: Option<f64>,
+    #[serde(default)]
+    pub cache_write: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogLimit {
+    #[serde(default)]
+    pub context: u64,
+    #[serde(default)]
+    pub output: u64,
+}
+
+impl CatalogModel {
+    /// Overwrite `model`'s metadata with the catalog's, keeping fields the catalog lacks.
+    fn apply_to(&self, model: &mut ModelDef) {
+        if let Some(r) = self.reasoning {
+            model.reasoning = r;
+        }
+        if let Some(t) = self.tool_call {
+            model.capabilities.tools = t;
+        }
+        if let Some(m) = &self.modalities {
+            let input: Vec<InputModality> = m
+                .input
+                .iter()
+                .filter_map(|s| match s.as_str() {
+                    "text" => Some(InputModality::Text),
+                    "image" => Some(InputModality::Image),
+                    "audio" => Some(InputModality::Audio),
+                    _ => None,
+                })
+                .collect();
+            if !input.is_empty() {
+                model.input = input;
+            }
+        }
+        if let Some(c) = &self.cost {
+            model.cost = ModelCost {
+                input: c.input,
+                output: c.output,
+                cache_read: c.cache_read.unwrap_or(0.0),
+                cache_write: c.cache_write.unwrap_or(0.0),
+            };
+        }
+        if let Some(l) = &self.limit {
+            if l.context > 0 {
+                model.context_window = l.context;
+            }
+            if l.output > 0 {
+                model.max_tokens = l.output;
+            }
+        }
+    }
+}
+
+impl Catalog {
+    /// Number of models across all providers.
+    pub fn model_count(&self) -> usize {
+        self.providers.values().map(|p| p.models.len()).sum()
+    }
+
+    fn provider(&self, provider: &str) -> Option<&CatalogProvider> {
+        let key = PROVIDER_ALIASES
+            .iter()
+            .find(|(ours, _)| *ours == provider)
+            .map(|(_, theirs)| *theirs)
+            .unwrap_or(provider);
+        self.providers.get(key)
+    }
+
+    /// Merge the catalog over `models` (all belonging to `provider`).
+    ///
+    /// New catalog models are only added when `models` is non-empty, since the API format
+    /// and base URL are taken from the existing entries, and never for subscription
+    /// providers, which can't serve every model of the API they share a catalog entry with.
+    pub fn merge(&self, provider: &str, mut models: Vec<ModelDef>) -> Vec<ModelDef> {
+        let Some(entry) = self.provider(provider) else {
+            return models;
+        };
+        for m in &mut models {
+            if let Some(c) = entry.models.get(&m.id) {
+                c.apply_to(m);
+            }
+        }
+        let Some(template) = models.first().cloned().filter(|_| !SUBSCRIPTION_PROVIDERS.contains(&provider)) else {
+            return models;
+        };
+
+        let mut new_ids: Vec<&String> = entry
+            .models
+            .keys()
+            .filter(|id| !models.iter().any(|m| &m.id == *id))
+            .collect();
+        new_ids.sort();
+        for id in new_ids {
+            let c = &entry.models[id];
+            let mut m = ModelDef {
+                id: id.clone(),
+                name: c.name.clone().unwrap_or_else(|| id.clone()),
+                reasoning: false,
+                input: vec![InputModality::Text],
+                cost: ModelCost::default(),
+                context_window: 128000,
+                max_tokens: 16384,
+                default_max_tokens: None,
+                thinking_budgets: None,
+                ..template.clone()
+            };
+            c.apply_to(&mut m);
+            models.push(m);
+        }
+        models
+    }
+
+    /// Merge over a mixed list of models, grouping by provider.
+    pub fn merge_all(&self, models: Vec<ModelDef>) -> Vec<ModelDef> {
+        let mut order: Vec<String> = Vec::new();
+        let mut by_provider: HashMap<String, Vec<ModelDef>> = HashMap::new();
+        for m in models {
+            if !by_provider.contains_key(&m.provider) {
+                order.push(m.provider.clone());
+            }
+            by_provider.entry(m.provider.clone()).or_default().push(m);
+        }
+        order
+            .into_iter()
+            .flat_map(|p| {
+                let ms = by_provider.remove(&p).unwrap_or_default();
+                self.merge(&p, ms)
+            })
+            .collect()
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Catalog>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Download & cache
+// ---------------------------------------------------------------------------
+
+/// Default cache location (`models-catalog.json` in the config root, ~/.zeroai/models-catalog.json).
+pub fn default_cache_path() -> PathBuf {
+    crate::auth::config::config_dir().join("models-catalog.json")
+}
+
+/// Download a catalog from `url`.
+pub async fn download(url: &str) -> anyhow::Result<Catalog> {
+    let resp = reqwest::Client::new().get(url).timeout(Duration::from_secs(30)).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        anyhow::bail!("Catalog download failed: {}", status);
+    }
+    Ok(resp.json().await?)
+}
+
+/// Download the catalog, write it to `path` and use it for the rest of the process.
+pub async fn update_catalog(url: &str, path: &Path) -> anyhow::Result<Catalog> {
+    let catalog = download(url).await?;
+    catalog.save(path)?;
+    install(catalog.clone());
+    Ok(catalog)
+}
+
+/// The catalog in use: loaded lazily from the default cache path.
+static ACTIVE: LazyLock<RwLock<Option<Arc<Catalog>>>> = LazyLock::new(|| {
+    let catalog = match Catalog::load(&default_cache_path()) {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable model catalog: {}", e);
+            None
+        }
+    };
+    RwLock::new(catalog.map(Arc::new))
+});
+
+/// The active catalog, if one has been downloaded.
+pub fn active() -> Option<Arc<Catalog>> {
+    ACTIVE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replace the active catalog for this process.
+pub fn install(catalog: Catalog) {
+    *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(catalog));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Catalog {
+        serde_json::from_value(serde_json::json!({
+            "anthropic": {
+                "id": "anthropic",
+                "models": {
+                    "claude-sonnet-4-5": {
+                        "id": "claude-sonnet-4-5",
+                        "name": "Claude Sonnet 4.5",
+                        "reasoning": true,
+                        "modalities": { "input": ["text", "image", "pdf"], "output": ["text"] },
+                        "cost": { "input": 3, "output": 15, "cache_read": 0.3 },
+                        "limit": { "context": 200000, "output": 64000 }
+                    },
+                    "claude-new-model": {
+                        "name": "Claude New",
+                        "limit": { "context": 500000, "output": 100000 }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn model(provider: &str, id: &str) -> ModelDef {
+        ModelDef {
+            id: id.into(),
+            name: id.into(),
+            api: Api::AnthropicMessages,
+            provider: provider.into(),
+            base_url: "https://api.anthropic.com".into(),
+            reasoning: false,
+            input: vec![InputModality::Text],
+            context_window: 100000,
+            max_tokens: 8192,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merges_metadata_and_adds_new_models() {
+        let merged = sample().merge("anthropic", vec![model("anthropic", "claude-sonnet-4-5")]);
+        assert_eq!(merged.len(), 2);
+
+        let known = &merged[0];
+        assert!(known.reasoning);
+        assert_eq!(known.context_window, 200000);
+        assert_eq!(known.max_tokens, 64000);
+        assert_eq!(known.cost.cache_read, 0.3);
+        assert_eq!(known.input, vec![InputModality::Text, InputModality::Image]);
+        assert_eq!(known.name, "claude-sonnet-4-5");
+
+        let added = &merged[1];
+        assert_eq!(added.id, "claude-new-model");
+        assert_eq!(added.name, "Claude New");
+        assert_eq!(added.api, Api::AnthropicMessages);
+        assert_eq!(added.context_window, 500000);
+    }
+
+    #[test]
+    fn aliases_and_unknown_providers() {
+        // Subscriptions get the metadata of their models, but no models they can't serve.
+        let merged = sample().merge("anthropic-oauth", vec![model("anthropic-oauth", "claude-sonnet-4-5")]);
+        assert_eq!(merged[0].max_tokens, 64000);
+        assert_eq!(merged.len(), 1);
+
+        let untouched = sample().merge("deepseek", vec![model("deepseek", "deepseek-chat")]);
+        assert_eq!(untouched.len(), 1);
+        assert_eq!(untouched[0].context_window, 100000);
+
+        assert!(sample().merge("anthropic", vec![]).is_empty());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.json");
+        assert!(Catalog::load(&path).unwrap().is_none());
+        sample().save(&path).unwrap();
+        let loaded = Catalog::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.model_count(), 2);
+    }
+}
@@ -4,6 +4,7 @@
 //! Base URLs come from auth::provider_base_url (single source for API and models).
 
 use crate::auth;
+use crate::models::cache::{CachedModelList, ModelListCache};
 use crate::models::static_models::static_models_for_provider;
 use crate::types::*;
 use reqwest::Client;
@@ -64,8 +65,174 @@ struct OllamaTagsResponse {
 #[derive(Debug, Deserialize)]
 struct OllamaModel {
     name: String,
+    #[serde(default)]
+    details: OllamaModelDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OllamaModelDetails {
+    /// e.g. `8.0B`
+    #[serde(default)]
+    parameter_size: Option<String>,
+    /// e.g. `Q4_K_M`
+    #[serde(default)]
+    quantization_level: Option<String>,
+    /// Architectures in the model; a `clip` or `mllama` projector means it takes images.
+    #[serde(default)]
+    families: Option<Vec<String>>,
+}
+
+/// Hugging Face router /v1/models response: each model with the inference providers serving it.
+#[derive(Debug, Deserialize)]
+struct HfModelsResponse {
+    #[serde(default)]
+    data: Vec<HfModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HfModel {
+    id: String,
+    #[serde(default)]
+    providers: Vec<HfProvider>,
+    #[serde(default)]
+    architecture: Option<HfArchitecture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HfArchitecture {
+    #[serde(default)]
+    input_modalities: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HfProvider {
+    /// e.g. `novita`, `sambanova`
+    provider: String,
+    /// `live`, or `staging` while the provider isn't serving the model yet.
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    context_length: Option<u64>,
+    /// USD per million tokens.
+    #[serde(default)]
+    pricing: Option<HfPricing>,
+    #[serde(default)]
+    supports_tools: Option<bool>,
+    #[serde(default)]
+    supports_structured_output: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+struct HfPricing {
+    #[serde(default)]
+    input: f64,
+    #[serde(default)]
+    output: f64,
+}
+
+/// OpenRouter /models response, with pricing, limits and supported request parameters.
+#[derive(Debug, Deserialize)]
+struct OpenRouterModelsResponse {
+    #[serde(default)]
+    data: Vec<OpenRouterModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterModel {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    context_length: Option<u64>,
+    #[serde(default)]
+    architecture: Option<OpenRouterArchitecture>,
+    #[serde(default)]
+    pricing: Option<OpenRouterPricing>,
+    #[serde(default)]
+    top_provider: Option<OpenRouterTopProvider>,
+    /// e.g. `tools`, `response_format`, `reasoning`
+    #[serde(default)]
+    supported_parameters: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterArchitecture {
+    #[serde(default)]
+    input_modalities: Vec<String>,
+}
+
+/// USD per token, as decimal strings (`"0.0000025"`); `"-1"` when the price varies by route.
+#[derive(Debug, Deserialize)]
+struct OpenRouterPricing {
+    #[serde(default)]
+    prompt: Option<String>,
+    #[serde(default)]
+    completion: Option<String>,
+    #[serde(default)]
+    input_cache_read: Option<String>,
+    #[serde(default)]
+    input_cache_write: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterTopProvider {
+    #[serde(default)]
+    context_length: Option<u64>,
+    #[serde(default)]
+    max_completion_tokens: Option<u64>,
+}
+
+/// An OpenAI-compatible `/models` list: `{"data": [...]}`, or a bare array (Together).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CompatibleModelList {
+    Data { data: Vec<CompatibleModelEntry> },
+    Bare(Vec<CompatibleModelEntry>),
+}
+
+/// A `/models` entry with the metadata Together, Fireworks and Groq add to the OpenAI shape.
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompatibleModelEntry {
+    id: String,
+    /// Together
+    #[serde(default)]
+    display_name: Option<String>,
+    /// Together: `chat`, `language`, `code`, `embedding`, `image`, `rerank`, ...
+    #[serde(default, rename = "type")]
+    model_type: Option<String>,
+    /// `context_length` on Together and Fireworks, `context_window` on Groq.
+    #[serde(default, alias = "context_window")]
+    context_length: Option<u64>,
+    /// Groq
+    #[serde(default)]
+    max_completion_tokens: Option<u64>,
+    /// Together, in USD per million tokens.
+    #[serde(default)]
+    pricing: Option<CompatiblePricing>,
+    /// Fireworks
+    #[serde(default)]
+    supports_chat: Option<bool>,
+    #[serde(default)]
+    supports_image_input: Option<bool>,
+    #[serde(default)]
+    supports_tools: Option<bool>,
+    /// Groq
+    #[serde(default)]
+    active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompatiblePricing {
+    #[serde(default)]
+    input: f64,
+    #[serde(default)]
+    output: f64,
+}
+
+/// Providers whose model list carries metadata the id-only list cache can't keep: fetched
+/// with their own parser every time.
+const RICH_MODEL_LISTS: &[&str] = &["ollama", "huggingface", "openrouter", "together", "fireworks", "groq"];
+
 /// Returns true if the provider is a custom OpenAI-compatible one (dynamic model list).
 pub fn is_custom_provider(provider: &str) -> bool {
     provider.starts_with("custom:")
@@ -74,8 +241,8 @@ pub fn is_custom_provider(provider: &str) -> bool {
 /// Providers that have a base_url but do not expose OpenAI-compatible GET /models (proprietary API).
 /// openai-codex: OAuth token lacks api.model.read; only static model list is used.
 const STATIC_ONLY_PROVIDERS: &[&str] = &[
-    "google", "anthropic", "anthropic-setup-token", "synthetic", "cloudflare-ai-gateway",
-    "github-copilot", "amazon-bedrock", "openai-codex", "qwen-portal",
+    "google", "anthropic", "anthropic-setup-token", "anthropic-oauth", "synthetic", "cloudflare-ai-gateway",
+    "github-copilot", "amazon-bedrock", "openai-codex", "qwen-portal", "cohere", "jina",
 ];
 
 /// Returns true if a provider supports dynamic model listing (GET /models).
@@ -102,11 +269,12 @@ pub fn default_model_def_for_provider(provider: &str, model_id: &str) -> Option<
         provider: provider.to_string(),
         base_url,
         reasoning: looks_like_reasoning_model(model_id),
-        input: vec![InputModality::Text],
-        cost: ModelCost::default(),
+        // Unknown input modalities are assumed to include images, so images aren't stripped
+        // from requests to a model that may well accept them.
+        input: vec![InputModality::Text, InputModality::Image],
         context_window: 128000,
         max_tokens: 16384,
-        headers: None,
+        ..Default::default()
     })
 }
 
@@ -134,11 +302,22 @@ pub async fn fetch_models_for_provider(
                 _ => format!("{}/models", base_url),
             };
 
-            let dynamic_result = if provider == "ollama" {
-                fetch_ollama_models(base_url, api_key).await
-            } else {
-                fetch_openai_compatible_models(&url, api_key).await
-            };
+            if provider == "ollama" {
+                return fetch_ollama_models(base_url, api_key).await;
+            }
+            if provider == "huggingface" {
+                let parsed: HfModelsResponse = get_json(&url, api_key, "Hugging Face").await?;
+                return Ok(parsed.data.iter().flat_map(|m| huggingface_model_defs(base_url, m)).collect());
+            }
+            if provider == "openrouter" {
+                let parsed: OpenRouterModelsResponse = get_json(&url, api_key, "OpenRouter").await?;
+                return Ok(parsed.data.iter().map(|m| openrouter_model_def(base_url, m)).collect());
+            }
+            if ["together", "fireworks", "groq"].contains(&provider) {
+                let list: CompatibleModelList = get_json(&url, api_key, provider).await?;
+                return Ok(compatible_model_defs(provider, base_url, list));
+            }
+            let dynamic_result = fetch_openai_compatible_models(&url, api_key).await;
 
             match dynamic_result {
                 Ok(ids) => return Ok(merge_dynamic_with_static(provider, base_url, &ids)),
@@ -152,17 +331,121 @@ pub async fn fetch_models_for_provider(
     Ok(static_models_for_provider(provider))
 }
 
+/// Fetch models for a provider, going through the on-disk model list cache.
+///
+/// A cached list younger than the TTL is returned without a request. Otherwise the list is
+/// revalidated (sending `If-None-Match` when an ETag is cached) and the cache updated. If the
+/// provider can't be reached, a stale cached list is returned so the TUI works offline; HTTP
+/// errors (bad credentials, wrong URL) are still reported. `refresh` skips the TTL check.
+///
+/// Providers without a dynamic model list behave exactly as [`fetch_models_for_provider`], and
+/// so do Ollama, Hugging Face, OpenRouter, Together, Fireworks and Groq: their lists carry
+/// metadata the cache can't (Ollama's is local and cheap to fetch, Hugging Face's says which
+/// inference providers are live right now, and the others have each model's limits or prices).
+pub async fn fetch_models_cached(
+    provider: &str,
+    api_key: Option<&str>,
+    models_url: Option<&str>,
+    refresh: bool,
+) -> Result<Vec<ModelDef>, FetchError> {
+    let Some((base_url, url)) = dynamic_models_source(provider, models_url).filter(|_| !RICH_MODEL_LISTS.contains(&provider))
+    else {
+        return fetch_models_for_provider(provider, api_key, models_url).await;
+    };
+
+    let cache = ModelListCache::default();
+    let cached = cache.load(provider).filter(|e| e.url == url);
+    if !refresh
+        && let Some(entry) = &cached
+        && cache.is_fresh(entry)
+    {
+        return Ok(models_from_ids(provider, &base_url, &entry.ids));
+    }
+
+    let etag = cached.as_ref().and_then(|e| e.etag.as_deref());
+    let result = request_model_ids(&url, api_key, etag).await;
+
+    let entry = match (result, cached) {
+        (Ok(ModelIdsResponse::Ids { ids, etag }), _) => CachedModelList::new(&url, etag, ids),
+        (Ok(ModelIdsResponse::NotModified), Some(mut entry)) => {
+            entry.touch();
+            entry
+        }
+        (Ok(ModelIdsResponse::NotModified), None) => {
+            return Err(FetchError {
+                status: Some(304),
+                message: "Not Modified without a cached model list".into(),
+            });
+        }
+        (Err(e), Some(entry)) if e.status.is_none() => {
+            tracing::warn!("Using cached model list for {}: {}", provider, e);
+            return Ok(models_from_ids(provider, &base_url, &entry.ids));
+        }
+        (Err(e), _) => return Err(e),
+    };
+
+    if let Err(e) = cache.store(provider, &entry) {
+        tracing::warn!("Failed to write model list cache for {}: {}", provider, e);
+    }
+    Ok(models_from_ids(provider, &base_url, &entry.ids))
+}
+
+/// `(base_url, models_url)` for providers whose model list is fetched dynamically.
+fn dynamic_models_source(provider: &str, models_url: Option<&str>) -> Option<(String, String)> {
+    let (base_url, default_url) = if is_custom_provider(provider) {
+        let base = provider.strip_prefix("custom:").unwrap_or("").trim().trim_end_matches('/');
+        if !base.starts_with("http://") && !base.starts_with("https://") {
+            return None;
+        }
+        (base.to_string(), format!("{}/v1/models", base))
+    } else if supports_dynamic_models(provider) {
+        let base = auth::provider_base_url(provider)?;
+        (base.to_string(), format!("{}/models", base))
+    } else {
+        return None;
+    };
+    let url = match models_url {
+        Some(u) if !u.trim().is_empty() => u.trim().to_string(),
+        _ => default_url,
+    };
+    Some((base_url, url))
+}
+
+fn models_from_ids(provider: &str, base_url: &str, ids: &[String]) -> Vec<ModelDef> {
+    if is_custom_provider(provider) {
+        ids.iter().map(|id| default_model(provider, base_url, id)).collect()
+    } else {
+        merge_dynamic_with_static(provider, base_url, ids)
+    }
+}
+
+/// Result of a (possibly conditional) GET /models request.
+enum ModelIdsResponse {
+    NotModified,
+    Ids { ids: Vec<String>, etag: Option<String> },
+}
+
 /// Fetch model IDs from an OpenAI-compatible /models endpoint.
 async fn fetch_openai_compatible_models(url: &str, api_key: Option<&str>) -> Result<Vec<String>, FetchError> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| FetchError { status: None, message: format!("HTTP client error: {}", e) })?;
+    match request_model_ids(url, api_key, None).await? {
+        ModelIdsResponse::Ids { ids, .. } => Ok(ids),
+        ModelIdsResponse::NotModified => Ok(Vec::new()),
+    }
+}
 
-    let mut req = client.get(url);
+/// GET an OpenAI-compatible /models endpoint, sending `If-None-Match` when `etag` is given.
+async fn request_model_ids(
+    url: &str,
+    api_key: Option<&str>,
+    etag: Option<&str>,
+) -> Result<ModelIdsResponse, FetchError> {
+    let mut req = Client::new().get(url).timeout(Duration::from_secs(10));
     if let Some(key) = api_key {
         req = req.header("Authorization", format!("Bearer {}", key));
     }
+    if let Some(tag) = etag {
+        req = req.header("If-None-Match", tag);
+    }
 
     let resp = req.send().await.map_err(|e| FetchError {
         status: None,
@@ -170,6 +453,9 @@ async fn fetch_openai_compatible_models(url: &str, api_key: Option<&str>) -> Res
     })?;
 
     let status = resp.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(ModelIdsResponse::NotModified);
+    }
     if !status.is_success() {
         let code = status.as_u16();
         let body = resp.text().await.unwrap_or_default();
@@ -177,6 +463,12 @@ async fn fetch_openai_compatible_models(url: &str, api_key: Option<&str>) -> Res
         return Err(FetchError { status: Some(code), message: msg });
     }
 
+    let etag = resp
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
     let body = resp.text().await.map_err(|e| FetchError {
         status: None,
         message: format!("Failed to read response body: {}", e),
@@ -187,21 +479,20 @@ async fn fetch_openai_compatible_models(url: &str, api_key: Option<&str>) -> Res
         message: format!("Invalid models list JSON: {}", e),
     })?;
 
-    Ok(parsed.data.into_iter().map(|e| e.id).collect())
+    Ok(ModelIdsResponse::Ids {
+        ids: parsed.data.into_iter().map(|e| e.id).collect(),
+        etag,
+    })
 }
 
-/// Fetch model names from Ollama's native /api/tags endpoint.
-async fn fetch_ollama_models(base_url: &str, api_key: Option<&str>) -> Result<Vec<String>, FetchError> {
+/// Fetch the installed models from Ollama's native /api/tags endpoint, with their parameter
+/// size and quantization in the name and image input for vision models.
+async fn fetch_ollama_models(base_url: &str, api_key: Option<&str>) -> Result<Vec<ModelDef>, FetchError> {
     // Ollama's native API lives at the root, not under /v1
     let api_base = base_url.trim_end_matches("/v1").trim_end_matches('/');
     let url = format!("{}/api/tags", api_base);
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .map_err(|e| FetchError { status: None, message: format!("HTTP client error: {}", e) })?;
-
-    let mut req = client.get(&url);
+    let mut req = Client::new().get(&url).timeout(Duration::from_secs(5));
     if let Some(key) = api_key {
         req = req.header("Authorization", format!("Bearer {}", key));
     }
@@ -214,7 +505,8 @@ async fn fetch_ollama_models(base_url: &str, api_key: Option<&str>) -> Result<Ve
     if !resp.status().is_success() {
         // Fall back to OpenAI-compatible endpoint
         let compat_url = format!("{}/models", base_url);
-        return fetch_openai_compatible_models(&compat_url, api_key).await;
+        let ids = fetch_openai_compatible_models(&compat_url, api_key).await?;
+        return Ok(merge_dynamic_with_static("ollama", base_url, &ids));
     }
 
     let body = resp.text().await.map_err(|e| FetchError {
@@ -227,7 +519,188 @@ async fn fetch_ollama_models(base_url: &str, api_key: Option<&str>) -> Result<Ve
         message: format!("Invalid Ollama tags JSON: {}", e),
     })?;
 
-    Ok(parsed.models.into_iter().map(|m| m.name).collect())
+    Ok(parsed.models.iter().map(|m| ollama_model_def(base_url, m)).collect())
+}
+
+fn ollama_model_def(base_url: &str, model: &OllamaModel) -> ModelDef {
+    let mut def = default_model("ollama", base_url, &model.name);
+    let details = &model.details;
+    let size: Vec<&str> = [&details.parameter_size, &details.quantization_level]
+        .into_iter()
+        .filter_map(|d| d.as_deref().filter(|d| !d.is_empty()))
+        .collect();
+    if !size.is_empty() {
+        def.name = format!("{} ({})", model.name, size.join(", "));
+    }
+    let families = details.families.as_deref().unwrap_or_default();
+    if !families.iter().any(|f| f == "clip" || f == "mllama") {
+        def.input = vec![InputModality::Text];
+    }
+    def
+}
+
+/// GET a provider's model list and parse it as `T` (`provider` names it in errors).
+async fn get_json<T: serde::de::DeserializeOwned>(url: &str, api_key: Option<&str>, provider: &str) -> Result<T, FetchError> {
+    let mut req = Client::new().get(url).timeout(Duration::from_secs(10));
+    if let Some(key) = api_key {
+        req = req.header("Authorization", format!("Bearer {}", key));
+    }
+
+    let resp = req.send().await.map_err(|e| FetchError {
+        status: None,
+        message: format!("Failed to fetch {} models: {}", provider, e),
+    })?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(FetchError {
+            status: Some(status.as_u16()),
+            message: crate::providers::api_error::ApiError::parse(&body).message,
+        });
+    }
+
+    resp.json().await.map_err(|e| FetchError {
+        status: None,
+        message: format!("Invalid {} models JSON: {}", provider, e),
+    })
+}
+
+/// An OpenRouter model with its prices (converted to USD per million tokens), context window,
+/// output limit, input modalities and the capabilities its `supported_parameters` name.
+fn openrouter_model_def(base_url: &str, model: &OpenRouterModel) -> ModelDef {
+    let mut def = default_model("openrouter", base_url, &model.id);
+    if let Some(name) = &model.name {
+        def.name = name.clone();
+    }
+    let top = model.top_provider.as_ref();
+    if let Some(context) = model.context_length.or(top.and_then(|t| t.context_length)) {
+        def.context_window = context;
+    }
+    def.max_tokens = top.and_then(|t| t.max_completion_tokens).unwrap_or(def.max_tokens).min(def.context_window);
+    if let Some(pricing) = &model.pricing {
+        let per_million = |price: &Option<String>| {
+            price.as_deref().and_then(|p| p.parse::<f64>().ok()).filter(|p| *p > 0.0).map_or(0.0, |p| p * 1_000_000.0)
+        };
+        def.cost = ModelCost {
+            input: per_million(&pricing.prompt),
+            output: per_million(&pricing.completion),
+            cache_read: per_million(&pricing.input_cache_read),
+            cache_write: per_million(&pricing.input_cache_write),
+        };
+    }
+    if let Some(arch) = &model.architecture {
+        def.input = vec![InputModality::Text];
+        for (modality, input) in [("image", InputModality::Image), ("audio", InputModality::Audio)] {
+            if arch.input_modalities.iter().any(|m| m == modality) {
+                def.input.push(input);
+            }
+        }
+    }
+    let supports = |param: &str| model.supported_parameters.iter().any(|p| p == param);
+    if !model.supported_parameters.is_empty() {
+        def.reasoning = supports("reasoning");
+        def.capabilities.tools = supports("tools");
+        def.capabilities.json_mode = supports("response_format") || supports("structured_outputs");
+    }
+    def
+}
+
+/// A router model as its bare ID, which the router sends to one of its live providers, plus
+/// `<id>:<provider>` for each of them. The bare ID gets the smallest context window, the
+/// highest price and the capabilities all of them share. Models with no live provider are
+/// left out.
+fn huggingface_model_defs(base_url: &str, model: &HfModel) -> Vec<ModelDef> {
+    let live: Vec<&HfProvider> = model
+        .providers
+        .iter()
+        .filter(|p| p.status.as_deref().is_none_or(|s| s == "live"))
+        .collect();
+    if live.is_empty() {
+        return Vec::new();
+    }
+    let images = model
+        .architecture
+        .as_ref()
+        .map(|a| a.input_modalities.iter().any(|m| m == "image"));
+    let def = |id: String, name: String, providers: &[&HfProvider]| {
+        let mut def = default_model("huggingface", base_url, &id);
+        def.name = name;
+        if images == Some(false) {
+            def.input = vec![InputModality::Text];
+        }
+        if let Some(context) = providers.iter().filter_map(|p| p.context_length).min() {
+            def.context_window = context;
+            def.max_tokens = def.max_tokens.min(context);
+        }
+        for pricing in providers.iter().filter_map(|p| p.pricing.as_ref()) {
+            def.cost.input = def.cost.input.max(pricing.input);
+            def.cost.output = def.cost.output.max(pricing.output);
+        }
+        def.capabilities.tools = providers.iter().all(|p| p.supports_tools != Some(false));
+        def.capabilities.json_mode = providers.iter().all(|p| p.supports_structured_output == Some(true));
+        def
+    };
+
+    let mut defs = vec![def(model.id.clone(), model.id.clone(), &live)];
+    defs.extend(live.iter().map(|p| {
+        def(format!("{}:{}", model.id, p.provider), format!("{} via {}", model.id, p.provider), &[*p])
+    }));
+    defs
+}
+
+/// Models of an OpenAI-compatible list, starting from the static catalog entry (or defaults) and
+/// taking the context window, output limit, prices, tool support and image input from the list
+/// where it reports them. Models that can't chat are left out: Together's embedding, image and
+/// rerank models, Fireworks' non-chat models and Groq's inactive ones.
+pub(crate) fn compatible_model_defs(provider: &str, base_url: &str, list: CompatibleModelList) -> Vec<ModelDef> {
+    let entries = match list {
+        CompatibleModelList::Data { data } => data,
+        CompatibleModelList::Bare(entries) => entries,
+    };
+    let static_models = static_models_for_provider(provider);
+
+    entries
+        .into_iter()
+        .filter(|e| {
+            e.model_type.as_deref().is_none_or(|t| ["chat", "language", "code"].contains(&t))
+                && e.supports_chat != Some(false)
+                && e.active != Some(false)
+        })
+        .map(|entry| {
+            let mut def = match static_models.iter().find(|m| m.id == entry.id) {
+                Some(m) => m.clone(),
+                None => {
+                    let mut def = default_model(provider, base_url, &entry.id);
+                    if let Some(name) = entry.display_name {
+                        def.name = name;
+                    }
+                    def
+                }
+            };
+            def.base_url = base_url.to_string();
+            if let Some(context) = entry.context_length.filter(|c| *c > 0) {
+                def.context_window = context;
+            }
+            if let Some(max) = entry.max_completion_tokens.filter(|m| *m > 0) {
+                def.max_tokens = max;
+            }
+            def.max_tokens = def.max_tokens.min(def.context_window);
+            if let Some(pricing) = entry.pricing.filter(|p| p.input > 0.0 || p.output > 0.0) {
+                def.cost.input = pricing.input;
+                def.cost.output = pricing.output;
+            }
+            if let Some(tools) = entry.supports_tools {
+                def.capabilities.tools = tools;
+            }
+            match entry.supports_image_input {
+                Some(true) if !def.input.contains(&InputModality::Image) => def.input.push(InputModality::Image),
+                Some(false) => def.input.retain(|m| *m != InputModality::Image),
+                _ => {}
+            }
+            def
+        })
+        .collect()
 }
 
 /// Merge dynamically discovered model IDs with the static catalog.
@@ -246,24 +719,28 @@ fn merge_dynamic_with_static(provider: &str, base_url: &str, dynamic_ids: &[Stri
             if let Some(s) = static_map.get(id.as_str()) {
                 (*s).clone()
             } else {
-                ModelDef {
-                    id: id.clone(),
-                    name: id.clone(),
-                    api: Api::OpenaiCompletions,
-                    provider: provider.to_string(),
-                    base_url: base_url.to_string(),
-                    reasoning: looks_like_reasoning_model(id),
-                    input: vec![InputModality::Text],
-                    cost: ModelCost::default(),
-                    context_window: 128000,
-                    max_tokens: 16384,
-                    headers: None,
-                }
+                default_model(provider, base_url, id)
             }
         })
         .collect()
 }
 
+/// Defaults for a dynamically discovered model with no static metadata.
+fn default_model(provider: &str, base_url: &str, id: &str) -> ModelDef {
+    ModelDef {
+        id: id.to_string(),
+        name: id.to_string(),
+        api: Api::OpenaiCompletions,
+        provider: provider.to_string(),
+        base_url: base_url.to_string(),
+        reasoning: looks_like_reasoning_model(id),
+        input: vec![InputModality::Text, InputModality::Image],
+        context_window: 128000,
+        max_tokens: 16384,
+        ..Default::default()
+    }
+}
+
 /// Heuristic: model IDs containing these substrings likely support reasoning/thinking.
 fn looks_like_reasoning_model(id: &str) -> bool {
     let lower = id.to_lowercase();
@@ -292,25 +769,7 @@ async fn fetch_custom_provider(
     };
 
     let ids = fetch_openai_compatible_models(&url, api_key).await?;
-
-    let models = ids
-        .into_iter()
-        .map(|id| ModelDef {
-            name: id.clone(),
-            reasoning: looks_like_reasoning_model(&id),
-            id,
-            api: Api::OpenaiCompletions,
-            provider: provider.to_string(),
-            base_url: base_url.to_string(),
-            input: vec![InputModality::Text],
-            cost: ModelCost::default(),
-            context_window: 128000,
-            max_tokens: 16384,
-            headers: None,
-        })
-        .collect();
-
-    Ok(models)
+    Ok(models_from_ids(provider, base_url, &ids))
 }
 
 #[cfg(test)]
@@ -355,6 +814,116 @@ mod tests {
         assert_eq!(parsed.models[0].name, "llama3:latest");
     }
 
+    #[test]
+    fn ollama_details_map_into_model_defs() {
+        let json = r#"{"models":[
+            {"name":"qwen3:32b","details":{"family":"qwen3","families":["qwen3"],"parameter_size":"32.8B","quantization_level":"Q4_K_M"}},
+            {"name":"llava:7b","details":{"families":["llama","clip"],"parameter_size":"7B","quantization_level":""}}
+        ]}"#;
+        let parsed: OllamaTagsResponse = serde_json::from_str(json).unwrap();
+        let defs: Vec<ModelDef> = parsed.models.iter().map(|m| ollama_model_def("http://127.0.0.1:11434/v1", m)).collect();
+        assert_eq!((defs[0].id.as_str(), defs[0].name.as_str()), ("qwen3:32b", "qwen3:32b (32.8B, Q4_K_M)"));
+        assert_eq!(defs[0].input, vec![InputModality::Text]);
+        assert_eq!(defs[1].name, "llava:7b (7B)");
+        assert!(defs[1].input.contains(&InputModality::Image));
+    }
+
+    #[test]
+    fn huggingface_models_list_each_live_provider() {
+        let json = r#"{"object":"list","data":[
+            {"id":"deepseek-ai/DeepSeek-R1","providers":[
+                {"provider":"novita","status":"live","context_length":64000,"pricing":{"input":0.7,"output":2.5},"supports_tools":false},
+                {"provider":"sambanova","status":"live","context_length":32000,"pricing":{"input":5.0,"output":7.0},"supports_tools":true},
+                {"provider":"nebius","status":"staging","context_length":128000}
+            ]},
+            {"id":"Qwen/Qwen2.5-VL-7B-Instruct","architecture":{"input_modalities":["text","image"]},"providers":[
+                {"provider":"hyperbolic","status":"live","supports_tools":true,"supports_structured_output":true}
+            ]},
+            {"id":"gone/model","providers":[{"provider":"novita","status":"staging"}]}
+        ]}"#;
+        let parsed: HfModelsResponse = serde_json::from_str(json).unwrap();
+        let defs: Vec<ModelDef> = parsed.data.iter().flat_map(|m| huggingface_model_defs("https://router.huggingface.co/v1", m)).collect();
+        let ids: Vec<&str> = defs.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, [
+            "deepseek-ai/DeepSeek-R1",
+            "deepseek-ai/DeepSeek-R1:novita",
+            "deepseek-ai/DeepSeek-R1:sambanova",
+            "Qwen/Qwen2.5-VL-7B-Instruct",
+            "Qwen/Qwen2.5-VL-7B-Instruct:hyperbolic",
+        ]);
+        // Any live provider may serve the bare ID.
+        assert_eq!((defs[0].context_window, defs[0].cost.output, defs[0].capabilities.tools), (32000, 7.0, false));
+        assert_eq!((defs[1].name.as_str(), defs[1].context_window, defs[1].cost.input), ("deepseek-ai/DeepSeek-R1 via novita", 64000, 0.7));
+        assert!(defs[2].capabilities.tools);
+        assert!(defs[0].supports_vision(), "no architecture listed, so the modalities are unknown");
+        assert!(defs[3].input.contains(&InputModality::Image) && defs[4].capabilities.json_mode);
+    }
+
+    #[test]
+    fn openrouter_metadata_maps_into_model_defs() {
+        let json = r#"{"data":[
+            {"id":"openai/gpt-4o","name":"OpenAI: GPT-4o","context_length":128000,
+             "architecture":{"modality":"text+image->text","input_modalities":["text","image","file"]},
+             "pricing":{"prompt":"0.0000025","completion":"0.00001","input_cache_read":"0.00000125"},
+             "top_provider":{"context_length":128000,"max_completion_tokens":16384},
+             "supported_parameters":["max_tokens","tools","tool_choice","response_format"]},
+            {"id":"openrouter/auto","context_length":2000000,"pricing":{"prompt":"-1","completion":"-1"},
+             "supported_parameters":["reasoning","include_reasoning"]}
+        ]}"#;
+        let parsed: OpenRouterModelsResponse = serde_json::from_str(json).unwrap();
+        let defs: Vec<ModelDef> = parsed.data.iter().map(|m| openrouter_model_def("https://openrouter.ai/api/v1", m)).collect();
+
+        let gpt = &defs[0];
+        assert_eq!((gpt.name.as_str(), gpt.context_window, gpt.max_tokens), ("OpenAI: GPT-4o", 128000, 16384));
+        assert_eq!((gpt.cost.input, gpt.cost.output, gpt.cost.cache_read), (2.5, 10.0, 1.25));
+        assert_eq!(gpt.input, vec![InputModality::Text, InputModality::Image]);
+        assert!(gpt.capabilities.tools && gpt.capabilities.json_mode && !gpt.reasoning);
+
+        // Variable prices aren't known up front.
+        let auto = &defs[1];
+        assert_eq!((auto.cost.input, auto.context_window), (0.0, 2000000));
+        assert!(auto.reasoning && !auto.capabilities.tools);
+    }
+
+    #[test]
+    fn compatible_lists_carry_limits_and_prices() {
+        let together = r#"[
+            {"id":"deepseek-ai/DeepSeek-R1","type":"chat","context_length":163840,"pricing":{"hourly":0,"input":3,"output":7}},
+            {"id":"Qwen/Qwen2.5-72B-Instruct-Turbo","display_name":"Qwen2.5 72B Instruct Turbo","type":"chat",
+             "context_length":32768,"pricing":{"input":1.2,"output":1.2}},
+            {"id":"BAAI/bge-large-en-v1.5","type":"embedding","context_length":512}
+        ]"#;
+        let list: CompatibleModelList = serde_json::from_str(together).unwrap();
+        let defs = compatible_model_defs("together", "https://api.together.xyz/v1", list);
+        assert_eq!(defs.len(), 2);
+        // The catalog entry keeps its metadata, with the live context window.
+        assert!(defs[0].reasoning);
+        assert_eq!(defs[0].context_window, 163840);
+        let qwen = &defs[1];
+        assert_eq!((qwen.name.as_str(), qwen.context_window, qwen.max_tokens), ("Qwen2.5 72B Instruct Turbo", 32768, 16384));
+        assert_eq!((qwen.cost.input, qwen.cost.output), (1.2, 1.2));
+
+        let groq = r#"{"object":"list","data":[
+            {"id":"llama-3.1-8b-instant","owned_by":"Meta","active":true,"context_window":131072,"max_completion_tokens":131072},
+            {"id":"retired-model","active":false,"context_window":8192}
+        ]}"#;
+        let list: CompatibleModelList = serde_json::from_str(groq).unwrap();
+        let defs = compatible_model_defs("groq", "https://api.groq.com/openai/v1", list);
+        assert_eq!(defs.len(), 1);
+        assert_eq!((defs[0].context_window, defs[0].max_tokens), (131072, 131072));
+
+        let fireworks = r#"{"data":[
+            {"id":"accounts/fireworks/models/llama4-maverick-instruct-basic","supports_chat":true,
+             "supports_image_input":true,"supports_tools":true,"context_length":1048576},
+            {"id":"accounts/fireworks/models/nomic-embed-text-v1","supports_chat":false}
+        ]}"#;
+        let list: CompatibleModelList = serde_json::from_str(fireworks).unwrap();
+        let defs = compatible_model_defs("fireworks", "https://api.fireworks.ai/inference/v1", list);
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].input, vec![InputModality::Text, InputModality::Image]);
+        assert!(defs[0].capabilities.tools);
+    }
+
     #[test]
     fn fallback_url_when_models_url_none() {
         let provider = "custom:https://api.example.com";
@@ -363,6 +932,18 @@ mod tests {
         assert_eq!(url, "https://api.example.com/v1/models");
     }
 
+    #[test]
+    fn dynamic_models_source_resolves_urls() {
+        assert_eq!(
+            dynamic_models_source("custom:https://api.example.com/", None),
+            Some(("https://api.example.com".into(), "https://api.example.com/v1/models".into()))
+        );
+        let (_, url) = dynamic_models_source("openai", Some(" https://proxy.local/models ")).unwrap();
+        assert_eq!(url, "https://proxy.local/models");
+        assert!(dynamic_models_source("anthropic", None).is_none());
+        assert!(dynamic_models_source("custom:not-a-url", None).is_none());
+    }
+
     #[test]
     fn fetch_error_is_auth_error() {
         assert!(FetchError { status: Some(401), message: String::new() }.is_auth_error());
@@ -408,6 +989,8 @@ mod tests {
         // gpt-new-model gets defaults
         assert_eq!(merged[1].id, "gpt-new-model");
         assert_eq!(merged[1].provider, "openai");
+        // Nothing says it's text-only, so images aren't stripped from its requests.
+        assert!(merged[1].supports_vision());
     }
 
     #[test]
@@ -1,5 +1,7 @@
+pub mod cache;
+pub mod catalog;
 pub mod fetch;
 pub mod static_models;
 
-pub use fetch::{fetch_models_for_provider, is_custom_provider, supports_dynamic_models, default_model_def_for_provider, FetchError};
+pub use fetch::{fetch_models_for_provider, fetch_models_cached, is_custom_provider, supports_dynamic_models, default_model_def_for_provider, FetchError};
 pub use static_models::*;
@@ -1,5 +1,10 @@
 use crate::auth;
-use crate::providers::anthropic::{static_anthropic_models, static_anthropic_setup_token_models};
+use crate::models::catalog;
+#[cfg(feature = "providers-anthropic")]
+use crate::providers::anthropic::{
+    static_anthropic_models, static_anthropic_oauth_models, static_anthropic_setup_token_models,
+};
+#[cfg(feature = "providers-google")]
 use crate::providers::google_gemini_cli::{static_antigravity_models, static_gemini_cli_models};
 use crate::types::*;
 
@@ -8,20 +13,64 @@ fn base_url(provider: &str) -> &'static str {
     auth::provider_base_url(provider).unwrap_or("https://api.example.com/v1")
 }
 
+/// All static models, with the downloaded model catalog (if any) merged over them.
 pub fn all_static_models() -> Vec<ModelDef> {
+    let models = builtin_models();
+    match catalog::active() {
+        Some(c) => c.merge_all(models),
+        None => models,
+    }
+}
+
+/// Static models for `provider`, with the downloaded model catalog (if any) merged over them.
+pub fn static_models_for_provider(provider: &str) -> Vec<ModelDef> {
+    let models = builtin_models_for_provider(provider);
+    match catalog::active() {
+        Some(c) => c.merge(provider, models),
+        None => models,
+    }
+}
+
+/// Models compiled into the crate, without catalog updates.
+pub fn builtin_models() -> Vec<ModelDef> {
+    let mut models = unpriced_models();
+    apply_prices(&mut models);
+    models
+}
+
+/// Models compiled into the crate for `provider`, without catalog updates.
+pub fn builtin_models_for_provider(provider: &str) -> Vec<ModelDef> {
+    let mut models = unpriced_models_for_provider(provider);
+    apply_prices(&mut models);
+    models
+}
+
+fn unpriced_models() -> Vec<ModelDef> {
     let mut models = Vec::new();
     models.extend(static_openai_models());
+    #[cfg(feature = "providers-openai-codex")]
     models.extend(static_openai_codex_models());
+    #[cfg(feature = "providers-anthropic")]
     models.extend(static_anthropic_models());
+    #[cfg(feature = "providers-anthropic")]
     models.extend(static_anthropic_setup_token_models());
+    #[cfg(feature = "providers-anthropic")]
+    models.extend(static_anthropic_oauth_models());
+    #[cfg(feature = "providers-google")]
     models.extend(static_google_models());
+    #[cfg(feature = "providers-google")]
     models.extend(static_gemini_cli_models());
+    #[cfg(feature = "providers-google")]
     models.extend(static_antigravity_models());
     models.extend(static_deepseek_models());
+    models.extend(static_mistral_models());
+    #[cfg(feature = "providers-cohere")]
+    models.extend(static_cohere_models());
     models.extend(static_xai_models());
     models.extend(static_groq_models());
     models.extend(static_together_models());
     models.extend(static_siliconflow_models());
+    #[cfg(feature = "providers-zhipu")]
     models.extend(static_zhipuai_models());
     models.extend(static_fireworks_models());
     models.extend(static_nebius_models());
@@ -29,32 +78,48 @@ pub fn all_static_models() -> Vec<ModelDef> {
     models.extend(static_minimax_models());
     models.extend(static_xiaomi_models());
     models.extend(static_moonshot_models());
+    #[cfg(feature = "providers-qwen-portal")]
     models.extend(static_qwen_portal_models());
     models.extend(static_qianfan_models());
+    #[cfg(feature = "providers-anthropic")]
     models.extend(static_synthetic_models());
+    #[cfg(feature = "providers-cloudflare")]
     models.extend(static_cloudflare_models());
     models.extend(static_ollama_models());
     models.extend(static_vllm_models());
     models.extend(static_huggingface_models());
+    #[cfg(feature = "providers-github-copilot")]
     models.extend(static_copilot_models());
     models.extend(static_bedrock_models());
     models
 }
 
-pub fn static_models_for_provider(provider: &str) -> Vec<ModelDef> {
+fn unpriced_models_for_provider(provider: &str) -> Vec<ModelDef> {
     match provider {
         "openai" => static_openai_models(),
+        #[cfg(feature = "providers-openai-codex")]
         "openai-codex" => static_openai_codex_models(),
+        #[cfg(feature = "providers-anthropic")]
         "anthropic" => static_anthropic_models(),
+        #[cfg(feature = "providers-anthropic")]
         "anthropic-setup-token" => static_anthropic_setup_token_models(),
+        #[cfg(feature = "providers-anthropic")]
+        "anthropic-oauth" => static_anthropic_oauth_models(),
+        #[cfg(feature = "providers-google")]
         "google" => static_google_models(),
+        #[cfg(feature = "providers-google")]
         "gemini-cli" => static_gemini_cli_models(),
+        #[cfg(feature = "providers-google")]
         "antigravity" => static_antigravity_models(),
         "deepseek" => static_deepseek_models(),
+        "mistral" => static_mistral_models(),
+        #[cfg(feature = "providers-cohere")]
+        "cohere" => static_cohere_models(),
         "xai" => static_xai_models(),
         "groq" => static_groq_models(),
         "together" => static_together_models(),
         "siliconflow" => static_siliconflow_models(),
+        #[cfg(feature = "providers-zhipu")]
         "zhipuai" => static_zhipuai_models(),
         "fireworks" => static_fireworks_models(),
         "nebius" => static_nebius_models(),
@@ -62,19 +127,108 @@ pub fn static_models_for_provider(provider: &str) -> Vec<ModelDef> {
         "minimax" => static_minimax_models(),
         "xiaomi" => static_xiaomi_models(),
         "moonshot" => static_moonshot_models(),
+        #[cfg(feature = "providers-qwen-portal")]
         "qwen-portal" => static_qwen_portal_models(),
         "qianfan" => static_qianfan_models(),
+        #[cfg(feature = "providers-anthropic")]
         "synthetic" => static_synthetic_models(),
+        #[cfg(feature = "providers-cloudflare")]
         "cloudflare-ai-gateway" => static_cloudflare_models(),
         "ollama" => static_ollama_models(),
         "vllm" => static_vllm_models(),
         "huggingface" => static_huggingface_models(),
+        #[cfg(feature = "providers-github-copilot")]
         "github-copilot" => static_copilot_models(),
         "amazon-bedrock" => static_bedrock_models(),
         _ => Vec::new(),
     }
 }
 
+// ---------------------------------------------------------------------------
+// Pricing
+// ---------------------------------------------------------------------------
+
+/// List prices in USD per million tokens: (provider, model, [input, output, cache read, cache write]).
+/// Subscription-backed providers (openai-codex, anthropic-oauth, gemini-cli, github-copilot, ...)
+/// are not billed per token and stay at zero.
+const PRICES: &[(&str, &str, [f64; 4])] = &[
+    ("openai", "gpt-4o", [2.5, 10.0, 1.25, 0.0]),
+    ("openai", "gpt-4o-mini", [0.15, 0.6, 0.075, 0.0]),
+    ("openai", "o1", [15.0, 60.0, 7.5, 0.0]),
+    ("openai", "o3-mini", [1.1, 4.4, 0.55, 0.0]),
+    ("openai", "gpt-5.2-codex", [1.75, 14.0, 0.175, 0.0]),
+    ("openai", "gpt-5.3-codex", [1.75, 14.0, 0.175, 0.0]),
+    ("anthropic", "claude-opus-4-6", [5.0, 25.0, 0.5, 6.25]),
+    ("anthropic", "claude-opus-4-5", [5.0, 25.0, 0.5, 6.25]),
+    ("anthropic", "claude-opus-4-5-20251101", [5.0, 25.0, 0.5, 6.25]),
+    ("anthropic", "claude-opus-4-1", [15.0, 75.0, 1.5, 18.75]),
+    ("anthropic", "claude-opus-4-1-20250805", [15.0, 75.0, 1.5, 18.75]),
+    ("anthropic", "claude-opus-4-0", [15.0, 75.0, 1.5, 18.75]),
+    ("anthropic", "claude-opus-4-20250514", [15.0, 75.0, 1.5, 18.75]),
+    ("anthropic", "claude-sonnet-4-5", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-sonnet-4-5-20250929", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-sonnet-4-0", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-sonnet-4-20250514", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-3-7-sonnet-20250219", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-3-7-sonnet-latest", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-3-5-sonnet-20241022", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-3-5-sonnet-20240620", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-haiku-4-5", [1.0, 5.0, 0.1, 1.25]),
+    ("anthropic", "claude-haiku-4-5-20251001", [1.0, 5.0, 0.1, 1.25]),
+    ("anthropic", "claude-3-opus-20240229", [15.0, 75.0, 1.5, 18.75]),
+    ("anthropic", "claude-3-sonnet-20240229", [3.0, 15.0, 0.3, 3.75]),
+    ("anthropic", "claude-3-haiku-20240307", [0.25, 1.25, 0.03, 0.3]),
+    ("google", "gemini-2.0-flash", [0.1, 0.4, 0.025, 0.0]),
+    ("deepseek", "deepseek-chat", [0.28, 0.42, 0.028, 0.0]),
+    ("deepseek", "deepseek-reasoner", [0.28, 0.42, 0.028, 0.0]),
+    ("mistral", "codestral-latest", [0.3, 0.9, 0.0, 0.0]),
+    ("mistral", "mistral-large-latest", [2.0, 6.0, 0.0, 0.0]),
+    ("mistral", "mistral-small-latest", [0.1, 0.3, 0.0, 0.0]),
+    ("cohere", "command-a-03-2025", [2.5, 10.0, 0.0, 0.0]),
+    ("cohere", "command-r-plus-08-2024", [2.5, 10.0, 0.0, 0.0]),
+    ("cohere", "command-r7b-12-2024", [0.0375, 0.15, 0.0, 0.0]),
+    ("xai", "grok-3", [3.0, 15.0, 0.75, 0.0]),
+    ("xai", "grok-3-mini", [0.3, 0.5, 0.075, 0.0]),
+    ("groq", "llama-3.3-70b-versatile", [0.59, 0.79, 0.0, 0.0]),
+    ("together", "deepseek-ai/DeepSeek-R1", [3.0, 7.0, 0.0, 0.0]),
+    ("fireworks", "accounts/fireworks/models/deepseek-r1", [3.0, 8.0, 0.0, 0.0]),
+    ("openrouter", "google/gemini-2.5-pro-preview", [1.25, 10.0, 0.31, 0.0]),
+    ("zhipuai", "glm-4.6", [0.6, 2.2, 0.11, 0.0]),
+    ("zhipuai", "glm-4.5", [0.6, 2.2, 0.11, 0.0]),
+    ("zhipuai", "glm-4.5-air", [0.2, 1.1, 0.03, 0.0]),
+    ("minimax", "MiniMax-M2.1", [0.3, 1.2, 0.03, 0.375]),
+    ("minimax", "MiniMax-M2.5", [0.3, 1.2, 0.03, 0.375]),
+    ("moonshot", "kimi-k2.5", [0.6, 3.0, 0.1, 0.0]),
+    ("amazon-bedrock", "anthropic.claude-3-5-sonnet-20241022-v2:0", [3.0, 15.0, 0.3, 3.75]),
+];
+
+/// List price for a built-in model, if known.
+pub fn list_price(provider: &str, model_id: &str) -> Option<ModelCost> {
+    // AI Gateway passes the upstream provider's prices through.
+    if provider == "cloudflare-ai-gateway"
+        && let Some((upstream, model)) = model_id.split_once('/')
+    {
+        return list_price(upstream, model);
+    }
+    PRICES
+        .iter()
+        .find(|(p, m, _)| *p == provider && *m == model_id)
+        .map(|(_, _, [input, output, cache_read, cache_write])| ModelCost {
+            input: *input,
+            output: *output,
+            cache_read: *cache_read,
+            cache_write: *cache_write,
+        })
+}
+
+fn apply_prices(models: &mut [ModelDef]) {
+    for m in models {
+        if let Some(cost) = list_price(&m.provider, &m.id) {
+            m.cost = cost;
+        }
+    }
+}
+
 fn oai(provider: &str, base_url: &str, id: &str, name: &str, reasoning: bool, ctx: u64, max_tok: u64) -> ModelDef {
     ModelDef {
         id: id.into(),
@@ -84,10 +238,10 @@ fn oai(provider: &str, base_url: &str, id: &str, name: &str, reasoning: bool, ct
         base_url: base_url.into(),
         reasoning,
         input: vec![InputModality::Text, InputModality::Image],
-        cost: ModelCost::default(),
         context_window: ctx,
         max_tokens: max_tok,
-        headers: None,
+        capabilities: ModelCapabilities { tools: true, json_mode: true },
+        ..Default::default()
     }
 }
 
@@ -100,13 +254,19 @@ fn oai_responses(provider: &str, base_url: &str, id: &str, name: &str, reasoning
         base_url: base_url.into(),
         reasoning,
         input: vec![InputModality::Text, InputModality::Image],
-        cost: ModelCost::default(),
         context_window: ctx,
         max_tokens: max_tok,
-        headers: None,
+        capabilities: ModelCapabilities { tools: true, json_mode: true },
+        ..Default::default()
     }
 }
 
+/// For models served without function calling (e.g. DeepSeek R1 on most hosts).
+fn without_tools(mut m: ModelDef) -> ModelDef {
+    m.capabilities.tools = false;
+    m
+}
+
 fn ant(provider: &str, base_url: &str, id: &str, name: &str, reasoning: bool, ctx: u64, max_tok: u64) -> ModelDef {
     ModelDef {
         id: id.into(),
@@ -116,10 +276,9 @@ fn ant(provider: &str, base_url: &str, id: &str, name: &str, reasoning: bool, ct
         base_url: base_url.into(),
         reasoning,
         input: vec![InputModality::Text, InputModality::Image],
-        cost: ModelCost::default(),
         context_window: ctx,
         max_tokens: max_tok,
-        headers: None,
+        ..Default::default()
     }
 }
 
@@ -165,8 +324,9 @@ pub fn static_google_models() -> Vec<ModelDef> {
             name: "Gemini 2.0 Flash".into(),
             api: api.clone(), provider: provider.into(), base_url: base_url.into(),
             reasoning: false, input: vec![InputModality::Text, InputModality::Image],
-            cost: ModelCost::default(),
-            context_window: 1048576, max_tokens: 8192, headers: None,
+            context_window: 1048576, max_tokens: 8192,
+            capabilities: ModelCapabilities { tools: true, json_mode: true },
+            ..Default::default()
         },
     ]
 }
@@ -180,6 +340,26 @@ pub fn static_deepseek_models() -> Vec<ModelDef> {
     ]
 }
 
+pub fn static_mistral_models() -> Vec<ModelDef> {
+    let p = "mistral";
+    let url = base_url(p);
+    vec![
+        oai(p, url, "codestral-latest", "Codestral", false, 256000, 8192),
+        oai(p, url, "mistral-large-latest", "Mistral Large", false, 128000, 8192),
+        oai(p, url, "mistral-small-latest", "Mistral Small", false, 128000, 8192),
+    ]
+}
+
+pub fn static_cohere_models() -> Vec<ModelDef> {
+    let p = "cohere";
+    let url = base_url(p);
+    vec![
+        oai(p, url, "command-a-03-2025", "Command A", false, 256000, 8000),
+        oai(p, url, "command-r-plus-08-2024", "Command R+", false, 128000, 4000),
+        oai(p, url, "command-r7b-12-2024", "Command R7B", false, 128000, 4000),
+    ]
+}
+
 pub fn static_xai_models() -> Vec<ModelDef> {
     let p = "xai";
     let url = base_url(p);
@@ -201,7 +381,7 @@ pub fn static_together_models() -> Vec<ModelDef> {
     let p = "together";
     let url = base_url(p);
     vec![
-        oai(p, url, "deepseek-ai/DeepSeek-R1", "DeepSeek R1", true, 128000, 8192),
+        without_tools(oai(p, url, "deepseek-ai/DeepSeek-R1", "DeepSeek R1", true, 128000, 8192)),
     ]
 }
 
@@ -217,6 +397,9 @@ pub fn static_zhipuai_models() -> Vec<ModelDef> {
     let p = "zhipuai";
     let url = base_url(p);
     vec![
+        oai(p, url, "glm-4.6", "GLM-4.6", true, 200000, 128000),
+        oai(p, url, "glm-4.5", "GLM-4.5", true, 128000, 96000),
+        oai(p, url, "glm-4.5-air", "GLM-4.5 Air", true, 128000, 96000),
         oai(p, url, "glm-4-plus", "GLM-4 Plus", false, 128000, 4096),
     ]
 }
@@ -225,7 +408,7 @@ pub fn static_fireworks_models() -> Vec<ModelDef> {
     let p = "fireworks";
     let url = base_url(p);
     vec![
-        oai(p, url, "accounts/fireworks/models/deepseek-r1", "DeepSeek R1", true, 128000, 8192),
+        without_tools(oai(p, url, "accounts/fireworks/models/deepseek-r1", "DeepSeek R1", true, 128000, 8192)),
     ]
 }
 
@@ -233,7 +416,7 @@ pub fn static_nebius_models() -> Vec<ModelDef> {
     let p = "nebius";
     let url = base_url(p);
     vec![
-        oai(p, url, "deepseek-ai/DeepSeek-R1", "DeepSeek R1", true, 128000, 8192),
+        without_tools(oai(p, url, "deepseek-ai/DeepSeek-R1", "DeepSeek R1", true, 128000, 8192)),
     ]
 }
 
@@ -296,12 +479,27 @@ pub fn static_synthetic_models() -> Vec<ModelDef> {
     ]
 }
 
+/// OpenAI's and Anthropic's models as `<upstream>/<model>` through the gateway, and a Workers AI
+/// model. Other upstreams' models can be enabled by ID.
+#[cfg(feature = "providers-cloudflare")]
 pub fn static_cloudflare_models() -> Vec<ModelDef> {
     let p = "cloudflare-ai-gateway";
     let url = base_url(p);
-    vec![
-        ant(p, url, "cloudflare-model", "Cloudflare AI Gateway", false, 128000, 8192),
-    ]
+    let routed = |upstream: &str, m: ModelDef| ModelDef {
+        id: format!("{}/{}", upstream, m.id),
+        name: format!("{} (AI Gateway)", m.name),
+        provider: p.into(),
+        base_url: url.into(),
+        ..m
+    };
+    let mut models: Vec<ModelDef> = static_openai_models()
+        .into_iter()
+        .filter(|m| m.api == Api::OpenaiCompletions)
+        .map(|m| routed("openai", m))
+        .collect();
+    models.extend(static_anthropic_models().into_iter().map(|m| routed("anthropic", m)));
+    models.push(oai(p, url, "workers-ai/@cf/meta/llama-3.3-70b-instruct-fp8-fast", "Llama 3.3 70B (Workers AI)", false, 24000, 8192));
+    models
 }
 
 pub fn static_ollama_models() -> Vec<ModelDef> {
@@ -343,3 +541,45 @@ pub fn static_bedrock_models() -> Vec<ModelDef> {
         oai(p, url, "anthropic.claude-3-5-sonnet-20241022-v2:0", "Bedrock Claude 3.5 Sonnet", false, 200000, 8192),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "providers-anthropic")]
+    fn api_models_carry_list_prices() {
+        let models = builtin_models_for_provider("anthropic");
+        let sonnet = models.iter().find(|m| m.id == "claude-sonnet-4-5").unwrap();
+        assert_eq!(sonnet.cost.input, 3.0);
+        assert_eq!(sonnet.cost.cache_write, 3.75);
+
+        // Subscription access is not billed per token.
+        let oauth = builtin_models_for_provider("anthropic-oauth");
+        assert!(oauth.iter().all(|m| m.cost.input == 0.0 && m.cost.output == 0.0));
+    }
+
+    #[test]
+    fn capability_flags() {
+        let gpt = builtin_models_for_provider("openai").into_iter().find(|m| m.id == "gpt-4o").unwrap();
+        assert!(gpt.supports_tools() && gpt.supports_vision() && gpt.supports_json_mode());
+        assert!(!gpt.supports_audio());
+
+        let r1 = &builtin_models_for_provider("together")[0];
+        assert!(!r1.supports_tools());
+    }
+
+    #[test]
+    #[cfg(feature = "all-providers")]
+    fn every_price_entry_matches_a_builtin_model() {
+        let models = builtin_models();
+        for (provider, id, _) in PRICES {
+            assert!(
+                models.iter().any(|m| m.provider == *provider && m.id == *id),
+                "price for unknown model {}/{}",
+                provider,
+                id
+            );
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Anthropic (Claude Pro/Max) OAuth via Authorization Code + PKCE.
+//! Same client and endpoints as the `claude` CLI; the resulting access token is an
+//! `sk-ant-oat01-` bearer token handled like a setup-token by the Anthropic provider.
+
+use super::*;
+use crate::oauth::pkce::generate_pkce;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+const CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+const AUTHORIZE_URL: &str = "https://claude.ai/oauth/authorize";
+const TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+const PROFILE_URL: &str = "https://api.anthropic.com/api/oauth/profile";
+const REDIRECT_URI: &str = "https://console.anthropic.com/oauth/code/callback";
+const SCOPE: &str = "org:create_api_key user:profile user:inference";
+
+#[derive(Deserialize)]
+struct TokenResp {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+}
+
+pub struct AnthropicOAuthProvider;
+
+/// Split the pasted value into (code, state). The callback page shows `code#state`;
+/// a full redirect URL with `code`/`state` query parameters is accepted as well.
+fn parse_authorization_input(input: &str) -> anyhow::Result<(String, Option<String>)> {
+    let input = input.trim();
+    if input.starts_with("http://") || input.starts_with("https://") {
+        let url = url::Url::parse(input)?;
+        let get = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.to_string());
+        let code = get("code").ok_or_else(|| anyhow::anyhow!("No authorization code in redirect URL"))?;
+        return Ok((code, get("state")));
+    }
+    let (code, state) = match input.split_once('#') {
+        Some((c, s)) => (c.trim(), Some(s.trim().to_string())),
+        None => (input, None),
+    };
+    if code.is_empty() {
+        anyhow::bail!("Empty authorization code");
+    }
+    Ok((code.to_string(), state.filter(|s| !s.is_empty())))
+}
+
+fn expires_at(expires_in: i64) -> i64 {
+    chrono::Utc::now().timestamp_millis() + expires_in * 1000 - 5 * 60 * 1000
+}
+
+#[async_trait]
+impl OAuthProvider for AnthropicOAuthProvider {
+    fn id(&self) -> &str {
+        "anthropic-oauth"
+    }
+
+    fn name(&self) -> &str {
+        "Anthropic (Claude Pro/Max OAuth)"
+    }
+
+    async fn login(&self, callbacks: &dyn OAuthCallbacks) -> anyhow::Result<OAuthCredentials> {
+        let pkce = generate_pkce();
+
+        let params = [
+            ("code", "true"),
+            ("client_id", CLIENT_ID),
+            ("response_type", "code"),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", SCOPE),
+            ("code_challenge", &pkce.challenge),
+            ("code_challenge_method", "S256"),
+            ("state", &pkce.verifier),
+        ];
+        let auth_url = format!("{}?{}", AUTHORIZE_URL, serde_urlencoded::to_string(params)?);
+
+        let _ = open::that(&auth_url);
+
+        callbacks.on_auth(OAuthAuthInfo {
+            url: auth_url,
+            instructions: Some(
+                "Sign in with your Claude account, then copy the code shown on the callback page.".into(),
+            ),
+        });
+
+        let input = callbacks
+            .on_prompt(OAuthPrompt {
+                message: "Paste the authorization code:".into(),
+                placeholder: Some("code#state".into()),
+            })
+            .await?;
+        let (code, state) = parse_authorization_input(&input)?;
+
+        callbacks.on_progress("Exchanging authorization code for tokens...");
+
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(TOKEN_URL)
+            .json(&json!({
+                "grant_type": "authorization_code",
+                "client_id": CLIENT_ID,
+                "code": code,
+                "state": state.unwrap_or_else(|| pkce.verifier.clone()),
+                "redirect_uri": REDIRECT_URI,
+                "code_verifier": pkce.verifier,
+            }))
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            anyhow::bail!("Token exchange failed: {}", resp.text().await?);
+        }
+
+        let token: TokenResp = resp.json().await?;
+        Ok(OAuthCredentials {
+            refresh: token.refresh_token,
+            access: token.access_token,
+            expires: expires_at(token.expires_in),
+            extra: HashMap::new(),
+        })
+    }
+
+    async fn refresh_token(&self, credentials: &OAuthCredentials) -> anyhow::Result<OAuthCredentials> {
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(TOKEN_URL)
+            .json(&json!({
+                "grant_type": "refresh_token",
+                "client_id": CLIENT_ID,
+                "refresh_token": credentials.refresh,
+            }))
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            anyhow::bail!("Token refresh failed: {}", resp.text().await?);
+        }
+
+        let token: TokenResp = resp.json().await?;
+        Ok(OAuthCredentials {
+            refresh: token.refresh_token,
+            access: token.access_token,
+            expires: expires_at(token.expires_in),
+            extra: credentials.extra.clone(),
+        })
+    }
+
+    fn get_api_key(&self, credentials: &OAuthCredentials) -> String {
+        credentials.access.clone()
+    }
+
+    async fn fetch_identity(&self, credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        let profile = get_json(PROFILE_URL, &credentials.access, &[("anthropic-beta", "oauth-2025-04-20")]).await?;
+        Ok(AccountIdentity {
+            email: json_str(&profile, "/account/email"),
+            org: json_str(&profile, "/organization/name"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_code_and_state() {
+        let (code, state) = parse_authorization_input("  abc123#xyz \n").unwrap();
+        assert_eq!(code, "abc123");
+        assert_eq!(state.as_deref(), Some("xyz"));
+    }
+
+    #[test]
+    fn parses_bare_code() {
+        let (code, state) = parse_authorization_input("abc123").unwrap();
+        assert_eq!(code, "abc123");
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn parses_redirect_url() {
+        let (code, state) = parse_authorization_input(
+            "https://console.anthropic.com/oauth/code/callback?code=c1&state=s1",
+        )
+        .unwrap();
+        assert_eq!(code, "c1");
+        assert_eq!(state.as_deref(), Some("s1"));
+    }
+
+    #[test]
+    fn rejects_empty_code() {
+        assert!(parse_authorization_input("#state").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::providers::github_copilot::exchange_token;
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -57,19 +58,12 @@ impl OAuthProvider for GitHubCopilotOAuthProvider {
             if let Some(access) = token_resp.access_token {
                 // Get real Copilot token
                 callbacks.on_progress("Exchanging GitHub token for Copilot token...");
-                let copilot_resp = client.get("https://api.github.com/copilot_internal/v2/token")
-                    .bearer_auth(&access)
-                    .header("User-Agent", "GitHubCopilotChat/0.35.0")
-                    .send().await?;
-                
-                #[derive(Deserialize)]
-                struct CopilotToken { token: String, expires_at: i64 }
-                let cp: CopilotToken = copilot_resp.json().await?;
+                let cp = exchange_token(&client, &access).await?;
 
                 return Ok(OAuthCredentials {
                     refresh: access, // GitHub token acts as refresh token
                     access: cp.token,
-                    expires: cp.expires_at * 1000 - 300000,
+                    expires: cp.expires_at_ms - 300000,
                     extra: HashMap::new(),
                 });
             }
@@ -86,19 +80,12 @@ impl OAuthProvider for GitHubCopilotOAuthProvider {
 
     async fn refresh_token(&self, credentials: &OAuthCredentials) -> anyhow::Result<OAuthCredentials> {
         let client = reqwest::Client::new();
-        let resp = client.get("https://api.github.com/copilot_internal/v2/token")
-            .bearer_auth(&credentials.refresh)
-            .header("User-Agent", "GitHubCopilotChat/0.35.0")
-            .send().await?;
-        
-        #[derive(Deserialize)]
-        struct CopilotToken { token: String, expires_at: i64 }
-        let cp: CopilotToken = resp.json().await?;
+        let cp = exchange_token(&client, &credentials.refresh).await?;
 
         Ok(OAuthCredentials {
             refresh: credentials.refresh.clone(),
             access: cp.token,
-            expires: cp.expires_at * 1000 - 300000,
+            expires: cp.expires_at_ms - 300000,
             extra: HashMap::new(),
         })
     }
@@ -106,4 +93,32 @@ impl OAuthProvider for GitHubCopilotOAuthProvider {
     fn get_api_key(&self, credentials: &OAuthCredentials) -> String {
         credentials.access.clone()
     }
+
+    async fn fetch_identity(&self, credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        // The GitHub token (kept as the refresh token) is the one the GitHub API takes.
+        let user = get_json("https://api.github.com/user", &credentials.refresh, &[("User-Agent", "zeroai")]).await?;
+        Ok(github_identity(&user))
+    }
+}
+
+/// The public email, else the login, and the company of a GitHub user.
+fn github_identity(user: &serde_json::Value) -> AccountIdentity {
+    AccountIdentity {
+        email: json_str(user, "/email").or_else(|| json_str(user, "/login")),
+        org: json_str(user, "/company").map(|c| c.trim_start_matches('@').to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn github_identity_falls_back_to_login() {
+        let user = serde_json::json!({"login": "alice", "email": null, "company": "@acme"});
+        assert_eq!(
+            github_identity(&user),
+            AccountIdentity { email: Some("alice".into()), org: Some("acme".into()) }
+        );
+    }
 }
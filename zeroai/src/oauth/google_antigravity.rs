@@ -211,6 +211,10 @@ impl OAuthProvider for AntigravityOAuthProvider {
         })
         .to_string()
     }
+
+    async fn fetch_identity(&self, credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        super::google_gemini_cli::google_identity(&credentials.access).await
+    }
 }
 
 /// Discover project for Antigravity.
@@ -31,6 +31,7 @@ const SCOPES: &[&str] = &[
 ];
 const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v2/userinfo";
 const CODE_ASSIST_ENDPOINT: &str = "https://cloudcode-pa.googleapis.com";
 
 /// Google Gemini CLI OAuth provider (Cloud Code Assist).
@@ -180,6 +181,19 @@ impl OAuthProvider for GeminiCliOAuthProvider {
         let project_id = credentials.extra.get("projectId").and_then(|v| v.as_str()).unwrap_or("");
         serde_json::json!({ "token": credentials.access, "projectId": project_id }).to_string()
     }
+
+    async fn fetch_identity(&self, credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        google_identity(&credentials.access).await
+    }
+}
+
+/// Email and Workspace domain of a Google login, from the userinfo endpoint.
+pub(crate) async fn google_identity(access_token: &str) -> anyhow::Result<AccountIdentity> {
+    let info = get_json(USERINFO_URL, access_token, &[]).await?;
+    Ok(AccountIdentity {
+        email: json_str(&info, "/email"),
+        org: json_str(&info, "/hd"),
+    })
 }
 
 async fn discover_project(access_token: &str, callbacks: &dyn OAuthCallbacks) -> anyhow::Result<String> {
@@ -1,11 +1,24 @@
+//! OAuth login and token refresh. The types and the provider registry are always available;
+//! the built-in flows need the `oauth` feature and their provider's feature.
+
+#[cfg(all(feature = "oauth", feature = "providers-anthropic"))]
+pub mod anthropic;
+#[cfg(all(feature = "oauth", feature = "providers-github-copilot"))]
 pub mod github_copilot;
+#[cfg(all(feature = "oauth", feature = "providers-google"))]
 pub mod google_antigravity;
+#[cfg(all(feature = "oauth", feature = "providers-google"))]
 pub mod google_gemini_cli;
+#[cfg(all(feature = "oauth", feature = "providers-openai-codex"))]
 pub mod openai_codex;
+#[cfg(feature = "oauth")]
 pub mod pkce;
+#[cfg(all(feature = "oauth", feature = "providers-qwen-portal"))]
 pub mod qwen_portal;
 
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
 
 /// Information about the OAuth authorization URL.
 #[derive(Debug, Clone)]
@@ -44,6 +57,14 @@ pub struct OAuthCredentials {
     pub extra: std::collections::HashMap<String, serde_json::Value>,
 }
 
+/// Who an OAuth login belongs to, shown instead of a generated account label.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountIdentity {
+    pub email: Option<String>,
+    /// Organization, workspace or hosted domain, where the provider has one.
+    pub org: Option<String>,
+}
+
 /// Trait for OAuth provider implementations.
 #[async_trait]
 pub trait OAuthProvider: Send + Sync {
@@ -61,4 +82,145 @@ pub trait OAuthProvider: Send + Sync {
 
     /// Convert credentials to an API key string.
     fn get_api_key(&self, credentials: &OAuthCredentials) -> String;
+
+    /// Ask the provider who `credentials` belong to (its userinfo endpoint). Providers without
+    /// one return an empty identity.
+    async fn fetch_identity(&self, _credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        Ok(AccountIdentity::default())
+    }
+}
+
+/// GET `url` with a bearer token and return the JSON body, for identity lookups.
+pub(crate) async fn get_json(
+    url: &str,
+    token: &str,
+    headers: &[(&str, &str)],
+) -> anyhow::Result<serde_json::Value> {
+    let mut req = reqwest::Client::new()
+        .get(url)
+        .bearer_auth(token)
+        .header("Accept", "application/json")
+        .timeout(std::time::Duration::from_secs(10));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let resp = req.send().await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("{} returned {}", url, resp.status());
+    }
+    Ok(resp.json().await?)
+}
+
+/// A non-empty string at `pointer` in `value`.
+pub(crate) fn json_str(value: &serde_json::Value, pointer: &str) -> Option<String> {
+    value.pointer(pointer)?.as_str().filter(|s| !s.is_empty()).map(String::from)
+}
+
+/// Email and default organization from OpenAI's `/v1/me`.
+pub(crate) fn openai_identity(me: &serde_json::Value) -> AccountIdentity {
+    let orgs = me.pointer("/orgs/data").and_then(|d| d.as_array()).cloned().unwrap_or_default();
+    let org = orgs
+        .iter()
+        .find(|o| o.get("is_default").and_then(|d| d.as_bool()) == Some(true))
+        .or(orgs.first())
+        .and_then(|o| json_str(o, "/title"));
+    AccountIdentity { email: json_str(me, "/email"), org }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+type OAuthProviderMap = HashMap<String, Arc<dyn OAuthProvider>>;
+
+static REGISTRY: LazyLock<RwLock<OAuthProviderMap>> = LazyLock::new(|| {
+    let builtins: Vec<Arc<dyn OAuthProvider>> = vec![
+        #[cfg(all(feature = "oauth", feature = "providers-google"))]
+        Arc::new(google_gemini_cli::GeminiCliOAuthProvider),
+        #[cfg(all(feature = "oauth", feature = "providers-google"))]
+        Arc::new(google_antigravity::AntigravityOAuthProvider),
+        #[cfg(all(feature = "oauth", feature = "providers-openai-codex"))]
+        Arc::new(openai_codex::OpenAiCodexOAuthProvider),
+        #[cfg(all(feature = "oauth", feature = "providers-github-copilot"))]
+        Arc::new(github_copilot::GitHubCopilotOAuthProvider),
+        #[cfg(all(feature = "oauth", feature = "providers-qwen-portal"))]
+        Arc::new(qwen_portal::QwenPortalOAuthProvider),
+        #[cfg(all(feature = "oauth", feature = "providers-anthropic"))]
+        Arc::new(anthropic::AnthropicOAuthProvider),
+    ];
+    RwLock::new(builtins.into_iter().map(|p| (p.id().to_string(), p)).collect())
+});
+
+/// Snapshot of all registered OAuth providers, keyed by provider ID.
+/// Includes the built-in providers plus anything added via [`register_provider`].
+pub fn registry() -> OAuthProviderMap {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Look up a registered OAuth provider by ID.
+pub fn get_provider(provider_id: &str) -> Option<Arc<dyn OAuthProvider>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(provider_id)
+        .cloned()
+}
+
+/// Register an OAuth provider under its `id()`, replacing any existing provider with that ID.
+/// Use this from downstream crates to plug in custom OAuth flows; login and token refresh
+/// in [`crate::ConfigManager`] pick it up automatically.
+pub fn register_provider(provider: Arc<dyn OAuthProvider>) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(provider.id().to_string(), provider);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyOAuth;
+
+    #[async_trait]
+    impl OAuthProvider for DummyOAuth {
+        fn id(&self) -> &str { "dummy-oauth" }
+        fn name(&self) -> &str { "Dummy" }
+        async fn login(&self, _: &dyn OAuthCallbacks) -> anyhow::Result<OAuthCredentials> {
+            anyhow::bail!("not supported")
+        }
+        async fn refresh_token(&self, c: &OAuthCredentials) -> anyhow::Result<OAuthCredentials> {
+            Ok(c.clone())
+        }
+        fn get_api_key(&self, c: &OAuthCredentials) -> String { c.access.clone() }
+    }
+
+    #[test]
+    fn openai_identity_prefers_the_default_org() {
+        let me = serde_json::json!({"email": "alice@example.com", "orgs": {"data": [
+            {"title": "Personal", "is_default": false},
+            {"title": "Acme", "is_default": true},
+        ]}});
+        assert_eq!(
+            openai_identity(&me),
+            AccountIdentity { email: Some("alice@example.com".into()), org: Some("Acme".into()) }
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "oauth", feature = "all-providers"))]
+    fn registry_contains_builtins() {
+        let reg = registry();
+        for id in ["gemini-cli", "antigravity", "openai-codex", "github-copilot", "qwen-portal", "anthropic-oauth"] {
+            assert!(reg.contains_key(id), "missing {}", id);
+        }
+    }
+
+    #[test]
+    fn register_custom_provider() {
+        assert!(get_provider("dummy-oauth").is_none());
+        register_provider(Arc::new(DummyOAuth));
+        assert_eq!(get_provider("dummy-oauth").unwrap().name(), "Dummy");
+        assert!(registry().contains_key("dummy-oauth"));
+    }
 }
@@ -1,6 +1,7 @@
 use super::*;
 use crate::oauth::pkce::generate_pkce;
 use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -107,4 +108,35 @@ impl OAuthProvider for OpenAiCodexOAuthProvider {
     fn get_api_key(&self, credentials: &OAuthCredentials) -> String {
         credentials.access.clone()
     }
+
+    async fn fetch_identity(&self, credentials: &OAuthCredentials) -> anyhow::Result<AccountIdentity> {
+        match get_json("https://api.openai.com/v1/me", &credentials.access, &[]).await {
+            Ok(me) => Ok(openai_identity(&me)),
+            // The access token carries the email too.
+            Err(e) => token_email(&credentials.access)
+                .map(|email| AccountIdentity { email: Some(email), org: None })
+                .ok_or(e),
+        }
+    }
+}
+
+/// The profile email claim of a ChatGPT access token (a JWT).
+fn token_email(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+    json_str(&claims, "/https:~1~1api.openai.com~1profile/email")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_email_from_access_token() {
+        let claims = serde_json::json!({"https://api.openai.com/profile": {"email": "alice@example.com"}});
+        let token = format!("h.{}.s", URL_SAFE_NO_PAD.encode(claims.to_string()));
+        assert_eq!(token_email(&token).as_deref(), Some("alice@example.com"));
+        assert_eq!(token_email("opaque"), None);
+    }
 }
@@ -0,0 +1,217 @@
+//! Capability probing: tiny live requests that check whether streaming, tool calling and
+//! image input actually work for a model, since catalog flags aren't always right (a proxy in
+//! between may drop tools, a "vision" model may reject images on some endpoints).
+//!
+//! [`probe_model`] runs the checks. Results are kept in the [`crate::store::Store`], and
+//! [`CapabilityProbe::apply_to`] turns off the capabilities a probe found broken, so routing
+//! stops sending requests that need them. Only an invalid-request reply (400/422) counts as
+//! broken: rate limits, server errors and timeouts leave the capability unknown.
+
+use crate::client::AiClient;
+use crate::providers::ProviderError;
+use crate::types::{
+    ChatContext, ContentBlock, ImageContent, InputModality, Message, ModelDef, RequestOptions, StreamEvent,
+    TextContent, ToolDef, UserMessage,
+};
+use futures::StreamExt;
+
+/// Output limit of a probe request.
+const PROBE_MAX_TOKENS: u64 = 256;
+
+/// Output limit of a probe request to a reasoning model, which thinks before it answers.
+const PROBE_REASONING_MAX_TOKENS: u64 = 4096;
+
+/// A 1x1 PNG.
+const PROBE_IMAGE_PNG: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+/// What probing found out about one model. A flag is `None` when it wasn't probed (e.g.
+/// vision for a text-only model) or the answer didn't tell (the model replied without using
+/// the tool, or the request failed for a reason other than the request itself).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityProbe {
+    /// `<provider>/<model>`
+    pub model: String,
+    pub probed_at_ms: i64,
+    pub streaming: Option<bool>,
+    pub tools: Option<bool>,
+    pub vision: Option<bool>,
+    /// The first error a probe got, for display.
+    pub error: Option<String>,
+}
+
+impl CapabilityProbe {
+    /// Turn off the capabilities of `def` that the probe found broken.
+    pub fn apply_to(&self, def: &mut ModelDef) {
+        if self.tools == Some(false) {
+            def.capabilities.tools = false;
+        }
+        if self.vision == Some(false) {
+            def.input.retain(|m| *m != InputModality::Image);
+        }
+    }
+}
+
+/// Probe `full_id` (which must be registered with `client`) with `api_key`: a streamed reply,
+/// then a tool call if the model claims tool support and an image if it claims image input.
+pub async fn probe_model(client: &AiClient, full_id: &str, api_key: &str, now_ms: i64) -> CapabilityProbe {
+    let def = client.get_model(full_id).cloned();
+    let max_tokens = if def.as_ref().is_some_and(|d| d.reasoning) { PROBE_REASONING_MAX_TOKENS } else { PROBE_MAX_TOKENS };
+    let options = RequestOptions {
+        temperature: Some(0.0),
+        max_tokens: Some(max_tokens),
+        api_key: Some(api_key.to_string()),
+        ..Default::default()
+    };
+    let mut probe = CapabilityProbe {
+        model: full_id.to_string(),
+        probed_at_ms: now_ms,
+        streaming: None,
+        tools: None,
+        vision: None,
+        error: None,
+    };
+    let fail = |probe: &mut CapabilityProbe, what: &str, e: ProviderError| {
+        probe.error.get_or_insert_with(|| format!("{}: {}", what, e));
+        matches!(e, ProviderError::Http { status: 400 | 422, .. }).then_some(false)
+    };
+
+    probe.streaming = match probe_streaming(client, full_id, &options).await {
+        Ok(()) => Some(true),
+        Err(e) => fail(&mut probe, "streaming", e),
+    };
+
+    if def.as_ref().is_some_and(ModelDef::supports_tools) {
+        let context = ChatContext {
+            tools: vec![ToolDef {
+                name: "get_current_time".into(),
+                description: "Get the current UTC time.".into(),
+                parameters: serde_json::json!({"type": "object", "properties": {}}),
+            }],
+            ..user_context(vec![text("What time is it? Use the get_current_time tool.")])
+        };
+        probe.tools = match client.chat(full_id, &context, &options).await {
+            Ok(m) => m.content.iter().any(|b| matches!(b, ContentBlock::ToolCall(_))).then_some(true),
+            Err(e) => fail(&mut probe, "tools", e),
+        };
+    }
+
+    if def.as_ref().is_some_and(ModelDef::supports_vision) {
+        let context = user_context(vec![
+            ContentBlock::Image(ImageContent {
+                data: PROBE_IMAGE_PNG.into(),
+                mime_type: "image/png".into(),
+            }),
+            text("What color is this image? Answer in one word."),
+        ]);
+        probe.vision = match client.chat(full_id, &context, &options).await {
+            Ok(_) => Some(true),
+            Err(e) => fail(&mut probe, "vision", e),
+        };
+    }
+    probe
+}
+
+/// Stream a short reply; it must produce text and finish.
+async fn probe_streaming(client: &AiClient, full_id: &str, options: &RequestOptions) -> Result<(), ProviderError> {
+    let context = user_context(vec![text("Reply with the word OK.")]);
+    let mut stream = client.stream(full_id, &context, options)?;
+    let mut got_text = false;
+    while let Some(event) = stream.next().await {
+        match event? {
+            StreamEvent::TextDelta(_) => got_text = true,
+            StreamEvent::Done { .. } if got_text => return Ok(()),
+            StreamEvent::Done { .. } => return Err(ProviderError::Other("finished without any text".into())),
+            StreamEvent::Error { message } => {
+                let text: String = message
+                    .content
+                    .iter()
+                    .filter_map(|b| if let ContentBlock::Text(t) = b { Some(t.text.as_str()) } else { None })
+                    .collect();
+                return Err(ProviderError::Other(text));
+            }
+            _ => {}
+        }
+    }
+    Err(ProviderError::Other("stream ended without finishing".into()))
+}
+
+fn text(text: &str) -> ContentBlock {
+    ContentBlock::Text(TextContent { text: text.into() })
+}
+
+fn user_context(content: Vec<ContentBlock>) -> ChatContext {
+    ChatContext {
+        system_prompt: None,
+        messages: vec![Message::User(UserMessage { content })],
+        tools: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockProvider, MockReply};
+    use std::sync::Arc;
+
+    fn client(mock: MockProvider, vision: bool) -> AiClient {
+        let mut def = MockProvider::model("test");
+        def.input = if vision { vec![InputModality::Text, InputModality::Image] } else { vec![InputModality::Text] };
+        AiClient::builder()
+            .with_provider("mock", Arc::new(mock))
+            .with_model("mock/test".into(), def)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn working_model_passes_every_probe() {
+        let mock = MockProvider::new()
+            .with_reply(MockReply::text("OK"))
+            .with_reply(MockReply::tool_call("get_current_time", serde_json::json!({})))
+            .with_reply(MockReply::text("White"));
+        let probe = probe_model(&client(mock, true), "mock/test", "k", 1).await;
+        assert_eq!((probe.streaming, probe.tools, probe.vision), (Some(true), Some(true), Some(true)));
+        assert_eq!(probe.error, None);
+
+        let mut def = MockProvider::model("test");
+        probe.apply_to(&mut def);
+        assert!(def.supports_tools() && def.supports_vision());
+    }
+
+    #[tokio::test]
+    async fn failed_probes_turn_capabilities_off() {
+        let mock = MockProvider::new()
+            .with_reply(MockReply::text("OK"))
+            .with_reply(MockReply::error(400))
+            .with_reply(MockReply::error(400));
+        let probe = probe_model(&client(mock, true), "mock/test", "k", 1).await;
+        assert_eq!((probe.streaming, probe.tools, probe.vision), (Some(true), Some(false), Some(false)));
+        assert!(probe.error.as_deref().unwrap().starts_with("tools: "));
+
+        let mut def = MockProvider::model("test");
+        probe.apply_to(&mut def);
+        assert!(!def.supports_tools() && !def.supports_vision());
+    }
+
+    #[tokio::test]
+    async fn transient_errors_leave_capabilities_unknown() {
+        let mock = MockProvider::new()
+            .with_reply(MockReply::error(503))
+            .with_reply(MockReply::error(429))
+            .with_reply(MockReply::error(500));
+        let probe = probe_model(&client(mock, true), "mock/test", "k", 1).await;
+        assert_eq!((probe.streaming, probe.tools, probe.vision), (None, None, None));
+        assert!(probe.error.as_deref().unwrap().starts_with("streaming: "));
+
+        let mut def = MockProvider::model("test");
+        probe.apply_to(&mut def);
+        assert!(def.supports_tools() && def.supports_vision());
+    }
+
+    #[tokio::test]
+    async fn unused_tool_is_inconclusive_and_text_models_skip_vision() {
+        let mock = MockProvider::new().with_reply(MockReply::text("It is noon."));
+        let probe = probe_model(&client(mock, false), "mock/test", "k", 1).await;
+        assert_eq!((probe.streaming, probe.tools, probe.vision), (Some(true), None, None));
+    }
+}
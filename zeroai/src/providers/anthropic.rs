@@ -1,12 +1,15 @@
-use super::sanitize;
-use super::{Provider, ProviderError};
+use super::api_error;
+use super::capture;
+use super::retry;
+use super::tool_ids;
+use super::{EventStream, Provider, ProviderError};
 use crate::types::*;
 use async_trait::async_trait;
-use futures::stream::{self, BoxStream, StreamExt};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct AnthropicProvider {
     client: Client,
@@ -34,6 +37,35 @@ struct MessagesRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<AnthropicTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<ThinkingParam>,
+}
+
+/// Extended thinking with a token budget (`{"type": "enabled", "budget_tokens": N}`).
+#[derive(Serialize)]
+struct ThinkingParam {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    budget_tokens: u64,
+}
+
+/// Smallest thinking budget the Messages API accepts.
+const MIN_THINKING_BUDGET: u64 = 1024;
+
+/// Body of `POST /v1/messages/count_tokens` (a Messages request without sampling fields).
+#[derive(Serialize)]
+struct CountTokensRequest {
+    model: String,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<AnthropicTool>>,
+}
+
+#[derive(Deserialize)]
+struct CountTokensResponse {
+    input_tokens: u64,
 }
 
 #[derive(Serialize)]
@@ -43,11 +75,40 @@ struct AnthropicMessage {
 }
 
 #[derive(Serialize)]
-struct AnthropicTool {
-    name: String,
-    description: String,
-    #[serde(rename = "input_schema")]
-    parameters: serde_json::Value,
+#[serde(untagged)]
+enum AnthropicTool {
+    Custom {
+        name: String,
+        description: String,
+        #[serde(rename = "input_schema")]
+        parameters: serde_json::Value,
+    },
+    /// Tool run by Anthropic itself (web search).
+    Server {
+        #[serde(rename = "type")]
+        tool_type: &'static str,
+        name: &'static str,
+        max_uses: u32,
+    },
+}
+
+/// Source attached to a text block; web search results are `web_search_result_location`s.
+#[derive(Deserialize)]
+struct CitationData {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+impl CitationData {
+    fn citation(&self) -> Option<Citation> {
+        Some(Citation {
+            url: self.url.clone()?,
+            title: self.title.clone(),
+            ..Citation::default()
+        })
+    }
 }
 
 #[derive(Deserialize)]
@@ -89,6 +150,8 @@ struct DeltaData {
     #[serde(default)]
     partial_json: Option<String>,
     #[serde(default)]
+    citation: Option<CitationData>,
+    #[serde(default)]
     stop_reason: Option<String>,
 }
 
@@ -116,9 +179,45 @@ fn is_anthropic_setup_or_session_token(api_key: &str) -> bool {
     api_key.starts_with("sk-ant-oat01-") || api_key.contains("sk-ant-sid")
 }
 
-/// When provider is anthropic-setup-token, always use Bearer (user explicitly chose setup-token).
+/// When provider is anthropic-setup-token or anthropic-oauth, always use Bearer (user explicitly chose it).
 fn use_bearer_auth(provider: &str, api_key: &str) -> bool {
-    provider == "anthropic-setup-token" || is_anthropic_setup_or_session_token(api_key)
+    provider == "anthropic-setup-token"
+        || provider == "anthropic-oauth"
+        || is_anthropic_setup_or_session_token(api_key)
+}
+
+/// Unix time (seconds) at which a subscription account's exhausted usage window (5-hour or
+/// weekly) resets. Sent to setup-token / OAuth accounts alongside the `-5h-` and `-7d-` details.
+const UNIFIED_RESET_HEADER: &str = "anthropic-ratelimit-unified-reset";
+
+/// Error for a failed response. A 429 that says when the limit lifts becomes `RateLimited`,
+/// so the account rests until then (hours, for a Claude plan window) instead of the generic
+/// cooldown.
+async fn error_from_response(resp: reqwest::Response) -> ProviderError {
+    let status = resp.status().as_u16();
+    let retry_after_ms = if status == 429 {
+        rate_limit_reset_ms(resp.headers(), chrono::Utc::now().timestamp_millis())
+    } else {
+        None
+    };
+    let error = api_error::from_response(resp).await;
+    match retry_after_ms {
+        Some(ms) => {
+            tracing::debug!("Anthropic rate limit for {}s: {}", ms / 1000, error);
+            ProviderError::RateLimited { retry_after_ms: Some(ms) }
+        }
+        None => error,
+    }
+}
+
+/// Milliseconds until a rate limit lifts: the subscription window reset, else `retry-after-ms`
+/// / `retry-after`.
+fn rate_limit_reset_ms(headers: &reqwest::header::HeaderMap, now_ms: i64) -> Option<u64> {
+    let reset = headers.get(UNIFIED_RESET_HEADER).and_then(|v| v.to_str().ok()?.trim().parse::<f64>().ok());
+    if let Some(reset) = reset {
+        return Some(((reset * 1000.0) as i64).saturating_sub(now_ms).max(0) as u64);
+    }
+    retry::retry_after_from_headers(headers, now_ms)
 }
 
 // ---------------------------------------------------------------------------
@@ -171,80 +270,47 @@ struct AnthropicContentResp {
     id: Option<String>,
     name: Option<String>,
     input: Option<serde_json::Value>,
+    #[serde(default)]
+    citations: Vec<CitationData>,
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl Provider for AnthropicProvider {
     fn stream(
         &self,
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+    ) -> EventStream {
         let api_key = match &options.api_key {
             Some(k) => k.clone(),
             None => return Box::pin(stream::once(async { Err(ProviderError::AuthRequired("API key required".into())) })),
         };
 
-        let provider_id = model.provider.as_str();
-        let is_setup_token = use_bearer_auth(provider_id, &api_key);
-        let mut headers = HashMap::new();
-        if is_setup_token {
-            headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
-        } else {
-            headers.insert("x-api-key".to_string(), api_key.clone());
-        }
-        headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
-        
-        let mut system_blocks = Vec::new();
-        if is_setup_token {
-            if api_key.contains("sk-ant-sid") {
-                headers.insert("anthropic-beta".to_string(), "claude-code-20250219,interleaved-thinking-2025-05-14".to_string());
-                headers.insert("user-agent".to_string(), "claude-cli/2.1.2 (external, cli)".to_string());
-                system_blocks.push(json!({"type": "text", "text": "You are Claude Code, Anthropic's official CLI for Claude."}));
-            } else {
-                headers.insert("anthropic-beta".to_string(), "oauth-2025-04-20".to_string());
-            }
-        }
-        if let Some(sys) = &context.system_prompt {
-            system_blocks.push(json!({"type": "text", "text": sys}));
-        }
-
-        let system = if system_blocks.is_empty() { None } else { Some(json!(system_blocks)) };
+        let is_setup_token = use_bearer_auth(&model.provider, &api_key);
         let requested_tools = context.tools.clone();
-        
-        let req_body = MessagesRequest {
-            model: model.id.clone(),
-            messages: convert_messages(context, is_setup_token),
-            max_tokens: options.max_tokens.unwrap_or(model.max_tokens),
-            system,
-            temperature: options.temperature,
-            stream: true,
-            tools: if context.tools.is_empty() { None } else { 
-                Some(context.tools.iter().map(|t| AnthropicTool {
-                    name: if is_setup_token { to_claude_code_name(&t.name) } else { t.name.clone() },
-                    description: t.description.clone(),
-                    parameters: t.parameters.clone(),
-                }).collect())
-            },
-        };
+        let (headers, req_body) = build_request(model, context, options, &api_key, true);
 
         let client = self.client.clone();
         let url = format!("{}/messages", model.base_url.trim_end_matches('/'));
         let model_id = model.id.clone();
         let provider_id = model.provider.clone();
+        let capture = options.capture.clone();
+        let response_headers = options.response_headers.clone();
 
         let s = async_stream::stream! {
             let mut req = client.post(&url);
             for (k, v) in &headers { req = req.header(k, v); }
-            let resp = match req.json(&req_body).send().await {
+            let resp = match capture::send(req.json(&req_body), capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => { yield Err(ProviderError::Network(e)); return; }
             };
-            let status = resp.status();
-            if !status.is_success() {
-                let body = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http { status: status.as_u16(), body: sanitize::sanitize_api_error(&body) });
+            if let Some(h) = &response_headers {
+                h.set(resp.headers());
+            }
+            if !resp.status().is_success() {
+                yield Err(error_from_response(resp).await);
                 return;
             }
             yield Ok(StreamEvent::Start);
@@ -253,6 +319,10 @@ impl Provider for AnthropicProvider {
             let mut thinking_buf = String::new();
             let mut signature_buf: Option<String> = None;
             let mut tool_calls: Vec<(String, String, String)> = Vec::new();
+            // Content block index -> index in `tool_calls`, for client `tool_use` blocks only:
+            // `server_tool_use` blocks (web search) stream their input too, which isn't ours.
+            let mut tool_blocks: HashMap<usize, usize> = HashMap::new();
+            let mut citations: Vec<Citation> = Vec::new();
             let mut usage = Usage::default();
             let mut stop_reason = StopReason::Stop;
             let mut line_buf = String::new();
@@ -279,6 +349,9 @@ impl Provider for AnthropicProvider {
                                         name = from_claude_code_name(&name, &requested_tools);
                                     }
                                     let idx = tool_calls.len();
+                                    if let Some(block) = evt.index {
+                                        tool_blocks.insert(block, idx);
+                                    }
                                     tool_calls.push((id.clone(), name.clone(), String::new()));
                                     yield Ok(StreamEvent::ToolCallStart { index: idx, id, name });
                                 }
@@ -291,25 +364,30 @@ impl Provider for AnthropicProvider {
                                 if let Some(sig) = d.signature {
                                     if signature_buf.is_none() { signature_buf = Some(String::new()); }
                                     signature_buf.as_mut().unwrap().push_str(&sig);
+                                    yield Ok(StreamEvent::ThoughtSignature(sig));
                                 }
-                                if let Some(pj) = d.partial_json {
-                                    if let Some(last) = tool_calls.last_mut() {
-                                        last.2.push_str(&pj);
-                                        yield Ok(StreamEvent::ToolCallDelta { index: tool_calls.len()-1, delta: pj });
-                                    }
+                                if let Some(citation) = d.citation.as_ref().and_then(CitationData::citation)
+                                    && !citations.contains(&citation)
+                                {
+                                    citations.push(citation.clone());
+                                    yield Ok(StreamEvent::CitationDelta(citation));
+                                }
+                                if let Some(pj) = d.partial_json
+                                    && let Some(&idx) = evt.index.and_then(|block| tool_blocks.get(&block))
+                                {
+                                    tool_calls[idx].2.push_str(&pj);
+                                    yield Ok(StreamEvent::ToolCallDelta { index: idx, delta: pj });
                                 }
                             }
                         }
                         "content_block_stop" => {
-                            if let Some(idx) = evt.index {
-                                if idx < tool_calls.len() {
-                                    let (id, name, args) = &tool_calls[idx];
-                                    yield Ok(StreamEvent::ToolCallEnd { index: idx, tool_call: ToolCall { id: id.clone(), name: name.clone(), arguments: serde_json::from_str(args).unwrap_or(json!({})) } });
-                                }
+                            if let Some(&idx) = evt.index.and_then(|block| tool_blocks.get(&block)) {
+                                let (id, name, args) = &tool_calls[idx];
+                                yield Ok(StreamEvent::ToolCallEnd { index: idx, tool_call: ToolCall { id: id.clone(), name: name.clone(), arguments: serde_json::from_str(args).unwrap_or(json!({})) } });
                             }
                         }
                         "message_delta" => {
-                            if let Some(d) = evt.delta { if let Some(sr) = d.stop_reason { stop_reason = match sr.as_str() { "end_turn" => StopReason::Stop, "tool_use" => StopReason::ToolUse, _ => StopReason::Stop }; } }
+                            if let Some(d) = evt.delta && let Some(sr) = d.stop_reason { stop_reason = StopReason::from_anthropic(&sr); }
                             if let Some(u) = evt.usage { usage.output_tokens = u.output_tokens; }
                         }
                         _ => {}
@@ -323,7 +401,7 @@ impl Provider for AnthropicProvider {
             for (id, name, args) in tool_calls { content.push(ContentBlock::ToolCall(ToolCall { id, name, arguments: serde_json::from_str(&args).unwrap_or(json!({})) })); }
             
             usage.total_tokens = usage.input_tokens + usage.output_tokens;
-            yield Ok(StreamEvent::Done { message: AssistantMessage { content, model: model_id, provider: provider_id, usage: Some(usage), stop_reason } });
+            yield Ok(StreamEvent::Done { message: AssistantMessage { content, model: model_id, provider: provider_id, usage: Some(usage), stop_reason, citations, safety_ratings: Vec::new() } });
         };
         Box::pin(s)
     }
@@ -343,73 +421,9 @@ impl Provider for AnthropicProvider {
             }
         };
 
-        let provider_id = model.provider.as_str();
-        let is_setup_token = use_bearer_auth(provider_id, &api_key);
-        let mut headers = HashMap::new();
-        if is_setup_token {
-            headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
-        } else {
-            headers.insert("x-api-key".to_string(), api_key.clone());
-        }
-        headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
-
-        let mut system_blocks = Vec::new();
-        if is_setup_token {
-            if api_key.contains("sk-ant-sid") {
-                headers.insert(
-                    "anthropic-beta".to_string(),
-                    "claude-code-20250219,interleaved-thinking-2025-05-14".to_string(),
-                );
-                headers.insert(
-                    "user-agent".to_string(),
-                    "claude-cli/2.1.2 (external, cli)".to_string(),
-                );
-                system_blocks.push(json!({
-                    "type": "text",
-                    "text": "You are Claude Code, Anthropic's official CLI for Claude."
-                }));
-            } else {
-                headers.insert("anthropic-beta".to_string(), "oauth-2025-04-20".to_string());
-            }
-        }
-        if let Some(sys) = &context.system_prompt {
-            system_blocks.push(json!({"type": "text", "text": sys}));
-        }
-
-        let system = if system_blocks.is_empty() {
-            None
-        } else {
-            Some(json!(system_blocks))
-        };
+        let is_setup_token = use_bearer_auth(&model.provider, &api_key);
         let requested_tools = context.tools.clone();
-
-        let req_body = MessagesRequest {
-            model: model.id.clone(),
-            messages: convert_messages(context, is_setup_token),
-            max_tokens: options.max_tokens.unwrap_or(model.max_tokens),
-            system,
-            temperature: options.temperature,
-            stream: false,
-            tools: if context.tools.is_empty() {
-                None
-            } else {
-                Some(
-                    context
-                        .tools
-                        .iter()
-                        .map(|t| AnthropicTool {
-                            name: if is_setup_token {
-                                to_claude_code_name(&t.name)
-                            } else {
-                                t.name.clone()
-                            },
-                            description: t.description.clone(),
-                            parameters: t.parameters.clone(),
-                        })
-                        .collect(),
-                )
-            },
-        };
+        let (headers, req_body) = build_request(model, context, options, &api_key, false);
 
         let url = format!("{}/messages", model.base_url.trim_end_matches('/'));
         let mut req = self.client.post(&url);
@@ -417,22 +431,26 @@ impl Provider for AnthropicProvider {
             req = req.header(k, v);
         }
 
-        let resp = req.json(&req_body).send().await?;
-        let status = resp.status();
-        if !status.is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status: status.as_u16(),
-                body: sanitize::sanitize_api_error(&body),
-            });
+        let resp = capture::send(req.json(&req_body), options.capture.as_ref()).await?;
+        if let Some(h) = &options.response_headers {
+            h.set(resp.headers());
+        }
+        if !resp.status().is_success() {
+            return Err(error_from_response(resp).await);
         }
 
         let msg_resp: MessagesResponse = resp.json().await?;
 
         let mut content = Vec::new();
+        let mut citations: Vec<Citation> = Vec::new();
         for block in msg_resp.content {
             match block.block_type.as_str() {
                 "text" => {
+                    for citation in block.citations.iter().filter_map(CitationData::citation) {
+                        if !citations.contains(&citation) {
+                            citations.push(citation);
+                        }
+                    }
                     if let Some(text) = block.text {
                         content.push(ContentBlock::Text(TextContent { text }));
                     }
@@ -465,11 +483,7 @@ impl Provider for AnthropicProvider {
             ..Default::default()
         };
 
-        let stop_reason = match msg_resp.stop_reason.as_deref() {
-            Some("end_turn") => StopReason::Stop,
-            Some("tool_use") => StopReason::ToolUse,
-            _ => StopReason::Stop,
-        };
+        let stop_reason = msg_resp.stop_reason.as_deref().map_or(StopReason::Stop, StopReason::from_anthropic);
 
         Ok(AssistantMessage {
             content,
@@ -477,29 +491,186 @@ impl Provider for AnthropicProvider {
             provider: model.provider.clone(),
             usage: Some(usage),
             stop_reason,
+            citations,
+            safety_ratings: Vec::new(),
         })
     }
 
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
     async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
         Ok(static_anthropic_models())
     }
+
+    async fn count_tokens(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<Option<u64>, ProviderError> {
+        let Some(api_key) = options.api_key.as_deref() else {
+            return Err(ProviderError::AuthRequired("API key required for Anthropic".into()));
+        };
+        let (headers, body) = build_request(model, context, options, api_key, false);
+        let req_body = CountTokensRequest {
+            model: body.model,
+            messages: body.messages,
+            system: body.system,
+            tools: body.tools,
+        };
+
+        let url = format!("{}/messages/count_tokens", model.base_url.trim_end_matches('/'));
+        let mut req = self.client.post(&url);
+        for (k, v) in &headers {
+            req = req.header(k, v);
+        }
+        let resp = req.json(&req_body).send().await?;
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
+        }
+        let counted: CountTokensResponse = resp.json().await?;
+        Ok(Some(counted.input_tokens))
+    }
+}
+
+/// Headers and body for a Messages API call. Setup/OAuth tokens get Bearer auth, the beta
+/// headers they require and Claude Code tool names.
+fn build_request(
+    model: &ModelDef,
+    context: &ChatContext,
+    options: &RequestOptions,
+    api_key: &str,
+    stream: bool,
+) -> (HashMap<String, String>, MessagesRequest) {
+    let is_setup_token = use_bearer_auth(&model.provider, api_key);
+    let mut headers = HashMap::new();
+    if is_setup_token {
+        headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
+    } else {
+        headers.insert("x-api-key".to_string(), api_key.to_string());
+    }
+    headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
+
+    let mut betas = Vec::new();
+    let mut system_blocks = Vec::new();
+    if is_setup_token {
+        if api_key.contains("sk-ant-sid") {
+            betas.push("claude-code-20250219");
+            headers.insert(
+                "user-agent".to_string(),
+                "claude-cli/2.1.2 (external, cli)".to_string(),
+            );
+            system_blocks.push(json!({
+                "type": "text",
+                "text": "You are Claude Code, Anthropic's official CLI for Claude."
+            }));
+        } else {
+            betas.push("oauth-2025-04-20");
+        }
+    }
+    // Model and request headers; their `anthropic-beta` values join the ones above.
+    for (k, v) in model.headers.iter().chain(&options.extra_headers).flatten() {
+        if k.eq_ignore_ascii_case(AnthropicBetas::HEADER) {
+            betas.extend(v.split(',').map(str::trim).filter(|b| !b.is_empty()));
+        } else {
+            headers.insert(k.clone(), v.clone());
+        }
+    }
+    betas.extend(options.anthropic_betas.names());
+    let mut seen = HashSet::new();
+    betas.retain(|b| seen.insert(*b));
+    if !betas.is_empty() {
+        headers.insert(AnthropicBetas::HEADER.to_string(), betas.join(","));
+    }
+    if let Some(sys) = &context.system_prompt {
+        system_blocks.push(json!({"type": "text", "text": sys}));
+    }
+
+    let system = if system_blocks.is_empty() {
+        None
+    } else {
+        Some(json!(system_blocks))
+    };
+
+    let mut tools: Vec<AnthropicTool> = context
+        .tools
+        .iter()
+        .map(|t| AnthropicTool::Custom {
+            name: if is_setup_token {
+                to_claude_code_name(&t.name)
+            } else {
+                t.name.clone()
+            },
+            description: t.description.clone(),
+            parameters: t.parameters.clone(),
+        })
+        .collect();
+    if options.web_search {
+        tools.push(AnthropicTool::Server {
+            tool_type: "web_search_20250305",
+            name: WEB_SEARCH_TOOL,
+            max_uses: 5,
+        });
+    }
+
+    // The budget has to be below max_tokens, and thinking doesn't allow setting temperature.
+    let max_tokens = options.max_tokens.unwrap_or(model.max_tokens);
+    let thinking = model
+        .thinking_budget(options)
+        .map(|budget| budget.min(max_tokens.saturating_sub(1)))
+        .filter(|budget| *budget >= MIN_THINKING_BUDGET)
+        .map(|budget_tokens| ThinkingParam { kind: "enabled", budget_tokens });
+
+    let body = MessagesRequest {
+        model: model.id.clone(),
+        messages: convert_messages(context, is_setup_token),
+        max_tokens,
+        system,
+        temperature: if thinking.is_some() { None } else { options.temperature },
+        stream,
+        tools: if tools.is_empty() { None } else { Some(tools) },
+        thinking,
+    };
+    (headers, body)
 }
 
 fn convert_messages(context: &ChatContext, is_setup_token: bool) -> Vec<AnthropicMessage> {
     context.messages.iter().map(|m| match m {
-        Message::User(u) => AnthropicMessage { role: "user".into(), content: json!(u.content.iter().filter_map(|b| match b {
+        Message::User(u) => AnthropicMessage { role: "user".into(), content: json!(user_content_blocks(&u.content)) },
+        // Thinking goes back with its signature, which extended thinking needs to continue a
+        // tool-use turn; unsigned thinking (e.g. from another provider) would be rejected.
+        Message::Assistant(a) => AnthropicMessage { role: "assistant".into(), content: json!(a.content.iter().filter_map(|b| match b {
+            ContentBlock::Thinking(th) => th.signature.as_ref().map(|sig| json!({"type": "thinking", "thinking": th.thinking, "signature": sig})),
             ContentBlock::Text(t) => Some(json!({"type": "text", "text": t.text})),
-            _ => None
-        }).collect::<Vec<_>>()) },
-        Message::Assistant(a) => AnthropicMessage { role: "assistant".into(), content: json!(a.content.iter().map(|b| match b {
-            ContentBlock::Text(t) => json!({"type": "text", "text": t.text}),
             ContentBlock::ToolCall(tc) => {
                 let name = if is_setup_token { to_claude_code_name(&tc.name) } else { tc.name.clone() };
-                json!({"type": "tool_use", "id": tc.id, "name": name, "input": tc.arguments})
+                Some(json!({"type": "tool_use", "id": tool_ids::sanitize(&tc.id), "name": name, "input": tc.arguments}))
             },
-            _ => json!({})
+            _ => None
         }).collect::<Vec<_>>()) },
-        Message::ToolResult(tr) => AnthropicMessage { role: "user".into(), content: json!([{"type": "tool_result", "tool_use_id": tr.tool_call_id, "content": user_content_to_text(&tr.content), "is_error": tr.is_error}]) },
+        Message::ToolResult(tr) => {
+            // Text-only results stay a plain string; results with images need content blocks.
+            let content = if tr.content.iter().any(|b| matches!(b, ContentBlock::Image(_))) {
+                json!(user_content_blocks(&tr.content))
+            } else {
+                json!(user_content_to_text(&tr.content))
+            };
+            AnthropicMessage { role: "user".into(), content: json!([{"type": "tool_result", "tool_use_id": tool_ids::sanitize(&tr.tool_call_id), "content": content, "is_error": tr.is_error}]) }
+        }
+    }).collect()
+}
+
+/// Text and base64 image blocks, as accepted in user messages and `tool_result` content.
+fn user_content_blocks(blocks: &[ContentBlock]) -> Vec<serde_json::Value> {
+    blocks.iter().filter_map(|b| match b {
+        ContentBlock::Text(t) => Some(json!({"type": "text", "text": t.text})),
+        ContentBlock::Image(img) => Some(json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": img.mime_type, "data": img.data}
+        })),
+        _ => None
     }).collect()
 }
 
@@ -525,10 +696,9 @@ fn ant(
         base_url: url.into(),
         reasoning,
         input: vec![InputModality::Text, InputModality::Image],
-        cost: ModelCost::default(),
         context_window: ctx,
         max_tokens: max_tok,
-        headers: None,
+        ..Default::default()
     }
 }
 
@@ -569,7 +739,15 @@ pub fn static_anthropic_models() -> Vec<ModelDef> {
 
 /// Static model list for Anthropic setup-token (OAuth / Claude Code). Aligned with openclaw ANTHROPIC_OAUTH_MODEL_KEYS.
 pub fn static_anthropic_setup_token_models() -> Vec<ModelDef> {
-    let p = "anthropic-setup-token";
+    claude_subscription_models("anthropic-setup-token")
+}
+
+/// Static model list for Anthropic OAuth login (same allowlist as setup-token).
+pub fn static_anthropic_oauth_models() -> Vec<ModelDef> {
+    claude_subscription_models("anthropic-oauth")
+}
+
+fn claude_subscription_models(p: &str) -> Vec<ModelDef> {
     let url = "https://api.anthropic.com/v1";
     const CTX: u64 = 200_000;
     vec![
@@ -579,3 +757,249 @@ pub fn static_anthropic_setup_token_models() -> Vec<ModelDef> {
         ant(p, url, "claude-haiku-4-5", "Claude Haiku 4.5", true, CTX, 64_000),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> ContentBlock {
+        ContentBlock::Image(ImageContent {
+            data: "aGVsbG8=".into(),
+            mime_type: "image/png".into(),
+        })
+    }
+
+    #[test]
+    fn rate_limit_waits_for_the_plan_window_reset() {
+        let now_ms = 1_700_000_000_000;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "30".parse().unwrap());
+        assert_eq!(rate_limit_reset_ms(&headers, now_ms), Some(30_000));
+
+        let reset = now_ms / 1000 + 4 * 60 * 60;
+        headers.insert(UNIFIED_RESET_HEADER, reset.to_string().parse().unwrap());
+        assert_eq!(rate_limit_reset_ms(&headers, now_ms), Some(4 * 60 * 60 * 1000));
+        assert_eq!(rate_limit_reset_ms(&reqwest::header::HeaderMap::new(), now_ms), None);
+    }
+
+    #[test]
+    fn images_become_base64_source_blocks() {
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![
+                Message::User(UserMessage {
+                    content: vec![ContentBlock::Text(TextContent { text: "what is this?".into() }), image()],
+                }),
+                Message::ToolResult(ToolResultMessage {
+                    tool_call_id: "toolu_1".into(),
+                    tool_name: "screenshot".into(),
+                    content: vec![image()],
+                    is_error: false,
+                }),
+                Message::ToolResult(ToolResultMessage {
+                    tool_call_id: "toolu_2".into(),
+                    tool_name: "read".into(),
+                    content: vec![ContentBlock::Text(TextContent { text: "ok".into() })],
+                    is_error: false,
+                }),
+            ],
+            tools: Vec::new(),
+        };
+        let msgs = convert_messages(&context, false);
+
+        let source = json!({"type": "base64", "media_type": "image/png", "data": "aGVsbG8="});
+        assert_eq!(msgs[0].content[1], json!({"type": "image", "source": source}));
+        assert_eq!(msgs[1].content[0]["content"][0]["source"], source);
+        assert_eq!(msgs[2].content[0]["content"], "ok");
+    }
+
+    #[test]
+    fn foreign_tool_call_ids_are_sanitized_consistently() {
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![
+                Message::Assistant(AssistantMessage {
+                    content: vec![ContentBlock::ToolCall(ToolCall {
+                        id: "functions.get_time:0".into(),
+                        name: "get_time".into(),
+                        arguments: json!({}),
+                    })],
+                    model: "kimi-k2".into(),
+                    provider: "moonshot".into(),
+                    usage: None,
+                    stop_reason: StopReason::ToolUse,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
+                }),
+                Message::ToolResult(ToolResultMessage {
+                    tool_call_id: "functions.get_time:0".into(),
+                    tool_name: "get_time".into(),
+                    content: vec![ContentBlock::Text(TextContent { text: "noon".into() })],
+                    is_error: false,
+                }),
+            ],
+            tools: Vec::new(),
+        };
+        let msgs = convert_messages(&context, false);
+        assert_eq!(msgs[0].content[0]["id"], "functions_get_time_0");
+        assert_eq!(msgs[1].content[0]["tool_use_id"], "functions_get_time_0");
+    }
+
+    #[test]
+    fn signed_thinking_is_replayed_before_tool_use() {
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![Message::Assistant(AssistantMessage {
+                content: vec![
+                    ContentBlock::Thinking(ThinkingContent { thinking: "check the clock".into(), signature: Some("sig==".into()) }),
+                    ContentBlock::Thinking(ThinkingContent { thinking: "unsigned".into(), signature: None }),
+                    ContentBlock::ToolCall(ToolCall { id: "toolu_1".into(), name: "get_time".into(), arguments: json!({}) }),
+                ],
+                model: "claude-sonnet-4-5".into(),
+                provider: "anthropic".into(),
+                usage: None,
+                stop_reason: StopReason::ToolUse,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
+            })],
+            tools: Vec::new(),
+        };
+        let msgs = convert_messages(&context, false);
+        let content = msgs[0].content.as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0], json!({"type": "thinking", "thinking": "check the clock", "signature": "sig=="}));
+        assert_eq!(content[1]["type"], "tool_use");
+    }
+
+    #[test]
+    fn betas_merge_token_model_and_request() {
+        let mut model = static_anthropic_setup_token_models().remove(0);
+        ModelOverride {
+            anthropic_betas: Some(AnthropicBetas { context_1m: true, ..Default::default() }),
+            ..Default::default()
+        }
+        .apply_to(&mut model);
+        let context = ChatContext {
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+        };
+        let options = RequestOptions {
+            anthropic_betas: AnthropicBetas { interleaved_thinking: true, context_1m: true, ..Default::default() },
+            ..Default::default()
+        };
+        let (headers, _) = build_request(&model, &context, &options, "sk-ant-sid01-x", false);
+        assert_eq!(
+            headers["anthropic-beta"],
+            "claude-code-20250219,context-1m-2025-08-07,interleaved-thinking-2025-05-14"
+        );
+
+        let plain = static_anthropic_models().remove(0);
+        let (headers, _) = build_request(&plain, &context, &RequestOptions::default(), "sk-ant-api03-x", false);
+        assert!(!headers.contains_key("anthropic-beta"));
+    }
+
+    #[test]
+    fn thinking_budget_comes_from_model_or_request() {
+        let mut model = static_anthropic_models().remove(0);
+        model.thinking_budgets = Some(ThinkingBudgets { high: 32_000, ..Default::default() });
+        let context = ChatContext {
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+        };
+        let thinking = |options: RequestOptions| {
+            let (_, body) = build_request(&model, &context, &options, "sk-ant-api03-x", false);
+            serde_json::to_value(&body).unwrap()
+        };
+
+        let body = thinking(RequestOptions { reasoning: Some(ThinkingLevel::High), temperature: Some(0.2), ..Default::default() });
+        assert_eq!(body["thinking"], json!({"type": "enabled", "budget_tokens": 32_000}));
+        assert!(body.get("temperature").is_none());
+
+        let body = thinking(RequestOptions { thinking_budget: Some(4096), max_tokens: Some(2048), ..Default::default() });
+        assert_eq!(body["thinking"]["budget_tokens"], 2047);
+
+        assert!(thinking(RequestOptions::default()).get("thinking").is_none());
+    }
+
+    #[test]
+    fn web_search_adds_server_tool_and_reads_citations() {
+        let model = static_anthropic_models().remove(0);
+        let context = ChatContext {
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+        };
+        let options = RequestOptions {
+            web_search: true,
+            ..Default::default()
+        };
+        let (_, body) = build_request(&model, &context, &options, "sk-ant-api03-x", false);
+        let body = serde_json::to_value(&body).unwrap();
+        assert_eq!(
+            body["tools"],
+            json!([{"type": "web_search_20250305", "name": "web_search", "max_uses": 5}])
+        );
+
+        let block: AnthropicContentResp = serde_json::from_value(json!({
+            "type": "text",
+            "text": "Rust 1.0 shipped in 2015.",
+            "citations": [{"type": "web_search_result_location", "url": "https://blog.rust-lang.org", "title": "Rust Blog", "cited_text": "..."}]
+        }))
+        .unwrap();
+        let citations: Vec<_> = block.citations.iter().filter_map(CitationData::citation).collect();
+        assert_eq!(citations[0].url, "https://blog.rust-lang.org");
+        assert_eq!(citations[0].title.as_deref(), Some("Rust Blog"));
+    }
+
+    #[tokio::test]
+    async fn server_tool_input_stays_out_of_client_tool_calls() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let events = [
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 10}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "lookup"}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{\"q\":\"rust\"}"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "server_tool_use", "id": "srvtoolu_1", "name": "web_search"}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"query\":\"rust 2024\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "content_block_start", "index": 2, "content_block": {"type": "tool_use", "id": "toolu_2", "name": "fetch"}}),
+            json!({"type": "content_block_delta", "index": 2, "delta": {"type": "input_json_delta", "partial_json": "{\"url\":\"https://rust-lang.org\"}"}}),
+            json!({"type": "content_block_stop", "index": 2}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 5}}),
+        ];
+        let body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let reply = format!("HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(reply.as_bytes()).await.unwrap();
+        });
+
+        let mut model = static_anthropic_models().remove(0);
+        model.base_url = format!("http://{}", addr);
+        let context = ChatContext { system_prompt: None, messages: Vec::new(), tools: Vec::new() };
+        let options = RequestOptions { api_key: Some("sk-ant-api03-x".into()), ..Default::default() };
+        let mut stream = AnthropicProvider::new().stream(&model, &context, &options);
+        let mut ended = Vec::new();
+        let mut done = None;
+        while let Some(event) = stream.next().await {
+            match event.unwrap() {
+                StreamEvent::ToolCallEnd { index, tool_call } => ended.push((index, tool_call.arguments)),
+                StreamEvent::Done { message } => done = Some(message),
+                _ => {}
+            }
+        }
+
+        assert_eq!(ended, [(0, json!({"q": "rust"})), (1, json!({"url": "https://rust-lang.org"}))]);
+        let calls: Vec<_> = done.unwrap().content.into_iter().filter_map(|b| match b {
+            ContentBlock::ToolCall(tc) => Some((tc.id, tc.arguments)),
+            _ => None,
+        }).collect();
+        assert_eq!(calls, [("toolu_1".to_string(), json!({"q": "rust"})), ("toolu_2".to_string(), json!({"url": "https://rust-lang.org"}))]);
+    }
+}
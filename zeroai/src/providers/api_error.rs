@@ -0,0 +1,229 @@
+//! Normalize provider error replies (OpenAI, Anthropic, Google RPC status, OpenRouter, Hugging
+//! Face, Cloudflare AI Gateway) into one [`ApiError`] shape.
+
+use super::ProviderError;
+use super::retry;
+use super::sanitize::sanitize_api_error;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// A provider's error reply. Fields the provider didn't send are `None`; `message` falls back
+/// to the raw body when it isn't a known JSON error format.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    /// e.g. `rate_limit_exceeded`, or Google's numeric code (`429`).
+    pub code: Option<String>,
+    /// e.g. `invalid_request_error`, or Google's RPC status (`RESOURCE_EXHAUSTED`).
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// Secret-scrubbed and truncated.
+    pub message: String,
+    /// The request parameter at fault, when the provider names one.
+    pub param: Option<String>,
+    /// The provider's ID for the failed request, from the body or `x-request-id` / `request-id`.
+    pub upstream_request_id: Option<String>,
+    /// Delay the response headers asked for before retrying.
+    #[serde(skip)]
+    pub retry_after_ms: Option<u64>,
+    /// The provider's `x-should-retry` verdict.
+    #[serde(skip)]
+    pub should_retry: Option<bool>,
+}
+
+impl ApiError {
+    /// Parse an error body.
+    pub fn parse(body: &str) -> Self {
+        let value = match serde_json::from_str(body) {
+            // Google's streaming endpoints wrap the error in an array.
+            Ok(Value::Array(mut items)) if !items.is_empty() => items.swap_remove(0),
+            Ok(value) => value,
+            Err(_) => Value::Null,
+        };
+        let mut error = Self::from_json(&value).unwrap_or_else(|| Self {
+            message: body.trim().to_string(),
+            ..Self::default()
+        });
+        error.message = sanitize_api_error(&error.message);
+        error
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let upstream_request_id = value.get("request_id").and_then(text);
+        let err = match value.get("error") {
+            Some(err @ Value::Object(_)) => err,
+            // Hugging Face: `{"error": "...", "error_type": "validation"}`, a list of messages,
+            // and `estimated_time` (seconds) while a model is still loading.
+            Some(Value::String(_) | Value::Array(_)) => {
+                let message = match &value["error"] {
+                    Value::Array(items) => items.iter().filter_map(text).collect::<Vec<_>>().join("; "),
+                    message => text(message).unwrap_or_default(),
+                };
+                return Some(Self {
+                    kind: value.get("error_type").and_then(text),
+                    message,
+                    upstream_request_id,
+                    retry_after_ms: value.get("estimated_time").and_then(Value::as_f64).map(|s| (s * 1000.0) as u64),
+                    ..Self::default()
+                });
+            }
+            // Cloudflare's API envelope: `{"success": false, "errors": [{"code": 2009, "message": ...}]}`.
+            _ if value.pointer("/errors/0/message").is_some() => {
+                let errors = value["errors"].as_array().into_iter().flatten();
+                return Some(Self {
+                    code: value.pointer("/errors/0/code").and_then(text),
+                    message: errors.filter_map(|e| e.get("message").and_then(text)).collect::<Vec<_>>().join("; "),
+                    upstream_request_id,
+                    ..Self::default()
+                });
+            }
+            // Bare `{"message": ...}` or FastAPI-style `{"detail": ...}`.
+            _ => {
+                let message = value.get("message").or_else(|| value.get("detail")).and_then(text)?;
+                return Some(Self {
+                    code: value.get("code").and_then(text),
+                    kind: value.get("type").and_then(text),
+                    message,
+                    upstream_request_id,
+                    ..Self::default()
+                });
+            }
+        };
+        let mut message = err.get("message").and_then(text).unwrap_or_default();
+        // OpenRouter passes the routed provider's own error along as `metadata.raw`.
+        if let Some(raw) = err.pointer("/metadata/raw").and_then(Value::as_str) {
+            let inner = Self::parse(raw).message;
+            if message.is_empty() {
+                message = inner;
+            } else if !inner.is_empty() && inner != message {
+                message = format!("{}: {}", message, inner);
+            }
+        }
+        Some(Self {
+            code: err.get("code").and_then(text),
+            kind: err.get("type").or_else(|| err.get("status")).and_then(text),
+            message,
+            param: err.get("param").and_then(text),
+            upstream_request_id,
+            ..Self::default()
+        })
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The provider's request ID from response headers (`x-request-id`, else `request-id`).
+pub fn upstream_request_id(headers: &HeaderMap) -> Option<String> {
+    ["x-request-id", "request-id"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(String::from)
+}
+
+/// Error for a failed response, consuming its body. A 429 that says when to retry becomes
+/// `RateLimited`; other errors keep the headers' retry hints on the [`ApiError`].
+pub async fn from_response(resp: reqwest::Response) -> ProviderError {
+    let status = resp.status().as_u16();
+    let headers = resp.headers();
+    let request_id = upstream_request_id(headers);
+    let retry_after_ms = retry::retry_after_from_headers(headers, chrono::Utc::now().timestamp_millis());
+    let should_retry = retry::should_retry_from_headers(headers);
+    let mut error = ApiError::parse(&resp.text().await.unwrap_or_default());
+    if status == 429
+        && should_retry != Some(false)
+        && let Some(ms) = retry_after_ms
+    {
+        tracing::debug!("Rate limited for {}ms: {}", ms, error);
+        return ProviderError::RateLimited { retry_after_ms: Some(ms) };
+    }
+    if error.upstream_request_id.is_none() {
+        error.upstream_request_id = request_id;
+    }
+    error.retry_after_ms = retry_after_ms.or(error.retry_after_ms);
+    error.should_retry = should_retry;
+    ProviderError::Http { status, error: Box::new(error) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_formats_are_normalized() {
+        let openai = ApiError::parse(
+            r#"{"error": {"message": "max_tokens is too large", "type": "invalid_request_error", "param": "max_tokens", "code": null}}"#,
+        );
+        assert_eq!(openai.kind.as_deref(), Some("invalid_request_error"));
+        assert_eq!(openai.param.as_deref(), Some("max_tokens"));
+        assert_eq!(openai.code, None);
+
+        let anthropic = ApiError::parse(
+            r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}, "request_id": "req_011"}"#,
+        );
+        assert_eq!(anthropic.kind.as_deref(), Some("overloaded_error"));
+        assert_eq!(anthropic.upstream_request_id.as_deref(), Some("req_011"));
+
+        let google = ApiError::parse(
+            r#"[{"error": {"code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED"}}]"#,
+        );
+        assert_eq!(
+            (google.code.as_deref(), google.kind.as_deref(), google.message.as_str()),
+            (Some("429"), Some("RESOURCE_EXHAUSTED"), "Quota exceeded")
+        );
+
+        let openrouter = ApiError::parse(
+            r#"{"error": {"message": "Provider returned error", "code": 400, "metadata": {"raw": "{\"error\":{\"message\":\"bad tool schema\"}}", "provider_name": "Fireworks"}}}"#,
+        );
+        assert_eq!(openrouter.message, "Provider returned error: bad tool schema");
+        assert_eq!(openrouter.code.as_deref(), Some("400"));
+
+        let huggingface = ApiError::parse(r#"{"error": "Model google/gemma-7b is currently loading", "estimated_time": 20.5}"#);
+        assert_eq!((huggingface.message.as_str(), huggingface.retry_after_ms), ("Model google/gemma-7b is currently loading", Some(20_500)));
+        let validation = ApiError::parse(r#"{"error": ["Input validation error: `inputs` must have less than 4096 tokens"], "error_type": "validation"}"#);
+        assert_eq!(validation.kind.as_deref(), Some("validation"));
+        assert!(validation.message.starts_with("Input validation error"));
+
+        let cloudflare = ApiError::parse(r#"{"success": false, "result": [], "messages": [], "errors": [{"code": 2009, "message": "Unauthorized"}]}"#);
+        assert_eq!((cloudflare.code.as_deref(), cloudflare.message.as_str()), (Some("2009"), "Unauthorized"));
+
+        let plain = ApiError::parse("Bad Gateway sk-secret123");
+        assert_eq!(plain.message, "Bad Gateway [REDACTED]");
+        assert_eq!(plain.kind, None);
+    }
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> reqwest::Response {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        reqwest::Response::from(builder.body(r#"{"error": {"message": "slow down"}}"#).unwrap())
+    }
+
+    #[tokio::test]
+    async fn retry_headers_reach_the_error() {
+        let limited = from_response(response(429, &[("retry-after", "12")])).await;
+        assert!(matches!(limited, ProviderError::RateLimited { retry_after_ms: Some(12_000) }));
+
+        // Told not to retry: stays an HTTP error so retries stop, but keeps the delay.
+        let refused = from_response(response(429, &[("retry-after-ms", "250"), ("x-should-retry", "false")])).await;
+        let ProviderError::Http { status: 429, error } = &refused else { panic!("{:?}", refused) };
+        assert_eq!((error.retry_after_ms, error.should_retry), (Some(250), Some(false)));
+        assert!(retry::is_non_retryable(&refused));
+
+        let unhinted = from_response(response(429, &[])).await;
+        assert!(matches!(unhinted, ProviderError::Http { status: 429, .. }));
+    }
+}
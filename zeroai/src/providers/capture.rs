@@ -0,0 +1,300 @@
+//! Debug capture and replay of upstream HTTP traffic (`RequestOptions::capture`).
+//!
+//! When recording, each upstream call of a request writes two files in the capture directory:
+//! `<id>-<n>.request` (request line, headers with credentials redacted, exact body) and
+//! `<id>-<n>.response` (status line, headers, then the raw body or SSE bytes as received).
+//!
+//! When replaying, no request leaves the process: the call is answered with the recorded
+//! response whose request has the same hash (method, redacted URL and body). Providers parse
+//! it exactly as they would a live one.
+//!
+//! On wasm32 there is no filesystem to capture to: calls go out as if capture were off.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Body, RequestBuilder, Response, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Headers whose values are replaced with [`REDACTED`] in captures.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "api-key",
+    "cookie",
+    "set-cookie",
+];
+
+/// Query parameters whose values are replaced with [`REDACTED`] (Gemini sends `?key=`).
+const SECRET_QUERY_PARAMS: &[&str] = &["key", "api_key", "access_token"];
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Where to record the upstream traffic of one request, or where to replay it from.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+#[derive(Debug, Clone)]
+enum Mode {
+    Record { id: String, calls: Arc<AtomicUsize> },
+    Replay,
+}
+
+impl Capture {
+    /// Record into `dir`, naming the files after `id`.
+    pub fn new(dir: impl Into<PathBuf>, id: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            mode: Mode::Record {
+                id: id.into(),
+                calls: Arc::new(AtomicUsize::new(0)),
+            },
+        }
+    }
+
+    /// Answer upstream calls from the recordings in `dir` instead of the network.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            mode: Mode::Replay,
+        }
+    }
+}
+
+/// Send `req`, recording it and its response, or answering it from a recording.
+///
+/// A recorded response body is copied to disk as it is read, so streaming callers see the
+/// same chunks they would without capture. A replayed call with no recording gets a 404
+/// response naming the request hash.
+pub async fn send(req: RequestBuilder, capture: Option<&Capture>) -> reqwest::Result<Response> {
+    match capture {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(capture) => record_or_replay(req, capture).await,
+        _ => req.send().await,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn record_or_replay(req: RequestBuilder, capture: &Capture) -> reqwest::Result<Response> {
+    let (client, request) = req.build_split();
+    let request = request?;
+    let url = redact_url(request.url());
+    let body = request.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+
+    let (id, calls) = match &capture.mode {
+        Mode::Replay => {
+            let hash = request_hash(request.method().as_str(), url.as_str(), body);
+            return Ok(replay(&capture.dir, &hash));
+        }
+        Mode::Record { id, calls } => (id, calls),
+    };
+    let stem = format!("{}-{}", id, calls.fetch_add(1, Ordering::Relaxed) + 1);
+
+    let mut head = format!("{} {} {:?}\n", request.method(), url, request.version());
+    push_headers(&mut head, request.headers());
+    head.push('\n');
+    let mut record = head.into_bytes();
+    record.extend_from_slice(body);
+    append(&capture.dir.join(format!("{}.request", stem)), &record);
+
+    let resp = client.execute(request).await?;
+    let mut head = format!("{:?} {}\n", resp.version(), resp.status());
+    push_headers(&mut head, resp.headers());
+    head.push('\n');
+    let path = capture.dir.join(format!("{}.response", stem));
+    append(&path, head.as_bytes());
+
+    let mut teed = http::Response::new(Body::from(""));
+    *teed.status_mut() = resp.status();
+    *teed.version_mut() = resp.version();
+    *teed.headers_mut() = resp.headers().clone();
+    let body = resp.bytes_stream().inspect(move |chunk| {
+        if let Ok(bytes) = chunk {
+            append(&path, bytes);
+        }
+    });
+    *teed.body_mut() = Body::wrap_stream(body);
+    Ok(Response::from(teed))
+}
+
+/// Identifies a recorded request for replay.
+fn request_hash(method: &str, url: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{} {}\n", method, url));
+    hasher.update(body);
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The recorded response for `hash`; the latest recording wins when there are several.
+#[cfg(not(target_arch = "wasm32"))]
+fn replay(dir: &Path, hash: &str) -> Response {
+    let mut requests: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "request"))
+        .collect();
+    requests.sort();
+    let recorded = requests
+        .iter()
+        .rev()
+        .filter(|p| {
+            std::fs::read(p)
+                .ok()
+                .and_then(|bytes| parse_request(&bytes).map(|(m, u, b)| request_hash(m, u, b)))
+                .is_some_and(|h| h == hash)
+        })
+        .find_map(|p| std::fs::read(p.with_extension("response")).ok())
+        .and_then(|bytes| parse_response(&bytes));
+    recorded.unwrap_or_else(|| {
+        let mut resp = http::Response::new(Body::from(format!(
+            "No recorded response for request {} in {}",
+            hash,
+            dir.display()
+        )));
+        *resp.status_mut() = StatusCode::NOT_FOUND;
+        Response::from(resp)
+    })
+}
+
+/// Method, URL and body of a `.request` file.
+fn parse_request(bytes: &[u8]) -> Option<(&str, &str, &[u8])> {
+    let (head, body) = split_head(bytes)?;
+    let mut request_line = head.lines().next()?.split(' ');
+    Some((request_line.next()?, request_line.next()?, body))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_response(bytes: &[u8]) -> Option<Response> {
+    let (head, body) = split_head(bytes)?;
+    let mut lines = head.lines();
+    let status = lines.next()?.split(' ').nth(1)?.parse::<StatusCode>().ok()?;
+    let mut resp = http::Response::new(Body::from(body.to_vec()));
+    *resp.status_mut() = status;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(": ")
+            && let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value))
+        {
+            resp.headers_mut().append(name, value);
+        }
+    }
+    Some(Response::from(resp))
+}
+
+/// Split a capture file at the blank line after the headers.
+fn split_head(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let end = bytes.windows(2).position(|w| w == b"\n\n")?;
+    let head = std::str::from_utf8(&bytes[..end]).ok()?;
+    Some((head, &bytes[end + 2..]))
+}
+
+fn push_headers(out: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            REDACTED
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+}
+
+fn redact_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    if url.query().is_none() {
+        return url;
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| {
+            let v = if SECRET_QUERY_PARAMS.contains(&k.as_ref()) {
+                REDACTED.to_string()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url
+}
+
+/// Capture is best effort: a failed write is logged, never surfaced to the request.
+fn append(path: &Path, bytes: &[u8]) {
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(bytes));
+    if let Err(e) = result {
+        tracing::warn!("Debug capture {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_secret_query_params() {
+        let url = Url::parse("https://example.com/v1/models/x:streamGenerateContent?alt=sse&key=secret").unwrap();
+        let redacted = redact_url(&url);
+        assert!(!redacted.as_str().contains("secret"));
+        assert!(redacted.as_str().contains("alt=sse"));
+    }
+
+    #[test]
+    fn redacts_secret_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer sk-123".parse().unwrap());
+        headers.insert("x-api-key", "sk-456".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let mut out = String::new();
+        push_headers(&mut out, &headers);
+        assert!(!out.contains("sk-"));
+        assert!(out.contains("content-type: application/json"));
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_response_by_request_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a-1.request"),
+            "POST https://api.example.com/v1/chat/completions?key=%5BREDACTED%5D HTTP/1.1\n\
+             authorization: [REDACTED]\n\n{\"stream\":true}",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("a-1.response"),
+            "HTTP/1.1 200 OK\ncontent-type: text/event-stream\n\ndata: {\"x\":1}\n\ndata: [DONE]\n\n",
+        )
+        .unwrap();
+        let replay = Capture::replay(dir.path());
+        let client = reqwest::Client::new();
+
+        let req = client
+            .post("https://api.example.com/v1/chat/completions?key=live-key")
+            .bearer_auth("live-token")
+            .body("{\"stream\":true}");
+        let resp = send(req, Some(&replay)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        assert_eq!(resp.text().await.unwrap(), "data: {\"x\":1}\n\ndata: [DONE]\n\n");
+
+        let req = client
+            .post("https://api.example.com/v1/chat/completions")
+            .body("{\"stream\":false}");
+        let resp = send(req, Some(&replay)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,264 @@
+//! Cloudflare AI Gateway (gateway.ai.cloudflare.com).
+//!
+//! Requests go to `{base_url}/{account_id}/{gateway_id}/...` and on to the upstream provider
+//! named by the model ID's first segment: `openai/gpt-4o` is sent to the gateway's `openai`
+//! route, `anthropic/claude-sonnet-4-20250514` to its Anthropic Messages route, and providers
+//! without a route here to the unified OpenAI-compatible `compat` endpoint, which takes the
+//! whole `<provider>/<model>` name.
+//!
+//! The gateway and its `cf-aig-*` options live in each account's API key, a JSON object:
+//!
+//! ```json
+//! {
+//!   "account_id": "0123abcd", "gateway_id": "my-gateway",
+//!   "api_key": "sk-upstream...", "gateway_token": "cf-token...",
+//!   "cache_ttl": 3600, "skip_cache": false, "collect_log": true,
+//!   "metadata": { "team": "infra" }
+//! }
+//! ```
+
+use super::anthropic::AnthropicProvider;
+use super::openai::OpenAiProvider;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One account's gateway, parsed from its API key.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GatewayCredentials {
+    pub account_id: String,
+    pub gateway_id: String,
+    /// The upstream provider's key. Without one the gateway token is sent in its place, for
+    /// keys stored in the gateway and unified billing.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// `cf-aig-authorization`, for authenticated gateways.
+    #[serde(default)]
+    pub gateway_token: Option<String>,
+    /// `cf-aig-cache-ttl`: cache responses for this many seconds.
+    #[serde(default)]
+    pub cache_ttl: Option<u64>,
+    /// `cf-aig-skip-cache`: never answer from the cache.
+    #[serde(default)]
+    pub skip_cache: bool,
+    /// `cf-aig-collect-log`: `false` keeps requests out of the gateway's logs.
+    #[serde(default)]
+    pub collect_log: Option<bool>,
+    /// `cf-aig-metadata`: custom fields logged with each request.
+    #[serde(default)]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl GatewayCredentials {
+    pub fn parse(api_key: &str) -> Result<Self, ProviderError> {
+        serde_json::from_str(api_key).map_err(|e| {
+            ProviderError::AuthRequired(format!(
+                "Cloudflare AI Gateway credentials must be JSON with account_id and gateway_id: {}",
+                e
+            ))
+        })
+    }
+
+    /// `cf-aig-*` request headers for the options set.
+    pub fn headers(&self) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        if let Some(token) = &self.gateway_token {
+            headers.insert("cf-aig-authorization".into(), format!("Bearer {}", token));
+        }
+        if let Some(ttl) = self.cache_ttl {
+            headers.insert("cf-aig-cache-ttl".into(), ttl.to_string());
+        }
+        if self.skip_cache {
+            headers.insert("cf-aig-skip-cache".into(), "true".into());
+        }
+        if let Some(collect) = self.collect_log {
+            headers.insert("cf-aig-collect-log".into(), collect.to_string());
+        }
+        if let Some(metadata) = &self.metadata {
+            headers.insert("cf-aig-metadata".into(), serde_json::Value::Object(metadata.clone()).to_string());
+        }
+        headers
+    }
+}
+
+/// Where the gateway at `gateway` sends `model_id`: the upstream base URL, its wire format and
+/// the model name the upstream expects.
+fn route(gateway: &str, model_id: &str) -> (String, Api, String) {
+    let (path, api) = match model_id.split_once('/').map(|(upstream, _)| upstream) {
+        Some("openai") => ("openai", Api::OpenaiCompletions),
+        Some("anthropic") => ("anthropic/v1", Api::AnthropicMessages),
+        Some("workers-ai") => ("workers-ai/v1", Api::OpenaiCompletions),
+        Some("groq") => ("groq", Api::OpenaiCompletions),
+        Some("deepseek") => ("deepseek", Api::OpenaiCompletions),
+        Some("mistral") => ("mistral/v1", Api::OpenaiCompletions),
+        _ => return (format!("{}/compat", gateway), Api::OpenaiCompletions, model_id.to_string()),
+    };
+    let model = model_id.split_once('/').map_or(model_id, |(_, m)| m);
+    (format!("{}/{}", gateway, path), api, model.to_string())
+}
+
+/// The model and options for the upstream request: routed through the account's gateway with
+/// its `cf-aig-*` headers, authenticated with the upstream key.
+fn prepare(model: &ModelDef, options: &RequestOptions) -> Result<(ModelDef, RequestOptions), ProviderError> {
+    let api_key = options
+        .api_key
+        .as_deref()
+        .ok_or_else(|| ProviderError::AuthRequired("Cloudflare AI Gateway credentials required".into()))?;
+    let creds = GatewayCredentials::parse(api_key)?;
+    let gateway = format!("{}/{}/{}", model.base_url.trim_end_matches('/'), creds.account_id, creds.gateway_id);
+    let (base_url, api, id) = route(&gateway, &model.id);
+
+    let mut model = model.clone();
+    model.base_url = base_url;
+    model.api = api;
+    model.id = id;
+    let mut headers = model.headers.take().unwrap_or_default();
+    headers.extend(creds.headers());
+    model.headers = Some(headers);
+
+    let mut options = options.clone();
+    options.api_key = creds.api_key.or(creds.gateway_token);
+    Ok((model, options))
+}
+
+pub struct CloudflareGatewayProvider {
+    openai: Arc<OpenAiProvider>,
+    anthropic: Arc<AnthropicProvider>,
+}
+
+impl CloudflareGatewayProvider {
+    pub fn new() -> Self {
+        Self {
+            openai: Arc::new(OpenAiProvider::new()),
+            anthropic: Arc::new(AnthropicProvider::new()),
+        }
+    }
+
+    /// The implementation speaking the routed model's wire format.
+    fn upstream(&self, model: &ModelDef) -> &dyn Provider {
+        match model.api {
+            Api::AnthropicMessages => self.anthropic.as_ref(),
+            _ => self.openai.as_ref(),
+        }
+    }
+}
+
+impl Default for CloudflareGatewayProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for CloudflareGatewayProvider {
+    fn stream(&self, model: &ModelDef, context: &ChatContext, options: &RequestOptions) -> EventStream {
+        match prepare(model, options) {
+            Ok((model, options)) => self.upstream(&model).stream(&model, context, &options),
+            Err(e) => Box::pin(futures::stream::once(async { Err(e) })),
+        }
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        let (model, options) = prepare(model, options)?;
+        self.upstream(&model).chat(&model, context, &options).await
+    }
+
+    async fn count_tokens(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<Option<u64>, ProviderError> {
+        let (model, options) = prepare(model, options)?;
+        self.upstream(&model).count_tokens(&model, context, &options).await
+    }
+
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let (model, options) = prepare(model, options)?;
+        self.upstream(&model).embed(&model, request, &options).await
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        self.openai.warm(base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("cloudflare-ai-gateway"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATEWAY: &str = "https://gateway.ai.cloudflare.com/v1/acct/gw";
+
+    #[test]
+    fn models_route_to_their_upstream() {
+        let routes = [
+            ("openai/gpt-4o", "openai", Api::OpenaiCompletions, "gpt-4o"),
+            ("anthropic/claude-sonnet-4-20250514", "anthropic/v1", Api::AnthropicMessages, "claude-sonnet-4-20250514"),
+            ("workers-ai/@cf/meta/llama-3.1-8b-instruct", "workers-ai/v1", Api::OpenaiCompletions, "@cf/meta/llama-3.1-8b-instruct"),
+            ("google-ai-studio/gemini-2.5-flash", "compat", Api::OpenaiCompletions, "google-ai-studio/gemini-2.5-flash"),
+        ];
+        for (model_id, path, api, upstream_model) in routes {
+            assert_eq!(route(GATEWAY, model_id), (format!("{}/{}", GATEWAY, path), api, upstream_model.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_carry_the_account_gateway_options() {
+        use futures::StreamExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket.write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_lowercase()
+        });
+
+        let mut model = crate::models::default_model_def_for_provider("cloudflare-ai-gateway", "openai/gpt-4o").unwrap();
+        model.base_url = format!("http://{}/v1", addr);
+        let options = RequestOptions {
+            api_key: Some(r#"{"account_id":"acct","gateway_id":"gw","api_key":"sk-up","gateway_token":"cf","cache_ttl":60,"collect_log":false}"#.into()),
+            ..Default::default()
+        };
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage { content: vec![ContentBlock::Text(TextContent { text: "hi".into() })] })],
+            tools: Vec::new(),
+        };
+        let mut stream = CloudflareGatewayProvider::new().stream(&model, &context, &options);
+        while stream.next().await.is_some() {}
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("post /v1/acct/gw/openai/chat/completions "), "{}", request);
+        for header in ["authorization: bearer sk-up", "cf-aig-authorization: bearer cf", "cf-aig-cache-ttl: 60", "cf-aig-collect-log: false"] {
+            assert!(request.contains(header), "{} missing from {}", header, request);
+        }
+        assert!(request.contains(r#""model":"gpt-4o""#));
+    }
+
+    #[test]
+    fn malformed_credentials_say_what_is_expected() {
+        let err = GatewayCredentials::parse("sk-plain-key").unwrap_err();
+        assert!(err.to_string().contains("account_id and gateway_id"));
+    }
+}
@@ -0,0 +1,104 @@
+//! Cohere provider.
+//!
+//! Chat and embeddings go through Cohere's OpenAI compatibility API (the provider base URL,
+//! `/compatibility/v1`); rerank only exists on the native v2 API, next to it at `/v2/rerank`.
+
+use super::openai::OpenAiProvider;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+
+const COMPATIBILITY_PATH: &str = "/compatibility/v1";
+
+pub struct CohereProvider {
+    inner: OpenAiProvider,
+}
+
+impl CohereProvider {
+    pub fn new() -> Self {
+        Self {
+            inner: OpenAiProvider::new(),
+        }
+    }
+}
+
+impl Default for CohereProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Native v2 API base for a compatibility base URL (other base URLs are used as-is).
+fn native_base_url(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    match base.strip_suffix(COMPATIBILITY_PATH) {
+        Some(root) => format!("{}/v2", root),
+        None => base.to_string(),
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for CohereProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> EventStream {
+        self.inner.stream(model, context, options)
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        self.inner.chat(model, context, options).await
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        self.inner.warm(base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("cohere"))
+    }
+
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        self.inner.embed(model, request, options).await
+    }
+
+    async fn rerank(
+        &self,
+        model: &ModelDef,
+        request: &RerankRequest,
+        options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        let native = ModelDef {
+            base_url: native_base_url(&model.base_url),
+            ..model.clone()
+        };
+        self.inner.rerank(&native, request, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rerank_uses_native_api() {
+        assert_eq!(
+            native_base_url("https://api.cohere.com/compatibility/v1/"),
+            "https://api.cohere.com/v2"
+        );
+        assert_eq!(native_base_url("https://proxy.local/v2"), "https://proxy.local/v2");
+    }
+}
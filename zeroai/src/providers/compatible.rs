@@ -1,19 +1,27 @@
 //! OpenAI-compatible custom provider: configurable base URL, auth, and model listing.
 //! Reference: zeroclaw/src/providers/compatible.rs
 
-use super::sanitize;
-use super::{Provider, ProviderError};
+use super::api_error;
+use super::capture;
+use super::completions::CompletionStyle;
+use super::embeddings::EmbeddingStyle;
+use super::rerank;
+use super::tool_ids::ToolCallIds;
+use super::{EventStream, Provider, ProviderError, with_extra_fields};
+use crate::models::fetch::{CompatibleModelList, compatible_model_defs};
 use crate::types::*;
 use async_trait::async_trait;
-use futures::stream::{self, BoxStream, StreamExt};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 /// How the API key is sent to the provider.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
 pub enum AuthStyle {
     /// `Authorization: Bearer <key>`
+    #[default]
     Bearer,
     /// `x-api-key: <key>`
     XApiKey,
@@ -29,6 +37,10 @@ pub struct OpenAiCompatibleProvider {
     pub auth_style: AuthStyle,
     /// Custom URL for listing models (GET). If None, uses `{base_url}/models`.
     pub models_url: Option<String>,
+    /// Wire format of the embeddings endpoint.
+    pub embedding_style: EmbeddingStyle,
+    /// Whether the server also has the legacy `/completions` endpoint.
+    pub completions: bool,
     client: Client,
 }
 
@@ -45,6 +57,8 @@ impl OpenAiCompatibleProvider {
             api_key: api_key.map(String::from),
             auth_style,
             models_url: None,
+            embedding_style: EmbeddingStyle::default(),
+            completions: false,
             client: Client::new(),
         }
     }
@@ -54,6 +68,16 @@ impl OpenAiCompatibleProvider {
         self
     }
 
+    pub fn with_embedding_style(mut self, style: EmbeddingStyle) -> Self {
+        self.embedding_style = style;
+        self
+    }
+
+    pub fn with_completions(mut self, completions: bool) -> Self {
+        self.completions = completions;
+        self
+    }
+
     fn chat_completions_url(&self) -> String {
         let base = self.base_url.trim_end_matches('/');
         if base.ends_with("/chat/completions") {
@@ -167,6 +191,7 @@ struct StreamChoice {
 #[derive(Deserialize)]
 struct DeltaContent {
     content: Option<String>,
+    refusal: Option<String>,
     tool_calls: Option<Vec<ToolCallDelta>>,
     #[allow(dead_code)]
     role: Option<String>,
@@ -209,13 +234,16 @@ struct ChatMessageResp {
     #[allow(dead_code)]
     role: String,
     content: Option<String>,
+    refusal: Option<String>,
     tool_calls: Option<Vec<ToolCallResp>>,
 }
 
 #[derive(Deserialize)]
 struct ToolCallResp {
+    #[serde(default)]
     id: String,
     #[allow(dead_code)]
+    #[serde(default)]
     r#type: String,
     function: FunctionResp,
 }
@@ -226,18 +254,6 @@ struct FunctionResp {
     arguments: String,
 }
 
-#[derive(Deserialize)]
-struct ModelsResponse {
-    data: Vec<ModelEntry>,
-}
-
-#[derive(Deserialize)]
-struct ModelEntry {
-    id: String,
-    #[allow(dead_code)]
-    owned_by: Option<String>,
-}
-
 fn convert_messages(context: &ChatContext) -> Vec<ChatMsg> {
     let mut msgs = Vec::new();
     if let Some(sys) = &context.system_prompt {
@@ -371,14 +387,15 @@ fn parse_sse_line(line: &str) -> Option<&str> {
     }
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl Provider for OpenAiCompatibleProvider {
     fn stream(
         &self,
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+    ) -> EventStream {
         let name = self.name.clone();
         let api_key = match options.api_key.as_deref().or(self.api_key.as_deref()) {
             Some(k) => k.to_string(),
@@ -407,6 +424,7 @@ impl Provider for OpenAiCompatibleProvider {
             stream: true,
             tools,
         };
+        let body = with_extra_fields(&body, options);
 
         let client = self.client.clone();
         let auth_style = self.auth_style.clone();
@@ -414,6 +432,8 @@ impl Provider for OpenAiCompatibleProvider {
         let provider_id = model.provider.clone();
         let extra_headers = options.extra_headers.clone();
         let model_headers = model.headers.clone();
+        let capture = options.capture.clone();
+        let response_headers = options.response_headers.clone();
 
         let s = async_stream::stream! {
             let mut req = client.post(&url).header("Content-Type", "application/json");
@@ -436,23 +456,23 @@ impl Provider for OpenAiCompatibleProvider {
                 }
             }
 
-            let resp = match req.json(&body).send().await {
+            let resp = match capture::send(req.json(&body), capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => { yield Err(ProviderError::Network(e)); return; }
             };
-            let status = resp.status();
-            if !status.is_success() {
-                let body_text = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http {
-                    status: status.as_u16(),
-                    body: sanitize::sanitize_api_error(&body_text),
-                });
+            if let Some(h) = &response_headers {
+                h.set(resp.headers());
+            }
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
                 return;
             }
             yield Ok(StreamEvent::Start);
 
             let mut text_buf = String::new();
+            let mut refusal_buf = String::new();
             let mut tool_calls: Vec<(String, String, String)> = Vec::new();
+            let mut tool_call_ids = ToolCallIds::new();
             let mut usage = Usage::default();
             let mut stop_reason = StopReason::Stop;
             let mut line_buf = String::new();
@@ -486,31 +506,34 @@ impl Provider for OpenAiCompatibleProvider {
                     if let Some(choices) = chunk.choices {
                         for choice in choices {
                             if let Some(reason) = &choice.finish_reason {
-                                stop_reason = match reason.as_str() {
-                                    "stop" => StopReason::Stop,
-                                    "length" => StopReason::Length,
-                                    "tool_calls" => StopReason::ToolUse,
-                                    _ => StopReason::Stop,
-                                };
+                                stop_reason = StopReason::from_openai(reason);
                             }
                             if let Some(delta) = &choice.delta {
                                 if let Some(content) = &delta.content {
                                     text_buf.push_str(content);
                                     yield Ok(StreamEvent::TextDelta(content.clone()));
                                 }
+                                if let Some(refusal) = &delta.refusal {
+                                    refusal_buf.push_str(refusal);
+                                    yield Ok(StreamEvent::RefusalDelta(refusal.clone()));
+                                }
                                 if let Some(tc_deltas) = &delta.tool_calls {
                                     for tc_delta in tc_deltas {
                                         let idx = tc_delta.index.unwrap_or(tool_calls.len());
                                         while tool_calls.len() <= idx {
                                             tool_calls.push((String::new(), String::new(), String::new()));
                                         }
-                                        if let Some(id) = &tc_delta.id {
+                                        // Later chunks may repeat the ID or send it empty; keep the first one.
+                                        if let Some(id) = tc_delta.id.as_ref().filter(|id| !id.is_empty())
+                                            && tool_calls[idx].0.is_empty()
+                                        {
                                             tool_calls[idx].0 = id.clone();
                                         }
                                         if let Some(func) = &tc_delta.function {
                                             if let Some(name) = &func.name {
                                                 if tool_calls[idx].1.is_empty() {
                                                     tool_calls[idx].1 = name.clone();
+                                                    tool_calls[idx].0 = tool_call_ids.id(Some(&tool_calls[idx].0));
                                                     yield Ok(StreamEvent::ToolCallStart {
                                                         index: idx,
                                                         id: tool_calls[idx].0.clone(),
@@ -551,6 +574,9 @@ impl Provider for OpenAiCompatibleProvider {
             if !text_buf.is_empty() {
                 content.push(ContentBlock::Text(TextContent { text: text_buf }));
             }
+            if !refusal_buf.is_empty() {
+                content.push(ContentBlock::Refusal(RefusalContent { text: refusal_buf }));
+            }
             for (id, name, args_str) in tool_calls {
                 let arguments: serde_json::Value =
                     serde_json::from_str(&args_str).unwrap_or(json!({}));
@@ -568,6 +594,8 @@ impl Provider for OpenAiCompatibleProvider {
                     provider: provider_id,
                     usage: Some(usage),
                     stop_reason,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
                 },
             });
         };
@@ -603,6 +631,7 @@ impl Provider for OpenAiCompatibleProvider {
             stream: false,
             tools,
         };
+        let body = with_extra_fields(&body, options);
 
         let mut req = self.client.post(&url).header("Content-Type", "application/json");
         req = self.apply_auth(req, api_key);
@@ -617,14 +646,12 @@ impl Provider for OpenAiCompatibleProvider {
             }
         }
 
-        let resp = req.json(&body).send().await?;
-        let status = resp.status();
-        if !status.is_success() {
-            let body_text = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status: status.as_u16(),
-                body: sanitize::sanitize_api_error(&body_text),
-            });
+        let resp = capture::send(req.json(&body), options.capture.as_ref()).await?;
+        if let Some(h) = &options.response_headers {
+            h.set(resp.headers());
+        }
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
         }
 
         let chat_resp: ChatResponse = resp.json().await?;
@@ -640,73 +667,143 @@ impl Provider for OpenAiCompatibleProvider {
             if let Some(text) = &choice.message.content {
                 content.push(ContentBlock::Text(TextContent { text: text.clone() }));
             }
+            if let Some(text) = &choice.message.refusal {
+                content.push(ContentBlock::Refusal(RefusalContent { text: text.clone() }));
+            }
             if let Some(tc_resps) = &choice.message.tool_calls {
+                let mut tool_call_ids = ToolCallIds::new();
                 for tc in tc_resps {
                     let arguments: serde_json::Value =
                         serde_json::from_str(&tc.function.arguments).unwrap_or(json!({}));
                     content.push(ContentBlock::ToolCall(ToolCall {
-                        id: tc.id.clone(),
+                        id: tool_call_ids.id(Some(&tc.id)),
                         name: tc.function.name.clone(),
                         arguments,
                     }));
                 }
             }
-            let stop_reason = match choice.finish_reason.as_deref() {
-                Some("stop") => StopReason::Stop,
-                Some("length") => StopReason::Length,
-                Some("tool_calls") => StopReason::ToolUse,
-                _ => StopReason::Stop,
-            };
+            let stop_reason = choice.finish_reason.as_deref().map_or(StopReason::Stop, StopReason::from_openai);
             Ok(AssistantMessage {
                 content,
                 model: model.id.clone(),
                 provider: model.provider.clone(),
                 usage: Some(usage),
                 stop_reason,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
             })
         } else {
             Err(ProviderError::Other("Empty response".into()))
         }
     }
 
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let api_key = options
+            .api_key
+            .as_deref()
+            .or(self.api_key.as_deref())
+            .ok_or_else(|| {
+                ProviderError::AuthRequired(format!("API key required for {}", self.name))
+            })?;
+
+        let base = self.base_url.trim_end_matches("/chat/completions");
+        let url = self.embedding_style.url(base);
+        let mut req = self.client.post(&url).header("Content-Type", "application/json");
+        req = self.apply_auth(req, api_key);
+        if let Some(extra) = &options.extra_headers {
+            for (k, v) in extra {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        if let Some(ref h) = model.headers {
+            for (k, v) in h {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        self.embedding_style.send(req, model, request).await
+    }
+
+    async fn rerank(
+        &self,
+        model: &ModelDef,
+        request: &RerankRequest,
+        options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        let api_key = options
+            .api_key
+            .as_deref()
+            .or(self.api_key.as_deref())
+            .ok_or_else(|| {
+                ProviderError::AuthRequired(format!("API key required for {}", self.name))
+            })?;
+
+        let url = rerank::url(self.base_url.trim_end_matches("/chat/completions"));
+        let mut req = self.client.post(&url).header("Content-Type", "application/json");
+        req = self.apply_auth(req, api_key);
+        if let Some(extra) = &options.extra_headers {
+            for (k, v) in extra {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        if let Some(ref h) = model.headers {
+            for (k, v) in h {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        rerank::send(req, model, request).await
+    }
+
+    fn stream_completion(
+        &self,
+        model: &ModelDef,
+        request: &CompletionRequest,
+        options: &RequestOptions,
+    ) -> Option<EventStream> {
+        if !self.completions {
+            return None;
+        }
+        let Some(api_key) = options.api_key.as_deref().or(self.api_key.as_deref()) else {
+            let err = ProviderError::AuthRequired(format!("API key required for {}", self.name));
+            return Some(Box::pin(stream::once(async { Err(err) })));
+        };
+
+        let url = CompletionStyle::OpenAi.url(self.base_url.trim_end_matches("/chat/completions"));
+        let mut req = self.client.post(&url).header("Content-Type", "application/json");
+        req = self.apply_auth(req, api_key);
+        if let Some(extra) = &options.extra_headers {
+            for (k, v) in extra {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        if let Some(ref h) = model.headers {
+            for (k, v) in h {
+                req = req.header(k.as_str(), v.as_str());
+            }
+        }
+        Some(CompletionStyle::OpenAi.stream(req, model, request, options))
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
     async fn list_models(&self, api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
         let url = self.models_list_url();
         let mut req = self.client.get(&url);
         req = self.apply_auth(req, api_key);
 
         let resp = req.send().await?;
-        let status = resp.status().as_u16();
         if !resp.status().is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status,
-                body: sanitize::sanitize_api_error(&body),
-            });
+            return Err(api_error::from_response(resp).await);
         }
 
-        let models_resp: ModelsResponse = resp.json().await?;
-        let base_url = self.base_url.clone();
-        let provider = self.name.clone();
-
-        let models = models_resp
-            .data
-            .into_iter()
-            .map(|entry| ModelDef {
-                id: entry.id.clone(),
-                name: entry.id.clone(),
-                api: Api::OpenaiCompletions,
-                provider: provider.clone(),
-                base_url: base_url.clone(),
-                reasoning: false,
-                input: vec![InputModality::Text],
-                cost: ModelCost::default(),
-                context_window: 128000,
-                max_tokens: 16384,
-                headers: None,
-            })
-            .collect();
-
-        Ok(models)
+        let list: CompatibleModelList = resp.json().await?;
+        Ok(compatible_model_defs(&self.name, &self.base_url, list))
     }
 }
 
@@ -817,4 +914,30 @@ mod tests {
         assert_eq!(p.base_url, "https://api.example.com");
         assert_eq!(p.api_key.as_deref(), Some("key"));
     }
+
+    #[test]
+    fn extra_fields_are_merged_into_the_body() {
+        let body = ChatRequest {
+            model: "openai/gpt-4o".into(),
+            messages: Vec::new(),
+            temperature: Some(0.5),
+            max_tokens: None,
+            stream: false,
+            tools: None,
+        };
+        let options = RequestOptions {
+            extra_body: Some(std::collections::HashMap::from([("top_k".to_string(), serde_json::json!(20))])),
+            provider_extra: serde_json::json!({"provider": {"order": ["Azure"], "allow_fallbacks": false}, "temperature": 0.1}),
+            ..Default::default()
+        };
+        let merged = with_extra_fields(&body, &options);
+        assert_eq!(merged["provider"]["order"][0], "Azure");
+        assert_eq!(merged["top_k"], 20);
+        // Fields of the request itself are never replaced.
+        assert_eq!(merged["temperature"], 0.5);
+        assert_eq!(merged["model"], "openai/gpt-4o");
+
+        let plain = with_extra_fields(&body, &RequestOptions::default());
+        assert!(plain.get("provider").is_none());
+    }
 }
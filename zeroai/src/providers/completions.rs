@@ -0,0 +1,189 @@
+//! Native text completions with `suffix` for fill-in-the-middle: the legacy OpenAI
+//! `/completions` (vLLM, Fireworks and other OpenAI-compatible servers, DeepSeek's beta API)
+//! and Mistral's `/fim/completions` for Codestral.
+
+use super::api_error;
+use super::capture;
+use super::{EventStream, ProviderError, with_extra_fields};
+use crate::types::*;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct CompletionBody<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u64>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<ChunkUsage>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    #[serde(default)]
+    text: Option<String>,
+    /// Mistral streams FIM completions as chat completion chunks.
+    #[serde(default)]
+    delta: Option<Delta>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+/// Request/response format of a provider's completions endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompletionStyle {
+    /// `POST {base}/completions`, `text` in each choice.
+    OpenAi,
+    /// `POST /beta/completions` next to DeepSeek's `/v1`; otherwise like `OpenAi`.
+    DeepSeek,
+    /// `POST {base}/fim/completions`, chat-style `delta.content` in each choice. Always
+    /// reports usage, and rejects `stream_options`.
+    Mistral,
+}
+
+impl CompletionStyle {
+    pub(crate) fn url(self, base_url: &str) -> String {
+        let base = base_url.trim_end_matches('/');
+        match self {
+            Self::OpenAi => format!("{}/completions", base),
+            Self::DeepSeek => format!("{}/beta/completions", base.trim_end_matches("/v1")),
+            Self::Mistral => format!("{}/fim/completions", base),
+        }
+    }
+
+    /// Stream a completion (`req` already carries auth and headers). The text arrives as
+    /// `TextDelta`s and ends up as the one text block of the `Done` message.
+    pub(crate) fn stream(
+        self,
+        req: reqwest::RequestBuilder,
+        model: &ModelDef,
+        request: &CompletionRequest,
+        options: &RequestOptions,
+    ) -> EventStream {
+        let body = CompletionBody {
+            model: &model.id,
+            prompt: &request.prompt,
+            suffix: request.suffix.as_deref(),
+            temperature: options.temperature,
+            max_tokens: options.max_tokens,
+            stream: true,
+            stream_options: (self != Self::Mistral).then_some(StreamOptions { include_usage: true }),
+        };
+        stream(req, model, with_extra_fields(&body, options), options)
+    }
+}
+
+fn stream(req: reqwest::RequestBuilder, model: &ModelDef, body: serde_json::Value, options: &RequestOptions) -> EventStream {
+    let (model_id, provider_id) = (model.id.clone(), model.provider.clone());
+    let capture = options.capture.clone();
+    let response_headers = options.response_headers.clone();
+
+    Box::pin(async_stream::stream! {
+        let resp = match capture::send(req.json(&body), capture.as_ref()).await {
+            Ok(r) => r,
+            Err(e) => { yield Err(ProviderError::Network(e)); return; }
+        };
+        if let Some(h) = &response_headers {
+            h.set(resp.headers());
+        }
+        if !resp.status().is_success() {
+            yield Err(api_error::from_response(resp).await);
+            return;
+        }
+        yield Ok(StreamEvent::Start);
+
+        let mut text = String::new();
+        let mut usage = Usage::default();
+        let mut stop_reason = StopReason::Stop;
+        let mut line_buf = String::new();
+        let mut bytes = resp.bytes_stream();
+        while let Some(chunk) = bytes.next().await {
+            let chunk = match chunk {
+                Ok(b) => b,
+                Err(e) => { yield Err(ProviderError::Network(e)); return; }
+            };
+            line_buf.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(newline) = line_buf.find('\n') {
+                let line: String = line_buf.drain(..=newline).collect();
+                let Some(data) = line.trim().strip_prefix("data: ").filter(|d| *d != "[DONE]") else {
+                    continue;
+                };
+                let Ok(chunk) = serde_json::from_str::<Chunk>(data) else {
+                    continue;
+                };
+                if let Some(u) = chunk.usage {
+                    usage.input_tokens = u.prompt_tokens;
+                    usage.output_tokens = u.completion_tokens;
+                }
+                for choice in chunk.choices {
+                    if let Some(reason) = &choice.finish_reason {
+                        stop_reason = StopReason::from_openai(reason);
+                    }
+                    let delta = choice.text.or(choice.delta.and_then(|d| d.content));
+                    if let Some(delta) = delta.filter(|t| !t.is_empty()) {
+                        text.push_str(&delta);
+                        yield Ok(StreamEvent::TextDelta(delta));
+                    }
+                }
+            }
+        }
+
+        usage.total_tokens = usage.input_tokens + usage.output_tokens;
+        yield Ok(StreamEvent::Done {
+            message: AssistantMessage {
+                content: vec![ContentBlock::Text(TextContent { text })],
+                model: model_id,
+                provider: provider_id,
+                usage: Some(usage),
+                stop_reason,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
+            },
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompletionStyle;
+
+    #[test]
+    fn endpoints_per_style() {
+        assert_eq!(CompletionStyle::OpenAi.url("http://127.0.0.1:8000/v1/"), "http://127.0.0.1:8000/v1/completions");
+        assert_eq!(CompletionStyle::DeepSeek.url("https://api.deepseek.com/v1"), "https://api.deepseek.com/beta/completions");
+        assert_eq!(CompletionStyle::Mistral.url("https://codestral.mistral.ai/v1"), "https://codestral.mistral.ai/v1/fim/completions");
+    }
+}
@@ -0,0 +1,205 @@
+//! Embedding wire formats for HTTP embedding APIs.
+//!
+//! OpenAI and most compatible servers (vLLM, Ollama, Together, Jina...) take `POST /embeddings`
+//! with `{model, input}`. Voyage uses the same shape with its own option names, and Cohere's
+//! v2 API takes `POST /embed` with `texts` and returns typed embedding arrays.
+
+use super::api_error;
+use super::ProviderError;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Request/response format of a provider's embeddings endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbeddingStyle {
+    /// `POST {base}/embeddings` with `input`, `dimensions`.
+    #[default]
+    OpenAi,
+    /// `POST {base}/embeddings` with `input`, `input_type`, `output_dimension`.
+    Voyage,
+    /// `POST {base}/embed` with `texts`, `input_type`, `embedding_types`.
+    Cohere,
+}
+
+impl EmbeddingStyle {
+    pub(crate) fn url(self, base_url: &str) -> String {
+        let base = base_url.trim_end_matches('/');
+        match self {
+            Self::OpenAi | Self::Voyage => format!("{}/embeddings", base),
+            Self::Cohere => format!("{}/embed", base),
+        }
+    }
+
+    pub(crate) fn request_body(self, model_id: &str, req: &EmbeddingRequest) -> serde_json::Value {
+        let mut body = match self {
+            Self::OpenAi => json!({"model": model_id, "input": req.input, "encoding_format": "float"}),
+            Self::Voyage => json!({"model": model_id, "input": req.input}),
+            Self::Cohere => json!({
+                "model": model_id,
+                "texts": req.input,
+                "embedding_types": ["float"],
+                // Required by Cohere's v3+ models; stored texts are the common case.
+                "input_type": match req.input_type {
+                    Some(EmbeddingInputType::Query) => "search_query",
+                    _ => "search_document",
+                },
+            }),
+        };
+        if let Some(dim) = req.dimensions {
+            let key = if self == Self::OpenAi { "dimensions" } else { "output_dimension" };
+            body[key] = json!(dim);
+        }
+        if self == Self::Voyage
+            && let Some(t) = req.input_type
+        {
+            body["input_type"] = json!(match t {
+                EmbeddingInputType::Query => "query",
+                EmbeddingInputType::Document => "document",
+            });
+        }
+        body
+    }
+
+    pub(crate) fn parse_response(self, body: serde_json::Value) -> Result<(Vec<Vec<f32>>, Option<Usage>), ProviderError> {
+        match self {
+            Self::OpenAi | Self::Voyage => {
+                let mut resp: OpenAiEmbeddingResponse = serde_json::from_value(body)?;
+                resp.data.sort_by_key(|d| d.index);
+                let usage = resp.usage.map(|u| Usage {
+                    input_tokens: u.prompt_tokens.unwrap_or(u.total_tokens),
+                    total_tokens: u.total_tokens,
+                    ..Usage::default()
+                });
+                Ok((resp.data.into_iter().map(|d| d.embedding).collect(), usage))
+            }
+            Self::Cohere => {
+                let resp: CohereEmbedResponse = serde_json::from_value(body)?;
+                let usage = resp.meta.and_then(|m| m.billed_units).map(|b| Usage {
+                    input_tokens: b.input_tokens,
+                    total_tokens: b.input_tokens,
+                    ..Usage::default()
+                });
+                Ok((resp.embeddings.float, usage))
+            }
+        }
+    }
+
+    /// Send an embeddings request (already carrying auth and headers) and parse the reply.
+    pub(crate) async fn send(
+        self,
+        req: reqwest::RequestBuilder,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let resp = req.json(&self.request_body(&model.id, request)).send().await?;
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
+        }
+        let (embeddings, usage) = self.parse_response(resp.json().await?)?;
+        Ok(EmbeddingResponse {
+            model: model.id.clone(),
+            provider: model.provider.clone(),
+            embeddings,
+            usage,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbedding>,
+    #[serde(default)]
+    usage: Option<OpenAiEmbeddingUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingUsage {
+    #[serde(default)]
+    prompt_tokens: Option<u64>,
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct CohereEmbedResponse {
+    embeddings: CohereEmbeddings,
+    #[serde(default)]
+    meta: Option<CohereMeta>,
+}
+
+#[derive(Deserialize)]
+struct CohereEmbeddings {
+    float: Vec<Vec<f32>>,
+}
+
+#[derive(Deserialize)]
+struct CohereMeta {
+    #[serde(default)]
+    billed_units: Option<CohereBilledUnits>,
+}
+
+#[derive(Deserialize)]
+struct CohereBilledUnits {
+    #[serde(default)]
+    input_tokens: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> EmbeddingRequest {
+        EmbeddingRequest {
+            dimensions: Some(256),
+            input_type: Some(EmbeddingInputType::Query),
+            ..EmbeddingRequest::new(["hello", "world"])
+        }
+    }
+
+    #[test]
+    fn request_bodies_per_style() {
+        let openai = EmbeddingStyle::OpenAi.request_body("text-embedding-3-small", &request());
+        assert_eq!(openai["input"], json!(["hello", "world"]));
+        assert_eq!(openai["dimensions"], 256);
+        assert!(openai.get("input_type").is_none());
+
+        let voyage = EmbeddingStyle::Voyage.request_body("voyage-3", &request());
+        assert_eq!(voyage["input_type"], "query");
+        assert_eq!(voyage["output_dimension"], 256);
+
+        let cohere = EmbeddingStyle::Cohere.request_body("embed-v4.0", &EmbeddingRequest::new(["a"]));
+        assert_eq!(cohere["texts"], json!(["a"]));
+        assert_eq!(cohere["input_type"], "search_document");
+        assert_eq!(EmbeddingStyle::Cohere.url("https://api.cohere.com/v2/"), "https://api.cohere.com/v2/embed");
+    }
+
+    #[test]
+    fn parses_openai_out_of_order_and_cohere() {
+        let (vectors, usage) = EmbeddingStyle::OpenAi
+            .parse_response(json!({
+                "data": [{"index": 1, "embedding": [0.5]}, {"index": 0, "embedding": [0.25]}],
+                "usage": {"prompt_tokens": 4, "total_tokens": 4}
+            }))
+            .unwrap();
+        assert_eq!(vectors, vec![vec![0.25], vec![0.5]]);
+        assert_eq!(usage.unwrap().input_tokens, 4);
+
+        let (vectors, usage) = EmbeddingStyle::Cohere
+            .parse_response(json!({
+                "embeddings": {"float": [[1.0, 2.0]]},
+                "meta": {"billed_units": {"input_tokens": 2}}
+            }))
+            .unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 2.0]]);
+        assert_eq!(usage.unwrap().total_tokens, 2);
+    }
+}
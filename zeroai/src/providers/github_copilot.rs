@@ -0,0 +1,551 @@
+//! GitHub Copilot provider: Copilot token exchange, editor headers and per-request
+//! vision/initiator flags on top of the OpenAI chat-completions wire format.
+//!
+//! The API key may be either a Copilot bearer token (what the device-flow OAuth stores)
+//! or a plain GitHub token (e.g. `GITHUB_COPILOT_API_KEY=ghu_...`), which is exchanged
+//! for a short-lived Copilot token and cached, per GitHub token (so per account), until
+//! shortly before it expires.
+
+use super::api_error;
+use super::openai::OpenAiProvider;
+use super::{EventStream, Provider, ProviderError};
+use crate::auth::usage::RequestQuota;
+use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub const COPILOT_TOKEN_URL: &str = "https://api.github.com/copilot_internal/v2/token";
+pub const DEFAULT_API_BASE: &str = "https://api.githubcopilot.com";
+
+const EDITOR_VERSION: &str = "vscode/1.99.3";
+const EDITOR_PLUGIN_VERSION: &str = "copilot-chat/0.35.0";
+pub const USER_AGENT: &str = "GitHubCopilotChat/0.35.0";
+const INTEGRATION_ID: &str = "vscode-chat";
+
+/// Refresh the exchanged token this long before it actually expires.
+const TOKEN_EXPIRY_MARGIN_MS: i64 = 5 * 60 * 1000;
+
+/// Premium-request quota snapshot sent with chat responses; older backends use the
+/// `premium_models` name.
+const QUOTA_HEADERS: &[&str] = &["x-quota-snapshot-premium_interactions", "x-quota-snapshot-premium_models"];
+
+// ---------------------------------------------------------------------------
+// Token exchange
+// ---------------------------------------------------------------------------
+
+/// A Copilot bearer token obtained from a GitHub token.
+#[derive(Debug, Clone)]
+pub struct CopilotToken {
+    pub token: String,
+    /// Expiry in milliseconds since epoch.
+    pub expires_at_ms: i64,
+    /// API base URL for this account (differs for business/enterprise plans).
+    pub api_base: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResp {
+    token: String,
+    expires_at: i64,
+    #[serde(default)]
+    endpoints: Option<TokenEndpoints>,
+}
+
+#[derive(Deserialize)]
+struct TokenEndpoints {
+    api: Option<String>,
+}
+
+/// Exchange a GitHub OAuth/PAT token for a Copilot bearer token.
+pub async fn exchange_token(client: &Client, github_token: &str) -> Result<CopilotToken, ProviderError> {
+    let resp = client
+        .get(COPILOT_TOKEN_URL)
+        .header("Authorization", format!("token {}", github_token))
+        .header("Accept", "application/json")
+        .header("User-Agent", USER_AGENT)
+        .header("Editor-Version", EDITOR_VERSION)
+        .header("Editor-Plugin-Version", EDITOR_PLUGIN_VERSION)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        return Err(api_error::from_response(resp).await);
+    }
+
+    let t: TokenResp = resp.json().await?;
+    let api_base = t
+        .endpoints
+        .and_then(|e| e.api)
+        .map(|u| u.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| api_base_from_token(&t.token));
+    Ok(CopilotToken {
+        token: t.token,
+        expires_at_ms: t.expires_at * 1000,
+        api_base,
+    })
+}
+
+/// True for raw GitHub tokens that must be exchanged before calling the Copilot API.
+pub fn is_github_token(key: &str) -> bool {
+    ["ghu_", "gho_", "ghp_", "github_pat_"]
+        .iter()
+        .any(|p| key.starts_with(p))
+}
+
+/// Derive the API base from a Copilot token's `proxy-ep=` field
+/// (`proxy.individual.githubcopilot.com` → `https://api.individual.githubcopilot.com`).
+pub fn api_base_from_token(token: &str) -> String {
+    token
+        .split(';')
+        .find_map(|kv| kv.trim().strip_prefix("proxy-ep="))
+        .filter(|host| !host.is_empty())
+        .map(|host| {
+            let host = host.strip_prefix("proxy.").unwrap_or(host);
+            format!("https://api.{}", host)
+        })
+        .unwrap_or_else(|| DEFAULT_API_BASE.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Request flags
+// ---------------------------------------------------------------------------
+
+/// Editor identification headers required on every Copilot API call.
+fn editor_headers() -> HashMap<String, String> {
+    let mut h = HashMap::new();
+    h.insert("User-Agent".into(), USER_AGENT.into());
+    h.insert("Editor-Version".into(), EDITOR_VERSION.into());
+    h.insert("Editor-Plugin-Version".into(), EDITOR_PLUGIN_VERSION.into());
+    h.insert("Copilot-Integration-Id".into(), INTEGRATION_ID.into());
+    h
+}
+
+/// Copilot-specific headers for a chat request.
+fn copilot_headers(context: &ChatContext) -> HashMap<String, String> {
+    let mut h = editor_headers();
+    h.insert("Openai-Intent".into(), "conversation-edits".into());
+    h.insert("X-Initiator".into(), initiator(context).into());
+    if has_images(context) {
+        h.insert("Copilot-Vision-Request".into(), "true".into());
+    }
+    h
+}
+
+/// "user" when the last message is from the user, "agent" for tool-loop follow-ups.
+/// Copilot only bills premium requests for user-initiated turns.
+fn initiator(context: &ChatContext) -> &'static str {
+    match context.messages.last() {
+        Some(Message::User(_)) | None => "user",
+        _ => "agent",
+    }
+}
+
+fn has_images(context: &ChatContext) -> bool {
+    context.messages.iter().any(|m| {
+        let blocks = match m {
+            Message::User(u) => &u.content,
+            Message::ToolResult(t) => &t.content,
+            Message::Assistant(_) => return false,
+        };
+        blocks.iter().any(|b| matches!(b, ContentBlock::Image(_)))
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Quota
+// ---------------------------------------------------------------------------
+
+/// The premium-request quota reported in a chat response's headers, e.g.
+/// `ent=300&ov=0.0&ovPerm=false&rem=12.5&rst=2025-08-01T00:00:00Z` (`ent=-1` is unlimited,
+/// `rem` is the percentage left).
+pub fn quota_from_headers(headers: &reqwest::header::HeaderMap, now_ms: i64) -> Option<RequestQuota> {
+    let value = QUOTA_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())?;
+    let fields: HashMap<String, String> = url::form_urlencoded::parse(value.as_bytes()).into_owned().collect();
+    let entitlement: i64 = fields.get("ent")?.parse().ok()?;
+    Some(RequestQuota {
+        entitlement: u64::try_from(entitlement).ok(),
+        remaining_percent: fields.get("rem")?.parse().ok()?,
+        overage_permitted: fields.get("ovPerm").is_some_and(|v| v == "true"),
+        resets_at_ms: fields.get("rst").and_then(|v| parse_reset_date(v)),
+        updated_ms: now_ms,
+    })
+}
+
+/// `rst` is an RFC 3339 timestamp or a plain date (midnight UTC).
+fn parse_reset_date(value: &str) -> Option<i64> {
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(t.timestamp_millis());
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis())
+}
+
+// ---------------------------------------------------------------------------
+// Provider
+// ---------------------------------------------------------------------------
+
+pub struct GitHubCopilotProvider {
+    client: Client,
+    inner: Arc<OpenAiProvider>,
+    /// Exchanged tokens by GitHub token, one per account.
+    cache: Arc<Mutex<TokenCache>>,
+}
+
+impl GitHubCopilotProvider {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            inner: Arc::new(OpenAiProvider::new()),
+            cache: Arc::new(Mutex::new(TokenCache::default())),
+        }
+    }
+}
+
+impl Default for GitHubCopilotProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Copilot tokens exchanged from GitHub tokens, keyed by the GitHub token.
+#[derive(Default)]
+struct TokenCache(HashMap<String, CopilotToken>);
+
+impl TokenCache {
+    /// The token for `github_token`, unless it expires within the margin.
+    fn get(&self, github_token: &str, now_ms: i64) -> Option<&CopilotToken> {
+        self.0.get(github_token).filter(|t| t.expires_at_ms - TOKEN_EXPIRY_MARGIN_MS > now_ms)
+    }
+
+    /// Store a freshly exchanged token, dropping the ones that have expired.
+    fn insert(&mut self, github_token: &str, token: CopilotToken, now_ms: i64) {
+        self.0.retain(|_, t| t.expires_at_ms > now_ms);
+        self.0.insert(github_token.to_string(), token);
+    }
+}
+
+/// Resolve the bearer token and API base for `api_key`, exchanging GitHub tokens as needed.
+async fn resolve_token(
+    client: &Client,
+    cache: &Mutex<TokenCache>,
+    api_key: &str,
+) -> Result<(String, String), ProviderError> {
+    if !is_github_token(api_key) {
+        return Ok((api_key.to_string(), api_base_from_token(api_key)));
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    if let Some(t) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(api_key, now) {
+        return Ok((t.token.clone(), t.api_base.clone()));
+    }
+
+    let t = exchange_token(client, api_key).await?;
+    let out = (t.token.clone(), t.api_base.clone());
+    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(api_key, t, now);
+    Ok(out)
+}
+
+/// Prepare model and options for the OpenAI-compatible call.
+fn prepare(
+    model: &ModelDef,
+    context: &ChatContext,
+    options: &RequestOptions,
+    token: String,
+    api_base: String,
+) -> (ModelDef, RequestOptions) {
+    let mut model = model.clone();
+    // Keep an explicitly configured base URL; otherwise use the account's endpoint.
+    if model.base_url.is_empty() || model.base_url.trim_end_matches('/') == DEFAULT_API_BASE {
+        model.base_url = api_base;
+    }
+    let mut headers = copilot_headers(context);
+    if let Some(h) = &model.headers {
+        headers.extend(h.clone());
+    }
+    model.headers = Some(headers);
+
+    let mut options = options.clone();
+    options.api_key = Some(token);
+    (model, options)
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for GitHubCopilotProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> EventStream {
+        let client = self.client.clone();
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let model = model.clone();
+        let context = context.clone();
+        let options = options.clone();
+
+        Box::pin(async_stream::stream! {
+            let Some(api_key) = options.api_key.clone() else {
+                yield Err(ProviderError::AuthRequired("GitHub Copilot token required".into()));
+                return;
+            };
+            let (token, api_base) = match resolve_token(&client, &cache, &api_key).await {
+                Ok(v) => v,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let (model, options) = prepare(&model, &context, &options, token, api_base);
+            let mut s = inner.stream(&model, &context, &options);
+            while let Some(item) = s.next().await {
+                yield item;
+            }
+        })
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        let api_key = options
+            .api_key
+            .as_deref()
+            .ok_or_else(|| ProviderError::AuthRequired("GitHub Copilot token required".into()))?;
+        let (token, api_base) = resolve_token(&self.client, &self.cache, api_key).await?;
+        let (model, options) = prepare(model, context, options, token, api_base);
+        self.inner.chat(&model, context, &options).await
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
+    async fn list_models(&self, api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        let (token, api_base) = resolve_token(&self.client, &self.cache, api_key).await?;
+        let mut req = self.client.get(format!("{}/models", api_base)).bearer_auth(&token);
+        for (k, v) in editor_headers() {
+            req = req.header(k, v);
+        }
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
+        }
+        let list: ModelsResp = resp.json().await?;
+        Ok(list
+            .data
+            .into_iter()
+            .filter_map(|m| m.into_model_def(&api_base))
+            .collect())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Model capabilities
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+struct ModelsResp {
+    data: Vec<CopilotModel>,
+}
+
+#[derive(Deserialize)]
+struct CopilotModel {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    model_picker_enabled: Option<bool>,
+    #[serde(default)]
+    capabilities: Option<CopilotCapabilities>,
+}
+
+#[derive(Deserialize, Default)]
+struct CopilotCapabilities {
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    limits: CopilotLimits,
+    #[serde(default)]
+    supports: CopilotSupports,
+}
+
+#[derive(Deserialize, Default)]
+struct CopilotLimits {
+    max_context_window_tokens: Option<u64>,
+    max_output_tokens: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+struct CopilotSupports {
+    #[serde(default)]
+    vision: bool,
+    #[serde(default)]
+    tool_calls: bool,
+    #[serde(default)]
+    structured_outputs: bool,
+    #[serde(default)]
+    reasoning_effort: Option<serde_json::Value>,
+    #[serde(default)]
+    thinking: Option<serde_json::Value>,
+}
+
+impl CopilotModel {
+    /// Convert a chat model entry; embeddings and hidden models are skipped.
+    fn into_model_def(self, api_base: &str) -> Option<ModelDef> {
+        let caps = self.capabilities.unwrap_or_default();
+        if caps.kind.as_deref().is_some_and(|k| k != "chat") || self.model_picker_enabled == Some(false) {
+            return None;
+        }
+        let mut input = vec![InputModality::Text];
+        if caps.supports.vision {
+            input.push(InputModality::Image);
+        }
+        Some(ModelDef {
+            name: self.name.unwrap_or_else(|| self.id.clone()),
+            id: self.id,
+            api: Api::OpenaiCompletions,
+            provider: "github-copilot".into(),
+            base_url: api_base.to_string(),
+            reasoning: caps.supports.reasoning_effort.is_some() || caps.supports.thinking.is_some(),
+            input,
+            context_window: caps.limits.max_context_window_tokens.unwrap_or(128000),
+            max_tokens: caps.limits.max_output_tokens.unwrap_or(16384),
+            capabilities: ModelCapabilities {
+                tools: caps.supports.tool_calls,
+                json_mode: caps.supports.structured_outputs,
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> Message {
+        Message::User(UserMessage {
+            content: vec![ContentBlock::Text(TextContent { text: text.into() })],
+        })
+    }
+
+    #[test]
+    fn token_cache_keeps_one_token_per_account() {
+        let token = |name: &str, expires_at_ms: i64| CopilotToken {
+            token: name.into(),
+            expires_at_ms,
+            api_base: DEFAULT_API_BASE.into(),
+        };
+        let now = 1_000_000_000;
+        let hour = 60 * 60 * 1000;
+        let mut cache = TokenCache::default();
+        cache.insert("ghu_old", token("old", now - 1), now);
+        cache.insert("ghu_a", token("a", now + hour), now);
+        cache.insert("ghu_b", token("b", now + hour), now);
+        assert_eq!(cache.get("ghu_a", now).unwrap().token, "a");
+        assert_eq!(cache.get("ghu_b", now).unwrap().token, "b");
+        // Inside the expiry margin the token is exchanged again; expired ones are dropped.
+        assert!(cache.get("ghu_a", now + hour - 60_000).is_none());
+        assert!(!cache.0.contains_key("ghu_old"));
+    }
+
+    #[test]
+    fn api_base_from_proxy_endpoint() {
+        let t = "tid=abc;exp=123;proxy-ep=proxy.business.githubcopilot.com;sku=x";
+        assert_eq!(api_base_from_token(t), "https://api.business.githubcopilot.com");
+        assert_eq!(api_base_from_token("opaque"), DEFAULT_API_BASE);
+    }
+
+    #[test]
+    fn parses_quota_snapshot_header() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            QUOTA_HEADERS[0],
+            "ent=300&ov=0.0&ovPerm=false&rem=12.5&rst=2025-08-01T00%3A00%3A00Z".parse().unwrap(),
+        );
+        let quota = quota_from_headers(&headers, 1).unwrap();
+        assert_eq!(quota.entitlement, Some(300));
+        assert_eq!(quota.used(), Some(263));
+        assert!(!quota.overage_permitted);
+        assert_eq!(quota.resets_at_ms, Some(1_754_006_400_000));
+
+        headers.insert(QUOTA_HEADERS[0], "ent=-1&ov=0.0&ovPerm=true&rem=100&rst=2025-08-01".parse().unwrap());
+        let quota = quota_from_headers(&headers, 1).unwrap();
+        assert_eq!(quota.entitlement, None);
+        assert_eq!(quota.resets_at_ms, Some(1_754_006_400_000));
+        assert!(quota_from_headers(&reqwest::header::HeaderMap::new(), 1).is_none());
+    }
+
+    #[test]
+    fn detects_github_tokens() {
+        assert!(is_github_token("ghu_abc"));
+        assert!(is_github_token("github_pat_abc"));
+        assert!(!is_github_token("tid=abc;exp=1"));
+    }
+
+    #[test]
+    fn initiator_and_vision_flags() {
+        let mut ctx = ChatContext {
+            system_prompt: None,
+            messages: vec![user("hi")],
+            tools: vec![],
+        };
+        let h = copilot_headers(&ctx);
+        assert_eq!(h.get("X-Initiator").map(String::as_str), Some("user"));
+        assert!(!h.contains_key("Copilot-Vision-Request"));
+
+        ctx.messages.push(Message::User(UserMessage {
+            content: vec![ContentBlock::Image(ImageContent {
+                data: "AAAA".into(),
+                mime_type: "image/png".into(),
+            })],
+        }));
+        ctx.messages.push(Message::ToolResult(ToolResultMessage {
+            tool_call_id: "1".into(),
+            tool_name: "t".into(),
+            content: vec![],
+            is_error: false,
+        }));
+        let h = copilot_headers(&ctx);
+        assert_eq!(h.get("X-Initiator").map(String::as_str), Some("agent"));
+        assert_eq!(h.get("Copilot-Vision-Request").map(String::as_str), Some("true"));
+    }
+
+    #[test]
+    fn model_capabilities_are_mapped() {
+        let m: CopilotModel = serde_json::from_value(serde_json::json!({
+            "id": "claude-sonnet-4",
+            "name": "Claude Sonnet 4",
+            "model_picker_enabled": true,
+            "capabilities": {
+                "type": "chat",
+                "limits": { "max_context_window_tokens": 200000, "max_output_tokens": 32000 },
+                "supports": { "vision": true, "tool_calls": true, "thinking": true }
+            }
+        }))
+        .unwrap();
+        let def = m.into_model_def(DEFAULT_API_BASE).unwrap();
+        assert_eq!(def.context_window, 200000);
+        assert_eq!(def.max_tokens, 32000);
+        assert!(def.reasoning);
+        assert!(def.input.contains(&InputModality::Image));
+        assert!(def.supports_tools());
+
+        let emb: CopilotModel = serde_json::from_value(serde_json::json!({
+            "id": "text-embedding-3-small",
+            "capabilities": { "type": "embeddings" }
+        }))
+        .unwrap();
+        assert!(emb.into_model_def(DEFAULT_API_BASE).is_none());
+    }
+}
@@ -1,8 +1,10 @@
-use super::sanitize;
-use super::{Provider, ProviderError};
+use super::api_error;
+use super::capture;
+use super::tool_ids::ToolCallIds;
+use super::{EventStream, Provider, ProviderError};
 use crate::types::*;
 use async_trait::async_trait;
-use futures::stream::{self, BoxStream, StreamExt};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -61,16 +63,24 @@ struct Part {
     inline_data: Option<InlineData>,
     #[serde(skip_serializing_if = "Option::is_none")]
     thought_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executable_code: Option<ExecutableCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_execution_result: Option<CodeExecutionResult>,
 }
 
 #[derive(Serialize)]
 struct FunctionCallPart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     name: String,
     args: serde_json::Value,
 }
 
 #[derive(Serialize)]
 struct FunctionResponsePart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     name: String,
     response: serde_json::Value,
 }
@@ -109,7 +119,13 @@ struct ThinkingConfig {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ToolDeclaration {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     function_declarations: Vec<FunctionDeclaration>,
+    /// Grounding with Google Search, sent as its own tool entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    google_search: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_execution: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
@@ -128,6 +144,7 @@ struct FunctionDeclaration {
 struct StreamChunk {
     candidates: Option<Vec<Candidate>>,
     usage_metadata: Option<UsageMetadata>,
+    prompt_feedback: Option<PromptFeedback>,
 }
 
 #[derive(Deserialize)]
@@ -135,6 +152,69 @@ struct StreamChunk {
 struct Candidate {
     content: Option<CandidateContent>,
     finish_reason: Option<String>,
+    grounding_metadata: Option<GroundingMetadata>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+/// Sent instead of candidates when Gemini refuses the prompt itself.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+/// Safety ratings and blocks gathered over the chunks of a Gemini response.
+#[derive(Default)]
+pub(crate) struct SafetyVerdict {
+    ratings: Vec<SafetyRating>,
+    prompt_block: Option<String>,
+}
+
+impl SafetyVerdict {
+    /// Record `ratings`, keeping the latest rating per category.
+    pub(crate) fn rate(&mut self, ratings: &[SafetyRating]) {
+        for rating in ratings {
+            match self.ratings.iter_mut().find(|r| r.category == rating.category) {
+                Some(existing) => *existing = rating.clone(),
+                None => self.ratings.push(rating.clone()),
+            }
+        }
+    }
+
+    pub(crate) fn prompt(&mut self, feedback: &PromptFeedback) {
+        self.rate(&feedback.safety_ratings);
+        if let Some(reason) = &feedback.block_reason {
+            self.prompt_block = Some(reason.clone());
+        }
+    }
+
+    /// The stop reason once the prompt block (if any) is taken into account.
+    pub(crate) fn stop_reason(&self, stop_reason: StopReason) -> StopReason {
+        if self.prompt_block.is_some() { StopReason::Safety } else { stop_reason }
+    }
+
+    /// Text explaining why the answer was blocked, or `None` when it wasn't.
+    pub(crate) fn refusal(&self, stop_reason: &StopReason) -> Option<String> {
+        let mut text = match (&self.prompt_block, stop_reason) {
+            (Some(reason), _) => format!("The prompt was blocked ({reason})"),
+            (None, StopReason::Safety) => "The response was blocked by safety filters".to_string(),
+            (None, StopReason::Recitation) => "The response was blocked for reciting source material".to_string(),
+            _ => return None,
+        };
+        let blocked: Vec<&str> = self.ratings.iter().filter(|r| r.blocked).map(|r| r.category.as_str()).collect();
+        if !blocked.is_empty() {
+            text.push_str(": ");
+            text.push_str(&blocked.join(", "));
+        }
+        Some(text)
+    }
+
+    pub(crate) fn into_ratings(self) -> Vec<SafetyRating> {
+        self.ratings
+    }
 }
 
 #[derive(Deserialize)]
@@ -149,10 +229,13 @@ struct ResponsePart {
     thought: Option<bool>,
     function_call: Option<FunctionCallResponse>,
     thought_signature: Option<String>,
+    executable_code: Option<ExecutableCode>,
+    code_execution_result: Option<CodeExecutionResult>,
 }
 
 #[derive(Deserialize)]
 struct FunctionCallResponse {
+    id: Option<String>,
     name: String,
     args: Option<serde_json::Value>,
 }
@@ -167,6 +250,83 @@ struct UsageMetadata {
     cached_content_token_count: Option<u64>,
 }
 
+/// Search grounding (`google_search` tool) results attached to a candidate.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GroundingMetadata {
+    #[serde(default)]
+    grounding_chunks: Vec<GroundingChunk>,
+    #[serde(default)]
+    grounding_supports: Vec<GroundingSupport>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingChunk {
+    web: Option<GroundingSource>,
+    retrieved_context: Option<GroundingSource>,
+}
+
+#[derive(Deserialize)]
+struct GroundingSource {
+    uri: Option<String>,
+    title: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingSupport {
+    segment: Option<GroundingSegment>,
+    #[serde(default)]
+    grounding_chunk_indices: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingSegment {
+    /// Byte offsets into the response text (zero values are omitted).
+    #[serde(default)]
+    start_index: usize,
+    end_index: Option<usize>,
+}
+
+impl GroundingMetadata {
+    /// Citations for the response `text`: one per supported segment and source, or one per
+    /// source when Gemini reports no segments.
+    pub(crate) fn citations(&self, text: &str) -> Vec<Citation> {
+        let source = |i: usize| {
+            let chunk = self.grounding_chunks.get(i)?;
+            let src = chunk.web.as_ref().or(chunk.retrieved_context.as_ref())?;
+            Some(Citation {
+                url: src.uri.clone()?,
+                title: src.title.clone(),
+                ..Citation::default()
+            })
+        };
+        if self.grounding_supports.is_empty() {
+            return (0..self.grounding_chunks.len()).filter_map(source).collect();
+        }
+        let char_offset = |byte: usize| text.char_indices().take_while(|(i, _)| *i < byte).count();
+        self.grounding_supports
+            .iter()
+            .flat_map(|support| {
+                let span = support
+                    .segment
+                    .as_ref()
+                    .and_then(|seg| Some((char_offset(seg.start_index), char_offset(seg.end_index?))));
+                support.grounding_chunk_indices.iter().filter_map(move |&i| {
+                    let mut citation = source(i)?;
+                    if let Some((start, end)) = span {
+                        citation.start_index = Some(start);
+                        citation.end_index = Some(end);
+                    }
+                    Some(citation)
+                })
+            })
+            .collect()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Models list response
 // ---------------------------------------------------------------------------
@@ -186,6 +346,47 @@ struct ModelInfo {
     output_token_limit: Option<u64>,
 }
 
+// ---------------------------------------------------------------------------
+// Embeddings
+// ---------------------------------------------------------------------------
+
+#[derive(Serialize)]
+struct BatchEmbedRequest {
+    requests: Vec<EmbedContentRequest>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EmbedContentRequest {
+    model: String,
+    content: EmbedContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimensionality: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct EmbedContent {
+    parts: Vec<EmbedPart>,
+}
+
+#[derive(Serialize)]
+struct EmbedPart {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct BatchEmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<EmbeddingValues>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingValues {
+    values: Vec<f32>,
+}
+
 // ---------------------------------------------------------------------------
 // Conversion helpers
 // ---------------------------------------------------------------------------
@@ -206,6 +407,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                             function_response: None,
                             inline_data: None,
                             thought_signature: None,
+                            executable_code: None,
+                            code_execution_result: None,
                         }),
                         ContentBlock::Image(img) => Some(Part {
                             text: None,
@@ -216,6 +419,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                                 data: img.data.clone(),
                             }),
                             thought_signature: None,
+                            executable_code: None,
+                            code_execution_result: None,
                         }),
                         _ => None,
                     })
@@ -241,6 +446,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                                 function_response: None,
                                 inline_data: None,
                                 thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                             pending_signature = tc.signature.clone();
                         }
@@ -255,6 +462,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                                     function_response: None,
                                     inline_data: None,
                                     thought_signature: Some(sig),
+                                    executable_code: None,
+                                    code_execution_result: None,
                                 });
                             }
                             parts.push(Part {
@@ -263,6 +472,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                                 function_response: None,
                                 inline_data: None,
                                 thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                         }
                         ContentBlock::ToolCall(tc) => {
@@ -276,15 +487,40 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                             parts.push(Part {
                                 text: None,
                                 function_call: Some(FunctionCallPart {
+                                    id: Some(tc.id.clone()).filter(|id| !id.is_empty()),
                                     name: tc.name.clone(),
                                     args: tc.arguments.clone(),
                                 }),
                                 function_response: None,
                                 inline_data: None,
                                 thought_signature: thought_sig,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                             is_first_tool_call = false;
                         }
+                        ContentBlock::ExecutableCode(code) => {
+                            parts.push(Part {
+                                text: None,
+                                function_call: None,
+                                function_response: None,
+                                inline_data: None,
+                                thought_signature: None,
+                                executable_code: Some(code.clone()),
+                                code_execution_result: None,
+                            });
+                        }
+                        ContentBlock::CodeExecutionResult(result) => {
+                            parts.push(Part {
+                                text: None,
+                                function_call: None,
+                                function_response: None,
+                                inline_data: None,
+                                thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: Some(result.clone()),
+                            });
+                        }
                         _ => {}
                     }
                 }
@@ -296,6 +532,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                         function_response: None,
                         inline_data: None,
                         thought_signature: Some(sig),
+                        executable_code: None,
+                        code_execution_result: None,
                     });
                 }
 
@@ -324,11 +562,14 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
                         text: None,
                         function_call: None,
                         function_response: Some(FunctionResponsePart {
+                            id: Some(tr.tool_call_id.clone()).filter(|id| !id.is_empty()),
                             name: tr.tool_name.clone(),
                             response: json!({"result": text}),
                         }),
                         inline_data: None,
                         thought_signature: None,
+                        executable_code: None,
+                        code_execution_result: None,
                     }],
                 });
             }
@@ -338,40 +579,61 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<Content> {
     contents
 }
 
-fn convert_tools(tools: &[ToolDef]) -> Vec<ToolDeclaration> {
-    vec![ToolDeclaration {
-        function_declarations: tools
-            .iter()
-            .map(|t| FunctionDeclaration {
-                name: t.name.clone(),
-                description: t.description.clone(),
-                parameters: t.parameters.clone(),
-            })
-            .collect(),
-    }]
+fn convert_tools(tools: &[ToolDef], options: &RequestOptions) -> Option<Vec<ToolDeclaration>> {
+    let mut decls = Vec::new();
+    if !tools.is_empty() {
+        decls.push(ToolDeclaration {
+            function_declarations: tools
+                .iter()
+                .map(|t| FunctionDeclaration {
+                    name: t.name.clone(),
+                    description: t.description.clone(),
+                    parameters: t.parameters.clone(),
+                })
+                .collect(),
+            google_search: None,
+            code_execution: None,
+        });
+    }
+    if options.web_search {
+        decls.push(ToolDeclaration {
+            function_declarations: Vec::new(),
+            google_search: Some(json!({})),
+            code_execution: None,
+        });
+    }
+    if options.code_execution {
+        decls.push(ToolDeclaration {
+            function_declarations: Vec::new(),
+            google_search: None,
+            code_execution: Some(json!({})),
+        });
+    }
+    if decls.is_empty() { None } else { Some(decls) }
 }
 
 // ---------------------------------------------------------------------------
 // Provider impl
 // ---------------------------------------------------------------------------
 
-static TOOL_CALL_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GenerateContentResponse {
+    #[serde(default)]
     candidates: Vec<Candidate>,
     usage_metadata: Option<UsageMetadata>,
+    prompt_feedback: Option<PromptFeedback>,
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl Provider for GoogleProvider {
     fn stream(
         &self,
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+    ) -> EventStream {
         let api_key = match &options.api_key {
             Some(k) => k.clone(),
             None => {
@@ -398,6 +660,8 @@ impl Provider for GoogleProvider {
                 function_response: None,
                 inline_data: None,
                 thought_signature: None,
+                executable_code: None,
+                code_execution_result: None,
             }],
         });
 
@@ -407,26 +671,14 @@ impl Provider for GoogleProvider {
             thinking_config: None,
         };
 
-        if model.reasoning {
-            if let Some(level) = &options.reasoning {
-                let budget = match level {
-                    ThinkingLevel::Minimal => 1024,
-                    ThinkingLevel::Low => 2048,
-                    ThinkingLevel::Medium => 8192,
-                    ThinkingLevel::High => 16384,
-                };
-                gen_config.thinking_config = Some(ThinkingConfig {
-                    include_thoughts: true,
-                    thinking_budget: Some(budget),
-                });
-            }
+        if let Some(budget) = model.thinking_budget(options) {
+            gen_config.thinking_config = Some(ThinkingConfig {
+                include_thoughts: true,
+                thinking_budget: Some(budget),
+            });
         }
 
-        let tools = if context.tools.is_empty() {
-            None
-        } else {
-            Some(convert_tools(&context.tools))
-        };
+        let tools = convert_tools(&context.tools, options);
 
         let body = GenerateContentRequest {
             contents,
@@ -438,15 +690,14 @@ impl Provider for GoogleProvider {
         let client = self.client.clone();
         let model_id = model.id.clone();
         let provider_id = model.provider.clone();
+        let capture = options.capture.clone();
 
         let s = async_stream::stream! {
-            let resp = match client
+            let req = client
                 .post(&url)
                 .header("Content-Type", "application/json")
-                .json(&body)
-                .send()
-                .await
-            {
+                .json(&body);
+            let resp = match capture::send(req, capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => {
                     yield Err(ProviderError::Network(e));
@@ -454,13 +705,8 @@ impl Provider for GoogleProvider {
                 }
             };
 
-            let status = resp.status();
-            if !status.is_success() {
-                let body_text = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http {
-                    status: status.as_u16(),
-                    body: sanitize::sanitize_api_error(&body_text),
-                });
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
                 return;
             }
 
@@ -470,8 +716,12 @@ impl Provider for GoogleProvider {
             let mut thinking_buf = String::new();
             let mut thought_signature: Option<String> = None;
             let mut tool_calls: Vec<ToolCall> = Vec::new();
+            let mut tool_call_ids = ToolCallIds::new();
             let mut usage = Usage::default();
             let mut stop_reason = StopReason::Stop;
+            let mut safety = SafetyVerdict::default();
+            let mut citations: Vec<Citation> = Vec::new();
+            let mut code_blocks: Vec<ContentBlock> = Vec::new();
             let mut line_buf = String::new();
 
             let mut byte_stream = resp.bytes_stream();
@@ -516,15 +766,16 @@ impl Provider for GoogleProvider {
                         usage.total_tokens = um.total_token_count.unwrap_or(0);
                     }
 
+                    if let Some(feedback) = &chunk.prompt_feedback {
+                        safety.prompt(feedback);
+                    }
+
                     if let Some(candidates) = &chunk.candidates {
                         for candidate in candidates {
                             if let Some(reason) = &candidate.finish_reason {
-                                stop_reason = match reason.as_str() {
-                                    "STOP" => StopReason::Stop,
-                                    "MAX_TOKENS" => StopReason::Length,
-                                    _ => StopReason::Stop,
-                                };
+                                stop_reason = StopReason::from_gemini(reason);
                             }
+                            safety.rate(&candidate.safety_ratings);
 
                             if let Some(content) = &candidate.content {
                                 if let Some(parts) = &content.parts {
@@ -544,9 +795,17 @@ impl Provider for GoogleProvider {
                                             }
                                         }
 
+                                        if let Some(code) = &part.executable_code {
+                                            code_blocks.push(ContentBlock::ExecutableCode(code.clone()));
+                                            yield Ok(StreamEvent::ExecutableCode(code.clone()));
+                                        }
+                                        if let Some(result) = &part.code_execution_result {
+                                            code_blocks.push(ContentBlock::CodeExecutionResult(result.clone()));
+                                            yield Ok(StreamEvent::CodeExecutionResult(result.clone()));
+                                        }
+
                                         if let Some(fc) = &part.function_call {
-                                            let counter = TOOL_CALL_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                            let tc_id = format!("{}_{}", fc.name, counter);
+                                            let tc_id = tool_call_ids.id(fc.id.as_deref());
                                             let args = fc.args.clone().unwrap_or(json!({}));
                                             let idx = tool_calls.len();
 
@@ -574,6 +833,15 @@ impl Provider for GoogleProvider {
                                     }
                                 }
                             }
+
+                            if let Some(meta) = &candidate.grounding_metadata {
+                                for citation in meta.citations(&text_buf) {
+                                    if !citations.contains(&citation) {
+                                        citations.push(citation.clone());
+                                        yield Ok(StreamEvent::CitationDelta(citation));
+                                    }
+                                }
+                            }
                         }
                     }
                 }
@@ -582,14 +850,23 @@ impl Provider for GoogleProvider {
             if !tool_calls.is_empty() {
                 stop_reason = StopReason::ToolUse;
             }
+            stop_reason = safety.stop_reason(stop_reason);
+            let refusal = safety.refusal(&stop_reason);
+            if let Some(text) = &refusal {
+                yield Ok(StreamEvent::RefusalDelta(text.clone()));
+            }
 
             let mut content = Vec::new();
             if !thinking_buf.is_empty() {
                 content.push(ContentBlock::Thinking(ThinkingContent { thinking: thinking_buf, signature: None }));
             }
+            content.extend(code_blocks);
             if !text_buf.is_empty() {
                 content.push(ContentBlock::Text(TextContent { text: text_buf }));
             }
+            if let Some(text) = refusal {
+                content.push(ContentBlock::Refusal(RefusalContent { text }));
+            }
             for tc in tool_calls {
                 content.push(ContentBlock::ToolCall(tc));
             }
@@ -603,6 +880,8 @@ impl Provider for GoogleProvider {
                 provider: provider_id,
                 usage: Some(usage),
                 stop_reason,
+                citations,
+                safety_ratings: safety.into_ratings(),
             };
 
             yield Ok(StreamEvent::Done { message: msg });
@@ -638,6 +917,8 @@ impl Provider for GoogleProvider {
                 function_response: None,
                 inline_data: None,
                 thought_signature: None,
+                executable_code: None,
+                code_execution_result: None,
             }],
         });
 
@@ -647,26 +928,14 @@ impl Provider for GoogleProvider {
             thinking_config: None,
         };
 
-        if model.reasoning {
-            if let Some(level) = &options.reasoning {
-                let budget = match level {
-                    ThinkingLevel::Minimal => 1024,
-                    ThinkingLevel::Low => 2048,
-                    ThinkingLevel::Medium => 8192,
-                    ThinkingLevel::High => 16384,
-                };
-                gen_config.thinking_config = Some(ThinkingConfig {
-                    include_thoughts: true,
-                    thinking_budget: Some(budget),
-                });
-            }
+        if let Some(budget) = model.thinking_budget(options) {
+            gen_config.thinking_config = Some(ThinkingConfig {
+                include_thoughts: true,
+                thinking_budget: Some(budget),
+            });
         }
 
-        let tools = if context.tools.is_empty() {
-            None
-        } else {
-            Some(convert_tools(&context.tools))
-        };
+        let tools = convert_tools(&context.tools, options);
 
         let body = GenerateContentRequest {
             contents,
@@ -675,20 +944,14 @@ impl Provider for GoogleProvider {
             tools,
         };
 
-        let resp = self.client
+        let req = self.client
             .post(&url)
             .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+            .json(&body);
+        let resp = capture::send(req, options.capture.as_ref()).await?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status: status.as_u16(),
-                body: sanitize::sanitize_api_error(&body),
-            });
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
         }
 
         let gen_resp: GenerateContentResponse = resp.json().await?;
@@ -697,8 +960,12 @@ impl Provider for GoogleProvider {
         let mut thinking_buf = String::new();
         let mut thought_signature: Option<String> = None;
         let mut tool_calls = Vec::new();
+        let mut tool_call_ids = ToolCallIds::new();
         let mut stop_reason = StopReason::Stop;
+        let mut safety = SafetyVerdict::default();
         let mut usage = Usage::default();
+        let mut citations = Vec::new();
+        let mut code_blocks = Vec::new();
 
         if let Some(um) = gen_resp.usage_metadata {
             let prompt = um.prompt_token_count.unwrap_or(0);
@@ -709,14 +976,15 @@ impl Provider for GoogleProvider {
             usage.total_tokens = um.total_token_count.unwrap_or(0);
         }
 
+        if let Some(feedback) = &gen_resp.prompt_feedback {
+            safety.prompt(feedback);
+        }
+
         if let Some(candidate) = gen_resp.candidates.first() {
             if let Some(reason) = &candidate.finish_reason {
-                stop_reason = match reason.as_str() {
-                    "STOP" => StopReason::Stop,
-                    "MAX_TOKENS" => StopReason::Length,
-                    _ => StopReason::Stop,
-                };
+                stop_reason = StopReason::from_gemini(reason);
             }
+            safety.rate(&candidate.safety_ratings);
 
             if let Some(content) = &candidate.content {
                 if let Some(parts) = &content.parts {
@@ -731,10 +999,15 @@ impl Provider for GoogleProvider {
                                 text_buf.push_str(text);
                             }
                         }
+                        if let Some(code) = &part.executable_code {
+                            code_blocks.push(ContentBlock::ExecutableCode(code.clone()));
+                        }
+                        if let Some(result) = &part.code_execution_result {
+                            code_blocks.push(ContentBlock::CodeExecutionResult(result.clone()));
+                        }
                         if let Some(fc) = &part.function_call {
-                            let counter = TOOL_CALL_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                             tool_calls.push(ToolCall {
-                                id: format!("{}_{}", fc.name, counter),
+                                id: tool_call_ids.id(fc.id.as_deref()),
                                 name: fc.name.clone(),
                                 arguments: fc.args.clone().unwrap_or(json!({})),
                             });
@@ -742,19 +1015,27 @@ impl Provider for GoogleProvider {
                     }
                 }
             }
+            if let Some(meta) = &candidate.grounding_metadata {
+                citations = meta.citations(&text_buf);
+            }
         }
 
         if !tool_calls.is_empty() {
             stop_reason = StopReason::ToolUse;
         }
+        stop_reason = safety.stop_reason(stop_reason);
 
         let mut content = Vec::new();
         if !thinking_buf.is_empty() {
             content.push(ContentBlock::Thinking(ThinkingContent { thinking: thinking_buf, signature: None }));
         }
+        content.extend(code_blocks);
         if !text_buf.is_empty() {
             content.push(ContentBlock::Text(TextContent { text: text_buf }));
         }
+        if let Some(text) = safety.refusal(&stop_reason) {
+            content.push(ContentBlock::Refusal(RefusalContent { text }));
+        }
         for tc in tool_calls {
             content.push(ContentBlock::ToolCall(tc));
         }
@@ -768,9 +1049,67 @@ impl Provider for GoogleProvider {
             provider: model.provider.clone(),
             usage: Some(usage),
             stop_reason,
+            citations,
+            safety_ratings: safety.into_ratings(),
+        })
+    }
+
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let api_key = options.api_key.as_deref().ok_or_else(|| {
+            ProviderError::AuthRequired("API key required for Google".into())
+        })?;
+
+        let base_url = model.base_url.trim_end_matches('/');
+        let url = format!("{}/models/{}:batchEmbedContents?key={}", base_url, model.id, api_key);
+        let task_type = request.input_type.map(|t| match t {
+            EmbeddingInputType::Query => "RETRIEVAL_QUERY",
+            EmbeddingInputType::Document => "RETRIEVAL_DOCUMENT",
+        });
+        let body = BatchEmbedRequest {
+            requests: request
+                .input
+                .iter()
+                .map(|text| EmbedContentRequest {
+                    model: format!("models/{}", model.id),
+                    content: EmbedContent {
+                        parts: vec![EmbedPart { text: text.clone() }],
+                    },
+                    task_type,
+                    output_dimensionality: request.dimensions,
+                })
+                .collect(),
+        };
+
+        let resp = self.client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
+        }
+
+        let embed_resp: BatchEmbedResponse = resp.json().await?;
+        Ok(EmbeddingResponse {
+            model: model.id.clone(),
+            provider: model.provider.clone(),
+            embeddings: embed_resp.embeddings.into_iter().map(|e| e.values).collect(),
+            // The batch endpoint does not report token usage.
+            usage: None,
         })
     }
 
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
     async fn list_models(&self, api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models?key={}",
@@ -779,13 +1118,8 @@ impl Provider for GoogleProvider {
 
         let resp = self.client.get(&url).send().await?;
 
-        let status = resp.status().as_u16();
         if !resp.status().is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status,
-                body: sanitize::sanitize_api_error(&body),
-            });
+            return Err(api_error::from_response(resp).await);
         }
 
         let list: ModelsListResponse = resp.json().await?;
@@ -815,10 +1149,10 @@ impl Provider for GoogleProvider {
                     base_url: "https://generativelanguage.googleapis.com/v1beta".into(),
                     reasoning,
                     input: vec![InputModality::Text, InputModality::Image],
-                    cost: ModelCost::default(),
                     context_window: m.input_token_limit.unwrap_or(128000),
                     max_tokens: m.output_token_limit.unwrap_or(8192),
-                    headers: None,
+                    capabilities: ModelCapabilities { tools: true, json_mode: true },
+                    ..Default::default()
                 }
             })
             .collect();
@@ -826,3 +1160,140 @@ impl Provider for GoogleProvider {
         Ok(models)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grounding_supports_become_char_offset_citations() {
+        let meta: GroundingMetadata = serde_json::from_value(json!({
+            "groundingChunks": [
+                {"web": {"uri": "https://a.example", "title": "A"}},
+                {"web": {"uri": "https://b.example"}}
+            ],
+            "groundingSupports": [
+                {"segment": {"endIndex": 6, "text": "Café"}, "groundingChunkIndices": [0]},
+                {"segment": {"startIndex": 7, "endIndex": 10}, "groundingChunkIndices": [0, 1]}
+            ]
+        }))
+        .unwrap();
+        // "é" is two bytes: byte offsets 6/7/10 are character offsets 5/6/9.
+        let citations = meta.citations("Café. Yes");
+        assert_eq!(citations.len(), 3);
+        assert_eq!((citations[0].start_index, citations[0].end_index), (Some(0), Some(5)));
+        assert_eq!(citations[0].title.as_deref(), Some("A"));
+        assert_eq!((citations[2].url.as_str(), citations[2].start_index), ("https://b.example", Some(6)));
+
+        let sources_only: GroundingMetadata =
+            serde_json::from_value(json!({"groundingChunks": [{"web": {"uri": "https://a.example"}}]})).unwrap();
+        assert_eq!(sources_only.citations("").len(), 1);
+    }
+
+    #[test]
+    fn safety_blocks_become_refusals() {
+        let chunk: StreamChunk = serde_json::from_value(json!({
+            "candidates": [{"finishReason": "SAFETY", "safetyRatings": [
+                {"category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE"},
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true}
+            ]}]
+        }))
+        .unwrap();
+        let candidate = &chunk.candidates.unwrap()[0];
+        let mut safety = SafetyVerdict::default();
+        safety.rate(&candidate.safety_ratings);
+        let stop_reason = safety.stop_reason(StopReason::from_gemini(candidate.finish_reason.as_deref().unwrap()));
+        assert_eq!(
+            safety.refusal(&stop_reason).as_deref(),
+            Some("The response was blocked by safety filters: HARM_CATEGORY_DANGEROUS_CONTENT")
+        );
+        assert_eq!(safety.into_ratings().len(), 2);
+
+        // A blocked prompt has no candidates at all.
+        let resp: GenerateContentResponse =
+            serde_json::from_value(json!({"promptFeedback": {"blockReason": "PROHIBITED_CONTENT"}})).unwrap();
+        let mut safety = SafetyVerdict::default();
+        safety.prompt(resp.prompt_feedback.as_ref().unwrap());
+        let stop_reason = safety.stop_reason(StopReason::Stop);
+        assert_eq!(stop_reason, StopReason::Safety);
+        assert_eq!(safety.refusal(&stop_reason).as_deref(), Some("The prompt was blocked (PROHIBITED_CONTENT)"));
+
+        assert_eq!(SafetyVerdict::default().refusal(&StopReason::Stop), None);
+    }
+
+    #[test]
+    fn code_execution_tool_and_parts() {
+        let options = RequestOptions {
+            code_execution: true,
+            ..Default::default()
+        };
+        let tools = serde_json::to_value(convert_tools(&[], &options)).unwrap();
+        assert_eq!(tools, json!([{"codeExecution": {}}]));
+
+        let parts: Vec<ResponsePart> = serde_json::from_value(json!([
+            {"executableCode": {"language": "PYTHON", "code": "print(6 * 7)"}},
+            {"codeExecutionResult": {"outcome": "OUTCOME_OK", "output": "42\n"}}
+        ]))
+        .unwrap();
+        let code = parts[0].executable_code.clone().unwrap();
+        assert_eq!(code.code, "print(6 * 7)");
+        assert_eq!(parts[1].code_execution_result.as_ref().unwrap().output, "42\n");
+
+        // Replayed history sends the parts back in Gemini's shape.
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![Message::Assistant(AssistantMessage {
+                content: vec![ContentBlock::ExecutableCode(code)],
+                model: "gemini-2.5-flash".into(),
+                provider: "google".into(),
+                usage: None,
+                stop_reason: StopReason::Stop,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
+            })],
+            tools: Vec::new(),
+        };
+        let model = crate::models::static_models_for_provider("google").remove(0);
+        let contents = serde_json::to_value(convert_messages(&context, &model)).unwrap();
+        assert_eq!(contents[0]["parts"][0]["executableCode"]["language"], "PYTHON");
+    }
+
+    #[test]
+    fn tool_call_ids_round_trip() {
+        let part: ResponsePart =
+            serde_json::from_value(json!({"functionCall": {"id": "fc-1", "name": "get_time", "args": {}}})).unwrap();
+        let mut ids = ToolCallIds::new();
+        assert_eq!(ids.id(part.function_call.unwrap().id.as_deref()), "fc-1");
+
+        let id = ids.id(None);
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![
+                Message::Assistant(AssistantMessage {
+                    content: vec![ContentBlock::ToolCall(ToolCall {
+                        id: id.clone(),
+                        name: "get_time".into(),
+                        arguments: json!({}),
+                    })],
+                    model: "gemini-2.5-flash".into(),
+                    provider: "google".into(),
+                    usage: None,
+                    stop_reason: StopReason::ToolUse,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
+                }),
+                Message::ToolResult(ToolResultMessage {
+                    tool_call_id: id.clone(),
+                    tool_name: "get_time".into(),
+                    content: vec![ContentBlock::Text(TextContent { text: "noon".into() })],
+                    is_error: false,
+                }),
+            ],
+            tools: Vec::new(),
+        };
+        let model = crate::models::static_models_for_provider("google").remove(0);
+        let contents = serde_json::to_value(convert_messages(&context, &model)).unwrap();
+        assert_eq!(contents[0]["parts"][0]["functionCall"]["id"], id.as_str());
+        assert_eq!(contents[1]["parts"][0]["functionResponse"]["id"], id.as_str());
+    }
+}
@@ -1,8 +1,11 @@
-use super::sanitize;
-use super::{Provider, ProviderError};
+use super::api_error;
+use super::capture;
+use super::google::{GroundingMetadata, PromptFeedback, SafetyVerdict};
+use super::tool_ids::ToolCallIds;
+use super::{EventStream, Provider, ProviderError};
 use crate::types::*;
 use async_trait::async_trait;
-use futures::stream::{self, BoxStream, StreamExt};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -36,6 +39,9 @@ impl GoogleGeminiCliProvider {
 const DEFAULT_ENDPOINT: &str = "https://cloudcode-pa.googleapis.com";
 const ANTIGRAVITY_DAILY_ENDPOINT: &str = "https://daily-cloudcode-pa.sandbox.googleapis.com";
 const DEFAULT_ANTIGRAVITY_VERSION: &str = "1.15.8";
+/// System prompt Antigravity requests start with, unless the model's `persona` says otherwise.
+const ANTIGRAVITY_PERSONA: &str =
+    "You are Antigravity, a powerful agentic AI coding assistant designed by the Google Deepmind team.";
 
 fn gemini_cli_headers() -> HashMap<String, String> {
     let mut h = HashMap::new();
@@ -120,16 +126,24 @@ struct GPart {
     function_response: Option<GFunctionResponse>,
     #[serde(skip_serializing_if = "Option::is_none")]
     thought_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executable_code: Option<ExecutableCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_execution_result: Option<CodeExecutionResult>,
 }
 
 #[derive(Serialize)]
 struct GFunctionCall {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     name: String,
     args: serde_json::Value,
 }
 
 #[derive(Serialize)]
 struct GFunctionResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     name: String,
     response: serde_json::Value,
 }
@@ -165,7 +179,13 @@ struct GThinkingConfig {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct GToolDeclaration {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     function_declarations: Vec<GFunctionDeclaration>,
+    /// Grounding with Google Search, sent as its own tool entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    google_search: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_execution: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
@@ -189,6 +209,7 @@ struct ChunkEnvelope {
 struct ResponseData {
     candidates: Option<Vec<RCandidate>>,
     usage_metadata: Option<RUsageMetadata>,
+    prompt_feedback: Option<PromptFeedback>,
 }
 
 #[derive(Deserialize)]
@@ -196,6 +217,9 @@ struct ResponseData {
 struct RCandidate {
     content: Option<RContent>,
     finish_reason: Option<String>,
+    grounding_metadata: Option<GroundingMetadata>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
 }
 
 #[derive(Deserialize)]
@@ -210,6 +234,8 @@ struct RPart {
     thought: Option<bool>,
     function_call: Option<RFunctionCall>,
     thought_signature: Option<String>,
+    executable_code: Option<ExecutableCode>,
+    code_execution_result: Option<CodeExecutionResult>,
 }
 
 #[derive(Deserialize)]
@@ -248,6 +274,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                             function_call: None,
                             function_response: None,
                             thought_signature: None,
+                            executable_code: None,
+                            code_execution_result: None,
                         }),
                         _ => None,
                     })
@@ -272,6 +300,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                                 function_call: None,
                                 function_response: None,
                                 thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                             pending_signature = tc.signature.clone();
                         }
@@ -285,6 +315,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                                     function_call: None,
                                     function_response: None,
                                     thought_signature: Some(sig),
+                                    executable_code: None,
+                                    code_execution_result: None,
                                 });
                             }
                             parts.push(GPart {
@@ -292,6 +324,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                                 function_call: None,
                                 function_response: None,
                                 thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                         }
                         ContentBlock::ToolCall(tc) => {
@@ -305,14 +339,37 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                             parts.push(GPart {
                                 text: None,
                                 function_call: Some(GFunctionCall {
+                                    id: Some(tc.id.clone()).filter(|id| !id.is_empty()),
                                     name: tc.name.clone(),
                                     args: tc.arguments.clone(),
                                 }),
                                 function_response: None,
                                 thought_signature: thought_sig,
+                                executable_code: None,
+                                code_execution_result: None,
                             });
                             is_first_tool_call = false;
                         }
+                        ContentBlock::ExecutableCode(code) => {
+                            parts.push(GPart {
+                                text: None,
+                                function_call: None,
+                                function_response: None,
+                                thought_signature: None,
+                                executable_code: Some(code.clone()),
+                                code_execution_result: None,
+                            });
+                        }
+                        ContentBlock::CodeExecutionResult(result) => {
+                            parts.push(GPart {
+                                text: None,
+                                function_call: None,
+                                function_response: None,
+                                thought_signature: None,
+                                executable_code: None,
+                                code_execution_result: Some(result.clone()),
+                            });
+                        }
                         _ => {}
                     }
                 }
@@ -323,6 +380,8 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                         function_call: None,
                         function_response: None,
                         thought_signature: Some(sig),
+                        executable_code: None,
+                        code_execution_result: None,
                     });
                 }
 
@@ -351,10 +410,13 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
                         text: None,
                         function_call: None,
                         function_response: Some(GFunctionResponse {
+                            id: Some(tr.tool_call_id.clone()).filter(|id| !id.is_empty()),
                             name: tr.tool_name.clone(),
                             response: json!({"result": text}),
                         }),
                         thought_signature: None,
+                        executable_code: None,
+                        code_execution_result: None,
                     }],
                 });
             }
@@ -364,17 +426,37 @@ fn convert_messages(context: &ChatContext, model: &ModelDef) -> Vec<GContent> {
     contents
 }
 
-fn convert_tools(tools: &[ToolDef]) -> Vec<GToolDeclaration> {
-    vec![GToolDeclaration {
-        function_declarations: tools
-            .iter()
-            .map(|t| GFunctionDeclaration {
-                name: t.name.clone(),
-                description: t.description.clone(),
-                parameters: t.parameters.clone(),
-            })
-            .collect(),
-    }]
+fn convert_tools(tools: &[ToolDef], options: &RequestOptions) -> Option<Vec<GToolDeclaration>> {
+    let mut decls = Vec::new();
+    if !tools.is_empty() {
+        decls.push(GToolDeclaration {
+            function_declarations: tools
+                .iter()
+                .map(|t| GFunctionDeclaration {
+                    name: t.name.clone(),
+                    description: t.description.clone(),
+                    parameters: t.parameters.clone(),
+                })
+                .collect(),
+            google_search: None,
+            code_execution: None,
+        });
+    }
+    if options.web_search {
+        decls.push(GToolDeclaration {
+            function_declarations: Vec::new(),
+            google_search: Some(json!({})),
+            code_execution: None,
+        });
+    }
+    if options.code_execution {
+        decls.push(GToolDeclaration {
+            function_declarations: Vec::new(),
+            google_search: None,
+            code_execution: Some(json!({})),
+        });
+    }
+    if decls.is_empty() { None } else { Some(decls) }
 }
 
 /// Parse the JSON-encoded API key used by Cloud Code Assist.
@@ -402,20 +484,19 @@ fn parse_cloud_code_api_key(api_key: &str) -> Result<(String, String), ProviderE
     Ok((parsed.token, parsed.project_id))
 }
 
-static TOOL_CALL_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-
 // ---------------------------------------------------------------------------
 // Provider impl
 // ---------------------------------------------------------------------------
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl Provider for GoogleGeminiCliProvider {
     fn stream(
         &self,
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+    ) -> EventStream {
         let api_key_raw = match &options.api_key {
             Some(k) => k.clone(),
             None => {
@@ -448,15 +529,14 @@ impl Provider for GoogleGeminiCliProvider {
         let contents = convert_messages(context, model);
 
         let mut sys_parts = Vec::new();
-        if is_antigravity {
+        if let Some(persona) = model.persona_prompt(is_antigravity.then_some(ANTIGRAVITY_PERSONA)) {
             sys_parts.push(GPart {
-                text: Some(
-                    "You are Antigravity, a powerful agentic AI coding assistant designed by the Google Deepmind team."
-                        .into(),
-                ),
+                text: Some(persona),
                 function_call: None,
                 function_response: None,
                 thought_signature: None,
+                executable_code: None,
+                code_execution_result: None,
             });
         }
         if let Some(sp) = &context.system_prompt {
@@ -465,6 +545,8 @@ impl Provider for GoogleGeminiCliProvider {
                 function_call: None,
                 function_response: None,
                 thought_signature: None,
+                executable_code: None,
+                code_execution_result: None,
             });
         }
 
@@ -484,41 +566,29 @@ impl Provider for GoogleGeminiCliProvider {
         };
 
         if model.reasoning {
-            if let Some(level) = &options.reasoning {
-                let is_gemini3 = model.id.contains("3-pro") || model.id.contains("3-flash");
-                if is_gemini3 {
-                    let level_str = match level {
-                        ThinkingLevel::Minimal => "MINIMAL",
-                        ThinkingLevel::Low => "LOW",
-                        ThinkingLevel::Medium => "MEDIUM",
-                        ThinkingLevel::High => "HIGH",
-                    };
-                    gen_config.thinking_config = Some(GThinkingConfig {
-                        include_thoughts: true,
-                        thinking_budget: None,
-                        thinking_level: Some(level_str.to_string()),
-                    });
-                } else {
-                    let budget = match level {
-                        ThinkingLevel::Minimal => 1024,
-                        ThinkingLevel::Low => 2048,
-                        ThinkingLevel::Medium => 8192,
-                        ThinkingLevel::High => 16384,
-                    };
-                    gen_config.thinking_config = Some(GThinkingConfig {
-                        include_thoughts: true,
-                        thinking_budget: Some(budget),
-                        thinking_level: None,
-                    });
-                }
+            let is_gemini3 = model.id.contains("3-pro") || model.id.contains("3-flash");
+            if let Some(level) = options.reasoning.as_ref().filter(|_| is_gemini3 && options.thinking_budget.is_none()) {
+                let level_str = match level {
+                    ThinkingLevel::Minimal => "MINIMAL",
+                    ThinkingLevel::Low => "LOW",
+                    ThinkingLevel::Medium => "MEDIUM",
+                    ThinkingLevel::High => "HIGH",
+                };
+                gen_config.thinking_config = Some(GThinkingConfig {
+                    include_thoughts: true,
+                    thinking_budget: None,
+                    thinking_level: Some(level_str.to_string()),
+                });
+            } else if let Some(budget) = model.thinking_budget(options) {
+                gen_config.thinking_config = Some(GThinkingConfig {
+                    include_thoughts: true,
+                    thinking_budget: Some(budget),
+                    thinking_level: None,
+                });
             }
         }
 
-        let tools = if context.tools.is_empty() {
-            None
-        } else {
-            Some(convert_tools(&context.tools))
-        };
+        let tools = convert_tools(&context.tools, options);
 
         let request_body = CloudCodeAssistRequest {
             project: project_id,
@@ -559,6 +629,7 @@ impl Provider for GoogleGeminiCliProvider {
         let model_id = model.id.clone();
         let provider_id = model.provider.clone();
         let opt_extra_headers = options.extra_headers.clone();
+        let capture = options.capture.clone();
 
         let s = async_stream::stream! {
             let mut req = client
@@ -576,7 +647,7 @@ impl Provider for GoogleGeminiCliProvider {
                 }
             }
 
-            let resp = match req.json(&request_body).send().await {
+            let resp = match capture::send(req.json(&request_body), capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => {
                     yield Err(ProviderError::Network(e));
@@ -584,13 +655,8 @@ impl Provider for GoogleGeminiCliProvider {
                 }
             };
 
-            let status = resp.status();
-            if !status.is_success() {
-                let body_text = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http {
-                    status: status.as_u16(),
-                    body: sanitize::sanitize_api_error(&body_text),
-                });
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
                 return;
             }
 
@@ -600,8 +666,12 @@ impl Provider for GoogleGeminiCliProvider {
             let mut thinking_buf = String::new();
             let mut thought_signature: Option<String> = None;
             let mut tool_calls: Vec<ToolCall> = Vec::new();
+            let mut tool_call_ids = ToolCallIds::new();
             let mut usage = Usage::default();
             let mut stop_reason = StopReason::Stop;
+            let mut safety = SafetyVerdict::default();
+            let mut citations: Vec<Citation> = Vec::new();
+            let mut code_blocks: Vec<ContentBlock> = Vec::new();
             let mut line_buf = String::new();
 
             let mut byte_stream = resp.bytes_stream();
@@ -651,15 +721,16 @@ impl Provider for GoogleGeminiCliProvider {
                         usage.total_tokens = um.total_token_count.unwrap_or(0);
                     }
 
+                    if let Some(feedback) = &resp_data.prompt_feedback {
+                        safety.prompt(feedback);
+                    }
+
                     if let Some(candidates) = &resp_data.candidates {
                         for candidate in candidates {
                             if let Some(reason) = &candidate.finish_reason {
-                                stop_reason = match reason.as_str() {
-                                    "STOP" => StopReason::Stop,
-                                    "MAX_TOKENS" => StopReason::Length,
-                                    _ => StopReason::Stop,
-                                };
+                                stop_reason = StopReason::from_gemini(reason);
                             }
+                            safety.rate(&candidate.safety_ratings);
 
                             if let Some(content) = &candidate.content {
                                 if let Some(parts) = &content.parts {
@@ -679,11 +750,17 @@ impl Provider for GoogleGeminiCliProvider {
                                             }
                                         }
 
+                                        if let Some(code) = &part.executable_code {
+                                            code_blocks.push(ContentBlock::ExecutableCode(code.clone()));
+                                            yield Ok(StreamEvent::ExecutableCode(code.clone()));
+                                        }
+                                        if let Some(result) = &part.code_execution_result {
+                                            code_blocks.push(ContentBlock::CodeExecutionResult(result.clone()));
+                                            yield Ok(StreamEvent::CodeExecutionResult(result.clone()));
+                                        }
+
                                         if let Some(fc) = &part.function_call {
-                                            let counter = TOOL_CALL_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                            let tc_id = fc.id.clone().unwrap_or_else(|| {
-                                                format!("{}_{}", fc.name, counter)
-                                            });
+                                            let tc_id = tool_call_ids.id(fc.id.as_deref());
                                             let args = fc.args.clone().unwrap_or(json!({}));
                                             let idx = tool_calls.len();
 
@@ -711,6 +788,15 @@ impl Provider for GoogleGeminiCliProvider {
                                     }
                                 }
                             }
+
+                            if let Some(meta) = &candidate.grounding_metadata {
+                                for citation in meta.citations(&text_buf) {
+                                    if !citations.contains(&citation) {
+                                        citations.push(citation.clone());
+                                        yield Ok(StreamEvent::CitationDelta(citation));
+                                    }
+                                }
+                            }
                         }
                     }
                 }
@@ -719,14 +805,23 @@ impl Provider for GoogleGeminiCliProvider {
             if !tool_calls.is_empty() {
                 stop_reason = StopReason::ToolUse;
             }
+            stop_reason = safety.stop_reason(stop_reason);
+            let refusal = safety.refusal(&stop_reason);
+            if let Some(text) = &refusal {
+                yield Ok(StreamEvent::RefusalDelta(text.clone()));
+            }
 
             let mut content = Vec::new();
             if !thinking_buf.is_empty() {
                 content.push(ContentBlock::Thinking(ThinkingContent { thinking: thinking_buf, signature: None }));
             }
+            content.extend(code_blocks);
             if !text_buf.is_empty() {
                 content.push(ContentBlock::Text(TextContent { text: text_buf }));
             }
+            if let Some(text) = refusal {
+                content.push(ContentBlock::Refusal(RefusalContent { text }));
+            }
             for tc in tool_calls {
                 content.push(ContentBlock::ToolCall(tc));
             }
@@ -740,6 +835,8 @@ impl Provider for GoogleGeminiCliProvider {
                 provider: provider_id,
                 usage: Some(usage),
                 stop_reason,
+                citations,
+                safety_ratings: safety.into_ratings(),
             };
 
             yield Ok(StreamEvent::Done { message: msg });
@@ -761,12 +858,16 @@ impl Provider for GoogleGeminiCliProvider {
             provider: model.provider.clone(),
             usage: None,
             stop_reason: StopReason::Stop,
+            citations: Vec::new(),
+            safety_ratings: Vec::new(),
         };
 
         let mut text_buf = String::new();
         let mut thinking_buf = String::new();
         let mut thought_signature: Option<String> = None;
         let mut tool_calls = Vec::new();
+        let mut code_blocks = Vec::new();
+        let mut refusal = None;
 
         while let Some(event) = stream.next().await {
             match event? {
@@ -774,9 +875,14 @@ impl Provider for GoogleGeminiCliProvider {
                 StreamEvent::ThinkingDelta(d) => thinking_buf.push_str(&d),
                 StreamEvent::ThoughtSignature(sig) => thought_signature = Some(sig),
                 StreamEvent::ToolCallEnd { tool_call, .. } => tool_calls.push(tool_call),
+                StreamEvent::ExecutableCode(code) => code_blocks.push(ContentBlock::ExecutableCode(code)),
+                StreamEvent::CodeExecutionResult(result) => code_blocks.push(ContentBlock::CodeExecutionResult(result)),
+                StreamEvent::RefusalDelta(text) => refusal = Some(text),
                 StreamEvent::Done { message } => {
                     full_msg.usage = message.usage;
                     full_msg.stop_reason = message.stop_reason;
+                    full_msg.citations = message.citations;
+                    full_msg.safety_ratings = message.safety_ratings;
                 }
                 _ => {}
             }
@@ -788,11 +894,15 @@ impl Provider for GoogleGeminiCliProvider {
                 signature: None,
             }));
         }
+        full_msg.content.extend(code_blocks);
         if !text_buf.is_empty() {
             full_msg.content.push(ContentBlock::Text(TextContent {
                 text: text_buf,
             }));
         }
+        if let Some(text) = refusal {
+            full_msg.content.push(ContentBlock::Refusal(RefusalContent { text }));
+        }
         for tc in tool_calls {
             full_msg.content.push(ContentBlock::ToolCall(tc));
         }
@@ -803,6 +913,10 @@ impl Provider for GoogleGeminiCliProvider {
         Ok(full_msg)
     }
 
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
     async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
         if self.is_antigravity {
             Ok(static_antigravity_models())
@@ -863,9 +977,9 @@ fn model_def(
         base_url: base_url.into(),
         reasoning,
         input: vec![InputModality::Text, InputModality::Image],
-        cost: ModelCost::default(),
         context_window,
         max_tokens,
-        headers: None,
+        capabilities: ModelCapabilities { tools: true, json_mode: true },
+        ..Default::default()
     }
 }
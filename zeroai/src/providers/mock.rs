@@ -0,0 +1,376 @@
+//! Scripted provider for tests.
+//!
+//! `MockProvider` answers with canned replies in order, so code built on `AiClient` can be
+//! exercised without a network: plain text, tool calls, HTTP errors such as 429 before any
+//! output, and streams that break after partial output. Latency can be added before the
+//! first event and between streamed chunks.
+//!
+//! ```
+//! use zeroai::{AiClient, MockProvider, MockReply};
+//! use std::sync::Arc;
+//!
+//! let mock = Arc::new(
+//!     MockProvider::new()
+//!         .with_reply(MockReply::error(429))
+//!         .with_reply(MockReply::text("Hello!")),
+//! );
+//! let client = AiClient::builder()
+//!     .with_provider("mock", mock.clone())
+//!     .with_model("mock/test".into(), MockProvider::model("test"))
+//!     .build();
+//! ```
+
+use super::api_error::ApiError;
+use super::{EventStream, Provider, ProviderError};
+use crate::tokens;
+use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// One scripted response of a [`MockProvider`].
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    /// Stream this text word by word, then finish.
+    Text(String),
+    /// Call these tools (stop reason `ToolUse`).
+    ToolCalls(Vec<ToolCall>),
+    /// Fail before any output with this HTTP status and body.
+    Error { status: u16, body: String },
+    /// Stream this text, then fail as if the connection dropped.
+    FailMidStream(String),
+}
+
+impl MockReply {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// A single tool call with ID `call_1`.
+    pub fn tool_call(name: impl Into<String>, arguments: serde_json::Value) -> Self {
+        Self::ToolCalls(vec![ToolCall {
+            id: "call_1".into(),
+            name: name.into(),
+            arguments,
+        }])
+    }
+
+    /// An HTTP error; `error(429)` is a rate limit.
+    pub fn error(status: u16) -> Self {
+        Self::Error {
+            status,
+            body: format!("mock error {}", status),
+        }
+    }
+}
+
+/// A provider that replays scripted replies and records the requests it receives.
+///
+/// Replies are used in order; the last one repeats once the script runs out.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    replies: Mutex<VecDeque<MockReply>>,
+    latency: Duration,
+    chunk_delay: Duration,
+    requests: Mutex<Vec<ChatContext>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a reply to the script.
+    pub fn with_reply(self, reply: MockReply) -> Self {
+        self.replies.lock().unwrap().push_back(reply);
+        self
+    }
+
+    /// Wait this long before the first event of every response.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Wait this long between streamed chunks.
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    /// The contexts of all requests received so far, in order.
+    pub fn requests(&self) -> Vec<ChatContext> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// A model definition for `id` served by the `mock` provider.
+    pub fn model(id: &str) -> ModelDef {
+        ModelDef {
+            id: id.into(),
+            name: id.into(),
+            api: Api::Custom("mock".into()),
+            provider: "mock".into(),
+            base_url: String::new(),
+            reasoning: false,
+            input: vec![InputModality::Text, InputModality::Image],
+            context_window: 128_000,
+            max_tokens: 4096,
+            ..Default::default()
+        }
+    }
+
+    fn next_reply(&self) -> Option<MockReply> {
+        let mut replies = self.replies.lock().unwrap();
+        if replies.len() > 1 {
+            replies.pop_front()
+        } else {
+            replies.front().cloned()
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for MockProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        _options: &RequestOptions,
+    ) -> EventStream {
+        self.requests.lock().unwrap().push(context.clone());
+        let reply = self.next_reply();
+        let latency = self.latency;
+        let chunk_delay = self.chunk_delay;
+        let input_tokens = tokens::estimate_input_tokens(context);
+        let model_id = model.id.clone();
+        let provider_id = model.provider.clone();
+
+        let s = async_stream::stream! {
+            if !latency.is_zero() {
+                crate::rt::sleep(latency).await;
+            }
+            let (text, tool_calls, fail) = match reply {
+                None => {
+                    yield Err(ProviderError::Other("MockProvider has no scripted replies".into()));
+                    return;
+                }
+                Some(MockReply::Error { status, body }) => {
+                    yield Err(ProviderError::Http { status, error: Box::new(ApiError::parse(&body)) });
+                    return;
+                }
+                Some(MockReply::Text(text)) => (text, Vec::new(), false),
+                Some(MockReply::ToolCalls(calls)) => (String::new(), calls, false),
+                Some(MockReply::FailMidStream(text)) => (text, Vec::new(), true),
+            };
+            yield Ok(StreamEvent::Start);
+
+            for (i, word) in text.split_inclusive(' ').enumerate() {
+                if i > 0 && !chunk_delay.is_zero() {
+                    crate::rt::sleep(chunk_delay).await;
+                }
+                yield Ok(StreamEvent::TextDelta(word.to_string()));
+            }
+            if fail {
+                yield Err(ProviderError::Other("Mock stream interrupted".into()));
+                return;
+            }
+            for (index, call) in tool_calls.iter().enumerate() {
+                yield Ok(StreamEvent::ToolCallStart { index, id: call.id.clone(), name: call.name.clone() });
+                yield Ok(StreamEvent::ToolCallDelta { index, delta: call.arguments.to_string() });
+                yield Ok(StreamEvent::ToolCallEnd { index, tool_call: call.clone() });
+            }
+
+            let output_tokens = tokens::estimate_text_tokens(&text)
+                + tool_calls.iter().map(|c| tokens::estimate_text_tokens(&c.arguments.to_string())).sum::<u64>();
+            let stop_reason = if tool_calls.is_empty() { StopReason::Stop } else { StopReason::ToolUse };
+            let mut content = Vec::new();
+            if !text.is_empty() {
+                content.push(ContentBlock::Text(TextContent { text }));
+            }
+            content.extend(tool_calls.into_iter().map(ContentBlock::ToolCall));
+            yield Ok(StreamEvent::Done {
+                message: AssistantMessage {
+                    content,
+                    model: model_id,
+                    provider: provider_id,
+                    usage: Some(Usage {
+                        input_tokens,
+                        output_tokens,
+                        total_tokens: input_tokens + output_tokens,
+                        ..Usage::default()
+                    }),
+                    stop_reason,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
+                },
+            });
+        };
+        Box::pin(s)
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        let mut stream = self.stream(model, context, options);
+        while let Some(event) = stream.next().await {
+            if let StreamEvent::Done { message } = event? {
+                return Ok(message);
+            }
+        }
+        Err(ProviderError::Other("Mock stream ended without a response".into()))
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AiClient;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn client(mock: Arc<MockProvider>) -> AiClient {
+        AiClient::builder()
+            .with_provider("mock", mock)
+            .with_model("mock/test".into(), MockProvider::model("test"))
+            .build()
+    }
+
+    fn context(text: &str) -> ChatContext {
+        ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage {
+                content: vec![ContentBlock::Text(TextContent { text: text.into() })],
+            })],
+            tools: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_scripted_text_and_tool_calls() {
+        let mock = Arc::new(
+            MockProvider::new()
+                .with_reply(MockReply::text("Hello there world"))
+                .with_reply(MockReply::tool_call("get_time", json!({"tz": "UTC"}))),
+        );
+        let client = client(mock.clone());
+
+        let events: Vec<_> = client
+            .stream("mock/test", &context("hi"), &RequestOptions::default())
+            .unwrap()
+            .collect()
+            .await;
+        let deltas: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Ok(StreamEvent::TextDelta(t)) => Some(t.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, ["Hello ", "there ", "world"]);
+
+        let message = client.chat("mock/test", &context("now?"), &RequestOptions::default()).await.unwrap();
+        assert_eq!(message.stop_reason, StopReason::ToolUse);
+        assert_eq!(message.model, "mock/test");
+        assert!(matches!(&message.content[0], ContentBlock::ToolCall(c) if c.name == "get_time"));
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_is_retried() {
+        let mock = Arc::new(
+            MockProvider::new()
+                .with_reply(MockReply::error(429))
+                .with_reply(MockReply::text("ok")),
+        );
+        let options = RequestOptions {
+            retry_config: Some(RetryConfig { max_retries: 1, base_backoff_ms: 1, ..RetryConfig::default() }),
+            ..RequestOptions::default()
+        };
+        let message = client(mock.clone()).chat("mock/test", &context("hi"), &options).await.unwrap();
+        assert!(matches!(&message.content[0], ContentBlock::Text(t) if t.text == "ok"));
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn responses_are_timed() {
+        let mock = Arc::new(
+            MockProvider::new()
+                .with_reply(MockReply::text("one two three"))
+                .with_latency(Duration::from_millis(30))
+                .with_chunk_delay(Duration::from_millis(10)),
+        );
+        let client = client(mock);
+        let events: Vec<_> = client
+            .stream("mock/test", &context("hi"), &RequestOptions::default())
+            .unwrap()
+            .collect()
+            .await;
+        let Some(Ok(StreamEvent::Done { message })) = events.last() else { panic!("expected Done") };
+        let usage = message.usage.as_ref().unwrap();
+        let (ttft, duration) = (usage.ttft_ms.unwrap(), usage.duration_ms.unwrap());
+        assert!(ttft >= 30 && duration >= ttft + 20, "ttft {} duration {}", ttft, duration);
+        assert!(usage.output_tokens_per_sec().is_some());
+
+        let message = client.chat("mock/test", &context("hi"), &RequestOptions::default()).await.unwrap();
+        let usage = message.usage.unwrap();
+        assert_eq!(usage.ttft_ms, None);
+        assert!(usage.duration_ms.unwrap() >= 30);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_cuts_off_queued_and_slow_calls() {
+        use crate::concurrency::ConcurrencyLimit;
+        use crate::providers::retry::AttemptBudget;
+        let mock = MockProvider::new().with_latency(Duration::from_secs(60));
+        let mock = (0..3).fold(mock, |m, _| m.with_reply(MockReply::text("ok")));
+        let client = AiClient::builder()
+            .with_provider("mock", Arc::new(mock))
+            .with_model("mock/test".into(), MockProvider::model("test"))
+            .with_concurrency_limits([("mock".to_string(), ConcurrencyLimit { max_requests: Some(1), ..Default::default() })].into())
+            .build();
+        let options = || RequestOptions {
+            attempt_budget: Some(AttemptBudget::new(None, Some(Duration::from_secs(5)))),
+            ..RequestOptions::default()
+        };
+
+        // Waiting for the concurrency slot counts against the deadline.
+        let busy = tokio::spawn({
+            let client = client.clone();
+            async move { client.chat("mock/test", &context("hi"), &RequestOptions::default()).await }
+        });
+        tokio::task::yield_now().await;
+        let started = tokio::time::Instant::now();
+        let err = client.chat("mock/test", &context("hi"), &options()).await.unwrap_err();
+        assert!(err.to_string().contains("deadline"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(60));
+        assert!(busy.await.unwrap().is_ok());
+
+        // So does the upstream call itself, streamed or not.
+        let err = client.chat("mock/test", &context("hi"), &options()).await.unwrap_err();
+        assert!(err.to_string().contains("deadline"), "{}", err);
+        let events: Vec<_> = client.stream("mock/test", &context("hi"), &options()).unwrap().collect().await;
+        assert!(matches!(events.as_slice(), [Err(e)] if e.to_string().contains("deadline")), "{:?}", events);
+    }
+
+    #[tokio::test]
+    async fn mid_stream_failure_follows_partial_output() {
+        let mock = Arc::new(MockProvider::new().with_reply(MockReply::FailMidStream("partial".into())));
+        let events: Vec<_> = client(mock)
+            .stream("mock/test", &context("hi"), &RequestOptions::default())
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(events[1], Ok(StreamEvent::TextDelta(ref t)) if t == "partial"));
+        assert!(matches!(events.last(), Some(Err(ProviderError::Other(_)))));
+    }
+}
@@ -1,20 +1,96 @@
+#[cfg(feature = "providers-anthropic")]
 pub mod anthropic;
+pub mod api_error;
+pub mod capture;
+#[cfg(feature = "providers-cloudflare")]
+pub mod cloudflare;
+#[cfg(feature = "providers-cohere")]
+pub mod cohere;
 pub mod compatible;
+pub mod completions;
+pub mod embeddings;
+#[cfg(feature = "providers-github-copilot")]
+pub mod github_copilot;
+#[cfg(feature = "providers-google")]
 pub mod google;
+#[cfg(feature = "providers-google")]
 pub mod google_gemini_cli;
+pub mod mock;
 pub mod openai;
+#[cfg(feature = "providers-openai-codex")]
+pub mod openai_codex;
+#[cfg(feature = "providers-qwen-portal")]
+pub mod qwen_portal;
+pub mod registry;
+pub mod rerank;
 pub mod retry;
 pub mod sanitize;
+pub mod tool_ids;
+#[cfg(feature = "providers-xai")]
+pub mod xai;
+#[cfg(feature = "providers-zhipu")]
+pub mod zhipu;
 
-use crate::types::{AssistantMessage, ChatContext, ModelDef, RequestOptions, StreamEvent};
+use crate::types::{
+    AssistantMessage, ChatContext, CompletionRequest, EmbeddingRequest, EmbeddingResponse, ModelDef, RequestOptions, RerankRequest,
+    RerankResponse, StreamEvent,
+};
 use async_trait::async_trait;
+#[cfg(not(target_arch = "wasm32"))]
 use futures::stream::BoxStream;
+#[cfg(target_arch = "wasm32")]
+use futures::stream::LocalBoxStream;
+use reqwest::header::HeaderMap;
+use std::sync::{Arc, Mutex};
+
+/// Hands the headers of the upstream response to the caller (`RequestOptions::response_headers`),
+/// for provider hints such as Copilot's quota snapshots and upstream request IDs. Filled by the
+/// OpenAI, custom OpenAI-compatible and Anthropic chat calls.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseHeaders(Arc<Mutex<Option<HeaderMap>>>);
+
+impl ResponseHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, headers: &HeaderMap) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(headers.clone());
+    }
+
+    /// Headers of the latest response, if one arrived since the last `take`.
+    pub fn take(&self) -> Option<HeaderMap> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// `body` as JSON with `options.extra_body` and then the fields of `options.provider_extra`
+/// (an object) added, the latter winning over the former. Fields `body` already has (`model`,
+/// `messages`, `stream`, ...) are never replaced. Used by the OpenAI-compatible chat requests
+/// for server-specific parameters and vendor extensions such as OpenRouter's `provider`
+/// routing preferences.
+pub(crate) fn with_extra_fields(body: &impl serde::Serialize, options: &RequestOptions) -> serde_json::Value {
+    let mut value = serde_json::to_value(body).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        let mut extra = serde_json::Map::new();
+        if let Some(body_extra) = &options.extra_body {
+            extra.extend(body_extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        if let Some(vendor) = options.provider_extra.as_object() {
+            extra.extend(vendor.clone());
+        }
+        for (name, value) in extra {
+            fields.entry(name).or_insert(value);
+        }
+    }
+    value
+}
 
 /// Errors from provider operations.
 #[derive(Debug, thiserror::Error)]
 pub enum ProviderError {
-    #[error("HTTP error {status}: {body}")]
-    Http { status: u16, body: String },
+    #[error("HTTP error {status}: {error}")]
+    Http { status: u16, error: Box<api_error::ApiError> },
 
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
@@ -25,18 +101,25 @@ pub enum ProviderError {
     #[error("Authentication required: {0}")]
     AuthRequired(String),
 
-    #[error("Rate limited, retry after {retry_after_ms:?}ms")]
+    #[error("Rate limited{}", .retry_after_ms.map(|ms| format!(", retry after {}s", ms.div_ceil(1000))).unwrap_or_default())]
     RateLimited { retry_after_ms: Option<u64> },
 
     #[error("{0}")]
     Other(String),
 }
 
+/// A provider's streamed response. Not `Send` on wasm32, where `fetch` futures aren't.
+#[cfg(not(target_arch = "wasm32"))]
+pub type EventStream = BoxStream<'static, Result<StreamEvent, ProviderError>>;
+#[cfg(target_arch = "wasm32")]
+pub type EventStream = LocalBoxStream<'static, Result<StreamEvent, ProviderError>>;
+
 /// Trait for AI provider implementations.
 ///
 /// Each provider (OpenAI, Anthropic, Google, etc.) implements this trait
 /// to handle the actual API calls.
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 pub trait Provider: Send + Sync {
     /// Stream a chat completion.
     fn stream(
@@ -44,7 +127,7 @@ pub trait Provider: Send + Sync {
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>>;
+    ) -> EventStream;
 
     /// Execute a chat completion (non-streaming).
     async fn chat(
@@ -57,4 +140,65 @@ pub trait Provider: Send + Sync {
     /// List models available from this provider.
     /// Some providers support dynamic model listing via API; others return a static list.
     async fn list_models(&self, api_key: &str) -> Result<Vec<ModelDef>, ProviderError>;
+
+    /// Count the input tokens of a request with the provider's own tokenizer.
+    /// `Ok(None)` means the provider has no counting endpoint (callers estimate locally).
+    async fn count_tokens(
+        &self,
+        _model: &ModelDef,
+        _context: &ChatContext,
+        _options: &RequestOptions,
+    ) -> Result<Option<u64>, ProviderError> {
+        Ok(None)
+    }
+
+    /// Generate embedding vectors for `request.input`, one per input in order.
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        _request: &EmbeddingRequest,
+        _options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        Err(ProviderError::Other(format!(
+            "Embeddings are not supported by provider {}",
+            model.provider
+        )))
+    }
+
+    /// Order `request.documents` by relevance to `request.query`.
+    async fn rerank(
+        &self,
+        model: &ModelDef,
+        _request: &RerankRequest,
+        _options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        Err(ProviderError::Other(format!(
+            "Rerank is not supported by provider {}",
+            model.provider
+        )))
+    }
+
+    /// Stream a text completion from the provider's native `/completions` endpoint.
+    /// `None` means the provider has none (callers complete `request.to_context()` instead).
+    fn stream_completion(
+        &self,
+        _model: &ModelDef,
+        _request: &CompletionRequest,
+        _options: &RequestOptions,
+    ) -> Option<EventStream> {
+        None
+    }
+
+    /// Open (or keep open) a pooled connection to `base_url`, so the next request skips the
+    /// TCP and TLS handshakes. Providers without an HTTP client of their own do nothing.
+    async fn warm(&self, _base_url: &str) -> Result<(), ProviderError> {
+        Ok(())
+    }
+}
+
+/// HEAD `base_url` so `client` keeps a connection to its host. Any HTTP status will do; only
+/// failing to connect is an error.
+pub(crate) async fn warm_connection(client: &reqwest::Client, base_url: &str) -> Result<(), ProviderError> {
+    client.head(base_url).timeout(std::time::Duration::from_secs(10)).send().await?;
+    Ok(())
 }
@@ -1,8 +1,13 @@
-use super::sanitize;
-use super::{Provider, ProviderError};
+use super::api_error;
+use super::capture;
+use super::completions::CompletionStyle;
+use super::embeddings::EmbeddingStyle;
+use super::rerank;
+use super::tool_ids::ToolCallIds;
+use super::{EventStream, Provider, ProviderError, with_extra_fields};
 use crate::types::*;
 use async_trait::async_trait;
-use futures::stream::{self, BoxStream};
+use futures::stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -19,6 +24,35 @@ impl OpenAiProvider {
             client: Client::new(),
         }
     }
+
+    /// JSON POST to `url` with Bearer auth, model headers and request extra headers.
+    fn json_post(
+        &self,
+        url: &str,
+        model: &ModelDef,
+        options: &RequestOptions,
+    ) -> Result<reqwest::RequestBuilder, ProviderError> {
+        let api_key = options.api_key.as_deref().ok_or_else(|| {
+            ProviderError::AuthRequired("API key required for OpenAI".into())
+        })?;
+
+        let mut headers_map = HashMap::new();
+        if let Some(model_headers) = &model.headers {
+            headers_map.extend(model_headers.clone());
+        }
+        if let Some(extra) = &options.extra_headers {
+            headers_map.extend(extra.clone());
+        }
+
+        let mut req = self.client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json");
+        for (k, v) in &headers_map {
+            req = req.header(k.as_str(), v.as_str());
+        }
+        Ok(req)
+    }
 }
 
 impl Default for OpenAiProvider {
@@ -44,6 +78,41 @@ struct ChatRequest {
     tools: Option<Vec<ToolSchema>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream_options: Option<StreamOptionsReq>,
+    /// Built-in web search (`*-search-preview` models).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web_search_options: Option<serde_json::Value>,
+    #[serde(flatten)]
+    tags: RequestTags,
+}
+
+/// End-user and storage fields of a chat completion request.
+#[derive(Serialize, Default)]
+struct RequestTags {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store: Option<bool>,
+}
+
+impl RequestTags {
+    /// `user` is accepted by OpenAI and OpenRouter; `metadata` and `store` only by OpenAI.
+    /// Other servers on this provider may reject unknown fields, so they get none.
+    fn new(model: &ModelDef, options: &RequestOptions) -> Self {
+        match model.provider.as_str() {
+            "openai" => Self {
+                user: options.user.clone(),
+                metadata: options.metadata.clone(),
+                store: options.store,
+            },
+            "openrouter" => Self {
+                user: options.user.clone(),
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -100,6 +169,9 @@ struct FunctionSchema {
 struct StreamChunk {
     choices: Option<Vec<StreamChoice>>,
     usage: Option<UsageResp>,
+    /// Source URLs (xAI Live Search), sent with the last chunk.
+    #[serde(default)]
+    citations: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -111,7 +183,10 @@ struct StreamChoice {
 #[derive(Deserialize)]
 struct DeltaContent {
     content: Option<String>,
+    refusal: Option<String>,
     tool_calls: Option<Vec<ToolCallDelta>>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
     #[allow(dead_code)]
     role: Option<String>,
 }
@@ -140,6 +215,9 @@ struct UsageResp {
 struct ChatResponse {
     choices: Vec<ChatChoice>,
     usage: Option<UsageResp>,
+    /// Source URLs (xAI Live Search).
+    #[serde(default)]
+    citations: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -153,13 +231,52 @@ struct ChatMessageResp {
     #[allow(dead_code)]
     role: String,
     content: Option<String>,
+    refusal: Option<String>,
     tool_calls: Option<Vec<ToolCallResp>>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+/// Message annotation; web search results arrive as `url_citation`s.
+#[derive(Deserialize)]
+struct Annotation {
+    url_citation: Option<UrlCitation>,
+}
+
+#[derive(Deserialize)]
+struct UrlCitation {
+    url: String,
+    title: Option<String>,
+    start_index: Option<usize>,
+    end_index: Option<usize>,
+}
+
+/// A citation for a bare source URL.
+fn url_citation(url: String) -> Citation {
+    Citation {
+        url,
+        ..Citation::default()
+    }
+}
+
+impl Annotation {
+    fn citation(&self) -> Option<Citation> {
+        let c = self.url_citation.as_ref()?;
+        Some(Citation {
+            url: c.url.clone(),
+            title: c.title.clone(),
+            start_index: c.start_index,
+            end_index: c.end_index,
+        })
+    }
 }
 
 #[derive(Deserialize)]
 struct ToolCallResp {
+    #[serde(default)]
     id: String,
     #[allow(dead_code)]
+    #[serde(default)]
     r#type: String,
     function: FunctionResp,
 }
@@ -336,7 +453,7 @@ fn parse_sse_line(line: &str) -> Option<&str> {
 }
 
 // ---------------------------------------------------------------------------
-// OpenAI Responses-style (used by OpenAI Codex OAuth via chatgpt.com backend)
+// OpenAI Responses-style (API-key Codex models; ChatGPT OAuth uses providers::openai_codex)
 // ---------------------------------------------------------------------------
 
 #[derive(Serialize)]
@@ -357,7 +474,6 @@ enum ResponsesInputContent {
 #[derive(Serialize)]
 struct ResponsesRequest {
     model: String,
-    // Codex backend requires an explicit instructions string.
     instructions: String,
     input: Vec<ResponsesInputMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -365,7 +481,6 @@ struct ResponsesRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_output_tokens: Option<u64>,
     stream: bool,
-    // ChatGPT backend codex endpoint requires store=false (see OpenClaw).
     store: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<serde_json::Value>>,
@@ -377,7 +492,7 @@ impl OpenAiProvider {
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+    ) -> EventStream {
         let api_key = match &options.api_key {
             Some(k) => k.clone(),
             None => {
@@ -389,15 +504,7 @@ impl OpenAiProvider {
             }
         };
 
-        let base_url = model.base_url.trim_end_matches('/').to_string();
-        // Two Responses endpoints exist:
-        // - OpenAI official API:   https://api.openai.com/v1/responses
-        // - Codex OAuth backend:   https://chatgpt.com/backend-api/codex/responses
-        let url = if base_url.contains("chatgpt.com") {
-            format!("{}/codex/responses", base_url)
-        } else {
-            format!("{}/responses", base_url)
-        };
+        let url = format!("{}/responses", model.base_url.trim_end_matches('/'));
 
         // Convert ChatContext → Responses input (text-only for now).
         let mut input: Vec<ResponsesInputMessage> = Vec::new();
@@ -442,7 +549,7 @@ impl OpenAiProvider {
                     });
                 }
                 Message::ToolResult(t) => {
-                    // Codex OAuth backend doesn't accept role=tool. Encode tool result as a user message.
+                    // Responses input has no role=tool. Encode tool result as a user message.
                     let text = t
                         .content
                         .iter()
@@ -461,8 +568,7 @@ impl OpenAiProvider {
             }
         }
 
-        let is_codex_oauth_backend = base_url.contains("chatgpt.com");
-
+        // Responses function tools are flat, without Chat Completions' `function` wrapper.
         let tools = if context.tools.is_empty() {
             None
         } else {
@@ -471,25 +577,12 @@ impl OpenAiProvider {
                     .tools
                     .iter()
                     .map(|t| {
-                        if is_codex_oauth_backend {
-                            // Codex OAuth backend expects a simplified tool schema.
-                            json!({
-                                "type": "function",
-                                "name": t.name,
-                                "description": t.description,
-                                "parameters": t.parameters,
-                            })
-                        } else {
-                            // Official OpenAI Responses API uses the nested function wrapper.
-                            json!({
-                                "type": "function",
-                                "function": {
-                                    "name": t.name,
-                                    "description": t.description,
-                                    "parameters": t.parameters,
-                                }
-                            })
-                        }
+                        json!({
+                            "type": "function",
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters,
+                        })
                     })
                     .collect(),
             )
@@ -504,9 +597,8 @@ impl OpenAiProvider {
             model: model.id.clone(),
             instructions,
             input,
-            // OpenAI official supports temperature/max_output_tokens; Codex OAuth backend rejects some.
-            temperature: if is_codex_oauth_backend { None } else { options.temperature },
-            max_output_tokens: if is_codex_oauth_backend { None } else { options.max_tokens.map(|v| v as u64) },
+            temperature: options.temperature,
+            max_output_tokens: options.max_tokens.map(|v| v as u64),
             stream: true,
             store: false,
             tools,
@@ -523,6 +615,8 @@ impl OpenAiProvider {
         let client = self.client.clone();
         let model_id = model.id.clone();
         let provider_id = model.provider.clone();
+        let capture = options.capture.clone();
+        let response_headers = options.response_headers.clone();
 
         Box::pin(async_stream::stream! {
             let mut req = client
@@ -535,27 +629,26 @@ impl OpenAiProvider {
                 req = req.header(k.as_str(), v.as_str());
             }
 
-            let resp = match req.json(&body).send().await {
+            let resp = match capture::send(req.json(&body), capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => {
                     yield Err(ProviderError::Network(e));
                     return;
                 }
             };
+            if let Some(h) = &response_headers {
+                h.set(resp.headers());
+            }
 
-            let status = resp.status();
-            if !status.is_success() {
-                let body_text = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http {
-                    status: status.as_u16(),
-                    body: sanitize::sanitize_api_error(&body_text),
-                });
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
                 return;
             }
 
             yield Ok(StreamEvent::Start);
 
             let mut text_buf = String::new();
+            let mut refusal_buf = String::new();
             let mut tool_calls: Vec<ToolCall> = Vec::new();
             let mut cur_tool: Option<(String, String, String)> = None; // (id, name, args_json_str)
             let mut usage = Usage::default();
@@ -603,6 +696,13 @@ impl OpenAiProvider {
                                 continue;
                             }
                         }
+                        if typ == "response.refusal.delta"
+                            && let Some(delta) = v.get("delta").and_then(|x| x.as_str())
+                        {
+                            refusal_buf.push_str(delta);
+                            yield Ok(StreamEvent::RefusalDelta(delta.to_string()));
+                            continue;
+                        }
                     }
 
                     // Fallback: some backends just send {"delta":"..."}
@@ -698,6 +798,11 @@ impl OpenAiProvider {
                                 if stop_reason == StopReason::Stop {
                                     stop_reason = StopReason::Stop;
                                 }
+                            } else if sr == "incomplete" && stop_reason != StopReason::ToolUse {
+                                stop_reason = match r.pointer("/incomplete_details/reason").and_then(|x| x.as_str()) {
+                                    Some("content_filter") => StopReason::ContentFilter,
+                                    _ => StopReason::Length,
+                                };
                             }
                         }
                     }
@@ -708,6 +813,9 @@ impl OpenAiProvider {
             if !text_buf.is_empty() {
                 content.push(ContentBlock::Text(TextContent { text: text_buf }));
             }
+            if !refusal_buf.is_empty() {
+                content.push(ContentBlock::Refusal(RefusalContent { text: refusal_buf }));
+            }
             for tc in &tool_calls {
                 content.push(ContentBlock::ToolCall(tc.clone()));
             }
@@ -718,6 +826,8 @@ impl OpenAiProvider {
                 provider: provider_id,
                 usage: Some(usage),
                 stop_reason,
+                citations: Vec::new(),
+                safety_ratings: Vec::new(),
             };
 
             yield Ok(StreamEvent::Done { message });
@@ -729,15 +839,27 @@ impl OpenAiProvider {
 // Provider impl
 // ---------------------------------------------------------------------------
 
-#[async_trait]
+/// The native completions endpoint serving `model`, if any. DeepSeek's only takes
+/// `deepseek-chat`, and Mistral's FIM endpoint only Codestral.
+fn completion_style(model: &ModelDef) -> Option<CompletionStyle> {
+    match model.provider.as_str() {
+        "vllm" | "fireworks" => Some(CompletionStyle::OpenAi),
+        "deepseek" if model.id == "deepseek-chat" => Some(CompletionStyle::DeepSeek),
+        "mistral" if model.id.starts_with("codestral") => Some(CompletionStyle::Mistral),
+        _ => None,
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl Provider for OpenAiProvider {
     fn stream(
         &self,
         model: &ModelDef,
         context: &ChatContext,
         options: &RequestOptions,
-    ) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
-        // Codex OAuth uses OpenAI "responses"-style streaming via ChatGPT backend.
+    ) -> EventStream {
+        // Models on the Responses API (e.g. API-key Codex models) use "responses"-style streaming.
         if model.api == Api::OpenaiResponses {
             return self.stream_responses(model, context, options);
         }
@@ -773,7 +895,10 @@ impl Provider for OpenAiProvider {
             stream_options: Some(StreamOptionsReq {
                 include_usage: true,
             }),
+            web_search_options: options.web_search.then(|| json!({})),
+            tags: RequestTags::new(model, options),
         };
+        let body = with_extra_fields(&body, options);
 
         let mut headers_map = HashMap::new();
         if let Some(model_headers) = &model.headers {
@@ -786,6 +911,8 @@ impl Provider for OpenAiProvider {
         let client = self.client.clone();
         let model_id = model.id.clone();
         let provider_id = model.provider.clone();
+        let capture = options.capture.clone();
+        let response_headers = options.response_headers.clone();
 
         let s = async_stream::stream! {
             let mut req = client
@@ -797,30 +924,31 @@ impl Provider for OpenAiProvider {
                 req = req.header(k.as_str(), v.as_str());
             }
 
-            let resp = match req.json(&body).send().await {
+            let resp = match capture::send(req.json(&body), capture.as_ref()).await {
                 Ok(r) => r,
                 Err(e) => {
                     yield Err(ProviderError::Network(e));
                     return;
                 }
             };
+            if let Some(h) = &response_headers {
+                h.set(resp.headers());
+            }
 
-            let status = resp.status();
-            if !status.is_success() {
-                let body_text = resp.text().await.unwrap_or_default();
-                yield Err(ProviderError::Http {
-                    status: status.as_u16(),
-                    body: sanitize::sanitize_api_error(&body_text),
-                });
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
                 return;
             }
 
             yield Ok(StreamEvent::Start);
 
             let mut text_buf = String::new();
+            let mut refusal_buf = String::new();
             let mut tool_calls: Vec<(String, String, String)> = Vec::new(); // (id, name, args)
+            let mut tool_call_ids = ToolCallIds::new();
             let mut usage = Usage::default();
             let mut stop_reason = StopReason::Stop;
+            let mut citations: Vec<Citation> = Vec::new();
             let mut line_buf = String::new();
 
             let mut byte_stream = resp.bytes_stream();
@@ -862,15 +990,17 @@ impl Provider for OpenAiProvider {
                         usage.total_tokens = u.total_tokens.unwrap_or(0);
                     }
 
+                    for citation in chunk.citations.into_iter().map(url_citation) {
+                        if !citations.contains(&citation) {
+                            citations.push(citation.clone());
+                            yield Ok(StreamEvent::CitationDelta(citation));
+                        }
+                    }
+
                     if let Some(choices) = chunk.choices {
                         for choice in choices {
                             if let Some(reason) = &choice.finish_reason {
-                                stop_reason = match reason.as_str() {
-                                    "stop" => StopReason::Stop,
-                                    "length" => StopReason::Length,
-                                    "tool_calls" => StopReason::ToolUse,
-                                    _ => StopReason::Stop,
-                                };
+                                stop_reason = StopReason::from_openai(reason);
                             }
 
                             if let Some(delta) = &choice.delta {
@@ -878,6 +1008,17 @@ impl Provider for OpenAiProvider {
                                     text_buf.push_str(content);
                                     yield Ok(StreamEvent::TextDelta(content.clone()));
                                 }
+                                if let Some(refusal) = &delta.refusal {
+                                    refusal_buf.push_str(refusal);
+                                    yield Ok(StreamEvent::RefusalDelta(refusal.clone()));
+                                }
+
+                                for citation in delta.annotations.iter().filter_map(Annotation::citation) {
+                                    if !citations.contains(&citation) {
+                                        citations.push(citation.clone());
+                                        yield Ok(StreamEvent::CitationDelta(citation));
+                                    }
+                                }
 
                                 if let Some(tc_deltas) = &delta.tool_calls {
                                     for tc_delta in tc_deltas {
@@ -887,7 +1028,10 @@ impl Provider for OpenAiProvider {
                                             tool_calls.push((String::new(), String::new(), String::new()));
                                         }
 
-                                        if let Some(id) = &tc_delta.id {
+                                        // Later chunks may repeat the ID or send it empty; keep the first one.
+                                        if let Some(id) = tc_delta.id.as_ref().filter(|id| !id.is_empty())
+                                            && tool_calls[idx].0.is_empty()
+                                        {
                                             tool_calls[idx].0 = id.clone();
                                         }
 
@@ -895,6 +1039,7 @@ impl Provider for OpenAiProvider {
                                             if let Some(name) = &func.name {
                                                 if tool_calls[idx].1.is_empty() {
                                                     tool_calls[idx].1 = name.clone();
+                                                    tool_calls[idx].0 = tool_call_ids.id(Some(&tool_calls[idx].0));
                                                     yield Ok(StreamEvent::ToolCallStart {
                                                         index: idx,
                                                         id: tool_calls[idx].0.clone(),
@@ -936,6 +1081,9 @@ impl Provider for OpenAiProvider {
             if !text_buf.is_empty() {
                 content.push(ContentBlock::Text(TextContent { text: text_buf }));
             }
+            if !refusal_buf.is_empty() {
+                content.push(ContentBlock::Refusal(RefusalContent { text: refusal_buf }));
+            }
             for (id, name, args_str) in tool_calls {
                 let arguments: serde_json::Value =
                     serde_json::from_str(&args_str).unwrap_or(json!({}));
@@ -952,6 +1100,8 @@ impl Provider for OpenAiProvider {
                 provider: provider_id,
                 usage: Some(usage),
                 stop_reason,
+                citations,
+                safety_ratings: Vec::new(),
             };
 
             yield Ok(StreamEvent::Done { message: msg });
@@ -993,7 +1143,10 @@ impl Provider for OpenAiProvider {
             stream: false,
             tools,
             stream_options: None,
+            web_search_options: options.web_search.then(|| json!({})),
+            tags: RequestTags::new(model, options),
         };
+        let body = with_extra_fields(&body, options);
 
         let mut headers_map = HashMap::new();
         if let Some(model_headers) = &model.headers {
@@ -1012,15 +1165,13 @@ impl Provider for OpenAiProvider {
             req = req.header(k.as_str(), v.as_str());
         }
 
-        let resp = req.json(&body).send().await?;
+        let resp = capture::send(req.json(&body), options.capture.as_ref()).await?;
+        if let Some(h) = &options.response_headers {
+            h.set(resp.headers());
+        }
 
-        let status = resp.status();
-        if !status.is_success() {
-            let body_text = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status: status.as_u16(),
-                body: sanitize::sanitize_api_error(&body_text),
-            });
+        if !resp.status().is_success() {
+            return Err(api_error::from_response(resp).await);
         }
 
         let chat_resp: ChatResponse = resp.json().await?;
@@ -1037,23 +1188,22 @@ impl Provider for OpenAiProvider {
             if let Some(text) = &choice.message.content {
                 content.push(ContentBlock::Text(TextContent { text: text.clone() }));
             }
+            if let Some(text) = &choice.message.refusal {
+                content.push(ContentBlock::Refusal(RefusalContent { text: text.clone() }));
+            }
             if let Some(tc_resps) = &choice.message.tool_calls {
+                let mut tool_call_ids = ToolCallIds::new();
                 for tc in tc_resps {
                     let arguments: serde_json::Value = serde_json::from_str(&tc.function.arguments).unwrap_or(json!({}));
                     content.push(ContentBlock::ToolCall(ToolCall {
-                        id: tc.id.clone(),
+                        id: tool_call_ids.id(Some(&tc.id)),
                         name: tc.function.name.clone(),
                         arguments,
                     }));
                 }
             }
 
-            let stop_reason = match choice.finish_reason.as_deref() {
-                Some("stop") => StopReason::Stop,
-                Some("length") => StopReason::Length,
-                Some("tool_calls") => StopReason::ToolUse,
-                _ => StopReason::Stop,
-            };
+            let stop_reason = choice.finish_reason.as_deref().map_or(StopReason::Stop, StopReason::from_openai);
 
             Ok(AssistantMessage {
                 content,
@@ -1061,12 +1211,58 @@ impl Provider for OpenAiProvider {
                 provider: model.provider.clone(),
                 usage: Some(usage),
                 stop_reason,
+                citations: choice
+                    .message
+                    .annotations
+                    .iter()
+                    .filter_map(Annotation::citation)
+                    .chain(chat_resp.citations.into_iter().map(url_citation))
+                    .collect(),
+                safety_ratings: Vec::new(),
             })
         } else {
             Err(ProviderError::Other("Empty response from OpenAI".into()))
         }
     }
 
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        let style = EmbeddingStyle::OpenAi;
+        let req = self.json_post(&style.url(&model.base_url), model, options)?;
+        style.send(req, model, request).await
+    }
+
+    async fn rerank(
+        &self,
+        model: &ModelDef,
+        request: &RerankRequest,
+        options: &RequestOptions,
+    ) -> Result<RerankResponse, ProviderError> {
+        let req = self.json_post(&rerank::url(&model.base_url), model, options)?;
+        rerank::send(req, model, request).await
+    }
+
+    fn stream_completion(
+        &self,
+        model: &ModelDef,
+        request: &CompletionRequest,
+        options: &RequestOptions,
+    ) -> Option<EventStream> {
+        let style = completion_style(model)?;
+        Some(match self.json_post(&style.url(&model.base_url), model, options) {
+            Ok(req) => style.stream(req, model, request, options),
+            Err(e) => Box::pin(stream::once(async { Err(e) })),
+        })
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
     async fn list_models(&self, api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
         // OpenAI supports GET /v1/models
         let url = "https://api.openai.com/v1/models";
@@ -1077,13 +1273,8 @@ impl Provider for OpenAiProvider {
             .send()
             .await?;
 
-        let status = resp.status().as_u16();
         if !resp.status().is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::Http {
-                status,
-                body: sanitize::sanitize_api_error(&body),
-            });
+            return Err(api_error::from_response(resp).await);
         }
 
         let models_resp: ModelsResponse = resp.json().await?;
@@ -1099,13 +1290,101 @@ impl Provider for OpenAiProvider {
                 base_url: "https://api.openai.com/v1".into(),
                 reasoning: false,
                 input: vec![InputModality::Text],
-                cost: ModelCost::default(),
                 context_window: 128000,
                 max_tokens: 16384,
-                headers: None,
+                capabilities: ModelCapabilities { tools: true, json_mode: true },
+                ..Default::default()
             })
             .collect();
 
         Ok(models)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Accept one request on a local port and answer it with a 400. Returns the base URL and
+    /// the request line and JSON body received.
+    async fn upstream() -> (String, tokio::task::JoinHandle<(String, serde_json::Value)>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let (mut received, mut buf) = (Vec::new(), [0u8; 8192]);
+            let (head, body) = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).into_owned();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                        .unwrap_or(0);
+                    if body.len() >= length || n == 0 {
+                        break (head.to_string(), body.to_string());
+                    }
+                }
+            };
+            let reply = r#"{"error":{"message":"captured"}}"#;
+            let reply = format!("HTTP/1.1 400 Bad Request\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", reply.len(), reply);
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            let request_line = head.lines().next().unwrap_or_default().to_string();
+            (request_line, serde_json::from_str(&body).unwrap())
+        });
+        (base_url, server)
+    }
+
+    fn context() -> ChatContext {
+        ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage { content: vec![ContentBlock::Text(TextContent { text: "hi".into() })] })],
+            tools: vec![ToolDef { name: "read".into(), description: "Read a file".into(), parameters: json!({"type": "object"}) }],
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_requests_use_flat_function_tools() {
+        let (base_url, server) = upstream().await;
+        let mut model = crate::models::default_model_def_for_provider("openai", "gpt-5-codex").unwrap();
+        model.api = Api::OpenaiResponses;
+        model.base_url = base_url;
+        let options = RequestOptions { api_key: Some("sk-test".into()), temperature: Some(0.3), ..Default::default() };
+        let _: Vec<_> = OpenAiProvider::new().stream(&model, &context(), &options).collect().await;
+
+        let (request_line, body) = server.await.unwrap();
+        assert!(request_line.starts_with("POST /v1/responses "), "{}", request_line);
+        assert_eq!(body["tools"][0], json!({"type": "function", "name": "read", "description": "Read a file", "parameters": {"type": "object"}}));
+        assert_eq!(body["temperature"], 0.3);
+    }
+
+    #[tokio::test]
+    async fn request_tags_only_reach_providers_that_accept_them() {
+        let options = RequestOptions {
+            api_key: Some("sk-test".into()),
+            user: Some("alice".into()),
+            metadata: Some([("team".to_string(), "search".to_string())].into()),
+            store: Some(true),
+            ..Default::default()
+        };
+        let tags = |provider: &str| {
+            let options = options.clone();
+            let provider = provider.to_string();
+            async move {
+                let (base_url, server) = upstream().await;
+                let mut model = crate::models::default_model_def_for_provider(&provider, "gpt-4o").unwrap();
+                model.base_url = base_url;
+                let _: Vec<_> = OpenAiProvider::new().stream(&model, &context(), &options).collect().await;
+                let (_, body) = server.await.unwrap();
+                (body["user"].clone(), body["metadata"].clone(), body["store"].clone())
+            }
+        };
+
+        assert_eq!(tags("openai").await, (json!("alice"), json!({"team": "search"}), json!(true)));
+        assert_eq!(tags("openrouter").await, (json!("alice"), json!(null), json!(null)));
+        assert_eq!(tags("groq").await, (json!(null), json!(null), json!(null)));
+    }
+}
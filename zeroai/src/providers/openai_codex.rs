@@ -0,0 +1,604 @@
+//! OpenAI Codex provider: the ChatGPT backend Responses API (`/codex/responses`) used with
+//! ChatGPT OAuth tokens.
+//!
+//! Differences from the chat-completions format: the system prompt goes into `instructions`,
+//! history is a flat list of input items (messages, `function_call`, `function_call_output`),
+//! and the stream is a sequence of typed `response.*` events including reasoning summaries.
+
+use super::api_error;
+use super::capture;
+use super::sanitize;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+const DEFAULT_INSTRUCTIONS: &str = "You are a helpful assistant.";
+const ORIGINATOR: &str = "codex_cli_rs";
+const JWT_AUTH_CLAIM: &str = "https://api.openai.com/auth";
+
+pub struct OpenAiCodexProvider {
+    client: Client,
+}
+
+impl OpenAiCodexProvider {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+        }
+    }
+}
+
+impl Default for OpenAiCodexProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Request types
+// ---------------------------------------------------------------------------
+
+#[derive(Serialize)]
+struct CodexRequest {
+    model: String,
+    instructions: String,
+    input: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    tool_choice: &'static str,
+    parallel_tool_calls: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<Value>,
+    // The ChatGPT backend rejects stored responses and non-streaming calls.
+    store: bool,
+    stream: bool,
+    include: Vec<&'static str>,
+}
+
+// ---------------------------------------------------------------------------
+// Conversion helpers
+// ---------------------------------------------------------------------------
+
+fn text_of(blocks: &[ContentBlock]) -> String {
+    blocks
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text(t) => Some(t.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Convert the chat history into Responses input items.
+fn convert_input(context: &ChatContext) -> Vec<Value> {
+    let mut input = Vec::new();
+    for msg in &context.messages {
+        match msg {
+            Message::User(u) => {
+                let content: Vec<Value> = u
+                    .content
+                    .iter()
+                    .filter_map(|b| match b {
+                        ContentBlock::Text(t) => Some(json!({"type": "input_text", "text": t.text})),
+                        ContentBlock::Image(img) => Some(json!({
+                            "type": "input_image",
+                            "image_url": format!("data:{};base64,{}", img.mime_type, img.data),
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+                if !content.is_empty() {
+                    input.push(json!({"type": "message", "role": "user", "content": content}));
+                }
+            }
+            Message::Assistant(a) => {
+                let text = text_of(&a.content);
+                if !text.is_empty() {
+                    input.push(json!({
+                        "type": "message",
+                        "role": "assistant",
+                        "content": [{"type": "output_text", "text": text}],
+                    }));
+                }
+                for b in &a.content {
+                    if let ContentBlock::ToolCall(tc) = b {
+                        input.push(json!({
+                            "type": "function_call",
+                            "call_id": tc.id,
+                            "name": tc.name,
+                            "arguments": tc.arguments.to_string(),
+                        }));
+                    }
+                }
+            }
+            Message::ToolResult(t) => {
+                input.push(json!({
+                    "type": "function_call_output",
+                    "call_id": t.tool_call_id,
+                    "output": text_of(&t.content),
+                }));
+            }
+        }
+    }
+    input
+}
+
+fn convert_tools(tools: &[ToolDef]) -> Vec<Value> {
+    tools
+        .iter()
+        .map(|t| {
+            json!({
+                "type": "function",
+                "name": t.name,
+                "description": t.description,
+                "parameters": t.parameters,
+                "strict": false,
+            })
+        })
+        .collect()
+}
+
+fn reasoning_effort(level: &ThinkingLevel) -> &'static str {
+    match level {
+        ThinkingLevel::Minimal => "minimal",
+        ThinkingLevel::Low => "low",
+        ThinkingLevel::Medium => "medium",
+        ThinkingLevel::High => "high",
+    }
+}
+
+/// Extract the ChatGPT account id from the OAuth access token (a JWT).
+fn account_id_from_token(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: Value = serde_json::from_slice(&bytes).ok()?;
+    claims
+        .get(JWT_AUTH_CLAIM)?
+        .get("chatgpt_account_id")?
+        .as_str()
+        .map(String::from)
+}
+
+// ---------------------------------------------------------------------------
+// Stream translation
+// ---------------------------------------------------------------------------
+
+fn is_function_call(event: &Value) -> bool {
+    event.pointer("/item/type").and_then(|x| x.as_str()) == Some("function_call")
+}
+
+/// A function call being streamed; keyed by output item id.
+struct PendingCall {
+    index: usize,
+    call_id: String,
+    name: String,
+    args: String,
+}
+
+/// Translates Responses stream events into `StreamEvent`s and accumulates the final message.
+#[derive(Default)]
+struct CodexStreamState {
+    text: String,
+    refusal: String,
+    thinking: String,
+    calls: HashMap<String, PendingCall>,
+    tool_calls: Vec<ToolCall>,
+    next_index: usize,
+    usage: Usage,
+    incomplete: bool,
+}
+
+impl CodexStreamState {
+    /// Handle one event; `Err` carries the backend's failure message.
+    fn handle(&mut self, v: &Value) -> Result<Vec<StreamEvent>, String> {
+        let typ = v.get("type").and_then(|x| x.as_str()).unwrap_or_default();
+        let mut out = Vec::new();
+        match typ {
+            "response.output_text.delta" => {
+                if let Some(d) = v.get("delta").and_then(|x| x.as_str()) {
+                    self.text.push_str(d);
+                    out.push(StreamEvent::TextDelta(d.to_string()));
+                }
+            }
+            "response.refusal.delta" => {
+                if let Some(d) = v.get("delta").and_then(|x| x.as_str()) {
+                    self.refusal.push_str(d);
+                    out.push(StreamEvent::RefusalDelta(d.to_string()));
+                }
+            }
+            "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+                if let Some(d) = v.get("delta").and_then(|x| x.as_str()) {
+                    self.thinking.push_str(d);
+                    out.push(StreamEvent::ThinkingDelta(d.to_string()));
+                }
+            }
+            // Separate consecutive summary parts.
+            "response.reasoning_summary_part.added" if !self.thinking.is_empty() => {
+                self.thinking.push_str("\n\n");
+                out.push(StreamEvent::ThinkingDelta("\n\n".into()));
+            }
+            "response.output_item.added" if is_function_call(v) => {
+                let item = &v["item"];
+                let item_id = item.get("id").and_then(|x| x.as_str()).unwrap_or_default();
+                let call_id = item
+                    .get("call_id")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or(item_id)
+                    .to_string();
+                let name = item.get("name").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+                let index = self.next_index;
+                self.next_index += 1;
+                out.push(StreamEvent::ToolCallStart {
+                    index,
+                    id: call_id.clone(),
+                    name: name.clone(),
+                });
+                self.calls.insert(
+                    item_id.to_string(),
+                    PendingCall { index, call_id, name, args: String::new() },
+                );
+            }
+            "response.function_call_arguments.delta" => {
+                let item_id = v.get("item_id").and_then(|x| x.as_str()).unwrap_or_default();
+                if let (Some(call), Some(d)) =
+                    (self.calls.get_mut(item_id), v.get("delta").and_then(|x| x.as_str()))
+                {
+                    call.args.push_str(d);
+                    out.push(StreamEvent::ToolCallDelta { index: call.index, delta: d.to_string() });
+                }
+            }
+            "response.output_item.done" if is_function_call(v) => {
+                let item = &v["item"];
+                let item_id = item.get("id").and_then(|x| x.as_str()).unwrap_or_default();
+                if let Some(call) = self.calls.remove(item_id) {
+                    // The done event carries the complete arguments; prefer them over the deltas.
+                    let args = item
+                        .get("arguments")
+                        .and_then(|x| x.as_str())
+                        .map(String::from)
+                        .unwrap_or(call.args);
+                    let arguments = serde_json::from_str(&args).unwrap_or_else(|_| json!({}));
+                    let tool_call = ToolCall { id: call.call_id, name: call.name, arguments };
+                    self.tool_calls.push(tool_call.clone());
+                    out.push(StreamEvent::ToolCallEnd { index: call.index, tool_call });
+                }
+            }
+            "response.completed" | "response.incomplete" => {
+                if let Some(r) = v.get("response") {
+                    self.read_usage(r);
+                    self.incomplete = r.get("status").and_then(|x| x.as_str()) == Some("incomplete");
+                }
+                if typ == "response.incomplete" {
+                    self.incomplete = true;
+                }
+            }
+            "response.failed" => {
+                let msg = v
+                    .pointer("/response/error/message")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or("Codex response failed");
+                return Err(msg.to_string());
+            }
+            "error" => {
+                let msg = v
+                    .get("message")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or("Codex stream error");
+                return Err(msg.to_string());
+            }
+            _ => {}
+        }
+        Ok(out)
+    }
+
+    fn read_usage(&mut self, r: &Value) {
+        let Some(u) = r.get("usage") else { return };
+        let get = |p: &str| u.pointer(p).and_then(|x| x.as_u64()).unwrap_or(0);
+        self.usage.input_tokens = get("/input_tokens");
+        self.usage.output_tokens = get("/output_tokens");
+        self.usage.cache_read_tokens = get("/input_tokens_details/cached_tokens");
+        self.usage.total_tokens = get("/total_tokens");
+    }
+
+    fn finish(self, model: &str, provider: &str) -> AssistantMessage {
+        let mut content = Vec::new();
+        if !self.thinking.is_empty() {
+            content.push(ContentBlock::Thinking(ThinkingContent {
+                thinking: self.thinking,
+                signature: None,
+            }));
+        }
+        if !self.text.is_empty() {
+            content.push(ContentBlock::Text(TextContent { text: self.text }));
+        }
+        if !self.refusal.is_empty() {
+            content.push(ContentBlock::Refusal(RefusalContent { text: self.refusal }));
+        }
+        let stop_reason = if !self.tool_calls.is_empty() {
+            StopReason::ToolUse
+        } else if self.incomplete {
+            StopReason::Length
+        } else {
+            StopReason::Stop
+        };
+        content.extend(self.tool_calls.into_iter().map(ContentBlock::ToolCall));
+        AssistantMessage {
+            content,
+            model: model.to_string(),
+            provider: provider.to_string(),
+            usage: Some(self.usage),
+            stop_reason,
+            citations: Vec::new(),
+            safety_ratings: Vec::new(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Provider impl
+// ---------------------------------------------------------------------------
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for OpenAiCodexProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> EventStream {
+        let client = self.client.clone();
+        let model = model.clone();
+        let api_key = options.api_key.clone();
+        let capture = options.capture.clone();
+
+        let reasoning = match (&options.reasoning, model.reasoning) {
+            (Some(level), true) => Some(json!({"effort": reasoning_effort(level), "summary": "auto"})),
+            (None, true) => Some(json!({"summary": "auto"})),
+            _ => None,
+        };
+        let body = CodexRequest {
+            model: model.id.clone(),
+            instructions: context
+                .system_prompt
+                .clone()
+                .unwrap_or_else(|| DEFAULT_INSTRUCTIONS.into()),
+            input: convert_input(context),
+            tools: convert_tools(&context.tools),
+            tool_choice: "auto",
+            parallel_tool_calls: true,
+            include: if reasoning.is_some() { vec!["reasoning.encrypted_content"] } else { vec![] },
+            reasoning,
+            store: false,
+            stream: true,
+        };
+
+        let mut headers_map = HashMap::new();
+        if let Some(model_headers) = &model.headers {
+            headers_map.extend(model_headers.clone());
+        }
+        if let Some(extra) = &options.extra_headers {
+            headers_map.extend(extra.clone());
+        }
+
+        Box::pin(async_stream::stream! {
+            let Some(api_key) = api_key else {
+                yield Err(ProviderError::AuthRequired("ChatGPT OAuth token required for OpenAI Codex".into()));
+                return;
+            };
+
+            let url = format!("{}/codex/responses", model.base_url.trim_end_matches('/'));
+            let mut req = client
+                .post(&url)
+                .bearer_auth(&api_key)
+                .header("Content-Type", "application/json")
+                .header("Accept", "text/event-stream")
+                .header("OpenAI-Beta", "responses=experimental")
+                .header("originator", ORIGINATOR)
+                .header("session_id", uuid::Uuid::new_v4().to_string());
+            if let Some(account_id) = account_id_from_token(&api_key) {
+                req = req.header("chatgpt-account-id", account_id);
+            }
+            for (k, v) in &headers_map {
+                req = req.header(k.as_str(), v.as_str());
+            }
+
+            let resp = match capture::send(req.json(&body), capture.as_ref()).await {
+                Ok(r) => r,
+                Err(e) => {
+                    yield Err(ProviderError::Network(e));
+                    return;
+                }
+            };
+
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
+                return;
+            }
+
+            yield Ok(StreamEvent::Start);
+
+            let mut state = CodexStreamState::default();
+            let mut line_buf = String::new();
+            let mut byte_stream = resp.bytes_stream();
+
+            while let Some(chunk) = byte_stream.next().await {
+                let chunk = match chunk {
+                    Ok(b) => b,
+                    Err(e) => {
+                        yield Err(ProviderError::Network(e));
+                        return;
+                    }
+                };
+                line_buf.push_str(&String::from_utf8_lossy(&chunk));
+
+                while let Some(pos) = line_buf.find('\n') {
+                    let line: String = line_buf.drain(..=pos).collect();
+                    let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                        continue;
+                    };
+                    if data.is_empty() || data == "[DONE]" {
+                        continue;
+                    }
+                    let Ok(v) = serde_json::from_str::<Value>(data) else {
+                        continue;
+                    };
+                    match state.handle(&v) {
+                        Ok(events) => {
+                            for ev in events {
+                                yield Ok(ev);
+                            }
+                        }
+                        Err(msg) => {
+                            yield Err(ProviderError::Other(sanitize::sanitize_api_error(&msg)));
+                            return;
+                        }
+                    }
+                }
+            }
+
+            yield Ok(StreamEvent::Done { message: state.finish(&model.id, &model.provider) });
+        })
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        // The backend only supports streaming; collect the final message.
+        let mut stream = self.stream(model, context, options);
+        while let Some(event) = stream.next().await {
+            if let StreamEvent::Done { message } = event? {
+                return Ok(message);
+            }
+        }
+        Err(ProviderError::Other("Codex stream ended without a response".into()))
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("openai-codex"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(state: &mut CodexStreamState, events: &[Value]) -> Vec<StreamEvent> {
+        events.iter().flat_map(|e| state.handle(e).unwrap()).collect()
+    }
+
+    #[test]
+    fn translates_text_reasoning_and_usage() {
+        let mut state = CodexStreamState::default();
+        let events = run(
+            &mut state,
+            &[
+                json!({"type": "response.output_item.added", "item": {"type": "reasoning", "id": "rs_1"}}),
+                json!({"type": "response.reasoning_summary_part.added", "item_id": "rs_1"}),
+                json!({"type": "response.reasoning_summary_text.delta", "item_id": "rs_1", "delta": "Thinking"}),
+                json!({"type": "response.output_text.delta", "delta": "Hello"}),
+                json!({"type": "response.output_text.delta", "delta": " world"}),
+                json!({"type": "response.completed", "response": {"status": "completed", "usage": {
+                    "input_tokens": 10, "output_tokens": 5, "total_tokens": 15,
+                    "input_tokens_details": {"cached_tokens": 4}
+                }}}),
+            ],
+        );
+        assert!(matches!(&events[0], StreamEvent::ThinkingDelta(d) if d == "Thinking"));
+        assert_eq!(events.len(), 3);
+
+        let msg = state.finish("gpt-5.2-codex", "openai-codex");
+        assert_eq!(msg.stop_reason, StopReason::Stop);
+        assert!(matches!(&msg.content[0], ContentBlock::Thinking(t) if t.thinking == "Thinking"));
+        assert!(matches!(&msg.content[1], ContentBlock::Text(t) if t.text == "Hello world"));
+        let usage = msg.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens, usage.cache_read_tokens), (10, 5, 4));
+    }
+
+    #[test]
+    fn translates_function_calls() {
+        let mut state = CodexStreamState::default();
+        let events = run(
+            &mut state,
+            &[
+                json!({"type": "response.output_item.added", "item": {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "get_weather", "arguments": ""}}),
+                json!({"type": "response.function_call_arguments.delta", "item_id": "fc_1", "delta": "{\"city\":"}),
+                json!({"type": "response.function_call_arguments.delta", "item_id": "fc_1", "delta": "\"Paris\"}"}),
+                json!({"type": "response.output_item.done", "item": {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}}),
+            ],
+        );
+        assert!(matches!(&events[0], StreamEvent::ToolCallStart { index: 0, id, .. } if id == "call_1"));
+        assert!(matches!(&events[1], StreamEvent::ToolCallDelta { index: 0, .. }));
+        let StreamEvent::ToolCallEnd { tool_call, .. } = &events[3] else { panic!("expected ToolCallEnd") };
+        assert_eq!(tool_call.arguments["city"], "Paris");
+
+        let msg = state.finish("m", "openai-codex");
+        assert_eq!(msg.stop_reason, StopReason::ToolUse);
+    }
+
+    #[test]
+    fn failed_response_is_an_error() {
+        let mut state = CodexStreamState::default();
+        let err = state
+            .handle(&json!({"type": "response.failed", "response": {"error": {"message": "usage limit reached"}}}))
+            .unwrap_err();
+        assert_eq!(err, "usage limit reached");
+    }
+
+    #[test]
+    fn converts_tool_history_to_input_items() {
+        let context = ChatContext {
+            system_prompt: Some("sys".into()),
+            messages: vec![
+                Message::Assistant(AssistantMessage {
+                    content: vec![ContentBlock::ToolCall(ToolCall {
+                        id: "call_1".into(),
+                        name: "ls".into(),
+                        arguments: json!({"path": "."}),
+                    })],
+                    model: "m".into(),
+                    provider: "openai-codex".into(),
+                    usage: None,
+                    stop_reason: StopReason::ToolUse,
+                    citations: Vec::new(),
+                    safety_ratings: Vec::new(),
+                }),
+                Message::ToolResult(ToolResultMessage {
+                    tool_call_id: "call_1".into(),
+                    tool_name: "ls".into(),
+                    content: vec![ContentBlock::Text(TextContent { text: "a.txt".into() })],
+                    is_error: false,
+                }),
+            ],
+            tools: vec![],
+        };
+        let input = convert_input(&context);
+        assert_eq!(input[0]["type"], "function_call");
+        assert_eq!(input[0]["arguments"], "{\"path\":\".\"}");
+        assert_eq!(input[1]["type"], "function_call_output");
+        assert_eq!(input[1]["call_id"], "call_1");
+        assert_eq!(input[1]["output"], "a.txt");
+    }
+
+    #[test]
+    fn extracts_account_id_from_jwt() {
+        let claims = json!({JWT_AUTH_CLAIM: {"chatgpt_account_id": "acct_123"}});
+        let token = format!("h.{}.s", URL_SAFE_NO_PAD.encode(claims.to_string()));
+        assert_eq!(account_id_from_token(&token).as_deref(), Some("acct_123"));
+        assert!(account_id_from_token("not-a-jwt").is_none());
+    }
+}
@@ -0,0 +1,269 @@
+//! Qwen Portal provider (portal.qwen.ai, Qwen OAuth accounts).
+//!
+//! The portal speaks OpenAI chat-completions but with a few quirks handled here:
+//! it expects the DashScope OAuth headers next to the bearer token, tool-call deltas may
+//! arrive without ids, `finish_reason` is often `stop` (or absent) when the model called
+//! tools, and usage is frequently missing from the stream.
+
+use super::openai::OpenAiProvider;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const USER_AGENT: &str = "QwenCode/0.0.14 (linux; x64)";
+
+pub struct QwenPortalProvider {
+    inner: Arc<OpenAiProvider>,
+}
+
+impl QwenPortalProvider {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(OpenAiProvider::new()),
+        }
+    }
+}
+
+impl Default for QwenPortalProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accept tokens pasted with their scheme (`Bearer xyz`) as well as bare access tokens.
+fn normalize_token(token: &str) -> String {
+    let t = token.trim();
+    t.strip_prefix("Bearer ")
+        .or_else(|| t.strip_prefix("bearer "))
+        .unwrap_or(t)
+        .trim()
+        .to_string()
+}
+
+fn portal_headers() -> HashMap<String, String> {
+    let mut h = HashMap::new();
+    h.insert("User-Agent".into(), USER_AGENT.into());
+    h.insert("X-DashScope-UserAgent".into(), USER_AGENT.into());
+    h.insert("X-DashScope-AuthType".into(), "qwen-oauth".into());
+    h.insert("X-DashScope-CacheControl".into(), "enable".into());
+    h
+}
+
+fn prepare(model: &ModelDef, options: &RequestOptions) -> Result<(ModelDef, RequestOptions), ProviderError> {
+    let token = options
+        .api_key
+        .as_deref()
+        .map(normalize_token)
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| ProviderError::AuthRequired("Qwen Portal OAuth token required".into()))?;
+
+    let mut model = model.clone();
+    let mut headers = portal_headers();
+    if let Some(h) = &model.headers {
+        headers.extend(h.clone());
+    }
+    model.headers = Some(headers);
+
+    let mut options = options.clone();
+    options.api_key = Some(token);
+    Ok((model, options))
+}
+
+// ---------------------------------------------------------------------------
+// Stream normalization
+// ---------------------------------------------------------------------------
+
+/// Rewrites events from the generic OpenAI stream to paper over portal quirks.
+#[derive(Default)]
+struct QwenStreamFixer {
+    /// Tool-call ids by stream index (synthesized when the portal omits them).
+    ids: HashMap<usize, String>,
+}
+
+impl QwenStreamFixer {
+    fn id_for(&mut self, index: usize, id: &str) -> String {
+        self.ids
+            .entry(index)
+            .or_insert_with(|| {
+                if id.is_empty() {
+                    format!("call_{}", uuid::Uuid::new_v4().simple())
+                } else {
+                    id.to_string()
+                }
+            })
+            .clone()
+    }
+
+    fn fix(&mut self, event: StreamEvent) -> StreamEvent {
+        match event {
+            StreamEvent::ToolCallStart { index, id, name } => StreamEvent::ToolCallStart {
+                id: self.id_for(index, &id),
+                index,
+                name,
+            },
+            StreamEvent::ToolCallEnd { index, mut tool_call } => {
+                tool_call.id = self.id_for(index, &tool_call.id);
+                StreamEvent::ToolCallEnd { index, tool_call }
+            }
+            StreamEvent::Done { message } => StreamEvent::Done {
+                message: self.fix_message(message),
+            },
+            other => other,
+        }
+    }
+
+    fn fix_message(&mut self, mut message: AssistantMessage) -> AssistantMessage {
+        let mut index = 0;
+        for block in &mut message.content {
+            if let ContentBlock::ToolCall(tc) = block {
+                tc.id = self.id_for(index, &tc.id);
+                index += 1;
+            }
+        }
+        // The portal reports "stop" (or nothing) after tool calls.
+        if index > 0 && message.stop_reason == StopReason::Stop {
+            message.stop_reason = StopReason::ToolUse;
+        }
+        // Usage is often absent from the stream; report None rather than zeros.
+        message.usage = message.usage.and_then(|mut u| {
+            if u.input_tokens == 0 && u.output_tokens == 0 && u.total_tokens == 0 {
+                return None;
+            }
+            if u.total_tokens == 0 {
+                u.total_tokens = u.input_tokens + u.output_tokens;
+            }
+            Some(u)
+        });
+        message
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Provider impl
+// ---------------------------------------------------------------------------
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for QwenPortalProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> EventStream {
+        let (model, options) = match prepare(model, options) {
+            Ok(v) => v,
+            Err(e) => return Box::pin(futures::stream::once(async { Err(e) })),
+        };
+        let mut fixer = QwenStreamFixer::default();
+        Box::pin(
+            self.inner
+                .stream(&model, context, &options)
+                .map(move |item| item.map(|ev| fixer.fix(ev))),
+        )
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        // Go through the stream so the same normalization applies.
+        let mut stream = self.stream(model, context, options);
+        while let Some(event) = stream.next().await {
+            if let StreamEvent::Done { message } = event? {
+                return Ok(message);
+            }
+        }
+        Err(ProviderError::Other("Qwen Portal stream ended without a response".into()))
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        self.inner.warm(base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("qwen-portal"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool_call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.into(),
+            name: "read_file".into(),
+            arguments: json!({}),
+        }
+    }
+
+    #[test]
+    fn strips_bearer_prefix() {
+        assert_eq!(normalize_token("Bearer abc "), "abc");
+        assert_eq!(normalize_token("abc"), "abc");
+    }
+
+    #[test]
+    fn synthesizes_missing_tool_call_ids_consistently() {
+        let mut fixer = QwenStreamFixer::default();
+        let StreamEvent::ToolCallStart { id: start_id, .. } = fixer.fix(StreamEvent::ToolCallStart {
+            index: 0,
+            id: String::new(),
+            name: "read_file".into(),
+        }) else {
+            panic!("expected ToolCallStart")
+        };
+        assert!(start_id.starts_with("call_"));
+
+        let StreamEvent::ToolCallEnd { tool_call, .. } = fixer.fix(StreamEvent::ToolCallEnd {
+            index: 0,
+            tool_call: tool_call(""),
+        }) else {
+            panic!("expected ToolCallEnd")
+        };
+        assert_eq!(tool_call.id, start_id);
+    }
+
+    #[test]
+    fn fixes_stop_reason_and_missing_usage() {
+        let mut fixer = QwenStreamFixer::default();
+        let msg = fixer.fix_message(AssistantMessage {
+            content: vec![ContentBlock::ToolCall(tool_call("call_1"))],
+            model: "coder-model".into(),
+            provider: "qwen-portal".into(),
+            usage: Some(Usage::default()),
+            stop_reason: StopReason::Stop,
+            citations: Vec::new(),
+            safety_ratings: Vec::new(),
+        });
+        assert_eq!(msg.stop_reason, StopReason::ToolUse);
+        assert!(msg.usage.is_none());
+        assert!(matches!(&msg.content[0], ContentBlock::ToolCall(tc) if tc.id == "call_1"));
+    }
+
+    #[test]
+    fn fills_in_total_tokens() {
+        let mut fixer = QwenStreamFixer::default();
+        let msg = fixer.fix_message(AssistantMessage {
+            content: vec![],
+            model: "coder-model".into(),
+            provider: "qwen-portal".into(),
+            usage: Some(Usage {
+                input_tokens: 7,
+                output_tokens: 3,
+                ..Usage::default()
+            }),
+            stop_reason: StopReason::Stop,
+            citations: Vec::new(),
+            safety_ratings: Vec::new(),
+        });
+        assert_eq!(msg.usage.unwrap().total_tokens, 10);
+    }
+}
@@ -0,0 +1,377 @@
+//! Provider registry: maps provider IDs to `Provider` implementations.
+//!
+//! `ProviderRegistry::builtin()` holds the providers shipped with zeroai. Downstream code can
+//! register its own `Provider` implementations, or declare new OpenAI-compatible providers
+//! with a [`ProviderDef`] (e.g. from config.json) without recompiling.
+
+#[cfg(feature = "providers-anthropic")]
+use super::anthropic::AnthropicProvider;
+#[cfg(feature = "providers-cloudflare")]
+use super::cloudflare::CloudflareGatewayProvider;
+#[cfg(feature = "providers-cohere")]
+use super::cohere::CohereProvider;
+use super::compatible::{AuthStyle, OpenAiCompatibleProvider};
+use super::embeddings::EmbeddingStyle;
+#[cfg(feature = "providers-github-copilot")]
+use super::github_copilot::GitHubCopilotProvider;
+#[cfg(feature = "providers-google")]
+use super::google::GoogleProvider;
+#[cfg(feature = "providers-google")]
+use super::google_gemini_cli::GoogleGeminiCliProvider;
+use super::openai::OpenAiProvider;
+#[cfg(feature = "providers-openai-codex")]
+use super::openai_codex::OpenAiCodexProvider;
+#[cfg(feature = "providers-qwen-portal")]
+use super::qwen_portal::QwenPortalProvider;
+#[cfg(feature = "providers-xai")]
+use super::xai::XaiProvider;
+#[cfg(feature = "providers-zhipu")]
+use super::zhipu::ZhipuProvider;
+use super::{Provider, ProviderError};
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Providers served by the generic OpenAI chat-completions implementation.
+const OPENAI_BACKED: &[&str] = &[
+    "openai",
+    "deepseek",
+    "mistral",
+    #[cfg(not(feature = "providers-xai"))]
+    "xai",
+    "groq",
+    "together",
+    "siliconflow",
+    "fireworks",
+    "nebius",
+    "openrouter",
+    "minimax",
+    "moonshot",
+    "qwen",
+    "qianfan",
+    "ollama",
+    "vllm",
+    "huggingface",
+    "amazon-bedrock",
+    "xiaomi",
+    "jina",
+];
+
+/// Providers served by the Anthropic Messages implementation.
+#[cfg(feature = "providers-anthropic")]
+const ANTHROPIC_BACKED: &[&str] = &[
+    "anthropic",
+    "anthropic-setup-token",
+    "anthropic-oauth",
+    "synthetic",
+];
+
+// ---------------------------------------------------------------------------
+// Declarative provider definitions
+// ---------------------------------------------------------------------------
+
+/// A model entry in a [`ProviderDef`]. Only `id` is required.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderModelDef {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub reasoning: bool,
+    #[serde(default = "default_input")]
+    pub input: Vec<InputModality>,
+    #[serde(default)]
+    pub cost: ModelCost,
+    #[serde(default = "default_context_window")]
+    pub context_window: u64,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u64,
+}
+
+fn default_input() -> Vec<InputModality> {
+    vec![InputModality::Text]
+}
+
+fn default_context_window() -> u64 {
+    128000
+}
+
+fn default_max_tokens() -> u64 {
+    16384
+}
+
+/// Declarative definition of an OpenAI-compatible provider.
+///
+/// ```json
+/// {
+///   "id": "my-llm",
+///   "base_url": "https://llm.example.com/v1",
+///   "auth": { "type": "x-api-key" },
+///   "api_key_env": "MY_LLM_KEY",
+///   "headers": { "X-Team": "infra" },
+///   "models": [{ "id": "my-model-large", "context_window": 32768 }]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderDef {
+    /// Provider ID used as the model prefix (`<id>/<model>`).
+    pub id: String,
+    /// Base URL; requests go to `{base_url}/chat/completions`.
+    pub base_url: String,
+    /// How the API key is sent. Defaults to `Authorization: Bearer`.
+    #[serde(default)]
+    pub auth: AuthStyle,
+    /// Environment variable holding the API key, used when no account is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Extra headers sent with every request.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Custom URL for listing models. Blank = `{base_url}/models`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models_url: Option<String>,
+    /// Static model list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<ProviderModelDef>,
+    /// Wire format of `POST {base_url}/embeddings` (`openai`, `voyage` or `cohere`).
+    #[serde(default, skip_serializing_if = "is_default_embedding_style")]
+    pub embeddings: EmbeddingStyle,
+    /// The server also has the legacy `POST {base_url}/completions` (prompt and `suffix`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub completions: bool,
+}
+
+fn is_default_embedding_style(style: &EmbeddingStyle) -> bool {
+    *style == EmbeddingStyle::default()
+}
+
+impl ProviderDef {
+    /// API key from `api_key_env`, if set and non-empty.
+    pub fn env_api_key(&self) -> Option<String> {
+        let var = self.api_key_env.as_deref()?;
+        std::env::var(var).ok().filter(|v| !v.trim().is_empty())
+    }
+
+    /// Build the provider implementation for this definition.
+    pub fn to_provider(&self) -> OpenAiCompatibleProvider {
+        let api_key = self.env_api_key();
+        let mut p = OpenAiCompatibleProvider::new(
+            &self.id,
+            &self.base_url,
+            api_key.as_deref(),
+            self.auth.clone(),
+        );
+        if let Some(url) = self.models_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            p = p.with_models_url(url);
+        }
+        p.with_embedding_style(self.embeddings).with_completions(self.completions)
+    }
+
+    /// Convert the static model list into `ModelDef`s.
+    pub fn model_defs(&self) -> Vec<ModelDef> {
+        self.models.iter().map(|m| self.build_model_def(m)).collect()
+    }
+
+    /// `ModelDef` for `model_id`: the static entry if listed, otherwise defaults.
+    pub fn model_def(&self, model_id: &str) -> ModelDef {
+        match self.models.iter().find(|m| m.id == model_id) {
+            Some(m) => self.build_model_def(m),
+            None => self.build_model_def(&ProviderModelDef {
+                id: model_id.to_string(),
+                name: None,
+                reasoning: false,
+                input: default_input(),
+                cost: ModelCost::default(),
+                context_window: default_context_window(),
+                max_tokens: default_max_tokens(),
+            }),
+        }
+    }
+
+    /// The static model list if one is configured, otherwise the provider's live model list
+    /// with the limits and prices it reports.
+    pub async fn fetch_models(&self, api_key: Option<&str>) -> Result<Vec<ModelDef>, ProviderError> {
+        if !self.models.is_empty() {
+            return Ok(self.model_defs());
+        }
+        let key = api_key.map(String::from).or_else(|| self.env_api_key()).unwrap_or_default();
+        let listed = self.to_provider().list_models(&key).await?;
+        Ok(listed
+            .into_iter()
+            .map(|m| {
+                let def = self.model_def(&m.id);
+                ModelDef { base_url: def.base_url, headers: def.headers, ..m }
+            })
+            .collect())
+    }
+
+    fn build_model_def(&self, m: &ProviderModelDef) -> ModelDef {
+        ModelDef {
+            id: m.id.clone(),
+            name: m.name.clone().unwrap_or_else(|| m.id.clone()),
+            api: Api::OpenaiCompletions,
+            provider: self.id.clone(),
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            reasoning: m.reasoning,
+            input: m.input.clone(),
+            cost: m.cost.clone(),
+            context_window: m.context_window,
+            max_tokens: m.max_tokens,
+            headers: if self.headers.is_empty() {
+                None
+            } else {
+                Some(self.headers.clone())
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Maps provider IDs to provider implementations.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<dyn Provider>>,
+}
+
+impl ProviderRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry pre-populated with the built-in providers compiled in (see the crate's
+    /// `providers-*` features).
+    pub fn builtin() -> Self {
+        let mut reg = Self::new();
+
+        let openai: Arc<dyn Provider> = Arc::new(OpenAiProvider::new());
+        for id in OPENAI_BACKED {
+            reg.register(id, openai.clone());
+        }
+
+        #[cfg(feature = "providers-anthropic")]
+        {
+            let anthropic: Arc<dyn Provider> = Arc::new(AnthropicProvider::new());
+            for id in ANTHROPIC_BACKED {
+                reg.register(id, anthropic.clone());
+            }
+        }
+
+        #[cfg(feature = "providers-cloudflare")]
+        reg.register("cloudflare-ai-gateway", Arc::new(CloudflareGatewayProvider::new()));
+        #[cfg(feature = "providers-github-copilot")]
+        reg.register("github-copilot", Arc::new(GitHubCopilotProvider::new()));
+        #[cfg(feature = "providers-openai-codex")]
+        reg.register("openai-codex", Arc::new(OpenAiCodexProvider::new()));
+        #[cfg(feature = "providers-qwen-portal")]
+        reg.register("qwen-portal", Arc::new(QwenPortalProvider::new()));
+        #[cfg(feature = "providers-xai")]
+        reg.register("xai", Arc::new(XaiProvider::new()));
+        #[cfg(feature = "providers-zhipu")]
+        reg.register("zhipuai", Arc::new(ZhipuProvider::new()));
+        #[cfg(feature = "providers-google")]
+        {
+            reg.register("google", Arc::new(GoogleProvider::new()));
+            reg.register("gemini-cli", Arc::new(GoogleGeminiCliProvider::new_gemini_cli()));
+            reg.register("antigravity", Arc::new(GoogleGeminiCliProvider::new_antigravity()));
+        }
+        #[cfg(feature = "providers-cohere")]
+        reg.register("cohere", Arc::new(CohereProvider::new()));
+        reg
+    }
+
+    /// Register (or replace) the provider for `id`.
+    pub fn register(&mut self, id: &str, provider: Arc<dyn Provider>) -> &mut Self {
+        self.providers.insert(id.to_string(), provider);
+        self
+    }
+
+    /// Register an OpenAI-compatible provider from a declarative definition.
+    pub fn register_def(&mut self, def: &ProviderDef) -> &mut Self {
+        self.register(&def.id, Arc::new(def.to_provider()))
+    }
+
+    /// Merge all providers from `other`, replacing entries with the same ID.
+    pub fn extend(&mut self, other: ProviderRegistry) -> &mut Self {
+        self.providers.extend(other.providers);
+        self
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<dyn Provider>> {
+        self.providers.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.providers.contains_key(id)
+    }
+
+    /// Registered provider IDs (unordered).
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.providers.keys().map(String::as_str)
+    }
+
+    pub(crate) fn into_map(self) -> HashMap<String, Arc<dyn Provider>> {
+        self.providers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "all-providers")]
+    fn builtin_has_core_providers() {
+        let reg = ProviderRegistry::builtin();
+        for id in ["openai", "anthropic", "google", "gemini-cli", "antigravity", "openai-codex"] {
+            assert!(reg.contains(id), "missing {}", id);
+        }
+    }
+
+    #[test]
+    fn provider_def_from_json_uses_defaults() {
+        let def: ProviderDef = serde_json::from_str(
+            r#"{
+                "id": "my-llm",
+                "base_url": "https://llm.example.com/v1/",
+                "auth": { "type": "x-api-key" },
+                "headers": { "X-Team": "infra" },
+                "models": [{ "id": "big", "reasoning": true }]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(def.auth, AuthStyle::XApiKey));
+
+        let models = def.model_defs();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "big");
+        assert_eq!(models[0].provider, "my-llm");
+        assert_eq!(models[0].base_url, "https://llm.example.com/v1");
+        assert_eq!(models[0].context_window, 128000);
+        assert!(models[0].reasoning);
+        assert_eq!(
+            models[0].headers.as_ref().unwrap().get("X-Team").map(String::as_str),
+            Some("infra")
+        );
+
+        let unlisted = def.model_def("small");
+        assert_eq!(unlisted.id, "small");
+        assert!(!unlisted.reasoning);
+        assert!(unlisted.headers.is_some());
+    }
+
+    #[test]
+    fn register_def_overrides_builtin() {
+        let mut reg = ProviderRegistry::builtin();
+        let def: ProviderDef =
+            serde_json::from_str(r#"{ "id": "openai", "base_url": "http://localhost:1234/v1" }"#).unwrap();
+        let before = reg.get("openai").unwrap().clone();
+        reg.register_def(&def);
+        assert!(!Arc::ptr_eq(&before, reg.get("openai").unwrap()));
+    }
+}
@@ -0,0 +1,99 @@
+//! Rerank wire format shared by Cohere (`/v2/rerank`), Jina (`/v1/rerank`) and
+//! OpenAI-compatible servers that expose the same shape (vLLM, Infinity, SiliconFlow...).
+
+use super::api_error;
+use super::ProviderError;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct RerankBody<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_n: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct RerankReply {
+    results: Vec<RerankResult>,
+    /// Jina and vLLM report tokens here; Cohere bills search units in `meta` instead.
+    #[serde(default)]
+    usage: Option<RerankUsage>,
+}
+
+#[derive(Deserialize)]
+struct RerankUsage {
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+pub(crate) fn url(base_url: &str) -> String {
+    format!("{}/rerank", base_url.trim_end_matches('/'))
+}
+
+fn parse_reply(body: serde_json::Value) -> Result<(Vec<RerankResult>, Option<Usage>), ProviderError> {
+    let mut reply: RerankReply = serde_json::from_value(body)?;
+    reply
+        .results
+        .sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+    let usage = reply.usage.map(|u| Usage {
+        input_tokens: u.total_tokens,
+        total_tokens: u.total_tokens,
+        ..Usage::default()
+    });
+    Ok((reply.results, usage))
+}
+
+/// Send a rerank request (already carrying auth and headers) and parse the reply.
+pub(crate) async fn send(
+    req: reqwest::RequestBuilder,
+    model: &ModelDef,
+    request: &RerankRequest,
+) -> Result<RerankResponse, ProviderError> {
+    let body = RerankBody {
+        model: &model.id,
+        query: &request.query,
+        documents: &request.documents,
+        top_n: request.top_n,
+    };
+    let resp = req.json(&body).send().await?;
+    if !resp.status().is_success() {
+        return Err(api_error::from_response(resp).await);
+    }
+    let (results, usage) = parse_reply(resp.json().await?)?;
+    Ok(RerankResponse {
+        model: model.id.clone(),
+        provider: model.provider.clone(),
+        results,
+        usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_cohere_and_jina_replies_most_relevant_first() {
+        let (results, usage) = parse_reply(json!({
+            "id": "abc",
+            "results": [{"index": 0, "relevance_score": 0.1}, {"index": 2, "relevance_score": 0.9}],
+            "meta": {"billed_units": {"search_units": 1}}
+        }))
+        .unwrap();
+        assert_eq!(results.iter().map(|r| r.index).collect::<Vec<_>>(), vec![2, 0]);
+        assert!(usage.is_none());
+
+        let (results, usage) = parse_reply(json!({
+            "model": "jina-reranker-v2-base-multilingual",
+            "results": [{"index": 1, "relevance_score": 0.5, "document": {"text": "b"}}],
+            "usage": {"total_tokens": 12}
+        }))
+        .unwrap();
+        assert_eq!(results[0].index, 1);
+        assert_eq!(usage.unwrap().total_tokens, 12);
+    }
+}
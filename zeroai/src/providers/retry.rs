@@ -1,21 +1,31 @@
 //! Retry logic for provider calls: exponential backoff, non-retryable 4xx detection,
 //! rate-limit (429) and Retry-After handling. Design reference: zeroclaw providers/reliable.rs
 
-use super::{Provider, ProviderError};
-use crate::types::{ChatContext, ModelDef, RequestOptions, RetryConfig, StreamEvent};
-use futures::stream::{BoxStream, StreamExt};
+use super::{EventStream, Provider, ProviderError};
+use crate::types::{ChatContext, ModelDef, RequestOptions, RetryConfig};
+use futures::StreamExt;
+use reqwest::header::HeaderMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::rt::{self, Instant};
 use std::time::Duration;
 
-/// True if the error is a client error (4xx) that should not be retried (excluding 429 and 408).
+/// Longest Retry-After worth waiting for in-process.
+const MAX_RETRY_AFTER_MS: u64 = 30_000;
+
+/// True if the error is a client error (4xx) that should not be retried (excluding 429 and 408),
+/// or a rate limit that lifts later than [`MAX_RETRY_AFTER_MS`] (e.g. a subscription window),
+/// which callers with several accounts should rotate away from instead.
+/// An explicit `x-should-retry` from the provider overrides the status.
 pub fn is_non_retryable(err: &ProviderError) -> bool {
     match err {
+        ProviderError::Http { error, .. } if error.should_retry.is_some() => error.should_retry == Some(false),
         ProviderError::Http { status, .. } => {
             let code = *status;
             (400..500).contains(&code) && code != 429 && code != 408
         }
         ProviderError::AuthRequired(_) => true,
-        ProviderError::RateLimited { .. } => false,
+        ProviderError::RateLimited { retry_after_ms } => retry_after_ms.is_some_and(|ms| ms > MAX_RETRY_AFTER_MS),
         _ => {
             let msg = err.to_string();
             for word in msg.split(|c: char| !c.is_ascii_digit()) {
@@ -30,8 +40,24 @@ pub fn is_non_retryable(err: &ProviderError) -> bool {
     }
 }
 
-/// True if the error indicates rate limiting (429).
+/// True if the provider as a whole is overloaded (Anthropic 529, or an error typed or described
+/// as overloaded), as opposed to the account running out of quota.
+pub fn is_overloaded(err: &ProviderError) -> bool {
+    match err {
+        ProviderError::Http { status: 529, .. } => true,
+        ProviderError::Http { status, error } => {
+            error.kind.as_deref().is_some_and(|k| k.contains("overloaded"))
+                || (*status == 503 && error.message.to_lowercase().contains("overloaded"))
+        }
+        _ => false,
+    }
+}
+
+/// True if the error indicates rate limiting (429). Overloads are not rate limits.
 pub fn is_rate_limited(err: &ProviderError) -> bool {
+    if is_overloaded(err) {
+        return false;
+    }
     match err {
         ProviderError::Http { status, .. } => *status == 429,
         ProviderError::RateLimited { .. } => true,
@@ -43,13 +69,13 @@ pub fn is_rate_limited(err: &ProviderError) -> bool {
     }
 }
 
-/// Extract Retry-After delay in milliseconds from error (body/message or RateLimited variant).
+/// Extract Retry-After delay in milliseconds from error (RateLimited variant, response headers,
+/// or body/message).
 pub fn parse_retry_after_ms(err: &ProviderError) -> Option<u64> {
-    if let ProviderError::RateLimited {
-        retry_after_ms: Some(ms),
-    } = err
-    {
-        return Some(*ms);
+    match err {
+        ProviderError::RateLimited { retry_after_ms: Some(ms) } => return Some(*ms),
+        ProviderError::Http { error, .. } if error.retry_after_ms.is_some() => return error.retry_after_ms,
+        _ => {}
     }
     let msg = err.to_string();
     let lower = msg.to_lowercase();
@@ -79,42 +105,191 @@ pub fn parse_retry_after_ms(err: &ProviderError) -> Option<u64> {
     None
 }
 
+/// Delay in ms a response asks for before retrying: `retry-after-ms` (Anthropic, OpenAI), else
+/// `retry-after` in seconds or as an HTTP date.
+pub fn retry_after_from_headers(headers: &HeaderMap, now_ms: i64) -> Option<u64> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Some(ms.max(0.0) as u64);
+    }
+    let value = header("retry-after")?;
+    if let Ok(secs) = value.parse::<f64>() {
+        return Some((secs.max(0.0) * 1000.0) as u64);
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(at.timestamp_millis().saturating_sub(now_ms).max(0) as u64)
+}
+
+/// The provider's explicit verdict on retrying (`x-should-retry: true|false`), if it sent one.
+pub fn should_retry_from_headers(headers: &HeaderMap) -> Option<bool> {
+    match headers.get("x-should-retry")?.to_str().ok()?.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
 /// Next backoff in ms: Retry-After if present (capped at 30s), else base; base is doubled for next call.
 pub fn compute_backoff(config: &RetryConfig, base_ms: u64, err: &ProviderError) -> u64 {
     let base = base_ms.max(config.base_backoff_ms.min(1));
     if let Some(retry_after) = parse_retry_after_ms(err) {
-        retry_after.min(30_000).max(base)
+        retry_after.min(MAX_RETRY_AFTER_MS).max(base)
     } else {
         base
     }
 }
 
-/// Stream that retries on retryable errors (429/408, network) with exponential backoff.
+/// Upstream attempts and time left for one client request, shared by all of its retries and
+/// account rotations (`RequestOptions::attempt_budget`). Clones share the same budget. Each
+/// upstream call, including its wait for a concurrency slot, is cut off at the deadline.
+#[derive(Debug, Clone)]
+pub struct AttemptBudget(Arc<BudgetState>);
+
+#[derive(Debug)]
+struct BudgetState {
+    attempts_left: Option<AtomicU32>,
+    deadline: Option<Instant>,
+}
+
+impl AttemptBudget {
+    pub fn new(max_attempts: Option<u32>, deadline: Option<Duration>) -> Self {
+        Self(Arc::new(BudgetState {
+            attempts_left: max_attempts.map(AtomicU32::new),
+            deadline: deadline.map(|d| Instant::now() + d),
+        }))
+    }
+
+    /// A fresh budget for one request, if `config` sets `max_attempts` or `deadline_ms`.
+    pub fn from_config(config: &RetryConfig) -> Option<Self> {
+        (config.max_attempts.is_some() || config.deadline_ms.is_some())
+            .then(|| Self::new(config.max_attempts, config.deadline_ms.map(Duration::from_millis)))
+    }
+
+    /// Take one attempt; false when none are left or the deadline has passed.
+    pub fn try_acquire(&self) -> bool {
+        if !self.allows(0) {
+            return false;
+        }
+        self.0
+            .attempts_left
+            .as_ref()
+            .is_none_or(|left| left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok())
+    }
+
+    /// Whether another attempt could start after waiting `wait_ms`.
+    pub fn allows(&self, wait_ms: u64) -> bool {
+        self.0.attempts_left.as_ref().is_none_or(|left| left.load(Ordering::SeqCst) > 0)
+            && self.0.deadline.is_none_or(|d| Instant::now() + Duration::from_millis(wait_ms) < d)
+    }
+
+    /// True once no further attempt may start.
+    pub fn is_spent(&self) -> bool {
+        !self.allows(0)
+    }
+
+    /// Time until the deadline, if there is one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0.deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// Run one upstream call, failing it when the deadline passes first.
+    pub async fn within<T>(
+        budget: Option<&Self>,
+        call: impl Future<Output = Result<T, ProviderError>>,
+    ) -> Result<T, ProviderError> {
+        match budget.and_then(Self::remaining) {
+            Some(remaining) => rt::timeout(remaining, call)
+                .await
+                .unwrap_or_else(|| Err(ProviderError::Other("Request deadline exceeded".into()))),
+            None => call.await,
+        }
+    }
+
+    /// Take one attempt, or the error for a request that has used up its budget.
+    pub fn acquire(budget: Option<&Self>) -> Result<(), ProviderError> {
+        match budget {
+            Some(b) if !b.try_acquire() => Err(ProviderError::Other("Request attempt budget exhausted".into())),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Retries left for one call and the backoff before the next. Overloads draw on their own,
+/// shorter schedule so a busy provider doesn't eat the budget for other failures. Retries also
+/// stop when the request's [`AttemptBudget`] can't cover another attempt.
+pub struct RetryState<'a> {
+    config: &'a RetryConfig,
+    budget: Option<AttemptBudget>,
+    attempts: u32,
+    backoff_ms: u64,
+    overload_attempts: u32,
+    overload_backoff_ms: u64,
+}
+
+impl<'a> RetryState<'a> {
+    pub fn new(config: &'a RetryConfig, budget: Option<AttemptBudget>) -> Self {
+        Self {
+            config,
+            budget,
+            attempts: 0,
+            backoff_ms: config.base_backoff_ms,
+            overload_attempts: 0,
+            overload_backoff_ms: config.overload_backoff_ms,
+        }
+    }
+
+    /// Milliseconds to wait before retrying after `err`, or `None` to give up.
+    pub fn next_delay(&mut self, err: &ProviderError) -> Option<u64> {
+        if is_non_retryable(err) {
+            return None;
+        }
+        let wait = if is_overloaded(err) {
+            if self.overload_attempts >= self.config.overload_retries {
+                return None;
+            }
+            self.overload_attempts += 1;
+            let wait = parse_retry_after_ms(err).map_or(self.overload_backoff_ms, |ms| ms.min(MAX_RETRY_AFTER_MS));
+            self.overload_backoff_ms = (self.overload_backoff_ms.saturating_mul(2)).min(10_000);
+            wait
+        } else {
+            if self.attempts >= self.config.max_retries {
+                return None;
+            }
+            self.attempts += 1;
+            let wait = compute_backoff(self.config, self.backoff_ms, err);
+            self.backoff_ms = (self.backoff_ms.saturating_mul(2)).min(10_000);
+            wait
+        };
+        self.budget.as_ref().is_none_or(|b| b.allows(wait)).then_some(wait)
+    }
+}
+
+/// Stream that retries on retryable errors (429/408, network, overload) with exponential backoff.
 pub fn retry_stream(
     provider: Arc<dyn Provider>,
     model_def: ModelDef,
     context: ChatContext,
     options: RequestOptions,
     config: RetryConfig,
-) -> BoxStream<'static, Result<StreamEvent, ProviderError>> {
+) -> EventStream {
     let stream = async_stream::stream! {
-        let mut attempt = 0u32;
-        let mut backoff_ms = config.base_backoff_ms;
+        let mut retry = RetryState::new(&config, options.attempt_budget.clone());
         loop {
+            if let Err(e) = AttemptBudget::acquire(options.attempt_budget.as_ref()) {
+                yield Err(e);
+                return;
+            }
             let mut inner = provider.stream(&model_def, &context, &options);
             loop {
                 match inner.next().await {
                     None => return,
                     Some(Ok(evt)) => yield Ok(evt),
                     Some(Err(e)) => {
-                        if is_non_retryable(&e) || attempt >= config.max_retries {
+                        let Some(wait) = retry.next_delay(&e) else {
                             yield Err(e);
                             return;
-                        }
-                        let wait = compute_backoff(&config, backoff_ms, &e);
-                        tokio::time::sleep(Duration::from_millis(wait)).await;
-                        backoff_ms = (backoff_ms.saturating_mul(2)).min(10_000);
-                        attempt += 1;
+                        };
+                        rt::sleep(Duration::from_millis(wait)).await;
                         break;
                     }
                 }
@@ -127,15 +302,104 @@ pub fn retry_stream(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::api_error::ApiError;
     use crate::types::RetryConfig;
 
     fn http_err(status: u16) -> ProviderError {
         ProviderError::Http {
             status,
-            body: String::new(),
+            error: Box::default(),
         }
     }
 
+    #[test]
+    fn retry_hints_come_from_headers() {
+        let now_ms = 1_445_412_450_000; // Wed, 21 Oct 2015 07:27:30 GMT
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                map.insert(*name, value.parse().unwrap());
+            }
+            map
+        };
+        assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "7")]), now_ms), Some(7000));
+        assert_eq!(
+            retry_after_from_headers(&headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]), now_ms),
+            Some(30_000)
+        );
+        assert_eq!(
+            retry_after_from_headers(&headers(&[("retry-after", "7"), ("retry-after-ms", "1500")]), now_ms),
+            Some(1500)
+        );
+        assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "soon")]), now_ms), None);
+
+        assert_eq!(should_retry_from_headers(&headers(&[("x-should-retry", "false")])), Some(false));
+        assert_eq!(should_retry_from_headers(&HeaderMap::new()), None);
+
+        // The provider's verdict beats the status code.
+        let verdict = |status, should_retry| ProviderError::Http {
+            status,
+            error: Box::new(ApiError { should_retry: Some(should_retry), ..ApiError::default() }),
+        };
+        assert!(is_non_retryable(&verdict(500, false)));
+        assert!(!is_non_retryable(&verdict(409, true)));
+        let hinted = ProviderError::Http {
+            status: 503,
+            error: Box::new(ApiError { retry_after_ms: Some(2000), ..ApiError::default() }),
+        };
+        assert_eq!(parse_retry_after_ms(&hinted), Some(2000));
+    }
+
+    #[test]
+    fn overloads_retry_on_their_own_short_schedule() {
+        let anthropic = ProviderError::Http {
+            status: 529,
+            error: Box::new(ApiError::parse(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#)),
+        };
+        let gemini = ProviderError::Http {
+            status: 503,
+            error: Box::new(ApiError::parse(
+                r#"{"error": {"code": 503, "message": "The model is overloaded. Please try again later.", "status": "UNAVAILABLE"}}"#,
+            )),
+        };
+        assert!(is_overloaded(&anthropic) && is_overloaded(&gemini));
+        assert!(!is_rate_limited(&anthropic));
+        assert!(!is_overloaded(&http_err(503)));
+
+        let config = RetryConfig { max_retries: 1, base_backoff_ms: 1000, overload_retries: 2, overload_backoff_ms: 100, ..RetryConfig::default() };
+        let mut retry = RetryState::new(&config, None);
+        assert_eq!(retry.next_delay(&anthropic), Some(100));
+        assert_eq!(retry.next_delay(&gemini), Some(200));
+        assert_eq!(retry.next_delay(&anthropic), None);
+        // Other failures still have their own budget.
+        assert_eq!(retry.next_delay(&http_err(500)), Some(1000));
+        assert_eq!(retry.next_delay(&http_err(500)), None);
+
+        let older: RetryConfig = serde_json::from_str(r#"{"max_retries": 5, "base_backoff_ms": 10}"#).unwrap();
+        assert_eq!((older.max_retries, older.overload_retries), (5, 2));
+    }
+
+    #[test]
+    fn attempt_budget_bounds_every_retry() {
+        let budget = AttemptBudget::new(Some(2), None);
+        let config = RetryConfig { max_retries: 5, base_backoff_ms: 10, ..RetryConfig::default() };
+        let mut retry = RetryState::new(&config, Some(budget.clone()));
+        assert!(budget.clone().try_acquire());
+        assert_eq!(retry.next_delay(&http_err(500)), Some(10));
+        assert!(budget.try_acquire());
+        assert_eq!(retry.next_delay(&http_err(500)), None);
+        assert!(budget.is_spent() && AttemptBudget::acquire(Some(&budget)).is_err());
+
+        // A wait that would run past the deadline isn't started.
+        let budget = AttemptBudget::new(None, Some(Duration::from_millis(500)));
+        let mut retry = RetryState::new(&config, Some(budget.clone()));
+        assert_eq!(retry.next_delay(&http_err(500)), Some(10));
+        assert_eq!(retry.next_delay(&ProviderError::RateLimited { retry_after_ms: Some(5_000) }), None);
+        assert!(!budget.is_spent());
+
+        assert!(AttemptBudget::from_config(&RetryConfig::default()).is_none());
+    }
+
     #[test]
     fn is_non_retryable_4xx_except_429_408() {
         assert!(is_non_retryable(&http_err(400)));
@@ -146,6 +410,15 @@ mod tests {
         assert!(!is_non_retryable(&http_err(408)));
     }
 
+    #[test]
+    fn long_rate_limit_is_not_retried() {
+        assert!(!is_non_retryable(&ProviderError::RateLimited { retry_after_ms: Some(5_000) }));
+        assert!(!is_non_retryable(&ProviderError::RateLimited { retry_after_ms: None }));
+        assert!(is_non_retryable(&ProviderError::RateLimited {
+            retry_after_ms: Some(5 * 60 * 60 * 1000)
+        }));
+    }
+
     #[test]
     fn is_non_retryable_5xx_and_other() {
         assert!(!is_non_retryable(&http_err(500)));
@@ -189,7 +462,7 @@ mod tests {
     fn parse_retry_after_ms_integer() {
         let err = ProviderError::Http {
             status: 429,
-            body: "429 Too Many Requests, Retry-After: 5".into(),
+            error: Box::new(ApiError::parse("429 Too Many Requests, Retry-After: 5")),
         };
         assert_eq!(parse_retry_after_ms(&err), Some(5000));
     }
@@ -219,7 +492,7 @@ mod tests {
         let config = RetryConfig::default();
         let err = ProviderError::Http {
             status: 429,
-            body: "429 Retry-After: 3".into(),
+            error: Box::new(ApiError::parse("429 Retry-After: 3")),
         };
         assert_eq!(compute_backoff(&config, 500, &err), 3000);
     }
@@ -229,7 +502,7 @@ mod tests {
         let config = RetryConfig::default();
         let err = ProviderError::Http {
             status: 429,
-            body: "429 Retry-After: 120".into(),
+            error: Box::new(ApiError::parse("429 Retry-After: 120")),
         };
         assert_eq!(compute_backoff(&config, 500, &err), 30_000);
     }
@@ -72,7 +72,7 @@ pub fn sanitize_api_error(input: &str) -> String {
 pub fn api_error_body(status: u16, body: &str) -> super::ProviderError {
     super::ProviderError::Http {
         status,
-        body: sanitize_api_error(body),
+        error: Box::new(super::api_error::ApiError::parse(body)),
     }
 }
 
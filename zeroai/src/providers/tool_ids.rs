@@ -0,0 +1,69 @@
+//! Tool-call IDs that hold up in agent loops across providers.
+//!
+//! A `ToolResult` is matched to its call by ID, so every `ToolCall` needs one that is unique
+//! and comes back unchanged. Providers' own IDs are kept as they are. Calls that arrive without
+//! one (Gemini, some OpenAI-compatible servers) get `call_<request>_<n>` from a [`ToolCallIds`]
+//! made for that request, so IDs don't repeat across requests, choices or restarts.
+
+/// Hands out IDs for the tool calls of one request.
+#[derive(Debug)]
+pub struct ToolCallIds {
+    request: String,
+    next: usize,
+}
+
+impl ToolCallIds {
+    pub fn new() -> Self {
+        Self {
+            request: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            next: 0,
+        }
+    }
+
+    /// The provider's ID when it sent a non-empty one, else a fresh one.
+    pub fn id(&mut self, original: Option<&str>) -> String {
+        if let Some(id) = original.filter(|id| !id.is_empty()) {
+            return id.to_string();
+        }
+        let id = format!("call_{}_{}", self.request, self.next);
+        self.next += 1;
+        id
+    }
+}
+
+impl Default for ToolCallIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An ID limited to `[A-Za-z0-9_-]`, the only characters Anthropic accepts in `tool_use` IDs.
+/// Other characters (e.g. Kimi's `functions.get_weather:0`) become `_`; the mapping is the
+/// same for a call and its result, so the pair still matches.
+pub fn sanitize(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_provider_ids_and_fills_missing_ones() {
+        let mut ids = ToolCallIds::new();
+        assert_eq!(ids.id(Some("toolu_01")), "toolu_01");
+        let a = ids.id(None);
+        let b = ids.id(Some(""));
+        assert!(a.starts_with("call_"));
+        assert_ne!(a, b);
+        assert_ne!(a, ToolCallIds::new().id(None));
+    }
+
+    #[test]
+    fn sanitizes_for_anthropic() {
+        assert_eq!(sanitize("functions.get_weather:0"), "functions_get_weather_0");
+        assert_eq!(sanitize("call_abc-1"), "call_abc-1");
+    }
+}
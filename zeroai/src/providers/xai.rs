@@ -0,0 +1,168 @@
+//! xAI Grok (api.x.ai): OpenAI chat completions plus Grok's own request fields.
+//!
+//! `RequestOptions::web_search` turns on Live Search (`search_parameters`) instead of OpenAI's
+//! `web_search_options`, and `RequestOptions::reasoning` is sent as `reasoning_effort` to the
+//! models that take it. The sources Live Search used come back in the response's `citations`
+//! and are reported as `Citation`s. Values the client already set in `extra_body` or
+//! `provider_extra` are kept.
+
+use super::openai::OpenAiProvider;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Whether `model` takes `reasoning_effort`. Grok 3 Mini does; Grok 4 always reasons and
+/// rejects the field.
+fn takes_reasoning_effort(model: &ModelDef) -> bool {
+    model.id.starts_with("grok-3-mini")
+}
+
+/// The options for the OpenAI-compatible request, with Grok's fields in `provider_extra`.
+fn prepare(model: &ModelDef, options: &RequestOptions) -> RequestOptions {
+    let client_set = |key: &str| {
+        options.extra_body.as_ref().is_some_and(|b| b.contains_key(key)) || options.provider_extra.get(key).is_some()
+    };
+    let mut fields = serde_json::Map::new();
+    if options.web_search && !client_set("search_parameters") {
+        fields.insert("search_parameters".into(), json!({ "mode": "on", "return_citations": true }));
+    }
+    if let Some(level) = &options.reasoning
+        && takes_reasoning_effort(model)
+        && !client_set("reasoning_effort")
+    {
+        let effort = match level {
+            ThinkingLevel::Minimal | ThinkingLevel::Low => "low",
+            ThinkingLevel::Medium | ThinkingLevel::High => "high",
+        };
+        fields.insert("reasoning_effort".into(), json!(effort));
+    }
+
+    let mut options = options.clone();
+    options.web_search = false;
+    if !fields.is_empty() {
+        let mut extra = options.provider_extra.as_object().cloned().unwrap_or_default();
+        extra.extend(fields);
+        options.provider_extra = serde_json::Value::Object(extra);
+    }
+    options
+}
+
+pub struct XaiProvider {
+    openai: Arc<OpenAiProvider>,
+}
+
+impl XaiProvider {
+    pub fn new() -> Self {
+        Self {
+            openai: Arc::new(OpenAiProvider::new()),
+        }
+    }
+}
+
+impl Default for XaiProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for XaiProvider {
+    fn stream(&self, model: &ModelDef, context: &ChatContext, options: &RequestOptions) -> EventStream {
+        self.openai.stream(model, context, &prepare(model, options))
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        self.openai.chat(model, context, &prepare(model, options)).await
+    }
+
+    async fn embed(
+        &self,
+        model: &ModelDef,
+        request: &EmbeddingRequest,
+        options: &RequestOptions,
+    ) -> Result<EmbeddingResponse, ProviderError> {
+        self.openai.embed(model, request, options).await
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        self.openai.warm(base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("xai"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(id: &str) -> ModelDef {
+        crate::models::default_model_def_for_provider("xai", id).unwrap()
+    }
+
+    #[test]
+    fn grok_fields_go_in_provider_extra() {
+        let options = RequestOptions {
+            web_search: true,
+            reasoning: Some(ThinkingLevel::Medium),
+            ..Default::default()
+        };
+        let mini = prepare(&model("grok-3-mini"), &options);
+        assert!(!mini.web_search);
+        assert_eq!(
+            mini.provider_extra,
+            json!({ "search_parameters": { "mode": "on", "return_citations": true }, "reasoning_effort": "high" })
+        );
+        assert_eq!(prepare(&model("grok-4"), &options).provider_extra.get("reasoning_effort"), None);
+
+        let mut own = options.clone();
+        own.extra_body = Some([("search_parameters".to_string(), json!({ "mode": "auto" }))].into());
+        assert_eq!(prepare(&model("grok-3-mini"), &own).provider_extra, json!({ "reasoning_effort": "high" }));
+    }
+
+    #[tokio::test]
+    async fn live_search_sources_become_citations() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let n = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"choices":[{"message":{"role":"assistant","content":"Sunny."},"finish_reason":"stop"}],
+                "citations":["https://weather.example/sf","https://news.example/today"]}"#;
+            let reply = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let mut grok = model("grok-3");
+        grok.base_url = format!("http://{}/v1", addr);
+        let options = RequestOptions {
+            api_key: Some("xai-key".into()),
+            web_search: true,
+            ..Default::default()
+        };
+        let context = ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage { content: vec![ContentBlock::Text(TextContent { text: "Weather in SF?".into() })] })],
+            tools: Vec::new(),
+        };
+        let message = XaiProvider::new().chat(&grok, &context, &options).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""search_parameters":{"mode":"on","return_citations":true}"#), "{}", request);
+        assert!(!request.contains("web_search_options"));
+        let urls: Vec<&str> = message.citations.iter().map(|c| c.url.as_str()).collect();
+        assert_eq!(urls, ["https://weather.example/sf", "https://news.example/today"]);
+    }
+}
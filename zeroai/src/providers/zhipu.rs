@@ -0,0 +1,542 @@
+//! ZhipuAI (BigModel) GLM provider.
+//!
+//! GLM's chat-completions API is OpenAI-shaped but adds a `thinking` request field,
+//! streams reasoning as `delta.reasoning_content`, streams tool-call arguments only with
+//! `tool_stream` (GLM-4.6+, otherwise whole calls arrive in one chunk, sometimes without
+//! an `index`), and reports built-in web search results in a top-level `web_search` array.
+
+use super::api_error;
+use super::capture;
+use super::{EventStream, Provider, ProviderError};
+use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+pub struct ZhipuProvider {
+    client: Client,
+}
+
+impl ZhipuProvider {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+        }
+    }
+}
+
+impl Default for ZhipuProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Request building
+// ---------------------------------------------------------------------------
+
+fn text_of(blocks: &[ContentBlock]) -> String {
+    blocks
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text(t) => Some(t.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn convert_messages(context: &ChatContext) -> Vec<Value> {
+    let mut msgs = Vec::new();
+    if let Some(sys) = &context.system_prompt {
+        msgs.push(json!({"role": "system", "content": sys}));
+    }
+    for msg in &context.messages {
+        match msg {
+            Message::User(u) => {
+                let has_image = u.content.iter().any(|b| matches!(b, ContentBlock::Image(_)));
+                let content = if has_image {
+                    let parts: Vec<Value> = u
+                        .content
+                        .iter()
+                        .filter_map(|b| match b {
+                            ContentBlock::Text(t) => Some(json!({"type": "text", "text": t.text})),
+                            ContentBlock::Image(img) => Some(json!({
+                                "type": "image_url",
+                                "image_url": {"url": format!("data:{};base64,{}", img.mime_type, img.data)}
+                            })),
+                            _ => None,
+                        })
+                        .collect();
+                    json!(parts)
+                } else {
+                    json!(text_of(&u.content))
+                };
+                msgs.push(json!({"role": "user", "content": content}));
+            }
+            Message::Assistant(a) => {
+                let tool_calls: Vec<Value> = a
+                    .content
+                    .iter()
+                    .filter_map(|b| match b {
+                        ContentBlock::ToolCall(tc) => Some(json!({
+                            "id": tc.id,
+                            "type": "function",
+                            "function": {"name": tc.name, "arguments": tc.arguments.to_string()}
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+                let mut m = json!({"role": "assistant", "content": text_of(&a.content)});
+                if !tool_calls.is_empty() {
+                    m["tool_calls"] = json!(tool_calls);
+                }
+                msgs.push(m);
+            }
+            Message::ToolResult(tr) => {
+                msgs.push(json!({
+                    "role": "tool",
+                    "tool_call_id": tr.tool_call_id,
+                    "content": text_of(&tr.content),
+                }));
+            }
+        }
+    }
+    msgs
+}
+
+fn build_body(model: &ModelDef, context: &ChatContext, options: &RequestOptions) -> Value {
+    let mut body = json!({
+        "model": model.id,
+        "messages": convert_messages(context),
+        "stream": true,
+    });
+    if let Some(t) = options.temperature {
+        body["temperature"] = json!(t);
+    }
+    if let Some(m) = options.max_tokens {
+        body["max_tokens"] = json!(m);
+    }
+    let mut tools: Vec<Value> = context
+        .tools
+        .iter()
+        .map(|t| {
+            json!({
+                "type": "function",
+                "function": {"name": t.name, "description": t.description, "parameters": t.parameters}
+            })
+        })
+        .collect();
+    if !tools.is_empty() {
+        body["tool_stream"] = json!(true);
+    }
+    if options.web_search {
+        tools.push(json!({"type": "web_search", "web_search": {"enable": true, "search_result": true}}));
+    }
+    if !tools.is_empty() {
+        body["tools"] = json!(tools);
+    }
+    if model.reasoning && options.reasoning.is_some() {
+        body["thinking"] = json!({"type": "enabled"});
+    }
+    body
+}
+
+// ---------------------------------------------------------------------------
+// Stream translation
+// ---------------------------------------------------------------------------
+
+#[derive(Default)]
+struct ZhipuStreamState {
+    text: String,
+    thinking: String,
+    /// (id, name, args) by tool-call index.
+    tool_calls: Vec<(String, String, String)>,
+    /// Web search references, in the order received.
+    search_results: Vec<(String, String)>,
+    usage: Option<Usage>,
+    stop_reason: StopReason,
+}
+
+impl ZhipuStreamState {
+    fn handle(&mut self, chunk: &Value) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+
+        if let Some(results) = chunk.get("web_search").and_then(|x| x.as_array()) {
+            for r in results {
+                let title = r.get("title").and_then(|x| x.as_str()).unwrap_or_default();
+                let link = r.get("link").and_then(|x| x.as_str()).unwrap_or_default();
+                if !link.is_empty() && !self.search_results.iter().any(|(_, l)| l == link) {
+                    self.search_results.push((title.to_string(), link.to_string()));
+                    out.push(StreamEvent::CitationDelta(Citation {
+                        url: link.to_string(),
+                        title: Some(title.to_string()).filter(|t| !t.is_empty()),
+                        ..Citation::default()
+                    }));
+                }
+            }
+        }
+
+        if let Some(u) = chunk.get("usage").filter(|u| u.is_object()) {
+            let get = |p: &str| u.pointer(p).and_then(|x| x.as_u64()).unwrap_or(0);
+            self.usage = Some(Usage {
+                input_tokens: get("/prompt_tokens"),
+                output_tokens: get("/completion_tokens"),
+                cache_read_tokens: get("/prompt_tokens_details/cached_tokens"),
+                total_tokens: get("/total_tokens"),
+                ..Usage::default()
+            });
+        }
+
+        let Some(choice) = chunk.pointer("/choices/0") else { return out };
+
+        if let Some(delta) = choice.get("delta") {
+            if let Some(r) = delta.get("reasoning_content").and_then(|x| x.as_str())
+                && !r.is_empty()
+            {
+                self.thinking.push_str(r);
+                out.push(StreamEvent::ThinkingDelta(r.to_string()));
+            }
+            if let Some(c) = delta.get("content").and_then(|x| x.as_str())
+                && !c.is_empty()
+            {
+                self.text.push_str(c);
+                out.push(StreamEvent::TextDelta(c.to_string()));
+            }
+            for tc in delta.get("tool_calls").and_then(|x| x.as_array()).into_iter().flatten() {
+                self.tool_call_delta(tc, &mut out);
+            }
+        }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|x| x.as_str()) {
+            self.stop_reason = match reason {
+                "length" => StopReason::Length,
+                "tool_calls" => StopReason::ToolUse,
+                "sensitive" => StopReason::ContentFilter,
+                "network_error" => StopReason::Error,
+                _ => StopReason::Stop,
+            };
+        }
+        out
+    }
+
+    fn tool_call_delta(&mut self, tc: &Value, out: &mut Vec<StreamEvent>) {
+        let id = tc.get("id").and_then(|x| x.as_str()).unwrap_or_default();
+        if tc.get("type").and_then(|x| x.as_str()).is_some_and(|t| t != "function") {
+            return;
+        }
+        // Without an index, a known id continues its call and a new id starts one.
+        let idx = match tc.get("index").and_then(|x| x.as_u64()) {
+            Some(i) => i as usize,
+            None => self
+                .tool_calls
+                .iter()
+                .position(|(existing, _, _)| !id.is_empty() && existing == id)
+                .unwrap_or(self.tool_calls.len()),
+        };
+        while self.tool_calls.len() <= idx {
+            self.tool_calls.push(Default::default());
+        }
+        let slot = &mut self.tool_calls[idx];
+        if !id.is_empty() {
+            slot.0 = id.to_string();
+        }
+        if let Some(name) = tc.pointer("/function/name").and_then(|x| x.as_str())
+            && slot.1.is_empty()
+        {
+            slot.1 = name.to_string();
+            out.push(StreamEvent::ToolCallStart {
+                index: idx,
+                id: slot.0.clone(),
+                name: name.to_string(),
+            });
+        }
+        if let Some(args) = tc.pointer("/function/arguments").and_then(|x| x.as_str())
+            && !args.is_empty()
+        {
+            slot.2.push_str(args);
+            out.push(StreamEvent::ToolCallDelta {
+                index: idx,
+                delta: args.to_string(),
+            });
+        }
+    }
+
+    /// Closing events: tool-call ends.
+    fn finish_events(&self) -> Vec<StreamEvent> {
+        self.parsed_tool_calls()
+            .into_iter()
+            .enumerate()
+            .map(|(index, tool_call)| StreamEvent::ToolCallEnd { index, tool_call })
+            .collect()
+    }
+
+    fn parsed_tool_calls(&self) -> Vec<ToolCall> {
+        self.tool_calls
+            .iter()
+            .map(|(id, name, args)| ToolCall {
+                id: id.clone(),
+                name: name.clone(),
+                arguments: serde_json::from_str(args).unwrap_or_else(|_| json!({})),
+            })
+            .collect()
+    }
+
+    fn into_message(self, model: &str, provider: &str) -> AssistantMessage {
+        let mut content = Vec::new();
+        if !self.thinking.is_empty() {
+            content.push(ContentBlock::Thinking(ThinkingContent {
+                thinking: self.thinking.clone(),
+                signature: None,
+            }));
+        }
+        if !self.text.is_empty() {
+            content.push(ContentBlock::Text(TextContent { text: self.text.clone() }));
+        }
+        let tool_calls = self.parsed_tool_calls();
+        let mut stop_reason = self.stop_reason;
+        if !tool_calls.is_empty() && stop_reason == StopReason::Stop {
+            stop_reason = StopReason::ToolUse;
+        }
+        content.extend(tool_calls.into_iter().map(ContentBlock::ToolCall));
+        let citations = self
+            .search_results
+            .into_iter()
+            .map(|(title, url)| Citation {
+                url,
+                title: Some(title).filter(|t| !t.is_empty()),
+                ..Citation::default()
+            })
+            .collect();
+        AssistantMessage {
+            content,
+            model: model.to_string(),
+            provider: provider.to_string(),
+            usage: self.usage,
+            stop_reason,
+            citations,
+            safety_ratings: Vec::new(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Provider impl
+// ---------------------------------------------------------------------------
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Provider for ZhipuProvider {
+    fn stream(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> EventStream {
+        let client = self.client.clone();
+        let api_key = options.api_key.clone();
+        let url = format!("{}/chat/completions", model.base_url.trim_end_matches('/'));
+        let body = build_body(model, context, options);
+        let model_id = model.id.clone();
+        let provider_id = model.provider.clone();
+        let capture = options.capture.clone();
+
+        let mut headers_map = HashMap::new();
+        if let Some(model_headers) = &model.headers {
+            headers_map.extend(model_headers.clone());
+        }
+        if let Some(extra) = &options.extra_headers {
+            headers_map.extend(extra.clone());
+        }
+
+        Box::pin(async_stream::stream! {
+            let Some(api_key) = api_key else {
+                yield Err(ProviderError::AuthRequired("API key required for ZhipuAI".into()));
+                return;
+            };
+
+            let mut req = client
+                .post(&url)
+                .bearer_auth(&api_key)
+                .header("Content-Type", "application/json");
+            for (k, v) in &headers_map {
+                req = req.header(k.as_str(), v.as_str());
+            }
+
+            let resp = match capture::send(req.json(&body), capture.as_ref()).await {
+                Ok(r) => r,
+                Err(e) => {
+                    yield Err(ProviderError::Network(e));
+                    return;
+                }
+            };
+            if !resp.status().is_success() {
+                yield Err(api_error::from_response(resp).await);
+                return;
+            }
+
+            yield Ok(StreamEvent::Start);
+
+            let mut state = ZhipuStreamState::default();
+            let mut line_buf = String::new();
+            let mut byte_stream = resp.bytes_stream();
+
+            while let Some(chunk) = byte_stream.next().await {
+                let chunk = match chunk {
+                    Ok(b) => b,
+                    Err(e) => {
+                        yield Err(ProviderError::Network(e));
+                        return;
+                    }
+                };
+                line_buf.push_str(&String::from_utf8_lossy(&chunk));
+
+                while let Some(pos) = line_buf.find('\n') {
+                    let line: String = line_buf.drain(..=pos).collect();
+                    let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                        continue;
+                    };
+                    if data.is_empty() || data == "[DONE]" {
+                        continue;
+                    }
+                    let Ok(v) = serde_json::from_str::<Value>(data) else {
+                        continue;
+                    };
+                    for ev in state.handle(&v) {
+                        yield Ok(ev);
+                    }
+                }
+            }
+
+            for ev in state.finish_events() {
+                yield Ok(ev);
+            }
+            yield Ok(StreamEvent::Done { message: state.into_message(&model_id, &provider_id) });
+        })
+    }
+
+    async fn chat(
+        &self,
+        model: &ModelDef,
+        context: &ChatContext,
+        options: &RequestOptions,
+    ) -> Result<AssistantMessage, ProviderError> {
+        let mut stream = self.stream(model, context, options);
+        while let Some(event) = stream.next().await {
+            if let StreamEvent::Done { message } = event? {
+                return Ok(message);
+            }
+        }
+        Err(ProviderError::Other("ZhipuAI stream ended without a response".into()))
+    }
+
+    async fn warm(&self, base_url: &str) -> Result<(), ProviderError> {
+        super::warm_connection(&self.client, base_url).await
+    }
+
+    async fn list_models(&self, _api_key: &str) -> Result<Vec<ModelDef>, ProviderError> {
+        Ok(crate::models::static_models_for_provider("zhipuai"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glm(reasoning: bool) -> ModelDef {
+        ModelDef {
+            id: "glm-4.6".into(),
+            name: "GLM-4.6".into(),
+            api: Api::OpenaiCompletions,
+            provider: "zhipuai".into(),
+            base_url: "https://open.bigmodel.cn/api/paas/v4".into(),
+            reasoning,
+            input: vec![InputModality::Text],
+            context_window: 200000,
+            max_tokens: 128000,
+            ..Default::default()
+        }
+    }
+
+    fn empty_context() -> ChatContext {
+        ChatContext {
+            system_prompt: None,
+            messages: vec![],
+            tools: vec![],
+        }
+    }
+
+    #[test]
+    fn thinking_field_follows_reasoning_option() {
+        let opts = RequestOptions {
+            reasoning: Some(ThinkingLevel::High),
+            ..Default::default()
+        };
+        let body = build_body(&glm(true), &empty_context(), &opts);
+        assert_eq!(body["thinking"]["type"], "enabled");
+
+        let body = build_body(&glm(true), &empty_context(), &RequestOptions::default());
+        assert!(body.get("thinking").is_none());
+
+        let body = build_body(&glm(false), &empty_context(), &opts);
+        assert!(body.get("thinking").is_none());
+    }
+
+    #[test]
+    fn translates_reasoning_and_content() {
+        let mut state = ZhipuStreamState::default();
+        let mut events = state.handle(&json!({"choices": [{"delta": {"reasoning_content": "hmm"}}]}));
+        events.extend(state.handle(&json!({"choices": [{"delta": {"content": "Hi"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5}})));
+        assert!(matches!(&events[0], StreamEvent::ThinkingDelta(d) if d == "hmm"));
+        assert!(matches!(&events[1], StreamEvent::TextDelta(d) if d == "Hi"));
+
+        let msg = state.into_message("glm-4.6", "zhipuai");
+        assert!(matches!(&msg.content[0], ContentBlock::Thinking(t) if t.thinking == "hmm"));
+        assert_eq!(msg.usage.unwrap().total_tokens, 5);
+        assert_eq!(msg.stop_reason, StopReason::Stop);
+    }
+
+    #[test]
+    fn whole_tool_calls_without_index() {
+        let mut state = ZhipuStreamState::default();
+        state.handle(&json!({"choices": [{"delta": {"tool_calls": [
+            {"id": "call_a", "type": "function", "function": {"name": "ls", "arguments": "{\"path\":\".\"}"}},
+            {"id": "call_b", "type": "function", "function": {"name": "pwd", "arguments": "{}"}}
+        ]}}]}));
+        let ends = state.finish_events();
+        assert_eq!(ends.len(), 2);
+        let msg = state.into_message("glm-4.6", "zhipuai");
+        assert_eq!(msg.stop_reason, StopReason::ToolUse);
+        assert!(matches!(&msg.content[0], ContentBlock::ToolCall(tc) if tc.id == "call_a" && tc.arguments["path"] == "."));
+    }
+
+    #[test]
+    fn web_search_results_become_citations() {
+        let mut state = ZhipuStreamState::default();
+        let events = state.handle(&json!({"web_search": [
+            {"title": "Rust", "link": "https://rust-lang.org"},
+            {"title": "Rust", "link": "https://rust-lang.org"}
+        ]}));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], StreamEvent::CitationDelta(c) if c.url == "https://rust-lang.org"));
+        assert!(state.finish_events().is_empty());
+        let msg = state.into_message("glm-4.6", "zhipuai");
+        assert!(msg.content.is_empty());
+        assert_eq!(msg.citations.len(), 1);
+        assert_eq!(msg.citations[0].title.as_deref(), Some("Rust"));
+    }
+
+    #[test]
+    fn web_search_option_adds_builtin_tool() {
+        let opts = RequestOptions {
+            web_search: true,
+            ..Default::default()
+        };
+        let body = build_body(&glm(false), &empty_context(), &opts);
+        assert_eq!(body["tools"][0]["type"], "web_search");
+        assert_eq!(body["tools"][0]["web_search"]["search_result"], true);
+    }
+}
@@ -0,0 +1,296 @@
+//! Model routing strategies: picking a concrete model for a request.
+//!
+//! `cheapest-capable` chooses the lowest-priced model that can serve a request: it must
+//! support what the request uses (tools, image or audio input, JSON mode) and its context
+//! window must fit the estimated input plus the requested output. Models without a price
+//! are only picked when no priced model qualifies. The proxy exposes it as the pseudo-model
+//! [`CHEAPEST_MODEL_ID`].
+//!
+//! `fastest-capable` chooses, among the same capable models, the one with the lowest rolling
+//! median time to first token. Models
+//! without recent measurements come after measured ones, except on exploring requests (one
+//! in [`EXPLORE_EVERY`]), which go to an unmeasured model so it gets measured too. The proxy
+//! exposes it as [`FASTEST_MODEL_ID`].
+//!
+//! Both take an `available` filter, which the proxy uses to skip providers that are down for
+//! maintenance or whose accounts are all resting after rate limits.
+//!
+//! An [`Experiment`] splits the requests for one model name between variants by weight, so
+//! two models (or settings of the same model) can be compared on live traffic. Each client
+//! keeps its variant for as long as the experiment's variants stay the same.
+
+use crate::auth::usage::LatencyStats;
+use crate::tokens;
+use crate::types::{ChatContext, ContentBlock, Message, ModelDef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Pseudo-model ID that routes each request to the cheapest capable model.
+pub const CHEAPEST_MODEL_ID: &str = "auto/cheapest";
+
+/// Pseudo-model ID that routes each request to the fastest healthy capable model.
+pub const FASTEST_MODEL_ID: &str = "auto/fastest";
+
+/// One in this many `auto/fastest` requests explores an unmeasured model.
+pub const EXPLORE_EVERY: u64 = 20;
+
+/// Whether `id` is one of the routing pseudo-models rather than a real model.
+pub fn is_routed(id: &str) -> bool {
+    id == CHEAPEST_MODEL_ID || id == FASTEST_MODEL_ID
+}
+
+/// What a model needs to support to serve a request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelRequirements {
+    pub tools: bool,
+    pub vision: bool,
+    pub audio: bool,
+    pub json_mode: bool,
+    /// Tokens the context window must hold: input plus output.
+    pub min_context: u64,
+}
+
+impl ModelRequirements {
+    /// Requirements of `context`, with room for `max_tokens` of output. Audio and JSON mode
+    /// aren't visible in a context; callers set them from the request.
+    pub fn for_context(context: &ChatContext, max_tokens: Option<u64>) -> Self {
+        let has_image = |blocks: &[ContentBlock]| blocks.iter().any(|b| matches!(b, ContentBlock::Image(_)));
+        Self {
+            tools: !context.tools.is_empty(),
+            vision: context.messages.iter().any(|m| match m {
+                Message::User(u) => has_image(&u.content),
+                Message::ToolResult(t) => has_image(&t.content),
+                Message::Assistant(_) => false,
+            }),
+            audio: false,
+            json_mode: false,
+            min_context: tokens::estimate_input_tokens(context) + max_tokens.unwrap_or(0),
+        }
+    }
+
+    pub fn is_met_by(&self, def: &ModelDef) -> bool {
+        (!self.tools || def.supports_tools())
+            && (!self.vision || def.supports_vision())
+            && (!self.audio || def.supports_audio())
+            && (!self.json_mode || def.supports_json_mode())
+            && (def.context_window == 0 || def.context_window >= self.min_context)
+    }
+}
+
+/// Price used to rank models: input plus output USD per million tokens. `None` when the
+/// price is unknown.
+fn blended_price(def: &ModelDef) -> Option<f64> {
+    let price = def.cost.input + def.cost.output;
+    (price > 0.0).then_some(price)
+}
+
+/// Full ID of the cheapest model in `models` that meets `requirements`, among those
+/// `available` accepts. When none of them has a price, the unpriced one with the smallest
+/// ID; ties also go to the smallest ID, so the choice is stable.
+pub fn cheapest_capable<'a>(
+    models: &'a HashMap<String, ModelDef>,
+    requirements: &ModelRequirements,
+    available: impl Fn(&str) -> bool,
+) -> Option<&'a str> {
+    let capable: Vec<_> = models
+        .iter()
+        .filter(|(id, def)| requirements.is_met_by(def) && available(id))
+        .collect();
+    let cheapest = capable
+        .iter()
+        .filter_map(|(id, def)| Some((id, blended_price(def)?)))
+        .min_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+    if let Some((id, _)) = cheapest {
+        return Some(id.as_str());
+    }
+    let unpriced = capable.iter().map(|(id, _)| id.as_str()).min()?;
+    tracing::info!("No priced model meets the request's requirements; routing to unpriced {}", unpriced);
+    Some(unpriced)
+}
+
+/// Full ID of the capable model in `models` with the lowest p50 latency in `latency`, among
+/// those `available` accepts. Unmeasured models rank after measured ones, or before them
+/// when `explore` is set; ties go to the smallest ID.
+pub fn fastest_capable<'a>(
+    models: &'a HashMap<String, ModelDef>,
+    requirements: &ModelRequirements,
+    latency: &HashMap<String, LatencyStats>,
+    explore: bool,
+    available: impl Fn(&str) -> bool,
+) -> Option<&'a str> {
+    let unmeasured = if explore { 0 } else { u64::MAX };
+    models
+        .iter()
+        .filter(|(id, def)| requirements.is_met_by(def) && available(id))
+        .map(|(id, _)| (id, latency.get(id).map_or(unmeasured, |l| l.p50_ms)))
+        .min_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(b.0)))
+        .map(|(id, _)| id.as_str())
+}
+
+/// An A/B experiment: requests for `model` are split between `variants` by weight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Experiment {
+    pub name: String,
+    /// Model name clients request to take part: an ID, alias or any other name.
+    pub model: String,
+    pub variants: Vec<ExperimentVariant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentVariant {
+    pub name: String,
+    /// Model that serves this variant: an ID, alias or routing pseudo-model.
+    pub model: String,
+    /// Share of traffic relative to the other variants.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl Experiment {
+    /// The variant for the client identified by `key`, always the same one for the same key;
+    /// a random one when there is no key. `None` when no variant has any weight.
+    pub fn assign(&self, key: Option<&str>) -> Option<&ExperimentVariant> {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut point = match key {
+            Some(key) => fnv1a(&[self.name.as_bytes(), &[0], key.as_bytes()]) % total,
+            None => rand::random_range(0..total),
+        };
+        self.variants.iter().find(|v| {
+            let hit = point < u64::from(v.weight);
+            point = point.saturating_sub(u64::from(v.weight));
+            hit
+        })
+    }
+}
+
+/// 64-bit FNV-1a, a hash that is stable across builds and platforms.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    parts.iter().flat_map(|p| p.iter()).fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ImageContent, InputModality, ModelCost, ToolDef, UserMessage};
+    use crate::MockProvider;
+
+    fn model(id: &str, input: f64, output: f64, tools: bool, vision: bool, context_window: u64) -> (String, ModelDef) {
+        let mut def = MockProvider::model(id);
+        def.cost = ModelCost { input, output, ..Default::default() };
+        def.capabilities.tools = tools;
+        def.input = if vision { vec![InputModality::Text, InputModality::Image] } else { vec![InputModality::Text] };
+        def.context_window = context_window;
+        (format!("mock/{}", id), def)
+    }
+
+    #[test]
+    fn picks_the_cheapest_model_that_fits() {
+        let models: HashMap<_, _> = [
+            model("tiny", 0.1, 0.2, false, false, 8_000),
+            model("small", 0.5, 1.0, true, false, 128_000),
+            model("vision", 1.0, 2.0, true, true, 128_000),
+            model("big", 3.0, 15.0, true, true, 1_000_000),
+            model("unpriced", 0.0, 0.0, true, true, 1_000_000),
+        ]
+        .into_iter()
+        .collect();
+        let pick = |r: ModelRequirements| cheapest_capable(&models, &r, |_| true);
+
+        assert_eq!(pick(ModelRequirements::default()), Some("mock/tiny"));
+        assert_eq!(pick(ModelRequirements { tools: true, ..Default::default() }), Some("mock/small"));
+        assert_eq!(pick(ModelRequirements { vision: true, ..Default::default() }), Some("mock/vision"));
+        assert_eq!(pick(ModelRequirements { min_context: 200_000, ..Default::default() }), Some("mock/big"));
+        assert_eq!(pick(ModelRequirements { audio: true, ..Default::default() }), None);
+        let without_tiny = cheapest_capable(&models, &ModelRequirements::default(), |id| id != "mock/tiny");
+        assert_eq!(without_tiny, Some("mock/small"));
+        // Only the unpriced model is left.
+        let priced_down = cheapest_capable(&models, &ModelRequirements::default(), |id| id == "mock/unpriced");
+        assert_eq!(priced_down, Some("mock/unpriced"));
+        assert_eq!(pick(ModelRequirements { min_context: 2_000_000, ..Default::default() }), None);
+    }
+
+    #[test]
+    fn picks_the_fastest_healthy_model() {
+        let models: HashMap<_, _> = [
+            model("slow", 0.1, 0.2, true, false, 128_000),
+            model("fast", 3.0, 15.0, true, false, 128_000),
+            model("faster", 1.0, 2.0, false, false, 128_000),
+            model("new", 1.0, 2.0, true, false, 128_000),
+        ]
+        .into_iter()
+        .collect();
+        let stats = |p50_ms| LatencyStats { samples: 10, p50_ms, p95_ms: p50_ms * 2 };
+        let latency: HashMap<String, LatencyStats> = [("mock/slow", 900), ("mock/fast", 300), ("mock/faster", 100)]
+            .into_iter()
+            .map(|(id, ms)| (id.to_string(), stats(ms)))
+            .collect();
+        let tools = ModelRequirements { tools: true, ..Default::default() };
+
+        assert_eq!(fastest_capable(&models, &ModelRequirements::default(), &latency, false, |_| true), Some("mock/faster"));
+        assert_eq!(fastest_capable(&models, &tools, &latency, false, |_| true), Some("mock/fast"));
+        assert_eq!(fastest_capable(&models, &tools, &latency, false, |id| id != "mock/fast"), Some("mock/slow"));
+        assert_eq!(fastest_capable(&models, &tools, &HashMap::new(), false, |_| true), Some("mock/fast"));
+        assert_eq!(fastest_capable(&models, &tools, &latency, false, |id| id == "mock/new"), Some("mock/new"));
+        // Exploring requests go to the unmeasured model, or the fastest when all are measured.
+        assert_eq!(fastest_capable(&models, &tools, &latency, true, |_| true), Some("mock/new"));
+        assert_eq!(fastest_capable(&models, &tools, &latency, true, |id| id != "mock/new"), Some("mock/fast"));
+    }
+
+    #[test]
+    fn experiments_split_by_weight_and_stick_to_clients() {
+        let variant = |name: &str, weight| ExperimentVariant {
+            name: name.into(),
+            model: format!("mock/{}", name),
+            weight,
+        };
+        let experiment = Experiment {
+            name: "chat".into(),
+            model: "chat".into(),
+            variants: vec![variant("control", 3), variant("candidate", 1), variant("off", 0)],
+        };
+        let mut counts = HashMap::new();
+        for i in 0..4000 {
+            let key = format!("user-{}", i);
+            let v = experiment.assign(Some(&key)).unwrap();
+            assert_eq!(experiment.assign(Some(&key)), Some(v));
+            *counts.entry(v.name.as_str()).or_insert(0) += 1;
+        }
+        assert!((2700..3300).contains(&counts["control"]), "{:?}", counts);
+        assert!(!counts.contains_key("off"));
+        assert!(experiment.assign(None).is_some());
+
+        let empty = Experiment { variants: vec![variant("off", 0)], ..experiment };
+        assert_eq!(empty.assign(Some("user-1")), None);
+    }
+
+    #[test]
+    fn requirements_come_from_the_context() {
+        let mut context = ChatContext {
+            system_prompt: None,
+            messages: vec![Message::User(UserMessage {
+                content: vec![ContentBlock::Image(ImageContent {
+                    data: String::new(),
+                    mime_type: "image/png".into(),
+                })],
+            })],
+            tools: Vec::new(),
+        };
+        let r = ModelRequirements::for_context(&context, Some(1000));
+        assert!(r.vision && !r.tools);
+        assert!(r.min_context >= 1000);
+
+        context.tools.push(ToolDef {
+            name: "read".into(),
+            description: String::new(),
+            parameters: serde_json::json!({"type": "object"}),
+        });
+        assert!(ModelRequirements::for_context(&context, None).tools);
+    }
+}
@@ -0,0 +1,27 @@
+//! Timers and the monotonic clock, from tokio/std natively and from the host on wasm32,
+//! where `std::time::Instant::now` panics and there is no tokio timer driver.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+use std::time::Duration;
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// `future`'s output, or `None` if `duration` passes first.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return tokio::time::timeout(duration, future).await.ok();
+    #[cfg(target_arch = "wasm32")]
+    match futures::future::select(std::pin::pin!(future), std::pin::pin!(sleep(duration))).await {
+        futures::future::Either::Left((output, _)) => Some(output),
+        futures::future::Either::Right(_) => None,
+    }
+}
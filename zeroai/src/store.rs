@@ -0,0 +1,632 @@
+//! SQLite store shared by everything zeroai keeps between runs (~/.zeroai/zeroai.db).
+//!
+//! Holds usage accounting (see [`crate::auth::usage`]), responses kept for idempotent
+//! replays, account health history, shadow traffic comparisons, capability probe results and
+//! the request journal. The schema is versioned with `PRAGMA user_version`: [`MIGRATIONS`]
+//! run in order on open, each at most once. The database is opened in WAL mode with a busy
+//! timeout so several proxy processes can share it.
+//!
+//! Calls block on SQLite; async code runs them through [`blocking`].
+//!
+//! [`Store::open_default`] also imports the `usage.json` file written by older versions.
+
+use crate::probe::CapabilityProbe;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Schema changes, applied in order. Never edit a released entry; append a new one.
+const MIGRATIONS: &[&str] = &[
+    // 1: usage, idempotent responses, account health.
+    "CREATE TABLE usage_subjects (
+        scope TEXT NOT NULL,
+        name TEXT NOT NULL,
+        last_used_ms INTEGER,
+        quota TEXT,
+        PRIMARY KEY (scope, name)
+    );
+    CREATE TABLE usage_hours (
+        scope TEXT NOT NULL,
+        name TEXT NOT NULL,
+        hour_ms INTEGER NOT NULL,
+        requests INTEGER NOT NULL DEFAULT 0,
+        input_tokens INTEGER NOT NULL DEFAULT 0,
+        output_tokens INTEGER NOT NULL DEFAULT 0,
+        cost_usd REAL NOT NULL DEFAULT 0,
+        PRIMARY KEY (scope, name, hour_ms)
+    );
+    CREATE TABLE responses (
+        key TEXT PRIMARY KEY,
+        request_hash TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        headers TEXT NOT NULL,
+        body BLOB NOT NULL,
+        expires_at_ms INTEGER NOT NULL
+    );
+    CREATE TABLE account_events (
+        provider TEXT NOT NULL,
+        account_id TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        until_ms INTEGER
+    );
+    CREATE INDEX account_events_at ON account_events (at_ms);",
+    // 2: response timing per usage bucket.
+    "ALTER TABLE usage_hours ADD COLUMN ttft_requests INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE usage_hours ADD COLUMN ttft_ms_total INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE usage_hours ADD COLUMN generation_ms INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE usage_hours ADD COLUMN generated_tokens INTEGER NOT NULL DEFAULT 0;",
+    // 3: recent latency per model, for percentiles.
+    "CREATE TABLE latency_samples (
+        model TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL
+    );
+    CREATE INDEX latency_samples_at ON latency_samples (at_ms);",
+    // 4: shadow traffic comparisons.
+    "CREATE TABLE shadow_results (
+        at_ms INTEGER NOT NULL,
+        model TEXT NOT NULL,
+        shadow_model TEXT NOT NULL,
+        error TEXT,
+        duration_ms INTEGER,
+        shadow_duration_ms INTEGER,
+        output_tokens INTEGER NOT NULL,
+        shadow_output_tokens INTEGER NOT NULL,
+        cost_usd REAL NOT NULL,
+        shadow_cost_usd REAL NOT NULL,
+        similarity REAL,
+        same_tool_calls INTEGER
+    );
+    CREATE INDEX shadow_results_at ON shadow_results (at_ms);",
+    // 5: capability probe results, latest per model.
+    "CREATE TABLE capability_probes (
+        model TEXT PRIMARY KEY,
+        probed_at_ms INTEGER NOT NULL,
+        streaming INTEGER,
+        tools INTEGER,
+        vision INTEGER,
+        error TEXT
+    );",
+    // 6: request journal, for looking up what was billed for a request.
+    "CREATE TABLE request_journal (
+        request_id TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        route TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        model TEXT NOT NULL,
+        account TEXT,
+        input_tokens INTEGER NOT NULL,
+        output_tokens INTEGER NOT NULL,
+        request_hash TEXT,
+        upstream_request_id TEXT
+    );
+    CREATE INDEX request_journal_id ON request_journal (request_id);
+    CREATE INDEX request_journal_at ON request_journal (at_ms);",
+];
+
+/// Account events older than this are dropped.
+pub const ACCOUNT_EVENT_RETENTION_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// Shadow comparisons older than this are dropped.
+pub const SHADOW_RESULT_RETENTION_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    /// Open (creating if needed) the database at `path` and bring its schema up to date.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(conn)
+    }
+
+    /// A private database that lives as long as the store (for tests and one-off tools).
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// The store at [`Store::default_path`], with a leftover `~/.zeroai/usage.json` imported
+    /// into it.
+    pub fn open_default() -> anyhow::Result<Self> {
+        let path = Self::default_path();
+        let store = Self::open(&path)?;
+        if let Some(dir) = path.parent() {
+            crate::auth::usage::import_legacy_file(&store, &dir.join("usage.json"))?;
+        }
+        Ok(store)
+    }
+
+    /// `zeroai.db` in the config root (~/.zeroai/zeroai.db unless moved, see
+    /// [`crate::auth::config::config_dir`]).
+    pub fn default_path() -> PathBuf {
+        crate::auth::config::config_dir().join("zeroai.db")
+    }
+
+    fn with_connection(mut conn: Connection) -> anyhow::Result<Self> {
+        migrate(&mut conn)?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Run `f` on the connection. Writes that must land together belong in one transaction.
+    pub(crate) fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> anyhow::Result<T> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(f(&mut conn)?)
+    }
+}
+
+/// Run store calls `f` on a blocking thread, so they don't stall the async runtime.
+pub async fn blocking<T: Send + 'static>(f: impl FnOnce() -> anyhow::Result<T> + Send + 'static) -> anyhow::Result<T> {
+    tokio::task::spawn_blocking(f).await?
+}
+
+fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Idempotent responses
+// ---------------------------------------------------------------------------
+
+/// A response kept for replaying requests that repeat an idempotency key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    /// Hash of the request that produced it; a repeat must match.
+    pub request_hash: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub expires_at_ms: i64,
+}
+
+impl Store {
+    /// The unexpired response kept under `key`. Expired responses are dropped.
+    pub fn response(&self, key: &str, now_ms: i64) -> anyhow::Result<Option<StoredResponse>> {
+        let row = self.with_conn(|conn| {
+            conn.execute("DELETE FROM responses WHERE expires_at_ms <= ?1", params![now_ms])?;
+            conn.query_row(
+                "SELECT request_hash, status, headers, body, expires_at_ms FROM responses WHERE key = ?1",
+                params![key],
+                |r| Ok((r.get::<_, String>(0)?, r.get(1)?, r.get::<_, String>(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .optional()
+        })?;
+        let Some((request_hash, status, headers, body, expires_at_ms)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(StoredResponse {
+            request_hash,
+            status,
+            headers: serde_json::from_str(&headers)?,
+            body,
+            expires_at_ms,
+        }))
+    }
+
+    pub fn put_response(&self, key: &str, response: &StoredResponse) -> anyhow::Result<()> {
+        let headers = serde_json::to_string(&response.headers)?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO responses (key, request_hash, status, headers, body, expires_at_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![key, response.request_hash, response.status, headers, response.body, response.expires_at_ms],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Account health history
+// ---------------------------------------------------------------------------
+
+/// Something that took an account out of rotation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountEvent {
+    pub provider: String,
+    pub account_id: String,
+    pub at_ms: i64,
+    /// e.g. `rate_limited`, `quota_exhausted`.
+    pub kind: String,
+    /// When the account was expected back, if known.
+    pub until_ms: Option<i64>,
+}
+
+impl Store {
+    /// Append an event and drop those older than [`ACCOUNT_EVENT_RETENTION_MS`].
+    pub fn record_account_event(&self, event: &AccountEvent) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO account_events (provider, account_id, at_ms, kind, until_ms) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![event.provider, event.account_id, event.at_ms, event.kind, event.until_ms],
+            )?;
+            tx.execute(
+                "DELETE FROM account_events WHERE at_ms < ?1",
+                params![event.at_ms - ACCOUNT_EVENT_RETENTION_MS],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Events at or after `since_ms`, oldest first.
+    pub fn account_events(&self, since_ms: i64) -> anyhow::Result<Vec<AccountEvent>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT provider, account_id, at_ms, kind, until_ms FROM account_events
+                 WHERE at_ms >= ?1 ORDER BY at_ms, rowid",
+            )?;
+            stmt.query_map(params![since_ms], |r| {
+                Ok(AccountEvent {
+                    provider: r.get(0)?,
+                    account_id: r.get(1)?,
+                    at_ms: r.get(2)?,
+                    kind: r.get(3)?,
+                    until_ms: r.get(4)?,
+                })
+            })?
+            .collect()
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Shadow traffic
+// ---------------------------------------------------------------------------
+
+/// A request answered by `model` that was also sent to `shadow_model`, with both outcomes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowResult {
+    pub at_ms: i64,
+    pub model: String,
+    pub shadow_model: String,
+    /// Why the shadow call failed; `None` when it succeeded.
+    pub error: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub shadow_duration_ms: Option<u64>,
+    pub output_tokens: u64,
+    pub shadow_output_tokens: u64,
+    pub cost_usd: f64,
+    pub shadow_cost_usd: f64,
+    /// How close the shadow's answer text is to the primary's, from 0 to 1.
+    pub similarity: Option<f64>,
+    /// Whether both answers called the same tools, in the same order.
+    pub same_tool_calls: Option<bool>,
+}
+
+/// Shadow results of one model pair, summed up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowSummary {
+    pub model: String,
+    pub shadow_model: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub avg_duration_ms: Option<f64>,
+    pub avg_shadow_duration_ms: Option<f64>,
+    pub cost_usd: f64,
+    pub shadow_cost_usd: f64,
+    pub avg_similarity: Option<f64>,
+    /// Share of successful shadow calls that called the same tools as the primary.
+    pub tool_call_agreement: Option<f64>,
+}
+
+impl Store {
+    /// Append a result and drop those older than [`SHADOW_RESULT_RETENTION_MS`].
+    pub fn record_shadow_result(&self, result: &ShadowResult) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO shadow_results (at_ms, model, shadow_model, error, duration_ms, shadow_duration_ms,
+                 output_tokens, shadow_output_tokens, cost_usd, shadow_cost_usd, similarity, same_tool_calls)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    result.at_ms,
+                    result.model,
+                    result.shadow_model,
+                    result.error,
+                    result.duration_ms,
+                    result.shadow_duration_ms,
+                    result.output_tokens,
+                    result.shadow_output_tokens,
+                    result.cost_usd,
+                    result.shadow_cost_usd,
+                    result.similarity,
+                    result.same_tool_calls,
+                ],
+            )?;
+            tx.execute(
+                "DELETE FROM shadow_results WHERE at_ms < ?1",
+                params![result.at_ms - SHADOW_RESULT_RETENTION_MS],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Results at or after `since_ms` per model pair, ordered by model.
+    pub fn shadow_summary(&self, since_ms: i64) -> anyhow::Result<Vec<ShadowSummary>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT model, shadow_model, COUNT(*), COUNT(error), AVG(duration_ms), AVG(shadow_duration_ms),
+                 SUM(cost_usd), SUM(shadow_cost_usd), AVG(similarity), AVG(same_tool_calls)
+                 FROM shadow_results WHERE at_ms >= ?1
+                 GROUP BY model, shadow_model ORDER BY model, shadow_model",
+            )?;
+            stmt.query_map(params![since_ms], |r| {
+                Ok(ShadowSummary {
+                    model: r.get(0)?,
+                    shadow_model: r.get(1)?,
+                    requests: r.get(2)?,
+                    errors: r.get(3)?,
+                    avg_duration_ms: r.get(4)?,
+                    avg_shadow_duration_ms: r.get(5)?,
+                    cost_usd: r.get(6)?,
+                    shadow_cost_usd: r.get(7)?,
+                    avg_similarity: r.get(8)?,
+                    tool_call_agreement: r.get(9)?,
+                })
+            })?
+            .collect()
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Capability probes
+// ---------------------------------------------------------------------------
+
+impl Store {
+    /// Keep `probe` as the latest result for its model.
+    pub fn put_capability_probe(&self, probe: &CapabilityProbe) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO capability_probes (model, probed_at_ms, streaming, tools, vision, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![probe.model, probe.probed_at_ms, probe.streaming, probe.tools, probe.vision, probe.error],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// The latest probe result per model, ordered by model.
+    pub fn capability_probes(&self) -> anyhow::Result<Vec<CapabilityProbe>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT model, probed_at_ms, streaming, tools, vision, error FROM capability_probes ORDER BY model",
+            )?;
+            stmt.query_map([], |r| {
+                Ok(CapabilityProbe {
+                    model: r.get(0)?,
+                    probed_at_ms: r.get(1)?,
+                    streaming: r.get(2)?,
+                    tools: r.get(3)?,
+                    vision: r.get(4)?,
+                    error: r.get(5)?,
+                })
+            })?
+            .collect()
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Request journal
+// ---------------------------------------------------------------------------
+
+/// What the proxy sent upstream for one request and what it was billed, kept to settle
+/// disputes with a provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub request_id: String,
+    pub at_ms: i64,
+    pub route: String,
+    pub status: u16,
+    pub model: String,
+    /// `<provider>/<account>` that served the request, if one was picked.
+    pub account: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// SHA-256 of the route and the JSON body with sorted keys, for matching the client's payload.
+    pub request_hash: Option<String>,
+    /// The provider's own ID for the response (`x-request-id` / `request-id`).
+    pub upstream_request_id: Option<String>,
+}
+
+impl Store {
+    /// Append an entry and drop those older than `retention_ms`.
+    pub fn record_journal_entry(&self, entry: &JournalEntry, retention_ms: i64) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO request_journal (request_id, at_ms, route, status, model, account, input_tokens,
+                 output_tokens, request_hash, upstream_request_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    entry.request_id,
+                    entry.at_ms,
+                    entry.route,
+                    entry.status,
+                    entry.model,
+                    entry.account,
+                    entry.input_tokens,
+                    entry.output_tokens,
+                    entry.request_hash,
+                    entry.upstream_request_id,
+                ],
+            )?;
+            tx.execute(
+                "DELETE FROM request_journal WHERE at_ms < ?1",
+                params![entry.at_ms.saturating_sub(retention_ms)],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Entries whose proxy or upstream request ID is `id`, oldest first.
+    pub fn journal_entries(&self, id: &str) -> anyhow::Result<Vec<JournalEntry>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT request_id, at_ms, route, status, model, account, input_tokens, output_tokens,
+                 request_hash, upstream_request_id FROM request_journal
+                 WHERE request_id = ?1 OR upstream_request_id = ?1 ORDER BY at_ms, rowid",
+            )?;
+            stmt.query_map(params![id], |r| {
+                Ok(JournalEntry {
+                    request_id: r.get(0)?,
+                    at_ms: r.get(1)?,
+                    route: r.get(2)?,
+                    status: r.get(3)?,
+                    model: r.get(4)?,
+                    account: r.get(5)?,
+                    input_tokens: r.get(6)?,
+                    output_tokens: r.get(7)?,
+                    request_hash: r.get(8)?,
+                    upstream_request_id: r.get(9)?,
+                })
+            })?
+            .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_run_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zeroai.db");
+        Store::open(&path).unwrap();
+        let store = Store::open(&path).unwrap();
+        let version: usize = store
+            .with_conn(|c| c.pragma_query_value(None, "user_version", |r| r.get(0)))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn responses_expire() {
+        let store = Store::open_in_memory().unwrap();
+        let response = StoredResponse {
+            request_hash: "h".into(),
+            status: 200,
+            headers: vec![("content-type".into(), "application/json".into())],
+            body: b"{}".to_vec(),
+            expires_at_ms: 1_000,
+        };
+        store.put_response("k", &response).unwrap();
+        assert_eq!(store.response("k", 999).unwrap(), Some(response));
+        assert_eq!(store.response("k", 1_000).unwrap(), None);
+    }
+
+    #[test]
+    fn old_account_events_are_dropped() {
+        let store = Store::open_in_memory().unwrap();
+        let event = |at_ms| AccountEvent {
+            provider: "openai".into(),
+            account_id: "a1".into(),
+            at_ms,
+            kind: "rate_limited".into(),
+            until_ms: Some(at_ms + 60_000),
+        };
+        store.record_account_event(&event(0)).unwrap();
+        store.record_account_event(&event(10)).unwrap();
+        assert_eq!(store.account_events(5).unwrap(), vec![event(10)]);
+
+        store.record_account_event(&event(ACCOUNT_EVENT_RETENTION_MS + 5)).unwrap();
+        assert_eq!(store.account_events(0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn shadow_results_are_summed_per_model_pair() {
+        let store = Store::open_in_memory().unwrap();
+        let result = |at_ms, error: Option<&str>, similarity| ShadowResult {
+            at_ms,
+            model: "openai/gpt-4o".into(),
+            shadow_model: "groq/llama".into(),
+            error: error.map(String::from),
+            duration_ms: Some(1000),
+            shadow_duration_ms: error.is_none().then_some(400),
+            output_tokens: 50,
+            shadow_output_tokens: if error.is_none() { 60 } else { 0 },
+            cost_usd: 0.01,
+            shadow_cost_usd: 0.001,
+            similarity,
+            same_tool_calls: similarity.map(|s| s > 0.5),
+        };
+        store.record_shadow_result(&result(0, None, Some(0.1))).unwrap();
+        store.record_shadow_result(&result(10, None, Some(0.9))).unwrap();
+        store.record_shadow_result(&result(20, None, Some(0.8))).unwrap();
+        store.record_shadow_result(&result(30, Some("HTTP 500"), None)).unwrap();
+
+        let summary = store.shadow_summary(10).unwrap();
+        assert_eq!(summary.len(), 1);
+        let s = &summary[0];
+        assert_eq!((s.requests, s.errors), (3, 1));
+        assert_eq!(s.avg_duration_ms, Some(1000.0));
+        assert_eq!(s.avg_shadow_duration_ms, Some(400.0));
+        assert!((s.shadow_cost_usd - 0.003).abs() < 1e-9);
+        assert!((s.avg_similarity.unwrap() - 0.85).abs() < 1e-9);
+        assert_eq!(s.tool_call_agreement, Some(1.0));
+    }
+
+    #[test]
+    fn capability_probes_keep_the_latest_per_model() {
+        let store = Store::open_in_memory().unwrap();
+        let probe = |model: &str, at_ms, tools| CapabilityProbe {
+            model: model.into(),
+            probed_at_ms: at_ms,
+            streaming: Some(true),
+            tools,
+            vision: None,
+            error: None,
+        };
+        store.put_capability_probe(&probe("openai/gpt-4o", 1, Some(false))).unwrap();
+        store.put_capability_probe(&probe("groq/llama", 1, None)).unwrap();
+        store.put_capability_probe(&probe("openai/gpt-4o", 2, Some(true))).unwrap();
+        assert_eq!(
+            store.capability_probes().unwrap(),
+            vec![probe("groq/llama", 1, None), probe("openai/gpt-4o", 2, Some(true))]
+        );
+    }
+
+    #[test]
+    fn journal_entries_are_found_by_either_id() {
+        let store = Store::open_in_memory().unwrap();
+        let entry = |request_id: &str, at_ms, upstream: Option<&str>| JournalEntry {
+            request_id: request_id.into(),
+            at_ms,
+            route: "/v1/chat/completions".into(),
+            status: 200,
+            model: "openai/gpt-4o".into(),
+            account: Some("openai/a1".into()),
+            input_tokens: 12,
+            output_tokens: 34,
+            request_hash: Some("h".into()),
+            upstream_request_id: upstream.map(String::from),
+        };
+        store.record_journal_entry(&entry("r1", 0, Some("req_abc")), 1_000).unwrap();
+        store.record_journal_entry(&entry("r2", 10, None), 1_000).unwrap();
+        assert_eq!(store.journal_entries("r1").unwrap(), vec![entry("r1", 0, Some("req_abc"))]);
+        assert_eq!(store.journal_entries("req_abc").unwrap().len(), 1);
+        assert!(store.journal_entries("r3").unwrap().is_empty());
+
+        store.record_journal_entry(&entry("r3", 1_005, None), 1_000).unwrap();
+        assert!(store.journal_entries("r1").unwrap().is_empty());
+        assert_eq!(store.journal_entries("r2").unwrap().len(), 1);
+    }
+}
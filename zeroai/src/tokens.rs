@@ -0,0 +1,162 @@
+//! Local input-token estimate, for providers without a token counting endpoint.
+//!
+//! A character heuristic, not a tokenizer: about 4 characters per token for Latin text and
+//! one token per CJK (or other non-ASCII) character, plus a small per-message overhead.
+//! Good enough for context budgeting; exact counts come from `Provider::count_tokens`.
+//! [`truncate_to_fit`] trims a conversation to such a budget.
+
+use crate::types::{ChatContext, ContentBlock, Message};
+
+/// Per-message framing (role markers, separators).
+const MESSAGE_OVERHEAD: u64 = 4;
+
+/// Images are billed by size; this is the cost of a typical ~1 megapixel image.
+const IMAGE_TOKENS: u64 = 1_600;
+
+/// Estimated token count of a piece of text.
+pub fn estimate_text_tokens(text: &str) -> u64 {
+    let (ascii, other) = text
+        .chars()
+        .fold((0u64, 0u64), |(a, o), c| if c.is_ascii() { (a + 1, o) } else { (a, o + 1) });
+    ascii.div_ceil(4) + other
+}
+
+fn estimate_blocks(blocks: &[ContentBlock]) -> u64 {
+    blocks
+        .iter()
+        .map(|b| match b {
+            ContentBlock::Text(t) => estimate_text_tokens(&t.text),
+            ContentBlock::Thinking(t) => estimate_text_tokens(&t.thinking),
+            ContentBlock::Image(_) => IMAGE_TOKENS,
+            ContentBlock::ToolCall(tc) => estimate_text_tokens(&tc.name) + estimate_text_tokens(&tc.arguments.to_string()),
+            ContentBlock::ThoughtSignature(_) => 0,
+            ContentBlock::ExecutableCode(c) => estimate_text_tokens(&c.code),
+            ContentBlock::CodeExecutionResult(r) => estimate_text_tokens(&r.output),
+            ContentBlock::Refusal(r) => estimate_text_tokens(&r.text),
+        })
+        .sum()
+}
+
+fn estimate_message(message: &Message) -> u64 {
+    MESSAGE_OVERHEAD
+        + match message {
+            Message::User(u) => estimate_blocks(&u.content),
+            Message::Assistant(a) => estimate_blocks(&a.content),
+            Message::ToolResult(tr) => estimate_text_tokens(&tr.tool_call_id) + estimate_blocks(&tr.content),
+        }
+}
+
+/// Estimated input tokens of a request: system prompt, messages and tool definitions.
+pub fn estimate_input_tokens(context: &ChatContext) -> u64 {
+    let system = context.system_prompt.as_deref().map(estimate_text_tokens).unwrap_or(0);
+    let messages: u64 = context.messages.iter().map(estimate_message).sum();
+    let tools: u64 = context
+        .tools
+        .iter()
+        .map(|t| {
+            MESSAGE_OVERHEAD
+                + estimate_text_tokens(&t.name)
+                + estimate_text_tokens(&t.description)
+                + estimate_text_tokens(&t.parameters.to_string())
+        })
+        .sum();
+    system + messages + tools
+}
+
+/// Drop the oldest messages of `context` until its estimate is at most `limit` tokens. Cuts
+/// are made only before a user message, so tool results stay with their calls, and the last
+/// user turn is always kept; the context may therefore still be over `limit`. Returns how
+/// many messages were dropped.
+pub fn truncate_to_fit(context: &mut ChatContext, limit: u64) -> usize {
+    let total = estimate_input_tokens(context);
+    if total <= limit {
+        return 0;
+    }
+    let mut dropped_tokens = 0;
+    let mut cut = 0;
+    for (i, message) in context.messages.iter().enumerate().skip(1) {
+        dropped_tokens += estimate_message(&context.messages[i - 1]);
+        if matches!(message, Message::User(_)) {
+            cut = i;
+            if total - dropped_tokens <= limit {
+                break;
+            }
+        }
+    }
+    context.messages.drain(..cut);
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TextContent, ToolDef, UserMessage};
+
+    #[test]
+    fn text_estimate_counts_cjk_per_character() {
+        assert_eq!(estimate_text_tokens(""), 0);
+        assert_eq!(estimate_text_tokens("Hello, world"), 3);
+        assert_eq!(estimate_text_tokens("你好世界"), 4);
+    }
+
+    #[test]
+    fn input_estimate_includes_system_messages_and_tools() {
+        let mut ctx = ChatContext {
+            system_prompt: Some("Be brief.".into()),
+            messages: vec![Message::User(UserMessage {
+                content: vec![ContentBlock::Text(TextContent { text: "What is 2+2?".into() })],
+            })],
+            tools: vec![],
+        };
+        let base = estimate_input_tokens(&ctx);
+        assert_eq!(base, 3 + MESSAGE_OVERHEAD + 3);
+
+        ctx.tools.push(ToolDef {
+            name: "calc".into(),
+            description: "Evaluate an expression".into(),
+            parameters: serde_json::json!({"type": "object"}),
+        });
+        assert!(estimate_input_tokens(&ctx) > base);
+    }
+
+    #[test]
+    fn truncation_drops_whole_turns_from_the_start() {
+        use crate::types::{AssistantMessage, StopReason, ToolCall, ToolResultMessage};
+        let text = |t: &str| vec![ContentBlock::Text(TextContent { text: t.repeat(400) })];
+        let user = |t: &str| Message::User(UserMessage { content: text(t) });
+        let call = Message::Assistant(AssistantMessage {
+            content: vec![ContentBlock::ToolCall(ToolCall {
+                id: "c1".into(),
+                name: "read".into(),
+                arguments: serde_json::json!({}),
+            })],
+            model: String::new(),
+            provider: String::new(),
+            usage: None,
+            stop_reason: StopReason::ToolUse,
+            citations: Vec::new(),
+            safety_ratings: Vec::new(),
+        });
+        let result = Message::ToolResult(ToolResultMessage {
+            tool_call_id: "c1".into(),
+            tool_name: "read".into(),
+            content: text("r"),
+            is_error: false,
+        });
+        let mut ctx = ChatContext {
+            system_prompt: None,
+            messages: vec![user("a"), call, result, user("b"), user("c")],
+            tools: vec![],
+        };
+        let limit = estimate_input_tokens(&ctx);
+        assert_eq!(truncate_to_fit(&mut ctx, limit), 0);
+
+        assert_eq!(truncate_to_fit(&mut ctx, limit - 1), 3);
+        assert!(matches!(ctx.messages[0], Message::User(_)));
+        assert_eq!(ctx.messages.len(), 2);
+
+        assert_eq!(truncate_to_fit(&mut ctx, 1), 1);
+        assert_eq!(ctx.messages.len(), 1);
+        assert_eq!(truncate_to_fit(&mut ctx, 1), 0);
+    }
+}
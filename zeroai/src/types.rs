@@ -30,6 +30,7 @@ pub type ProviderId = String;
 // ---------------------------------------------------------------------------
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModelCost {
     /// Cost per million input tokens (USD).
     pub input: f64,
@@ -52,14 +53,48 @@ impl Default for ModelCost {
     }
 }
 
+impl ModelCost {
+    /// Cost in USD of the tokens in `usage`.
+    pub fn usage_cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input
+            + usage.output_tokens as f64 * self.output
+            + usage.cache_read_tokens as f64 * self.cache_read
+            + usage.cache_write_tokens as f64 * self.cache_write)
+            / 1_000_000.0
+    }
+}
+
 /// Supported input modalities.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum InputModality {
     Text,
     Image,
+    Audio,
 }
 
+/// Request features a model supports beyond its input modalities.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelCapabilities {
+    /// Function / tool calling.
+    pub tools: bool,
+    /// Structured output (`response_format` JSON object / schema).
+    pub json_mode: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            json_mode: false,
+        }
+    }
+}
+
+/// Output limit for Anthropic models whose `max_tokens` is unknown.
+const DEFAULT_ANTHROPIC_MAX_TOKENS: u64 = 4096;
+
 /// A model definition known to the system.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelDef {
@@ -83,9 +118,193 @@ pub struct ModelDef {
     pub context_window: u64,
     /// Maximum output tokens.
     pub max_tokens: u64,
+    /// Output token limit sent when the caller doesn't set one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_max_tokens: Option<u64>,
+    /// Thinking token budgets for each `ThinkingLevel` (defaults when unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budgets: Option<ThinkingBudgets>,
     /// Additional headers to send with every request.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub headers: Option<HashMap<String, String>>,
+    /// Changes to the persona the provider puts in front of the system prompt (Antigravity's
+    /// "You are Antigravity…"); `None` keeps the provider's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<Persona>,
+    /// Supported request features (tools, JSON mode).
+    #[serde(default)]
+    pub capabilities: ModelCapabilities,
+}
+
+impl Default for ModelDef {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            api: Api::OpenaiCompletions,
+            provider: String::new(),
+            base_url: String::new(),
+            reasoning: false,
+            input: vec![InputModality::Text],
+            cost: ModelCost::default(),
+            context_window: 128000,
+            max_tokens: 16384,
+            default_max_tokens: None,
+            thinking_budgets: None,
+            headers: None,
+            persona: None,
+            capabilities: ModelCapabilities::default(),
+        }
+    }
+}
+
+/// What to do with the persona a provider injects into the system prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Persona {
+    /// Send no persona, for the raw model behavior.
+    Off,
+    /// Send this text instead.
+    Replace(String),
+    /// Send the provider's persona followed by this text.
+    Append(String),
+}
+
+impl ModelDef {
+    pub fn supports_tools(&self) -> bool {
+        self.capabilities.tools
+    }
+
+    pub fn supports_vision(&self) -> bool {
+        self.input.contains(&InputModality::Image)
+    }
+
+    pub fn supports_audio(&self) -> bool {
+        self.input.contains(&InputModality::Audio)
+    }
+
+    pub fn supports_json_mode(&self) -> bool {
+        self.capabilities.json_mode
+    }
+
+    /// Output token limit to send: the requested one, else `default_max_tokens`, capped at
+    /// `max_tokens` (providers reject larger values). The Messages API requires a limit, so
+    /// Anthropic models fall back to `max_tokens`.
+    pub fn output_token_limit(&self, requested: Option<u64>) -> Option<u64> {
+        let cap = (self.max_tokens > 0).then_some(self.max_tokens);
+        let limit = requested.or(self.default_max_tokens).or(match self.api {
+            Api::AnthropicMessages => Some(cap.unwrap_or(DEFAULT_ANTHROPIC_MAX_TOKENS)),
+            _ => None,
+        })?;
+        Some(cap.map_or(limit, |cap| limit.min(cap)).max(1))
+    }
+
+    /// The persona to put in front of the system prompt: the provider's `default` (if it has
+    /// one) with the model's `persona` setting applied.
+    pub fn persona_prompt(&self, default: Option<&str>) -> Option<String> {
+        match &self.persona {
+            None => default.map(String::from),
+            Some(Persona::Off) => None,
+            Some(Persona::Replace(text)) => Some(text.clone()),
+            Some(Persona::Append(text)) => Some(match default {
+                Some(default) => format!("{}\n\n{}", default, text),
+                None => text.clone(),
+            }),
+        }
+    }
+
+    /// Thinking token budget for a request: the explicit `thinking_budget`, else the model's
+    /// budget for the requested level. `None` when thinking is off or the model can't reason.
+    pub fn thinking_budget(&self, options: &RequestOptions) -> Option<u64> {
+        if !self.reasoning {
+            return None;
+        }
+        options.thinking_budget.or_else(|| {
+            let level = options.reasoning.as_ref()?;
+            Some(self.thinking_budgets.unwrap_or_default().for_level(level))
+        })
+    }
+}
+
+/// User overrides for an enabled model's metadata (e.g. limits of a self-hosted model).
+/// Unset fields keep the built-in value. Keyed by `<provider>/<model>`, or `<provider>/*` for
+/// every model of a provider.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Output token limit for requests that don't set one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_max_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budgets: Option<ThinkingBudgets>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<bool>,
+    /// Extra headers, added to (and replacing same-named) built-in headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Anthropic betas to enable on every request to this model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anthropic_betas: Option<AnthropicBetas>,
+    /// Turn off, replace or extend the persona the provider injects (Antigravity, Gemini CLI).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<Persona>,
+}
+
+impl ModelOverride {
+    /// Apply the overrides in `overrides` that cover `full_id`: its provider's `<provider>/*`
+    /// entry, then the model's own.
+    pub fn apply_all(overrides: &HashMap<String, ModelOverride>, full_id: &str, model: &mut ModelDef) {
+        if let Some((provider, _)) = crate::split_model_id(full_id)
+            && let Some(o) = overrides.get(&format!("{}/*", provider))
+        {
+            o.apply_to(model);
+        }
+        if let Some(o) = overrides.get(full_id) {
+            o.apply_to(model);
+        }
+    }
+
+    pub fn apply_to(&self, model: &mut ModelDef) {
+        if let Some(v) = self.context_window {
+            model.context_window = v;
+        }
+        if let Some(v) = self.max_tokens {
+            model.max_tokens = v;
+        }
+        if let Some(v) = self.default_max_tokens {
+            model.default_max_tokens = Some(v);
+        }
+        if let Some(v) = self.thinking_budgets {
+            model.thinking_budgets = Some(v);
+        }
+        if let Some(v) = self.reasoning {
+            model.reasoning = v;
+        }
+        if let Some(h) = &self.headers {
+            model.headers.get_or_insert_with(HashMap::new).extend(h.clone());
+        }
+        if let Some(u) = &self.base_url {
+            model.base_url = u.trim_end_matches('/').to_string();
+        }
+        if let Some(p) = &self.persona {
+            model.persona = Some(p.clone());
+        }
+        if let Some(betas) = self.anthropic_betas {
+            // Kept in the model's headers; the Anthropic provider merges them with its own.
+            for name in betas.names() {
+                let value = model.headers.get_or_insert_with(HashMap::new).entry(AnthropicBetas::HEADER.to_string()).or_default();
+                if !value.is_empty() {
+                    value.push(',');
+                }
+                value.push_str(name);
+            }
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -119,6 +338,31 @@ pub struct ToolCall {
     pub arguments: serde_json::Value,
 }
 
+/// Code the model wrote and the provider ran (Gemini `code_execution`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutableCode {
+    /// Language as reported by the provider, e.g. "PYTHON".
+    pub language: String,
+    pub code: String,
+}
+
+/// Result of running an [`ExecutableCode`] block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeExecutionResult {
+    /// Provider outcome, e.g. "OUTCOME_OK" or "OUTCOME_FAILED".
+    pub outcome: String,
+    /// Stdout on success, stderr or a description otherwise.
+    #[serde(default)]
+    pub output: String,
+}
+
+/// Why the model didn't answer: its own refusal (OpenAI `refusal`) or a provider's safety
+/// block (Gemini), so an empty answer can be told apart from a declined one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefusalContent {
+    pub text: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentBlock {
@@ -127,6 +371,9 @@ pub enum ContentBlock {
     Image(ImageContent),
     ToolCall(ToolCall),
     ThoughtSignature(String),
+    ExecutableCode(ExecutableCode),
+    CodeExecutionResult(CodeExecutionResult),
+    Refusal(RefusalContent),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -141,6 +388,37 @@ pub struct AssistantMessage {
     pub provider: ProviderId,
     pub usage: Option<Usage>,
     pub stop_reason: StopReason,
+    /// Sources the response text is grounded in (web search results, documents).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    /// The provider's harm ratings of the prompt and answer (Gemini `safetyRatings`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+/// How likely a prompt or answer falls in a harm category, and whether that blocked it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SafetyRating {
+    /// e.g. `HARM_CATEGORY_DANGEROUS_CONTENT`.
+    pub category: String,
+    /// e.g. `NEGLIGIBLE`, `HIGH`.
+    #[serde(default)]
+    pub probability: String,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// A source cited by the response, optionally tied to a span of the response text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Character offsets of the cited span in the response text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -170,6 +448,21 @@ pub struct Usage {
     pub cache_read_tokens: u64,
     pub cache_write_tokens: u64,
     pub total_tokens: u64,
+    /// Milliseconds from sending the request to the first streamed token (streams only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttft_ms: Option<u64>,
+    /// Milliseconds from sending the request to the end of the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+impl Usage {
+    /// Output tokens per second of generation: after the first token when streaming, over the
+    /// whole request otherwise. `None` without timing or output.
+    pub fn output_tokens_per_sec(&self) -> Option<f64> {
+        let generation_ms = self.duration_ms?.saturating_sub(self.ttft_ms.unwrap_or(0));
+        (generation_ms > 0 && self.output_tokens > 0).then(|| self.output_tokens as f64 * 1000.0 / generation_ms as f64)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -180,6 +473,49 @@ pub enum StopReason {
     ToolUse,
     Error,
     Aborted,
+    /// Output withheld by the provider's content filter (OpenAI `content_filter`).
+    ContentFilter,
+    /// Stopped by Gemini's safety filters.
+    Safety,
+    /// Stopped by Gemini because the output recited training data.
+    Recitation,
+    /// The model declined to answer (Anthropic `refusal`).
+    Refusal,
+    /// A long-running turn paused by Anthropic; send the response back to continue it.
+    PauseTurn,
+}
+
+impl StopReason {
+    /// An OpenAI chat completion `finish_reason`.
+    pub fn from_openai(reason: &str) -> Self {
+        match reason {
+            "length" => Self::Length,
+            "tool_calls" | "function_call" => Self::ToolUse,
+            "content_filter" => Self::ContentFilter,
+            _ => Self::Stop,
+        }
+    }
+
+    /// An Anthropic Messages `stop_reason`.
+    pub fn from_anthropic(reason: &str) -> Self {
+        match reason {
+            "max_tokens" | "model_context_window_exceeded" => Self::Length,
+            "tool_use" => Self::ToolUse,
+            "refusal" => Self::Refusal,
+            "pause_turn" => Self::PauseTurn,
+            _ => Self::Stop,
+        }
+    }
+
+    /// A Gemini `finishReason`.
+    pub fn from_gemini(reason: &str) -> Self {
+        match reason {
+            "MAX_TOKENS" => Self::Length,
+            "SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII" | "IMAGE_SAFETY" => Self::Safety,
+            "RECITATION" => Self::Recitation,
+            _ => Self::Stop,
+        }
+    }
 }
 
 impl Default for StopReason {
@@ -200,6 +536,32 @@ pub struct ToolDef {
     pub parameters: serde_json::Value,
 }
 
+/// Name of the pseudo-tool that asks for the provider's built-in web search.
+pub const WEB_SEARCH_TOOL: &str = "web_search";
+
+/// Name of the pseudo-tool that asks for the provider's built-in code execution.
+pub const CODE_EXECUTION_TOOL: &str = "code_execution";
+
+impl ToolDef {
+    /// A `web_search` tool without parameters. A client-implemented `web_search` function
+    /// (one that declares parameters) is left alone and called like any other tool.
+    pub fn is_web_search(&self) -> bool {
+        self.name == WEB_SEARCH_TOOL && !self.has_parameters()
+    }
+
+    /// A `code_execution` tool without parameters (same rule as [`ToolDef::is_web_search`]).
+    pub fn is_code_execution(&self) -> bool {
+        self.name == CODE_EXECUTION_TOOL && !self.has_parameters()
+    }
+
+    fn has_parameters(&self) -> bool {
+        self.parameters
+            .get("properties")
+            .and_then(|p| p.as_object())
+            .is_some_and(|p| !p.is_empty())
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Chat context
 // ---------------------------------------------------------------------------
@@ -219,11 +581,24 @@ pub struct ChatContext {
 
 /// Retry behavior for provider calls (exponential backoff, 429/408 retry, non-retryable 4xx).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RetryConfig {
     /// Maximum number of retries (0 = no retries, 1 = one retry, etc.). Default 3.
     pub max_retries: u32,
     /// Base backoff delay in milliseconds. Doubled each retry, capped at 10s. Default 1000.
     pub base_backoff_ms: u64,
+    /// Retries when the provider is overloaded (Anthropic 529, a 503 `overloaded`), on the same
+    /// account and on top of `max_retries`. Default 2.
+    pub overload_retries: u32,
+    /// Base backoff for overload retries in milliseconds, doubled each retry. Default 500.
+    pub overload_backoff_ms: u64,
+    /// Upstream attempts allowed per client request, counting every retry and account rotation.
+    /// Unset: only the per-call limits above apply.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// Milliseconds after which a request starts no more retries or rotations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
 }
 
 impl Default for RetryConfig {
@@ -231,6 +606,10 @@ impl Default for RetryConfig {
         Self {
             max_retries: 3,
             base_backoff_ms: 1000,
+            overload_retries: 2,
+            overload_backoff_ms: 500,
+            max_attempts: None,
+            deadline_ms: None,
         }
     }
 }
@@ -248,15 +627,239 @@ pub enum ThinkingLevel {
     High,
 }
 
+/// Thinking token budget for each `ThinkingLevel`, for APIs that take a budget rather than
+/// a level (Gemini 2.5, Anthropic).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThinkingBudgets {
+    pub minimal: u64,
+    pub low: u64,
+    pub medium: u64,
+    pub high: u64,
+}
+
+impl Default for ThinkingBudgets {
+    fn default() -> Self {
+        Self {
+            minimal: 1024,
+            low: 2048,
+            medium: 8192,
+            high: 16384,
+        }
+    }
+}
+
+impl ThinkingBudgets {
+    pub fn for_level(&self, level: &ThinkingLevel) -> u64 {
+        match level {
+            ThinkingLevel::Minimal => self.minimal,
+            ThinkingLevel::Low => self.low,
+            ThinkingLevel::Medium => self.medium,
+            ThinkingLevel::High => self.high,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RequestOptions {
     pub temperature: Option<f64>,
     pub max_tokens: Option<u64>,
     pub reasoning: Option<ThinkingLevel>,
+    /// Explicit thinking token budget; takes precedence over the budget for `reasoning`.
+    pub thinking_budget: Option<u64>,
     pub api_key: Option<String>,
     pub extra_headers: Option<HashMap<String, String>>,
     /// When set, retry failed requests with exponential backoff (429/408 retried; other 4xx not).
     pub retry_config: Option<RetryConfig>,
+    /// Attempts and time left for the client request this call belongs to, shared with its
+    /// other calls (see `RetryConfig::max_attempts`).
+    pub attempt_budget: Option<crate::providers::retry::AttemptBudget>,
+    /// Use the provider's built-in web search (OpenAI search models, Anthropic `web_search`,
+    /// Gemini `google_search`, GLM `web_search`, xAI Live Search). Sources are reported as
+    /// citations.
+    pub web_search: bool,
+    /// Let the model write and run code on the provider's side (Gemini `code_execution`).
+    /// The code and its output come back as `ExecutableCode` / `CodeExecutionResult` blocks.
+    pub code_execution: bool,
+    /// Write the upstream HTTP request and raw response of this call to disk for debugging.
+    pub capture: Option<crate::providers::capture::Capture>,
+    /// Receives the upstream response headers (OpenAI-compatible chat calls only).
+    pub response_headers: Option<crate::providers::ResponseHeaders>,
+    /// Anthropic beta features to enable for this request, on top of the model's own.
+    pub anthropic_betas: AnthropicBetas,
+    /// End-user ID for the provider's abuse monitoring (OpenAI, OpenRouter `user`).
+    pub user: Option<String>,
+    /// Tags stored with the completion (OpenAI `metadata`).
+    pub metadata: Option<HashMap<String, String>>,
+    /// Keep the completion for OpenAI's evals and distillation (`store`).
+    pub store: Option<bool>,
+    /// Extra top-level fields for OpenAI-compatible chat request bodies, for parameters this
+    /// crate doesn't model (e.g. vLLM `top_k`, `repetition_penalty`, `guided_json`).
+    pub extra_body: Option<HashMap<String, serde_json::Value>>,
+    /// Vendor fields (a JSON object) added to OpenAI-compatible chat request bodies, e.g.
+    /// OpenRouter's `provider` routing preferences and `transforms`. Other APIs ignore it.
+    pub provider_extra: serde_json::Value,
+}
+
+/// Opt-in Anthropic beta features, each sent as a value of the `anthropic-beta` header.
+/// Other APIs ignore them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnthropicBetas {
+    /// Thinking between tool calls within one turn.
+    pub interleaved_thinking: bool,
+    /// Stream tool inputs as they are generated instead of buffering each JSON value.
+    pub fine_grained_tool_streaming: bool,
+    /// 1M-token context window (Sonnet 4 and later).
+    pub context_1m: bool,
+}
+
+impl AnthropicBetas {
+    pub const HEADER: &str = "anthropic-beta";
+    pub const INTERLEAVED_THINKING: &str = "interleaved-thinking-2025-05-14";
+    pub const FINE_GRAINED_TOOL_STREAMING: &str = "fine-grained-tool-streaming-2025-05-14";
+    pub const CONTEXT_1M: &str = "context-1m-2025-08-07";
+
+    /// The known betas named in an `anthropic-beta` header value (comma separated).
+    pub fn from_header(value: &str) -> Self {
+        let names: Vec<&str> = value.split(',').map(str::trim).collect();
+        Self {
+            interleaved_thinking: names.contains(&Self::INTERLEAVED_THINKING),
+            fine_grained_tool_streaming: names.contains(&Self::FINE_GRAINED_TOOL_STREAMING),
+            context_1m: names.contains(&Self::CONTEXT_1M),
+        }
+    }
+
+    /// Header values of the enabled betas.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.interleaved_thinking, Self::INTERLEAVED_THINKING),
+            (self.fine_grained_tool_streaming, Self::FINE_GRAINED_TOOL_STREAMING),
+            (self.context_1m, Self::CONTEXT_1M),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Embeddings
+// ---------------------------------------------------------------------------
+
+/// Whether embedded texts are search queries or the documents searched over. Retrieval
+/// models (Voyage, Cohere, Gemini) embed the two differently; others ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingInputType {
+    Query,
+    Document,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    /// Texts to embed; one vector is returned per input, in order.
+    pub input: Vec<String>,
+    /// Output vector size, for models that support shortened embeddings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_type: Option<EmbeddingInputType>,
+}
+
+impl EmbeddingRequest {
+    pub fn new(input: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            input: input.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub model: String,
+    pub provider: ProviderId,
+    pub embeddings: Vec<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+// ---------------------------------------------------------------------------
+// Rerank
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RerankRequest {
+    pub query: String,
+    pub documents: Vec<String>,
+    /// Return only the `top_n` most relevant documents (all when unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_n: Option<usize>,
+}
+
+impl RerankRequest {
+    pub fn new(query: impl Into<String>, documents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            query: query.into(),
+            documents: documents.into_iter().map(Into::into).collect(),
+            top_n: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RerankResult {
+    /// Position of the document in `RerankRequest::documents`.
+    pub index: usize,
+    pub relevance_score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankResponse {
+    pub model: String,
+    pub provider: ProviderId,
+    /// Most relevant first.
+    pub results: Vec<RerankResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+// ---------------------------------------------------------------------------
+// Text completions
+// ---------------------------------------------------------------------------
+
+/// A legacy text completion (`/v1/completions`): continue `prompt`, or with a `suffix`, write
+/// the text that goes between the two (fill-in-the-middle, as code completion clients ask).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+}
+
+impl CompletionRequest {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self { prompt: prompt.into(), suffix: None }
+    }
+
+    /// The request as a chat, for models without a completions endpoint: the prompt as the
+    /// user's message, or with a suffix, both parts and an instruction to fill the gap.
+    pub fn to_context(&self) -> ChatContext {
+        let user = |text: String| Message::User(UserMessage { content: vec![ContentBlock::Text(TextContent { text })] });
+        match self.suffix.as_deref().filter(|s| !s.is_empty()) {
+            None => ChatContext { system_prompt: None, messages: vec![user(self.prompt.clone())], tools: Vec::new() },
+            Some(suffix) => ChatContext {
+                system_prompt: Some(
+                    "Write the text that goes between <prefix> and <suffix>. Reply with only that text: \
+                     don't repeat the prefix or suffix, explain, or wrap it in code fences."
+                        .into(),
+                ),
+                messages: vec![user(format!("<prefix>{}</prefix><suffix>{}</suffix>", self.prompt, suffix))],
+                tools: Vec::new(),
+            },
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -282,6 +885,13 @@ pub enum StreamEvent {
         tool_call: ToolCall,
     },
     ThoughtSignature(String),
+    /// A citation, sent as soon as the provider reports it.
+    CitationDelta(Citation),
+    /// Text of a refusal (see [`RefusalContent`]).
+    RefusalDelta(String),
+    /// Code the provider is about to run; its result follows as `CodeExecutionResult`.
+    ExecutableCode(ExecutableCode),
+    CodeExecutionResult(CodeExecutionResult),
     Done {
         message: AssistantMessage,
     },